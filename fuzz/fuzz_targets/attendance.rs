@@ -0,0 +1,25 @@
+//! Attendance log parsing, with and without a known record count
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zkrust_types::records::{self, AttendanceLayout};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&count, body)) = data.split_first() else {
+        return;
+    };
+
+    for layout in AttendanceLayout::ALL {
+        if let Ok(parsed) = records::parse_attendance(body, layout) {
+            assert_eq!(parsed.len() * layout.record_size(), body.len());
+        }
+    }
+
+    let _ = records::parse_attendance_auto(body, None);
+    let _ = records::parse_attendance_auto(body, Some(count as usize));
+
+    if let Ok(body) = records::strip_size_prefix(body) {
+        let _ = records::parse_attendance_auto(body, None);
+    }
+});
@@ -0,0 +1,15 @@
+//! Capacity, option and firmware version parsing
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zkrust_types::{DeviceCapacity, DeviceOptions, FirmwareVersion};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = DeviceCapacity::parse(data);
+    let _ = DeviceOptions::parse(data);
+
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = text.parse::<FirmwareVersion>();
+    }
+});
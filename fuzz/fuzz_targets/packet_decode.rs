@@ -0,0 +1,15 @@
+//! `Packet::decode` on arbitrary datagrams
+//!
+//! Anything that decodes must encode back to the same bytes.
+
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use zkrust_core::Packet;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = Packet::decode(BytesMut::from(data)) {
+        assert_eq!(&packet.encode()[..], data);
+    }
+});
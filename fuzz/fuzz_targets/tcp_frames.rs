@@ -0,0 +1,46 @@
+//! TCP wrapper framing on an arbitrary byte stream
+//!
+//! Feeds the stream to `ZkCodec` in two chunks, the way partial reads
+//! arrive, and checks every decoded packet re-frames to the bytes it came
+//! from.
+
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::{Decoder, Encoder};
+use zkrust_transport::codec::TCP_HEADER_SIZE;
+use zkrust_transport::ZkCodec;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&split, stream)) = data.split_first() else {
+        return;
+    };
+    let split = (split as usize).min(stream.len());
+
+    let mut codec = ZkCodec::tcp();
+    let mut buf = BytesMut::from(&stream[..split]);
+    let mut consumed = 0;
+
+    for chunk in [&stream[split..], &[][..]] {
+        buf.extend_from_slice(chunk);
+        loop {
+            let before = buf.len();
+            match codec.decode(&mut buf) {
+                Ok(Some(packet)) => {
+                    let frame_len = before - buf.len();
+                    assert!(frame_len >= TCP_HEADER_SIZE);
+
+                    let mut framed = BytesMut::new();
+                    codec.encode(packet, &mut framed).unwrap();
+                    assert_eq!(&framed[..], &stream[consumed..consumed + frame_len]);
+                    consumed += frame_len;
+                }
+                Ok(None) => break,
+                Err(_) => return,
+            }
+        }
+    }
+
+    let _ = codec.decode_eof(&mut buf);
+});
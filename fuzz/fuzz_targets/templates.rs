@@ -0,0 +1,22 @@
+//! Fingerprint table and user/template upload parsing
+//!
+//! Unknown flag bits are dropped on parse, so re-encoding a parsed table
+//! must parse to the same templates rather than reproduce the input.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zkrust_types::template;
+use zkrust_types::user::UserRecordLayout;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(templates) = template::parse_fingerprints(data, 10) {
+        let encoded = template::encode_fingerprints(&templates).unwrap();
+        assert_eq!(encoded.len(), data.len());
+        assert_eq!(template::parse_fingerprints(&encoded, 10).unwrap(), templates);
+    }
+
+    for layout in [UserRecordLayout::Compact, UserRecordLayout::Extended] {
+        let _ = template::decode_user_templates(layout, data, 10);
+    }
+});
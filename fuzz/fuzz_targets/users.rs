@@ -0,0 +1,16 @@
+//! User table parsing in both record layouts
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zkrust_types::user::{self, UserRecordLayout};
+
+fuzz_target!(|data: &[u8]| {
+    for layout in [UserRecordLayout::Compact, UserRecordLayout::Extended] {
+        let _ = user::parse_users(data, layout);
+
+        if data.len() >= layout.record_size() {
+            let _ = layout.decode(&data[..layout.record_size()]);
+        }
+    }
+});
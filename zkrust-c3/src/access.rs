@@ -0,0 +1,212 @@
+//! Card users and door permissions
+//!
+//! Panels keep access rules in data tables, written as rows of
+//! tab-separated `Field=Value` pairs (the field names of the PullSDK):
+//!
+//! ```text
+//! user:           CardNo=12345678  Pin=1001  Password=  Group=0  StartTime=0  EndTime=0
+//! userauthorize:  Pin=1001  AuthorizeTimezoneId=1  AuthorizeDoorId=3
+//! ```
+//!
+//! A user can open a door when an authorization row grants it in one of
+//! the panel's time zones; time zone 1 is "always" on a factory panel.
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::error::{Error, Result};
+use crate::event::MAX_DOORS;
+
+/// Table of card users
+pub const USER_TABLE: &str = "user";
+
+/// Table of door permissions
+pub const AUTHORIZE_TABLE: &str = "userauthorize";
+
+/// User allowed to present a card or PIN at the panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardUser {
+    /// User number, shared with the `pin` of events
+    pub pin: u32,
+
+    /// Card number, 0 for none
+    pub card: u32,
+
+    /// Keypad password, empty for none
+    pub password: String,
+
+    /// Multi-card opening group, 0 for none
+    pub group: u8,
+
+    /// First day the card works
+    pub valid_from: Option<NaiveDate>,
+
+    /// Last day the card works
+    pub valid_until: Option<NaiveDate>,
+}
+
+impl CardUser {
+    /// Create a user with a card and no restrictions
+    pub fn new(pin: u32, card: u32) -> Self {
+        Self {
+            pin,
+            card,
+            password: String::new(),
+            group: 0,
+            valid_from: None,
+            valid_until: None,
+        }
+    }
+
+    /// Set the keypad password
+    pub fn with_password(mut self, password: impl Into<String>) -> Self {
+        self.password = password.into();
+        self
+    }
+
+    /// Restrict the card to a date range
+    pub fn with_validity(mut self, from: NaiveDate, until: NaiveDate) -> Self {
+        self.valid_from = Some(from);
+        self.valid_until = Some(until);
+        self
+    }
+
+    /// Row of the `user` table
+    pub(crate) fn row(&self) -> Result<String> {
+        if self.pin == 0 {
+            return Err(Error::InvalidArgument("PIN must not be 0".into()));
+        }
+        if !self.password.chars().all(|c| c.is_ascii_digit()) || self.password.len() > 8 {
+            return Err(Error::InvalidArgument(format!(
+                "Password of {} must be up to 8 digits",
+                self.pin
+            )));
+        }
+        if let (Some(from), Some(until)) = (self.valid_from, self.valid_until) {
+            if from > until {
+                return Err(Error::InvalidArgument(format!("Validity of {} ends before it starts", self.pin)));
+            }
+        }
+
+        Ok(format!(
+            "CardNo={}\tPin={}\tPassword={}\tGroup={}\tStartTime={}\tEndTime={}",
+            self.card,
+            self.pin,
+            self.password,
+            self.group,
+            date(self.valid_from),
+            date(self.valid_until),
+        ))
+    }
+}
+
+/// Dates as `YYYYMMDD`, 0 for none
+fn date(date: Option<NaiveDate>) -> u32 {
+    date.map_or(0, |date| date.year() as u32 * 10000 + date.month() * 100 + date.day())
+}
+
+/// Permission for a user to open doors during a time zone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoorAuthorization {
+    pub pin: u32,
+
+    /// Time zone configured on the panel
+    pub timezone_id: u32,
+
+    /// Doors (1-4)
+    pub doors: Vec<u8>,
+}
+
+impl DoorAuthorization {
+    /// Allow `pin` through `doors` at any time (time zone 1)
+    pub fn new(pin: u32, doors: impl Into<Vec<u8>>) -> Self {
+        Self {
+            pin,
+            timezone_id: 1,
+            doors: doors.into(),
+        }
+    }
+
+    /// Limit the permission to a time zone
+    pub fn with_timezone(mut self, timezone_id: u32) -> Self {
+        self.timezone_id = timezone_id;
+        self
+    }
+
+    /// Row of the `userauthorize` table
+    pub(crate) fn row(&self) -> Result<String> {
+        let mut mask = 0u8;
+        for door in &self.doors {
+            if !(1..=MAX_DOORS as u8).contains(door) {
+                return Err(Error::InvalidArgument(format!("Door {} is not 1-{}", door, MAX_DOORS)));
+            }
+            mask |= 1 << (door - 1);
+        }
+        if mask == 0 {
+            return Err(Error::InvalidArgument(format!("No doors for {}", self.pin)));
+        }
+
+        Ok(format!(
+            "Pin={}\tAuthorizeTimezoneId={}\tAuthorizeDoorId={}",
+            self.pin, self.timezone_id, mask
+        ))
+    }
+}
+
+/// Data of a set or delete command: the table name, then one row per line
+pub(crate) fn table_data(table: &str, rows: &[String]) -> Vec<u8> {
+    let mut data = table.as_bytes().to_vec();
+    for row in rows {
+        data.extend_from_slice(b"\r\n");
+        data.extend_from_slice(row.as_bytes());
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_row() {
+        let user = CardUser::new(1001, 12345678).with_password("4321").with_validity(
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 12, 31).unwrap(),
+        );
+        assert_eq!(
+            user.row().unwrap(),
+            "CardNo=12345678\tPin=1001\tPassword=4321\tGroup=0\tStartTime=20240101\tEndTime=20241231"
+        );
+        assert_eq!(
+            CardUser::new(7, 0).row().unwrap(),
+            "CardNo=0\tPin=7\tPassword=\tGroup=0\tStartTime=0\tEndTime=0"
+        );
+    }
+
+    #[test]
+    fn test_invalid_user() {
+        assert!(CardUser::new(0, 1).row().is_err());
+        assert!(CardUser::new(1, 1).with_password("12ab").row().is_err());
+        let backwards = CardUser::new(1, 1).with_validity(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+        );
+        assert!(matches!(backwards.row(), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_authorization_row() {
+        let authorization = DoorAuthorization::new(1001, [1, 2, 4]).with_timezone(3);
+        assert_eq!(
+            authorization.row().unwrap(),
+            "Pin=1001\tAuthorizeTimezoneId=3\tAuthorizeDoorId=11"
+        );
+        assert!(DoorAuthorization::new(1, [5]).row().is_err());
+        assert!(DoorAuthorization::new(1, []).row().is_err());
+    }
+
+    #[test]
+    fn test_table_data() {
+        let data = table_data(USER_TABLE, &["Pin=1".into(), "Pin=2".into()]);
+        assert_eq!(data, b"user\r\nPin=1\r\nPin=2");
+    }
+}
@@ -0,0 +1,29 @@
+//! C3 panel error types
+
+use std::time::Duration;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Panel did not answer within {0:?}")]
+    Timeout(Duration),
+
+    #[error("Invalid frame: {0}")]
+    InvalidFrame(String),
+
+    #[error("Invalid reply: {0}")]
+    InvalidReply(String),
+
+    #[error("Panel rejected command 0x{command:02X} with error {code}")]
+    Rejected { command: u8, code: i32 },
+
+    #[error("Not connected")]
+    NotConnected,
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+}
@@ -0,0 +1,195 @@
+//! C3 frame format
+//!
+//! Every request and reply is one frame, over TCP or RS-485 alike:
+//!
+//! ```text
+//! | 0xAA | address | command | length (u16 LE) | data | CRC-16 (u16 LE) | 0x55 |
+//! ```
+//!
+//! The address selects the panel on an RS-485 bus and is 1 over TCP. The
+//! CRC (CRC-16/ARC) covers everything from the address to the end of the
+//! data. Inside a session, request data starts with the session id and a
+//! request number (both u16 LE), which replies echo.
+
+use crate::error::{Error, Result};
+
+/// First byte of a frame
+pub const START: u8 = 0xAA;
+
+/// Last byte of a frame
+pub const END: u8 = 0x55;
+
+/// Bytes before the data
+pub const HEADER_SIZE: usize = 5;
+
+/// Bytes after the data
+pub const TRAILER_SIZE: usize = 3;
+
+/// Request commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Command {
+    /// End the session
+    Disconnect = 0x02,
+    /// Read parameters by name
+    GetParam = 0x04,
+    /// Operate doors and outputs
+    Control = 0x05,
+    /// Add or replace table rows
+    SetData = 0x08,
+    /// Delete table rows
+    DeleteData = 0x0A,
+    /// Read the real-time event log
+    RtLog = 0x0B,
+    /// Start a session
+    Connect = 0x76,
+}
+
+impl From<Command> for u8 {
+    fn from(command: Command) -> u8 {
+        command as u8
+    }
+}
+
+/// Reply code of a successful command
+pub const REPLY_OK: u8 = 0xC8;
+
+/// Reply code of a failed command, with an error code (i32 LE) as data
+pub const REPLY_ERROR: u8 = 0xC9;
+
+/// One protocol frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// RS-485 address of the panel
+    pub address: u8,
+
+    /// Command or reply code
+    pub command: u8,
+
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(address: u8, command: impl Into<u8>, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            address,
+            command: command.into(),
+            data: data.into(),
+        }
+    }
+
+    /// Serialize to wire format
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let length = u16::try_from(self.data.len())
+            .map_err(|_| Error::InvalidArgument(format!("{} bytes of data don't fit a frame", self.data.len())))?;
+
+        let mut buf = Vec::with_capacity(HEADER_SIZE + self.data.len() + TRAILER_SIZE);
+        buf.push(START);
+        buf.push(self.address);
+        buf.push(self.command);
+        buf.extend_from_slice(&length.to_le_bytes());
+        buf.extend_from_slice(&self.data);
+        let crc = crc16(&buf[1..]);
+        buf.extend_from_slice(&crc.to_le_bytes());
+        buf.push(END);
+        Ok(buf)
+    }
+
+    /// Total frame size announced by a header
+    pub fn frame_len(header: &[u8; HEADER_SIZE]) -> Result<usize> {
+        if header[0] != START {
+            return Err(Error::InvalidFrame(format!("start byte 0x{:02X}", header[0])));
+        }
+        let length = u16::from_le_bytes([header[3], header[4]]) as usize;
+        Ok(HEADER_SIZE + length + TRAILER_SIZE)
+    }
+
+    /// Parse one complete frame
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let header: &[u8; HEADER_SIZE] = buf
+            .get(..HEADER_SIZE)
+            .and_then(|header| header.try_into().ok())
+            .ok_or_else(|| Error::InvalidFrame(format!("{} bytes is too short", buf.len())))?;
+        let len = Self::frame_len(header)?;
+        if buf.len() != len {
+            return Err(Error::InvalidFrame(format!("expected {} bytes, got {}", len, buf.len())));
+        }
+        if buf[len - 1] != END {
+            return Err(Error::InvalidFrame(format!("end byte 0x{:02X}", buf[len - 1])));
+        }
+
+        let crc_at = len - TRAILER_SIZE;
+        let expected = u16::from_le_bytes([buf[crc_at], buf[crc_at + 1]]);
+        let actual = crc16(&buf[1..crc_at]);
+        if expected != actual {
+            return Err(Error::InvalidFrame(format!(
+                "CRC 0x{:04X}, expected 0x{:04X}",
+                expected, actual
+            )));
+        }
+
+        Ok(Self {
+            address: buf[1],
+            command: buf[2],
+            data: buf[HEADER_SIZE..crc_at].to_vec(),
+        })
+    }
+}
+
+/// CRC-16/ARC (polynomial 0x8005 reflected, initial value 0)
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc16() {
+        // CRC-16/ARC check value
+        assert_eq!(crc16(b"123456789"), 0xBB3D);
+        assert_eq!(crc16(&[]), 0);
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        let frame = Frame::new(1, Command::Connect, vec![]);
+        let encoded = frame.encode().unwrap();
+        assert_eq!(encoded[..5], [0xAA, 0x01, 0x76, 0x00, 0x00]);
+        assert_eq!(*encoded.last().unwrap(), 0x55);
+        assert_eq!(Frame::decode(&encoded).unwrap(), frame);
+
+        let frame = Frame::new(3, REPLY_OK, vec![0x34, 0x12, 0x01, 0x00, b'O', b'K']);
+        let encoded = frame.encode().unwrap();
+        assert_eq!(Frame::frame_len(encoded[..5].try_into().unwrap()).unwrap(), encoded.len());
+        assert_eq!(Frame::decode(&encoded).unwrap(), frame);
+    }
+
+    #[test]
+    fn test_decode_rejects_damage() {
+        let encoded = Frame::new(1, Command::GetParam, b"LockCount".to_vec()).encode().unwrap();
+
+        let mut corrupt = encoded.clone();
+        corrupt[6] ^= 0xFF;
+        assert!(matches!(Frame::decode(&corrupt), Err(Error::InvalidFrame(_))));
+
+        let mut bad_start = encoded.clone();
+        bad_start[0] = 0x50;
+        assert!(Frame::decode(&bad_start).is_err());
+
+        let mut bad_end = encoded.clone();
+        *bad_end.last_mut().unwrap() = 0;
+        assert!(Frame::decode(&bad_end).is_err());
+
+        assert!(Frame::decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(Frame::decode(&[0xAA, 1]).is_err());
+    }
+}
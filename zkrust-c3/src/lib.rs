@@ -0,0 +1,50 @@
+//! # zkrust-c3
+//!
+//! Client for ZKAccess C3 and inBio access control panels.
+//!
+//! Panels speak a protocol of their own rather than the attendance
+//! terminal one: different framing with a CRC-16 (see [`frame`]),
+//! sessions, and parameters and data tables addressed by name. The same
+//! frames run over TCP (port 4370) and over the RS-485 bus that links
+//! panels at older sites.
+//!
+//! - Door control: unlock, lock, hold open, auxiliary outputs, alarms
+//! - Reader events from the real-time log ([`event`]), also as the
+//!   [`LiveEvent`](zkrust_types::LiveEvent)s terminals report ([`monitor`])
+//! - Card users and door permissions ([`access`])
+//!
+//! ## Quick Start
+//!
+//! ```no_run
+//! use zkrust_c3::{C3Panel, CardUser, DoorAuthorization};
+//!
+//! #[tokio::main]
+//! async fn main() -> zkrust_c3::Result<()> {
+//!     let mut panel = C3Panel::new("192.168.1.210", 4370);
+//!     panel.connect().await?;
+//!
+//!     // Card 12345678 opens doors 1 and 2 at any time
+//!     panel.set_card_users(&[CardUser::new(1001, 12345678)]).await?;
+//!     panel.set_authorizations(&[DoorAuthorization::new(1001, [1, 2])]).await?;
+//!
+//!     panel.disconnect().await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Over RS-485, pass the serial port (any `AsyncRead + AsyncWrite`) to
+//! [`C3Panel::with_stream`] and set the panel's bus address with
+//! [`C3Panel::with_address`].
+
+pub mod access;
+pub mod error;
+pub mod event;
+pub mod frame;
+pub mod monitor;
+pub mod panel;
+
+pub use access::{CardUser, DoorAuthorization};
+pub use error::{Error, Result};
+pub use event::{AccessEvent, Direction, DoorSensor, DoorStatus, EventType, RtLogEntry};
+pub use monitor::EventMonitor;
+pub use panel::{C3Panel, PanelInfo};
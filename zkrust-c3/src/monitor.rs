@@ -0,0 +1,196 @@
+//! Live event monitoring
+//!
+//! Panels don't push events; they buffer them until the RT log is read.
+//! [`EventMonitor`] polls the log and turns each door event into a
+//! [`LiveEvent`], the form attendance terminals report punches in, so one
+//! consumer can follow terminals and panels from the same channel:
+//!
+//! ```no_run
+//! use tokio::sync::mpsc;
+//! use zkrust_c3::{C3Panel, EventMonitor};
+//!
+//! # async fn example() -> zkrust_c3::Result<()> {
+//! let (events, mut received) = mpsc::channel(64);
+//!
+//! let mut panel = C3Panel::new("192.168.1.210", 4370);
+//! panel.connect().await?;
+//! tokio::spawn(async move { EventMonitor::new("lobby-panel").run(&mut panel, events).await });
+//!
+//! while let Some(event) = received.recv().await {
+//!     println!("{}", event);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Status snapshots in the log are not forwarded: door sensor changes
+//! arrive as their own events.
+
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, warn};
+use zkrust_types::{LiveEvent, LiveEventKind};
+
+use crate::error::{Error, Result};
+use crate::event::{AccessEvent, EventType, RtLogEntry};
+use crate::panel::C3Panel;
+
+/// Default RT log polling interval
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Normalize a panel event
+pub fn live_event(source: &str, event: &AccessEvent) -> LiveEvent {
+    let door = event.door;
+    let card = (event.card != 0).then_some(event.card);
+
+    let kind = match event.event {
+        // Punch opens: normal, normal-open period, first card, multi-card,
+        // emergency password, duress, multi-card fingerprint
+        EventType(0..=4 | 101 | 103 | 203) => LiveEventKind::AccessGranted { door, card },
+        EventType::DOOR_OPENED => LiveEventKind::DoorOpened { door },
+        EventType::DOOR_CLOSED => LiveEventKind::DoorClosed { door },
+        EventType::DOOR_OPEN_TIMEOUT => LiveEventKind::DoorHeldOpen { door },
+        EventType::DOOR_FORCED_OPEN => LiveEventKind::DoorForcedOpen { door },
+        code if code.is_denied() => LiveEventKind::AccessDenied {
+            door,
+            card,
+            reason: code.description().unwrap_or("Access denied").to_string(),
+        },
+        code => LiveEventKind::Other {
+            code: u32::from(code.0),
+            description: code.to_string(),
+        },
+    };
+
+    LiveEvent {
+        source: source.to_string(),
+        timestamp: event.timestamp,
+        user_id: (event.pin != 0).then(|| event.pin.to_string()),
+        kind,
+    }
+}
+
+/// Poller forwarding panel events as [`LiveEvent`]s
+#[derive(Debug, Clone)]
+pub struct EventMonitor {
+    source: String,
+    interval: Duration,
+}
+
+impl EventMonitor {
+    /// Create a monitor labelling events with `source`
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Set how often the RT log is read (default: 1 second)
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Poll `panel` and send its events until the receiver goes away
+    ///
+    /// A timed out poll is retried on the next tick; any other error ends
+    /// monitoring so the caller can reconnect.
+    pub async fn run(&self, panel: &mut C3Panel, events: mpsc::Sender<LiveEvent>) -> Result<()> {
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = events.closed() => return Ok(()),
+            }
+
+            let entries = match panel.get_rt_log().await {
+                Ok(entries) => entries,
+                Err(e @ Error::Timeout(_)) => {
+                    warn!("RT log poll of {} failed: {}", self.source, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            for entry in entries {
+                let RtLogEntry::Event(event) = entry else {
+                    continue;
+                };
+                let event = live_event(&self.source, &event);
+                debug!("{}", event);
+                if events.send(event).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::event::tests::swipe;
+    use crate::panel::tests::connected;
+
+    #[test]
+    fn test_live_event() {
+        let granted = live_event("panel", &swipe());
+        assert_eq!(granted.user_id.as_deref(), Some("1001"));
+        assert_eq!(
+            granted.kind,
+            LiveEventKind::AccessGranted {
+                door: 2,
+                card: Some(0x00C0FFEE)
+            }
+        );
+
+        let denied = AccessEvent {
+            event: EventType::UNREGISTERED_CARD,
+            pin: 0,
+            ..swipe()
+        };
+        let denied = live_event("panel", &denied);
+        assert_eq!(denied.user_id, None);
+        assert!(matches!(
+            denied.kind,
+            LiveEventKind::AccessDenied { door: 2, ref reason, .. } if reason == "Unregistered card"
+        ));
+
+        let kinds: Vec<_> = [EventType::DOOR_OPEN_TIMEOUT, EventType::DOOR_FORCED_OPEN, EventType::DEVICE_START]
+            .into_iter()
+            .map(|event| live_event("panel", &AccessEvent { event, ..swipe() }).kind)
+            .collect();
+        assert_eq!(kinds[0], LiveEventKind::DoorHeldOpen { door: 2 });
+        assert_eq!(kinds[1], LiveEventKind::DoorForcedOpen { door: 2 });
+        assert_eq!(
+            kinds[2],
+            LiveEventKind::Other {
+                code: 206,
+                description: "Device start (206)".into()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_monitor() {
+        let (mut panel, _) = connected().await;
+        let (events, mut received) = mpsc::channel(4);
+
+        let monitor = tokio::spawn(async move {
+            EventMonitor::new("panel")
+                .with_interval(Duration::from_millis(10))
+                .run(&mut panel, events)
+                .await
+        });
+
+        assert_eq!(received.recv().await.unwrap(), live_event("panel", &swipe()));
+        drop(received);
+        monitor.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,505 @@
+//! Panel client
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, info};
+
+use crate::access::{self, CardUser, DoorAuthorization};
+use crate::error::{Error, Result};
+use crate::event::{parse_rt_log, RtLogEntry, MAX_DOORS};
+use crate::frame::{Command, Frame, HEADER_SIZE, REPLY_ERROR, REPLY_OK};
+
+/// Default port of C3/inBio panels
+pub const DEFAULT_PORT: u16 = 4370;
+
+/// Parameters read by [`C3Panel::get_panel_info`]
+const INFO_PARAMS: [&str; 5] = ["~SerialNumber", "LockCount", "ReaderCount", "AuxInCount", "AuxOutCount"];
+
+/// Byte stream to a panel: TCP, or a serial port on an RS-485 bus
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Identity and size of a panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanelInfo {
+    pub serial_number: String,
+    /// Doors (1, 2 or 4)
+    pub doors: u8,
+    pub readers: u8,
+    pub aux_inputs: u8,
+    pub aux_outputs: u8,
+}
+
+/// ZKAccess C3/inBio access control panel
+///
+/// # Examples
+///
+/// ```no_run
+/// use zkrust_c3::C3Panel;
+///
+/// # async fn example() -> zkrust_c3::Result<()> {
+/// let mut panel = C3Panel::new("192.168.1.210", 4370);
+/// panel.connect().await?;
+///
+/// panel.open_door(1, 5).await?;
+/// for entry in panel.get_rt_log().await? {
+///     println!("{:?}", entry);
+/// }
+///
+/// panel.disconnect().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct C3Panel {
+    host: String,
+    port: u16,
+    address: u8,
+    password: Option<String>,
+    timeout: Duration,
+    stream: Option<Box<dyn Stream>>,
+    session_id: Option<u16>,
+    request_nr: u16,
+}
+
+impl C3Panel {
+    /// Create a client for the panel at `host`:`port` (not yet connected)
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            address: 1,
+            password: None,
+            timeout: Duration::from_secs(5),
+            stream: None,
+            session_id: None,
+            request_nr: 0,
+        }
+    }
+
+    /// Create a client talking over an open stream, such as a serial port
+    /// on the panel's RS-485 bus
+    ///
+    /// Set the panel's bus address with [`with_address`](Self::with_address).
+    pub fn with_stream(stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static) -> Self {
+        let mut panel = Self::new("", 0);
+        panel.stream = Some(Box::new(stream));
+        panel
+    }
+
+    /// Set the RS-485 address of the panel (default: 1)
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Set the communication password configured on the panel
+    pub fn with_password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Set how long to wait for each reply (default: 5 seconds)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Check if a session is open
+    pub fn is_connected(&self) -> bool {
+        self.session_id.is_some()
+    }
+
+    /// Open a session
+    pub async fn connect(&mut self) -> Result<()> {
+        if self.stream.is_none() {
+            let stream = tokio::time::timeout(self.timeout, TcpStream::connect((self.host.as_str(), self.port)))
+                .await
+                .map_err(|_| Error::Timeout(self.timeout))??;
+            self.stream = Some(Box::new(stream));
+        }
+
+        self.session_id = None;
+        let password = self.password.clone().unwrap_or_default().into_bytes();
+        let reply = self.request(Command::Connect, &password).await?;
+        let session_id = reply
+            .get(..2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(|| Error::InvalidReply("connect reply without session id".into()))?;
+
+        self.session_id = Some(session_id);
+        self.request_nr = 0;
+        info!("Connected to C3 panel {}:{} (session {})", self.host, self.port, session_id);
+        Ok(())
+    }
+
+    /// End the session and close the connection
+    pub async fn disconnect(&mut self) -> Result<()> {
+        if self.is_connected() {
+            let result = self.request(Command::Disconnect, &[]).await;
+            self.session_id = None;
+            self.stream = None;
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Read parameters by name (`LockCount`, `~SerialNumber`, `IPAddress`, ...)
+    ///
+    /// Names the panel doesn't know are missing from the result.
+    pub async fn get_params(&mut self, names: &[&str]) -> Result<BTreeMap<String, String>> {
+        let reply = self.session_request(Command::GetParam, names.join(",").as_bytes()).await?;
+        Ok(parse_params(&reply))
+    }
+
+    /// Read the serial number and door/reader/auxiliary counts
+    pub async fn get_panel_info(&mut self) -> Result<PanelInfo> {
+        let params = self.get_params(&INFO_PARAMS).await?;
+        let count = |name: &str| -> Result<u8> {
+            params
+                .get(name)
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| Error::InvalidReply(format!("missing {}", name)))
+        };
+
+        Ok(PanelInfo {
+            serial_number: params.get("~SerialNumber").cloned().unwrap_or_default(),
+            doors: count("LockCount")?,
+            readers: count("ReaderCount")?,
+            aux_inputs: count("AuxInCount")?,
+            aux_outputs: count("AuxOutCount")?,
+        })
+    }
+
+    /// Unlock `door` (1-4) for `seconds` (1-254)
+    pub async fn open_door(&mut self, door: u8, seconds: u8) -> Result<()> {
+        if !(1..=254).contains(&seconds) {
+            return Err(Error::InvalidArgument(format!("Open time {} is not 1-254 seconds", seconds)));
+        }
+        self.output(door, 1, seconds).await
+    }
+
+    /// Lock `door` (1-4) now
+    pub async fn close_door(&mut self, door: u8) -> Result<()> {
+        self.output(door, 1, 0).await
+    }
+
+    /// Keep `door` (1-4) unlocked until it is closed again
+    pub async fn hold_door_open(&mut self, door: u8) -> Result<()> {
+        self.output(door, 1, 255).await
+    }
+
+    /// Switch auxiliary output `output` (1-4) on for `seconds`, 0 to switch
+    /// it off, 255 to keep it on
+    pub async fn set_aux_output(&mut self, output: u8, seconds: u8) -> Result<()> {
+        self.output(output, 2, seconds).await
+    }
+
+    /// Silence alarms on all doors
+    pub async fn cancel_alarm(&mut self) -> Result<()> {
+        self.control([2, 0, 0, 0, 0]).await
+    }
+
+    /// Restart the panel, which ends the session
+    pub async fn restart(&mut self) -> Result<()> {
+        self.control([3, 0, 0, 0, 0]).await?;
+        self.session_id = None;
+        self.stream = None;
+        Ok(())
+    }
+
+    /// Read the events buffered since the last call, plus a status snapshot
+    pub async fn get_rt_log(&mut self) -> Result<Vec<RtLogEntry>> {
+        let reply = self.session_request(Command::RtLog, &[]).await?;
+        parse_rt_log(&reply)
+    }
+
+    /// Add or replace card users
+    pub async fn set_card_users(&mut self, users: &[CardUser]) -> Result<()> {
+        let rows = users.iter().map(CardUser::row).collect::<Result<Vec<_>>>()?;
+        self.set_data(access::USER_TABLE, &rows).await
+    }
+
+    /// Delete card users by PIN
+    ///
+    /// Their door permissions stay until deleted with
+    /// [`delete_authorizations`](Self::delete_authorizations).
+    pub async fn delete_card_users(&mut self, pins: &[u32]) -> Result<()> {
+        let rows: Vec<_> = pins.iter().map(|pin| format!("Pin={}", pin)).collect();
+        self.delete_data(access::USER_TABLE, &rows).await
+    }
+
+    /// Add or replace door permissions
+    pub async fn set_authorizations(&mut self, authorizations: &[DoorAuthorization]) -> Result<()> {
+        let rows = authorizations
+            .iter()
+            .map(DoorAuthorization::row)
+            .collect::<Result<Vec<_>>>()?;
+        self.set_data(access::AUTHORIZE_TABLE, &rows).await
+    }
+
+    /// Delete all door permissions of users by PIN
+    pub async fn delete_authorizations(&mut self, pins: &[u32]) -> Result<()> {
+        let rows: Vec<_> = pins.iter().map(|pin| format!("Pin={}", pin)).collect();
+        self.delete_data(access::AUTHORIZE_TABLE, &rows).await
+    }
+
+    async fn set_data(&mut self, table: &str, rows: &[String]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        debug!("Writing {} {} rows", rows.len(), table);
+        self.session_request(Command::SetData, &access::table_data(table, rows)).await?;
+        Ok(())
+    }
+
+    async fn delete_data(&mut self, table: &str, rows: &[String]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        debug!("Deleting {} {} rows", rows.len(), table);
+        self.session_request(Command::DeleteData, &access::table_data(table, rows)).await?;
+        Ok(())
+    }
+
+    /// Drive a door lock (`kind` 1) or auxiliary output (`kind` 2)
+    async fn output(&mut self, number: u8, kind: u8, seconds: u8) -> Result<()> {
+        if !(1..=MAX_DOORS as u8).contains(&number) {
+            return Err(Error::InvalidArgument(format!("Output {} is not 1-{}", number, MAX_DOORS)));
+        }
+        self.control([1, number, kind, seconds, 0]).await
+    }
+
+    async fn control(&mut self, operation: [u8; 5]) -> Result<()> {
+        self.session_request(Command::Control, &operation).await?;
+        Ok(())
+    }
+
+    async fn session_request(&mut self, command: Command, data: &[u8]) -> Result<Vec<u8>> {
+        if !self.is_connected() {
+            return Err(Error::NotConnected);
+        }
+        self.request(command, data).await
+    }
+
+    /// Send one command and wait for its reply data
+    async fn request(&mut self, command: Command, data: &[u8]) -> Result<Vec<u8>> {
+        let mut payload = Vec::with_capacity(4 + data.len());
+        if let Some(session_id) = self.session_id {
+            self.request_nr = self.request_nr.wrapping_add(1);
+            payload.extend_from_slice(&session_id.to_le_bytes());
+            payload.extend_from_slice(&self.request_nr.to_le_bytes());
+        }
+        payload.extend_from_slice(data);
+        let frame = Frame::new(self.address, command, payload).encode()?;
+
+        let timeout = self.timeout;
+        let stream = self.stream.as_mut().ok_or(Error::NotConnected)?;
+        let reply = tokio::time::timeout(timeout, async {
+            stream.write_all(&frame).await?;
+            read_frame(stream).await
+        })
+        .await
+        .map_err(|_| Error::Timeout(timeout))??;
+
+        let mut data = reply.data;
+        if self.session_id.is_some() {
+            if data.len() < 4 {
+                return Err(Error::InvalidReply("reply without session header".into()));
+            }
+            let request_nr = u16::from_le_bytes([data[2], data[3]]);
+            if request_nr != self.request_nr {
+                return Err(Error::InvalidReply(format!(
+                    "reply to request {}, expected {}",
+                    request_nr, self.request_nr
+                )));
+            }
+            data.drain(..4);
+        }
+
+        match reply.command {
+            REPLY_OK => Ok(data),
+            REPLY_ERROR => {
+                let code = data.get(..4).map_or(0, |bytes| i32::from_le_bytes(bytes.try_into().unwrap()));
+                Err(Error::Rejected {
+                    command: command.into(),
+                    code,
+                })
+            }
+            other => Err(Error::InvalidReply(format!("reply code 0x{:02X}", other))),
+        }
+    }
+}
+
+impl std::fmt::Debug for C3Panel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("C3Panel")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("address", &self.address)
+            .field("timeout", &self.timeout)
+            .field("session_id", &self.session_id)
+            .finish()
+    }
+}
+
+/// Read one frame from `stream`
+async fn read_frame<S: AsyncRead + Unpin + ?Sized>(stream: &mut S) -> Result<Frame> {
+    let mut header = [0; HEADER_SIZE];
+    stream.read_exact(&mut header).await?;
+    let mut buf = vec![0; Frame::frame_len(&header)?];
+    buf[..HEADER_SIZE].copy_from_slice(&header);
+    stream.read_exact(&mut buf[HEADER_SIZE..]).await?;
+    Frame::decode(&buf)
+}
+
+/// `Name=Value` pairs separated by commas, possibly NUL-terminated
+fn parse_params(data: &[u8]) -> BTreeMap<String, String> {
+    String::from_utf8_lossy(data)
+        .trim_end_matches('\0')
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::DuplexStream;
+
+    use crate::event::tests::{event_record, swipe};
+
+    const SESSION: u16 = 0x1234;
+
+    pub(crate) type Requests = Arc<Mutex<Vec<(u8, Vec<u8>)>>>;
+
+    /// Answer requests the way a panel does, recording them
+    async fn fake_panel(mut stream: DuplexStream, requests: Requests) {
+        while let Ok(request) = read_frame(&mut stream).await {
+            let (header, data) = if request.command == u8::from(Command::Connect) {
+                (Vec::new(), request.data.clone())
+            } else {
+                assert_eq!(request.data[..2], SESSION.to_le_bytes());
+                (request.data[..4].to_vec(), request.data[4..].to_vec())
+            };
+            requests.lock().unwrap().push((request.command, data.clone()));
+
+            let (code, payload) = match request.command {
+                0x76 if data == b"secret" => (REPLY_OK, SESSION.to_le_bytes().to_vec()),
+                0x76 => (REPLY_ERROR, (-14i32).to_le_bytes().to_vec()),
+                0x04 => (
+                    REPLY_OK,
+                    b"~SerialNumber=DGD9190019050335134,LockCount=4,ReaderCount=4,AuxInCount=4,AuxOutCount=4\0".to_vec(),
+                ),
+                0x0B => (REPLY_OK, event_record(&swipe())),
+                0x0A => (REPLY_ERROR, (-5i32).to_le_bytes().to_vec()),
+                _ => (REPLY_OK, Vec::new()),
+            };
+            let reply = Frame::new(request.address, code, [header, payload].concat());
+            if stream.write_all(&reply.encode().unwrap()).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    pub(crate) async fn connected() -> (C3Panel, Requests) {
+        let (client, server) = tokio::io::duplex(4096);
+        let requests = Requests::default();
+        tokio::spawn(fake_panel(server, requests.clone()));
+
+        let mut panel = C3Panel::with_stream(client).with_password("secret");
+        panel.connect().await.unwrap();
+        (panel, requests)
+    }
+
+    #[tokio::test]
+    async fn test_connect_and_info() {
+        let (mut panel, _) = connected().await;
+        assert!(panel.is_connected());
+
+        let info = panel.get_panel_info().await.unwrap();
+        assert_eq!(info.serial_number, "DGD9190019050335134");
+        assert_eq!((info.doors, info.readers, info.aux_inputs, info.aux_outputs), (4, 4, 4, 4));
+
+        panel.disconnect().await.unwrap();
+        assert!(!panel.is_connected());
+        assert!(matches!(panel.get_rt_log().await, Err(Error::NotConnected)));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_password() {
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(fake_panel(server, Requests::default()));
+
+        let mut panel = C3Panel::with_stream(client).with_password("guess");
+        let result = panel.connect().await;
+        assert!(matches!(result, Err(Error::Rejected { command: 0x76, code: -14 })));
+        assert!(!panel.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_door_control() {
+        let (mut panel, requests) = connected().await;
+        panel.open_door(2, 5).await.unwrap();
+        panel.hold_door_open(1).await.unwrap();
+        panel.set_aux_output(3, 0).await.unwrap();
+        panel.cancel_alarm().await.unwrap();
+        assert!(matches!(panel.open_door(5, 5).await, Err(Error::InvalidArgument(_))));
+        assert!(panel.open_door(1, 0).await.is_err());
+
+        let requests = requests.lock().unwrap();
+        let controls: Vec<_> = requests.iter().filter(|(command, _)| *command == 0x05).collect();
+        assert_eq!(controls[0].1, [1, 2, 1, 5, 0]);
+        assert_eq!(controls[1].1, [1, 1, 1, 255, 0]);
+        assert_eq!(controls[2].1, [1, 3, 2, 0, 0]);
+        assert_eq!(controls[3].1, [2, 0, 0, 0, 0]);
+        assert_eq!(controls.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_rt_log() {
+        let (mut panel, _) = connected().await;
+        assert_eq!(panel.get_rt_log().await.unwrap(), [RtLogEntry::Event(swipe())]);
+    }
+
+    #[tokio::test]
+    async fn test_card_permissions() {
+        let (mut panel, requests) = connected().await;
+        panel.set_card_users(&[CardUser::new(1001, 12345678)]).await.unwrap();
+        panel.set_authorizations(&[DoorAuthorization::new(1001, [1, 2])]).await.unwrap();
+        panel.set_card_users(&[]).await.unwrap();
+        assert!(panel.set_authorizations(&[DoorAuthorization::new(1, [9])]).await.is_err());
+
+        let result = panel.delete_card_users(&[1001]).await;
+        assert!(matches!(result, Err(Error::Rejected { command: 0x0A, code: -5 })));
+
+        let requests = requests.lock().unwrap();
+        let writes: Vec<_> = requests.iter().filter(|(command, _)| *command == 0x08).collect();
+        assert_eq!(writes.len(), 2);
+        assert!(writes[0].1.starts_with(b"user\r\nCardNo=12345678\tPin=1001\t"));
+        assert_eq!(writes[1].1, b"userauthorize\r\nPin=1001\tAuthorizeTimezoneId=1\tAuthorizeDoorId=3");
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        // Nobody answers
+        let (client, _server) = tokio::io::duplex(4096);
+        let mut panel = C3Panel::with_stream(client).with_timeout(Duration::from_millis(50));
+        assert!(matches!(panel.connect().await, Err(Error::Timeout(_))));
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let params = parse_params(b"LockCount=2,~SerialNumber=ABC,broken\0\0");
+        assert_eq!(params.len(), 2);
+        assert_eq!(params["LockCount"], "2");
+        assert_eq!(params["~SerialNumber"], "ABC");
+    }
+}
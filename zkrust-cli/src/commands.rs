@@ -0,0 +1,291 @@
+//! Subcommand implementations
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+use zkrust::{Device, Error, SettingsProfile};
+use zkrust_sync::{CsvExporter, Cursor, NdjsonExporter};
+use zkrust_types::{AttendanceRecord, DeviceInfo, FingerprintTemplate, User};
+
+use crate::shell;
+use crate::{AttlogCommand, Command, EventsCommand, Format, OptionCommand, TimeCommand, UsersCommand};
+
+/// Parse `--since`: a date (midnight) or a date and time
+pub fn parse_since(value: &str) -> std::result::Result<NaiveDateTime, String> {
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(time);
+        }
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_time(chrono::NaiveTime::MIN))
+        .map_err(|_| format!("expected YYYY-MM-DD or YYYY-MM-DDTHH:MM[:SS], got {:?}", value))
+}
+
+/// Run a subcommand on a connected device
+pub async fn run(device: &mut Device, command: Command) -> Result<()> {
+    match command {
+        Command::Info => info(device).await,
+        Command::Users(command) => users(device, command).await,
+        Command::Attlog(AttlogCommand::Pull { since, format, output }) => {
+            attlog_pull(device, since, format, output.as_deref()).await
+        }
+        Command::Time(command) => time(device, command).await,
+        Command::Events(EventsCommand::Watch { interval }) => watch(device, Duration::from_secs(interval)).await,
+        Command::Option(command) => option(device, command).await,
+        Command::Backup { output } => backup(device, output.as_deref()).await,
+        Command::Shell => shell::run(device).await,
+        #[cfg(feature = "tui")]
+        Command::Monitor { .. } => unreachable!("monitor runs before connecting a single device"),
+        Command::Decode { .. } => unreachable!("decode runs without a device"),
+    }
+}
+
+/// Writer for `--output`, or stdout
+fn output(path: Option<&Path>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).with_context(|| format!("Cannot create {}", path.display()))?,
+        )),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
+async fn info(device: &mut Device) -> Result<()> {
+    let info = device.get_device_info().await?;
+    let platform = device.get_platform_info().await?;
+    let capacity = device.get_capacity().await?;
+    let time = device.get_time().await?;
+
+    println!("Serial number: {}", info.serial_number);
+    println!("Firmware:      {}", info.firmware_version);
+    for (label, value) in [
+        ("Model:        ", &info.model),
+        ("Platform:     ", &info.platform),
+        ("Vendor:       ", &platform.vendor),
+        ("OS:           ", &platform.os),
+        ("Name:         ", &info.device_name),
+        ("MAC address:  ", &info.mac_address),
+    ] {
+        if let Some(value) = value {
+            println!("{} {}", label, value);
+        }
+    }
+    println!("Fingerprint:   ZKFinger VX{}.0", platform.fingerprint_algorithm);
+    if let Some(face) = platform.face_algorithm {
+        println!("Face:          version {}", face);
+    }
+    println!("Clock:         {}", time);
+    println!("Storage:       {}", capacity);
+    Ok(())
+}
+
+async fn users(device: &mut Device, command: UsersCommand) -> Result<()> {
+    match command {
+        UsersCommand::List { json } => {
+            let users = device.get_users().await?;
+            if json {
+                NdjsonExporter::new().write_users(io::stdout().lock(), &users)?;
+                return Ok(());
+            }
+
+            println!("{:>5}  {:<9}  {:<24}  {:<9}  {:>10}  ENABLED", "UID", "USER ID", "NAME", "PRIVILEGE", "CARD");
+            for user in &users {
+                println!(
+                    "{:>5}  {:<9}  {:<24}  {:<9}  {:>10}  {}",
+                    user.uid,
+                    user.user_id,
+                    user.name,
+                    user.privilege.name(),
+                    user.card,
+                    if user.enabled { "yes" } else { "no" }
+                );
+            }
+        }
+        UsersCommand::Add(add) => {
+            let user = User::builder(add.uid, add.user_id)
+                .name(add.name)
+                .privilege(add.privilege)
+                .password(add.password)
+                .card(add.card)
+                .group_id(add.group)
+                .build()?;
+
+            device.set_user(&user).await?;
+            device.refresh_data().await?;
+            println!("Saved {}", user);
+        }
+        UsersCommand::Del { uid } => {
+            device.delete_user(uid).await?;
+            device.refresh_data().await?;
+            println!("Deleted uid {}", uid);
+        }
+    }
+    Ok(())
+}
+
+async fn attlog_pull(
+    device: &mut Device,
+    since: Option<NaiveDateTime>,
+    format: Format,
+    path: Option<&Path>,
+) -> Result<()> {
+    let mut records = device.get_attendance().await?;
+    records.retain(|r| since.is_none_or(|since| r.timestamp >= since));
+    records.sort();
+
+    let mut out = output(path)?;
+    match format {
+        Format::Csv => CsvExporter::new().write(&mut out, &records)?,
+        Format::Ndjson => {
+            NdjsonExporter::new().write_attendance(&mut out, &records)?;
+        }
+    }
+    out.flush()?;
+
+    eprintln!("{} records", records.len());
+    Ok(())
+}
+
+async fn time(device: &mut Device, command: TimeCommand) -> Result<()> {
+    match command {
+        TimeCommand::Show => {
+            let time = device.get_time().await?;
+            let skew = time - Local::now().naive_local();
+            println!("{} ({:+}s from this host)", time, skew.num_seconds());
+        }
+        TimeCommand::Sync => {
+            let before = device.get_time().await?;
+            let now = Local::now().naive_local();
+            device.set_time(now).await?;
+            println!("Clock set to {} (was {:+}s off)", now, (before - now).num_seconds());
+        }
+    }
+    Ok(())
+}
+
+async fn watch(device: &mut Device, interval: Duration) -> Result<()> {
+    // Records already on the device are not events
+    let mut cursor = Cursor::default();
+    cursor.advance(&cursor.filter_new(device.get_attendance().await?));
+    eprintln!("Watching for new attendance records (Ctrl-C to stop)...");
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+
+        let records = match device.get_attendance().await {
+            Ok(records) => cursor.filter_new(records),
+            Err(e) if e.retry_class().is_retryable() => {
+                eprintln!("Connection lost ({}), reconnecting...", e);
+                device.connect().await?;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        for record in &records {
+            println!("{}", record);
+        }
+        cursor.advance(&records);
+    }
+}
+
+async fn option(device: &mut Device, command: OptionCommand) -> Result<()> {
+    match command {
+        OptionCommand::Get { name } => match device.get_option(&name).await? {
+            Some(value) => println!("{}", value),
+            None => anyhow::bail!("Option {:?} is not set", name),
+        },
+        OptionCommand::Set { name, value } => {
+            device.set_option(&name, &value).await?;
+            device.refresh_data().await?;
+        }
+        OptionCommand::Snapshot { output: path } => {
+            let info = device.get_device_info().await?;
+            let profile = device
+                .snapshot_settings()
+                .await?
+                .with_comment(format!("Settings of {} ({})", info.serial_number, info.firmware_version));
+            let mut out = output(path.as_deref())?;
+            write!(out, "{}", profile)?;
+            out.flush()?;
+        }
+        OptionCommand::Apply { profile, dry_run } => {
+            let profile = SettingsProfile::load(&profile)?;
+            let diff = device.diff_settings(&profile).await?;
+            println!("{}", diff);
+            if !dry_run {
+                device.apply_diff(&diff).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Contents of a backup file
+#[derive(Serialize)]
+struct Backup {
+    created_at: NaiveDateTime,
+    device: DeviceInfo,
+    users: Vec<User>,
+    fingerprints: Vec<FingerprintTemplate>,
+    attendance: Vec<AttendanceRecord>,
+}
+
+async fn backup(device: &mut Device, path: Option<&Path>) -> Result<()> {
+    let info = device.get_device_info().await?;
+    let users = device.get_users().await?;
+    let fingerprints = match device.get_fingerprints().await {
+        Ok(fingerprints) => fingerprints,
+        Err(Error::NotSupported(reason)) => {
+            eprintln!("Skipping fingerprints: {}", reason);
+            Vec::new()
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let attendance = device.get_attendance().await?;
+
+    let backup = Backup {
+        created_at: Local::now().naive_local(),
+        device: info,
+        users,
+        fingerprints,
+        attendance,
+    };
+
+    let mut out = output(path)?;
+    serde_json::to_writer_pretty(&mut out, &backup)?;
+    writeln!(out)?;
+    out.flush()?;
+
+    eprintln!(
+        "Backed up {} users, {} fingerprints, {} records",
+        backup.users.len(),
+        backup.fingerprints.len(),
+        backup.attendance.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert_eq!(parse_since("2024-03-01").unwrap(), date.and_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(parse_since("2024-03-01T08:30").unwrap(), date.and_hms_opt(8, 30, 0).unwrap());
+        assert_eq!(parse_since("2024-03-01 08:30:15").unwrap(), date.and_hms_opt(8, 30, 15).unwrap());
+        assert!(parse_since("yesterday").is_err());
+    }
+}
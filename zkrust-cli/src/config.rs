@@ -0,0 +1,98 @@
+//! Device configuration file
+//!
+//! A [`DeviceRegistry`] file; see [`zkrust::registry`] for every key.
+//!
+//! ```toml
+//! default = "front-door"
+//!
+//! [devices.front-door]
+//! host = "192.168.1.201"
+//! password = 1234
+//!
+//! [devices.warehouse]
+//! host = "10.0.0.50"
+//! port = 4370
+//! transport = "udp"
+//! password_env = "WAREHOUSE_COMMKEY"
+//! timeout_secs = 10
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use zkrust::registry::DeviceRegistry;
+pub use zkrust::registry::{DeviceEntry, TransportKind, DEFAULT_PORT};
+
+/// Load the configuration file, or an empty registry if the default file
+/// doesn't exist
+///
+/// An explicitly given path must exist. Files ending in `.json` are parsed
+/// as JSON, anything else as TOML.
+pub fn load(path: Option<&Path>) -> Result<DeviceRegistry> {
+    let path = match (path, default_path()) {
+        (Some(path), _) => path.to_path_buf(),
+        (None, Some(path)) if path.exists() => path,
+        (None, _) => return Ok(DeviceRegistry::new()),
+    };
+
+    DeviceRegistry::load(&path).with_context(|| format!("Invalid config {}", path.display()))
+}
+
+/// Look up a device by name, or the default device when `name` is None
+pub fn device(registry: &DeviceRegistry, name: Option<&str>) -> Result<DeviceEntry> {
+    if name.is_none() && registry.default_name().is_none() {
+        anyhow::bail!("No device given; use --device, --host or set `default` in the config file");
+    }
+    let (_, entry) = registry.resolve(name)?;
+    Ok(entry.clone())
+}
+
+/// `$ZKRUST_CONFIG`, else `~/.config/zkrust/config.toml`
+fn default_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("ZKRUST_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/zkrust/config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = DeviceRegistry::from_toml(
+            r#"
+            default = "door"
+
+            [devices.door]
+            host = "192.168.1.201"
+            password = 1234
+
+            [devices.gate]
+            host = "10.0.0.50"
+            port = 5005
+            transport = "udp"
+            "#,
+        )
+        .unwrap();
+
+        let door = device(&config, None).unwrap();
+        assert_eq!(door.port, DEFAULT_PORT);
+        assert_eq!(door.password, Some(1234));
+        assert_eq!(door.transport, TransportKind::Tcp);
+
+        let gate = device(&config, Some("gate")).unwrap();
+        assert_eq!(gate.port, 5005);
+        assert_eq!(gate.transport, TransportKind::Udp);
+
+        assert!(device(&config, Some("lobby")).is_err());
+    }
+
+    #[test]
+    fn test_reject_unknown_keys() {
+        assert!(DeviceRegistry::from_toml("[devices.door]\nhost = \"a\"\npasword = 1\n").is_err());
+        assert!(device(&DeviceRegistry::new(), None).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! `zk decode`: offline packet decoder
+//!
+//! Takes frames as hex (`50 50 82 7d ...`, `5050827d...`, `0x..`) or
+//! base64, e.g. copied from Wireshark, and prints the header fields,
+//! checksum validity and, where the command is known, the parsed payload.
+//! The TCP wrapper is detected.
+
+use std::io::{self, BufRead};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+
+use zkrust_core::{checksum, Command, Packet};
+use zkrust_types::user::{self, UserRecordLayout};
+use zkrust_types::{time, DeviceCapacity};
+
+/// First bytes of the TCP wrapper header
+const TCP_MAGIC: [u8; 4] = [0x50, 0x50, 0x82, 0x7d];
+
+/// Decode each frame, or one frame per line of stdin if none are given
+pub fn run(frames: &[String], reply_to: Option<Command>) -> Result<()> {
+    if !frames.is_empty() {
+        for (index, text) in frames.iter().enumerate() {
+            print_frame(index + 1, text, reply_to);
+        }
+        return Ok(());
+    }
+
+    let mut index = 0;
+    for line in io::stdin().lock().lines() {
+        let line = line.context("Cannot read stdin")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        index += 1;
+        print_frame(index, &line, reply_to);
+    }
+    Ok(())
+}
+
+fn print_frame(index: usize, text: &str, reply_to: Option<Command>) {
+    println!("frame {}:", index);
+    match parse_input(text) {
+        Ok(bytes) => print!("{}", describe(&bytes, reply_to)),
+        Err(e) => println!("  error: {}", e),
+    }
+}
+
+/// Parse hex (whitespace, `:` and a `0x` prefix allowed) or base64
+pub fn parse_input(text: &str) -> Result<Vec<u8>> {
+    let text = text.trim();
+    let hex: String = text
+        .trim_start_matches("0x")
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect();
+
+    if !hex.is_empty() && hex.len() % 2 == 0 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).context("Invalid hex"))
+            .collect();
+    }
+
+    base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .context("Not valid hex or base64")
+}
+
+/// Human-readable dissection of one frame
+pub fn describe(frame: &[u8], reply_to: Option<Command>) -> String {
+    let mut out = String::new();
+    let mut line = |label: &str, value: String| out.push_str(&format!("  {:<9} {}\n", label, value));
+
+    let mut data = frame;
+    if data.len() >= 8 && data[..4] == TCP_MAGIC {
+        let declared = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let note = if declared == data.len() - 8 { "" } else { " (does not match)" };
+        line("wrapper", format!("TCP, declares {} bytes{}", declared, note));
+        data = &data[8..];
+    }
+
+    if data.len() < Packet::HEADER_SIZE {
+        line("error", format!("{} bytes is shorter than a packet header", data.len()));
+        return out;
+    }
+
+    let field = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let (code, carried, session_id, reply_id) = (field(0), field(2), field(4), field(6));
+
+    let payload = &data[Packet::HEADER_SIZE..];
+
+    let command = Command::try_from(code).ok();
+    let kind = match command {
+        Some(command) if command.is_response() => "response",
+        Some(_) => "request",
+        None => "unknown command",
+    };
+    line(
+        "command",
+        format!("{} ({}), {}", command.map_or("?", Command::name), code, kind),
+    );
+
+    let expected = checksum::calculate(code, session_id, reply_id, payload);
+    let validity = if carried == expected {
+        "valid".to_string()
+    } else {
+        format!("INVALID, expected {:#06x}", expected)
+    };
+    line("checksum", format!("{:#06x} ({})", carried, validity));
+    line("session", session_id.to_string());
+    line("reply id", reply_id.to_string());
+    line("payload", format!("{} bytes", payload.len()));
+
+    if !payload.is_empty() {
+        line("hex", hex(payload));
+    }
+    if let Some(decoded) = command.and_then(|command| decode_payload(command, payload, reply_to)) {
+        line("decoded", decoded);
+    }
+    out
+}
+
+/// Meaning of `payload` for commands with a known layout
+///
+/// Replies carry no command of their own; `reply_to` names the request
+/// they answer.
+pub fn decode_payload(command: Command, payload: &[u8], reply_to: Option<Command>) -> Option<String> {
+    let u16_at = |at: usize| payload.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let u32_at = |at: usize| payload.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+    let about = if command.is_response() { reply_to? } else { command };
+    match about {
+        Command::Auth => u32_at(0).map(|key| format!("CommKey {:#010x}", key)),
+        Command::GetTime | Command::SetTime => {
+            let value = u32_at(0)?;
+            Some(match time::decode_time(value) {
+                Ok(time) => format!("time {}", time),
+                Err(e) => e.to_string(),
+            })
+        }
+        Command::OptionsRrq | Command::OptionsWrq | Command::GetVersion => Some(text(payload)),
+        Command::GetFreeSizes if command.is_response() => {
+            DeviceCapacity::parse(payload).ok().map(|capacity| format!("{:?}", capacity))
+        }
+        Command::PrepareData => u32_at(0).map(|size| format!("bulk transfer of {} bytes", size)),
+        Command::DeleteUser => u16_at(0).map(|uid| format!("uid {}", uid)),
+        Command::ChangeSpeed => payload.first().map(|speed| format!("speed {}", speed)),
+        Command::UserWrq => {
+            let layout = UserRecordLayout::detect(payload.len(), 1)?;
+            let users = user::parse_users_with(payload, layout, &zkrust_types::text::Utf8).ok()?;
+            users.first().map(|user| format!("{:?} user {}", layout, user))
+        }
+        _ => None,
+    }
+}
+
+/// Printable text of a NUL-terminated payload
+fn text(payload: &[u8]) -> String {
+    format!("{:?}", String::from_utf8_lossy(payload).trim_end_matches('\0'))
+}
+
+/// Space-separated hex bytes
+pub fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(parse_input("e8 03:17 fc").unwrap(), [0xe8, 0x03, 0x17, 0xfc]);
+        assert_eq!(parse_input("0xe803").unwrap(), [0xe8, 0x03]);
+        assert_eq!(parse_input("6AM=").unwrap(), [0xe8, 0x03]);
+        assert!(parse_input("not a frame!").is_err());
+    }
+
+    #[test]
+    fn test_describe_wrapped_request() {
+        let packet = Packet::with_payload(Command::DeleteUser, 0x1234, 7, vec![42, 0]);
+        let mut frame = TCP_MAGIC.to_vec();
+        frame.extend_from_slice(&10u32.to_le_bytes());
+        frame.extend_from_slice(&packet.encode());
+
+        let text = describe(&frame, None);
+        assert!(text.contains("TCP, declares 10 bytes\n"), "{}", text);
+        assert!(text.contains("CMD_DELETE_USER (18), request"), "{}", text);
+        assert!(text.contains("(valid)"), "{}", text);
+        assert!(text.contains("session   4660"), "{}", text);
+        assert!(text.contains("decoded   uid 42"), "{}", text);
+    }
+
+    #[test]
+    fn test_describe_bad_checksum_and_reply() {
+        let stamp = time::encode_time(&chrono::NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(8, 30, 0)
+            .unwrap())
+        .unwrap();
+        let mut frame = Packet::with_payload(Command::AckOk, 1, 2, stamp.to_le_bytes().to_vec()).encode();
+        frame[2] ^= 0xff;
+
+        let text = describe(&frame, Some(Command::GetTime));
+        assert!(text.contains("INVALID, expected"), "{}", text);
+        assert!(text.contains("CMD_ACK_OK (2000), response"), "{}", text);
+        assert!(text.contains("time 2024-03-01 08:30:00"), "{}", text);
+        assert!(!describe(&frame, None).contains("decoded"));
+
+        assert!(describe(&[1, 2, 3], None).contains("shorter than a packet header"));
+    }
+}
@@ -0,0 +1,333 @@
+//! `zk` - command-line tool for ZKTeco devices
+//!
+//! Devices are picked by name from the configuration file (see
+//! [`config`]) or given directly with `--host`.
+
+mod commands;
+mod config;
+mod decode;
+#[cfg(feature = "tui")]
+mod monitor;
+mod shell;
+
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use zkrust::capture::Capture;
+use zkrust_core::constants::Privilege;
+
+use crate::config::{DeviceEntry, TransportKind, DEFAULT_PORT};
+
+#[derive(Debug, Parser)]
+#[command(name = "zk", version, about = "Manage ZKTeco attendance devices")]
+struct Cli {
+    /// Configuration file [default: $ZKRUST_CONFIG or ~/.config/zkrust/config.toml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Device name from the configuration file
+    #[arg(short, long, global = true, conflicts_with = "host")]
+    device: Option<String>,
+
+    #[command(flatten)]
+    target: Target,
+
+    /// Log protocol traffic (-v debug, -vv trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Record every frame to FILE (pcap for .pcap, JSON lines otherwise)
+    #[arg(long, global = true, value_name = "FILE")]
+    capture: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Device given on the command line instead of the configuration file
+#[derive(Debug, Args)]
+struct Target {
+    /// Device address
+    #[arg(long, global = true)]
+    host: Option<String>,
+
+    #[arg(long, global = true, default_value_t = DEFAULT_PORT)]
+    port: u16,
+
+    /// Device CommKey (overrides the configuration file)
+    #[arg(long, global = true, env = "ZKRUST_COMMKEY", hide_env_values = true)]
+    commkey: Option<u32>,
+
+    #[arg(long, global = true, default_value_t = TransportKind::Tcp)]
+    transport: TransportKind,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Show device information, storage usage and clock
+    Info,
+
+    /// Manage enrolled users
+    #[command(subcommand)]
+    Users(UsersCommand),
+
+    /// Attendance log
+    #[command(subcommand)]
+    Attlog(AttlogCommand),
+
+    /// Device clock
+    #[command(subcommand)]
+    Time(TimeCommand),
+
+    /// Live attendance events
+    #[command(subcommand)]
+    Events(EventsCommand),
+
+    /// Device options
+    #[command(subcommand)]
+    Option(OptionCommand),
+
+    /// Save device info, users, fingerprints and attendance to a JSON file
+    Backup {
+        /// Output file [default: stdout]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Interactive prompt for sending raw commands and reading the responses
+    Shell,
+
+    /// Live dashboard of all configured devices
+    #[cfg(feature = "tui")]
+    Monitor {
+        /// Seconds between polls of each device
+        #[arg(long, default_value_t = 10)]
+        interval: u64,
+    },
+
+    /// Decode hex or base64 protocol frames without a device
+    Decode {
+        /// Frames to decode [default: one per line from stdin]
+        frames: Vec<String>,
+
+        /// Command the frames reply to, for decoding response payloads
+        #[arg(long, value_name = "CMD")]
+        reply_to: Option<zkrust_core::Command>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum UsersCommand {
+    /// List enrolled users
+    List {
+        /// Print NDJSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Create or update a user
+    Add(AddUser),
+
+    /// Delete a user by internal UID
+    Del { uid: u16 },
+}
+
+#[derive(Debug, Args)]
+struct AddUser {
+    /// Internal device slot
+    #[arg(long)]
+    uid: u16,
+
+    /// User ID (badge number)
+    #[arg(long)]
+    user_id: String,
+
+    #[arg(long, default_value = "")]
+    name: String,
+
+    /// user, enroller, manager or admin
+    #[arg(long, default_value = "user")]
+    privilege: Privilege,
+
+    /// Keypad password
+    #[arg(long, default_value = "")]
+    password: String,
+
+    #[arg(long, default_value_t = 0)]
+    card: u64,
+
+    #[arg(long, default_value = "")]
+    group: String,
+}
+
+#[derive(Debug, Subcommand)]
+enum AttlogCommand {
+    /// Download attendance records
+    Pull {
+        /// Only records at or after this date (2024-03-01) or time (2024-03-01T08:00:00)
+        #[arg(long, value_parser = commands::parse_since)]
+        since: Option<chrono::NaiveDateTime>,
+
+        #[arg(long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+
+        /// Output file [default: stdout]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Csv,
+    Ndjson,
+}
+
+#[derive(Debug, Subcommand)]
+enum TimeCommand {
+    /// Show the device clock and its offset from this host
+    Show,
+
+    /// Set the device clock to this host's local time
+    Sync,
+}
+
+#[derive(Debug, Subcommand)]
+enum EventsCommand {
+    /// Poll the device and print new attendance records as they appear
+    Watch {
+        /// Seconds between polls
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum OptionCommand {
+    /// Read an option, e.g. `~SerialNumber`
+    Get { name: String },
+
+    /// Write an option
+    Set { name: String, value: String },
+
+    /// Save a curated set of the device's settings to a profile file
+    Snapshot {
+        /// Output file [default: stdout]
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Configure the device from a profile file, showing the changes
+    Apply {
+        /// Profile written by `option snapshot`
+        profile: PathBuf,
+
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+impl Cli {
+    /// Resolve the device to talk to
+    fn device_config(&self) -> anyhow::Result<DeviceEntry> {
+        let mut device = match &self.target.host {
+            Some(host) => DeviceEntry::new(host.clone())
+                .with_port(self.target.port)
+                .with_transport(self.target.transport),
+            None => config::device(&config::load(self.config.as_deref())?, self.device.as_deref())?,
+        };
+        if let Some(commkey) = self.target.commkey {
+            device = device.with_password(commkey);
+        }
+        Ok(device)
+    }
+
+    /// Devices to monitor: the one given, else every configured device
+    #[cfg(feature = "tui")]
+    fn monitor_targets(&self) -> anyhow::Result<Vec<(String, DeviceEntry)>> {
+        if self.target.host.is_some() || self.device.is_some() {
+            let device = self.device_config()?;
+            let name = self.device.clone().unwrap_or_else(|| device.host.clone());
+            return Ok(vec![(name, device)]);
+        }
+
+        let config = config::load(self.config.as_deref())?;
+        anyhow::ensure!(!config.is_empty(), "No devices in the config file; use --host or --device");
+        Ok(config.iter().map(|(name, entry)| (name.to_string(), entry.clone())).collect())
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    // Log lines would corrupt the dashboard; it shows errors itself
+    #[cfg(feature = "tui")]
+    if let Command::Monitor { interval } = cli.command {
+        return monitor::run(cli.monitor_targets()?, std::time::Duration::from_secs(interval)).await;
+    }
+
+    if let Command::Decode { frames, reply_to } = &cli.command {
+        return decode::run(frames, *reply_to);
+    }
+
+    let level = match cli.verbose {
+        0 => "warn",
+        1 => "debug",
+        _ => "trace",
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| level.into()))
+        .with_writer(std::io::stderr)
+        .init();
+
+    let target = cli.device_config()?;
+    let mut device = target.build()?;
+    if let Some(path) = &cli.capture {
+        let capture = Capture::create(path).with_context(|| format!("Cannot create {}", path.display()))?;
+        device.start_capture(capture);
+    }
+    device
+        .connect()
+        .await
+        .with_context(|| format!("Cannot connect to {}:{}", target.host, target.port))?;
+
+    let result = commands::run(&mut device, cli.command).await;
+
+    // Keep the command's error if disconnecting fails too
+    let disconnected = device.disconnect().await;
+    result?;
+    Ok(disconnected?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        let cli = Cli::try_parse_from([
+            "zk", "--host", "10.0.0.5", "users", "add", "--uid", "3", "--user-id", "42", "--privilege", "admin",
+        ])
+        .unwrap();
+
+        let device = cli.device_config().unwrap();
+        assert_eq!(device.host, "10.0.0.5");
+        assert_eq!(device.port, DEFAULT_PORT);
+        match cli.command {
+            Command::Users(UsersCommand::Add(user)) => assert_eq!(user.privilege, Privilege::Admin),
+            other => panic!("Unexpected command {:?}", other),
+        }
+
+        assert!(Cli::try_parse_from(["zk", "--host", "a", "--device", "b", "info"]).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+//! Terminal fleet monitor (`zk monitor`)
+//!
+//! Polls every configured device in the background and shows connectivity,
+//! clock skew and storage per device above a scrolling feed of new
+//! attendance records. Press `q` or Esc to quit.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{Local, NaiveDateTime};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Cell, List, ListItem, Row, Table};
+use ratatui::Frame;
+use tokio::sync::mpsc;
+
+use zkrust::{Device, HealthIssue, HealthReport};
+use zkrust_sync::Cursor;
+use zkrust_types::AttendanceRecord;
+
+use crate::config::DeviceEntry;
+
+/// Attendance records kept in the feed
+const FEED_LENGTH: usize = 500;
+
+/// How often the screen is redrawn and keys are checked
+const FRAME_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Result of one poll, sent from a device task to the UI
+#[derive(Debug)]
+enum Update {
+    Health { device: usize, report: HealthReport, error: Option<String> },
+    Events { device: usize, records: Vec<AttendanceRecord> },
+}
+
+/// Latest known state of one device
+#[derive(Debug)]
+struct DeviceRow {
+    name: String,
+    address: String,
+    health: Option<HealthReport>,
+    error: Option<String>,
+    events: u64,
+}
+
+/// Everything the screen shows
+#[derive(Debug)]
+struct Monitor {
+    devices: Vec<DeviceRow>,
+    feed: VecDeque<(String, AttendanceRecord)>,
+}
+
+impl Monitor {
+    fn new(targets: &[(String, DeviceEntry)]) -> Self {
+        Self {
+            devices: targets
+                .iter()
+                .map(|(name, config)| DeviceRow {
+                    name: name.clone(),
+                    address: config.address(),
+                    health: None,
+                    error: None,
+                    events: 0,
+                })
+                .collect(),
+            feed: VecDeque::new(),
+        }
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Health { device, report, error } => {
+                let row = &mut self.devices[device];
+                row.health = Some(report);
+                row.error = error;
+            }
+            Update::Events { device, records } => {
+                let row = &mut self.devices[device];
+                row.events += records.len() as u64;
+                for record in records {
+                    self.feed.push_front((row.name.clone(), record));
+                }
+                self.feed.truncate(FEED_LENGTH);
+            }
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [devices, feed] =
+            Layout::vertical([Constraint::Length(self.devices.len() as u16 + 3), Constraint::Min(3)])
+                .areas(frame.area());
+
+        let header = Row::new(["DEVICE", "ADDRESS", "STATUS", "SKEW", "USERS", "FINGERS", "RECORDS", "CHECKED", "ISSUES"])
+            .style(Style::new().add_modifier(Modifier::BOLD));
+        let rows = self.devices.iter().map(device_row);
+        let widths = [
+            Constraint::Length(16),
+            Constraint::Length(21),
+            Constraint::Length(6),
+            Constraint::Length(7),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(15),
+            Constraint::Length(8),
+            Constraint::Min(10),
+        ];
+        let title = format!(" Devices ({}) - q to quit ", self.devices.len());
+        frame.render_widget(Table::new(rows, widths).header(header).block(Block::bordered().title(title)), devices);
+
+        let items = self
+            .feed
+            .iter()
+            .map(|(device, record)| ListItem::new(format!("{:<16} {}", device, record)));
+        frame.render_widget(List::new(items).block(Block::bordered().title(" Attendance ")), feed);
+    }
+}
+
+fn device_row(row: &DeviceRow) -> Row<'_> {
+    let Some(health) = &row.health else {
+        return Row::new([row.name.as_str(), row.address.as_str(), "..."]);
+    };
+
+    let (status, color) = match (health.connected, health.is_healthy()) {
+        (false, _) => ("DOWN", Color::Red),
+        (true, true) => ("OK", Color::Green),
+        (true, false) => ("WARN", Color::Yellow),
+    };
+    let usage = |used: Option<(u32, u32)>| match used {
+        Some((used, total)) => format!("{}/{}", used, total),
+        None => "-".to_string(),
+    };
+    let capacity = health.capacity;
+    // A failed connect explains "not connected" better than the issue itself
+    let mut issues: Vec<String> = health
+        .issues
+        .iter()
+        .filter(|issue| row.error.is_none() || **issue != HealthIssue::NotConnected)
+        .map(ToString::to_string)
+        .collect();
+    issues.extend(row.error.clone());
+
+    Row::new([
+        Cell::from(row.name.as_str()),
+        Cell::from(row.address.as_str()),
+        Cell::from(status).style(Style::new().fg(color)),
+        Cell::from(health.clock_skew.map_or("-".to_string(), |s| format!("{:+}s", s.num_seconds()))),
+        Cell::from(usage(capacity.map(|c| (c.users, c.users_capacity)))),
+        Cell::from(usage(capacity.map(|c| (c.fingers, c.fingers_capacity)))),
+        Cell::from(usage(capacity.map(|c| (c.records, c.records_capacity)))),
+        Cell::from(time_of_day(health.checked_at)),
+        Cell::from(issues.join("; ")),
+    ])
+}
+
+fn time_of_day(time: NaiveDateTime) -> String {
+    time.format("%H:%M:%S").to_string()
+}
+
+/// Run the monitor until the user quits
+pub async fn run(targets: Vec<(String, DeviceEntry)>, interval: Duration) -> Result<()> {
+    let mut monitor = Monitor::new(&targets);
+    let (updates, mut received) = mpsc::channel(256);
+
+    let devices = targets
+        .iter()
+        .map(|(_, config)| config.build())
+        .collect::<zkrust::Result<Vec<_>>>()?;
+    let tasks: Vec<_> = devices
+        .into_iter()
+        .enumerate()
+        .map(|(index, device)| tokio::spawn(poll(index, device, interval, updates.clone())))
+        .collect();
+    drop(updates);
+
+    let mut terminal = ratatui::init();
+    let mut frames = tokio::time::interval(FRAME_INTERVAL);
+
+    let result = loop {
+        tokio::select! {
+            Some(update) = received.recv() => {
+                monitor.apply(update);
+                continue;
+            }
+            _ = frames.tick() => {}
+        }
+
+        if let Err(e) = terminal.draw(|frame| monitor.render(frame)) {
+            break Err(e.into());
+        }
+
+        match quit_requested() {
+            Ok(false) => {}
+            Ok(true) => break Ok(()),
+            Err(e) => break Err(e),
+        }
+    };
+
+    ratatui::restore();
+    for task in tasks {
+        task.abort();
+    }
+    result
+}
+
+/// Drain pending key events without blocking
+fn quit_requested() -> Result<bool> {
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Poll one device until the UI goes away
+async fn poll(index: usize, mut device: Device, interval: Duration, updates: mpsc::Sender<Update>) {
+    // Records already on the device when monitoring starts are not shown
+    let mut cursor: Option<Cursor> = None;
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        let mut error = None;
+        if !device.is_connected() {
+            if let Err(e) = device.connect().await {
+                error = Some(e.to_string());
+            }
+        }
+
+        let mut report = device.health_check().await;
+        report.checked_at = Local::now().naive_local();
+        let connected = report.connected;
+
+        let mut records = Vec::new();
+        if connected {
+            match device.get_attendance().await {
+                Ok(all) => match &mut cursor {
+                    Some(cursor) => {
+                        records = cursor.filter_new(all);
+                        cursor.advance(&records);
+                    }
+                    None => {
+                        let mut primed = Cursor::default();
+                        let existing = primed.filter_new(all);
+                        primed.advance(&existing);
+                        cursor = Some(primed);
+                    }
+                },
+                Err(e) => error = Some(e.to_string()),
+            }
+        }
+
+        let update = Update::Health { device: index, report, error };
+        if updates.send(update).await.is_err() {
+            break;
+        }
+        if !records.is_empty() && updates.send(Update::Events { device: index, records }).await.is_err() {
+            break;
+        }
+    }
+
+    let _ = device.disconnect().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+
+    fn target(name: &str, host: &str) -> (String, DeviceEntry) {
+        (name.to_string(), DeviceEntry::new(host))
+    }
+
+    fn screen(monitor: &Monitor) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(140, 20)).unwrap();
+        terminal.draw(|frame| monitor.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_render_devices_and_feed() {
+        let mut monitor = Monitor::new(&[target("front-door", "10.0.0.1"), target("warehouse", "10.0.0.2")]);
+        let checked_at = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+
+        monitor.apply(Update::Health {
+            device: 1,
+            report: HealthReport {
+                checked_at,
+                connected: false,
+                authenticated: false,
+                latency: None,
+                device_time: None,
+                clock_skew: None,
+                capacity: None,
+                issues: vec![HealthIssue::NotConnected],
+            },
+            error: Some("Connection refused".into()),
+        });
+        monitor.apply(Update::Events {
+            device: 0,
+            records: vec![AttendanceRecord::new("42", checked_at, VerifyMode::Fingerprint, PunchType::CheckIn)],
+        });
+
+        let screen = screen(&monitor);
+        assert!(screen.contains("front-door"), "{}", screen);
+        assert!(screen.contains("10.0.0.2:4370"), "{}", screen);
+        assert!(screen.contains("DOWN"), "{}", screen);
+        assert!(screen.contains("Connection refused"), "{}", screen);
+        assert!(screen.contains("42 Check-In"), "{}", screen);
+        assert_eq!(monitor.devices[0].events, 1);
+    }
+
+    #[test]
+    fn test_feed_is_bounded() {
+        let mut monitor = Monitor::new(&[target("door", "10.0.0.1")]);
+        let time = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let records: Vec<_> = (0..FEED_LENGTH + 10)
+            .map(|i| AttendanceRecord::new(i.to_string(), time, VerifyMode::Card, PunchType::CheckIn))
+            .collect();
+
+        monitor.apply(Update::Events { device: 0, records });
+
+        assert_eq!(monitor.feed.len(), FEED_LENGTH);
+        // Newest first
+        assert_eq!(monitor.feed[0].1.user_id, (FEED_LENGTH + 9).to_string());
+    }
+}
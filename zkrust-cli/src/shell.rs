@@ -0,0 +1,134 @@
+//! `zk shell`: interactive protocol prompt
+//!
+//! Each line names a command (`get-time`, `CMD_GET_TIME`, `201`) and an
+//! optional payload, sends it on the connected device and prints the
+//! decoded response. Meant for exploring undocumented firmware behaviour.
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::{Context, Result};
+
+use zkrust::{Command, Device};
+
+use crate::decode;
+
+const HELP: &str = "\
+<command> [payload]   send a command by name or code, e.g. `get-time` or `201`
+                      payload: hex or base64 bytes, or \"text\" (sent NUL-terminated)
+commands              list known commands and their codes
+help                  show this help
+quit                  disconnect and exit";
+
+/// One parsed input line
+#[derive(Debug, PartialEq, Eq)]
+enum Input {
+    Empty,
+    Help,
+    Commands,
+    Quit,
+    Send(Command, Vec<u8>),
+}
+
+/// Read commands from stdin until `quit` or end of input
+pub async fn run(device: &mut Device) -> Result<()> {
+    println!("Connected. Type `help` for usage.");
+    loop {
+        print!("zk> ");
+        io::stdout().flush()?;
+
+        let Some(line) = tokio::task::spawn_blocking(read_line).await?? else {
+            println!();
+            return Ok(());
+        };
+
+        match parse_line(&line) {
+            Ok(Input::Empty) => {}
+            Ok(Input::Help) => println!("{}", HELP),
+            Ok(Input::Commands) => {
+                for command in Command::iter() {
+                    println!("{:>5}  {}", command as u16, command.name());
+                }
+            }
+            Ok(Input::Quit) => return Ok(()),
+            Ok(Input::Send(command, payload)) => send(device, command, payload).await,
+            Err(e) => println!("error: {:#}", e),
+        }
+    }
+}
+
+fn read_line() -> Result<Option<String>> {
+    let mut line = String::new();
+    let read = io::stdin().lock().read_line(&mut line).context("Cannot read stdin")?;
+    Ok((read > 0).then_some(line))
+}
+
+async fn send(device: &mut Device, command: Command, payload: Vec<u8>) {
+    println!("-> {} ({}), {} bytes", command.name(), command as u16, payload.len());
+    let response = match device.send_raw(command, payload).await {
+        Ok(response) => response,
+        Err(e) => return println!("error: {}", e),
+    };
+
+    let payload = &response.payload[..];
+    println!(
+        "<- {} ({}), reply id {}, {} bytes",
+        response.command.name(),
+        response.command as u16,
+        response.reply_id,
+        payload.len()
+    );
+    if !payload.is_empty() {
+        println!("   hex      {}", decode::hex(payload));
+    }
+    if let Some(decoded) = decode::decode_payload(response.command, payload, Some(command)) {
+        println!("   decoded  {}", decoded);
+    }
+}
+
+fn parse_line(line: &str) -> Result<Input> {
+    let line = line.trim();
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+
+    Ok(match word {
+        "" => Input::Empty,
+        "help" | "?" => Input::Help,
+        "commands" => Input::Commands,
+        "quit" | "exit" => Input::Quit,
+        _ => {
+            let command: Command = word.parse()?;
+            let payload = if rest.is_empty() {
+                Vec::new()
+            } else if let Some(text) = rest.strip_prefix('"') {
+                let text = text.strip_suffix('"').context("Unterminated text payload")?;
+                let mut bytes = text.as_bytes().to_vec();
+                bytes.push(0);
+                bytes
+            } else {
+                decode::parse_input(rest)?
+            };
+            Input::Send(command, payload)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_line("  \n").unwrap(), Input::Empty);
+        assert_eq!(parse_line("quit\n").unwrap(), Input::Quit);
+        assert_eq!(parse_line("get-time").unwrap(), Input::Send(Command::GetTime, vec![]));
+        assert_eq!(parse_line("18 2a 00").unwrap(), Input::Send(Command::DeleteUser, vec![0x2a, 0]));
+        assert_eq!(
+            parse_line("CMD_OPTIONS_RRQ \"~Platform\"").unwrap(),
+            Input::Send(Command::OptionsRrq, b"~Platform\0".to_vec())
+        );
+
+        assert!(parse_line("no-such-command").is_err());
+        assert!(parse_line("get-time zz").is_err());
+        assert!(parse_line("options-rrq \"~Platform").is_err());
+    }
+}
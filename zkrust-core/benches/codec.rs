@@ -0,0 +1,62 @@
+//! Packet codec benchmarks
+//!
+//! Run with `cargo bench -p zkrust-core`.
+
+use std::hint::black_box;
+
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use zkrust_core::{auth, checksum, Command, Packet};
+
+/// Payload sizes: empty command, typical upload chunk, largest classic packet
+const SIZES: [usize; 3] = [0, 1024, Packet::MAX_PAYLOAD_SIZE];
+
+fn bench_checksum(c: &mut Criterion) {
+    let mut group = c.benchmark_group("checksum");
+    for size in SIZES {
+        let payload = vec![0xA5; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_function(size.to_string(), |b| {
+            b.iter(|| checksum::calculate(black_box(1501), black_box(0x1234), black_box(7), black_box(&payload)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for size in SIZES {
+        let packet = Packet::with_payload(Command::Data, 0x1234, 7, vec![0xA5; size]);
+        group.throughput(Throughput::Bytes((Packet::HEADER_SIZE + size) as u64));
+        group.bench_function(format!("alloc/{}", size), |b| b.iter(|| black_box(&packet).encode()));
+        group.bench_function(format!("reuse/{}", size), |b| {
+            let mut buf = BytesMut::new();
+            b.iter(|| {
+                buf.clear();
+                black_box(&packet).encode_into(&mut buf);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for size in SIZES {
+        let encoded = Packet::with_payload(Command::Data, 0x1234, 7, vec![0xA5; size]).encode();
+        group.throughput(Throughput::Bytes(encoded.len() as u64));
+        group.bench_function(size.to_string(), |b| {
+            b.iter_batched(|| encoded.clone(), Packet::decode, BatchSize::SmallInput)
+        });
+    }
+    group.finish();
+}
+
+fn bench_commkey(c: &mut Criterion) {
+    c.bench_function("commkey", |b| {
+        b.iter(|| auth::make_commkey(black_box(123456), black_box(0x1234), black_box(50)))
+    });
+}
+
+criterion_group!(benches, bench_checksum, bench_encode, bench_decode, bench_commkey);
+criterion_main!(benches);
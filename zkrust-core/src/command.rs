@@ -1,326 +1,815 @@
-//! ZKTeco protocol command definitions
-
-use std::fmt;
-
-use crate::error::{Error, Result};
-
-/// Protocol command codes
-///
-/// All commands from the ZKTeco Communication Protocol Manual.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[repr(u16)]
-pub enum Command {
-    // Connection commands
-    Connect = 1000,
-    Exit = 1001,
-    EnableDevice = 1002,
-    DisableDevice = 1003,
-    Restart = 1004,
-    PowerOff = 1005,
-    Sleep = 1006,
-    Resume = 1007,
-    
-    // Device interaction
-    CaptureFinger = 1009,
-    TestTemp = 1011,
-    CaptureImage = 1012,
-    RefreshData = 1013,
-    RefreshOption = 1014,
-    TestVoice = 1017,
-    
-    // Device information
-    GetVersion = 1100,
-    ChangeSpeed = 1101,
-    Auth = 1102,
-    
-    // Data transfer
-    PrepareData = 1500,
-    Data = 1501,
-    FreeData = 1502,
-    
-    // Database operations
-    DbRrq = 7,
-    UserWrq = 8,
-    UserTempRrq = 9,
-    UserTempWrq = 10,
-    OptionsRrq = 11,
-    OptionsWrq = 12,
-    AttLogRrq = 13,
-    ClearData = 14,
-    ClearAttLog = 15,
-    DeleteUser = 18,
-    DeleteUserTemp = 19,
-    ClearAdmin = 20,
-    
-    // Group & timezone management
-    UserGrpRrq = 21,
-    UserGrpWrq = 22,
-    UserTzRrq = 23,
-    UserTzWrq = 24,
-    GrpTzRrq = 25,
-    GrpTzWrq = 26,
-    TzRrq = 27,
-    TzWrq = 28,
-    UlgRrq = 29,
-    UlgWrq = 30,
-    Unlock = 31,
-    ClearAcc = 32,
-    ClearOpLog = 33,
-    OpLogRrq = 34,
-    
-    // Device status
-    GetFreeSizes = 50,
-    EnableClock = 57,
-    StartVerify = 60,
-    StartEnroll = 61,
-    CancelCapture = 62,
-    StateRrq = 64,
-    WriteLcd = 66,
-    ClearLcd = 67,
-    GetPinWidth = 69,
-    
-    // SMS operations
-    SmsWrq = 70,
-    SmsRrq = 71,
-    DeleteSms = 72,
-    UDataWrq = 73,
-    DeleteUData = 74,
-    
-    // Access control
-    DoorStateRrq = 75,
-    WriteMifare = 76,
-    EmptyMifare = 78,
-    
-    // Time operations
-    GetTime = 201,
-    SetTime = 202,
-    
-    // Real-time events
-    RegEvent = 500,
-    
-    // Response commands (from device)
-    AckOk = 2000,
-    AckError = 2001,
-    AckData = 2002,
-    AckRetry = 2003,
-    AckRepeat = 2004,
-    AckUnauth = 2005,
-    AckUnknown = 0xFFFF,
-    AckErrorCmd = 0xFFFD,
-    AckErrorInit = 0xFFFC,
-    AckErrorData = 0xFFFB,
-}
-
-impl Command {
-    /// Check if this is a request command (from PC to device)
-    pub fn is_request(self) -> bool {
-        !self.is_response()
-    }
-    
-    /// Check if this is a response command (from device to PC)
-    pub fn is_response(self) -> bool {
-        matches!(
-            self,
-            Self::AckOk
-                | Self::AckError
-                | Self::AckData
-                | Self::AckRetry
-                | Self::AckRepeat
-                | Self::AckUnauth
-                | Self::AckUnknown
-                | Self::AckErrorCmd
-                | Self::AckErrorInit
-                | Self::AckErrorData
-        )
-    }
-    
-    /// Check if this is a success response
-    pub fn is_success(self) -> bool {
-        matches!(self, Self::AckOk | Self::AckData)
-    }
-    
-    /// Check if this is an error response
-    pub fn is_error(self) -> bool {
-        matches!(
-            self,
-            Self::AckError
-                | Self::AckErrorCmd
-                | Self::AckErrorInit
-                | Self::AckErrorData
-        )
-    }
-    
-    /// Get command name
-    pub fn name(self) -> &'static str {
-        match self {
-            Self::Connect => "CMD_CONNECT",
-            Self::Exit => "CMD_EXIT",
-            Self::EnableDevice => "CMD_ENABLEDEVICE",
-            Self::DisableDevice => "CMD_DISABLEDEVICE",
-            Self::Restart => "CMD_RESTART",
-            Self::PowerOff => "CMD_POWEROFF",
-            Self::Sleep => "CMD_SLEEP",
-            Self::Resume => "CMD_RESUME",
-            Self::CaptureFinger => "CMD_CAPTUREFINGER",
-            Self::TestTemp => "CMD_TEST_TEMP",
-            Self::CaptureImage => "CMD_CAPTUREIMAGE",
-            Self::RefreshData => "CMD_REFRESHDATA",
-            Self::RefreshOption => "CMD_REFRESHOPTION",
-            Self::TestVoice => "CMD_TESTVOICE",
-            Self::GetVersion => "CMD_GET_VERSION",
-            Self::ChangeSpeed => "CMD_CHANGE_SPEED",
-            Self::Auth => "CMD_AUTH",
-            Self::PrepareData => "CMD_PREPARE_DATA",
-            Self::Data => "CMD_DATA",
-            Self::FreeData => "CMD_FREE_DATA",
-            Self::DbRrq => "CMD_DB_RRQ",
-            Self::UserWrq => "CMD_USER_WRQ",
-            Self::UserTempRrq => "CMD_USERTEMP_RRQ",
-            Self::UserTempWrq => "CMD_USERTEMP_WRQ",
-            Self::OptionsRrq => "CMD_OPTIONS_RRQ",
-            Self::OptionsWrq => "CMD_OPTIONS_WRQ",
-            Self::AttLogRrq => "CMD_ATTLOG_RRQ",
-            Self::ClearData => "CMD_CLEAR_DATA",
-            Self::ClearAttLog => "CMD_CLEAR_ATTLOG",
-            Self::DeleteUser => "CMD_DELETE_USER",
-            Self::DeleteUserTemp => "CMD_DELETE_USERTEMP",
-            Self::ClearAdmin => "CMD_CLEAR_ADMIN",
-            Self::GetTime => "CMD_GET_TIME",
-            Self::SetTime => "CMD_SET_TIME",
-            Self::RegEvent => "CMD_REG_EVENT",
-            Self::AckOk => "CMD_ACK_OK",
-            Self::AckError => "CMD_ACK_ERROR",
-            Self::AckData => "CMD_ACK_DATA",
-            Self::AckUnauth => "CMD_ACK_UNAUTH",
-            _ => "CMD_UNKNOWN",
-        }
-    }
-}
-
-impl From<Command> for u16 {
-    fn from(cmd: Command) -> u16 {
-        cmd as u16
-    }
-}
-
-impl TryFrom<u16> for Command {
-    type Error = Error;
-    
-    fn try_from(value: u16) -> Result<Self> {
-        match value {
-            1000 => Ok(Self::Connect),
-            1001 => Ok(Self::Exit),
-            1002 => Ok(Self::EnableDevice),
-            1003 => Ok(Self::DisableDevice),
-            1004 => Ok(Self::Restart),
-            1005 => Ok(Self::PowerOff),
-            1006 => Ok(Self::Sleep),
-            1007 => Ok(Self::Resume),
-            1009 => Ok(Self::CaptureFinger),
-            1011 => Ok(Self::TestTemp),
-            1012 => Ok(Self::CaptureImage),
-            1013 => Ok(Self::RefreshData),
-            1014 => Ok(Self::RefreshOption),
-            1017 => Ok(Self::TestVoice),
-            1100 => Ok(Self::GetVersion),
-            1101 => Ok(Self::ChangeSpeed),
-            1102 => Ok(Self::Auth),
-            1500 => Ok(Self::PrepareData),
-            1501 => Ok(Self::Data),
-            1502 => Ok(Self::FreeData),
-            7 => Ok(Self::DbRrq),
-            8 => Ok(Self::UserWrq),
-            9 => Ok(Self::UserTempRrq),
-            10 => Ok(Self::UserTempWrq),
-            11 => Ok(Self::OptionsRrq),
-            12 => Ok(Self::OptionsWrq),
-            13 => Ok(Self::AttLogRrq),
-            14 => Ok(Self::ClearData),
-            15 => Ok(Self::ClearAttLog),
-            18 => Ok(Self::DeleteUser),
-            19 => Ok(Self::DeleteUserTemp),
-            20 => Ok(Self::ClearAdmin),
-            21 => Ok(Self::UserGrpRrq),
-            22 => Ok(Self::UserGrpWrq),
-            23 => Ok(Self::UserTzRrq),
-            24 => Ok(Self::UserTzWrq),
-            25 => Ok(Self::GrpTzRrq),
-            26 => Ok(Self::GrpTzWrq),
-            27 => Ok(Self::TzRrq),
-            28 => Ok(Self::TzWrq),
-            29 => Ok(Self::UlgRrq),
-            30 => Ok(Self::UlgWrq),
-            31 => Ok(Self::Unlock),
-            32 => Ok(Self::ClearAcc),
-            33 => Ok(Self::ClearOpLog),
-            34 => Ok(Self::OpLogRrq),
-            50 => Ok(Self::GetFreeSizes),
-            57 => Ok(Self::EnableClock),
-            60 => Ok(Self::StartVerify),
-            61 => Ok(Self::StartEnroll),
-            62 => Ok(Self::CancelCapture),
-            64 => Ok(Self::StateRrq),
-            66 => Ok(Self::WriteLcd),
-            67 => Ok(Self::ClearLcd),
-            69 => Ok(Self::GetPinWidth),
-            70 => Ok(Self::SmsWrq),
-            71 => Ok(Self::SmsRrq),
-            72 => Ok(Self::DeleteSms),
-            73 => Ok(Self::UDataWrq),
-            74 => Ok(Self::DeleteUData),
-            75 => Ok(Self::DoorStateRrq),
-            76 => Ok(Self::WriteMifare),
-            78 => Ok(Self::EmptyMifare),
-            201 => Ok(Self::GetTime),
-            202 => Ok(Self::SetTime),
-            500 => Ok(Self::RegEvent),
-            2000 => Ok(Self::AckOk),
-            2001 => Ok(Self::AckError),
-            2002 => Ok(Self::AckData),
-            2003 => Ok(Self::AckRetry),
-            2004 => Ok(Self::AckRepeat),
-            2005 => Ok(Self::AckUnauth),
-            0xFFFF => Ok(Self::AckUnknown),
-            0xFFFD => Ok(Self::AckErrorCmd),
-            0xFFFC => Ok(Self::AckErrorInit),
-            0xFFFB => Ok(Self::AckErrorData),
-            _ => Err(Error::UnknownCommand(value)),
-        }
-    }
-}
-
-impl fmt::Display for Command {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}({})", self.name(), *self as u16)
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
-    #[test]
-    fn test_command_conversion() {
-        assert_eq!(u16::from(Command::Connect), 1000);
-        assert_eq!(Command::try_from(1000).unwrap(), Command::Connect);
-    }
-    
-    #[test]
-    fn test_command_is_response() {
-        assert!(Command::AckOk.is_response());
-        assert!(!Command::Connect.is_response());
-    }
-    
-    #[test]
-    fn test_command_is_success() {
-        assert!(Command::AckOk.is_success());
-        assert!(Command::AckData.is_success());
-        assert!(!Command::AckError.is_success());
-    }
-    
-    #[test]
-    fn test_unknown_command() {
-        let result = Command::try_from(9999);
-        assert!(result.is_err());
-    }
+//! ZKTeco protocol command definitions
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::constants::normalize_name;
+use crate::error::{Error, Result};
+
+/// Protocol command codes
+///
+/// All commands from the ZKTeco Communication Protocol Manual.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(u16)]
+pub enum Command {
+    // Connection commands
+    Connect = 1000,
+    Exit = 1001,
+    EnableDevice = 1002,
+    DisableDevice = 1003,
+    Restart = 1004,
+    PowerOff = 1005,
+    Sleep = 1006,
+    Resume = 1007,
+    
+    // Device interaction
+    CaptureFinger = 1009,
+    TestTemp = 1011,
+    CaptureImage = 1012,
+    RefreshData = 1013,
+    RefreshOption = 1014,
+    TestVoice = 1017,
+    
+    // Device information
+    GetVersion = 1100,
+    ChangeSpeed = 1101,
+    Auth = 1102,
+    
+    // Data transfer
+    PrepareData = 1500,
+    Data = 1501,
+    FreeData = 1502,
+    
+    // Database operations
+    DbRrq = 7,
+    UserWrq = 8,
+    UserTempRrq = 9,
+    UserTempWrq = 10,
+    OptionsRrq = 11,
+    OptionsWrq = 12,
+    AttLogRrq = 13,
+    ClearData = 14,
+    ClearAttLog = 15,
+    DeleteUser = 18,
+    DeleteUserTemp = 19,
+    ClearAdmin = 20,
+    
+    // Group & timezone management
+    UserGrpRrq = 21,
+    UserGrpWrq = 22,
+    UserTzRrq = 23,
+    UserTzWrq = 24,
+    GrpTzRrq = 25,
+    GrpTzWrq = 26,
+    TzRrq = 27,
+    TzWrq = 28,
+    UlgRrq = 29,
+    UlgWrq = 30,
+    Unlock = 31,
+    ClearAcc = 32,
+    ClearOpLog = 33,
+    OpLogRrq = 34,
+    
+    // Device status
+    GetFreeSizes = 50,
+    EnableClock = 57,
+    StartVerify = 60,
+    StartEnroll = 61,
+    CancelCapture = 62,
+    StateRrq = 64,
+    WriteLcd = 66,
+    ClearLcd = 67,
+    GetPinWidth = 69,
+    
+    // SMS operations
+    SmsWrq = 70,
+    SmsRrq = 71,
+    DeleteSms = 72,
+    UDataWrq = 73,
+    DeleteUData = 74,
+    
+    // Access control
+    DoorStateRrq = 75,
+    WriteMifare = 76,
+    EmptyMifare = 78,
+    
+    // Bulk user upload
+    SaveUserTemps = 110,
+    
+    // Attendance photos (camera models)
+    PhotoNamesRrq = 1950,
+    PhotoRrq = 1951,
+    
+    // Time operations
+    GetTime = 201,
+    SetTime = 202,
+    
+    // Real-time events
+    RegEvent = 500,
+    
+    // Response commands (from device)
+    AckOk = 2000,
+    AckError = 2001,
+    AckData = 2002,
+    AckRetry = 2003,
+    AckRepeat = 2004,
+    AckUnauth = 2005,
+    AckUnknown = 0xFFFF,
+    AckErrorCmd = 0xFFFD,
+    AckErrorInit = 0xFFFC,
+    AckErrorData = 0xFFFB,
+}
+
+impl Command {
+    /// Every known command, in declaration order
+    ///
+    /// Handy for dispatch tables and tooling that must cover the whole
+    /// protocol, e.g. an emulator answering every request it recognizes.
+    pub const ALL: [Command; 79] = [
+        Self::Connect,
+        Self::Exit,
+        Self::EnableDevice,
+        Self::DisableDevice,
+        Self::Restart,
+        Self::PowerOff,
+        Self::Sleep,
+        Self::Resume,
+        Self::CaptureFinger,
+        Self::TestTemp,
+        Self::CaptureImage,
+        Self::RefreshData,
+        Self::RefreshOption,
+        Self::TestVoice,
+        Self::GetVersion,
+        Self::ChangeSpeed,
+        Self::Auth,
+        Self::PrepareData,
+        Self::Data,
+        Self::FreeData,
+        Self::DbRrq,
+        Self::UserWrq,
+        Self::UserTempRrq,
+        Self::UserTempWrq,
+        Self::OptionsRrq,
+        Self::OptionsWrq,
+        Self::AttLogRrq,
+        Self::ClearData,
+        Self::ClearAttLog,
+        Self::DeleteUser,
+        Self::DeleteUserTemp,
+        Self::ClearAdmin,
+        Self::UserGrpRrq,
+        Self::UserGrpWrq,
+        Self::UserTzRrq,
+        Self::UserTzWrq,
+        Self::GrpTzRrq,
+        Self::GrpTzWrq,
+        Self::TzRrq,
+        Self::TzWrq,
+        Self::UlgRrq,
+        Self::UlgWrq,
+        Self::Unlock,
+        Self::ClearAcc,
+        Self::ClearOpLog,
+        Self::OpLogRrq,
+        Self::GetFreeSizes,
+        Self::EnableClock,
+        Self::StartVerify,
+        Self::StartEnroll,
+        Self::CancelCapture,
+        Self::StateRrq,
+        Self::WriteLcd,
+        Self::ClearLcd,
+        Self::GetPinWidth,
+        Self::SmsWrq,
+        Self::SmsRrq,
+        Self::DeleteSms,
+        Self::UDataWrq,
+        Self::DeleteUData,
+        Self::DoorStateRrq,
+        Self::WriteMifare,
+        Self::EmptyMifare,
+        Self::SaveUserTemps,
+        Self::PhotoNamesRrq,
+        Self::PhotoRrq,
+        Self::GetTime,
+        Self::SetTime,
+        Self::RegEvent,
+        Self::AckOk,
+        Self::AckError,
+        Self::AckData,
+        Self::AckRetry,
+        Self::AckRepeat,
+        Self::AckUnauth,
+        Self::AckUnknown,
+        Self::AckErrorCmd,
+        Self::AckErrorInit,
+        Self::AckErrorData,
+    ];
+    
+    /// Iterate over every known command
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zkrust_core::Command;
+    ///
+    /// let destructive: Vec<_> = Command::iter().filter(|cmd| cmd.meta().destructive).collect();
+    /// assert!(destructive.contains(&Command::ClearAttLog));
+    /// ```
+    pub fn iter() -> impl Iterator<Item = Command> {
+        Self::ALL.into_iter()
+    }
+    
+    /// Look up a command by its canonical CMD_* name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::iter().find(|cmd| cmd.name() == name)
+    }
+    
+    /// Check if this is a request command (from PC to device)
+    pub fn is_request(self) -> bool {
+        !self.is_response()
+    }
+    
+    /// Check if this is a response command (from device to PC)
+    pub fn is_response(self) -> bool {
+        matches!(
+            self,
+            Self::AckOk
+                | Self::AckError
+                | Self::AckData
+                | Self::AckRetry
+                | Self::AckRepeat
+                | Self::AckUnauth
+                | Self::AckUnknown
+                | Self::AckErrorCmd
+                | Self::AckErrorInit
+                | Self::AckErrorData
+        )
+    }
+    
+    /// Check if this is a success response
+    pub fn is_success(self) -> bool {
+        matches!(self, Self::AckOk | Self::AckData)
+    }
+    
+    /// Check if this is an error response
+    pub fn is_error(self) -> bool {
+        matches!(
+            self,
+            Self::AckError
+                | Self::AckErrorCmd
+                | Self::AckErrorInit
+                | Self::AckErrorData
+        )
+    }
+    
+    /// Get command name
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Connect => "CMD_CONNECT",
+            Self::Exit => "CMD_EXIT",
+            Self::EnableDevice => "CMD_ENABLEDEVICE",
+            Self::DisableDevice => "CMD_DISABLEDEVICE",
+            Self::Restart => "CMD_RESTART",
+            Self::PowerOff => "CMD_POWEROFF",
+            Self::Sleep => "CMD_SLEEP",
+            Self::Resume => "CMD_RESUME",
+            Self::CaptureFinger => "CMD_CAPTUREFINGER",
+            Self::TestTemp => "CMD_TEST_TEMP",
+            Self::CaptureImage => "CMD_CAPTUREIMAGE",
+            Self::RefreshData => "CMD_REFRESHDATA",
+            Self::RefreshOption => "CMD_REFRESHOPTION",
+            Self::TestVoice => "CMD_TESTVOICE",
+            Self::GetVersion => "CMD_GET_VERSION",
+            Self::ChangeSpeed => "CMD_CHANGE_SPEED",
+            Self::Auth => "CMD_AUTH",
+            Self::PrepareData => "CMD_PREPARE_DATA",
+            Self::Data => "CMD_DATA",
+            Self::FreeData => "CMD_FREE_DATA",
+            Self::DbRrq => "CMD_DB_RRQ",
+            Self::UserWrq => "CMD_USER_WRQ",
+            Self::UserTempRrq => "CMD_USERTEMP_RRQ",
+            Self::UserTempWrq => "CMD_USERTEMP_WRQ",
+            Self::OptionsRrq => "CMD_OPTIONS_RRQ",
+            Self::OptionsWrq => "CMD_OPTIONS_WRQ",
+            Self::AttLogRrq => "CMD_ATTLOG_RRQ",
+            Self::ClearData => "CMD_CLEAR_DATA",
+            Self::ClearAttLog => "CMD_CLEAR_ATTLOG",
+            Self::DeleteUser => "CMD_DELETE_USER",
+            Self::DeleteUserTemp => "CMD_DELETE_USERTEMP",
+            Self::ClearAdmin => "CMD_CLEAR_ADMIN",
+            Self::UserGrpRrq => "CMD_USERGRP_RRQ",
+            Self::UserGrpWrq => "CMD_USERGRP_WRQ",
+            Self::UserTzRrq => "CMD_USERTZ_RRQ",
+            Self::UserTzWrq => "CMD_USERTZ_WRQ",
+            Self::GrpTzRrq => "CMD_GRPTZ_RRQ",
+            Self::GrpTzWrq => "CMD_GRPTZ_WRQ",
+            Self::TzRrq => "CMD_TZ_RRQ",
+            Self::TzWrq => "CMD_TZ_WRQ",
+            Self::UlgRrq => "CMD_ULG_RRQ",
+            Self::UlgWrq => "CMD_ULG_WRQ",
+            Self::Unlock => "CMD_UNLOCK",
+            Self::ClearAcc => "CMD_CLEAR_ACC",
+            Self::ClearOpLog => "CMD_CLEAR_OPLOG",
+            Self::OpLogRrq => "CMD_OPLOG_RRQ",
+            Self::GetFreeSizes => "CMD_GET_FREE_SIZES",
+            Self::EnableClock => "CMD_ENABLE_CLOCK",
+            Self::StartVerify => "CMD_STARTVERIFY",
+            Self::StartEnroll => "CMD_STARTENROLL",
+            Self::CancelCapture => "CMD_CANCELCAPTURE",
+            Self::StateRrq => "CMD_STATE_RRQ",
+            Self::WriteLcd => "CMD_WRITE_LCD",
+            Self::ClearLcd => "CMD_CLEAR_LCD",
+            Self::GetPinWidth => "CMD_GET_PINWIDTH",
+            Self::SmsWrq => "CMD_SMS_WRQ",
+            Self::SmsRrq => "CMD_SMS_RRQ",
+            Self::DeleteSms => "CMD_DELETE_SMS",
+            Self::UDataWrq => "CMD_UDATA_WRQ",
+            Self::DeleteUData => "CMD_DELETE_UDATA",
+            Self::DoorStateRrq => "CMD_DOORSTATE_RRQ",
+            Self::WriteMifare => "CMD_WRITE_MIFARE",
+            Self::EmptyMifare => "CMD_EMPTY_MIFARE",
+            Self::SaveUserTemps => "CMD_SAVE_USERTEMPS",
+            Self::PhotoNamesRrq => "CMD_PHOTO_NAMES_RRQ",
+            Self::PhotoRrq => "CMD_PHOTO_RRQ",
+            Self::GetTime => "CMD_GET_TIME",
+            Self::SetTime => "CMD_SET_TIME",
+            Self::RegEvent => "CMD_REG_EVENT",
+            Self::AckOk => "CMD_ACK_OK",
+            Self::AckError => "CMD_ACK_ERROR",
+            Self::AckData => "CMD_ACK_DATA",
+            Self::AckRetry => "CMD_ACK_RETRY",
+            Self::AckRepeat => "CMD_ACK_REPEAT",
+            Self::AckUnauth => "CMD_ACK_UNAUTH",
+            Self::AckUnknown => "CMD_ACK_UNKNOWN",
+            Self::AckErrorCmd => "CMD_ACK_ERROR_CMD",
+            Self::AckErrorInit => "CMD_ACK_ERROR_INIT",
+            Self::AckErrorData => "CMD_ACK_ERROR_DATA",
+        }
+    }
+}
+
+/// Direction a command travels in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// PC to device
+    Request,
+    
+    /// Device to PC
+    Response,
+    
+    /// Either way (bulk transfer framing)
+    Both,
+}
+
+/// Static metadata describing a command
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CommandMeta {
+    /// Which side sends this command
+    pub direction: Direction,
+    
+    /// Response payload may be a bulk transfer (CMD_PREPARE_DATA / CMD_DATA)
+    pub bulk_data: bool,
+    
+    /// Changes device state (settings, users, clock, ...)
+    pub writes: bool,
+    
+    /// Erases data or takes the device out of service
+    pub destructive: bool,
+    
+    /// Response commands the device may answer with
+    pub expected_responses: &'static [Command],
+}
+
+/// Replies to ordinary requests
+const ACK: &[Command] = &[
+    Command::AckOk,
+    Command::AckError,
+    Command::AckRetry,
+    Command::AckRepeat,
+    Command::AckUnauth,
+    Command::AckUnknown,
+    Command::AckErrorCmd,
+];
+
+/// Replies to requests that return data
+const ACK_DATA: &[Command] = &[
+    Command::AckOk,
+    Command::AckData,
+    Command::PrepareData,
+    Command::Data,
+    Command::AckError,
+    Command::AckRetry,
+    Command::AckRepeat,
+    Command::AckUnauth,
+    Command::AckUnknown,
+    Command::AckErrorCmd,
+    Command::AckErrorData,
+];
+
+/// Replies to connection and authentication
+const ACK_CONNECT: &[Command] = &[
+    Command::AckOk,
+    Command::AckUnauth,
+    Command::AckError,
+    Command::AckErrorInit,
+];
+
+impl CommandMeta {
+    const fn request(expected_responses: &'static [Command]) -> Self {
+        Self {
+            direction: Direction::Request,
+            bulk_data: false,
+            writes: false,
+            destructive: false,
+            expected_responses,
+        }
+    }
+    
+    const fn read() -> Self {
+        Self::request(ACK_DATA)
+    }
+    
+    const fn bulk_read() -> Self {
+        Self {
+            bulk_data: true,
+            ..Self::request(ACK_DATA)
+        }
+    }
+    
+    const fn write() -> Self {
+        Self {
+            writes: true,
+            ..Self::request(ACK)
+        }
+    }
+    
+    const fn destructive() -> Self {
+        Self {
+            writes: true,
+            destructive: true,
+            ..Self::request(ACK)
+        }
+    }
+    
+    const fn response() -> Self {
+        Self {
+            direction: Direction::Response,
+            ..Self::request(&[])
+        }
+    }
+    
+    const fn transfer() -> Self {
+        Self {
+            direction: Direction::Both,
+            bulk_data: true,
+            ..Self::request(ACK)
+        }
+    }
+}
+
+impl Command {
+    /// Get static metadata for this command
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zkrust_core::Command;
+    ///
+    /// assert!(Command::ClearAttLog.meta().destructive);
+    /// assert!(Command::AttLogRrq.meta().bulk_data);
+    /// assert!(Command::GetTime.meta().expected_responses.contains(&Command::AckOk));
+    /// ```
+    pub const fn meta(self) -> CommandMeta {
+        match self {
+            // Session control
+            Self::Connect | Self::Auth => CommandMeta::request(ACK_CONNECT),
+            Self::Exit => CommandMeta::request(ACK),
+            Self::EnableDevice | Self::DisableDevice | Self::Resume => CommandMeta::request(ACK),
+            Self::RefreshData | Self::RefreshOption | Self::TestVoice => CommandMeta::request(ACK),
+            Self::EnableClock | Self::ClearLcd | Self::WriteLcd => CommandMeta::request(ACK),
+            Self::RegEvent | Self::CancelCapture | Self::ChangeSpeed => CommandMeta::request(ACK),
+            Self::StartVerify | Self::StartEnroll | Self::CaptureFinger => CommandMeta::request(ACK),
+            Self::Unlock => CommandMeta::request(ACK),
+            
+            // Out of service
+            Self::Restart | Self::PowerOff | Self::Sleep => CommandMeta::destructive(),
+            
+            // Reads
+            Self::GetVersion | Self::GetTime | Self::GetFreeSizes => CommandMeta::read(),
+            Self::GetPinWidth | Self::StateRrq | Self::DoorStateRrq => CommandMeta::read(),
+            Self::OptionsRrq | Self::TestTemp => CommandMeta::read(),
+            Self::UserGrpRrq | Self::UserTzRrq | Self::GrpTzRrq | Self::TzRrq => CommandMeta::read(),
+            Self::UlgRrq | Self::SmsRrq => CommandMeta::read(),
+            
+            // Bulk reads
+            Self::DbRrq | Self::UserTempRrq | Self::AttLogRrq | Self::OpLogRrq => {
+                CommandMeta::bulk_read()
+            }
+            Self::CaptureImage | Self::PhotoNamesRrq | Self::PhotoRrq => CommandMeta::bulk_read(),
+            
+            // Writes
+            Self::UserWrq | Self::UserTempWrq | Self::OptionsWrq | Self::SetTime => {
+                CommandMeta::write()
+            }
+            Self::UserGrpWrq | Self::UserTzWrq | Self::GrpTzWrq | Self::TzWrq => CommandMeta::write(),
+            Self::UlgWrq | Self::SmsWrq | Self::UDataWrq | Self::WriteMifare => CommandMeta::write(),
+            Self::SaveUserTemps => CommandMeta::write(),
+            
+            // Data loss
+            Self::ClearData | Self::ClearAttLog | Self::ClearAdmin | Self::ClearAcc => {
+                CommandMeta::destructive()
+            }
+            Self::ClearOpLog | Self::DeleteUser | Self::DeleteUserTemp => CommandMeta::destructive(),
+            Self::DeleteSms | Self::DeleteUData | Self::EmptyMifare => CommandMeta::destructive(),
+            
+            // Bulk transfer framing
+            Self::PrepareData | Self::Data | Self::FreeData => CommandMeta::transfer(),
+            
+            // Responses
+            Self::AckOk
+            | Self::AckError
+            | Self::AckData
+            | Self::AckRetry
+            | Self::AckRepeat
+            | Self::AckUnauth
+            | Self::AckUnknown
+            | Self::AckErrorCmd
+            | Self::AckErrorInit
+            | Self::AckErrorData => CommandMeta::response(),
+        }
+    }
+    
+    /// Check if the device may answer this command with `response`
+    pub fn accepts_response(self, response: Command) -> bool {
+        self.meta().expected_responses.contains(&response)
+    }
+}
+
+impl From<Command> for u16 {
+    fn from(cmd: Command) -> u16 {
+        cmd as u16
+    }
+}
+
+impl TryFrom<u16> for Command {
+    type Error = Error;
+    
+    fn try_from(value: u16) -> Result<Self> {
+        match value {
+            1000 => Ok(Self::Connect),
+            1001 => Ok(Self::Exit),
+            1002 => Ok(Self::EnableDevice),
+            1003 => Ok(Self::DisableDevice),
+            1004 => Ok(Self::Restart),
+            1005 => Ok(Self::PowerOff),
+            1006 => Ok(Self::Sleep),
+            1007 => Ok(Self::Resume),
+            1009 => Ok(Self::CaptureFinger),
+            1011 => Ok(Self::TestTemp),
+            1012 => Ok(Self::CaptureImage),
+            1013 => Ok(Self::RefreshData),
+            1014 => Ok(Self::RefreshOption),
+            1017 => Ok(Self::TestVoice),
+            1100 => Ok(Self::GetVersion),
+            1101 => Ok(Self::ChangeSpeed),
+            1102 => Ok(Self::Auth),
+            1500 => Ok(Self::PrepareData),
+            1501 => Ok(Self::Data),
+            1502 => Ok(Self::FreeData),
+            7 => Ok(Self::DbRrq),
+            8 => Ok(Self::UserWrq),
+            9 => Ok(Self::UserTempRrq),
+            10 => Ok(Self::UserTempWrq),
+            11 => Ok(Self::OptionsRrq),
+            12 => Ok(Self::OptionsWrq),
+            13 => Ok(Self::AttLogRrq),
+            14 => Ok(Self::ClearData),
+            15 => Ok(Self::ClearAttLog),
+            18 => Ok(Self::DeleteUser),
+            19 => Ok(Self::DeleteUserTemp),
+            20 => Ok(Self::ClearAdmin),
+            21 => Ok(Self::UserGrpRrq),
+            22 => Ok(Self::UserGrpWrq),
+            23 => Ok(Self::UserTzRrq),
+            24 => Ok(Self::UserTzWrq),
+            25 => Ok(Self::GrpTzRrq),
+            26 => Ok(Self::GrpTzWrq),
+            27 => Ok(Self::TzRrq),
+            28 => Ok(Self::TzWrq),
+            29 => Ok(Self::UlgRrq),
+            30 => Ok(Self::UlgWrq),
+            31 => Ok(Self::Unlock),
+            32 => Ok(Self::ClearAcc),
+            33 => Ok(Self::ClearOpLog),
+            34 => Ok(Self::OpLogRrq),
+            50 => Ok(Self::GetFreeSizes),
+            57 => Ok(Self::EnableClock),
+            60 => Ok(Self::StartVerify),
+            61 => Ok(Self::StartEnroll),
+            62 => Ok(Self::CancelCapture),
+            64 => Ok(Self::StateRrq),
+            66 => Ok(Self::WriteLcd),
+            67 => Ok(Self::ClearLcd),
+            69 => Ok(Self::GetPinWidth),
+            70 => Ok(Self::SmsWrq),
+            71 => Ok(Self::SmsRrq),
+            72 => Ok(Self::DeleteSms),
+            73 => Ok(Self::UDataWrq),
+            74 => Ok(Self::DeleteUData),
+            75 => Ok(Self::DoorStateRrq),
+            76 => Ok(Self::WriteMifare),
+            78 => Ok(Self::EmptyMifare),
+            110 => Ok(Self::SaveUserTemps),
+            1950 => Ok(Self::PhotoNamesRrq),
+            1951 => Ok(Self::PhotoRrq),
+            201 => Ok(Self::GetTime),
+            202 => Ok(Self::SetTime),
+            500 => Ok(Self::RegEvent),
+            2000 => Ok(Self::AckOk),
+            2001 => Ok(Self::AckError),
+            2002 => Ok(Self::AckData),
+            2003 => Ok(Self::AckRetry),
+            2004 => Ok(Self::AckRepeat),
+            2005 => Ok(Self::AckUnauth),
+            0xFFFF => Ok(Self::AckUnknown),
+            0xFFFD => Ok(Self::AckErrorCmd),
+            0xFFFC => Ok(Self::AckErrorInit),
+            0xFFFB => Ok(Self::AckErrorData),
+            _ => Err(Error::UnknownCommand(value)),
+        }
+    }
+}
+
+/// Parses a command code, a CMD_* name or a variant name
+///
+/// Names ignore case, `_` and `-`, so `CMD_GET_TIME`, `get-time` and
+/// `GetTime` are all [`Command::GetTime`].
+impl FromStr for Command {
+    type Err = Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(code) = s.trim().parse::<u16>() {
+            return Self::try_from(code);
+        }
+        
+        let wanted = normalize_name(s);
+        let wanted = wanted.strip_prefix("cmd").unwrap_or(&wanted);
+        Self::iter()
+            .find(|cmd| normalize_name(&cmd.name()[4..]) == wanted)
+            .ok_or_else(|| Error::UnknownName {
+                kind: "command",
+                name: s.to_string(),
+            })
+    }
+}
+
+/// Serialized as the canonical CMD_* name in human-readable formats and as
+/// the numeric code otherwise; both forms (and anything [`FromStr`]
+/// accepts) deserialize.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Command {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(self.name())
+        } else {
+            serializer.serialize_u16(*self as u16)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Command {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+        
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = Command;
+            
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a CMD_* name or command code")
+            }
+            
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> std::result::Result<Command, E> {
+                value.parse().map_err(E::custom)
+            }
+            
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> std::result::Result<Command, E> {
+                u16::try_from(value)
+                    .map_err(|_| E::custom(format!("command code out of range: {}", value)))
+                    .and_then(|code| Command::try_from(code).map_err(E::custom))
+            }
+        }
+        
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.name(), *self as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_command_conversion() {
+        assert_eq!(u16::from(Command::Connect), 1000);
+        assert_eq!(Command::try_from(1000).unwrap(), Command::Connect);
+    }
+    
+    #[test]
+    fn test_command_is_response() {
+        assert!(Command::AckOk.is_response());
+        assert!(!Command::Connect.is_response());
+    }
+    
+    #[test]
+    fn test_command_is_success() {
+        assert!(Command::AckOk.is_success());
+        assert!(Command::AckData.is_success());
+        assert!(!Command::AckError.is_success());
+    }
+    
+    #[test]
+    fn test_command_meta() {
+        let meta = Command::DeleteUser.meta();
+        assert_eq!(meta.direction, Direction::Request);
+        assert!(meta.writes);
+        assert!(meta.destructive);
+        
+        let meta = Command::SetTime.meta();
+        assert!(meta.writes);
+        assert!(!meta.destructive);
+        
+        let meta = Command::AttLogRrq.meta();
+        assert!(meta.bulk_data);
+        assert!(!meta.writes);
+        
+        assert_eq!(Command::Data.meta().direction, Direction::Both);
+        assert!(Command::AckOk.meta().expected_responses.is_empty());
+    }
+    
+    #[test]
+    fn test_meta_direction_matches_is_response() {
+        for code in 0..=u16::MAX {
+            if let Ok(cmd) = Command::try_from(code) {
+                if cmd.is_response() {
+                    assert_eq!(cmd.meta().direction, Direction::Response, "{}", cmd);
+                } else {
+                    assert_ne!(cmd.meta().direction, Direction::Response, "{}", cmd);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn test_accepts_response() {
+        assert!(Command::Connect.accepts_response(Command::AckUnauth));
+        assert!(Command::GetTime.accepts_response(Command::AckData));
+        assert!(!Command::EnableDevice.accepts_response(Command::AckData));
+    }
+    
+    #[test]
+    fn test_all_commands() {
+        let decoded = (0..=u16::MAX).filter_map(|code| Command::try_from(code).ok()).count();
+        assert_eq!(decoded, Command::ALL.len());
+        
+        let names: std::collections::HashSet<_> = Command::iter().map(Command::name).collect();
+        assert_eq!(names.len(), Command::ALL.len());
+        
+        for cmd in Command::iter() {
+            assert_eq!(Command::try_from(u16::from(cmd)).unwrap(), cmd);
+            assert_eq!(Command::from_name(cmd.name()), Some(cmd));
+        }
+    }
+        
+    #[test]
+    fn test_command_from_str() {
+        assert_eq!("CMD_GET_TIME".parse::<Command>().unwrap(), Command::GetTime);
+        assert_eq!("get-time".parse::<Command>().unwrap(), Command::GetTime);
+        assert_eq!("201".parse::<Command>().unwrap(), Command::GetTime);
+        assert_eq!("AckUnknown".parse::<Command>().unwrap(), Command::AckUnknown);
+        assert!("CMD_NOPE".parse::<Command>().is_err());
+        assert!("9999".parse::<Command>().is_err());
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_command_serde() {
+        assert_eq!(serde_json::to_string(&Command::AttLogRrq).unwrap(), "\"CMD_ATTLOG_RRQ\"");
+        assert_eq!(serde_json::from_str::<Command>("\"CMD_ATTLOG_RRQ\"").unwrap(), Command::AttLogRrq);
+        assert_eq!(serde_json::from_str::<Command>("13").unwrap(), Command::AttLogRrq);
+        assert!(serde_json::from_str::<Command>("70000").is_err());
+    }
+    
+    #[test]
+    fn test_unknown_command() {
+        let result = Command::try_from(9999);
+        assert!(result.is_err());
+    }
 }
\ No newline at end of file
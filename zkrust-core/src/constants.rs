@@ -1,88 +1,676 @@
-//! Protocol constants
-
-/// TCP magic header (for TCP-wrapped packets in some devices)
-pub const TCP_MAGIC_1: u16 = 0x5050;
-pub const TCP_MAGIC_2: u16 = 0x8272;
-
-/// Default connection timeout (seconds)
-pub const DEFAULT_TIMEOUT: u64 = 5;
-
-/// Default read timeout (seconds)
-pub const DEFAULT_READ_TIMEOUT: u64 = 5;
-
-/// Maximum retries for network operations
-pub const MAX_RETRIES: usize = 3;
-
-/// Real-time event flags
-pub mod events {
-    /// Attendance log event
-    pub const EF_ATTLOG: u32 = 1;
-    
-    /// Fingerprint pressed
-    pub const EF_FINGER: u32 = 1 << 1;
-    
-    /// User enrolled
-    pub const EF_ENROLLUSER: u32 = 1 << 2;
-    
-    /// Fingerprint enrolled
-    pub const EF_ENROLLFINGER: u32 = 1 << 3;
-    
-    /// Button pressed
-    pub const EF_BUTTON: u32 = 1 << 4;
-    
-    /// Door unlocked
-    pub const EF_UNLOCK: u32 = 1 << 5;
-    
-    /// Verification event
-    pub const EF_VERIFY: u32 = 1 << 7;
-    
-    /// Fingerprint minutiae captured
-    pub const EF_FPFTR: u32 = 1 << 8;
-    
-    /// Alarm signal
-    pub const EF_ALARM: u32 = 1 << 9;
-}
-
-/// Data type flags (for CMD_DB_RRQ, etc.)
-pub mod data_types {
-    /// Attendance log
-    pub const FCT_ATTLOG: u8 = 1;
-    
-    /// Fingerprint template
-    pub const FCT_FINGERTMP: u8 = 2;
-    
-    /// Operation log
-    pub const FCT_OPLOG: u8 = 4;
-    
-    /// User record
-    pub const FCT_USER: u8 = 5;
-    
-    /// SMS
-    pub const FCT_SMS: u8 = 6;
-    
-    /// User data
-    pub const FCT_UDATA: u8 = 7;
-    
-    /// Work code
-    pub const FCT_WORKCODE: u8 = 8;
-}
-
-/// Verification modes
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
-pub enum VerifyMode {
-    Password = 0,
-    Fingerprint = 1,
-    Card = 3,
-    Face = 15,
-}
-
-/// Punch types
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
-pub enum PunchType {
-    CheckIn = 0,
-    CheckOut = 1,
-    OvertimeIn = 2,
-    OvertimeOut = 3,
+//! Protocol constants
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+/// TCP magic header (for TCP-wrapped packets in some devices)
+pub const TCP_MAGIC_1: u16 = 0x5050;
+pub const TCP_MAGIC_2: u16 = 0x8272;
+
+/// Default connection timeout (seconds)
+pub const DEFAULT_TIMEOUT: u64 = 5;
+
+/// Default read timeout (seconds)
+pub const DEFAULT_READ_TIMEOUT: u64 = 5;
+
+/// Maximum retries for network operations
+pub const MAX_RETRIES: usize = 3;
+
+/// Real-time event flags
+pub mod events {
+    /// Attendance log event
+    pub const EF_ATTLOG: u32 = 1;
+    
+    /// Fingerprint pressed
+    pub const EF_FINGER: u32 = 1 << 1;
+    
+    /// User enrolled
+    pub const EF_ENROLLUSER: u32 = 1 << 2;
+    
+    /// Fingerprint enrolled
+    pub const EF_ENROLLFINGER: u32 = 1 << 3;
+    
+    /// Button pressed
+    pub const EF_BUTTON: u32 = 1 << 4;
+    
+    /// Door unlocked
+    pub const EF_UNLOCK: u32 = 1 << 5;
+    
+    /// Verification event
+    pub const EF_VERIFY: u32 = 1 << 7;
+    
+    /// Fingerprint minutiae captured
+    pub const EF_FPFTR: u32 = 1 << 8;
+    
+    /// Alarm signal
+    pub const EF_ALARM: u32 = 1 << 9;
+    
+    /// All events
+    pub const EF_ALL: u32 = EF_ATTLOG
+        | EF_FINGER
+        | EF_ENROLLUSER
+        | EF_ENROLLFINGER
+        | EF_BUTTON
+        | EF_UNLOCK
+        | EF_VERIFY
+        | EF_FPFTR
+        | EF_ALARM;
+}
+
+/// Data type flags (for CMD_DB_RRQ, etc.)
+pub mod data_types {
+    /// Attendance log
+    pub const FCT_ATTLOG: u8 = 1;
+    
+    /// Fingerprint template
+    pub const FCT_FINGERTMP: u8 = 2;
+    
+    /// Operation log
+    pub const FCT_OPLOG: u8 = 4;
+    
+    /// User record
+    pub const FCT_USER: u8 = 5;
+    
+    /// SMS
+    pub const FCT_SMS: u8 = 6;
+    
+    /// User data
+    pub const FCT_UDATA: u8 = 7;
+    
+    /// Work code
+    pub const FCT_WORKCODE: u8 = 8;
+}
+
+/// Convert a raw code into a catalog enum
+macro_rules! code_enum_try_from {
+    ($name:ident, $repr:ty, $kind:literal, [$($variant:ident),+ $(,)?]) => {
+        impl TryFrom<$repr> for $name {
+            type Error = Error;
+            
+            fn try_from(code: $repr) -> Result<Self> {
+                $(
+                    if code == Self::$variant as $repr {
+                        return Ok(Self::$variant);
+                    }
+                )+
+                Err(Error::UnknownCode {
+                    kind: $kind,
+                    code: code as u32,
+                })
+            }
+        }
+        
+        impl From<$name> for $repr {
+            fn from(value: $name) -> $repr {
+                value as $repr
+            }
+        }
+    };
+}
+
+/// Human-readable names for a catalog enum
+///
+/// `FromStr` accepts the display name, the variant name or the numeric code,
+/// ignoring case, whitespace, `-` and `_`.
+macro_rules! code_enum_names {
+    ($name:ident, $repr:ty, $kind:literal, [$($variant:ident => $display:literal),+ $(,)?]) => {
+        impl $name {
+            /// Human-readable name
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $display,)+
+                }
+            }
+        }
+        
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+        
+        impl FromStr for $name {
+            type Err = Error;
+            
+            fn from_str(s: &str) -> Result<Self> {
+                if let Ok(code) = s.trim().parse::<$repr>() {
+                    return Self::try_from(code);
+                }
+                
+                let wanted = normalize_name(s);
+                $(
+                    if wanted == normalize_name($display)
+                        || wanted == normalize_name(stringify!($variant))
+                    {
+                        return Ok(Self::$variant);
+                    }
+                )+
+                Err(Error::UnknownName {
+                    kind: $kind,
+                    name: s.to_string(),
+                })
+            }
+        }
+    };
+}
+
+/// Lowercase and drop separators for name comparisons
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Data types (for CMD_DB_RRQ, CMD_CLEAR_DATA, etc.)
+///
+/// Typed counterpart of the [`data_types`] constants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum DataType {
+    AttLog = data_types::FCT_ATTLOG,
+    FingerTemplate = data_types::FCT_FINGERTMP,
+    OpLog = data_types::FCT_OPLOG,
+    User = data_types::FCT_USER,
+    Sms = data_types::FCT_SMS,
+    UserData = data_types::FCT_UDATA,
+    WorkCode = data_types::FCT_WORKCODE,
+}
+
+code_enum_try_from!(DataType, u8, "data type", [
+    AttLog, FingerTemplate, OpLog, User, Sms, UserData, WorkCode,
+]);
+
+/// Verification modes
+///
+/// Values 5-14 and 16+ are combined modes reported by multi-modal readers
+/// (`Or` = any one factor, `And` = all factors). Newer firmware reports
+/// modes past the catalog as [`VerifyMode::Other`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VerifyMode {
+    Password,
+    Fingerprint,
+    /// User ID (PIN) only
+    Pin,
+    Card,
+    /// RF card on firmware that reports cards as 4
+    Rfid,
+    FingerprintOrPassword,
+    FingerprintOrCard,
+    PasswordOrCard,
+    PinAndFingerprint,
+    FingerprintAndPassword,
+    FingerprintAndCard,
+    PasswordAndCard,
+    FingerprintAndPasswordAndCard,
+    PinAndFingerprintAndPassword,
+    FingerprintAndCardAndPin,
+    Face,
+    FaceAndFingerprint,
+    FaceAndPassword,
+    FaceAndCard,
+    FaceAndFingerprintAndCard,
+    FaceAndFingerprintAndPassword,
+    FingerVein,
+    FingerVeinAndPassword,
+    FingerVeinAndCard,
+    FingerVeinAndPasswordAndCard,
+    Palm,
+    PalmAndCard,
+    PalmAndFace,
+    PalmAndFingerprint,
+    PalmAndFingerprintAndFace,
+    /// Mode this catalog doesn't know (codes 30-255)
+    Other(u8),
+}
+
+impl VerifyMode {
+    /// Known modes, in code order
+    pub const KNOWN: [Self; 30] = [
+        Self::Password,
+        Self::Fingerprint,
+        Self::Pin,
+        Self::Card,
+        Self::Rfid,
+        Self::FingerprintOrPassword,
+        Self::FingerprintOrCard,
+        Self::PasswordOrCard,
+        Self::PinAndFingerprint,
+        Self::FingerprintAndPassword,
+        Self::FingerprintAndCard,
+        Self::PasswordAndCard,
+        Self::FingerprintAndPasswordAndCard,
+        Self::PinAndFingerprintAndPassword,
+        Self::FingerprintAndCardAndPin,
+        Self::Face,
+        Self::FaceAndFingerprint,
+        Self::FaceAndPassword,
+        Self::FaceAndCard,
+        Self::FaceAndFingerprintAndCard,
+        Self::FaceAndFingerprintAndPassword,
+        Self::FingerVein,
+        Self::FingerVeinAndPassword,
+        Self::FingerVeinAndCard,
+        Self::FingerVeinAndPasswordAndCard,
+        Self::Palm,
+        Self::PalmAndCard,
+        Self::PalmAndFace,
+        Self::PalmAndFingerprint,
+        Self::PalmAndFingerprintAndFace,
+    ];
+    
+    /// Mode code as stored in attendance records
+    pub const fn code(self) -> u8 {
+        match self {
+            Self::Password => 0,
+            Self::Fingerprint => 1,
+            Self::Pin => 2,
+            Self::Card => 3,
+            Self::Rfid => 4,
+            Self::FingerprintOrPassword => 5,
+            Self::FingerprintOrCard => 6,
+            Self::PasswordOrCard => 7,
+            Self::PinAndFingerprint => 8,
+            Self::FingerprintAndPassword => 9,
+            Self::FingerprintAndCard => 10,
+            Self::PasswordAndCard => 11,
+            Self::FingerprintAndPasswordAndCard => 12,
+            Self::PinAndFingerprintAndPassword => 13,
+            Self::FingerprintAndCardAndPin => 14,
+            Self::Face => 15,
+            Self::FaceAndFingerprint => 16,
+            Self::FaceAndPassword => 17,
+            Self::FaceAndCard => 18,
+            Self::FaceAndFingerprintAndCard => 19,
+            Self::FaceAndFingerprintAndPassword => 20,
+            Self::FingerVein => 21,
+            Self::FingerVeinAndPassword => 22,
+            Self::FingerVeinAndCard => 23,
+            Self::FingerVeinAndPasswordAndCard => 24,
+            Self::Palm => 25,
+            Self::PalmAndCard => 26,
+            Self::PalmAndFace => 27,
+            Self::PalmAndFingerprint => 28,
+            Self::PalmAndFingerprintAndFace => 29,
+            Self::Other(code) => code,
+        }
+    }
+    
+    /// Human-readable name of a known mode; `"Other"` for the others
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Password => "Password",
+            Self::Fingerprint => "Fingerprint",
+            Self::Pin => "PIN",
+            Self::Card => "Card",
+            Self::Rfid => "RFID",
+            Self::FingerprintOrPassword => "Fingerprint/Password",
+            Self::FingerprintOrCard => "Fingerprint/Card",
+            Self::PasswordOrCard => "Password/Card",
+            Self::PinAndFingerprint => "PIN+Fingerprint",
+            Self::FingerprintAndPassword => "Fingerprint+Password",
+            Self::FingerprintAndCard => "Fingerprint+Card",
+            Self::PasswordAndCard => "Password+Card",
+            Self::FingerprintAndPasswordAndCard => "Fingerprint+Password+Card",
+            Self::PinAndFingerprintAndPassword => "PIN+Fingerprint+Password",
+            Self::FingerprintAndCardAndPin => "Fingerprint+Card+PIN",
+            Self::Face => "Face",
+            Self::FaceAndFingerprint => "Face+Fingerprint",
+            Self::FaceAndPassword => "Face+Password",
+            Self::FaceAndCard => "Face+Card",
+            Self::FaceAndFingerprintAndCard => "Face+Fingerprint+Card",
+            Self::FaceAndFingerprintAndPassword => "Face+Fingerprint+Password",
+            Self::FingerVein => "Finger vein",
+            Self::FingerVeinAndPassword => "Finger vein+Password",
+            Self::FingerVeinAndCard => "Finger vein+Card",
+            Self::FingerVeinAndPasswordAndCard => "Finger vein+Password+Card",
+            Self::Palm => "Palm",
+            Self::PalmAndCard => "Palm+Card",
+            Self::PalmAndFace => "Palm+Face",
+            Self::PalmAndFingerprint => "Palm+Fingerprint",
+            Self::PalmAndFingerprintAndFace => "Palm+Fingerprint+Face",
+            Self::Other(_) => "Other",
+        }
+    }
+}
+
+/// Every code is a verify mode; codes past the catalog are [`VerifyMode::Other`]
+impl TryFrom<u8> for VerifyMode {
+    type Error = Error;
+    
+    fn try_from(code: u8) -> Result<Self> {
+        Ok(Self::KNOWN.get(code as usize).copied().unwrap_or(Self::Other(code)))
+    }
+}
+
+impl From<VerifyMode> for u8 {
+    fn from(value: VerifyMode) -> u8 {
+        value.code()
+    }
+}
+
+impl fmt::Display for VerifyMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Other(code) => write!(f, "Mode-{}", code),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+/// Accepts the display name, the variant name, the numeric code or
+/// `Mode-<code>`, ignoring case, whitespace, `-` and `_`
+impl FromStr for VerifyMode {
+    type Err = Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        let wanted = normalize_name(s);
+        let code = wanted.strip_prefix("mode").unwrap_or(&wanted);
+        if let Ok(code) = code.parse::<u8>() {
+            return Self::try_from(code);
+        }
+        
+        Self::KNOWN
+            .into_iter()
+            .find(|mode| {
+                wanted == normalize_name(mode.name()) || wanted == normalize_name(&format!("{:?}", mode))
+            })
+            .ok_or_else(|| Error::UnknownName {
+                kind: "verify mode",
+                name: s.to_string(),
+            })
+    }
+}
+
+/// Punch types (attendance state)
+///
+/// Numbering follows the device's status keys: 0-1 check in/out,
+/// 2-3 break out/in, 4-5 overtime in/out. Firmware with a configurable
+/// state table reports further states as [`PunchType::Custom`]; their
+/// display names come from that table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PunchType {
+    CheckIn,
+    CheckOut,
+    BreakOut,
+    BreakIn,
+    OvertimeIn,
+    OvertimeOut,
+    /// Firmware-defined state (codes 6-255)
+    Custom(u8),
+}
+
+impl PunchType {
+    /// Standard states, in code order
+    pub const STANDARD: [Self; 6] = [
+        Self::CheckIn,
+        Self::CheckOut,
+        Self::BreakOut,
+        Self::BreakIn,
+        Self::OvertimeIn,
+        Self::OvertimeOut,
+    ];
+    
+    /// State code as stored in attendance records
+    pub const fn code(self) -> u8 {
+        match self {
+            Self::CheckIn => 0,
+            Self::CheckOut => 1,
+            Self::BreakOut => 2,
+            Self::BreakIn => 3,
+            Self::OvertimeIn => 4,
+            Self::OvertimeOut => 5,
+            Self::Custom(code) => code,
+        }
+    }
+    
+    /// Human-readable name of a standard state; `"Custom"` for the others
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::CheckIn => "Check-In",
+            Self::CheckOut => "Check-Out",
+            Self::BreakOut => "Break-Out",
+            Self::BreakIn => "Break-In",
+            Self::OvertimeIn => "Overtime-In",
+            Self::OvertimeOut => "Overtime-Out",
+            Self::Custom(_) => "Custom",
+        }
+    }
+    
+    /// Check if this is a firmware-defined state
+    pub const fn is_custom(self) -> bool {
+        matches!(self, Self::Custom(_))
+    }
+}
+
+/// Every code is a punch type; codes past the standard ones are custom
+impl TryFrom<u8> for PunchType {
+    type Error = Error;
+    
+    fn try_from(code: u8) -> Result<Self> {
+        Ok(Self::STANDARD.get(code as usize).copied().unwrap_or(Self::Custom(code)))
+    }
+}
+
+impl From<PunchType> for u8 {
+    fn from(value: PunchType) -> u8 {
+        value.code()
+    }
+}
+
+impl fmt::Display for PunchType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Custom(code) => write!(f, "State-{}", code),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+/// Accepts the display name, the variant name, the numeric code or
+/// `State-<code>`, ignoring case, whitespace, `-` and `_`
+impl FromStr for PunchType {
+    type Err = Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        let wanted = normalize_name(s);
+        let code = wanted.strip_prefix("state").unwrap_or(&wanted);
+        if let Ok(code) = code.parse::<u8>() {
+            return Self::try_from(code);
+        }
+        
+        Self::STANDARD
+            .into_iter()
+            .find(|punch| {
+                wanted == normalize_name(punch.name()) || wanted == normalize_name(&format!("{:?}", punch))
+            })
+            .ok_or_else(|| Error::UnknownName {
+                kind: "punch type",
+                name: s.to_string(),
+            })
+    }
+}
+
+/// User privilege levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum Privilege {
+    /// Normal user
+    User = 0,
+    /// Can enroll users
+    Enroller = 2,
+    /// Can manage users and settings
+    Manager = 6,
+    /// Super administrator
+    Admin = 14,
+}
+
+code_enum_try_from!(Privilege, u8, "privilege", [User, Enroller, Manager, Admin]);
+
+code_enum_names!(Privilege, u8, "privilege", [
+    User => "User",
+    Enroller => "Enroller",
+    Manager => "Manager",
+    Admin => "Admin",
+]);
+
+/// Output relays (CMD_UNLOCK and access control)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Relay {
+    /// Door lock
+    Lock = 1,
+    /// Alarm siren
+    Alarm = 2,
+}
+
+code_enum_try_from!(Relay, u8, "relay", [Lock, Alarm]);
+
+/// Alarm types reported with EF_ALARM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u16)]
+pub enum AlarmType {
+    Misoperation = 22,
+    DoorCloseDetected = 50,
+    DoorOpenDetected = 51,
+    ExitButton = 53,
+    DoorForcedOpen = 54,
+    Tamper = 55,
+    InvalidVerification = 58,
+    AlarmCancelled = 65535,
+}
+
+code_enum_try_from!(AlarmType, u16, "alarm type", [
+    Misoperation, DoorCloseDetected, DoorOpenDetected, ExitButton,
+    DoorForcedOpen, Tamper, InvalidVerification, AlarmCancelled,
+]);
+
+/// Machine status codes (counters and capacities)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum MachineStatus {
+    AdminCount = 1,
+    UserCount = 2,
+    FingerprintCount = 3,
+    PasswordCount = 4,
+    OpLogCount = 5,
+    AttLogCount = 6,
+    FingerprintCapacity = 7,
+    UserCapacity = 8,
+    AttLogCapacity = 9,
+    FingerprintRemaining = 10,
+    UserRemaining = 11,
+    AttLogRemaining = 12,
+    FaceCount = 21,
+    FaceCapacity = 22,
+}
+
+code_enum_try_from!(MachineStatus, u8, "machine status", [
+    AdminCount, UserCount, FingerprintCount, PasswordCount, OpLogCount, AttLogCount,
+    FingerprintCapacity, UserCapacity, AttLogCapacity,
+    FingerprintRemaining, UserRemaining, AttLogRemaining,
+    FaceCount, FaceCapacity,
+]);
+
+/// Bulk transfer speeds (CMD_CHANGE_SPEED)
+///
+/// Not every firmware supports high speed; rejected requests leave the
+/// device at normal speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(u32)]
+pub enum TransferSpeed {
+    /// Speed every device supports
+    #[default]
+    Normal = 0,
+    /// Larger data chunks
+    High = 1,
+}
+
+code_enum_try_from!(TransferSpeed, u32, "transfer speed", [Normal, High]);
+
+impl TransferSpeed {
+    /// CMD_DATA payload size to upload at this speed
+    pub const fn chunk_size(self) -> usize {
+        match self {
+            Self::Normal => 1024,
+            Self::High => 16 * 1024,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_verify_mode_roundtrip() {
+        for code in 0..=29u8 {
+            let mode = VerifyMode::try_from(code).unwrap();
+            assert_eq!(u8::from(mode), code);
+            assert_ne!(mode, VerifyMode::Other(code));
+        }
+        assert_eq!(VerifyMode::try_from(30).unwrap(), VerifyMode::Other(30));
+        assert_eq!(u8::from(VerifyMode::Other(30)), 30);
+    }
+    
+    #[test]
+    fn test_data_type_matches_constants() {
+        assert_eq!(u8::from(DataType::AttLog), data_types::FCT_ATTLOG);
+        assert_eq!(DataType::try_from(data_types::FCT_USER).unwrap(), DataType::User);
+        assert!(matches!(
+            DataType::try_from(3),
+            Err(Error::UnknownCode { kind: "data type", code: 3 })
+        ));
+    }
+    
+    #[test]
+    fn test_alarm_and_status_codes() {
+        assert_eq!(AlarmType::try_from(65535).unwrap(), AlarmType::AlarmCancelled);
+        assert_eq!(MachineStatus::try_from(22).unwrap(), MachineStatus::FaceCapacity);
+        assert_eq!(Privilege::try_from(14).unwrap(), Privilege::Admin);
+        assert!(Privilege::try_from(1).is_err());
+    }
+    
+    #[test]
+    fn test_names_roundtrip() {
+        for code in [0, 15, 29, 30, 255] {
+            let mode = VerifyMode::try_from(code).unwrap();
+            assert_eq!(mode.to_string().parse::<VerifyMode>().unwrap(), mode);
+        }
+        for code in [0, 1, 2, 3, 4, 5, 6, 200] {
+            let punch = PunchType::try_from(code).unwrap();
+            assert_eq!(punch.to_string().parse::<PunchType>().unwrap(), punch);
+            assert_eq!(u8::from(punch), code);
+        }
+        assert_eq!(Privilege::Admin.to_string(), "Admin");
+    }
+    
+    #[test]
+    fn test_parse_names() {
+        assert_eq!("check in".parse::<PunchType>().unwrap(), PunchType::CheckIn);
+        assert_eq!("OVERTIME_OUT".parse::<PunchType>().unwrap(), PunchType::OvertimeOut);
+        assert_eq!("state 9".parse::<PunchType>().unwrap(), PunchType::Custom(9));
+        assert!("lunch".parse::<PunchType>().is_err());
+        assert_eq!("fingerprint_or_card".parse::<VerifyMode>().unwrap(), VerifyMode::FingerprintOrCard);
+        assert_eq!("face + card".parse::<VerifyMode>().unwrap(), VerifyMode::FaceAndCard);
+        assert_eq!("mode 31".parse::<VerifyMode>().unwrap(), VerifyMode::Other(31));
+        assert!("retina".parse::<VerifyMode>().is_err());
+        assert_eq!("14".parse::<Privilege>().unwrap(), Privilege::Admin);
+        
+        assert!(matches!(
+            "superuser".parse::<Privilege>(),
+            Err(Error::UnknownName { kind: "privilege", .. })
+        ));
+        assert!(matches!("1".parse::<Privilege>(), Err(Error::UnknownCode { .. })));
+    }
+    
+    #[test]
+    fn test_event_mask() {
+        assert_eq!(events::EF_ALL, 0x3BF);
+    }
 }
\ No newline at end of file
@@ -1,96 +1,176 @@
-//! Error types for zkrust-core
-
-
-
-/// Result type alias for zkrust operations
-pub type Result<T> = std::result::Result<T, Error>;
-
-/// Core protocol errors
-#[derive(Debug, thiserror::Error)]
-pub enum Error {
-    /// Packet is too short to be valid
-    #[error("Packet too short: expected at least {expected} bytes, got {actual} bytes")]
-    PacketTooShort {
-        expected: usize,
-        actual: usize,
-    },
-    
-    /// Checksum verification failed
-    #[error("Checksum mismatch: expected 0x{expected:04X}, received 0x{received:04X}")]
-    ChecksumMismatch {
-        expected: u16,
-        received: u16,
-    },
-    
-    /// Unknown command code
-    #[error("Unknown command code: {0}")]
-    UnknownCommand(u16),
-    
-    /// Invalid session state
-    #[error("Invalid session state: {0}")]
-    InvalidSessionState(String),
-    
-    /// Session not initialized
-    #[error("Session not initialized - connect to device first")]
-    SessionNotInitialized,
-    
-    /// Device returned error response
-    #[error("Device returned error: {command}")]
-    DeviceError {
-        command: crate::command::Command,
-    },
-    
-    /// Authentication required
-    #[error("Authentication required - device has CommKey set")]
-    AuthenticationRequired,
-    
-    /// Authentication failed
-    #[error("Authentication failed - invalid password")]
-    AuthenticationFailed,
-    
-    /// Timeout waiting for response
-    #[error("Timeout waiting for response after {seconds}s")]
-    Timeout {
-        seconds: u64,
-    },
-    
-    /// Payload too large
-    #[error("Payload too large: {size} bytes (max: {max} bytes)")]
-    PayloadTooLarge {
-        size: usize,
-        max: usize,
-    },
-    
-    /// Invalid reply ID
-    #[error("Invalid reply ID: expected {expected}, got {actual}")]
-    InvalidReplyId {
-        expected: u16,
-        actual: u16,
-    },
-    
-    /// I/O error
-    #[error("I/O error: {0}")]
-    Io(#[from] std::io::Error),
-}
-
-impl Error {
-    /// Check if error is recoverable (retry might succeed)
-    pub fn is_recoverable(&self) -> bool {
-        matches!(
-            self,
-            Self::Timeout { .. } 
-                | Self::Io(_)
-                | Self::DeviceError { .. }
-        )
-    }
-    
-    /// Check if error requires reconnection
-    pub fn requires_reconnect(&self) -> bool {
-        matches!(
-            self,
-            Self::SessionNotInitialized
-                | Self::InvalidSessionState(_)
-                | Self::Io(_)
-        )
-    }
-}
\ No newline at end of file
+//! Error types for zkrust-core
+
+
+
+/// Result type alias for zkrust operations
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Core protocol errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Packet is too short to be valid
+    #[error("Packet too short: expected at least {expected} bytes, got {actual} bytes")]
+    PacketTooShort {
+        expected: usize,
+        actual: usize,
+    },
+    
+    /// Packet exceeds the protocol's maximum size
+    #[error("Packet too large: {size} bytes (max: {max} bytes)")]
+    PacketTooLarge {
+        size: usize,
+        max: usize,
+    },
+    
+    /// Checksum verification failed
+    #[error("Checksum mismatch: expected 0x{expected:04X}, received 0x{received:04X}")]
+    ChecksumMismatch {
+        expected: u16,
+        received: u16,
+    },
+    
+    /// Unknown command code
+    #[error("Unknown command code: {0}")]
+    UnknownCommand(u16),
+    
+    /// Code not found in a constants catalog
+    #[error("Unknown {kind} code: {code}")]
+    UnknownCode {
+        kind: &'static str,
+        code: u32,
+    },
+    
+    /// Name not found in a constants catalog
+    #[error("Unknown {kind}: {name:?}")]
+    UnknownName {
+        kind: &'static str,
+        name: String,
+    },
+    
+    /// Invalid session state
+    #[error("Invalid session state: {0}")]
+    InvalidSessionState(String),
+    
+    /// Session not initialized
+    #[error("Session not initialized - connect to device first")]
+    SessionNotInitialized,
+    
+    /// Device returned error response
+    #[error("Device returned error: {command}")]
+    DeviceError {
+        command: crate::command::Command,
+    },
+    
+    /// Authentication required
+    #[error("Authentication required - device has CommKey set")]
+    AuthenticationRequired,
+    
+    /// Authentication failed
+    #[error("Authentication failed - invalid password")]
+    AuthenticationFailed,
+    
+    /// Timeout waiting for response
+    #[error("Timeout waiting for response after {seconds}s")]
+    Timeout {
+        seconds: u64,
+    },
+    
+    /// Payload too large
+    #[error("Payload too large: {size} bytes (max: {max} bytes)")]
+    PayloadTooLarge {
+        size: usize,
+        max: usize,
+    },
+    
+    /// Invalid reply ID
+    #[error("Invalid reply ID: expected {expected}, got {actual}")]
+    InvalidReplyId {
+        expected: u16,
+        actual: u16,
+    },
+    
+    /// Response belongs to another session
+    #[error("Session mismatch: expected session {expected}, got {actual}")]
+    SessionMismatch {
+        expected: u16,
+        actual: u16,
+    },
+    
+    /// Request was cancelled before its response arrived
+    #[error("Request {command} (reply_id={reply_id}) cancelled before a response arrived")]
+    RequestCancelled {
+        command: crate::command::Command,
+        reply_id: u16,
+    },
+    
+    /// I/O error
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// How a caller should react to an error
+///
+/// The same classification is available on the core, transport and
+/// high-level error types, so retry loops can decide without matching
+/// variants from three crates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetryClass {
+    /// Transient; sending the command again may succeed
+    Retryable,
+    
+    /// The connection or session is broken; reconnect, then retry
+    Reconnect,
+    
+    /// Credentials are missing or wrong; retrying won't help until they change
+    Auth,
+    
+    /// Retrying cannot succeed (bad input, unsupported operation, ...)
+    Fatal,
+}
+
+impl RetryClass {
+    /// Check if the operation may succeed when tried again, possibly after
+    /// reconnecting
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Retryable | Self::Reconnect)
+    }
+    
+    /// Check if the connection has to be re-established first
+    pub fn needs_reconnect(self) -> bool {
+        self == Self::Reconnect
+    }
+}
+
+impl Error {
+    /// Classify the error for retry decisions
+    pub fn retry_class(&self) -> RetryClass {
+        match self {
+            Self::Timeout { .. }
+            | Self::DeviceError { .. }
+            | Self::PacketTooShort { .. }
+            | Self::ChecksumMismatch { .. }
+            | Self::InvalidReplyId { .. }
+            | Self::RequestCancelled { .. } => RetryClass::Retryable,
+            Self::SessionNotInitialized
+            | Self::InvalidSessionState(_)
+            | Self::SessionMismatch { .. }
+            | Self::Io(_) => RetryClass::Reconnect,
+            Self::AuthenticationRequired | Self::AuthenticationFailed => RetryClass::Auth,
+            Self::PacketTooLarge { .. }
+            | Self::PayloadTooLarge { .. }
+            | Self::UnknownCommand(_)
+            | Self::UnknownCode { .. }
+            | Self::UnknownName { .. } => RetryClass::Fatal,
+        }
+    }
+    
+    /// Check if error is recoverable (retry might succeed)
+    pub fn is_recoverable(&self) -> bool {
+        self.retry_class().is_retryable()
+    }
+    
+    /// Check if error requires reconnection
+    pub fn requires_reconnect(&self) -> bool {
+        self.retry_class().needs_reconnect()
+    }
+}
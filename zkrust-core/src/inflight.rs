@@ -0,0 +1,173 @@
+//! In-flight request tracking
+//!
+//! The device echoes the request's reply ID in its response, which lets several
+//! independent requests be outstanding at once (pipelining on TCP) and lets
+//! responses that arrive out of order be matched to the right caller.
+//!
+//! Each registered request yields a [`ResponseFuture`] that resolves once
+//! [`Session::complete`](crate::Session::complete) is called with the matching
+//! response packet. The future is runtime-agnostic (no tokio dependency).
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use crate::sync::Mutex;
+
+use crate::command::Command;
+use crate::error::{Error, Result};
+use crate::packet::Packet;
+
+/// Completion slot shared between the pending table and the waiting future
+#[derive(Debug, Default)]
+struct Slot {
+    result: Option<Result<Packet>>,
+    waker: Option<Waker>,
+}
+
+impl Slot {
+    fn fill(&mut self, result: Result<Packet>) {
+        self.result = Some(result);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    command: Command,
+    slot: Arc<Mutex<Slot>>,
+}
+
+/// Table of outstanding requests keyed by reply ID
+#[derive(Debug, Default)]
+pub(crate) struct PendingRequests {
+    entries: Mutex<HashMap<u16, Entry>>,
+}
+
+impl PendingRequests {
+    /// Register a request and return the future for its response
+    pub(crate) fn register(&self, command: Command, reply_id: u16) -> ResponseFuture {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+
+        let previous = self.entries.lock().insert(
+            reply_id,
+            Entry {
+                command,
+                slot: Arc::clone(&slot),
+            },
+        );
+
+        // Reply IDs wrapped around onto a request that never completed
+        if let Some(stale) = previous {
+            stale.slot.lock().fill(Err(Error::RequestCancelled {
+                command: stale.command,
+                reply_id,
+            }));
+        }
+
+        ResponseFuture {
+            command,
+            reply_id,
+            slot,
+        }
+    }
+
+    /// Deliver a response to the matching request
+    ///
+    /// Returns the packet back if no request is waiting for its reply ID.
+    pub(crate) fn complete(&self, packet: Packet) -> std::result::Result<(), Packet> {
+        match self.entries.lock().remove(&packet.reply_id) {
+            Some(entry) => {
+                entry.slot.lock().fill(Ok(packet));
+                Ok(())
+            }
+            None => Err(packet),
+        }
+    }
+
+    /// Cancel a single request
+    pub(crate) fn cancel(&self, reply_id: u16) -> bool {
+        match self.entries.lock().remove(&reply_id) {
+            Some(entry) => {
+                entry.slot.lock().fill(Err(Error::RequestCancelled {
+                    command: entry.command,
+                    reply_id,
+                }));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancel every outstanding request
+    pub(crate) fn cancel_all(&self) {
+        for (reply_id, entry) in self.entries.lock().drain() {
+            entry.slot.lock().fill(Err(Error::RequestCancelled {
+                command: entry.command,
+                reply_id,
+            }));
+        }
+    }
+
+    /// Check if a reply ID is outstanding
+    pub(crate) fn contains(&self, reply_id: u16) -> bool {
+        self.entries.lock().contains_key(&reply_id)
+    }
+
+    /// Number of outstanding requests
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+}
+
+/// Future resolving to the response of an in-flight request
+///
+/// Created by [`Session::register_request`](crate::Session::register_request).
+#[derive(Debug)]
+pub struct ResponseFuture {
+    command: Command,
+    reply_id: u16,
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl ResponseFuture {
+    /// Command of the request
+    pub fn command(&self) -> Command {
+        self.command
+    }
+
+    /// Reply ID the response is expected to carry
+    pub fn reply_id(&self) -> u16 {
+        self.reply_id
+    }
+
+    /// Check if the response has arrived without waiting
+    pub fn is_complete(&self) -> bool {
+        self.slot.lock().result.is_some()
+    }
+
+    /// Take the response if it has arrived
+    pub fn try_take(&mut self) -> Option<Result<Packet>> {
+        self.slot.lock().result.take()
+    }
+}
+
+impl Future for ResponseFuture {
+    type Output = Result<Packet>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock();
+
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -8,20 +8,48 @@
 //! - Command definitions
 //! - Protocol constants
 //! - Authentication
+//!
+//! It has no networking or async runtime dependencies and builds for
+//! `wasm32-unknown-unknown`; the `wasm` feature adds JavaScript bindings.
+//!
+//! ## Features
+//!
+//! | Feature       | Default | Enables                                         |
+//! |---------------|---------|-------------------------------------------------|
+//! | `tracing`     | yes     | Log events through `tracing`                    |
+//! | `parking_lot` | yes     | `parking_lot` session locks (else `std::sync`)  |
+//! | `serde`       | no      | `Serialize`/`Deserialize` for protocol enums    |
+//! | `wasm`        | no      | JavaScript bindings, see `wasm` module          |
+//!
+//! `default-features = false` builds a client with only `bytes`,
+//! `byteorder` and `thiserror`.
 
 pub mod auth;
 pub mod checksum;
 pub mod command;
 pub mod constants;
 pub mod error;
+pub mod inflight;
+mod log;
 pub mod packet;
+pub mod protocol;
 pub mod session;
+pub mod stats;
+mod sync;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(test)]
+mod vectors;
 
 pub use auth::make_commkey;
-pub use command::Command;
-pub use error::{Error, Result};
+pub use command::{Command, CommandMeta};
+pub use error::{Error, Result, RetryClass};
+pub use inflight::ResponseFuture;
 pub use packet::Packet;
+pub use protocol::ProtocolStrictness;
 pub use session::Session;
+pub use stats::SessionStats;
 
 /// Protocol version information
 pub const PROTOCOL_VERSION: &str = "1.0";
@@ -0,0 +1,15 @@
+//! Logging macros
+//!
+//! Forward to `tracing` with the `tracing` feature (the default) and expand
+//! to nothing without it, so minimal builds drop the dependency.
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::trace;
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! disabled {
+    ($($arg:tt)*) => {};
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use disabled as trace;
@@ -0,0 +1,29 @@
+//! Protocol compliance
+//!
+//! Every supported device speaks the packet format of
+//! [`Packet`](crate::packet::Packet), but not always to the letter;
+//! [`ProtocolStrictness`] decides how much deviation is tolerated.
+
+/// How strictly received packets are held to the protocol
+///
+/// Real firmwares deviate from the protocol in different ways: some get
+/// checksums wrong, some answer with a stale reply ID, some send command
+/// codes nobody documented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProtocolStrictness {
+    /// Fail on a bad checksum, an unexpected reply ID or an unknown command
+    #[default]
+    Strict,
+
+    /// Log deviations and carry on: packets with a bad checksum or reply ID
+    /// are accepted, packets with an unknown command are skipped
+    Lenient,
+}
+
+impl ProtocolStrictness {
+    /// Check if deviations are tolerated
+    pub fn is_lenient(self) -> bool {
+        self == Self::Lenient
+    }
+}
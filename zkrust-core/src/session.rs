@@ -1,248 +1,652 @@
-//! Session management for ZKTeco protocol
-//!
-//! A session represents a connection to a device and tracks:
-//! - Session ID (assigned by device)
-//! - Reply counter (increments per command)
-//! - Authentication state
-
-use std::sync::atomic::{AtomicU16, Ordering};
-use std::sync::Arc;
-
-use crate::error::{Error, Result};
-
-/// Session state
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum SessionState {
-    /// Not connected
-    Disconnected,
-    
-    /// Connected but not authenticated
-    Connected,
-    
-    /// Authenticated and ready for commands
-    Authenticated,
-}
-
-/// Session manager
-///
-/// Manages session state and reply ID generation.
-/// Thread-safe and can be cloned cheaply (Arc internally).
-#[derive(Debug, Clone)]
-pub struct Session {
-    inner: Arc<SessionInner>,
-}
-
-#[derive(Debug)]
-struct SessionInner {
-    /// Session ID assigned by device (0 when not connected)
-    session_id: AtomicU16,
-    
-    /// Reply counter (starts at USHRT_MAX - 1 = 65534)
-    reply_counter: AtomicU16,
-    
-    /// Current session state
-    state: parking_lot::RwLock<SessionState>,
-}
-
-impl Session {
-    /// Initial reply ID (from protocol manual: USHRT_MAX - 1)
-    pub const INITIAL_REPLY_ID: u16 = 65534;
-    
-    /// Create a new disconnected session
-    pub fn new() -> Self {
-        Self {
-            inner: Arc::new(SessionInner {
-                session_id: AtomicU16::new(0),
-                reply_counter: AtomicU16::new(Self::INITIAL_REPLY_ID),
-                state: parking_lot::RwLock::new(SessionState::Disconnected),
-            }),
-        }
-    }
-    
-    /// Get current session ID
-    pub fn session_id(&self) -> u16 {
-        self.inner.session_id.load(Ordering::Acquire)
-    }
-    
-    /// Get current state
-    pub fn state(&self) -> SessionState {
-        *self.inner.state.read()
-    }
-    
-    /// Check if connected
-    pub fn is_connected(&self) -> bool {
-        !matches!(self.state(), SessionState::Disconnected)
-    }
-    
-    /// Check if authenticated
-    pub fn is_authenticated(&self) -> bool {
-        matches!(self.state(), SessionState::Authenticated)
-    }
-    
-    /// Initialize session with device-assigned session ID
-    pub fn initialize(&self, session_id: u16) -> Result<()> {
-        let mut state = self.inner.state.write();
-        
-        if *state != SessionState::Disconnected {
-            return Err(Error::InvalidSessionState(
-                format!("Cannot initialize from state: {:?}", *state)
-            ));
-        }
-        
-        self.inner.session_id.store(session_id, Ordering::Release);
-        self.inner.reply_counter.store(Self::INITIAL_REPLY_ID, Ordering::Release);
-        *state = SessionState::Connected;
-        
-        Ok(())
-    }
-    
-    /// Mark session as authenticated
-    pub fn authenticate(&self) -> Result<()> {
-        let mut state = self.inner.state.write();
-        
-        if *state != SessionState::Connected {
-            return Err(Error::InvalidSessionState(
-                format!("Cannot authenticate from state: {:?}", *state)
-            ));
-        }
-        
-        *state = SessionState::Authenticated;
-        Ok(())
-    }
-    
-    /// Close session
-    pub fn close(&self) {
-        self.inner.session_id.store(0, Ordering::Release);
-        self.inner.reply_counter.store(Self::INITIAL_REPLY_ID, Ordering::Release);
-        *self.inner.state.write() = SessionState::Disconnected;
-    }
-    
-    /// Get next reply ID
-    ///
-    /// Reply ID starts at 65534 and increments per command.
-    /// Wraps around after reaching 65535.
-    pub fn next_reply_id(&self) -> u16 {
-        let current = self.inner.reply_counter.fetch_add(1, Ordering::AcqRel);
-        
-        // Wrap around if we hit max
-        if current >= 65535 {
-            self.inner.reply_counter.store(0, Ordering::Release);
-        }
-        
-        current
-    }
-    
-    /// Reset reply counter (used in testing)
-    #[cfg(test)]
-    pub fn reset_reply_counter(&self) {
-        self.inner.reply_counter.store(Self::INITIAL_REPLY_ID, Ordering::Release);
-    }
-}
-
-impl Default for Session {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
-    #[test]
-    fn test_session_new() {
-        let session = Session::new();
-        assert_eq!(session.session_id(), 0);
-        assert_eq!(session.state(), SessionState::Disconnected);
-        assert!(!session.is_connected());
-        assert!(!session.is_authenticated());
-    }
-    
-    #[test]
-    fn test_session_initialize() {
-        let session = Session::new();
-        session.initialize(1234).unwrap();
-        
-        assert_eq!(session.session_id(), 1234);
-        assert_eq!(session.state(), SessionState::Connected);
-        assert!(session.is_connected());
-        assert!(!session.is_authenticated());
-    }
-    
-    #[test]
-    fn test_session_authenticate() {
-        let session = Session::new();
-        session.initialize(1234).unwrap();
-        session.authenticate().unwrap();
-        
-        assert_eq!(session.state(), SessionState::Authenticated);
-        assert!(session.is_authenticated());
-    }
-    
-    #[test]
-    fn test_session_close() {
-        let session = Session::new();
-        session.initialize(1234).unwrap();
-        session.authenticate().unwrap();
-        
-        session.close();
-        
-        assert_eq!(session.session_id(), 0);
-        assert_eq!(session.state(), SessionState::Disconnected);
-    }
-    
-    #[test]
-    fn test_reply_id_generation() {
-        let session = Session::new();
-        session.initialize(100).unwrap();
-        
-        let id1 = session.next_reply_id();
-        let id2 = session.next_reply_id();
-        let id3 = session.next_reply_id();
-        
-        assert_eq!(id1, 65534);
-        assert_eq!(id2, 65535);
-        assert_eq!(id3, 0); // Wrapped
-    }
-    
-    #[test]
-    fn test_reply_id_wrap() {
-        let session = Session::new();
-        session.initialize(100).unwrap();
-        
-        // Generate many IDs to test wrapping
-        for _ in 0..70000 {
-            session.next_reply_id();
-        }
-        
-        // Should have wrapped multiple times
-        let id = session.next_reply_id();
-        assert!(id < 10000); // Wrapped back to low values
-    }
-    
-    #[test]
-    fn test_invalid_state_transitions() {
-        let session = Session::new();
-        
-        // Cannot authenticate without connecting
-        assert!(session.authenticate().is_err());
-        
-        // Cannot initialize twice
-        session.initialize(100).unwrap();
-        assert!(session.initialize(200).is_err());
-    }
-    
-    #[test]
-    fn test_session_clone() {
-        let session1 = Session::new();
-        session1.initialize(1234).unwrap();
-        
-        let session2 = session1.clone();
-        
-        // Both share same state
-        assert_eq!(session2.session_id(), 1234);
-        
-        session1.authenticate().unwrap();
-        assert!(session2.is_authenticated());
-    }
+//! Session management for ZKTeco protocol
+//!
+//! A session represents a connection to a device and tracks:
+//! - Session ID (assigned by device)
+//! - Reply counter (increments per command)
+//! - Authentication state
+//! - Protocol version (packet format)
+//! - In-flight requests awaiting a response
+//! - Reply IDs already answered, to spot duplicate responses
+//! - Traffic statistics
+//! - State-change observers
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU16, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::command::Command;
+use crate::error::{Error, Result};
+use crate::inflight::{PendingRequests, ResponseFuture};
+use crate::packet::Packet;
+use crate::stats::{SessionCounters, SessionStats};
+use crate::sync::{Mutex, RwLock};
+
+/// Session state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    /// Not connected
+    Disconnected,
+    
+    /// Connected but not authenticated
+    Connected,
+    
+    /// Authenticated and ready for commands
+    Authenticated,
+}
+
+/// A session state transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateChange {
+    /// State before the transition
+    pub from: SessionState,
+    
+    /// State after the transition
+    pub to: SessionState,
+}
+
+/// Handle identifying a registered state observer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+type StateObserver = Arc<dyn Fn(StateChange) + Send + Sync>;
+
+/// Session manager
+///
+/// Manages session state and reply ID generation.
+/// Thread-safe and can be cloned cheaply (Arc internally).
+#[derive(Debug, Clone)]
+pub struct Session {
+    inner: Arc<SessionInner>,
+}
+
+struct SessionInner {
+    /// Session ID assigned by device (0 when not connected)
+    session_id: AtomicU16,
+    
+    /// Reply counter (starts at USHRT_MAX - 1 = 65534)
+    reply_counter: AtomicU16,
+    
+    /// Current session state
+    state: RwLock<SessionState>,
+    
+    /// Requests awaiting a response, keyed by reply ID
+    pending: PendingRequests,
+    
+    /// Reply IDs of finished exchanges, oldest first
+    handled: Mutex<VecDeque<u16>>,
+    
+    /// Traffic counters (kept across reconnects)
+    counters: SessionCounters,
+    
+    /// State-change observers
+    observers: Mutex<Vec<(ObserverId, StateObserver)>>,
+    
+    /// Next observer ID
+    next_observer_id: AtomicU64,
+}
+
+impl std::fmt::Debug for SessionInner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionInner")
+            .field("session_id", &self.session_id)
+            .field("reply_counter", &self.reply_counter)
+            .field("state", &self.state)
+            .field("pending", &self.pending)
+            .field("handled", &self.handled.lock().len())
+            .field("counters", &self.counters)
+            .field("observers", &self.observers.lock().len())
+            .finish()
+    }
+}
+
+impl Session {
+    /// Initial reply ID (from protocol manual: USHRT_MAX - 1)
+    pub const INITIAL_REPLY_ID: u16 = 65534;
+    
+    /// Finished reply IDs remembered for duplicate detection
+    pub const HANDLED_HISTORY: usize = 32;
+    
+    /// Create a new disconnected session
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(SessionInner {
+                session_id: AtomicU16::new(0),
+                reply_counter: AtomicU16::new(Self::INITIAL_REPLY_ID),
+                state: RwLock::new(SessionState::Disconnected),
+                pending: PendingRequests::default(),
+                handled: Mutex::new(VecDeque::with_capacity(Self::HANDLED_HISTORY)),
+                counters: SessionCounters::default(),
+                observers: Mutex::new(Vec::new()),
+                next_observer_id: AtomicU64::new(0),
+            }),
+        }
+    }
+    
+    /// Get current session ID
+    pub fn session_id(&self) -> u16 {
+        self.inner.session_id.load(Ordering::Acquire)
+    }
+    
+    /// Get current state
+    pub fn state(&self) -> SessionState {
+        *self.inner.state.read()
+    }
+    
+    /// Check if connected
+    pub fn is_connected(&self) -> bool {
+        !matches!(self.state(), SessionState::Disconnected)
+    }
+    
+    /// Check if authenticated
+    pub fn is_authenticated(&self) -> bool {
+        matches!(self.state(), SessionState::Authenticated)
+    }
+    
+    /// Initialize session with device-assigned session ID
+    pub fn initialize(&self, session_id: u16) -> Result<()> {
+        let mut state = self.inner.state.write();
+        
+        if *state != SessionState::Disconnected {
+            return Err(Error::InvalidSessionState(
+                format!("Cannot initialize from state: {:?}", *state)
+            ));
+        }
+        
+        self.inner.session_id.store(session_id, Ordering::Release);
+        self.inner.reply_counter.store(Self::INITIAL_REPLY_ID, Ordering::Release);
+        *state = SessionState::Connected;
+        drop(state);
+        
+        self.notify(SessionState::Disconnected, SessionState::Connected);
+        Ok(())
+    }
+    
+    /// Mark session as authenticated
+    pub fn authenticate(&self) -> Result<()> {
+        let mut state = self.inner.state.write();
+        
+        if *state != SessionState::Connected {
+            return Err(Error::InvalidSessionState(
+                format!("Cannot authenticate from state: {:?}", *state)
+            ));
+        }
+        
+        *state = SessionState::Authenticated;
+        drop(state);
+        
+        self.notify(SessionState::Connected, SessionState::Authenticated);
+        Ok(())
+    }
+    
+    /// Close session
+    ///
+    /// Any in-flight requests are resolved with [`Error::RequestCancelled`].
+    pub fn close(&self) {
+        self.inner.pending.cancel_all();
+        self.inner.handled.lock().clear();
+        self.inner.session_id.store(0, Ordering::Release);
+        self.inner.reply_counter.store(Self::INITIAL_REPLY_ID, Ordering::Release);
+        
+        let previous = std::mem::replace(&mut *self.inner.state.write(), SessionState::Disconnected);
+        if previous != SessionState::Disconnected {
+            self.notify(previous, SessionState::Disconnected);
+        }
+    }
+    
+    /// Register a callback fired on every state transition
+    ///
+    /// Callbacks run synchronously on the thread performing the transition,
+    /// after the state has been updated, so they should be quick (e.g. send
+    /// on a channel). Closing an already disconnected session does not fire.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zkrust_core::session::{Session, SessionState};
+    ///
+    /// let session = Session::new();
+    /// session.on_state_change(|change| {
+    ///     if change.to == SessionState::Disconnected {
+    ///         println!("device went away");
+    ///     }
+    /// });
+    /// ```
+    pub fn on_state_change<F>(&self, callback: F) -> ObserverId
+    where
+        F: Fn(StateChange) + Send + Sync + 'static,
+    {
+        let id = ObserverId(self.inner.next_observer_id.fetch_add(1, Ordering::Relaxed));
+        self.inner.observers.lock().push((id, Arc::new(callback)));
+        id
+    }
+    
+    /// Remove a previously registered state callback
+    ///
+    /// Returns `false` if the observer was not registered.
+    pub fn remove_observer(&self, id: ObserverId) -> bool {
+        let mut observers = self.inner.observers.lock();
+        let before = observers.len();
+        observers.retain(|(observer_id, _)| *observer_id != id);
+        observers.len() != before
+    }
+    
+    fn notify(&self, from: SessionState, to: SessionState) {
+        // Clone the list so callbacks may (un)register observers
+        let observers: Vec<StateObserver> = self
+            .inner
+            .observers
+            .lock()
+            .iter()
+            .map(|(_, observer)| Arc::clone(observer))
+            .collect();
+        
+        let change = StateChange { from, to };
+        for observer in observers {
+            observer(change);
+        }
+    }
+    
+    /// Get next reply ID
+    ///
+    /// Reply ID starts at 65534 and increments per command.
+    /// Wraps around after reaching 65535.
+    pub fn next_reply_id(&self) -> u16 {
+        let current = self.inner.reply_counter.fetch_add(1, Ordering::AcqRel);
+        
+        // Wrap around if we hit max
+        if current == u16::MAX {
+            self.inner.reply_counter.store(0, Ordering::Release);
+        }
+        
+        // A reused ID starts a new exchange (0 is also CMD_CONNECT's)
+        self.inner.handled.lock().retain(|&id| id != current);
+        
+        current
+    }
+    
+    /// Mark the exchange with `reply_id` as finished
+    ///
+    /// Responses still arriving for it afterwards are duplicates (UDP may
+    /// deliver a datagram twice, or late after a retransmission); see
+    /// [`Session::was_handled`]. The last [`Session::HANDLED_HISTORY`] IDs
+    /// are kept.
+    pub fn mark_handled(&self, reply_id: u16) {
+        let mut handled = self.inner.handled.lock();
+        if handled.back() == Some(&reply_id) {
+            return;
+        }
+        if handled.len() == Self::HANDLED_HISTORY {
+            handled.pop_front();
+        }
+        handled.push_back(reply_id);
+    }
+    
+    /// Check if the exchange with `reply_id` already finished
+    pub fn was_handled(&self, reply_id: u16) -> bool {
+        self.inner.handled.lock().contains(&reply_id)
+    }
+    
+    /// Register an in-flight request
+    ///
+    /// Allocates the next reply ID and returns a future that resolves when
+    /// the matching response is passed to [`Session::complete`]. Several
+    /// requests may be outstanding at once; responses are matched by reply ID
+    /// regardless of arrival order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SessionNotInitialized`] if the session is disconnected.
+    pub fn register_request(&self, command: Command) -> Result<ResponseFuture> {
+        if !self.is_connected() {
+            return Err(Error::SessionNotInitialized);
+        }
+        
+        let reply_id = self.next_reply_id();
+        Ok(self.inner.pending.register(command, reply_id))
+    }
+    
+    /// Deliver a response packet to the request waiting for its reply ID
+    ///
+    /// Returns the packet back if no request is outstanding for it
+    /// (e.g. a duplicate or a stray reply from a previous session).
+    pub fn complete(&self, packet: Packet) -> std::result::Result<(), Packet> {
+        self.inner.pending.complete(packet)
+    }
+    
+    /// Cancel an in-flight request
+    ///
+    /// Returns `false` if no request was outstanding for `reply_id`.
+    pub fn cancel_request(&self, reply_id: u16) -> bool {
+        self.inner.pending.cancel(reply_id)
+    }
+    
+    /// Check if a response is still expected for `reply_id`
+    pub fn is_pending(&self, reply_id: u16) -> bool {
+        self.inner.pending.contains(reply_id)
+    }
+    
+    /// Number of requests awaiting a response
+    pub fn pending_count(&self) -> usize {
+        self.inner.pending.len()
+    }
+    
+    /// Record a packet of `bytes` sent to the device
+    pub fn record_sent(&self, bytes: usize) {
+        self.inner.counters.record_sent(bytes);
+    }
+    
+    /// Record a packet of `bytes` received from the device
+    pub fn record_received(&self, bytes: usize) {
+        self.inner.counters.record_received(bytes);
+    }
+    
+    /// Record a command retransmission
+    pub fn record_retry(&self) {
+        self.inner.counters.record_retry();
+    }
+    
+    /// Record a received packet that failed checksum verification
+    pub fn record_checksum_failure(&self) {
+        self.inner.counters.record_checksum_failure();
+    }
+    
+    /// Record a received packet discarded for belonging to another session
+    pub fn record_stray(&self) {
+        self.inner.counters.record_stray();
+    }
+    
+    /// Record a duplicate response to a finished exchange
+    pub fn record_duplicate(&self) {
+        self.inner.counters.record_duplicate();
+    }
+    
+    /// Get a snapshot of the session counters
+    ///
+    /// Counters are kept across reconnects; use [`Session::reset_stats`]
+    /// to start over.
+    pub fn stats(&self) -> SessionStats {
+        self.inner.counters.snapshot()
+    }
+    
+    /// Reset all session counters to zero
+    pub fn reset_stats(&self) {
+        self.inner.counters.reset();
+    }
+    
+    /// Reset reply counter (used in testing)
+    #[cfg(test)]
+    pub fn reset_reply_counter(&self) {
+        self.inner.reply_counter.store(Self::INITIAL_REPLY_ID, Ordering::Release);
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_session_new() {
+        let session = Session::new();
+        assert_eq!(session.session_id(), 0);
+        assert_eq!(session.state(), SessionState::Disconnected);
+        assert!(!session.is_connected());
+        assert!(!session.is_authenticated());
+    }
+    
+    #[test]
+    fn test_session_initialize() {
+        let session = Session::new();
+        session.initialize(1234).unwrap();
+        
+        assert_eq!(session.session_id(), 1234);
+        assert_eq!(session.state(), SessionState::Connected);
+        assert!(session.is_connected());
+        assert!(!session.is_authenticated());
+    }
+    
+    #[test]
+    fn test_session_authenticate() {
+        let session = Session::new();
+        session.initialize(1234).unwrap();
+        session.authenticate().unwrap();
+        
+        assert_eq!(session.state(), SessionState::Authenticated);
+        assert!(session.is_authenticated());
+    }
+    
+    #[test]
+    fn test_session_close() {
+        let session = Session::new();
+        session.initialize(1234).unwrap();
+        session.authenticate().unwrap();
+        
+        session.close();
+        
+        assert_eq!(session.session_id(), 0);
+        assert_eq!(session.state(), SessionState::Disconnected);
+    }
+    
+    #[test]
+    fn test_reply_id_generation() {
+        let session = Session::new();
+        session.initialize(100).unwrap();
+        
+        let id1 = session.next_reply_id();
+        let id2 = session.next_reply_id();
+        let id3 = session.next_reply_id();
+        
+        assert_eq!(id1, 65534);
+        assert_eq!(id2, 65535);
+        assert_eq!(id3, 0); // Wrapped
+    }
+    
+    #[test]
+    fn test_reply_id_wrap() {
+        let session = Session::new();
+        session.initialize(100).unwrap();
+        
+        // Generate many IDs to test wrapping
+        for _ in 0..70000 {
+            session.next_reply_id();
+        }
+        
+        // Should have wrapped multiple times
+        let id = session.next_reply_id();
+        assert!(id < 10000); // Wrapped back to low values
+    }
+    
+    #[test]
+    fn test_invalid_state_transitions() {
+        let session = Session::new();
+        
+        // Cannot authenticate without connecting
+        assert!(session.authenticate().is_err());
+        
+        // Cannot initialize twice
+        session.initialize(100).unwrap();
+        assert!(session.initialize(200).is_err());
+    }
+    
+    #[test]
+    fn test_session_clone() {
+        let session1 = Session::new();
+        session1.initialize(1234).unwrap();
+        
+        let session2 = session1.clone();
+        
+        // Both share same state
+        assert_eq!(session2.session_id(), 1234);
+        
+        session1.authenticate().unwrap();
+        assert!(session2.is_authenticated());
+    }
+    
+    #[test]
+    fn test_handled_reply_ids() {
+        let session = Session::new();
+        session.initialize(1).unwrap();
+        
+        session.mark_handled(65534);
+        session.mark_handled(65534);
+        assert!(session.was_handled(65534));
+        assert!(!session.was_handled(65535));
+        
+        // Only the most recent IDs are remembered
+        for reply_id in 0..Session::HANDLED_HISTORY as u16 {
+            session.mark_handled(reply_id);
+        }
+        assert!(!session.was_handled(65534));
+        assert!(session.was_handled(0));
+        
+        // Until the ID comes round again
+        session.mark_handled(65534);
+        session.reset_reply_counter();
+        session.next_reply_id();
+        assert!(!session.was_handled(65534));
+        
+        session.close();
+        assert!(!session.was_handled(0));
+    }
+    
+    #[test]
+    fn test_session_stats() {
+        let session = Session::new();
+        assert_eq!(session.stats(), SessionStats::default());
+        
+        session.record_sent(16);
+        session.record_sent(8);
+        session.record_received(24);
+        session.record_retry();
+        session.record_checksum_failure();
+        session.record_stray();
+        session.record_duplicate();
+        
+        let stats = session.stats();
+        assert_eq!(stats.commands_sent, 2);
+        assert_eq!(stats.bytes_out, 24);
+        assert_eq!(stats.responses_received, 1);
+        assert_eq!(stats.bytes_in, 24);
+        assert_eq!(stats.retries, 1);
+        assert_eq!(stats.checksum_failures, 1);
+        assert_eq!(stats.stray_packets, 1);
+        assert_eq!(stats.duplicate_packets, 1);
+        assert!(stats.last_activity.is_some());
+        
+        // Stats survive close, cleared only on reset
+        session.close();
+        assert_eq!(session.stats().commands_sent, 2);
+        session.reset_stats();
+        assert_eq!(session.stats(), SessionStats::default());
+    }
+    
+    #[test]
+    fn test_state_change_callbacks() {
+        let session = Session::new();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        
+        let sink = Arc::clone(&changes);
+        let id = session.on_state_change(move |change| sink.lock().push(change));
+        
+        session.initialize(1).unwrap();
+        session.authenticate().unwrap();
+        session.close();
+        session.close(); // No-op, already disconnected
+        
+        assert_eq!(
+            *changes.lock(),
+            vec![
+                StateChange { from: SessionState::Disconnected, to: SessionState::Connected },
+                StateChange { from: SessionState::Connected, to: SessionState::Authenticated },
+                StateChange { from: SessionState::Authenticated, to: SessionState::Disconnected },
+            ]
+        );
+        
+        assert!(session.remove_observer(id));
+        assert!(!session.remove_observer(id));
+        session.initialize(2).unwrap();
+        assert_eq!(changes.lock().len(), 3);
+    }
+    
+    fn poll_once(future: &mut ResponseFuture) -> std::task::Poll<Result<Packet>> {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::{Context, Waker};
+        
+        let mut cx = Context::from_waker(Waker::noop());
+        Pin::new(future).poll(&mut cx)
+    }
+    
+    #[test]
+    fn test_register_requires_connection() {
+        let session = Session::new();
+        assert!(matches!(
+            session.register_request(Command::GetVersion),
+            Err(Error::SessionNotInitialized)
+        ));
+    }
+    
+    #[test]
+    fn test_pipelined_out_of_order_completion() {
+        let session = Session::new();
+        session.initialize(100).unwrap();
+        
+        let mut first = session.register_request(Command::GetVersion).unwrap();
+        let mut second = session.register_request(Command::GetTime).unwrap();
+        assert_eq!(session.pending_count(), 2);
+        assert!(poll_once(&mut first).is_pending());
+        
+        // Second response arrives first
+        let reply = Packet::new(Command::AckOk, 100, second.reply_id());
+        session.complete(reply).unwrap();
+        
+        assert!(!first.is_complete());
+        match poll_once(&mut second) {
+            std::task::Poll::Ready(Ok(packet)) => assert_eq!(packet.reply_id, second.reply_id()),
+            other => panic!("unexpected poll result: {:?}", other),
+        }
+        
+        let reply = Packet::new(Command::AckData, 100, first.reply_id());
+        session.complete(reply).unwrap();
+        assert_eq!(first.try_take().unwrap().unwrap().command, Command::AckData);
+        assert_eq!(session.pending_count(), 0);
+    }
+    
+    #[test]
+    fn test_complete_unmatched_returns_packet() {
+        let session = Session::new();
+        session.initialize(100).unwrap();
+        
+        let stray = Packet::new(Command::AckOk, 100, 1234);
+        let returned = session.complete(stray.clone()).unwrap_err();
+        assert_eq!(returned, stray);
+    }
+    
+    #[test]
+    fn test_cancel_and_close_resolve_pending() {
+        let session = Session::new();
+        session.initialize(100).unwrap();
+        
+        let mut cancelled = session.register_request(Command::GetVersion).unwrap();
+        let mut closed = session.register_request(Command::GetTime).unwrap();
+        
+        assert!(session.cancel_request(cancelled.reply_id()));
+        assert!(!session.is_pending(cancelled.reply_id()));
+        assert!(matches!(
+            cancelled.try_take(),
+            Some(Err(Error::RequestCancelled { command: Command::GetVersion, .. }))
+        ));
+        
+        session.close();
+        assert!(matches!(
+            poll_once(&mut closed),
+            std::task::Poll::Ready(Err(Error::RequestCancelled { .. }))
+        ));
+        assert_eq!(session.pending_count(), 0);
+    }
 }
\ No newline at end of file
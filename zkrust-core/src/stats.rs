@@ -0,0 +1,126 @@
+//! Session statistics
+//!
+//! Counters are kept on the [`Session`](crate::Session) and survive reconnects,
+//! so long-running services can report per-device protocol health.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use web_time::Instant;
+
+use crate::sync::Mutex;
+
+/// Snapshot of session counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SessionStats {
+    /// Commands sent to the device
+    pub commands_sent: u64,
+
+    /// Response packets received from the device
+    pub responses_received: u64,
+
+    /// Bytes written to the transport
+    pub bytes_out: u64,
+
+    /// Bytes read from the transport
+    pub bytes_in: u64,
+
+    /// Commands re-sent after a timeout or retry request
+    pub retries: u64,
+
+    /// Received packets that failed checksum verification
+    pub checksum_failures: u64,
+
+    /// Received packets discarded for belonging to another session
+    pub stray_packets: u64,
+
+    /// Repeated responses to exchanges that had already finished
+    pub duplicate_packets: u64,
+
+    /// Time of the last send or receive
+    pub last_activity: Option<Instant>,
+}
+
+impl SessionStats {
+    /// Time elapsed since the last send or receive
+    pub fn idle_for(&self) -> Option<Duration> {
+        self.last_activity.map(|at| at.elapsed())
+    }
+}
+
+/// Live counters backing [`SessionStats`]
+#[derive(Debug, Default)]
+pub(crate) struct SessionCounters {
+    commands_sent: AtomicU64,
+    responses_received: AtomicU64,
+    bytes_out: AtomicU64,
+    bytes_in: AtomicU64,
+    retries: AtomicU64,
+    checksum_failures: AtomicU64,
+    stray_packets: AtomicU64,
+    duplicate_packets: AtomicU64,
+    last_activity: Mutex<Option<Instant>>,
+}
+
+impl SessionCounters {
+    pub(crate) fn record_sent(&self, bytes: usize) {
+        self.commands_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub(crate) fn record_received(&self, bytes: usize) {
+        self.responses_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub(crate) fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_checksum_failure(&self) {
+        self.checksum_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_stray(&self) {
+        self.stray_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_duplicate(&self) {
+        self.duplicate_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> SessionStats {
+        SessionStats {
+            commands_sent: self.commands_sent.load(Ordering::Relaxed),
+            responses_received: self.responses_received.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            checksum_failures: self.checksum_failures.load(Ordering::Relaxed),
+            stray_packets: self.stray_packets.load(Ordering::Relaxed),
+            duplicate_packets: self.duplicate_packets.load(Ordering::Relaxed),
+            last_activity: *self.last_activity.lock(),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.commands_sent.store(0, Ordering::Relaxed);
+        self.responses_received.store(0, Ordering::Relaxed);
+        self.bytes_out.store(0, Ordering::Relaxed);
+        self.bytes_in.store(0, Ordering::Relaxed);
+        self.retries.store(0, Ordering::Relaxed);
+        self.checksum_failures.store(0, Ordering::Relaxed);
+        self.stray_packets.store(0, Ordering::Relaxed);
+        self.duplicate_packets.store(0, Ordering::Relaxed);
+        *self.last_activity.lock() = None;
+    }
+
+    fn touch(&self) {
+        *self.last_activity.lock() = Some(Instant::now());
+    }
+}
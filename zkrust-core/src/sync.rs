@@ -0,0 +1,47 @@
+//! Locks used by the session
+//!
+//! `parking_lot` locks with the `parking_lot` feature (the default). Without
+//! it, thin wrappers over `std::sync` with the same non-poisoning API: a
+//! panic while a lock is held leaves the data as it was, which is what the
+//! session state already tolerates.
+
+#[cfg(feature = "parking_lot")]
+pub(crate) use parking_lot::{Mutex, RwLock};
+
+#[cfg(not(feature = "parking_lot"))]
+pub(crate) use self::std_locks::{Mutex, RwLock};
+
+#[cfg(not(feature = "parking_lot"))]
+mod std_locks {
+    use std::sync::{self, MutexGuard, PoisonError, RwLockReadGuard, RwLockWriteGuard};
+
+    #[derive(Debug, Default)]
+    pub(crate) struct Mutex<T>(sync::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        pub(crate) const fn new(value: T) -> Self {
+            Self(sync::Mutex::new(value))
+        }
+
+        pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub(crate) struct RwLock<T>(sync::RwLock<T>);
+
+    impl<T> RwLock<T> {
+        pub(crate) const fn new(value: T) -> Self {
+            Self(sync::RwLock::new(value))
+        }
+
+        pub(crate) fn read(&self) -> RwLockReadGuard<'_, T> {
+            self.0.read().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        pub(crate) fn write(&self) -> RwLockWriteGuard<'_, T> {
+            self.0.write().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! JavaScript bindings for browser-based tools
+//!
+//! Enabled by the `wasm` feature. Covers the pure protocol pieces (frames,
+//! checksums, CommKeys) so a diagnostics page can build and pick apart
+//! packets without a server round trip; talking to a device still needs a
+//! native build. Build with:
+//!
+//! ```text
+//! cargo rustc -p zkrust-core --lib --release --features wasm \
+//!     --target wasm32-unknown-unknown --crate-type cdylib
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/zkrust_core.wasm
+//! ```
+//!
+//! Command codes are passed as plain numbers so codes this crate doesn't
+//! know can still be encoded and inspected.
+
+use wasm_bindgen::prelude::*;
+
+use crate::auth;
+use crate::checksum;
+use crate::command::Command;
+use crate::error::Error;
+use crate::packet::Packet;
+
+/// Header fields and payload of a frame
+///
+/// Decoding is lenient: a bad checksum or unknown command is reported here
+/// rather than thrown, since those are what a diagnostic tool is looking for.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct DecodedPacket {
+    command: u16,
+    checksum: u16,
+    expected_checksum: u16,
+    session_id: u16,
+    reply_id: u16,
+    payload: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl DecodedPacket {
+    /// Command code
+    #[wasm_bindgen(getter)]
+    pub fn command(&self) -> u16 {
+        self.command
+    }
+
+    /// Protocol name of the command, e.g. "CMD_CONNECT", if known
+    #[wasm_bindgen(getter, js_name = commandName)]
+    pub fn command_name(&self) -> Option<String> {
+        command_name(self.command)
+    }
+
+    /// Checksum carried in the frame
+    #[wasm_bindgen(getter)]
+    pub fn checksum(&self) -> u16 {
+        self.checksum
+    }
+
+    /// Checksum the frame should carry
+    #[wasm_bindgen(getter, js_name = expectedChecksum)]
+    pub fn expected_checksum(&self) -> u16 {
+        self.expected_checksum
+    }
+
+    /// Whether the carried checksum is correct
+    #[wasm_bindgen(getter, js_name = checksumValid)]
+    pub fn checksum_valid(&self) -> bool {
+        self.checksum == self.expected_checksum
+    }
+
+    #[wasm_bindgen(getter, js_name = sessionId)]
+    pub fn session_id(&self) -> u16 {
+        self.session_id
+    }
+
+    #[wasm_bindgen(getter, js_name = replyId)]
+    pub fn reply_id(&self) -> u16 {
+        self.reply_id
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn payload(&self) -> Vec<u8> {
+        self.payload.clone()
+    }
+}
+
+/// Encode a frame (without the TCP wrapper)
+#[wasm_bindgen(js_name = encodePacket)]
+pub fn encode_packet(command: u16, session_id: u16, reply_id: u16, payload: &[u8]) -> Result<Vec<u8>, JsError> {
+    if payload.len() > Packet::MAX_PAYLOAD_SIZE {
+        return Err(Error::PacketTooLarge {
+            size: Packet::HEADER_SIZE + payload.len(),
+            max: Packet::MAX_SIZE,
+        }
+        .into());
+    }
+
+    let checksum = checksum::calculate(command, session_id, reply_id, payload);
+    let mut frame = Vec::with_capacity(Packet::HEADER_SIZE + payload.len());
+    for field in [command, checksum, session_id, reply_id] {
+        frame.extend_from_slice(&field.to_le_bytes());
+    }
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+/// Split a frame (without the TCP wrapper) into its fields
+#[wasm_bindgen(js_name = decodePacket)]
+pub fn decode_packet(frame: &[u8]) -> Result<DecodedPacket, JsError> {
+    if frame.len() < Packet::HEADER_SIZE {
+        return Err(Error::PacketTooShort {
+            expected: Packet::HEADER_SIZE,
+            actual: frame.len(),
+        }
+        .into());
+    }
+
+    let field = |at: usize| u16::from_le_bytes([frame[at], frame[at + 1]]);
+    let (command, session_id, reply_id) = (field(0), field(4), field(6));
+    let payload = frame[Packet::HEADER_SIZE..].to_vec();
+
+    Ok(DecodedPacket {
+        command,
+        checksum: field(2),
+        expected_checksum: checksum::calculate(command, session_id, reply_id, &payload),
+        session_id,
+        reply_id,
+        payload,
+    })
+}
+
+/// Checksum of a frame's fields
+#[wasm_bindgen(js_name = calculateChecksum)]
+pub fn calculate_checksum(command: u16, session_id: u16, reply_id: u16, payload: &[u8]) -> u16 {
+    checksum::calculate(command, session_id, reply_id, payload)
+}
+
+/// CommKey sent in CMD_AUTH
+#[wasm_bindgen(js_name = makeCommkey)]
+pub fn make_commkey(password: u32, session_id: u16, ticks: u8) -> Vec<u8> {
+    auth::make_commkey(password, session_id, ticks).to_vec()
+}
+
+/// Protocol name of a command code, e.g. "CMD_CONNECT", if known
+#[wasm_bindgen(js_name = commandName)]
+pub fn command_name(command: u16) -> Option<String> {
+    Command::try_from(command).ok().map(|command| command.name().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_matches_packet() {
+        let packet = Packet::with_payload(Command::Auth, 0x1234, 2, vec![1, 2, 3]);
+        let frame = encode_packet(1102, 0x1234, 2, &[1, 2, 3]).unwrap();
+        assert_eq!(frame, packet.encode().to_vec());
+    }
+
+    #[test]
+    fn test_decode_reports_bad_checksum() {
+        let mut frame = encode_packet(1000, 0, 0, &[]).unwrap();
+        let decoded = decode_packet(&frame).unwrap();
+        assert!(decoded.checksum_valid());
+        assert_eq!(decoded.command_name().as_deref(), Some("CMD_CONNECT"));
+
+        frame[2] ^= 0xFF;
+        let decoded = decode_packet(&frame).unwrap();
+        assert!(!decoded.checksum_valid());
+        assert_eq!(decoded.checksum(), decoded.expected_checksum() ^ 0xFF);
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let frame = encode_packet(1503, 7, 9, &[0xAA]).unwrap();
+        let decoded = decode_packet(&frame).unwrap();
+        assert_eq!((decoded.command(), decoded.session_id(), decoded.reply_id()), (1503, 7, 9));
+        assert_eq!(decoded.command_name(), None);
+        assert_eq!(decoded.payload(), vec![0xAA]);
+    }
+
+    #[test]
+    fn test_make_commkey() {
+        assert_eq!(make_commkey(1234, 0x5678, 50), auth::make_commkey(1234, 0x5678, 50).to_vec());
+    }
+}
@@ -0,0 +1,322 @@
+#!/usr/bin/env python3
+"""Regenerate the pyzk compatibility vectors.
+
+The routines below are transcribed from pyzk 0.9 (zk/base.py):
+__create_checksum, __create_header, make_commkey, __encode_time and the
+user/attendance struct formats of set_user/get_users/get_attendance.
+They are kept as close to the original as possible, quirks included, so
+the vectors show what pyzk puts on the wire rather than what it should.
+
+Usage (from the repository root):
+
+    python3 zkrust-core/testdata/gen_pyzk_vectors.py
+
+Writes zkrust-core/testdata/pyzk.txt and zkrust-types/testdata/pyzk.txt.
+"""
+
+import os
+from datetime import datetime
+from struct import pack, unpack
+
+USHRT_MAX = 65535
+
+# --- zk/base.py ------------------------------------------------------------
+
+
+def make_commkey(key, session_id, ticks=50):
+    key = int(key)
+    session_id = int(session_id)
+    k = 0
+    for i in range(32):
+        if key & (1 << i):
+            k = (k << 1 | 1)
+        else:
+            k = k << 1
+    k += session_id
+
+    k = pack(b'I', k)
+    k = unpack(b'BBBB', k)
+    k = pack(
+        b'BBBB',
+        k[0] ^ ord('Z'),
+        k[1] ^ ord('K'),
+        k[2] ^ ord('S'),
+        k[3] ^ ord('O'))
+    k = unpack(b'HH', k)
+    k = pack(b'HH', k[1], k[0])
+
+    B = 0xff & ticks
+    k = unpack(b'BBBB', k)
+    k = pack(
+        b'BBBB',
+        k[0] ^ B,
+        k[1] ^ B,
+        B,
+        k[3] ^ B)
+    return k
+
+
+def create_checksum(p):
+    l = len(p)
+    checksum = 0
+    while l > 1:
+        checksum += unpack('H', pack('BB', p[0], p[1]))[0]
+        p = p[2:]
+        if checksum > USHRT_MAX:
+            checksum -= USHRT_MAX
+        l -= 2
+    if l:
+        checksum = checksum + p[-1]
+
+    while checksum > USHRT_MAX:
+        checksum -= USHRT_MAX
+
+    checksum = ~checksum
+
+    while checksum < 0:
+        checksum += USHRT_MAX
+
+    return pack('H', checksum)
+
+
+def create_header(command, command_string, session_id, reply_id):
+    buf = pack('<4H', command, 0, session_id, reply_id) + command_string
+    buf = unpack('8B' + '%sB' % len(command_string), buf)
+    checksum = unpack('H', create_checksum(buf))[0]
+    reply_id += 1
+    if reply_id >= USHRT_MAX:
+        reply_id -= USHRT_MAX
+
+    buf = pack('<4H', command, checksum, session_id, reply_id)
+    return buf + command_string
+
+
+def encode_time(t):
+    d = (
+        ((t.year % 100) * 12 * 31 + ((t.month - 1) * 31) + t.day - 1) *
+        (24 * 60 * 60) + (t.hour * 60 + t.minute) * 60 + t.second
+    )
+    return d
+
+
+def pack_user_28(uid, privilege, password, name, card, group_id, user_id):
+    return pack('HB5s8sIxBHI', uid, privilege, password.encode(),
+                name.encode(), card, int(group_id) if group_id else 0, 0,
+                int(user_id))
+
+
+def pack_user_72(uid, privilege, password, name, card, group_id, user_id):
+    card_str = pack('<I', int(card))
+    return pack('HB8s24s4sx7sx24s', uid, privilege, password.encode(),
+                name.encode(), card_str, group_id.encode(), user_id.encode())
+
+
+def pack_att_8(uid, status, timestamp, punch):
+    return pack('HB4sB', uid, status, pack('<I', timestamp), punch)
+
+
+def pack_att_16(user_id, timestamp, status, punch, workcode):
+    return pack('<I4sBB2sI', user_id, pack('<I', timestamp), status, punch,
+                b'\x00\x00', workcode)
+
+
+def pack_att_40(uid, user_id, status, timestamp, punch):
+    return pack('<H24sB4sB8s', uid, user_id.encode(), status,
+                pack('<I', timestamp), punch, b'\x00' * 8)
+
+
+# --- vectors ---------------------------------------------------------------
+
+CMD_CONNECT = 1000
+CMD_EXIT = 1001
+CMD_ENABLEDEVICE = 1002
+CMD_DISABLEDEVICE = 1003
+CMD_AUTH = 1102
+CMD_OPTIONS_RRQ = 11
+CMD_USER_WRQ = 8
+CMD_DELETE_USER = 18
+CMD_GET_FREE_SIZES = 50
+CMD_CLEAR_ATTLOG = 15
+CMD_GET_VERSION = 1100
+CMD_SET_TIME = 202
+CMD_GET_TIME = 201
+CMD_REG_EVENT = 500
+CMD_PREPARE_DATA = 1500
+CMD_FREE_DATA = 1502
+CMD_ACK_OK = 2000
+
+# (command, payload, session_id, reply_id before pyzk increments it)
+PACKETS = [
+    (CMD_CONNECT, b'', 0, USHRT_MAX - 1),  # pyzk's first packet
+    (CMD_AUTH, make_commkey(0, 32031), 32031, 0),
+    (CMD_AUTH, make_commkey(123456, 43690), 43690, 0),
+    (CMD_GET_FREE_SIZES, b'', 32031, 1),
+    (CMD_OPTIONS_RRQ, b'~SerialNumber\x00', 32031, 2),
+    (CMD_OPTIONS_RRQ, b'~Platform\x00', 4660, 17),
+    (CMD_DISABLEDEVICE, b'', 32031, 3),
+    (CMD_ENABLEDEVICE, b'', 32031, 4),
+    (CMD_GET_TIME, b'', 1, 1),
+    (CMD_SET_TIME, pack(b'I', encode_time(datetime(2024, 3, 1, 8, 30, 0))), 32031, 5),
+    (CMD_REG_EVENT, pack('I', 0xFFFF), 2222, 9),
+    (CMD_DELETE_USER, pack('h', 7), 32031, 6),
+    (CMD_GET_VERSION, b'', 32031, 7),
+    (CMD_CLEAR_ATTLOG, b'', 32031, 8),
+    (CMD_FREE_DATA, b'', 32031, 9),
+    (CMD_PREPARE_DATA, pack('I', 1234), 32031, 10),
+    (CMD_USER_WRQ, pack_user_72(1, 14, '12345', 'Jane Doe', 9876543, '1', '1001'), 32031, 11),
+    (CMD_USER_WRQ, pack_user_28(2, 0, '', 'Bob', 0, '', '42'), 32031, 12),
+    (CMD_EXIT, b'', 32031, 13),
+    (CMD_ACK_OK, b'\x01\x02\x03', 0xFFFF, 0x8000),  # odd-length payload
+    (CMD_ACK_OK, b'\xff' * 33, 0xFFFF, 0xFFF0),  # sum folds many times
+]
+
+# (password, session_id, ticks)
+COMMKEYS = [
+    (0, 0, 50),
+    (0, 32031, 50),
+    (0, 65535, 50),
+    (1, 1, 50),
+    (123456, 43690, 50),
+    (999999, 12345, 50),
+    (2024, 7, 50),
+    (8888, 54321, 0),
+    (8888, 54321, 255),
+    (0x7FFFFFFF, 0, 50),
+    (1, 65535, 37),
+]
+
+# (uid, privilege byte, password, name, card, group_id, user_id)
+USERS_28 = [
+    (1, 0, '', '', 0, '', '1'),
+    (2, 14, '12345', 'Admin', 123456, '1', '9001'),
+    (3, 1, '0', 'Dis', 0, '', '77'),  # disabled user
+    (500, 6, '99999', 'Full8chr', 4294967295, '255', '999999999'),
+]
+USERS_72 = [
+    (1, 0, '', '', 0, '', '1'),
+    (2, 14, '12345678', 'Jane Doe', 9876543, '1', '1001'),
+    (3, 3, '', 'Disabled Enroller', 0, '', '17'),
+    (65535, 6, '1', 'x' * 24, 1, '1234567', '123456789'),
+]
+
+T = [
+    datetime(2000, 1, 1, 0, 0, 0),
+    datetime(2024, 3, 1, 8, 30, 0),
+    datetime(2024, 2, 29, 17, 45, 59),
+    datetime(2099, 12, 31, 23, 59, 59),
+]
+
+# (uid, status, time, punch)
+ATT_8 = [
+    (1, 1, T[1], 0),
+    (42, 15, T[2], 1),
+    (65535, 0, T[0], 5),
+]
+# (user_id, time, status, punch, workcode)
+ATT_16 = [
+    (1, T[1], 1, 0, 0),
+    (9001, T[2], 4, 1, 12),
+    (999999999, T[3], 15, 5, 0),
+]
+# (uid, user_id, status, time, punch)
+ATT_40 = [
+    (1, '1', 1, T[1], 0),
+    (2, 'A-17', 15, T[2], 3),
+    (300, 'z' * 23, 0, T[3], 4),
+]
+
+
+def hx(data):
+    return data.hex() if data else '-'
+
+
+def row(*fields):
+    return '\t'.join(str(field) for field in fields)
+
+
+def fmt_time(t):
+    return t.strftime('%Y-%m-%dT%H:%M:%S')
+
+
+HEADER = '''# Generated by zkrust-core/testdata/gen_pyzk_vectors.py from routines
+# transcribed from pyzk 0.9 (zk/base.py). Do not edit by hand.
+# Fields are tab-separated; "-" stands for an empty string.
+'''
+
+
+def core_vectors():
+    out = [HEADER.rstrip('\n')]
+
+    out.append('''#
+# commkey <password> <session_id> <ticks> <key hex>
+#   make_commkey()''')
+    for password, session_id, ticks in COMMKEYS:
+        key = make_commkey(password, session_id, ticks)
+        out.append(row('commkey', password, session_id, ticks, key.hex()))
+
+    out.append('''#
+# packet <command> <session_id> <reply_id> <payload hex> <frame hex>
+#   __create_header() output; reply_id is the value on the wire. pyzk
+#   checksums the pre-increment reply_id and folds ~sum with 0xFFFF, and
+#   the two off-by-ones cancel, so the frames carry a valid checksum.''')
+    for command, payload, session_id, reply_id in PACKETS:
+        frame = create_header(command, payload, session_id, reply_id)
+        wire_reply = unpack('<H', frame[6:8])[0]
+        out.append(row('packet', command, session_id, wire_reply, hx(payload), frame.hex()))
+
+    out.append('''#
+# checksum <command> <session_id> <reply_id> <payload hex> <checksum hex>
+#   checksum field of the frames above''')
+    for command, payload, session_id, reply_id in PACKETS:
+        frame = create_header(command, payload, session_id, reply_id)
+        command, checksum, session_id, wire_reply = unpack('<4H', frame[:8])
+        out.append(row('checksum', command, session_id, wire_reply, hx(payload), '%04x' % checksum))
+
+    return '\n'.join(out) + '\n'
+
+
+def types_vectors():
+    out = [HEADER.rstrip('\n')]
+
+    out.append('''#
+# user28 <uid> <privilege byte> <password> <name> <card> <group_id> <user_id> <record hex>
+# user72 <uid> <privilege byte> <password> <name> <card> <group_id> <user_id> <record hex>
+#   set_user() records; bit 0 of the privilege byte marks a disabled user''')
+    for fields in USERS_28:
+        uid, privilege, password, name, card, group_id, user_id = fields
+        record = pack_user_28(*fields)
+        assert len(record) == 28
+        out.append(row(
+            'user28', uid, privilege, password or '-', name or '-', card, group_id or '-', user_id, record.hex()))
+    for fields in USERS_72:
+        uid, privilege, password, name, card, group_id, user_id = fields
+        record = pack_user_72(*fields)
+        assert len(record) == 72
+        out.append(row(
+            'user72', uid, privilege, password or '-', name or '-', card, group_id or '-', user_id, record.hex()))
+
+    out.append('''#
+# att8  <uid> <verify> <time> <punch> <record hex>
+# att16 <user_id> <verify> <time> <punch> <work code> <record hex>
+# att40 <uid> <user_id> <verify> <time> <punch> <record hex>
+#   get_attendance() record formats ('HB4sB', '<I4sBB2sI', '<H24sB4sB8s')''')
+    for uid, status, t, punch in ATT_8:
+        record = pack_att_8(uid, status, encode_time(t), punch)
+        out.append(row('att8', uid, status, fmt_time(t), punch, record.hex()))
+    for user_id, t, status, punch, workcode in ATT_16:
+        record = pack_att_16(user_id, encode_time(t), status, punch, workcode)
+        out.append(row('att16', user_id, status, fmt_time(t), punch, workcode, record.hex()))
+    for uid, user_id, status, t, punch in ATT_40:
+        record = pack_att_40(uid, user_id, status, encode_time(t), punch)
+        out.append(row('att40', uid, user_id, status, fmt_time(t), punch, record.hex()))
+
+    return '\n'.join(out) + '\n'
+
+
+if __name__ == '__main__':
+    here = os.path.dirname(os.path.abspath(__file__))
+    root = os.path.dirname(os.path.dirname(here))
+    with open(os.path.join(root, 'zkrust-core', 'testdata', 'pyzk.txt'), 'w') as f:
+        f.write(core_vectors())
+    with open(os.path.join(root, 'zkrust-types', 'testdata', 'pyzk.txt'), 'w') as f:
+        f.write(types_vectors())
@@ -0,0 +1,402 @@
+//! Emulator builder and running instances
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bytes::Bytes;
+use chrono::NaiveDateTime;
+use tokio::net::{TcpListener, ToSocketAddrs, UdpSocket};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+use zkrust_types::{
+    AttendanceLayout, AttendanceRecord, FingerprintTemplate, User, UserRecordLayout,
+};
+
+use crate::handler::{Event, SharedState};
+use crate::server;
+use crate::state::DeviceState;
+
+/// Events buffered per session before the oldest are dropped
+const EVENT_BUFFER: usize = 256;
+
+/// Emulated device, configured before it starts serving
+///
+/// Starts with no users or records, no CommKey and the options
+/// `~SerialNumber`, `~DeviceName`, `~Platform`, `MAC` and `~ZKFPVersion`.
+///
+/// # Examples
+///
+/// ```
+/// use zkrust_emulator::Emulator;
+/// use zkrust_types::User;
+///
+/// # async fn example() -> std::io::Result<()> {
+/// let emulator = Emulator::new()
+///     .with_option("~SerialNumber", "TEST0001")
+///     .with_user(User::builder(1, "1001").name("Alice").build().unwrap())
+///     .bind_tcp("127.0.0.1:0")
+///     .await?;
+///
+/// assert_eq!(emulator.users().len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Emulator {
+    state: DeviceState,
+}
+
+impl Emulator {
+    /// Create an emulator with default options and empty tables
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require CommKey authentication with `password` (default: 0, none)
+    pub fn with_commkey(mut self, password: u32) -> Self {
+        self.state.commkey = password;
+        self
+    }
+
+    /// Set the firmware string returned by CMD_GET_VERSION
+    pub fn with_firmware(mut self, firmware: impl Into<String>) -> Self {
+        self.state.firmware = firmware.into();
+        self
+    }
+
+    /// Set a device option
+    pub fn with_option(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.state.options.insert(name.into(), value.into());
+        self
+    }
+
+    /// Enroll a user
+    pub fn with_user(mut self, user: User) -> Self {
+        self.state.put_user(user);
+        self
+    }
+
+    /// Store a fingerprint template
+    pub fn with_fingerprint(mut self, template: FingerprintTemplate) -> Self {
+        self.state.put_fingerprint(template);
+        self
+    }
+
+    /// Append an attendance record
+    pub fn with_attendance(mut self, record: AttendanceRecord) -> Self {
+        self.state.attendance.push(record);
+        self
+    }
+
+    /// Set the user record layout (default: 72-byte Extended)
+    pub fn with_user_layout(mut self, layout: UserRecordLayout) -> Self {
+        self.state.user_layout = layout;
+        self
+    }
+
+    /// Set the attendance record layout (default: 40-byte Extended)
+    pub fn with_attendance_layout(mut self, layout: AttendanceLayout) -> Self {
+        self.state.attendance_layout = layout;
+        self
+    }
+
+    /// Serve over UDP
+    pub async fn bind_udp(self, addr: impl ToSocketAddrs) -> io::Result<EmulatorHandle> {
+        let socket = UdpSocket::bind(addr).await?;
+        let local_addr = socket.local_addr()?;
+
+        let (state, events) = self.into_shared();
+        let task = tokio::spawn(server::serve_udp(socket, state.clone(), events.subscribe()));
+
+        info!("Emulating device on udp://{}", local_addr);
+        Ok(EmulatorHandle { local_addr, state, events, task })
+    }
+
+    /// Serve over TCP, with or without the TCP wrapper
+    ///
+    /// The framing is picked per connection from the client's first packet.
+    pub async fn bind_tcp(self, addr: impl ToSocketAddrs) -> io::Result<EmulatorHandle> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+
+        let (state, events) = self.into_shared();
+        let task = tokio::spawn(server::serve_tcp(listener, state.clone(), events.clone()));
+
+        info!("Emulating device on tcp://{}", local_addr);
+        Ok(EmulatorHandle { local_addr, state, events, task })
+    }
+
+    fn into_shared(self) -> (SharedState, broadcast::Sender<Event>) {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        (Arc::new(Mutex::new(self.state)), events)
+    }
+}
+
+/// Running emulator
+///
+/// Gives tests access to the device tables while clients talk to it.
+/// Dropping the handle stops the emulator and closes every session.
+#[derive(Debug)]
+pub struct EmulatorHandle {
+    local_addr: SocketAddr,
+    state: SharedState,
+    events: broadcast::Sender<Event>,
+    task: JoinHandle<()>,
+}
+
+impl EmulatorHandle {
+    /// Address the emulator listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Enrolled users, by uid
+    pub fn users(&self) -> Vec<User> {
+        self.lock().users.values().cloned().collect()
+    }
+
+    /// Stored fingerprint templates, by uid and finger
+    pub fn fingerprints(&self) -> Vec<FingerprintTemplate> {
+        self.lock().fingerprints.values().cloned().collect()
+    }
+
+    /// Attendance log, oldest first
+    pub fn attendance(&self) -> Vec<AttendanceRecord> {
+        self.lock().attendance.clone()
+    }
+
+    /// Read a device option
+    pub fn option(&self, name: &str) -> Option<String> {
+        self.lock().options.get(name).cloned()
+    }
+
+    /// Current device clock
+    pub fn time(&self) -> NaiveDateTime {
+        self.lock().now()
+    }
+
+    /// Check if the device accepts input (not disabled by CMD_DISABLEDEVICE)
+    pub fn is_enabled(&self) -> bool {
+        self.lock().enabled
+    }
+
+    /// Record a punch, as if a user verified on the device
+    ///
+    /// The record is appended to the log and sent as an EF_ATTLOG event to
+    /// every session that registered for it.
+    pub fn push_attendance(&self, record: AttendanceRecord) {
+        let event = Event::attendance(&record);
+        self.lock().attendance.push(record);
+        self.send_event(event);
+    }
+
+    /// Send a raw real-time event to sessions registered for `flag`
+    ///
+    /// See [`zkrust_core::constants::events`] for the flags.
+    pub fn push_event(&self, flag: u32, payload: impl Into<Bytes>) {
+        self.send_event(Event {
+            flag,
+            payload: payload.into(),
+        });
+    }
+
+    fn send_event(&self, event: Event) {
+        // No receivers just means no session is listening
+        let _ = self.events.send(event);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DeviceState> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Drop for EmulatorHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use bytes::BytesMut;
+    use chrono::NaiveDate;
+    use zkrust::Device;
+    use zkrust_core::constants::{events, PunchType, VerifyMode};
+    use zkrust_core::{Command, Packet};
+    use zkrust_transport::TcpTransport;
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn punch(user_id: &str, minute: u32) -> AttendanceRecord {
+        AttendanceRecord::new(user_id, at(8, minute), VerifyMode::Fingerprint, PunchType::CheckIn)
+    }
+
+    fn user(uid: u16, name: &str) -> User {
+        User::builder(uid, (1000 + uid).to_string()).name(name).build().unwrap()
+    }
+
+    async fn connect(device: &mut Device) {
+        device.connect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_udp_device_info() {
+        let emulator = Emulator::new()
+            .with_option("~SerialNumber", "EMU42")
+            .bind_udp("127.0.0.1:0")
+            .await
+            .unwrap();
+
+        let mut device = Device::new_udp("127.0.0.1", emulator.local_addr().port());
+        connect(&mut device).await;
+
+        let info = device.get_device_info().await.unwrap();
+        assert_eq!(info.serial_number, "EMU42");
+        assert_eq!(info.platform.as_deref(), Some("ZMM220_TFT"));
+        assert!(device.firmware().is_some());
+
+        device.disconnect().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_commkey_over_wrapped_tcp() {
+        let emulator = Emulator::new()
+            .with_commkey(1234)
+            .bind_tcp("127.0.0.1:0")
+            .await
+            .unwrap();
+        let port = emulator.local_addr().port();
+
+        let mut device = Device::with_transport(TcpTransport::new("127.0.0.1", port)).with_password(1234);
+        connect(&mut device).await;
+        assert!(device.is_authenticated());
+        assert!(device.get_capacity().await.is_ok());
+
+        let mut wrong = Device::with_transport(TcpTransport::new("127.0.0.1", port)).with_password(1);
+        assert!(wrong.connect().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bare_tcp_users() {
+        let emulator = Emulator::new().with_user(user(1, "Alice")).bind_tcp("127.0.0.1:0").await.unwrap();
+
+        let mut device = Device::new("127.0.0.1", emulator.local_addr().port());
+        connect(&mut device).await;
+
+        device.set_user(&user(2, "Bob")).await.unwrap();
+        let users = device.get_users().await.unwrap();
+        assert_eq!(users, vec![user(1, "Alice"), user(2, "Bob")]);
+
+        device.delete_user(1).await.unwrap();
+        assert_eq!(emulator.users(), vec![user(2, "Bob")]);
+        assert!(device.delete_user(1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_attendance() {
+        let records: Vec<_> = (0..50).map(|i| punch(&i.to_string(), i)).collect();
+
+        for layout in [AttendanceLayout::Extended, AttendanceLayout::Standard] {
+            let emulator = records
+                .iter()
+                .cloned()
+                .fold(Emulator::new().with_attendance_layout(layout), Emulator::with_attendance);
+
+            let udp = emulator.clone().bind_udp("127.0.0.1:0").await.unwrap();
+            let mut device = Device::new_udp("127.0.0.1", udp.local_addr().port());
+            connect(&mut device).await;
+            assert_eq!(device.get_attendance().await.unwrap(), records);
+
+            let tcp = emulator.bind_tcp("127.0.0.1:0").await.unwrap();
+            let mut device = Device::with_transport(TcpTransport::new("127.0.0.1", tcp.local_addr().port()));
+            connect(&mut device).await;
+            assert_eq!(device.get_attendance().await.unwrap(), records);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_users_with_fingerprints() {
+        let emulator = Emulator::new()
+            .with_fingerprint(FingerprintTemplate::new(9, 1, 10, vec![7; 600]).unwrap())
+            .bind_udp("127.0.0.1:0")
+            .await
+            .unwrap();
+
+        let mut device = Device::new_udp("127.0.0.1", emulator.local_addr().port());
+        connect(&mut device).await;
+
+        let fingers = vec![
+            FingerprintTemplate::new(1, 0, 10, vec![1; 800]).unwrap(),
+            FingerprintTemplate::new(1, 6, 10, vec![2; 700]).unwrap(),
+        ];
+        device.save_users(&[(user(1, "Alice"), fingers.clone())]).await.unwrap();
+
+        assert_eq!(emulator.users(), vec![user(1, "Alice")]);
+        let stored = device.get_fingerprints().await.unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(&stored[..2], &fingers[..]);
+    }
+
+    #[tokio::test]
+    async fn test_clock_and_options() {
+        let emulator = Emulator::new().bind_udp("127.0.0.1:0").await.unwrap();
+
+        let mut device = Device::new_udp("127.0.0.1", emulator.local_addr().port());
+        connect(&mut device).await;
+
+        device.set_time(at(9, 15)).await.unwrap();
+        let time = device.get_time().await.unwrap();
+        assert!(time >= at(9, 15) && time < at(9, 16), "{}", time);
+
+        device.set_option("Language", "73").await.unwrap();
+        assert_eq!(emulator.option("Language").as_deref(), Some("73"));
+        assert_eq!(device.get_option("NoSuchOption").await.unwrap(), None);
+
+        device.batch(async |_| Ok(())).await.unwrap();
+        assert!(emulator.is_enabled());
+    }
+
+    #[tokio::test]
+    async fn test_attendance_events() {
+        let emulator = Emulator::new().bind_udp("127.0.0.1:0").await.unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(emulator.local_addr()).await.unwrap();
+
+        let mut buf = vec![0u8; 2048];
+        let mut exchange = async |packet: Packet| {
+            socket.send(&packet.encode()).await.unwrap();
+            let len = tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+            Packet::decode(BytesMut::from(&buf[..len])).unwrap()
+        };
+
+        let session = exchange(Packet::new(Command::Connect, 0, 0)).await.session_id;
+        let mask = Bytes::copy_from_slice(&events::EF_ATTLOG.to_le_bytes());
+        let ack = exchange(Packet::with_payload(Command::RegEvent, session, 1, mask)).await;
+        assert_eq!(ack.command, Command::AckOk);
+
+        emulator.push_attendance(punch("1002", 30));
+
+        let len = tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        let event = Packet::decode(BytesMut::from(&buf[..len])).unwrap();
+        assert_eq!(event.command, Command::RegEvent);
+        assert_eq!(event.session_id as u32, events::EF_ATTLOG);
+        assert_eq!(&event.payload[..4], b"1002");
+        assert_eq!(&event.payload[26..], &[24, 3, 1, 8, 30, 0]);
+        assert_eq!(emulator.attendance().len(), 1);
+    }
+}
@@ -0,0 +1,445 @@
+//! Device side of the request/response protocol
+//!
+//! A [`Connection`] holds one client session and turns each request into
+//! the packets a device would answer with. It knows nothing about sockets;
+//! the servers in [`crate::server`] feed it packets and send the replies.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bytes::{Bytes, BytesMut};
+use chrono::{Datelike, Timelike};
+use tracing::{debug, warn};
+
+use zkrust_core::constants::DataType;
+use zkrust_core::{auth, Command, Packet};
+use zkrust_types::{template, time, AttendanceRecord};
+
+use crate::state::DeviceState;
+
+/// Device tables shared by every connection
+pub(crate) type SharedState = Arc<Mutex<DeviceState>>;
+
+/// Largest CMD_DATA payload sent in one packet
+const MAX_CHUNK: usize = 1024;
+
+/// Real-time event queued for sessions that registered for it
+#[derive(Debug, Clone)]
+pub(crate) struct Event {
+    /// Event flag (see [`zkrust_core::constants::events`])
+    pub(crate) flag: u32,
+    pub(crate) payload: Bytes,
+}
+
+impl Event {
+    /// EF_ATTLOG event: user ID \[24\], verify, punch and a 6-byte
+    /// `yy mm dd hh mm ss` timestamp
+    pub(crate) fn attendance(record: &AttendanceRecord) -> Self {
+        let ts = record.timestamp;
+        let mut payload = Vec::with_capacity(32);
+
+        payload.extend_from_slice(record.user_id.as_bytes());
+        payload.resize(24, 0);
+        payload.push(u8::from(record.verify_mode));
+        payload.push(u8::from(record.punch));
+        payload.extend_from_slice(&[
+            (ts.year() - 2000) as u8,
+            ts.month() as u8,
+            ts.day() as u8,
+            ts.hour() as u8,
+            ts.minute() as u8,
+            ts.second() as u8,
+        ]);
+
+        Self {
+            flag: zkrust_core::constants::events::EF_ATTLOG,
+            payload: Bytes::from(payload),
+        }
+    }
+}
+
+/// Packets to send back, and whether the session ends afterwards
+#[derive(Debug, Default)]
+pub(crate) struct Reply {
+    pub(crate) packets: Vec<Packet>,
+    pub(crate) close: bool,
+}
+
+/// One client session
+pub(crate) struct Connection {
+    state: SharedState,
+    session_id: u16,
+    ready: bool, // Connected, and authenticated if a CommKey is set
+    event_mask: u32,
+    upload: BytesMut, // Staged by CMD_PREPARE_DATA / CMD_DATA
+}
+
+impl Connection {
+    pub(crate) fn new(state: SharedState) -> Self {
+        let session_id = state.lock().unwrap().open_session();
+        Self {
+            state,
+            session_id,
+            ready: false,
+            event_mask: 0,
+            upload: BytesMut::new(),
+        }
+    }
+
+    /// Check if the session registered for events with `flag`
+    pub(crate) fn wants(&self, flag: u32) -> bool {
+        self.ready && self.event_mask & flag != 0
+    }
+
+    /// CMD_REG_EVENT packet for `event`; the session ID field carries the flag
+    pub(crate) fn event_packet(&self, event: &Event) -> Packet {
+        Packet::with_payload(Command::RegEvent, event.flag as u16, 0, event.payload.clone())
+    }
+
+    /// Answer one request
+    pub(crate) fn handle(&mut self, request: Packet) -> Reply {
+        // Clients acknowledge events; nothing to answer
+        if request.is_response() {
+            return Reply::default();
+        }
+
+        debug!("Session {}: {}", self.session_id, request);
+
+        let command = request.command;
+        match command {
+            Command::Connect => return self.connect(&request),
+            Command::Auth => return self.authenticate(&request),
+            _ if !self.ready => return self.reply(&request, Command::AckUnauth, Bytes::new()),
+            _ => {}
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let payload = &request.payload;
+
+        let (status, data) = match command {
+            Command::Exit | Command::Restart | Command::PowerOff => {
+                drop(state);
+                let mut reply = self.reply(&request, Command::AckOk, Bytes::new());
+                reply.close = true;
+                return reply;
+            }
+            Command::EnableDevice | Command::DisableDevice => {
+                state.enabled = command == Command::EnableDevice;
+                (Command::AckOk, Bytes::new())
+            }
+            Command::RefreshData | Command::RefreshOption => (Command::AckOk, Bytes::new()),
+            Command::GetVersion => (Command::AckOk, c_string(&state.firmware)),
+            Command::GetFreeSizes => (Command::AckOk, Bytes::from(state.capacity().encode())),
+            Command::GetTime => match time::encode_time(&state.now()) {
+                Ok(now) => (Command::AckOk, Bytes::copy_from_slice(&now.to_le_bytes())),
+                Err(_) => (Command::AckError, Bytes::new()),
+            },
+            Command::SetTime => match u32_at(payload, 0).map(time::decode_time) {
+                Some(Ok(time)) => {
+                    state.set_time(time);
+                    (Command::AckOk, Bytes::new())
+                }
+                _ => (Command::AckError, Bytes::new()),
+            },
+            Command::OptionsRrq => {
+                let name = String::from_utf8_lossy(payload);
+                let name = name.trim_end_matches('\0');
+                match state.options.get(name) {
+                    Some(value) => (Command::AckOk, c_string(&format!("{}={}", name, value))),
+                    None => (Command::AckError, Bytes::new()),
+                }
+            }
+            Command::OptionsWrq => {
+                let text = String::from_utf8_lossy(payload);
+                match text.trim_end_matches('\0').split_once('=') {
+                    Some((name, value)) if !name.is_empty() => {
+                        state.options.insert(name.to_string(), value.to_string());
+                        (Command::AckOk, Bytes::new())
+                    }
+                    _ => (Command::AckError, Bytes::new()),
+                }
+            }
+            Command::AttLogRrq => return self.bulk(&request, attendance_table(&state)),
+            Command::UserTempRrq if payload.first() == Some(&u8::from(DataType::User)) => {
+                let layout = state.user_layout;
+                let users: zkrust_types::Result<Vec<_>> =
+                    state.users.values().map(|user| layout.encode(user)).collect();
+                return self.bulk(&request, users.map(|users| users.concat()));
+            }
+            Command::DbRrq if payload.first() == Some(&u8::from(DataType::FingerTemplate)) => {
+                let templates: Vec<_> = state.fingerprints.values().cloned().collect();
+                return self.bulk(&request, template::encode_fingerprints(&templates));
+            }
+            Command::UserWrq => match state.user_layout.decode(payload) {
+                Ok(user) => {
+                    state.put_user(user);
+                    (Command::AckOk, Bytes::new())
+                }
+                Err(e) => {
+                    warn!("Rejecting user record: {}", e);
+                    (Command::AckError, Bytes::new())
+                }
+            },
+            Command::DeleteUser => match payload.get(..2) {
+                Some(uid) if state.delete_user(u16::from_le_bytes([uid[0], uid[1]])) => {
+                    (Command::AckOk, Bytes::new())
+                }
+                _ => (Command::AckError, Bytes::new()),
+            },
+            Command::ClearAttLog => {
+                state.attendance.clear();
+                (Command::AckOk, Bytes::new())
+            }
+            Command::ClearData => {
+                state.users.clear();
+                state.fingerprints.clear();
+                state.attendance.clear();
+                (Command::AckOk, Bytes::new())
+            }
+            Command::RegEvent => match u32_at(payload, 0) {
+                Some(mask) => {
+                    self.event_mask = mask;
+                    (Command::AckOk, Bytes::new())
+                }
+                None => (Command::AckError, Bytes::new()),
+            },
+            Command::FreeData => {
+                self.upload.clear();
+                (Command::AckOk, Bytes::new())
+            }
+            Command::PrepareData => {
+                self.upload.clear();
+                self.upload.reserve(u32_at(payload, 0).unwrap_or(0) as usize);
+                (Command::AckOk, Bytes::new())
+            }
+            Command::Data => {
+                self.upload.extend_from_slice(payload);
+                (Command::AckOk, Bytes::new())
+            }
+            Command::SaveUserTemps => {
+                let version = state.fingerprint_algorithm();
+                match template::decode_user_templates(state.user_layout, &self.upload, version) {
+                    Ok(entries) => {
+                        for (user, fingers) in entries {
+                            fingers.into_iter().for_each(|finger| state.put_fingerprint(finger));
+                            state.put_user(user);
+                        }
+                        self.upload.clear();
+                        (Command::AckOk, Bytes::new())
+                    }
+                    Err(e) => {
+                        warn!("Rejecting user upload: {}", e);
+                        (Command::AckError, Bytes::new())
+                    }
+                }
+            }
+            Command::UserTempRrq | Command::DbRrq => (Command::AckError, Bytes::new()),
+            _ => (Command::AckUnknown, Bytes::new()),
+        };
+
+        drop(state);
+        self.reply(&request, status, data)
+    }
+
+    fn connect(&mut self, request: &Packet) -> Reply {
+        let state = self.lock();
+
+        if state.commkey == 0 {
+            drop(state);
+            self.ready = true;
+            return self.reply(request, Command::AckOk, Bytes::new());
+        }
+
+        drop(state);
+        self.ready = false;
+        self.reply(request, Command::AckUnauth, Bytes::new())
+    }
+
+    /// Accept the key made with any ticks value
+    fn authenticate(&mut self, request: &Packet) -> Reply {
+        let password = self.lock().commkey;
+
+        self.ready = (0..=u8::MAX)
+            .any(|ticks| auth::make_commkey(password, self.session_id, ticks) == request.payload);
+
+        let status = if self.ready { Command::AckOk } else { Command::AckUnauth };
+        self.reply(request, status, Bytes::new())
+    }
+
+    /// Answer a bulk read: inline CMD_ACK_DATA when it fits one packet,
+    /// otherwise CMD_PREPARE_DATA, CMD_DATA chunks and a closing CMD_ACK_OK
+    ///
+    /// The data goes out behind its `u32` size prefix.
+    fn bulk(&self, request: &Packet, body: zkrust_types::Result<Vec<u8>>) -> Reply {
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Cannot encode {} reply: {}", request.command, e);
+                return self.reply(request, Command::AckError, Bytes::new());
+            }
+        };
+
+        let mut data = Vec::with_capacity(4 + body.len());
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend_from_slice(&body);
+
+        if data.len() <= MAX_CHUNK {
+            return self.reply(request, Command::AckData, Bytes::from(data));
+        }
+
+        let packet = |command, payload| {
+            Packet::with_payload(command, self.session_id, request.reply_id, payload)
+        };
+
+        let mut packets = vec![packet(
+            Command::PrepareData,
+            Bytes::copy_from_slice(&(data.len() as u32).to_le_bytes()),
+        )];
+        packets.extend(data.chunks(MAX_CHUNK).map(|chunk| packet(Command::Data, Bytes::copy_from_slice(chunk))));
+        packets.push(packet(Command::AckOk, Bytes::new()));
+
+        Reply { packets, close: false }
+    }
+
+    fn reply(&self, request: &Packet, command: Command, payload: Bytes) -> Reply {
+        Reply {
+            packets: vec![Packet::with_payload(command, self.session_id, request.reply_id, payload)],
+            close: false,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DeviceState> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// Attendance log in the configured layout, owners looked up by user ID
+fn attendance_table(state: &DeviceState) -> zkrust_types::Result<Vec<u8>> {
+    let layout = state.attendance_layout;
+    let mut table = Vec::with_capacity(state.attendance.len() * layout.record_size());
+
+    for record in &state.attendance {
+        table.extend_from_slice(&layout.encode(state.uid_of(&record.user_id), record)?);
+    }
+
+    Ok(table)
+}
+
+/// NUL-terminated string payload
+fn c_string(text: &str) -> Bytes {
+    let mut payload = text.as_bytes().to_vec();
+    payload.push(0);
+    Bytes::from(payload)
+}
+
+fn u32_at(payload: &[u8], offset: usize) -> Option<u32> {
+    payload
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+    use zkrust_types::records;
+
+    fn connection(state: DeviceState) -> Connection {
+        Connection::new(Arc::new(Mutex::new(state)))
+    }
+
+    fn request(command: Command, payload: impl Into<Bytes>) -> Packet {
+        Packet::with_payload(command, 0, 7, payload)
+    }
+
+    fn answer(connection: &mut Connection, command: Command, payload: impl Into<Bytes>) -> Packet {
+        let mut reply = connection.handle(request(command, payload));
+        assert_eq!(reply.packets.len(), 1, "{:?}", reply.packets);
+        reply.packets.remove(0)
+    }
+
+    #[test]
+    fn test_commands_need_a_session() {
+        let mut conn = connection(DeviceState::default());
+
+        assert_eq!(answer(&mut conn, Command::GetVersion, Bytes::new()).command, Command::AckUnauth);
+
+        let ok = answer(&mut conn, Command::Connect, Bytes::new());
+        assert_eq!(ok.command, Command::AckOk);
+        assert_eq!(ok.session_id, conn.session_id);
+        assert_eq!(ok.reply_id, 7);
+
+        let version = answer(&mut conn, Command::GetVersion, Bytes::new());
+        assert_eq!(&version.payload[..], b"Ver 6.60 Apr 28 2017\0");
+        assert_eq!(answer(&mut conn, Command::CaptureImage, Bytes::new()).command, Command::AckUnknown);
+    }
+
+    #[test]
+    fn test_commkey_any_ticks() {
+        let state = DeviceState {
+            commkey: 1234,
+            ..DeviceState::default()
+        };
+        let mut conn = connection(state);
+
+        let unauth = answer(&mut conn, Command::Connect, Bytes::new());
+        assert_eq!(unauth.command, Command::AckUnauth);
+
+        let wrong = auth::make_commkey(4321, unauth.session_id, 50);
+        assert_eq!(answer(&mut conn, Command::Auth, wrong).command, Command::AckUnauth);
+        assert_eq!(answer(&mut conn, Command::GetTime, Bytes::new()).command, Command::AckUnauth);
+
+        let key = auth::make_commkey(1234, unauth.session_id, 99);
+        assert_eq!(answer(&mut conn, Command::Auth, key).command, Command::AckOk);
+        assert_eq!(answer(&mut conn, Command::GetTime, Bytes::new()).command, Command::AckOk);
+    }
+
+    #[test]
+    fn test_large_bulk_read_is_chunked() {
+        let time = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 30, 0).unwrap();
+        let state = DeviceState {
+            attendance: (0..30)
+                .map(|i| AttendanceRecord::new(i.to_string(), time, VerifyMode::Fingerprint, PunchType::CheckIn))
+                .collect(),
+            ..DeviceState::default()
+        };
+        let mut conn = connection(state);
+        conn.handle(request(Command::Connect, Bytes::new()));
+
+        let reply = conn.handle(request(Command::AttLogRrq, Bytes::new()));
+        let commands: Vec<_> = reply.packets.iter().map(|p| p.command).collect();
+        assert_eq!(
+            commands,
+            [Command::PrepareData, Command::Data, Command::Data, Command::AckOk]
+        );
+
+        // 4-byte prefix + 30 extended records
+        assert_eq!(&reply.packets[0].payload[..], &1204u32.to_le_bytes());
+        let data: Vec<u8> = reply.packets[1..3].iter().flat_map(|p| p.payload.to_vec()).collect();
+        let records = records::parse_attendance(
+            records::strip_size_prefix(&data).unwrap(),
+            zkrust_types::AttendanceLayout::Extended,
+        )
+        .unwrap();
+        assert_eq!(records.len(), 30);
+        assert_eq!(records[29].user_id, "29");
+    }
+
+    #[test]
+    fn test_attendance_event_payload() {
+        let time = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(17, 5, 12).unwrap();
+        let record = AttendanceRecord::new("1002", time, VerifyMode::Face, PunchType::CheckOut);
+
+        let event = Event::attendance(&record);
+        assert_eq!(event.payload.len(), 32);
+        assert_eq!(&event.payload[..5], b"1002\0");
+        assert_eq!(&event.payload[24..], &[15, 1, 24, 3, 1, 17, 5, 12]);
+
+        let mut conn = connection(DeviceState::default());
+        assert!(!conn.wants(event.flag));
+        conn.handle(request(Command::Connect, Bytes::new()));
+        answer(&mut conn, Command::RegEvent, Bytes::copy_from_slice(&u32::MAX.to_le_bytes()));
+        assert!(conn.wants(event.flag));
+        assert_eq!(conn.event_packet(&event).session_id, 1);
+    }
+}
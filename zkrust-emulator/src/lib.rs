@@ -0,0 +1,37 @@
+//! # zkrust-emulator
+//!
+//! The device side of the ZKTeco protocol, for integration tests without
+//! hardware.
+//!
+//! An [`Emulator`] holds options, users, fingerprint templates and an
+//! attendance log in memory and serves them over UDP or TCP (with or
+//! without the TCP wrapper). It answers connect and CommKey
+//! authentication, option reads and writes, bulk reads and uploads, and
+//! pushes real-time attendance events to sessions that registered for
+//! them with CMD_REG_EVENT.
+//!
+//! ## Quick Start
+//!
+//! ```no_run
+//! use zkrust::Device;
+//! use zkrust_emulator::Emulator;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let emulator = Emulator::new().with_commkey(1234).bind_udp("127.0.0.1:0").await?;
+//!
+//!     let port = emulator.local_addr().port();
+//!     let mut device = Device::new_udp("127.0.0.1", port).with_password(1234);
+//!     device.connect().await?;
+//!
+//!     println!("{}", device.get_device_info().await?);
+//!     Ok(())
+//! }
+//! ```
+
+pub mod emulator;
+mod handler;
+mod server;
+mod state;
+
+pub use emulator::{Emulator, EmulatorHandle};
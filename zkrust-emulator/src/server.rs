@@ -0,0 +1,153 @@
+//! UDP and TCP front ends
+//!
+//! UDP keeps one [`Connection`] per peer address. TCP runs one task per
+//! client and answers in the framing the client opened with: wrapped
+//! (`0x5050 0x8272 len`) or bare packets.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinSet;
+use tokio_util::codec::Framed;
+use tracing::{debug, warn};
+
+use zkrust_core::{Command, Packet};
+use zkrust_transport::{codec, ZkCodec};
+
+use crate::handler::{Connection, Event, SharedState};
+
+/// Pause between the packets of a multi-packet TCP reply
+///
+/// Clients read one segment per receive, so back-to-back packets would
+/// arrive coalesced.
+const TCP_PACKET_GAP: Duration = Duration::from_millis(10);
+
+pub(crate) async fn serve_udp(socket: UdpSocket, state: SharedState, mut events: broadcast::Receiver<Event>) {
+    let mut peers: HashMap<SocketAddr, Connection> = HashMap::new();
+    let mut buf = vec![0u8; Packet::MAX_SIZE];
+
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => {
+                let (len, peer) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!("UDP receive failed: {}", e);
+                        continue;
+                    }
+                };
+
+                let packet = match Packet::decode(BytesMut::from(&buf[..len])) {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        warn!("Dropping malformed datagram from {}: {}", peer, e);
+                        continue;
+                    }
+                };
+
+                // A new CMD_CONNECT starts over, even from a known peer
+                if packet.command == Command::Connect {
+                    peers.remove(&peer);
+                }
+                let connection = peers.entry(peer).or_insert_with(|| Connection::new(state.clone()));
+
+                let reply = connection.handle(packet);
+                for packet in reply.packets {
+                    if let Err(e) = socket.send_to(&packet.encode(), peer).await {
+                        warn!("UDP send to {} failed: {}", peer, e);
+                    }
+                }
+                if reply.close {
+                    peers.remove(&peer);
+                }
+            }
+            event = events.recv() => match event {
+                Ok(event) => {
+                    for (peer, connection) in peers.iter().filter(|(_, c)| c.wants(event.flag)) {
+                        if let Err(e) = socket.send_to(&connection.event_packet(&event).encode(), peer).await {
+                            warn!("UDP send to {} failed: {}", peer, e);
+                        }
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => warn!("Dropped {} events", missed),
+                Err(RecvError::Closed) => return,
+            },
+        }
+    }
+}
+
+pub(crate) async fn serve_tcp(listener: TcpListener, state: SharedState, events: broadcast::Sender<Event>) {
+    // Dropped with the accept loop, which aborts every open connection
+    let mut connections = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    debug!("Accepted TCP client {}", peer);
+                    connections.spawn(serve_tcp_client(stream, state.clone(), events.subscribe()));
+                }
+                Err(e) => warn!("TCP accept failed: {}", e),
+            },
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+}
+
+async fn serve_tcp_client(stream: TcpStream, state: SharedState, mut events: broadcast::Receiver<Event>) {
+    if let Err(e) = stream.set_nodelay(true) {
+        warn!("Cannot disable Nagle's algorithm: {}", e);
+    }
+
+    let mut header = [0u8; codec::TCP_HEADER_SIZE];
+    let wrapped = match stream.peek(&mut header).await {
+        Ok(len) => codec::has_tcp_header(&header[..len]),
+        Err(_) => return,
+    };
+
+    let mut framed = Framed::new(stream, ZkCodec::new().with_tcp_wrapper(wrapped));
+    let mut connection = Connection::new(state);
+
+    loop {
+        tokio::select! {
+            frame = framed.next() => {
+                let packet = match frame {
+                    Some(Ok(packet)) => packet,
+                    Some(Err(e)) => {
+                        warn!("Closing TCP client after bad frame: {}", e);
+                        return;
+                    }
+                    None => return,
+                };
+
+                let reply = connection.handle(packet);
+                for (index, packet) in reply.packets.into_iter().enumerate() {
+                    if index > 0 {
+                        tokio::time::sleep(TCP_PACKET_GAP).await;
+                    }
+                    if framed.send(packet).await.is_err() {
+                        return;
+                    }
+                }
+                if reply.close {
+                    return;
+                }
+            }
+            event = events.recv() => match event {
+                Ok(event) if connection.wants(event.flag) => {
+                    if framed.send(connection.event_packet(&event)).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => warn!("Dropped {} events", missed),
+                Err(RecvError::Closed) => return,
+            },
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! In-memory device tables
+
+use std::collections::BTreeMap;
+
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use zkrust_types::{
+    AttendanceLayout, AttendanceRecord, DeviceCapacity, FingerprintTemplate, User,
+    UserRecordLayout,
+};
+
+/// Fingerprint algorithm reported when `~ZKFPVersion` is unset
+const DEFAULT_FP_VERSION: u8 = 10;
+
+/// Everything an emulated device stores
+///
+/// Shared between the connection tasks and the
+/// [`EmulatorHandle`](crate::EmulatorHandle) behind a mutex.
+#[derive(Debug, Clone)]
+pub(crate) struct DeviceState {
+    pub(crate) firmware: String,
+    pub(crate) commkey: u32, // 0 = no authentication
+    pub(crate) options: BTreeMap<String, String>,
+    pub(crate) users: BTreeMap<u16, User>,
+    pub(crate) fingerprints: BTreeMap<(u16, u8), FingerprintTemplate>,
+    pub(crate) attendance: Vec<AttendanceRecord>,
+    pub(crate) user_layout: UserRecordLayout,
+    pub(crate) attendance_layout: AttendanceLayout,
+    pub(crate) clock_offset: TimeDelta, // Device clock minus host clock
+    pub(crate) enabled: bool,
+    pub(crate) last_session_id: u16,
+}
+
+impl Default for DeviceState {
+    fn default() -> Self {
+        let options = [
+            ("~SerialNumber", "EMU0000000001"),
+            ("~DeviceName", "zkrust-emulator"),
+            ("~Platform", "ZMM220_TFT"),
+            ("MAC", "00:17:61:00:00:01"),
+            ("~ZKFPVersion", "10"),
+        ];
+
+        Self {
+            firmware: "Ver 6.60 Apr 28 2017".into(),
+            commkey: 0,
+            options: options.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            users: BTreeMap::new(),
+            fingerprints: BTreeMap::new(),
+            attendance: Vec::new(),
+            user_layout: UserRecordLayout::default(),
+            attendance_layout: AttendanceLayout::Extended,
+            clock_offset: TimeDelta::zero(),
+            enabled: true,
+            last_session_id: 0,
+        }
+    }
+}
+
+impl DeviceState {
+    /// Allocate a session ID for a new connection (never 0)
+    pub(crate) fn open_session(&mut self) -> u16 {
+        self.last_session_id = self.last_session_id.checked_add(1).unwrap_or(1);
+        self.last_session_id
+    }
+
+    /// Current device clock
+    pub(crate) fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local() + self.clock_offset
+    }
+
+    /// Set the device clock, keeping it running from `time`
+    pub(crate) fn set_time(&mut self, time: NaiveDateTime) {
+        self.clock_offset = time - Local::now().naive_local();
+    }
+
+    /// Fingerprint algorithm version from `~ZKFPVersion`
+    pub(crate) fn fingerprint_algorithm(&self) -> u8 {
+        self.options
+            .get("~ZKFPVersion")
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_FP_VERSION)
+    }
+
+    /// Internal uid of the user shown as `user_id` (0 if unknown)
+    pub(crate) fn uid_of(&self, user_id: &str) -> u16 {
+        self.users
+            .values()
+            .find(|user| user.user_id == user_id)
+            .map_or(0, |user| user.uid)
+    }
+
+    /// Create or replace a user
+    pub(crate) fn put_user(&mut self, user: User) {
+        self.users.insert(user.uid, user);
+    }
+
+    /// Remove a user together with their templates
+    pub(crate) fn delete_user(&mut self, uid: u16) -> bool {
+        self.fingerprints.retain(|(owner, _), _| *owner != uid);
+        self.users.remove(&uid).is_some()
+    }
+
+    /// Store a template, replacing the one on the same finger
+    pub(crate) fn put_fingerprint(&mut self, template: FingerprintTemplate) {
+        self.fingerprints.insert((template.uid, template.finger), template);
+    }
+
+    /// Counters for CMD_GET_FREE_SIZES
+    pub(crate) fn capacity(&self) -> DeviceCapacity {
+        DeviceCapacity {
+            users: self.users.len() as u32,
+            users_capacity: 3000,
+            fingers: self.fingerprints.len() as u32,
+            fingers_capacity: 3000,
+            records: self.attendance.len() as u32,
+            records_capacity: 100_000,
+            cards: self.users.values().filter(|user| user.card != 0).count() as u32,
+            faces: 0,
+            faces_capacity: 0,
+        }
+    }
+}
@@ -0,0 +1,10 @@
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    // Keep the checked-in header in step with the exported API
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    cbindgen::generate(&crate_dir)
+        .expect("Unable to generate C header")
+        .write_to_file(format!("{}/include/zkrust.h", crate_dir));
+}
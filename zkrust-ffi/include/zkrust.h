@@ -0,0 +1,208 @@
+/* zkrust C API. Generated by cbindgen from zkrust-ffi; do not edit. */
+
+#ifndef ZKRUST_H
+#define ZKRUST_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// Result of a `zk_*` call
+//
+// On failure the message is available from `zk_last_error` on the same
+// thread.
+typedef enum ZkStatus {
+  // Success
+  ZK_STATUS_OK = 0,
+  // NULL pointer or invalid string argument
+  ZK_STATUS_INVALID_ARGUMENT = 1,
+  // Device unreachable or the connection dropped
+  ZK_STATUS_UNAVAILABLE = 2,
+  // Device did not answer in time
+  ZK_STATUS_TIMEOUT = 3,
+  // Device rejected the CommKey password
+  ZK_STATUS_AUTH_FAILED = 4,
+  // Device is not connected or was closed
+  ZK_STATUS_NOT_CONNECTED = 5,
+  // Any other failure
+  ZK_STATUS_FAILED = 6,
+} ZkStatus;
+
+// Connected device (opaque)
+//
+// Owns a small runtime that runs the connection and subscriptions; the
+// `zk_*` functions may be called from any thread except a subscription
+// callback.
+typedef struct ZkDevice ZkDevice;
+
+// Connection settings for `zk_connect`
+typedef struct ZkOptions {
+  // Use UDP instead of TCP
+  bool udp;
+  // CommKey password, 0 if the device has none
+  uint32_t password;
+  // Per-command timeout in milliseconds, 0 for the library default
+  uint32_t timeout_ms;
+} ZkOptions;
+
+// Device user
+typedef struct ZkUser {
+  // Internal record index
+  uint16_t uid;
+  // User ID shown on the device
+  char user_id[25];
+  // Display name
+  char name[25];
+  // Privilege level: 0 user, 2 enroller, 6 manager, 14 admin
+  uint8_t privilege;
+  // Whether the user may verify
+  bool enabled;
+  // Numeric password, empty if unset
+  char password[9];
+  // Card number, 0 if unset
+  uint32_t card;
+  // Group ID, empty for the default group
+  char group_id[8];
+} ZkUser;
+
+// Users returned by `zk_get_users`
+typedef struct ZkUserList {
+  struct ZkUser *items;
+  size_t len;
+} ZkUserList;
+
+// Attendance log entry
+//
+// The time is the device's local time, without a timezone.
+typedef struct ZkAttendance {
+  // User ID shown on the device
+  char user_id[25];
+  uint16_t year;
+  uint8_t month;
+  uint8_t day;
+  uint8_t hour;
+  uint8_t minute;
+  uint8_t second;
+  // Verification mode code (1 fingerprint, 3 card, 15 face, ...)
+  uint8_t verify_mode;
+  // Punch state code (0 check-in, 1 check-out, ...)
+  uint8_t punch;
+  // Work code, 0 if none was entered
+  uint32_t work_code;
+} ZkAttendance;
+
+// Records returned by `zk_get_attlog`
+typedef struct ZkAttendanceList {
+  struct ZkAttendance *items;
+  size_t len;
+} ZkAttendanceList;
+
+// Called with each new attendance record
+//
+// `record` is only valid for the duration of the call. Runs on a library
+// thread, never the caller's.
+typedef void (*ZkAttendanceCallback)(const struct ZkAttendance *record, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Library version as a NUL-terminated string
+const char *zk_version(void);
+
+// Connect to a device
+//
+// `options` may be NULL for TCP without a password. On success `*out`
+// receives a device to pass to the other functions and finally to
+// `zk_disconnect`.
+//
+// # Safety
+//
+// `host` must be a NUL-terminated string, `options` NULL or valid, and
+// `out` a valid pointer.
+enum ZkStatus zk_connect(const char *host,
+                         uint16_t port,
+                         const struct ZkOptions *options,
+                         struct ZkDevice **out);
+
+// Cancel all subscriptions, disconnect and free the device
+//
+// # Safety
+//
+// `device` must be NULL or a device from `zk_connect` that is not used
+// afterwards.
+enum ZkStatus zk_disconnect(struct ZkDevice *device);
+
+// Download all users
+//
+// Release the list with `zk_user_list_free`.
+//
+// # Safety
+//
+// `device` must come from `zk_connect` and `out` must be valid.
+enum ZkStatus zk_get_users(const struct ZkDevice *device, struct ZkUserList *out);
+
+// Download the attendance log
+//
+// Release the list with `zk_attendance_list_free`.
+//
+// # Safety
+//
+// `device` must come from `zk_connect` and `out` must be valid.
+enum ZkStatus zk_get_attlog(const struct ZkDevice *device, struct ZkAttendanceList *out);
+
+// Call `callback` for every attendance record punched from now on
+//
+// The log is polled every `interval_ms` milliseconds (0 for 10 seconds);
+// records already on the device are skipped. Poll failures are retried
+// at the next interval, reconnecting if needed. `*id` receives the
+// subscription ID for `zk_unsubscribe`.
+//
+// # Safety
+//
+// `device` must come from `zk_connect` and `id` must be valid.
+// `user_data` must stay usable from another thread until the
+// subscription is cancelled.
+enum ZkStatus zk_subscribe(const struct ZkDevice *device,
+                           uint32_t interval_ms,
+                           ZkAttendanceCallback callback,
+                           void *user_data,
+                           uint64_t *id);
+
+// Cancel a subscription
+//
+// Once this returns the callback is not running and won't be called
+// again, so `user_data` may be freed.
+//
+// # Safety
+//
+// `device` must come from `zk_connect`. Must not be called from the
+// subscription callback.
+enum ZkStatus zk_unsubscribe(const struct ZkDevice *device, uint64_t id);
+
+// Message of the last failed call on this thread, or NULL
+//
+// The string stays valid until the next failing call on the same thread.
+const char *zk_last_error(void);
+
+// Release a list filled by `zk_get_users`
+//
+// # Safety
+//
+// `list` must be NULL or a list filled by `zk_get_users` that was not
+// freed before.
+void zk_user_list_free(struct ZkUserList *list);
+
+// Release a list filled by `zk_get_attlog`
+//
+// # Safety
+//
+// `list` must be NULL or a list filled by `zk_get_attlog` that was not
+// freed before.
+void zk_attendance_list_free(struct ZkAttendanceList *list);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ZKRUST_H */
@@ -0,0 +1,433 @@
+//! Device connections and attendance subscriptions
+
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::runtime::{Builder, Runtime};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use zkrust::{Device, DeviceHandle, Error};
+use zkrust_sync::Cursor;
+
+use crate::error::{fail, fail_with, ZkStatus};
+use crate::types::{ZkAttendance, ZkAttendanceList, ZkUserList};
+
+/// Poll interval used when `zk_subscribe` is given 0
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Connection settings for `zk_connect`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZkOptions {
+    /// Use UDP instead of TCP
+    pub udp: bool,
+    /// CommKey password, 0 if the device has none
+    pub password: u32,
+    /// Per-command timeout in milliseconds, 0 for the library default
+    pub timeout_ms: u32,
+}
+
+/// Called with each new attendance record
+///
+/// `record` is only valid for the duration of the call. Runs on a library
+/// thread, never the caller's.
+pub type ZkAttendanceCallback = Option<unsafe extern "C" fn(record: *const ZkAttendance, user_data: *mut c_void)>;
+
+/// Connected device (opaque)
+///
+/// Owns a small runtime that runs the connection and subscriptions; the
+/// `zk_*` functions may be called from any thread except a subscription
+/// callback.
+pub struct ZkDevice {
+    runtime: Runtime,
+    handle: DeviceHandle,
+    subscriptions: Mutex<HashMap<u64, JoinHandle<()>>>,
+    next_subscription: AtomicU64,
+}
+
+impl ZkDevice {
+    /// Stop a subscription and wait until its callback can no longer run
+    fn cancel(&self, task: JoinHandle<()>) {
+        task.abort();
+        let _ = self.runtime.block_on(task);
+    }
+}
+
+/// C callback and the pointer handed back to it
+struct Callback {
+    function: unsafe extern "C" fn(*const ZkAttendance, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// The caller promises `user_data` may be used from the library thread
+unsafe impl Send for Callback {}
+
+impl Callback {
+    fn call(&self, record: &ZkAttendance) {
+        unsafe { (self.function)(record, self.user_data) }
+    }
+}
+
+/// Connect to a device
+///
+/// `options` may be NULL for TCP without a password. On success `*out`
+/// receives a device to pass to the other functions and finally to
+/// `zk_disconnect`.
+///
+/// # Safety
+///
+/// `host` must be a NUL-terminated string, `options` NULL or valid, and
+/// `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk_connect(
+    host: *const c_char,
+    port: u16,
+    options: *const ZkOptions,
+    out: *mut *mut ZkDevice,
+) -> ZkStatus {
+    if host.is_null() || out.is_null() {
+        return fail(ZkStatus::InvalidArgument, "host and out must not be NULL");
+    }
+    let Ok(host) = unsafe { CStr::from_ptr(host) }.to_str() else {
+        return fail(ZkStatus::InvalidArgument, "host is not valid UTF-8");
+    };
+    let options = unsafe { options.as_ref() }.copied().unwrap_or_default();
+
+    let runtime = match Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("zkrust-ffi")
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => return fail(ZkStatus::Failed, format!("Failed to start runtime: {}", e)),
+    };
+
+    let mut device = if options.udp {
+        Device::new_udp(host, port)
+    } else {
+        Device::new(host, port)
+    };
+    if options.password != 0 {
+        device = device.with_password(options.password);
+    }
+    if options.timeout_ms != 0 {
+        device = device.with_timeout(Duration::from_millis(u64::from(options.timeout_ms)));
+    }
+
+    let handle = runtime.block_on(async move {
+        device.connect().await?;
+        Ok::<_, Error>(device.into_handle())
+    });
+    let handle = match handle {
+        Ok(handle) => handle,
+        Err(e) => return fail_with(e),
+    };
+
+    let device = Box::new(ZkDevice {
+        runtime,
+        handle,
+        subscriptions: Mutex::default(),
+        next_subscription: AtomicU64::new(1),
+    });
+    unsafe { *out = Box::into_raw(device) };
+    ZkStatus::Ok
+}
+
+/// Cancel all subscriptions, disconnect and free the device
+///
+/// # Safety
+///
+/// `device` must be NULL or a device from `zk_connect` that is not used
+/// afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk_disconnect(device: *mut ZkDevice) -> ZkStatus {
+    if device.is_null() {
+        return ZkStatus::Ok;
+    }
+    let device = unsafe { Box::from_raw(device) };
+
+    let tasks: Vec<_> = device.subscriptions.lock().unwrap().drain().map(|(_, task)| task).collect();
+    for task in tasks {
+        device.cancel(task);
+    }
+
+    match device.runtime.block_on(device.handle.disconnect()) {
+        Ok(()) => ZkStatus::Ok,
+        Err(e) => fail_with(e),
+    }
+}
+
+/// Download all users
+///
+/// Release the list with `zk_user_list_free`.
+///
+/// # Safety
+///
+/// `device` must come from `zk_connect` and `out` must be valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk_get_users(device: *const ZkDevice, out: *mut ZkUserList) -> ZkStatus {
+    let (Some(device), false) = (unsafe { device.as_ref() }, out.is_null()) else {
+        return fail(ZkStatus::InvalidArgument, "device and out must not be NULL");
+    };
+
+    match device.runtime.block_on(device.handle.get_users()) {
+        Ok(users) => {
+            unsafe { out.write(ZkUserList::new(&users)) };
+            ZkStatus::Ok
+        }
+        Err(e) => fail_with(e),
+    }
+}
+
+/// Download the attendance log
+///
+/// Release the list with `zk_attendance_list_free`.
+///
+/// # Safety
+///
+/// `device` must come from `zk_connect` and `out` must be valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk_get_attlog(device: *const ZkDevice, out: *mut ZkAttendanceList) -> ZkStatus {
+    let (Some(device), false) = (unsafe { device.as_ref() }, out.is_null()) else {
+        return fail(ZkStatus::InvalidArgument, "device and out must not be NULL");
+    };
+
+    match device.runtime.block_on(device.handle.get_attendance()) {
+        Ok(records) => {
+            unsafe { out.write(ZkAttendanceList::new(&records)) };
+            ZkStatus::Ok
+        }
+        Err(e) => fail_with(e),
+    }
+}
+
+/// Call `callback` for every attendance record punched from now on
+///
+/// The log is polled every `interval_ms` milliseconds (0 for 10 seconds);
+/// records already on the device are skipped. Poll failures are retried
+/// at the next interval, reconnecting if needed. `*id` receives the
+/// subscription ID for `zk_unsubscribe`.
+///
+/// # Safety
+///
+/// `device` must come from `zk_connect` and `id` must be valid.
+/// `user_data` must stay usable from another thread until the
+/// subscription is cancelled.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk_subscribe(
+    device: *const ZkDevice,
+    interval_ms: u32,
+    callback: ZkAttendanceCallback,
+    user_data: *mut c_void,
+    id: *mut u64,
+) -> ZkStatus {
+    let (Some(device), Some(function), false) = (unsafe { device.as_ref() }, callback, id.is_null()) else {
+        return fail(ZkStatus::InvalidArgument, "device, callback and id must not be NULL");
+    };
+
+    let interval = match interval_ms {
+        0 => DEFAULT_POLL_INTERVAL,
+        ms => Duration::from_millis(u64::from(ms)),
+    };
+    let callback = Callback { function, user_data };
+
+    let subscription = device.next_subscription.fetch_add(1, Ordering::Relaxed);
+    let task = device
+        .runtime
+        .spawn(poll_attendance(device.handle.clone(), interval, callback));
+    device.subscriptions.lock().unwrap().insert(subscription, task);
+
+    unsafe { *id = subscription };
+    ZkStatus::Ok
+}
+
+/// Cancel a subscription
+///
+/// Once this returns the callback is not running and won't be called
+/// again, so `user_data` may be freed.
+///
+/// # Safety
+///
+/// `device` must come from `zk_connect`. Must not be called from the
+/// subscription callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk_unsubscribe(device: *const ZkDevice, id: u64) -> ZkStatus {
+    let Some(device) = (unsafe { device.as_ref() }) else {
+        return fail(ZkStatus::InvalidArgument, "device must not be NULL");
+    };
+
+    let Some(task) = device.subscriptions.lock().unwrap().remove(&id) else {
+        return fail(ZkStatus::InvalidArgument, format!("No subscription {}", id));
+    };
+    device.cancel(task);
+    ZkStatus::Ok
+}
+
+/// Poll until cancelled, passing records not seen yet to `callback`
+async fn poll_attendance(handle: DeviceHandle, interval: Duration, callback: Callback) {
+    let mut cursor = Cursor::default();
+    let mut primed = false;
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        let records = match poll(&handle).await {
+            Ok(records) => cursor.filter_new(records),
+            Err(Error::HandleClosed) => {
+                debug!("Device closed, ending attendance subscription");
+                return;
+            }
+            Err(e) => {
+                warn!("Attendance poll failed: {}", e);
+                continue;
+            }
+        };
+        cursor.advance(&records);
+
+        if !primed {
+            // First poll only marks what is already on the device as seen
+            primed = true;
+            continue;
+        }
+
+        for record in &records {
+            callback.call(&ZkAttendance::from(record));
+        }
+    }
+}
+
+async fn poll(handle: &DeviceHandle) -> zkrust::Result<Vec<zkrust::AttendanceRecord>> {
+    if !handle.is_connected().await? {
+        handle.connect().await?;
+    }
+    handle.get_attendance().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CString;
+    use std::sync::mpsc;
+
+    use chrono::NaiveDate;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+    use zkrust_emulator::{Emulator, EmulatorHandle};
+    use zkrust_types::{AttendanceRecord, User};
+
+    use crate::types::{zk_attendance_list_free, zk_user_list_free};
+
+    fn record(user_id: &str, minute: u32) -> AttendanceRecord {
+        let time = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, minute, 0).unwrap();
+        AttendanceRecord::new(user_id, time, VerifyMode::Fingerprint, PunchType::CheckIn)
+    }
+
+    /// Emulator on its own runtime, kept alive alongside the handle
+    fn emulator() -> (Runtime, EmulatorHandle) {
+        let runtime = Builder::new_multi_thread().worker_threads(1).enable_all().build().unwrap();
+        let emulator = runtime.block_on(
+            Emulator::new()
+                .with_commkey(1234)
+                .with_user(User::builder(1, "42").name("Ann").build().unwrap())
+                .with_attendance(record("42", 0))
+                .bind_udp("127.0.0.1:0"),
+        );
+        (runtime, emulator.unwrap())
+    }
+
+    fn connect(emulator: &EmulatorHandle, password: u32) -> (ZkStatus, *mut ZkDevice) {
+        let host = CString::new("127.0.0.1").unwrap();
+        let options = ZkOptions {
+            udp: true,
+            password,
+            timeout_ms: 1000,
+        };
+        let mut device = std::ptr::null_mut();
+        let status = unsafe { zk_connect(host.as_ptr(), emulator.local_addr().port(), &options, &mut device) };
+        (status, device)
+    }
+
+    #[test]
+    fn test_get_users_and_attlog() {
+        let (_runtime, emulator) = emulator();
+        let (status, device) = connect(&emulator, 1234);
+        assert_eq!(status, ZkStatus::Ok);
+
+        let mut users = ZkUserList {
+            items: std::ptr::null_mut(),
+            len: 0,
+        };
+        assert_eq!(unsafe { zk_get_users(device, &mut users) }, ZkStatus::Ok);
+        assert_eq!(users.len, 1);
+        let user = unsafe { &*users.items };
+        assert_eq!(unsafe { CStr::from_ptr(user.user_id.as_ptr()) }.to_str().unwrap(), "42");
+        unsafe { zk_user_list_free(&mut users) };
+
+        let mut records = ZkAttendanceList {
+            items: std::ptr::null_mut(),
+            len: 0,
+        };
+        assert_eq!(unsafe { zk_get_attlog(device, &mut records) }, ZkStatus::Ok);
+        assert_eq!(records.len, 1);
+        let record = unsafe { &*records.items };
+        assert_eq!((record.year, record.month, record.day, record.hour), (2024, 3, 1, 8));
+        assert_eq!(record.verify_mode, 1);
+        unsafe { zk_attendance_list_free(&mut records) };
+
+        assert_eq!(unsafe { zk_disconnect(device) }, ZkStatus::Ok);
+    }
+
+    #[test]
+    fn test_connect_errors() {
+        let (_runtime, emulator) = emulator();
+
+        let (status, device) = connect(&emulator, 999);
+        assert_eq!(status, ZkStatus::AuthFailed);
+        assert!(device.is_null());
+        let message = unsafe { CStr::from_ptr(crate::error::zk_last_error()) };
+        assert!(message.to_str().unwrap().contains("Authentication"), "{:?}", message);
+
+        let mut device = std::ptr::null_mut();
+        let status = unsafe { zk_connect(std::ptr::null(), 4370, std::ptr::null(), &mut device) };
+        assert_eq!(status, ZkStatus::InvalidArgument);
+    }
+
+    unsafe extern "C" fn on_record(record: *const ZkAttendance, user_data: *mut c_void) {
+        let sender = unsafe { &*(user_data as *const mpsc::Sender<String>) };
+        let user_id = unsafe { CStr::from_ptr((*record).user_id.as_ptr()) };
+        sender.send(user_id.to_str().unwrap().to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let (_runtime, emulator) = emulator();
+        let (_, device) = connect(&emulator, 1234);
+
+        let (sender, received) = mpsc::channel::<String>();
+        let mut id = 0;
+        let user_data = &sender as *const _ as *mut c_void;
+        assert_eq!(unsafe { zk_subscribe(device, 50, Some(on_record), user_data, &mut id) }, ZkStatus::Ok);
+
+        // The record already on the device is not reported
+        std::thread::sleep(Duration::from_millis(200));
+        emulator.push_attendance(record("7", 5));
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(received.recv_timeout(timeout).unwrap(), "7");
+        assert!(received.try_recv().is_err());
+
+        assert_eq!(unsafe { zk_unsubscribe(device, id) }, ZkStatus::Ok);
+        assert_eq!(unsafe { zk_unsubscribe(device, id) }, ZkStatus::InvalidArgument);
+        emulator.push_attendance(record("8", 6));
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(received.try_recv().is_err());
+
+        assert_eq!(unsafe { zk_disconnect(device) }, ZkStatus::Ok);
+    }
+}
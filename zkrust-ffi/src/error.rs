@@ -0,0 +1,69 @@
+//! Status codes and the last-error message
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+
+use zkrust::Error;
+use zkrust_core::Error as CoreError;
+
+/// Result of a `zk_*` call
+///
+/// On failure the message is available from `zk_last_error` on the same
+/// thread.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZkStatus {
+    /// Success
+    Ok = 0,
+    /// NULL pointer or invalid string argument
+    InvalidArgument = 1,
+    /// Device unreachable or the connection dropped
+    Unavailable = 2,
+    /// Device did not answer in time
+    Timeout = 3,
+    /// Device rejected the CommKey password
+    AuthFailed = 4,
+    /// Device is not connected or was closed
+    NotConnected = 5,
+    /// Any other failure
+    Failed = 6,
+}
+
+impl From<&Error> for ZkStatus {
+    fn from(error: &Error) -> Self {
+        match error.root() {
+            Error::Core(CoreError::Timeout { .. }) | Error::Timeout { .. } | Error::OperationTimeout(_) => {
+                Self::Timeout
+            }
+            Error::Core(CoreError::AuthenticationRequired | CoreError::AuthenticationFailed) => Self::AuthFailed,
+            Error::Transport(_) | Error::DeviceBusy { .. } => Self::Unavailable,
+            Error::NotConnected | Error::HandleClosed => Self::NotConnected,
+            _ => Self::Failed,
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record `message` as this thread's last error and return `status`
+pub(crate) fn fail(status: ZkStatus, message: impl Into<String>) -> ZkStatus {
+    // Interior NULs would truncate the message in C; replace them
+    let message = message.into().replace('\0', " ");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+    status
+}
+
+/// Map a library error to its status, recording the message
+pub(crate) fn fail_with(error: Error) -> ZkStatus {
+    fail(ZkStatus::from(&error), error.to_string())
+}
+
+/// Message of the last failed call on this thread, or NULL
+///
+/// The string stays valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn zk_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null(), |message| message.as_ptr()))
+}
@@ -0,0 +1,47 @@
+//! # zkrust-ffi
+//!
+//! C ABI for zkrust, for C, C++, Delphi and other software built on the
+//! Windows-only official SDK. Builds as a shared (`cdylib`) and static
+//! library; the header is generated into `include/zkrust.h`.
+//!
+//! Every call returns a [`ZkStatus`]; on failure `zk_last_error()` gives
+//! the message. Lists returned by the library are released with their
+//! `_free` function.
+//!
+//! ## Quick Start
+//!
+//! ```c
+//! #include "zkrust.h"
+//!
+//! ZkOptions options = { .udp = true, .password = 0, .timeout_ms = 0 };
+//! ZkDevice *device;
+//! if (zk_connect("192.168.1.201", 4370, &options, &device) != ZK_STATUS_OK) {
+//!     fprintf(stderr, "%s\n", zk_last_error());
+//!     return 1;
+//! }
+//!
+//! ZkAttendanceList records;
+//! if (zk_get_attlog(device, &records) == ZK_STATUS_OK) {
+//!     for (size_t i = 0; i < records.len; i++)
+//!         printf("%s\n", records.items[i].user_id);
+//!     zk_attendance_list_free(&records);
+//! }
+//!
+//! zk_disconnect(device);
+//! ```
+
+use std::ffi::c_char;
+
+pub mod device;
+pub mod error;
+pub mod types;
+
+pub use device::{ZkAttendanceCallback, ZkDevice, ZkOptions};
+pub use error::ZkStatus;
+pub use types::{ZkAttendance, ZkAttendanceList, ZkUser, ZkUserList};
+
+/// Library version as a NUL-terminated string
+#[unsafe(no_mangle)]
+pub extern "C" fn zk_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
@@ -0,0 +1,211 @@
+//! C representations of users and attendance records
+//!
+//! Strings are fixed-size, NUL-terminated UTF-8 arrays sized for the
+//! largest value any firmware stores, so records can be copied around
+//! without ownership concerns. Lists are allocated by the library and must
+//! be released with their `_free` function.
+
+use std::ffi::c_char;
+
+use chrono::{Datelike, Timelike};
+use zkrust_types::{AttendanceRecord, User};
+
+/// Device user
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ZkUser {
+    /// Internal record index
+    pub uid: u16,
+    /// User ID shown on the device
+    pub user_id: [c_char; 25],
+    /// Display name
+    pub name: [c_char; 25],
+    /// Privilege level: 0 user, 2 enroller, 6 manager, 14 admin
+    pub privilege: u8,
+    /// Whether the user may verify
+    pub enabled: bool,
+    /// Numeric password, empty if unset
+    pub password: [c_char; 9],
+    /// Card number, 0 if unset
+    pub card: u32,
+    /// Group ID, empty for the default group
+    pub group_id: [c_char; 8],
+}
+
+impl From<&User> for ZkUser {
+    fn from(user: &User) -> Self {
+        Self {
+            uid: user.uid,
+            user_id: c_array(&user.user_id),
+            name: c_array(&user.name),
+            privilege: u8::from(user.privilege),
+            enabled: user.enabled,
+            password: c_array(&user.password),
+            card: user.card,
+            group_id: c_array(&user.group_id),
+        }
+    }
+}
+
+/// Attendance log entry
+///
+/// The time is the device's local time, without a timezone.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ZkAttendance {
+    /// User ID shown on the device
+    pub user_id: [c_char; 25],
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Verification mode code (1 fingerprint, 3 card, 15 face, ...)
+    pub verify_mode: u8,
+    /// Punch state code (0 check-in, 1 check-out, ...)
+    pub punch: u8,
+    /// Work code, 0 if none was entered
+    pub work_code: u32,
+}
+
+impl From<&AttendanceRecord> for ZkAttendance {
+    fn from(record: &AttendanceRecord) -> Self {
+        let time = record.timestamp;
+        Self {
+            user_id: c_array(&record.user_id),
+            year: time.year() as u16,
+            month: time.month() as u8,
+            day: time.day() as u8,
+            hour: time.hour() as u8,
+            minute: time.minute() as u8,
+            second: time.second() as u8,
+            verify_mode: u8::from(record.verify_mode),
+            punch: u8::from(record.punch),
+            work_code: record.work_code.unwrap_or(0),
+        }
+    }
+}
+
+/// Users returned by `zk_get_users`
+#[repr(C)]
+#[derive(Debug)]
+pub struct ZkUserList {
+    pub items: *mut ZkUser,
+    pub len: usize,
+}
+
+/// Records returned by `zk_get_attlog`
+#[repr(C)]
+#[derive(Debug)]
+pub struct ZkAttendanceList {
+    pub items: *mut ZkAttendance,
+    pub len: usize,
+}
+
+impl ZkUserList {
+    pub(crate) fn new(users: &[User]) -> Self {
+        let (items, len) = into_raw(users.iter().map(ZkUser::from).collect());
+        Self { items, len }
+    }
+}
+
+impl ZkAttendanceList {
+    pub(crate) fn new(records: &[AttendanceRecord]) -> Self {
+        let (items, len) = into_raw(records.iter().map(ZkAttendance::from).collect());
+        Self { items, len }
+    }
+}
+
+/// Release a list filled by `zk_get_users`
+///
+/// # Safety
+///
+/// `list` must be NULL or a list filled by `zk_get_users` that was not
+/// freed before.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk_user_list_free(list: *mut ZkUserList) {
+    if let Some(list) = unsafe { list.as_mut() } {
+        unsafe { free_raw(list.items, list.len) };
+        list.items = std::ptr::null_mut();
+        list.len = 0;
+    }
+}
+
+/// Release a list filled by `zk_get_attlog`
+///
+/// # Safety
+///
+/// `list` must be NULL or a list filled by `zk_get_attlog` that was not
+/// freed before.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk_attendance_list_free(list: *mut ZkAttendanceList) {
+    if let Some(list) = unsafe { list.as_mut() } {
+        unsafe { free_raw(list.items, list.len) };
+        list.items = std::ptr::null_mut();
+        list.len = 0;
+    }
+}
+
+/// Copy `value` into a NUL-terminated array, truncating at a char boundary
+fn c_array<const N: usize>(value: &str) -> [c_char; N] {
+    let mut end = value.len().min(N - 1);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let mut array = [0; N];
+    for (dst, src) in array.iter_mut().zip(&value.as_bytes()[..end]) {
+        *dst = *src as c_char;
+    }
+    array
+}
+
+fn into_raw<T>(items: Vec<T>) -> (*mut T, usize) {
+    let items = items.into_boxed_slice();
+    let len = items.len();
+    (Box::into_raw(items).cast::<T>(), len)
+}
+
+/// # Safety
+///
+/// `items` and `len` must come from one [`into_raw`] call.
+unsafe fn free_raw<T>(items: *mut T, len: usize) {
+    if !items.is_null() {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(items, len)) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_c_array_truncates() {
+        let array: [c_char; 5] = c_array("1234567");
+        assert_eq!(unsafe { CStr::from_ptr(array.as_ptr()) }.to_str().unwrap(), "1234");
+
+        // Never splits a multi-byte character
+        let array: [c_char; 4] = c_array("añb");
+        assert_eq!(unsafe { CStr::from_ptr(array.as_ptr()) }.to_str().unwrap(), "añ");
+        let array: [c_char; 3] = c_array("añb");
+        assert_eq!(unsafe { CStr::from_ptr(array.as_ptr()) }.to_str().unwrap(), "a");
+    }
+
+    #[test]
+    fn test_list_roundtrip() {
+        let users = vec![User::builder(1, "42").name("Ann").build().unwrap()];
+        let mut list = ZkUserList::new(&users);
+        assert_eq!(list.len, 1);
+
+        let user = unsafe { &*list.items };
+        assert_eq!(user.uid, 1);
+        assert_eq!(unsafe { CStr::from_ptr(user.name.as_ptr()) }.to_str().unwrap(), "Ann");
+
+        unsafe { zk_user_list_free(&mut list) };
+        assert!(list.items.is_null());
+        unsafe { zk_user_list_free(&mut list) };
+    }
+}
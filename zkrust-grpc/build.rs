@@ -0,0 +1,8 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use the vendored protoc so builds don't need one installed
+    let mut config = prost_build::Config::new();
+    config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+
+    tonic_prost_build::configure().compile_with_config(config, &["proto/zkrust.proto"], &["proto"])?;
+    Ok(())
+}
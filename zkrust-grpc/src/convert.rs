@@ -0,0 +1,176 @@
+//! Conversions between zkrust types and protobuf messages
+
+use chrono::NaiveDateTime;
+use tonic::Status;
+
+use zkrust::{Error, HealthReport};
+use zkrust_core::constants::Privilege;
+use zkrust_types::{AttendanceRecord, DeviceCapacity, DeviceInfo, User};
+
+use crate::proto;
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+pub(crate) fn format_time(time: NaiveDateTime) -> String {
+    time.format(TIME_FORMAT).to_string()
+}
+
+/// Map a device error to the closest gRPC status
+pub(crate) fn status(error: Error) -> Status {
+    let message = error.to_string();
+    match error.root() {
+        Error::NotConnected | Error::HandleClosed | Error::Transport(_) | Error::DeviceBusy { .. } => {
+            Status::unavailable(message)
+        }
+        Error::Core(zkrust_core::Error::Timeout { .. }) | Error::Timeout { .. } | Error::OperationTimeout(_) => {
+            Status::deadline_exceeded(message)
+        }
+        Error::ReadOnly { .. } => Status::permission_denied(message),
+        Error::NotSupported(_) => Status::unimplemented(message),
+        Error::CapacityExceeded { .. } => Status::resource_exhausted(message),
+        Error::Types(_) => Status::invalid_argument(message),
+        _ => Status::internal(message),
+    }
+}
+
+impl From<DeviceInfo> for proto::DeviceInfo {
+    fn from(info: DeviceInfo) -> Self {
+        Self {
+            serial_number: info.serial_number,
+            firmware_version: info.firmware_version,
+            model: info.model,
+            platform: info.platform,
+            device_name: info.device_name,
+            mac_address: info.mac_address,
+        }
+    }
+}
+
+impl From<DeviceCapacity> for proto::Capacity {
+    fn from(capacity: DeviceCapacity) -> Self {
+        Self {
+            users: capacity.users,
+            users_capacity: capacity.users_capacity,
+            fingers: capacity.fingers,
+            fingers_capacity: capacity.fingers_capacity,
+            records: capacity.records,
+            records_capacity: capacity.records_capacity,
+            cards: capacity.cards,
+            faces: capacity.faces,
+            faces_capacity: capacity.faces_capacity,
+        }
+    }
+}
+
+impl From<&HealthReport> for proto::Health {
+    fn from(report: &HealthReport) -> Self {
+        Self {
+            healthy: report.is_healthy(),
+            connected: report.connected,
+            authenticated: report.authenticated,
+            latency_ms: report.latency.map(|l| l.as_millis() as u64),
+            clock_skew_secs: report.clock_skew.map(|s| s.num_seconds()),
+            issues: report.issues.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+impl From<User> for proto::User {
+    fn from(user: User) -> Self {
+        Self {
+            uid: u32::from(user.uid),
+            user_id: user.user_id,
+            name: user.name,
+            privilege: u32::from(u8::from(user.privilege)),
+            enabled: user.enabled,
+            password: user.password,
+            card: user.card,
+            group_id: user.group_id,
+        }
+    }
+}
+
+impl TryFrom<proto::User> for User {
+    type Error = Status;
+
+    fn try_from(user: proto::User) -> Result<Self, Status> {
+        let uid = u16::try_from(user.uid)
+            .map_err(|_| Status::invalid_argument(format!("uid {} out of range", user.uid)))?;
+        let privilege = u8::try_from(user.privilege)
+            .ok()
+            .and_then(|code| Privilege::try_from(code).ok())
+            .ok_or_else(|| Status::invalid_argument(format!("unknown privilege {}", user.privilege)))?;
+
+        User::builder(uid, user.user_id)
+            .name(user.name)
+            .privilege(privilege)
+            .enabled(user.enabled)
+            .password(user.password)
+            .card(u64::from(user.card))
+            .group_id(user.group_id)
+            .build()
+            .map_err(|e| Status::invalid_argument(e.to_string()))
+    }
+}
+
+pub(crate) fn attendance_event(device_id: &str, record: AttendanceRecord) -> proto::AttendanceEvent {
+    proto::AttendanceEvent {
+        device_id: device_id.to_string(),
+        timestamp: format_time(record.timestamp),
+        verify_mode: u32::from(u8::from(record.verify_mode)),
+        verify_mode_name: record.verify_mode.name().to_string(),
+        punch: u32::from(u8::from(record.punch)),
+        punch_name: record.punch.name().to_string(),
+        work_code: record.work_code,
+        user_id: record.user_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    #[test]
+    fn test_user_roundtrip() {
+        let user = User::builder(7, "42")
+            .name("Ann")
+            .privilege(Privilege::Admin)
+            .card(123456)
+            .build()
+            .unwrap();
+
+        let message = proto::User::from(user.clone());
+        assert_eq!(message.privilege, 14);
+        assert_eq!(User::try_from(message).unwrap(), user);
+    }
+
+    #[test]
+    fn test_invalid_user_rejected() {
+        let mut message = proto::User::from(User::builder(1, "1").build().unwrap());
+        message.privilege = 3;
+        assert_eq!(User::try_from(message.clone()).unwrap_err().code(), Code::InvalidArgument);
+
+        message.privilege = 0;
+        message.uid = 70000;
+        assert_eq!(User::try_from(message).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_status_codes() {
+        assert_eq!(status(Error::NotConnected).code(), Code::Unavailable);
+        assert_eq!(status(Error::NotSupported("faces".into())).code(), Code::Unimplemented);
+        assert_eq!(
+            status(zkrust_core::Error::Timeout { seconds: 5 }.into()).code(),
+            Code::DeadlineExceeded
+        );
+
+        let timeout = Error::Timeout {
+            command: zkrust_core::Command::GetTime,
+            device: "192.168.1.201:4370".into(),
+            elapsed: std::time::Duration::from_secs(5),
+            attempt: 1,
+        };
+        assert_eq!(status(timeout).code(), Code::DeadlineExceeded);
+    }
+}
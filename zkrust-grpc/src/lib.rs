@@ -0,0 +1,41 @@
+//! # zkrust-grpc
+//!
+//! gRPC service for ZKTeco devices, built on tonic.
+//!
+//! [`ZkService`] exposes device management, user CRUD and a streaming
+//! attendance RPC for a set of registered devices. The protobuf
+//! definitions live in `proto/zkrust.proto`; the generated messages,
+//! server and client are in [`proto`].
+//!
+//! ## Quick Start
+//!
+//! ```no_run
+//! use zkrust::Device;
+//! use zkrust_grpc::ZkService;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let service = ZkService::new()
+//!         .with_device("front-door", Device::new("192.168.1.201", 4370).into_handle());
+//!
+//!     tonic::transport::Server::builder()
+//!         .add_service(service.into_server())
+//!         .serve("0.0.0.0:50051".parse()?)
+//!         .await?;
+//!     Ok(())
+//! }
+//! ```
+
+mod convert;
+mod service;
+
+/// Generated protobuf messages, server and client
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("zkrust.v1");
+}
+
+// Re-exports
+pub use proto::device_service_client::DeviceServiceClient;
+pub use proto::device_service_server::DeviceServiceServer;
+pub use service::ZkService;
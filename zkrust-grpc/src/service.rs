@@ -0,0 +1,309 @@
+//! `DeviceService` implementation
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tracing::{debug, warn};
+
+use zkrust::{DeviceHandle, Error};
+use zkrust_sync::Cursor;
+
+use crate::convert::{attendance_event, format_time, status};
+use crate::proto::device_service_server::{DeviceService, DeviceServiceServer};
+use crate::proto::{
+    AttendanceEvent, Capacity, DeviceInfo, DeviceRequest, DeviceTime, Empty, Health, ListDevicesRequest,
+    ListDevicesResponse, ListUsersResponse, SetUserRequest, StreamAttendanceRequest, User, UserRequest,
+};
+
+/// Events buffered per stream before polling waits for the client
+const STREAM_BUFFER: usize = 64;
+
+/// gRPC service over a set of registered devices
+///
+/// Devices are connected on first use and reconnected after they drop.
+#[derive(Debug, Clone)]
+pub struct ZkService {
+    devices: BTreeMap<String, DeviceHandle>,
+    poll_interval: Duration,
+}
+
+impl ZkService {
+    /// Create a service without devices
+    pub fn new() -> Self {
+        Self {
+            devices: BTreeMap::new(),
+            poll_interval: Duration::from_secs(30),
+        }
+    }
+
+    /// Register a device under `device_id`, replacing any previous one
+    pub fn add_device(&mut self, device_id: impl Into<String>, device: DeviceHandle) {
+        self.devices.insert(device_id.into(), device);
+    }
+
+    /// Builder form of [`add_device`](Self::add_device)
+    pub fn with_device(mut self, device_id: impl Into<String>, device: DeviceHandle) -> Self {
+        self.add_device(device_id, device);
+        self
+    }
+
+    /// Set the poll interval used when a stream request doesn't give one
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Wrap the service for [`tonic::transport::Server::add_service`]
+    pub fn into_server(self) -> DeviceServiceServer<Self> {
+        DeviceServiceServer::new(self)
+    }
+
+    /// Look up a device and make sure it is connected
+    async fn device(&self, device_id: &str) -> Result<DeviceHandle, Status> {
+        let device = self
+            .devices
+            .get(device_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("Unknown device {:?}", device_id)))?;
+
+        connect(&device).await.map_err(status)?;
+        Ok(device)
+    }
+}
+
+impl Default for ZkService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn connect(device: &DeviceHandle) -> zkrust::Result<()> {
+    if !device.is_connected().await? {
+        device.connect().await?;
+    }
+    Ok(())
+}
+
+#[tonic::async_trait]
+impl DeviceService for ZkService {
+    async fn list_devices(
+        &self,
+        _request: Request<ListDevicesRequest>,
+    ) -> Result<Response<ListDevicesResponse>, Status> {
+        Ok(Response::new(ListDevicesResponse {
+            device_ids: self.devices.keys().cloned().collect(),
+        }))
+    }
+
+    async fn get_device_info(&self, request: Request<DeviceRequest>) -> Result<Response<DeviceInfo>, Status> {
+        let device = self.device(&request.get_ref().device_id).await?;
+        let info = device.get_device_info().await.map_err(status)?;
+        Ok(Response::new(info.into()))
+    }
+
+    async fn get_capacity(&self, request: Request<DeviceRequest>) -> Result<Response<Capacity>, Status> {
+        let device = self.device(&request.get_ref().device_id).await?;
+        let capacity = device.get_capacity().await.map_err(status)?;
+        Ok(Response::new(capacity.into()))
+    }
+
+    async fn get_time(&self, request: Request<DeviceRequest>) -> Result<Response<DeviceTime>, Status> {
+        let device = self.device(&request.get_ref().device_id).await?;
+        let time = device.get_time().await.map_err(status)?;
+        Ok(Response::new(DeviceTime { time: format_time(time) }))
+    }
+
+    async fn health_check(&self, request: Request<DeviceRequest>) -> Result<Response<Health>, Status> {
+        // Report unreachable devices as unhealthy rather than failing the call
+        let device = self
+            .devices
+            .get(&request.get_ref().device_id)
+            .ok_or_else(|| Status::not_found(format!("Unknown device {:?}", request.get_ref().device_id)))?;
+        if let Err(e) = connect(device).await {
+            debug!("Health check connect failed: {}", e);
+        }
+
+        let report = device.health_check().await.map_err(status)?;
+        Ok(Response::new((&report).into()))
+    }
+
+    async fn enable_device(&self, request: Request<DeviceRequest>) -> Result<Response<Empty>, Status> {
+        let device = self.device(&request.get_ref().device_id).await?;
+        device.enable_device().await.map_err(status)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn disable_device(&self, request: Request<DeviceRequest>) -> Result<Response<Empty>, Status> {
+        let device = self.device(&request.get_ref().device_id).await?;
+        device.disable_device().await.map_err(status)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn restart(&self, request: Request<DeviceRequest>) -> Result<Response<Empty>, Status> {
+        let device = self.device(&request.get_ref().device_id).await?;
+        device.restart().await.map_err(status)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn list_users(&self, request: Request<DeviceRequest>) -> Result<Response<ListUsersResponse>, Status> {
+        let device = self.device(&request.get_ref().device_id).await?;
+        let users = device.get_users().await.map_err(status)?;
+        Ok(Response::new(ListUsersResponse {
+            users: users.into_iter().map(User::from).collect(),
+        }))
+    }
+
+    async fn get_user(&self, request: Request<UserRequest>) -> Result<Response<User>, Status> {
+        let request = request.into_inner();
+        let device = self.device(&request.device_id).await?;
+        let users = device.get_users().await.map_err(status)?;
+
+        users
+            .into_iter()
+            .find(|u| u32::from(u.uid) == request.uid)
+            .map(|u| Response::new(u.into()))
+            .ok_or_else(|| Status::not_found(format!("No user with uid {}", request.uid)))
+    }
+
+    async fn set_user(&self, request: Request<SetUserRequest>) -> Result<Response<Empty>, Status> {
+        let request = request.into_inner();
+        let user = request
+            .user
+            .ok_or_else(|| Status::invalid_argument("Missing user"))?
+            .try_into()?;
+
+        let device = self.device(&request.device_id).await?;
+        device.set_user(user).await.map_err(status)?;
+        device.refresh_data().await.map_err(status)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn delete_user(&self, request: Request<UserRequest>) -> Result<Response<Empty>, Status> {
+        let request = request.into_inner();
+        let uid = u16::try_from(request.uid)
+            .map_err(|_| Status::invalid_argument(format!("uid {} out of range", request.uid)))?;
+
+        let device = self.device(&request.device_id).await?;
+        device.delete_user(uid).await.map_err(status)?;
+        device.refresh_data().await.map_err(status)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    type StreamAttendanceStream = ReceiverStream<Result<AttendanceEvent, Status>>;
+
+    async fn stream_attendance(
+        &self,
+        request: Request<StreamAttendanceRequest>,
+    ) -> Result<Response<Self::StreamAttendanceStream>, Status> {
+        let request = request.into_inner();
+        let device = self.device(&request.device_id).await?;
+        let interval = match request.poll_interval_secs {
+            0 => self.poll_interval,
+            secs => Duration::from_secs(u64::from(secs)),
+        };
+
+        let (events, stream) = mpsc::channel(STREAM_BUFFER);
+        tokio::spawn(poll_attendance(
+            request.device_id,
+            device,
+            interval,
+            request.include_existing,
+            events,
+        ));
+
+        Ok(Response::new(ReceiverStream::new(stream)))
+    }
+}
+
+/// Poll a device until the client goes away, sending records not seen yet
+async fn poll_attendance(
+    device_id: String,
+    device: DeviceHandle,
+    interval: Duration,
+    include_existing: bool,
+    events: mpsc::Sender<Result<AttendanceEvent, Status>>,
+) {
+    let mut cursor = Cursor::default();
+    let mut primed = include_existing;
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = events.closed() => break,
+        }
+
+        let records = match connect(&device).await {
+            Ok(()) => device.get_attendance().await,
+            Err(e) => Err(e),
+        };
+        let records = match records {
+            Ok(records) => cursor.filter_new(records),
+            Err(Error::HandleClosed) => {
+                let _ = events.send(Err(status(Error::HandleClosed))).await;
+                break;
+            }
+            Err(e) => {
+                warn!("Attendance poll of {} failed: {}", device_id, e);
+                continue;
+            }
+        };
+        cursor.advance(&records);
+
+        if !primed {
+            // First poll only marks what is already on the device as seen
+            primed = true;
+            continue;
+        }
+
+        for record in records {
+            if events.send(Ok(attendance_event(&device_id, record))).await.is_err() {
+                debug!("Attendance stream of {} closed by client", device_id);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+    use zkrust::Device;
+
+    fn service() -> ZkService {
+        // Nothing listens on the discard port, so connecting fails fast
+        ZkService::new().with_device("door", Device::new("127.0.0.1", 9).into_handle())
+    }
+
+    #[tokio::test]
+    async fn test_list_devices() {
+        let response = service()
+            .list_devices(Request::new(ListDevicesRequest {}))
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().device_ids, vec!["door".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_device() {
+        let request = Request::new(DeviceRequest {
+            device_id: "gate".into(),
+        });
+        let err = service().get_device_info(request).await.unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_set_user_validates_before_connecting() {
+        let request = Request::new(SetUserRequest {
+            device_id: "door".into(),
+            user: None,
+        });
+        let err = service().set_user(request).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,178 @@
+//! ATTLOG uploads
+//!
+//! Each row is one punch, tab-separated:
+//!
+//! ```text
+//! PIN  YYYY-MM-DD hh:mm:ss  status  verify  workcode  reserved  reserved
+//! ```
+//!
+//! Newer firmware appends more fields (mask and temperature readings),
+//! which are ignored. Rows become the same [`AttendanceRecord`] the pull
+//! path produces, so sinks don't need to know how a record arrived.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use tracing::warn;
+
+use zkrust_core::constants::{PunchType, VerifyMode};
+use zkrust_types::AttendanceRecord;
+
+use crate::error::{Error, Result};
+use crate::protocol::rows;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// How ATTLOG status codes map to punch types
+///
+/// Codes 0-5 are the standard state keys. Devices without state keys, or
+/// where none was selected, send 255; sites with custom state keys send
+/// their own codes. Codes without a mapping use the default (check-in).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusMap {
+    codes: HashMap<u8, PunchType>,
+    default: PunchType,
+}
+
+impl Default for StatusMap {
+    fn default() -> Self {
+        let codes = (0..=5)
+            .filter_map(|code| PunchType::try_from(code).ok().map(|punch| (code, punch)))
+            .collect();
+        Self {
+            codes,
+            default: PunchType::CheckIn,
+        }
+    }
+}
+
+impl StatusMap {
+    /// Standard mapping
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map `code` to `punch`
+    pub fn with_status(mut self, code: u8, punch: PunchType) -> Self {
+        self.codes.insert(code, punch);
+        self
+    }
+
+    /// Set the punch type of unmapped codes
+    pub fn with_default(mut self, punch: PunchType) -> Self {
+        self.default = punch;
+        self
+    }
+
+    /// Punch type for `code`
+    pub fn punch(&self, code: u8) -> PunchType {
+        self.codes.get(&code).copied().unwrap_or(self.default)
+    }
+}
+
+/// Parse one ATTLOG row
+pub fn parse_row(row: &str, statuses: &StatusMap) -> Result<AttendanceRecord> {
+    let invalid = |reason: &str| Error::invalid_row("ATTLOG", row, reason);
+    let fields: Vec<&str> = row.split('\t').map(str::trim).collect();
+    if fields.len() < 4 {
+        return Err(invalid("expected at least 4 fields"));
+    }
+
+    let user_id = fields[0];
+    if user_id.is_empty() {
+        return Err(invalid("empty PIN"));
+    }
+    let timestamp = NaiveDateTime::parse_from_str(fields[1], TIME_FORMAT).map_err(|e| invalid(&e.to_string()))?;
+    let status: u8 = fields[2].parse().map_err(|_| invalid("status is not a number"))?;
+    let verify: u8 = fields[3].parse().map_err(|_| invalid("verify is not a number"))?;
+    let verify_mode = VerifyMode::try_from(verify).map_err(|e| invalid(&e.to_string()))?;
+
+    let mut record = AttendanceRecord::new(user_id, timestamp, verify_mode, statuses.punch(status));
+    // Work code 0 and an empty field both mean none was entered
+    if let Some(work_code) = fields.get(4).and_then(|field| field.parse().ok()).filter(|code| *code != 0) {
+        record = record.with_work_code(work_code);
+    }
+    Ok(record)
+}
+
+/// Parse an upload body, skipping (and logging) rows that don't parse
+pub(crate) fn parse(serial_number: &str, body: &str, statuses: &StatusMap) -> Vec<AttendanceRecord> {
+    rows(body)
+        .filter_map(|row| {
+            parse_row(row, statuses)
+                .inspect_err(|e| warn!("Skipping upload from {}: {}", serial_number, e))
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    fn at(hour: u32, minute: u32, second: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(hour, minute, second).unwrap()
+    }
+
+    #[test]
+    fn test_parse_row() {
+        let record = parse_row("1001\t2024-03-01 08:30:05\t1\t15\t0\t0\t0", &StatusMap::new()).unwrap();
+        assert_eq!(
+            record,
+            AttendanceRecord::new("1001", at(8, 30, 5), VerifyMode::Face, PunchType::CheckOut)
+        );
+
+        // Work code and the extra fields of newer firmware
+        let record = parse_row("7\t2024-03-01 17:00:00\t5\t1\t12\t0\t0\t0\t36.6", &StatusMap::new()).unwrap();
+        assert_eq!(record.punch, PunchType::OvertimeOut);
+        assert_eq!(record.work_code, Some(12));
+
+        // Older firmware stops after the verify field
+        let record = parse_row("7\t2024-03-01 17:00:00\t0\t3", &StatusMap::new()).unwrap();
+        assert_eq!(record.verify_mode, VerifyMode::Card);
+        assert_eq!(record.work_code, None);
+    }
+
+    #[test]
+    fn test_status_mapping() {
+        let statuses = StatusMap::new();
+        assert_eq!(statuses.punch(3), PunchType::BreakIn);
+        assert_eq!(statuses.punch(255), PunchType::CheckIn);
+
+        let statuses = StatusMap::new()
+            .with_status(8, PunchType::CheckOut)
+            .with_default(PunchType::CheckOut);
+        let record = parse_row("1\t2024-03-01 08:00:00\t8\t1", &statuses).unwrap();
+        assert_eq!(record.punch, PunchType::CheckOut);
+        assert_eq!(statuses.punch(255), PunchType::CheckOut);
+        assert_eq!(statuses.punch(0), PunchType::CheckIn);
+    }
+
+    #[test]
+    fn test_invalid_rows() {
+        let statuses = StatusMap::new();
+        for row in [
+            "1\t2024-03-01 08:00:00\t0",
+            "\t2024-03-01 08:00:00\t0\t1",
+            "1\t01/03/2024 08:00\t0\t1",
+            "1\t2024-03-01 08:00:00\tx\t1",
+            "1\t2024-03-01 08:00:00\t0\t300",
+        ] {
+            assert!(matches!(parse_row(row, &statuses), Err(Error::InvalidRow { .. })), "{:?}", row);
+        }
+
+        // Unknown verify codes are kept, not rejected
+        let record = parse_row("1\t2024-03-01 08:00:00\t0\t99", &statuses).unwrap();
+        assert_eq!(record.verify_mode, VerifyMode::Other(99));
+    }
+
+    #[test]
+    fn test_parse_skips_bad_rows() {
+        let body = "1\t2024-03-01 08:00:00\t0\t1\t0\t0\t0\r\nbroken\n2\t2024-03-01 08:01:00\t1\t1\t0\t0\t0\n";
+        let records = parse("A1", body, &StatusMap::new());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].user_id, "2");
+    }
+}
@@ -0,0 +1,90 @@
+//! ATTPHOTO uploads
+//!
+//! Each upload is one photo: a few header lines, a NUL byte, then the
+//! JPEG data.
+//!
+//! ```text
+//! PIN=20240301083005-1001.jpg
+//! SN=CEXJ201260001
+//! size=10240
+//! CMD=uploadphoto\0<image>
+//! ```
+//!
+//! The photo name carries the punch time and user ID (see
+//! [`AttendancePhoto`]).
+
+use zkrust_types::AttendancePhoto;
+
+use crate::error::{Error, Result};
+
+/// Parse a photo upload body
+pub fn parse_upload(body: &[u8]) -> Result<AttendancePhoto> {
+    let split = body.iter().position(|byte| *byte == 0);
+    let header = String::from_utf8_lossy(&body[..split.unwrap_or(body.len())]);
+    let invalid = |reason: &str| Error::invalid_row("ATTPHOTO", &header, reason);
+    let Some(split) = split else {
+        return Err(invalid("missing image data"));
+    };
+
+    let field = |key: &str| {
+        header
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.trim())
+    };
+
+    let mut data = &body[split + 1..];
+    if let Some(size) = field("size") {
+        let size: usize = size.parse().map_err(|_| invalid("size is not a number"))?;
+        if data.len() < size {
+            return Err(invalid(&format!("expected {} bytes of image data, got {}", size, data.len())));
+        }
+        data = &data[..size];
+    }
+    if data.is_empty() {
+        return Err(invalid("missing image data"));
+    }
+
+    let name = field("PIN").ok_or_else(|| invalid("missing PIN"))?;
+    AttendancePhoto::new(name, data).map_err(|e| invalid(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upload(header: &str, data: &[u8]) -> Vec<u8> {
+        let mut body = header.as_bytes().to_vec();
+        body.push(0);
+        body.extend_from_slice(data);
+        body
+    }
+
+    #[test]
+    fn test_parse_upload() {
+        let header = "PIN=20240301083005-1001.jpg\nSN=A1\nsize=4\nCMD=uploadphoto";
+        let photo = parse_upload(&upload(header, &[0xFF, 0xD8, 0xFF, 0xD9, 0x0A])).unwrap();
+        assert_eq!(photo.name, "20240301083005-1001.jpg");
+        assert_eq!(photo.user_id.as_deref(), Some("1001"));
+        assert_eq!(photo.data, [0xFF, 0xD8, 0xFF, 0xD9]);
+
+        // Without a size, everything after the header is the image
+        let photo = parse_upload(&upload("PIN=20240301083005.jpg\r\nCMD=uploadphoto", &[1, 2, 3])).unwrap();
+        assert_eq!(photo.user_id, None);
+        assert_eq!(photo.size(), 3);
+    }
+
+    #[test]
+    fn test_invalid_uploads() {
+        for body in [
+            b"PIN=20240301083005-1001.jpg\nsize=4".to_vec(),
+            upload("PIN=20240301083005-1001.jpg\nsize=10", &[1, 2]),
+            upload("PIN=20240301083005-1001.jpg", &[]),
+            upload("SN=A1\nsize=1", &[1]),
+            upload("PIN=photo.jpg", &[1]),
+        ] {
+            assert!(matches!(parse_upload(&body), Err(Error::InvalidRow { .. })), "{:?}", body);
+        }
+    }
+}
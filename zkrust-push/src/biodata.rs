@@ -0,0 +1,288 @@
+//! Biometric template lines
+//!
+//! Templates enrolled on the terminal are uploaded in one of three line
+//! formats, depending on firmware. Older firmware puts fingerprints and
+//! faces in the operation log:
+//!
+//! ```text
+//! FP PIN=1001\tFID=6\tSize=1024\tValid=1\tTMP=TThTUzIx...
+//! FACE PIN=1001\tFID=0\tSIZE=2048\tVALID=1\tTMP=AAAA...
+//! ```
+//!
+//! Newer firmware uploads every modality as rows of the BIODATA table,
+//! with `Type` 1 for fingerprints, 2 for infrared faces and 9 for
+//! visible-light faces:
+//!
+//! ```text
+//! BIODATA Pin=1001\tNo=6\tIndex=0\tValid=1\tDuress=0\tType=1\tMajorVer=10\tMinorVer=0\tFormat=0\tTmp=...
+//! ```
+//!
+//! Infrared face templates arrive in up to 12 parts (`FID` or `Index`),
+//! which [`merge_faces`] joins into one [`FaceTemplate`] per user, the way
+//! the pull protocol stores them.
+
+use std::collections::BTreeMap;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use zkrust_types::{FaceTemplate, FingerprintTemplate};
+
+use crate::error::{Error, Result};
+use crate::protocol::{pin_uid, Fields};
+
+/// Algorithm of templates in FP lines (ZKFinger VX10.0)
+const FP_ALGORITHM: u8 = 10;
+
+/// Algorithm of templates in FACE lines (ZKFace VX7.0)
+const FACE_ALGORITHM: u8 = 7;
+
+/// Part of a face template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FacePart {
+    /// Record index, from the PIN
+    pub uid: u16,
+
+    /// Position within the template
+    pub index: u8,
+
+    pub valid: bool,
+
+    pub algorithm_version: u8,
+
+    pub data: Vec<u8>,
+}
+
+/// Template from a BIODATA row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Biometric {
+    Fingerprint(FingerprintTemplate),
+    Face(FacePart),
+}
+
+/// Parse the fields of an `FP` line
+pub(crate) fn parse_fp(line: &str, rest: &str) -> Result<FingerprintTemplate> {
+    let invalid = |reason: &str| Error::invalid_row("FP", line, reason);
+    let fields = Fields::parse(rest);
+
+    let uid = pin_uid(fields.get("PIN")).ok_or_else(|| invalid("PIN does not fit a record index"))?;
+    let finger: u8 = fields.get("FID").parse().map_err(|_| invalid("FID is not a number"))?;
+    let flags = flag(fields.get("VALID"), FingerprintTemplate::FLAG_VALID).ok_or_else(|| invalid("invalid Valid"))?;
+    let data = template_data(fields.get("TMP")).map_err(|reason| invalid(&reason))?;
+
+    FingerprintTemplate::new(uid, finger, FP_ALGORITHM, data)
+        .map(|template| template.with_flags(flags))
+        .map_err(|e| invalid(&e.to_string()))
+}
+
+/// Parse the fields of a `FACE` line
+pub(crate) fn parse_face(line: &str, rest: &str) -> Result<FacePart> {
+    let invalid = |reason: &str| Error::invalid_row("FACE", line, reason);
+    let fields = Fields::parse(rest);
+
+    Ok(FacePart {
+        uid: pin_uid(fields.get("PIN")).ok_or_else(|| invalid("PIN does not fit a record index"))?,
+        index: fields.get("FID").parse().map_err(|_| invalid("FID is not a number"))?,
+        valid: flag(fields.get("VALID"), 1).ok_or_else(|| invalid("invalid VALID"))? != 0,
+        algorithm_version: FACE_ALGORITHM,
+        data: template_data(fields.get("TMP")).map_err(|reason| invalid(&reason))?,
+    })
+}
+
+/// Parse the fields of a `BIODATA` row, `None` for types other than
+/// fingerprints and faces
+pub(crate) fn parse_biodata(line: &str, rest: &str) -> Result<Option<Biometric>> {
+    let invalid = |reason: &str| Error::invalid_row("BIODATA", line, reason);
+    let fields = Fields::parse(rest);
+    let number = |key: &str| {
+        fields
+            .get(key)
+            .parse::<u8>()
+            .map_err(|_| invalid(&format!("{} is not a number", key)))
+    };
+
+    let kind = number("TYPE")?;
+    if !matches!(kind, 1 | 2 | 9) {
+        return Ok(None);
+    }
+    let uid = pin_uid(fields.get("PIN")).ok_or_else(|| invalid("PIN does not fit a record index"))?;
+    let valid = flag(fields.get("VALID"), 1).ok_or_else(|| invalid("invalid Valid"))? != 0;
+    let duress = flag(fields.get("DURESS"), 0).ok_or_else(|| invalid("invalid Duress"))? != 0;
+    let algorithm_version = number("MAJORVER")?;
+    let data = template_data(fields.get("TMP")).map_err(|reason| invalid(&reason))?;
+
+    if kind == 1 {
+        let mut flags = 0;
+        if valid {
+            flags |= FingerprintTemplate::FLAG_VALID;
+        }
+        if duress {
+            flags |= FingerprintTemplate::FLAG_DURESS;
+        }
+        return FingerprintTemplate::new(uid, number("NO")?, algorithm_version, data)
+            .map(|template| Some(Biometric::Fingerprint(template.with_flags(flags))))
+            .map_err(|e| invalid(&e.to_string()));
+    }
+
+    Ok(Some(Biometric::Face(FacePart {
+        uid,
+        index: number("INDEX")?,
+        valid,
+        algorithm_version,
+        data,
+    })))
+}
+
+/// Numeric flag field, `default` if missing
+fn flag(value: &str, default: u8) -> Option<u8> {
+    if value.is_empty() { Some(default) } else { value.parse().ok() }
+}
+
+fn template_data(value: &str) -> std::result::Result<Vec<u8>, String> {
+    let data = BASE64.decode(value).map_err(|e| e.to_string())?;
+    if data.is_empty() {
+        return Err("empty template".to_string());
+    }
+    Ok(data)
+}
+
+/// `FP` line fields of `template`, with the PIN of its user
+pub(crate) fn fingerprint_fields(user_id: &str, template: &FingerprintTemplate) -> String {
+    format!(
+        "PIN={}\tFID={}\tSize={}\tValid={}\tTMP={}",
+        user_id,
+        template.finger,
+        template.size(),
+        template.flags(),
+        BASE64.encode(&template.data),
+    )
+}
+
+/// Join face parts into one template per user, in index order
+///
+/// A part repeated in the same upload replaces the earlier one. The
+/// template is only valid if every part is.
+pub fn merge_faces(parts: &[FacePart]) -> Vec<FaceTemplate> {
+    let mut users: BTreeMap<u16, BTreeMap<u8, &FacePart>> = BTreeMap::new();
+    for part in parts {
+        users.entry(part.uid).or_default().insert(part.index, part);
+    }
+
+    users
+        .into_iter()
+        .map(|(uid, parts)| {
+            let first = parts.values().next().expect("at least one part");
+            let mut template = FaceTemplate::new(
+                uid,
+                first.algorithm_version,
+                parts.values().flat_map(|part| part.data.iter().copied()).collect::<Vec<u8>>(),
+            );
+            template.valid = parts.values().all(|part| part.valid);
+            template
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(line: &str) -> (&str, &str) {
+        line.split_once(' ').unwrap()
+    }
+
+    #[test]
+    fn test_parse_fp() {
+        let (kind, rest) = split("FP PIN=1001\tFID=6\tSize=8\tValid=3\tTMP=AQIDBAUGBwg=");
+        let template = parse_fp(kind, rest).unwrap();
+        assert_eq!((template.uid, template.finger), (1001, 6));
+        assert!(template.valid && template.duress);
+        assert_eq!(template.algorithm_version, 10);
+        assert_eq!(template.data, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let template = FingerprintTemplate::new(1001, 3, 10, [1, 2, 3, 4]).unwrap();
+        let line = format!("FP {}", fingerprint_fields("1001", &template));
+        assert_eq!(line, "FP PIN=1001\tFID=3\tSize=4\tValid=1\tTMP=AQIDBA==");
+        assert_eq!(parse_fp(&line, split(&line).1).unwrap(), template);
+    }
+
+    #[test]
+    fn test_parse_face() {
+        let line = "FACE PIN=7\tFID=11\tSIZE=2\tVALID=0\tTMP=AQI=";
+        let part = parse_face(line, split(line).1).unwrap();
+        assert_eq!(
+            part,
+            FacePart {
+                uid: 7,
+                index: 11,
+                valid: false,
+                algorithm_version: 7,
+                data: vec![1, 2],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_biodata() {
+        let line = "BIODATA Pin=1001\tNo=6\tIndex=0\tValid=1\tDuress=1\tType=1\tMajorVer=12\tMinorVer=0\tTmp=AQID";
+        let Some(Biometric::Fingerprint(template)) = parse_biodata(line, split(line).1).unwrap() else {
+            panic!("not a fingerprint");
+        };
+        assert_eq!((template.uid, template.finger, template.algorithm_version), (1001, 6, 12));
+        assert!(template.valid && template.duress);
+
+        let line = "BIODATA Pin=1001\tNo=0\tIndex=0\tValid=1\tDuress=0\tType=9\tMajorVer=40\tTmp=AQID";
+        let Some(Biometric::Face(part)) = parse_biodata(line, split(line).1).unwrap() else {
+            panic!("not a face");
+        };
+        assert_eq!((part.uid, part.algorithm_version), (1001, 40));
+
+        let line = "BIODATA Pin=1001\tNo=0\tIndex=0\tValid=1\tType=7\tMajorVer=1\tTmp=AQID";
+        assert_eq!(parse_biodata(line, split(line).1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        for line in [
+            "FP PIN=1\tFID=10\tTMP=AQID",
+            "FP PIN=1\tFID=0\tTMP=not base64!",
+            "FP PIN=1\tFID=0\tValid=x\tTMP=AQID",
+            "FP PIN=1\tFID=0\tTMP=",
+        ] {
+            assert!(matches!(parse_fp(line, split(line).1), Err(Error::InvalidRow { .. })), "{:?}", line);
+        }
+        for line in ["FACE PIN=0\tFID=0\tTMP=AQID", "FACE PIN=1\tTMP=AQID"] {
+            assert!(parse_face(line, split(line).1).is_err(), "{:?}", line);
+        }
+        for line in [
+            "BIODATA Pin=1\tNo=0\tIndex=0\tMajorVer=10\tTmp=AQID",
+            "BIODATA Pin=1\tNo=0\tIndex=0\tType=1\tTmp=AQID",
+            "BIODATA Pin=1\tNo=10\tIndex=0\tType=1\tMajorVer=10\tTmp=AQID",
+        ] {
+            assert!(parse_biodata(line, split(line).1).is_err(), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_merge_faces() {
+        let part = |uid, index, valid, data: &[u8]| FacePart {
+            uid,
+            index,
+            valid,
+            algorithm_version: 7,
+            data: data.to_vec(),
+        };
+        let faces = merge_faces(&[
+            part(2, 1, true, &[3]),
+            part(1, 0, true, &[9]),
+            part(2, 0, true, &[1, 2]),
+            part(2, 1, true, &[4]),
+            part(3, 0, false, &[5]),
+        ]);
+
+        assert_eq!(faces.len(), 3);
+        assert_eq!(faces[0], FaceTemplate::new(1, 7, vec![9]));
+        assert_eq!(faces[1], FaceTemplate::new(2, 7, vec![1, 2, 4]));
+        assert!(!faces[2].valid);
+    }
+}
@@ -0,0 +1,338 @@
+//! Server-to-device commands
+//!
+//! Push devices can't be contacted, so commands are queued per device and
+//! handed out the next time it polls `getrequest`, one per line:
+//!
+//! ```text
+//! C:12:DATA UPDATE USERINFO PIN=1001\tName=Ann\tPri=0\t...
+//! C:13:REBOOT
+//! ```
+//!
+//! After running them the device posts one reply per command to
+//! `devicecmd`. `Return` is 0 on success and a negative error code
+//! otherwise:
+//!
+//! ```text
+//! ID=12&Return=0&CMD=DATA
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::NaiveDateTime;
+
+use zkrust_types::time::encode_time;
+use zkrust_types::{FingerprintTemplate, User};
+
+use crate::biodata::fingerprint_fields;
+use crate::error::{Error, Result};
+use crate::operlog::user_fields;
+
+/// Finished commands kept for [`PushState::command`](crate::PushState::command)
+/// lookups; older ones are forgotten first
+const MAX_FINISHED: usize = 1024;
+
+/// Command for a push device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushCommand {
+    /// Add or replace a user (`DATA UPDATE USERINFO`)
+    UpdateUser(User),
+    /// Delete a user and their templates by PIN (`DATA DELETE USERINFO`)
+    DeleteUser(String),
+    /// Add or replace a fingerprint of the user with a PIN (`DATA UPDATE FINGERTMP`)
+    UpdateFingerprint {
+        user_id: String,
+        template: FingerprintTemplate,
+    },
+    /// Delete all attendance records (`CLEAR LOG`)
+    ClearLog,
+    /// Delete all users, templates and records (`CLEAR DATA`)
+    ClearData,
+    /// Restart the device (`REBOOT`)
+    Reboot,
+    /// Set the device clock, in device local time (`SET OPTIONS DateTime`)
+    SetTime(NaiveDateTime),
+    /// Upload device information (`INFO`)
+    Info,
+    /// Upload any records the server hasn't acknowledged (`CHECK`)
+    Check,
+    /// Any other command, sent verbatim
+    Raw(String),
+}
+
+impl PushCommand {
+    /// Command line as sent to the device, without the `C:<id>:` prefix
+    pub fn render(&self) -> Result<String> {
+        let line = match self {
+            Self::UpdateUser(user) => format!("DATA UPDATE USERINFO {}", user_fields(user)),
+            Self::DeleteUser(user_id) => format!("DATA DELETE USERINFO PIN={}", user_id),
+            Self::UpdateFingerprint { user_id, template } => {
+                format!("DATA UPDATE FINGERTMP {}", fingerprint_fields(user_id, template))
+            }
+            Self::ClearLog => "CLEAR LOG".to_string(),
+            Self::ClearData => "CLEAR DATA".to_string(),
+            Self::Reboot => "REBOOT".to_string(),
+            Self::SetTime(time) => {
+                let value = encode_time(time).map_err(|e| Error::InvalidCommand(e.to_string()))?;
+                format!("SET OPTIONS DateTime={}", value)
+            }
+            Self::Info => "INFO".to_string(),
+            Self::Check => "CHECK".to_string(),
+            Self::Raw(line) => line.clone(),
+        };
+
+        if line.trim().is_empty() || line.contains(['\r', '\n']) {
+            return Err(Error::InvalidCommand(format!("{:?}", line)));
+        }
+        Ok(line)
+    }
+}
+
+/// Where a queued command is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandState {
+    /// Waiting for the device to poll
+    Pending,
+    /// Handed to the device (UTC), no reply yet
+    Sent(NaiveDateTime),
+    /// Reply received (UTC)
+    Done {
+        return_code: i32,
+        at: NaiveDateTime,
+    },
+}
+
+/// Command queued for a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedCommand {
+    /// Identifier sent to and echoed back by the device
+    pub id: u64,
+
+    /// Device the command is for
+    pub serial_number: String,
+
+    pub command: PushCommand,
+
+    /// Time the command was queued (UTC)
+    pub queued_at: NaiveDateTime,
+
+    pub state: CommandState,
+}
+
+impl QueuedCommand {
+    /// Whether the device replied with success
+    pub fn succeeded(&self) -> bool {
+        matches!(self.state, CommandState::Done { return_code: 0, .. })
+    }
+}
+
+/// Reply to one command, posted to `devicecmd`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandReply {
+    pub id: u64,
+    pub return_code: i32,
+    /// Command name as echoed by the device (`DATA`, `REBOOT`, ...)
+    pub command: String,
+}
+
+impl fmt::Display for CommandReply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} #{} returned {}", self.command, self.id, self.return_code)
+    }
+}
+
+/// Parse one `devicecmd` reply line
+pub fn parse_reply(line: &str) -> Result<CommandReply> {
+    let invalid = |reason: &str| Error::invalid_row("devicecmd", line, reason);
+    let field = |key: &str| {
+        line.split('&')
+            .filter_map(|field| field.split_once('='))
+            .find(|(name, _)| name.trim() == key)
+            .map(|(_, value)| value.trim())
+    };
+
+    Ok(CommandReply {
+        id: field("ID").and_then(|id| id.parse().ok()).ok_or_else(|| invalid("missing ID"))?,
+        return_code: field("Return")
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| invalid("missing Return"))?,
+        command: field("CMD").unwrap_or_default().to_string(),
+    })
+}
+
+/// Commands of every device, by id
+#[derive(Debug, Default)]
+pub(crate) struct CommandQueue {
+    next_id: u64,
+    commands: BTreeMap<u64, QueuedCommand>,
+}
+
+impl CommandQueue {
+    /// Queue `command` for `serial_number`, returning its id
+    pub(crate) fn push(&mut self, serial_number: &str, command: PushCommand, now: NaiveDateTime) -> Result<u64> {
+        command.render()?;
+        self.next_id += 1;
+        let id = self.next_id;
+        self.commands.insert(
+            id,
+            QueuedCommand {
+                id,
+                serial_number: serial_number.to_string(),
+                command,
+                queued_at: now,
+                state: CommandState::Pending,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Pending commands of `serial_number` as a `getrequest` answer, marking them sent
+    pub(crate) fn take(&mut self, serial_number: &str, now: NaiveDateTime) -> Option<String> {
+        let mut body = String::new();
+        for queued in self.commands.values_mut() {
+            if queued.serial_number == serial_number && queued.state == CommandState::Pending {
+                // Validated when queued
+                let line = queued.command.render().unwrap_or_default();
+                body.push_str(&format!("C:{}:{}\n", queued.id, line));
+                queued.state = CommandState::Sent(now);
+            }
+        }
+        (!body.is_empty()).then_some(body)
+    }
+
+    /// Record a reply from `serial_number`, returning false for unknown commands
+    pub(crate) fn complete(&mut self, serial_number: &str, reply: &CommandReply, now: NaiveDateTime) -> bool {
+        let Some(queued) = self.commands.get_mut(&reply.id).filter(|queued| queued.serial_number == serial_number)
+        else {
+            return false;
+        };
+        queued.state = CommandState::Done {
+            return_code: reply.return_code,
+            at: now,
+        };
+        self.prune();
+        true
+    }
+
+    pub(crate) fn get(&self, id: u64) -> Option<QueuedCommand> {
+        self.commands.get(&id).cloned()
+    }
+
+    pub(crate) fn device(&self, serial_number: &str) -> Vec<QueuedCommand> {
+        self.commands
+            .values()
+            .filter(|queued| queued.serial_number == serial_number)
+            .cloned()
+            .collect()
+    }
+
+    fn prune(&mut self) {
+        let finished: Vec<u64> = self
+            .commands
+            .values()
+            .filter(|queued| matches!(queued.state, CommandState::Done { .. }))
+            .map(|queued| queued.id)
+            .collect();
+        for id in finished.iter().take(finished.len().saturating_sub(MAX_FINISHED)) {
+            self.commands.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+    use zkrust_core::constants::Privilege;
+
+    fn at(hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let user = User::builder(5, "1001").name("Ann").privilege(Privilege::Admin).build().unwrap();
+        assert_eq!(
+            PushCommand::UpdateUser(user).render().unwrap(),
+            "DATA UPDATE USERINFO PIN=1001\tName=Ann\tPri=14\tPasswd=\tCard=\tGrp=\tTZ=0000000000000000\tVerify=0"
+        );
+        assert_eq!(
+            PushCommand::DeleteUser("1001".into()).render().unwrap(),
+            "DATA DELETE USERINFO PIN=1001"
+        );
+        assert_eq!(PushCommand::ClearLog.render().unwrap(), "CLEAR LOG");
+        assert_eq!(PushCommand::Reboot.render().unwrap(), "REBOOT");
+
+        let time = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 30, 0).unwrap();
+        let line = PushCommand::SetTime(time).render().unwrap();
+        assert_eq!(line, format!("SET OPTIONS DateTime={}", encode_time(&time).unwrap()));
+    }
+
+    #[test]
+    fn test_render_rejects_invalid() {
+        let time = NaiveDate::from_ymd_opt(1999, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert!(matches!(PushCommand::SetTime(time).render(), Err(Error::InvalidCommand(_))));
+        assert!(PushCommand::Raw("INFO\nREBOOT".into()).render().is_err());
+        assert!(PushCommand::Raw(" ".into()).render().is_err());
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let reply = parse_reply("ID=12&Return=-1002&CMD=DATA").unwrap();
+        assert_eq!(
+            reply,
+            CommandReply {
+                id: 12,
+                return_code: -1002,
+                command: "DATA".into()
+            }
+        );
+        assert!(matches!(parse_reply("Return=0&CMD=INFO"), Err(Error::InvalidRow { .. })));
+        assert!(parse_reply("ID=1&CMD=INFO").is_err());
+    }
+
+    #[test]
+    fn test_queue() {
+        let mut queue = CommandQueue::default();
+        let reboot = queue.push("A1", PushCommand::Reboot, at(8)).unwrap();
+        let info = queue.push("B2", PushCommand::Info, at(8)).unwrap();
+        let clear = queue.push("A1", PushCommand::ClearLog, at(8)).unwrap();
+        assert!(queue.push("A1", PushCommand::Raw(String::new()), at(8)).is_err());
+
+        let body = queue.take("A1", at(9)).unwrap();
+        assert_eq!(body, format!("C:{}:REBOOT\nC:{}:CLEAR LOG\n", reboot, clear));
+        assert_eq!(queue.take("A1", at(9)), None);
+        assert_eq!(queue.get(reboot).unwrap().state, CommandState::Sent(at(9)));
+        assert_eq!(queue.get(info).unwrap().state, CommandState::Pending);
+
+        let reply = CommandReply {
+            id: reboot,
+            return_code: 0,
+            command: "REBOOT".into(),
+        };
+        // Only the device the command was for can complete it
+        assert!(!queue.complete("B2", &reply, at(10)));
+        assert!(queue.complete("A1", &reply, at(10)));
+        assert!(queue.get(reboot).unwrap().succeeded());
+        assert!(!queue.get(clear).unwrap().succeeded());
+        assert_eq!(queue.device("A1").len(), 2);
+    }
+
+    #[test]
+    fn test_queue_forgets_old_commands() {
+        let mut queue = CommandQueue::default();
+        for _ in 0..MAX_FINISHED + 5 {
+            let id = queue.push("A1", PushCommand::Info, at(8)).unwrap();
+            queue.take("A1", at(8));
+            let reply = CommandReply {
+                id,
+                return_code: 0,
+                command: "INFO".into(),
+            };
+            queue.complete("A1", &reply, at(8));
+        }
+        assert_eq!(queue.device("A1").len(), MAX_FINISHED);
+        assert!(queue.get(1).is_none());
+    }
+}
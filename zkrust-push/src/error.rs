@@ -0,0 +1,32 @@
+//! Push server error types
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid {table} row {row:?}: {reason}")]
+    InvalidRow {
+        table: &'static str,
+        row: String,
+        reason: String,
+    },
+
+    #[error("Invalid command: {0}")]
+    InvalidCommand(String),
+
+    #[error("Stamp store error: {0}")]
+    Stamps(String),
+
+    #[error("Sink error: {0}")]
+    Sink(#[from] zkrust_sync::Error),
+}
+
+impl Error {
+    pub(crate) fn invalid_row(table: &'static str, row: &str, reason: impl Into<String>) -> Self {
+        Self::InvalidRow {
+            table,
+            row: row.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! HTTP request handlers
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::slice;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, FromRequestParts, Query, State};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use tracing::{debug, info, warn};
+
+use crate::command::parse_reply;
+use crate::biodata::merge_faces;
+use crate::{attlog, attphoto, operlog};
+use crate::protocol::{device_options, rows, Table};
+use crate::state::Shared;
+
+/// Query and origin of a device request
+#[derive(Debug)]
+pub(crate) struct DeviceRequest {
+    serial_number: String,
+    query: HashMap<String, String>,
+    address: Option<IpAddr>,
+}
+
+impl DeviceRequest {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query.get(name).map(String::as_str)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for DeviceRequest {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Query(mut query) = Query::<HashMap<String, String>>::try_from_uri(&parts.uri)
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid query string"))?;
+        let serial_number = query
+            .remove("SN")
+            .filter(|sn| !sn.is_empty())
+            .ok_or((StatusCode::BAD_REQUEST, "Missing SN"))?;
+        // Only present when served with connect info, see `PushServer::bind`
+        let address = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(Self {
+            serial_number,
+            query,
+            address,
+        })
+    }
+}
+
+/// `GET /iclock/cdata`: registration, answered with the upload options
+pub(crate) async fn register(State(shared): State<Arc<Shared>>, request: DeviceRequest) -> String {
+    let push_version = request.param("pushver").map(str::to_string);
+    shared.touch(&request.serial_number, request.address, |device| {
+        if !device.registered {
+            info!(
+                "Push device {} registered from {:?} (push version {:?})",
+                request.serial_number, request.address, push_version
+            );
+        }
+        device.registered = true;
+        device.push_version = push_version;
+    });
+
+    let stamps = shared.stamps(&request.serial_number).await;
+    shared.options.render(&request.serial_number, &stamps)
+}
+
+/// `POST /iclock/cdata`: table upload
+///
+/// Answered with the number of rows accepted, after which the upload stamp
+/// is recorded. A failed sink write answers 500 so the device uploads the
+/// rows again after `ErrorDelay`.
+pub(crate) async fn upload(
+    State(shared): State<Arc<Shared>>,
+    request: DeviceRequest,
+    body: Bytes,
+) -> Result<String, (StatusCode, &'static str)> {
+    let serial_number = &request.serial_number;
+    let table = Table::parse(request.param("table").unwrap_or_default());
+
+    let count = match table {
+        Table::AttLog => {
+            let body = String::from_utf8_lossy(&body);
+            let records = attlog::parse(serial_number, &body, &shared.statuses);
+            if let Err(e) = shared.store_attendance(serial_number, &records).await {
+                warn!("Failed to store attendance from {}: {}", serial_number, e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "ERROR"));
+            }
+            records.len()
+        }
+        Table::OperLog | Table::BioData => {
+            let log = operlog::parse(serial_number, &String::from_utf8_lossy(&body));
+            for op in &log.operations {
+                debug!("{} operation {} by {} at {}", serial_number, op.code, op.admin, op.timestamp);
+            }
+            let faces = merge_faces(&log.faces);
+            if let Err(e) = shared
+                .store_enrollments(serial_number, &log.users, &log.fingerprints, &faces)
+                .await
+            {
+                warn!("Failed to store enrollments from {}: {}", serial_number, e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "ERROR"));
+            }
+            log.len()
+        }
+        Table::AttPhoto => match attphoto::parse_upload(&body) {
+            Ok(photo) => {
+                if let Err(e) = shared.store_photos(serial_number, slice::from_ref(&photo)).await {
+                    warn!("Failed to store photo from {}: {}", serial_number, e);
+                    return Err((StatusCode::INTERNAL_SERVER_ERROR, "ERROR"));
+                }
+                1
+            }
+            Err(e) => {
+                warn!("Skipping upload from {}: {}", serial_number, e);
+                0
+            }
+        },
+        Table::Options => {
+            let options: Vec<_> = device_options(&String::from_utf8_lossy(&body)).collect();
+            let count = options.len();
+            shared.touch(serial_number, request.address, |device| device.options.extend(options));
+            count
+        }
+        _ => rows(&String::from_utf8_lossy(&body)).count(),
+    };
+
+    // Older firmware sends the operation log position as OpStamp
+    let stamp = match table {
+        Table::OperLog => request.param("OpStamp").or(request.param("Stamp")),
+        _ => request.param("Stamp"),
+    };
+    if let Some(stamp) = stamp.filter(|stamp| !stamp.is_empty()) {
+        shared.record_stamp(serial_number, &table, stamp).await;
+    }
+
+    debug!("{} uploaded {} {} rows", serial_number, count, table);
+    shared.touch(serial_number, request.address, |device| {
+        device.rows_received += count as u64;
+    });
+
+    Ok(format!("OK: {}", count))
+}
+
+/// `GET /iclock/getrequest`: command poll, answered with the queued commands
+pub(crate) async fn get_request(State(shared): State<Arc<Shared>>, request: DeviceRequest) -> String {
+    shared.touch(&request.serial_number, request.address, |_| ());
+    match shared.take_commands(&request.serial_number) {
+        Some(commands) => {
+            debug!("Sending commands to {}: {:?}", request.serial_number, commands);
+            commands
+        }
+        None => "OK".to_string(),
+    }
+}
+
+/// `POST /iclock/devicecmd`: command results
+pub(crate) async fn device_cmd(State(shared): State<Arc<Shared>>, request: DeviceRequest, body: Bytes) -> &'static str {
+    let serial_number = &request.serial_number;
+    let body = String::from_utf8_lossy(&body);
+    // Replies to INFO and similar commands continue with lines of their own
+    for row in rows(&body).filter(|row| row.starts_with("ID=")) {
+        match parse_reply(row) {
+            Ok(reply) if shared.complete_command(serial_number, &reply) => {
+                debug!("{} command {}", serial_number, reply);
+            }
+            Ok(reply) => warn!("{} replied to unknown command {}", serial_number, reply),
+            Err(e) => warn!("Ignoring reply from {}: {}", serial_number, e),
+        }
+    }
+    shared.touch(serial_number, request.address, |_| ());
+    "OK"
+}
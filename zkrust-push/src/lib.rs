@@ -0,0 +1,67 @@
+//! # zkrust-push
+//!
+//! Server side of the ZKTeco HTTP push protocol ("ADMS" or "iclock"),
+//! spoken by cloud-connected devices.
+//!
+//! Instead of being polled over UDP/TCP port 4370, these devices open HTTP
+//! requests to a configured server: they register, upload attendance and
+//! operation logs as they happen, and poll for commands. That works for
+//! devices behind NAT or on networks the server can't reach. See
+//! [`protocol`] for the endpoints.
+//!
+//! Uploaded attendance is parsed into the same
+//! [`AttendanceRecord`](zkrust_types::AttendanceRecord) the pull path
+//! produces and handed to a [`zkrust_sync::Sink`], so existing sinks work
+//! unchanged. Users and templates enrolled on the terminal arrive in the
+//! operation log or BIODATA table and are upserted into the same sink (see
+//! [`operlog`] and [`biodata`]), as are attendance photos ([`attphoto`]).
+//!
+//! Commands (user changes, clearing logs, reboots, setting the clock) are
+//! queued with [`PushState::queue`] and delivered on the device's next
+//! poll; see [`command`].
+//!
+//! ## Quick Start
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use zkrust_push::{FileStampStore, PushServer};
+//! use zkrust_sync::MemorySink;
+//!
+//! #[tokio::main]
+//! async fn main() -> std::io::Result<()> {
+//!     // Point the device's "Cloud Server Setting" at this address
+//!     let server = PushServer::new()
+//!         .with_sink(MemorySink::new())
+//!         .with_stamp_store(FileStampStore::new("push-stamps.json"))
+//!         .bind("0.0.0.0:8081")
+//!         .await?;
+//!
+//!     loop {
+//!         tokio::time::sleep(Duration::from_secs(60)).await;
+//!         for device in server.devices() {
+//!             println!("{}: {} rows", device.serial_number, device.rows_received);
+//!         }
+//!     }
+//! }
+//! ```
+
+pub mod attlog;
+pub mod attphoto;
+pub mod biodata;
+pub mod command;
+pub mod error;
+mod handler;
+pub mod operlog;
+pub mod protocol;
+pub mod server;
+pub mod stamp;
+pub mod state;
+
+pub use attlog::StatusMap;
+pub use command::{CommandState, PushCommand, QueuedCommand};
+pub use error::{Error, Result};
+pub use operlog::{OperLogEntry, Operation};
+pub use protocol::{PushOptions, Table};
+pub use server::{PushHandle, PushServer, PushState};
+pub use stamp::{FileStampStore, MemoryStampStore, StampStore, Stamps};
+pub use state::DeviceStatus;
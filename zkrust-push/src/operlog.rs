@@ -0,0 +1,277 @@
+//! OPERLOG uploads
+//!
+//! Besides operation records, the operation log carries the user and
+//! template records of enrollments done on the terminal. Each line starts
+//! with its type, followed by tab-separated fields:
+//!
+//! ```text
+//! OPLOG 4\t0\t2024-03-01 08:00:00\t0\t0\t0\t0
+//! USER PIN=1001\tName=Ann\tPri=0\tPasswd=\tCard=\tGrp=1\tTZ=0000000000000000\tVerify=0
+//! FP PIN=1001\tFID=6\tSize=1024\tValid=1\tTMP=TThTUzIx...
+//! ```
+//!
+//! Template lines (`FP`, `FACE` and `BIODATA`) are described in
+//! [`biodata`](crate::biodata); BIODATA uploads are parsed the same way.
+//!
+//! The sync store keys users and templates by record index (`uid`), which
+//! push devices don't send, so the PIN stands in for it. PINs above 65535
+//! can't be stored that way and are skipped with a warning.
+
+use chrono::NaiveDateTime;
+use tracing::warn;
+
+use zkrust_core::constants::Privilege;
+use zkrust_types::{FingerprintTemplate, User};
+
+use crate::biodata::{self, Biometric, FacePart};
+use crate::error::{Error, Result};
+use crate::protocol::{pin_uid, rows, Fields};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Operation record (`OPLOG` line)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    /// Operation code (0 power on, 4 enter menu, 6 enroll fingerprint, ...)
+    pub code: u16,
+
+    /// PIN of the administrator who performed it, "0" for the device itself
+    pub admin: String,
+
+    /// Device local time
+    pub timestamp: NaiveDateTime,
+
+    /// Operation-specific values
+    pub objects: Vec<String>,
+}
+
+/// One line of an OPERLOG upload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperLogEntry {
+    Operation(Operation),
+    User(User),
+    Fingerprint(FingerprintTemplate),
+    Face(FacePart),
+    /// Line of a type this crate doesn't handle, kept verbatim
+    Other(String),
+}
+
+/// Parse one OPERLOG line
+pub fn parse_line(line: &str) -> Result<OperLogEntry> {
+    let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+    match kind {
+        "OPLOG" => parse_operation(line, rest).map(OperLogEntry::Operation),
+        "USER" => parse_user(line, rest).map(OperLogEntry::User),
+        "FP" => biodata::parse_fp(line, rest).map(OperLogEntry::Fingerprint),
+        "FACE" => biodata::parse_face(line, rest).map(OperLogEntry::Face),
+        "BIODATA" => Ok(match biodata::parse_biodata(line, rest)? {
+            Some(Biometric::Fingerprint(template)) => OperLogEntry::Fingerprint(template),
+            Some(Biometric::Face(part)) => OperLogEntry::Face(part),
+            None => OperLogEntry::Other(line.to_string()),
+        }),
+        _ => Ok(OperLogEntry::Other(line.to_string())),
+    }
+}
+
+fn parse_operation(line: &str, rest: &str) -> Result<Operation> {
+    let invalid = |reason: &str| Error::invalid_row("OPERLOG", line, reason);
+    let fields: Vec<&str> = rest.split('\t').map(str::trim).collect();
+    if fields.len() < 3 {
+        return Err(invalid("expected at least 3 fields"));
+    }
+
+    Ok(Operation {
+        code: fields[0].parse().map_err(|_| invalid("code is not a number"))?,
+        admin: fields[1].to_string(),
+        timestamp: NaiveDateTime::parse_from_str(fields[2], TIME_FORMAT)
+            .map_err(|e| invalid(&e.to_string()))?,
+        objects: fields[3..].iter().map(|field| field.to_string()).collect(),
+    })
+}
+
+fn parse_user(line: &str, rest: &str) -> Result<User> {
+    let invalid = |reason: &str| Error::invalid_row("USER", line, reason);
+    let fields = Fields::parse(rest);
+
+    let pin = fields.get("PIN");
+    let uid = pin_uid(pin).ok_or_else(|| invalid("PIN does not fit a record index"))?;
+    // Bit 0 of the privilege byte marks a disabled user, as in the pull protocol
+    let code: u8 = match fields.get("PRI") {
+        "" => 0,
+        value => value.parse().map_err(|_| invalid("Pri is not a number"))?,
+    };
+    let privilege = Privilege::try_from(code & !1).map_err(|e| invalid(&e.to_string()))?;
+    let card = parse_card(fields.get("CARD")).ok_or_else(|| invalid("invalid Card"))?;
+
+    User::builder(uid, pin)
+        .name(fields.get("NAME"))
+        .privilege(privilege)
+        .enabled(code & 1 == 0)
+        .password(fields.get("PASSWD"))
+        .card(u64::from(card))
+        .group_id(fields.get("GRP"))
+        .build()
+        .map_err(|e| invalid(&e.to_string()))
+}
+
+/// Card number, decimal or as bracketed little-endian hex bytes ("[0A0B0C0D00]")
+fn parse_card(card: &str) -> Option<u32> {
+    let Some(hex) = card.strip_prefix('[').and_then(|card| card.strip_suffix(']')) else {
+        return if card.is_empty() { Some(0) } else { card.parse().ok() };
+    };
+
+    let bytes = hex::decode(hex).ok()?;
+    let mut number = [0; 4];
+    for (dst, src) in number.iter_mut().zip(&bytes) {
+        *dst = *src;
+    }
+    Some(u32::from_le_bytes(number))
+}
+
+/// `USER` line fields of `user`, as sent in `DATA UPDATE USERINFO`
+pub(crate) fn user_fields(user: &User) -> String {
+    let card = if user.card == 0 {
+        String::new()
+    } else {
+        format!("[{}00]", hex::encode_upper(user.card.to_le_bytes()))
+    };
+    format!(
+        "PIN={}\tName={}\tPri={}\tPasswd={}\tCard={}\tGrp={}\tTZ=0000000000000000\tVerify=0",
+        user.user_id,
+        user.name,
+        u8::from(user.privilege) | u8::from(!user.enabled),
+        user.password,
+        card,
+        user.group_id,
+    )
+}
+
+/// Contents of an OPERLOG or BIODATA upload, by line type
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OperLog {
+    pub(crate) operations: Vec<Operation>,
+    pub(crate) users: Vec<User>,
+    pub(crate) fingerprints: Vec<FingerprintTemplate>,
+    pub(crate) faces: Vec<FacePart>,
+    pub(crate) other: Vec<String>,
+}
+
+impl OperLog {
+    /// Lines accepted
+    pub(crate) fn len(&self) -> usize {
+        self.operations.len() + self.users.len() + self.fingerprints.len() + self.faces.len() + self.other.len()
+    }
+}
+
+/// Parse an upload body, skipping (and logging) lines that don't parse
+pub(crate) fn parse(serial_number: &str, body: &str) -> OperLog {
+    let mut log = OperLog::default();
+    for line in rows(body) {
+        match parse_line(line) {
+            Ok(OperLogEntry::Operation(operation)) => log.operations.push(operation),
+            Ok(OperLogEntry::User(user)) => log.users.push(user),
+            Ok(OperLogEntry::Fingerprint(template)) => log.fingerprints.push(template),
+            Ok(OperLogEntry::Face(part)) => log.faces.push(part),
+            Ok(OperLogEntry::Other(line)) => log.other.push(line),
+            Err(e) => warn!("Skipping upload from {}: {}", serial_number, e),
+        }
+    }
+    log
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_operation() {
+        let entry = parse_line("OPLOG 6\t1\t2024-03-01 08:00:00\t1001\t6\t0\t0").unwrap();
+        let OperLogEntry::Operation(operation) = entry else {
+            panic!("not an operation");
+        };
+        assert_eq!(operation.code, 6);
+        assert_eq!(operation.admin, "1");
+        assert_eq!(operation.objects, ["1001", "6", "0", "0"]);
+    }
+
+    #[test]
+    fn test_parse_user() {
+        let line = "USER PIN=1001\tName=Ann Lee\tPri=14\tPasswd=123\tCard=[0A0B0C0D00]\tGrp=1\tVerify=0";
+        let OperLogEntry::User(user) = parse_line(line).unwrap() else {
+            panic!("not a user");
+        };
+        assert_eq!(user.uid, 1001);
+        assert_eq!(user.user_id, "1001");
+        assert_eq!(user.name, "Ann Lee");
+        assert_eq!(user.privilege, Privilege::Admin);
+        assert!(user.enabled);
+        assert_eq!(user.password, "123");
+        assert_eq!(user.card, 0x0D0C0B0A);
+        assert_eq!(user.group_id, "1");
+
+        // Disabled, decimal card
+        let OperLogEntry::User(user) = parse_line("USER PIN=7\tName=Bo\tPri=1\tCard=4660").unwrap() else {
+            panic!("not a user");
+        };
+        assert!(!user.enabled);
+        assert_eq!(user.privilege, Privilege::User);
+        assert_eq!(user.card, 4660);
+    }
+
+    #[test]
+    fn test_user_fields_round_trip() {
+        let user = User::builder(1001, "1001")
+            .name("Ann")
+            .privilege(Privilege::Manager)
+            .enabled(false)
+            .card(0x0D0C0B0A)
+            .group_id("2")
+            .build()
+            .unwrap();
+        let line = format!("USER {}", user_fields(&user));
+        assert!(line.contains("\tCard=[0A0B0C0D00]\t"));
+        assert_eq!(parse_line(&line).unwrap(), OperLogEntry::User(user));
+    }
+
+    #[test]
+    fn test_template_lines() {
+        let line = "FP PIN=1001\tFID=6\tSize=4\tValid=1\tTMP=AQIDBA==";
+        assert!(matches!(parse_line(line).unwrap(), OperLogEntry::Fingerprint(_)));
+        let line = "BIODATA Pin=1001\tNo=0\tIndex=3\tValid=1\tDuress=0\tType=2\tMajorVer=7\tTmp=AQIDBA==";
+        assert!(matches!(parse_line(line).unwrap(), OperLogEntry::Face(_)));
+        // Palm templates aren't handled
+        let line = "BIODATA Pin=1001\tNo=0\tIndex=0\tValid=1\tDuress=0\tType=8\tMajorVer=1\tTmp=AQIDBA==";
+        assert_eq!(parse_line(line).unwrap(), OperLogEntry::Other(line.into()));
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        for line in [
+            "OPLOG x\t0\t2024-03-01 08:00:00",
+            "USER PIN=70000\tName=Big",
+            "USER PIN=\tName=Nobody",
+            "USER PIN=1\tPri=4",
+            "USER PIN=1\tCard=[zz]",
+            "FP PIN=1\tFID=10\tTMP=AQID",
+            "FP PIN=1\tFID=0\tTMP=not base64!",
+        ] {
+            assert!(matches!(parse_line(line), Err(Error::InvalidRow { .. })), "{:?}", line);
+        }
+        assert_eq!(parse_line("USERPIC PIN=1").unwrap(), OperLogEntry::Other("USERPIC PIN=1".into()));
+    }
+
+    #[test]
+    fn test_parse_skips_bad_lines() {
+        let body = "OPLOG 4\t0\t2024-03-01 08:00:00\t0\t0\t0\t0\n\
+                    USER PIN=1\tName=A\n\
+                    USER PIN=x\n\
+                    FP PIN=1\tFID=0\tTMP=AQID\n\
+                    USERPIC PIN=1\n";
+        let log = parse("A1", body);
+        assert_eq!(log.operations.len(), 1);
+        assert_eq!(log.users.len(), 1);
+        assert_eq!(log.fingerprints.len(), 1);
+        assert_eq!(log.other, ["USERPIC PIN=1"]);
+        assert_eq!(log.len(), 4);
+    }
+}
@@ -0,0 +1,238 @@
+//! Push protocol messages
+//!
+//! Devices talk to the server with plain HTTP requests under `/iclock`,
+//! identifying themselves with the `SN` query parameter:
+//!
+//! | Request | Purpose | Answer |
+//! |---|---|---|
+//! | `GET cdata?SN=..&options=all` | Registration at start-up | Upload options |
+//! | `POST cdata?SN=..&table=..&Stamp=..` | Table rows, one per line | `OK: <rows>` |
+//! | `GET getrequest?SN=..` | Poll for commands every `Delay` seconds | Commands, or `OK` |
+//! | `POST devicecmd?SN=..` | Results of executed commands | `OK` |
+//!
+//! Older firmware appends `.aspx` to the endpoint names. See
+//! [`command`](crate::command) for the command and reply formats.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::stamp::Stamps;
+
+/// Upload flags requested from devices: every table, enrollments included
+const TRANS_FLAG: &str = "TransData AttLog OpLog AttPhoto EnrollUser ChgUser EnrollFP ChgFP UserPic";
+
+/// Table uploaded with `POST /iclock/cdata`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Table {
+    /// Attendance records
+    AttLog,
+    /// Operation log, with user and fingerprint records embedded
+    OperLog,
+    /// Attendance photos
+    AttPhoto,
+    /// Biometric templates of newer firmware
+    BioData,
+    /// Device option values
+    Options,
+    /// Any table this crate doesn't know
+    Other(String),
+}
+
+impl Table {
+    /// Parse the `table` query parameter
+    pub fn parse(name: &str) -> Self {
+        match name.to_ascii_uppercase().as_str() {
+            "ATTLOG" => Self::AttLog,
+            "OPERLOG" => Self::OperLog,
+            "ATTPHOTO" => Self::AttPhoto,
+            "BIODATA" => Self::BioData,
+            "OPTIONS" => Self::Options,
+            _ => Self::Other(name.to_string()),
+        }
+    }
+
+    /// Name as sent by devices
+    pub fn name(&self) -> &str {
+        match self {
+            Self::AttLog => "ATTLOG",
+            Self::OperLog => "OPERLOG",
+            Self::AttPhoto => "ATTPHOTO",
+            Self::BioData => "BIODATA",
+            Self::Options => "OPTIONS",
+            Self::Other(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Upload settings sent to devices when they register
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushOptions {
+    /// Interval between command polls (`Delay`)
+    pub delay: Duration,
+
+    /// Retry interval after a failed request (`ErrorDelay`)
+    pub error_delay: Duration,
+
+    /// Upload each record as it happens rather than in batches (`Realtime`)
+    pub realtime: bool,
+
+    /// Batch upload interval when not in real-time mode (`TransInterval`)
+    pub trans_interval: Duration,
+
+    /// Device timezone in hours from UTC (`TimeZone`), left as configured on
+    /// the device if unset
+    pub timezone: Option<i8>,
+}
+
+impl Default for PushOptions {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_secs(10),
+            error_delay: Duration::from_secs(30),
+            realtime: true,
+            trans_interval: Duration::from_secs(60),
+            timezone: None,
+        }
+    }
+}
+
+impl PushOptions {
+    /// Registration answer for `serial_number`, resuming uploads after `stamps`
+    pub(crate) fn render(&self, serial_number: &str, stamps: &Stamps) -> String {
+        let stamp = |stamp: &Option<String>| stamp.clone().unwrap_or_else(|| "None".to_string());
+        let mut lines = vec![
+            format!("GET OPTION FROM: {}", serial_number),
+            format!("ATTLOGStamp={}", stamp(&stamps.attlog)),
+            format!("OPERLOGStamp={}", stamp(&stamps.operlog)),
+            format!("ATTPHOTOStamp={}", stamp(&stamps.attphoto)),
+            format!("ErrorDelay={}", self.error_delay.as_secs().max(1)),
+            format!("Delay={}", self.delay.as_secs().max(1)),
+            "TransTimes=00:00;12:00".to_string(),
+            format!("TransInterval={}", (self.trans_interval.as_secs() / 60).max(1)),
+            format!("TransFlag={}", TRANS_FLAG),
+            format!("Realtime={}", u8::from(self.realtime)),
+            "Encrypt=None".to_string(),
+        ];
+        if let Some(timezone) = self.timezone {
+            lines.push(format!("TimeZone={}", timezone));
+        }
+
+        let mut body = lines.join("\n");
+        body.push('\n');
+        body
+    }
+}
+
+/// Non-empty lines of an upload body
+pub(crate) fn rows(body: &str) -> impl Iterator<Item = &str> {
+    body.lines().map(|line| line.trim_end_matches('\r')).filter(|line| !line.trim().is_empty())
+}
+
+/// Values of an OPTIONS upload
+///
+/// Devices report their settings and counters as `Key=Value` pairs
+/// separated by commas or line breaks; some keys start with `~`, which is
+/// dropped.
+pub(crate) fn device_options(body: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    body.split([',', '\n'])
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim().trim_start_matches('~').to_string(), value.trim().to_string()))
+        .filter(|(key, _)| !key.is_empty())
+}
+
+/// `Key=Value` fields of a table line, separated by tabs
+///
+/// Key case differs between firmware (`PIN`, `Pin`), so keys match
+/// case-insensitively.
+pub(crate) struct Fields<'a>(HashMap<String, &'a str>);
+
+impl<'a> Fields<'a> {
+    pub(crate) fn parse(fields: &'a str) -> Self {
+        let fields = fields
+            .split('\t')
+            .filter_map(|field| field.split_once('='))
+            .map(|(key, value)| (key.trim().to_ascii_uppercase(), value.trim()))
+            .collect();
+        Self(fields)
+    }
+
+    /// Value of `key` (upper case), empty if missing
+    pub(crate) fn get(&self, key: &str) -> &'a str {
+        self.0.get(key).copied().unwrap_or_default()
+    }
+}
+
+/// Record index standing in for a PIN
+///
+/// Push devices don't send record indexes, so PINs of 1-65535 are used
+/// in their place.
+pub(crate) fn pin_uid(pin: &str) -> Option<u16> {
+    pin.parse().ok().filter(|uid| *uid != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_parse() {
+        assert_eq!(Table::parse("ATTLOG"), Table::AttLog);
+        assert_eq!(Table::parse("operlog"), Table::OperLog);
+        assert_eq!(Table::parse("rtlog"), Table::Other("rtlog".into()));
+        assert_eq!(Table::parse("rtlog").to_string(), "rtlog");
+    }
+
+    #[test]
+    fn test_render_options() {
+        let options = PushOptions {
+            timezone: Some(3),
+            ..Default::default()
+        };
+        let body = options.render("CEXJ201260001", &Stamps::default());
+
+        assert!(body.starts_with("GET OPTION FROM: CEXJ201260001\nATTLOGStamp=None\n"));
+        assert!(body.contains("\nDelay=10\n"));
+        assert!(body.contains("\nRealtime=1\n"));
+        assert!(body.contains("\nTransInterval=1\n"));
+        assert!(body.ends_with("TimeZone=3\n"));
+
+        let stamps = Stamps {
+            operlog: Some("42".into()),
+            ..Default::default()
+        };
+        let body = PushOptions::default().render("A1", &stamps);
+        assert!(body.contains("\nATTLOGStamp=None\nOPERLOGStamp=42\nATTPHOTOStamp=None\n"));
+    }
+
+    #[test]
+    fn test_fields() {
+        let fields = Fields::parse("Pin=1\tNAME=A=B\t broken \tTmp=AQ==");
+        assert_eq!(fields.get("PIN"), "1");
+        assert_eq!(fields.get("NAME"), "A=B");
+        assert_eq!(fields.get("TMP"), "AQ==");
+        assert_eq!(fields.get("CARD"), "");
+    }
+
+    #[test]
+    fn test_device_options() {
+        let body = "~DeviceName=SpeedFace-V5L,MAC=00:17:61:12:34:56,\r\nUserCount=12,FWVersion=Ver 8.0.4.2\n";
+        let options: Vec<_> = device_options(body).collect();
+        assert_eq!(options[0], ("DeviceName".into(), "SpeedFace-V5L".into()));
+        assert_eq!(options[1], ("MAC".into(), "00:17:61:12:34:56".into()));
+        assert_eq!(options[3], ("FWVersion".into(), "Ver 8.0.4.2".into()));
+        assert_eq!(options.len(), 4);
+    }
+
+    #[test]
+    fn test_rows() {
+        let rows: Vec<_> = rows("1\t2024\r\n\r\n2\t2024\n  \n").collect();
+        assert_eq!(rows, ["1\t2024", "2\t2024"]);
+    }
+}
@@ -0,0 +1,600 @@
+//! Server builder and running instances
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::DefaultBodyLimit;
+use axum::routing::get;
+use axum::Router;
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use zkrust_sync::Sink;
+
+use crate::attlog::StatusMap;
+use crate::command::{PushCommand, QueuedCommand};
+use crate::error::Result;
+use crate::handler;
+use crate::protocol::PushOptions;
+use crate::stamp::{MemoryStampStore, StampStore};
+use crate::state::{DeviceStatus, Shared};
+
+/// Largest accepted upload (photo uploads are the big ones)
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+/// Push protocol server, configured before it starts serving
+///
+/// Uploaded attendance goes to the [`Sink`] set with
+/// [`with_sink`](Self::with_sink), keyed by device serial number; without
+/// one it is only counted. Upload stamps are kept in memory unless a
+/// [`StampStore`] is set with [`with_stamp_store`](Self::with_stamp_store),
+/// so after a restart devices upload their whole log again.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use zkrust_push::PushServer;
+/// use zkrust_sync::MemorySink;
+///
+/// # async fn example() -> std::io::Result<()> {
+/// let server = PushServer::new()
+///     .with_sink(MemorySink::new())
+///     .with_delay(Duration::from_secs(30))
+///     .bind("0.0.0.0:8081")
+///     .await?;
+///
+/// for device in server.devices() {
+///     println!("{} last seen {}", device.serial_number, device.last_seen);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct PushServer {
+    options: PushOptions,
+    statuses: StatusMap,
+    sink: Option<Box<dyn Sink>>,
+    stamps: Box<dyn StampStore>,
+}
+
+impl Default for PushServer {
+    fn default() -> Self {
+        Self {
+            options: PushOptions::default(),
+            statuses: StatusMap::default(),
+            sink: None,
+            stamps: Box::new(MemoryStampStore::new()),
+        }
+    }
+}
+
+impl PushServer {
+    /// Create a server with default upload options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver uploaded records to `sink`
+    pub fn with_sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sink = Some(Box::new(sink));
+        self
+    }
+
+    /// Persist upload stamps in `store`, so devices resume where they left
+    /// off after a restart
+    pub fn with_stamp_store(mut self, store: impl StampStore + 'static) -> Self {
+        self.stamps = Box::new(store);
+        self
+    }
+
+    /// Set how ATTLOG status codes map to punch types
+    pub fn with_status_map(mut self, statuses: StatusMap) -> Self {
+        self.statuses = statuses;
+        self
+    }
+
+    /// Set all upload options at once
+    pub fn with_options(mut self, options: PushOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set how often devices poll for commands (default: 10 seconds)
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.options.delay = delay;
+        self
+    }
+
+    /// Set how long devices wait after a failed request (default: 30 seconds)
+    pub fn with_error_delay(mut self, delay: Duration) -> Self {
+        self.options.error_delay = delay;
+        self
+    }
+
+    /// Upload each record immediately (default) or in batches
+    pub fn with_realtime(mut self, realtime: bool) -> Self {
+        self.options.realtime = realtime;
+        self
+    }
+
+    /// Set the timezone devices are told to use, in hours from UTC
+    pub fn with_timezone(mut self, hours: i8) -> Self {
+        self.options.timezone = Some(hours);
+        self
+    }
+
+    /// Routes for mounting in an existing axum application
+    ///
+    /// Device addresses are only recorded when the application is served
+    /// with `into_make_service_with_connect_info::<SocketAddr>()`.
+    pub fn into_router(self) -> (Router, PushState) {
+        let shared = Shared::new(self.options, self.statuses, self.sink, self.stamps);
+        (router(shared.clone()), PushState { shared })
+    }
+
+    /// Listen on `addr`
+    pub async fn bind(self, addr: impl ToSocketAddrs) -> io::Result<PushHandle> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+
+        let (router, state) = self.into_router();
+        let service = router.into_make_service_with_connect_info::<SocketAddr>();
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, service).await {
+                warn!("Push server stopped: {}", e);
+            }
+        });
+
+        info!("Push server listening on http://{}", local_addr);
+        Ok(PushHandle { local_addr, state, task })
+    }
+}
+
+impl fmt::Debug for PushServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PushServer")
+            .field("options", &self.options)
+            .field("statuses", &self.statuses)
+            .field("sink", &self.sink.is_some())
+            .finish()
+    }
+}
+
+fn router(shared: Arc<Shared>) -> Router {
+    Router::new()
+        .route("/iclock/cdata", get(handler::register).post(handler::upload))
+        .route("/iclock/cdata.aspx", get(handler::register).post(handler::upload))
+        .route("/iclock/getrequest", get(handler::get_request))
+        .route("/iclock/getrequest.aspx", get(handler::get_request))
+        .route("/iclock/devicecmd", get(handler::get_request).post(handler::device_cmd))
+        .route("/iclock/devicecmd.aspx", get(handler::get_request).post(handler::device_cmd))
+        .layer(DefaultBodyLimit::max(MAX_BODY))
+        .with_state(shared)
+}
+
+/// Status of the devices talking to a server, and their command queues
+#[derive(Debug, Clone)]
+pub struct PushState {
+    shared: Arc<Shared>,
+}
+
+impl PushState {
+    /// Every device seen since the server started, by serial number
+    pub fn devices(&self) -> Vec<DeviceStatus> {
+        self.shared.devices()
+    }
+
+    /// Status of one device, if it has been seen
+    pub fn device(&self, serial_number: &str) -> Option<DeviceStatus> {
+        self.shared.device(serial_number)
+    }
+
+    /// Queue `command` for `serial_number`, returning its id
+    ///
+    /// The device receives it on its next command poll, which may be the
+    /// first one if it hasn't been seen yet.
+    pub fn queue(&self, serial_number: &str, command: PushCommand) -> Result<u64> {
+        self.shared.queue(serial_number, command)
+    }
+
+    /// Queued command by id
+    ///
+    /// Only the most recent finished commands are kept.
+    pub fn command(&self, id: u64) -> Option<QueuedCommand> {
+        self.shared.command(id)
+    }
+
+    /// Commands queued for one device, oldest first
+    pub fn commands(&self, serial_number: &str) -> Vec<QueuedCommand> {
+        self.shared.commands(serial_number)
+    }
+}
+
+/// Running server
+///
+/// Dropping the handle stops the server.
+#[derive(Debug)]
+pub struct PushHandle {
+    local_addr: SocketAddr,
+    state: PushState,
+    task: JoinHandle<()>,
+}
+
+impl PushHandle {
+    /// Address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Device status, usable after the handle is dropped
+    pub fn state(&self) -> PushState {
+        self.state.clone()
+    }
+
+    /// Every device seen since the server started, by serial number
+    pub fn devices(&self) -> Vec<DeviceStatus> {
+        self.state.devices()
+    }
+
+    /// Status of one device, if it has been seen
+    pub fn device(&self, serial_number: &str) -> Option<DeviceStatus> {
+        self.state.device(serial_number)
+    }
+
+    /// Queue `command` for `serial_number`, see [`PushState::queue`]
+    pub fn queue(&self, serial_number: &str, command: PushCommand) -> Result<u64> {
+        self.state.queue(serial_number, command)
+    }
+
+    /// Queued command by id
+    pub fn command(&self, id: u64) -> Option<QueuedCommand> {
+        self.state.command(id)
+    }
+
+    /// Commands queued for one device, oldest first
+    pub fn commands(&self, serial_number: &str) -> Vec<QueuedCommand> {
+        self.state.commands(serial_number)
+    }
+}
+
+impl Drop for PushHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Method, Request, StatusCode};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tower::ServiceExt;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+    use zkrust_types::{AttendancePhoto, AttendanceRecord, FaceTemplate, FingerprintTemplate, User};
+
+    use crate::command::CommandState;
+    use crate::stamp::FileStampStore;
+
+    type Writes = Vec<(String, Vec<AttendanceRecord>)>;
+
+    /// Sink recording writes, or failing them all
+    #[derive(Clone, Default)]
+    struct Recorder {
+        writes: Arc<Mutex<Writes>>,
+        users: Arc<Mutex<Vec<User>>>,
+        fingerprints: Arc<Mutex<Vec<FingerprintTemplate>>>,
+        faces: Arc<Mutex<Vec<FaceTemplate>>>,
+        photos: Arc<Mutex<Vec<AttendancePhoto>>>,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for Recorder {
+        async fn write_attendance(&mut self, device_id: &str, records: &[AttendanceRecord]) -> zkrust_sync::Result<()> {
+            if self.fail {
+                return Err(zkrust_sync::Error::Sink("disk full".into()));
+            }
+            self.writes.lock().unwrap().push((device_id.to_string(), records.to_vec()));
+            Ok(())
+        }
+
+        async fn update_users(&mut self, _device_id: &str, users: &[User]) -> zkrust_sync::Result<()> {
+            if self.fail {
+                return Err(zkrust_sync::Error::Sink("disk full".into()));
+            }
+            self.users.lock().unwrap().extend_from_slice(users);
+            Ok(())
+        }
+
+        async fn write_fingerprints(
+            &mut self,
+            _device_id: &str,
+            templates: &[FingerprintTemplate],
+        ) -> zkrust_sync::Result<()> {
+            self.fingerprints.lock().unwrap().extend_from_slice(templates);
+            Ok(())
+        }
+
+        async fn write_faces(&mut self, _device_id: &str, templates: &[FaceTemplate]) -> zkrust_sync::Result<()> {
+            self.faces.lock().unwrap().extend_from_slice(templates);
+            Ok(())
+        }
+
+        async fn write_photos(&mut self, _device_id: &str, photos: &[AttendancePhoto]) -> zkrust_sync::Result<()> {
+            if self.fail {
+                return Err(zkrust_sync::Error::Sink("disk full".into()));
+            }
+            self.photos.lock().unwrap().extend_from_slice(photos);
+            Ok(())
+        }
+    }
+
+    async fn call(router: &Router, method: Method, uri: &str, body: &str) -> (StatusCode, String) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_registration() {
+        let (router, state) = PushServer::new().with_delay(Duration::from_secs(5)).into_router();
+
+        let uri = "/iclock/cdata?SN=CEXJ201260001&options=all&pushver=2.4.1&language=69";
+        let (status, body) = call(&router, Method::GET, uri, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("GET OPTION FROM: CEXJ201260001\n"));
+        assert!(body.contains("\nDelay=5\n"));
+
+        let device = state.device("CEXJ201260001").unwrap();
+        assert!(device.registered);
+        assert_eq!(device.push_version.as_deref(), Some("2.4.1"));
+    }
+
+    #[tokio::test]
+    async fn test_stamps_survive_restart() {
+        let path = std::env::temp_dir().join(format!("zkrust-push-stamps-{}.json", std::process::id()));
+        let (router, _) = PushServer::new().with_stamp_store(FileStampStore::new(&path)).into_router();
+
+        let uri = "/iclock/cdata?SN=A1&table=ATTLOG&Stamp=9999";
+        call(&router, Method::POST, uri, "1\t2024-03-01 08:00:00\t0\t1\n").await;
+        let uri = "/iclock/cdata?SN=A1&table=OPERLOG&OpStamp=42";
+        call(&router, Method::POST, uri, "OPLOG 4\t0\t2024-03-01 08:00:00\t0\t0\t0\t0\n").await;
+
+        // A failed write keeps the previous stamp
+        let failing = Recorder {
+            fail: true,
+            ..Default::default()
+        };
+        let (router, _) = PushServer::new()
+            .with_sink(failing)
+            .with_stamp_store(FileStampStore::new(&path))
+            .into_router();
+        let uri = "/iclock/cdata?SN=A1&table=ATTLOG&Stamp=10000";
+        call(&router, Method::POST, uri, "1\t2024-03-01 08:01:00\t0\t1\n").await;
+
+        let (router, _) = PushServer::new().with_stamp_store(FileStampStore::new(&path)).into_router();
+        let (_, body) = call(&router, Method::GET, "/iclock/cdata?SN=A1&options=all", "").await;
+        assert!(body.contains("\nATTLOGStamp=9999\nOPERLOGStamp=42\nATTPHOTOStamp=None\n"), "{}", body);
+        let (_, body) = call(&router, Method::GET, "/iclock/cdata?SN=B2&options=all", "").await;
+        assert!(body.contains("\nATTLOGStamp=None\n"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_options_upload() {
+        let (router, state) = PushServer::new().into_router();
+
+        let body = "~DeviceName=SpeedFace-V5L,UserCount=12,FWVersion=Ver 8.0.4.2";
+        let uri = "/iclock/cdata?SN=A1&table=options";
+        assert_eq!(call(&router, Method::POST, uri, body).await, (StatusCode::OK, "OK: 3".into()));
+
+        let options = state.device("A1").unwrap().options;
+        assert_eq!(options["DeviceName"], "SpeedFace-V5L");
+        assert_eq!(options["UserCount"], "12");
+    }
+
+    #[tokio::test]
+    async fn test_upload_counts_rows() {
+        let (router, state) = PushServer::new().into_router();
+
+        let body = "1\t2024-03-01 08:00:00\t0\t1\t\t0\t0\n2\t2024-03-01 08:01:00\t0\t1\t\t0\t0\n";
+        let uri = "/iclock/cdata?SN=A1&table=ATTLOG&Stamp=9999";
+        assert_eq!(call(&router, Method::POST, uri, body).await, (StatusCode::OK, "OK: 2".into()));
+
+        let uri = "/iclock/cdata.aspx?SN=A1&table=rtlog";
+        assert_eq!(call(&router, Method::POST, uri, "x\n").await, (StatusCode::OK, "OK: 1".into()));
+
+        let device = state.device("A1").unwrap();
+        assert!(!device.registered);
+        assert_eq!(device.rows_received, 3);
+    }
+
+    #[tokio::test]
+    async fn test_attlog_reaches_sink() {
+        let recorder = Recorder::default();
+        let statuses = StatusMap::new().with_default(PunchType::CheckOut);
+        let (router, state) = PushServer::new()
+            .with_sink(recorder.clone())
+            .with_status_map(statuses)
+            .into_router();
+
+        let body = "1001\t2024-03-01 08:00:00\t0\t15\t0\t0\t0\nbroken\n1002\t2024-03-01 08:01:00\t255\t1\t0\t0\t0\n";
+        let uri = "/iclock/cdata?SN=A1&table=ATTLOG&Stamp=1";
+        assert_eq!(call(&router, Method::POST, uri, body).await, (StatusCode::OK, "OK: 2".into()));
+
+        let writes = recorder.writes.lock().unwrap();
+        assert_eq!(writes.len(), 1);
+        let (device_id, records) = &writes[0];
+        assert_eq!(device_id, "A1");
+        assert_eq!(records[0].verify_mode, VerifyMode::Face);
+        assert_eq!(records[0].punch, PunchType::CheckIn);
+        assert_eq!(records[1].punch, PunchType::CheckOut);
+        assert_eq!(state.device("A1").unwrap().rows_received, 2);
+    }
+
+    #[tokio::test]
+    async fn test_sink_failure_asks_for_retry() {
+        let recorder = Recorder {
+            fail: true,
+            ..Default::default()
+        };
+        let (router, state) = PushServer::new().with_sink(recorder).into_router();
+
+        let uri = "/iclock/cdata?SN=A1&table=ATTLOG";
+        let (status, _) = call(&router, Method::POST, uri, "1\t2024-03-01 08:00:00\t0\t1\n").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(state.device("A1").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_operlog_enrollments_reach_sink() {
+        let recorder = Recorder::default();
+        let (router, state) = PushServer::new().with_sink(recorder.clone()).into_router();
+
+        let body = "OPLOG 6\t0\t2024-03-01 08:00:00\t1001\t6\t0\t0\n\
+                    USER PIN=1001\tName=Ann\tPri=0\tPasswd=\tCard=\tGrp=1\tTZ=0000000000000000\tVerify=0\n\
+                    USER PIN=123456\tName=Too Big\n\
+                    FP PIN=1001\tFID=6\tSize=4\tValid=1\tTMP=AQIDBA==\n";
+        let uri = "/iclock/cdata?SN=A1&table=OPERLOG&OpStamp=1";
+        assert_eq!(call(&router, Method::POST, uri, body).await, (StatusCode::OK, "OK: 3".into()));
+
+        {
+            let users = recorder.users.lock().unwrap();
+            assert_eq!(users.len(), 1);
+            assert_eq!((users[0].uid, users[0].name.as_str()), (1001, "Ann"));
+            let fingerprints = recorder.fingerprints.lock().unwrap();
+            assert_eq!((fingerprints[0].uid, fingerprints[0].finger), (1001, 6));
+        }
+        assert_eq!(state.device("A1").unwrap().rows_received, 3);
+
+        let recorder = Recorder {
+            fail: true,
+            ..Default::default()
+        };
+        let (router, _) = PushServer::new().with_sink(recorder).into_router();
+        let (status, _) = call(&router, Method::POST, uri, body).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_command_endpoints() {
+        let (router, state) = PushServer::new().into_router();
+
+        let (_, body) = call(&router, Method::GET, "/iclock/getrequest?SN=A1", "").await;
+        assert_eq!(body, "OK");
+        let (_, body) = call(&router, Method::POST, "/iclock/devicecmd?SN=A1", "ID=1&Return=0&CMD=INFO\n").await;
+        assert_eq!(body, "OK");
+
+        assert_eq!(state.devices().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_biodata_reaches_sink() {
+        let recorder = Recorder::default();
+        let (router, _) = PushServer::new().with_sink(recorder.clone()).into_router();
+
+        let body = "BIODATA Pin=7\tNo=3\tIndex=0\tValid=1\tDuress=0\tType=1\tMajorVer=12\tTmp=AQID\n\
+                    BIODATA Pin=7\tNo=0\tIndex=1\tValid=1\tDuress=0\tType=2\tMajorVer=7\tTmp=Aw==\n\
+                    BIODATA Pin=7\tNo=0\tIndex=0\tValid=1\tDuress=0\tType=2\tMajorVer=7\tTmp=AQI=\n";
+        let uri = "/iclock/cdata?SN=A1&table=BIODATA";
+        assert_eq!(call(&router, Method::POST, uri, body).await, (StatusCode::OK, "OK: 3".into()));
+
+        let fingerprints = recorder.fingerprints.lock().unwrap();
+        assert_eq!((fingerprints[0].uid, fingerprints[0].finger), (7, 3));
+        assert_eq!(fingerprints[0].algorithm_version, 12);
+        assert_eq!(*recorder.faces.lock().unwrap(), [FaceTemplate::new(7, 7, vec![1, 2, 3])]);
+    }
+
+    #[tokio::test]
+    async fn test_photo_reaches_sink() {
+        let recorder = Recorder::default();
+        let (router, state) = PushServer::new().with_sink(recorder.clone()).into_router();
+
+        let mut body = b"PIN=20240301083005-1001.jpg\nSN=A1\nsize=3\nCMD=uploadphoto\0".to_vec();
+        body.extend_from_slice(&[0xFF, 0x00, 0xD8]);
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/iclock/cdata?SN=A1&table=ATTPHOTO&Stamp=1")
+            .body(Body::from(body))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let photos = recorder.photos.lock().unwrap();
+        assert_eq!(photos[0].user_id.as_deref(), Some("1001"));
+        assert_eq!(photos[0].data, [0xFF, 0x00, 0xD8]);
+        assert_eq!(state.device("A1").unwrap().rows_received, 1);
+    }
+
+    #[tokio::test]
+    async fn test_command_queue() {
+        let (router, state) = PushServer::new().into_router();
+        let user = User::builder(1, "1001").name("Ann").build().unwrap();
+        let update = state.queue("A1", PushCommand::UpdateUser(user)).unwrap();
+        let reboot = state.queue("A1", PushCommand::Reboot).unwrap();
+        state.queue("B2", PushCommand::ClearLog).unwrap();
+
+        let (_, body) = call(&router, Method::GET, "/iclock/getrequest?SN=A1", "").await;
+        let lines: Vec<_> = body.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(&format!("C:{}:DATA UPDATE USERINFO PIN=1001\t", update)));
+        assert_eq!(lines[1], format!("C:{}:REBOOT", reboot));
+        assert!(matches!(state.command(update).unwrap().state, CommandState::Sent(_)));
+
+        // Nothing left for A1
+        let (_, body) = call(&router, Method::GET, "/iclock/getrequest?SN=A1", "").await;
+        assert_eq!(body, "OK");
+
+        let replies = format!("ID={}&Return=0&CMD=DATA\nID={}&Return=-1&CMD=REBOOT\n", update, reboot);
+        let (_, body) = call(&router, Method::POST, "/iclock/devicecmd?SN=A1", &replies).await;
+        assert_eq!(body, "OK");
+        assert!(state.command(update).unwrap().succeeded());
+        assert!(matches!(
+            state.command(reboot).unwrap().state,
+            CommandState::Done { return_code: -1, .. }
+        ));
+        assert_eq!(state.commands("B2")[0].state, CommandState::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_missing_serial_number() {
+        let (router, state) = PushServer::new().into_router();
+
+        let (status, _) = call(&router, Method::GET, "/iclock/cdata?options=all", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(&router, Method::GET, "/iclock/getrequest?SN=", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(state.devices().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bind_records_address() {
+        let server = PushServer::new().bind("127.0.0.1:0").await.unwrap();
+
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        let request = "GET /iclock/cdata?SN=A1&options=all HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n";
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("GET OPTION FROM: A1"));
+        assert_eq!(server.device("A1").unwrap().address, Some("127.0.0.1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,180 @@
+//! Upload stamps
+//!
+//! Every upload carries a stamp (`Stamp`, or `OpStamp` for the operation
+//! log of older firmware) marking how far the device has sent that table.
+//! The registration answer hands the last stamps back (`ATTLOGStamp=...`),
+//! and the device resumes after them. Without stored stamps the answer
+//! says `None` and the device uploads its whole log again, so stamps must
+//! survive server restarts to keep uploads incremental.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::error::{Error, Result};
+use crate::protocol::Table;
+
+/// Upload positions of one device
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamps {
+    /// Last ATTLOG upload
+    pub attlog: Option<String>,
+
+    /// Last OPERLOG upload
+    pub operlog: Option<String>,
+
+    /// Last ATTPHOTO upload
+    pub attphoto: Option<String>,
+}
+
+impl Stamps {
+    /// Stamp of `table`, if the table has one
+    pub fn get(&self, table: &Table) -> Option<&str> {
+        match table {
+            Table::AttLog => self.attlog.as_deref(),
+            Table::OperLog => self.operlog.as_deref(),
+            Table::AttPhoto => self.attphoto.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Record `stamp` for `table`, returning whether it changed
+    pub fn set(&mut self, table: &Table, stamp: &str) -> bool {
+        let slot = match table {
+            Table::AttLog => &mut self.attlog,
+            Table::OperLog => &mut self.operlog,
+            Table::AttPhoto => &mut self.attphoto,
+            _ => return false,
+        };
+        if slot.as_deref() == Some(stamp) {
+            return false;
+        }
+        *slot = Some(stamp.to_string());
+        true
+    }
+}
+
+/// Storage for stamps
+#[async_trait::async_trait]
+pub trait StampStore: Send {
+    /// Load the stamps of `serial_number`, if any were saved
+    async fn load(&mut self, serial_number: &str) -> Result<Option<Stamps>>;
+
+    /// Persist the stamps of `serial_number`
+    async fn save(&mut self, serial_number: &str, stamps: &Stamps) -> Result<()>;
+}
+
+/// In-memory stamp store (lost on restart)
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStampStore {
+    stamps: HashMap<String, Stamps>,
+}
+
+impl MemoryStampStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl StampStore for MemoryStampStore {
+    async fn load(&mut self, serial_number: &str) -> Result<Option<Stamps>> {
+        Ok(self.stamps.get(serial_number).cloned())
+    }
+
+    async fn save(&mut self, serial_number: &str, stamps: &Stamps) -> Result<()> {
+        self.stamps.insert(serial_number.to_string(), stamps.clone());
+        Ok(())
+    }
+}
+
+/// Stamp store backed by one JSON file
+///
+/// The file maps serial numbers to stamps and is rewritten atomically
+/// (write to a temporary file, then rename) on every save.
+#[derive(Debug, Clone)]
+pub struct FileStampStore {
+    path: PathBuf,
+}
+
+impl FileStampStore {
+    /// Create a store at `path` (created on first save)
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn read_all(&self) -> Result<HashMap<String, Stamps>> {
+        match std::fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| self.error(e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(self.error(e)),
+        }
+    }
+
+    fn error(&self, e: impl std::fmt::Display) -> Error {
+        Error::Stamps(format!("{}: {}", self.path.display(), e))
+    }
+}
+
+#[async_trait::async_trait]
+impl StampStore for FileStampStore {
+    async fn load(&mut self, serial_number: &str) -> Result<Option<Stamps>> {
+        Ok(self.read_all()?.remove(serial_number))
+    }
+
+    async fn save(&mut self, serial_number: &str, stamps: &Stamps) -> Result<()> {
+        let mut all = self.read_all()?;
+        all.insert(serial_number.to_string(), stamps.clone());
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+
+        let data = serde_json::to_vec_pretty(&all).map_err(|e| self.error(e))?;
+        std::fs::write(&tmp, data).map_err(|e| self.error(e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| self.error(e))?;
+
+        debug!("Saved stamps for {} to {}", serial_number, self.path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamps() {
+        let mut stamps = Stamps::default();
+        assert!(stamps.set(&Table::AttLog, "100"));
+        assert!(!stamps.set(&Table::AttLog, "100"));
+        assert!(stamps.set(&Table::OperLog, "7"));
+        assert!(!stamps.set(&Table::Options, "1"));
+
+        assert_eq!(stamps.get(&Table::AttLog), Some("100"));
+        assert_eq!(stamps.get(&Table::OperLog), Some("7"));
+        assert_eq!(stamps.get(&Table::AttPhoto), None);
+    }
+
+    #[tokio::test]
+    async fn test_file_stamp_store() {
+        let path = std::env::temp_dir().join(format!("zkrust-stamps-{}.json", std::process::id()));
+        let mut store = FileStampStore::new(&path);
+        assert_eq!(store.load("A1").await.unwrap(), None);
+
+        let mut stamps = Stamps::default();
+        stamps.set(&Table::AttLog, "9999");
+        store.save("A1", &stamps).await.unwrap();
+        store.save("B2", &Stamps::default()).await.unwrap();
+
+        let mut reopened = FileStampStore::new(&path);
+        assert_eq!(reopened.load("A1").await.unwrap(), Some(stamps));
+        assert_eq!(reopened.load("B2").await.unwrap(), Some(Stamps::default()));
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(matches!(reopened.load("A1").await, Err(Error::Stamps(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+//! Devices known to the server
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{NaiveDateTime, Utc};
+use tokio::sync::Mutex as AsyncMutex;
+use tracing::warn;
+
+use zkrust_sync::Sink;
+use zkrust_types::{AttendancePhoto, AttendanceRecord, FaceTemplate, FingerprintTemplate, User};
+
+use crate::attlog::StatusMap;
+use crate::command::{CommandQueue, CommandReply, PushCommand, QueuedCommand};
+use crate::error::Result;
+use crate::protocol::{PushOptions, Table};
+use crate::stamp::{StampStore, Stamps};
+
+/// What the server knows about a push device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceStatus {
+    /// Serial number the device identifies itself with
+    pub serial_number: String,
+
+    /// Source address of the last request
+    pub address: Option<IpAddr>,
+
+    /// Push protocol version reported at registration (`pushver`)
+    pub push_version: Option<String>,
+
+    /// First request since the server started (UTC)
+    pub first_seen: NaiveDateTime,
+
+    /// Latest request (UTC)
+    pub last_seen: NaiveDateTime,
+
+    /// Whether the device registered since the server started
+    pub registered: bool,
+
+    /// Table rows uploaded since the server started
+    pub rows_received: u64,
+
+    /// Settings and counters from the device's latest OPTIONS upload
+    pub options: BTreeMap<String, String>,
+}
+
+impl DeviceStatus {
+    fn new(serial_number: &str, now: NaiveDateTime) -> Self {
+        Self {
+            serial_number: serial_number.to_string(),
+            address: None,
+            push_version: None,
+            first_seen: now,
+            last_seen: now,
+            registered: false,
+            rows_received: 0,
+            options: BTreeMap::new(),
+        }
+    }
+}
+
+/// State shared by the request handlers
+pub(crate) struct Shared {
+    pub(crate) options: PushOptions,
+    pub(crate) statuses: StatusMap,
+    sink: Option<AsyncMutex<Box<dyn Sink>>>,
+    stamps: AsyncMutex<Box<dyn StampStore>>,
+    devices: Mutex<HashMap<String, DeviceStatus>>,
+    commands: Mutex<CommandQueue>,
+}
+
+impl Shared {
+    pub(crate) fn new(
+        options: PushOptions,
+        statuses: StatusMap,
+        sink: Option<Box<dyn Sink>>,
+        stamps: Box<dyn StampStore>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            options,
+            statuses,
+            sink: sink.map(AsyncMutex::new),
+            stamps: AsyncMutex::new(stamps),
+            devices: Mutex::default(),
+            commands: Mutex::default(),
+        })
+    }
+
+    /// Hand uploaded records of `serial_number` to the sink
+    pub(crate) async fn store_attendance(&self, serial_number: &str, records: &[AttendanceRecord]) -> Result<()> {
+        if let Some(sink) = &self.sink {
+            if !records.is_empty() {
+                sink.lock().await.write_attendance(serial_number, records).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand users and templates enrolled on `serial_number` to the sink
+    ///
+    /// Uploads only carry the records that changed, so all are upserted.
+    pub(crate) async fn store_enrollments(
+        &self,
+        serial_number: &str,
+        users: &[User],
+        fingerprints: &[FingerprintTemplate],
+        faces: &[FaceTemplate],
+    ) -> Result<()> {
+        let Some(sink) = &self.sink else {
+            return Ok(());
+        };
+        let mut sink = sink.lock().await;
+        if !users.is_empty() {
+            sink.update_users(serial_number, users).await?;
+        }
+        if !fingerprints.is_empty() {
+            sink.write_fingerprints(serial_number, fingerprints).await?;
+        }
+        if !faces.is_empty() {
+            sink.write_faces(serial_number, faces).await?;
+        }
+        Ok(())
+    }
+
+    /// Hand attendance photos of `serial_number` to the sink
+    pub(crate) async fn store_photos(&self, serial_number: &str, photos: &[AttendancePhoto]) -> Result<()> {
+        if let Some(sink) = &self.sink {
+            sink.lock().await.write_photos(serial_number, photos).await?;
+        }
+        Ok(())
+    }
+
+    /// Stamps to resume the uploads of `serial_number` from
+    ///
+    /// A store failure is logged and answered with no stamps, which makes
+    /// the device upload everything again rather than lose records.
+    pub(crate) async fn stamps(&self, serial_number: &str) -> Stamps {
+        match self.stamps.lock().await.load(serial_number).await {
+            Ok(stamps) => stamps.unwrap_or_default(),
+            Err(e) => {
+                warn!("Failed to load stamps of {}: {}", serial_number, e);
+                Stamps::default()
+            }
+        }
+    }
+
+    /// Remember that `serial_number` uploaded `table` up to `stamp`
+    pub(crate) async fn record_stamp(&self, serial_number: &str, table: &Table, stamp: &str) {
+        let mut store = self.stamps.lock().await;
+        let result = match store.load(serial_number).await {
+            Ok(stamps) => {
+                let mut stamps = stamps.unwrap_or_default();
+                if stamps.set(table, stamp) {
+                    store.save(serial_number, &stamps).await
+                } else {
+                    Ok(())
+                }
+            }
+            Err(e) => Err(e),
+        };
+        // The records are stored; at worst the device sends them again
+        if let Err(e) = result {
+            warn!("Failed to save {} stamp of {}: {}", table, serial_number, e);
+        }
+    }
+
+    /// Record a request from `serial_number` and update its status
+    pub(crate) fn touch<T>(
+        &self,
+        serial_number: &str,
+        address: Option<IpAddr>,
+        update: impl FnOnce(&mut DeviceStatus) -> T,
+    ) -> T {
+        let now = Utc::now().naive_utc();
+        let mut devices = self.lock();
+        let device = devices
+            .entry(serial_number.to_string())
+            .or_insert_with(|| DeviceStatus::new(serial_number, now));
+
+        device.last_seen = now;
+        if address.is_some() {
+            device.address = address;
+        }
+        update(device)
+    }
+
+    pub(crate) fn devices(&self) -> Vec<DeviceStatus> {
+        let mut devices: Vec<_> = self.lock().values().cloned().collect();
+        devices.sort_by(|a, b| a.serial_number.cmp(&b.serial_number));
+        devices
+    }
+
+    pub(crate) fn device(&self, serial_number: &str) -> Option<DeviceStatus> {
+        self.lock().get(serial_number).cloned()
+    }
+
+    pub(crate) fn queue(&self, serial_number: &str, command: PushCommand) -> Result<u64> {
+        self.commands.lock().unwrap().push(serial_number, command, Utc::now().naive_utc())
+    }
+
+    /// Pending commands of `serial_number` as a `getrequest` answer
+    pub(crate) fn take_commands(&self, serial_number: &str) -> Option<String> {
+        self.commands.lock().unwrap().take(serial_number, Utc::now().naive_utc())
+    }
+
+    pub(crate) fn complete_command(&self, serial_number: &str, reply: &CommandReply) -> bool {
+        self.commands.lock().unwrap().complete(serial_number, reply, Utc::now().naive_utc())
+    }
+
+    pub(crate) fn command(&self, id: u64) -> Option<QueuedCommand> {
+        self.commands.lock().unwrap().get(id)
+    }
+
+    pub(crate) fn commands(&self, serial_number: &str) -> Vec<QueuedCommand> {
+        self.commands.lock().unwrap().device(serial_number)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, DeviceStatus>> {
+        self.devices.lock().unwrap()
+    }
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("options", &self.options)
+            .field("statuses", &self.statuses)
+            .field("sink", &self.sink.is_some())
+            .field("devices", &self.devices)
+            .field("commands", &self.commands)
+            .finish()
+    }
+}
@@ -0,0 +1,240 @@
+//! Per-device sync cursors
+//!
+//! Devices have no "records since" query, so every poll downloads the whole
+//! log. A [`Cursor`] remembers the newest timestamp delivered (the
+//! watermark) plus the records seen at exactly that second, which is enough
+//! to tell new records from old ones without keeping the full history.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use zkrust_types::AttendanceRecord;
+
+use crate::error::{Error, Result};
+
+/// Sync position for one device
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// Timestamp of the newest delivered record
+    pub watermark: Option<NaiveDateTime>,
+
+    /// Keys of the delivered records stamped exactly at the watermark
+    pub seen: BTreeSet<String>,
+
+    /// Total records delivered through this cursor
+    pub delivered: u64,
+}
+
+impl Cursor {
+    /// Select the records not delivered yet, sorted chronologically
+    ///
+    /// Duplicates within `records` are dropped. Records older than the
+    /// watermark are treated as delivered, so a device clock set backwards
+    /// can hide punches until it catches up.
+    pub fn filter_new(&self, records: Vec<AttendanceRecord>) -> Vec<AttendanceRecord> {
+        let mut batch = BTreeSet::new();
+
+        let mut new: Vec<_> = records
+            .into_iter()
+            .filter(|record| match self.watermark {
+                None => true,
+                Some(watermark) if record.timestamp > watermark => true,
+                Some(watermark) if record.timestamp == watermark => {
+                    !self.seen.contains(&record_key(record))
+                }
+                Some(_) => false,
+            })
+            .filter(|record| batch.insert(record_key(record)))
+            .collect();
+
+        new.sort();
+        new
+    }
+
+    /// Move the cursor past `delivered`
+    pub fn advance(&mut self, delivered: &[AttendanceRecord]) {
+        let Some(newest) = delivered.iter().map(|r| r.timestamp).max() else {
+            return;
+        };
+
+        if self.watermark.is_none_or(|watermark| newest > watermark) {
+            self.watermark = Some(newest);
+            self.seen.clear();
+        }
+
+        if Some(newest) == self.watermark {
+            self.seen.extend(
+                delivered
+                    .iter()
+                    .filter(|r| r.timestamp == newest)
+                    .map(record_key),
+            );
+        }
+
+        self.delivered += delivered.len() as u64;
+    }
+}
+
+/// Identity of a record for deduplication
+pub fn record_key(record: &AttendanceRecord) -> String {
+    format!(
+        "{}|{}|{}|{}|{}",
+        record.user_id,
+        record.timestamp,
+        u8::from(record.punch),
+        u8::from(record.verify_mode),
+        record.work_code.unwrap_or(0)
+    )
+}
+
+/// Storage for cursors
+#[async_trait::async_trait]
+pub trait CursorStore: Send {
+    /// Load the cursor of `device_id`, if one was saved
+    async fn load(&mut self, device_id: &str) -> Result<Option<Cursor>>;
+
+    /// Persist the cursor of `device_id`
+    async fn save(&mut self, device_id: &str, cursor: &Cursor) -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<T: CursorStore + ?Sized> CursorStore for &mut T {
+    async fn load(&mut self, device_id: &str) -> Result<Option<Cursor>> {
+        (**self).load(device_id).await
+    }
+
+    async fn save(&mut self, device_id: &str, cursor: &Cursor) -> Result<()> {
+        (**self).save(device_id, cursor).await
+    }
+}
+
+/// In-memory cursor store (lost on restart)
+#[derive(Debug, Clone, Default)]
+pub struct MemoryCursorStore {
+    cursors: HashMap<String, Cursor>,
+}
+
+impl MemoryCursorStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl CursorStore for MemoryCursorStore {
+    async fn load(&mut self, device_id: &str) -> Result<Option<Cursor>> {
+        Ok(self.cursors.get(device_id).cloned())
+    }
+
+    async fn save(&mut self, device_id: &str, cursor: &Cursor) -> Result<()> {
+        self.cursors.insert(device_id.to_string(), cursor.clone());
+        Ok(())
+    }
+}
+
+/// Cursor store backed by one JSON file
+///
+/// The file maps device IDs to cursors and is rewritten atomically (write
+/// to a temporary file, then rename) on every save.
+#[derive(Debug, Clone)]
+pub struct FileCursorStore {
+    path: PathBuf,
+}
+
+impl FileCursorStore {
+    /// Create a store at `path` (created on first save)
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn read_all(&self) -> Result<HashMap<String, Cursor>> {
+        match std::fs::read(&self.path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CursorStore for FileCursorStore {
+    async fn load(&mut self, device_id: &str) -> Result<Option<Cursor>> {
+        Ok(self.read_all()?.remove(device_id))
+    }
+
+    async fn save(&mut self, device_id: &str, cursor: &Cursor) -> Result<()> {
+        let mut cursors = self.read_all()?;
+        cursors.insert(device_id.to_string(), cursor.clone());
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&cursors)?)?;
+        std::fs::rename(&tmp, &self.path)
+            .map_err(|e| Error::Cursor(format!("{}: {}", self.path.display(), e)))?;
+
+        debug!("Saved cursor for {} to {}", device_id, self.path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+
+    fn record(user_id: &str, minute: u32) -> AttendanceRecord {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(8, minute, 0)
+            .unwrap();
+        AttendanceRecord::new(user_id, timestamp, VerifyMode::Fingerprint, PunchType::CheckIn)
+    }
+
+    #[test]
+    fn test_cursor_delivers_each_record_once() {
+        let mut cursor = Cursor::default();
+
+        let first = cursor.filter_new(vec![record("2", 1), record("1", 0), record("1", 0)]);
+        assert_eq!(first, vec![record("1", 0), record("2", 1)]);
+        cursor.advance(&first);
+
+        // Same log plus one punch in the watermark second and one later
+        let second = cursor.filter_new(vec![
+            record("1", 0),
+            record("2", 1),
+            record("3", 1),
+            record("4", 2),
+        ]);
+        assert_eq!(second, vec![record("3", 1), record("4", 2)]);
+        cursor.advance(&second);
+
+        assert!(cursor.filter_new(vec![record("3", 1), record("4", 2)]).is_empty());
+        assert_eq!(cursor.delivered, 4);
+    }
+
+    #[tokio::test]
+    async fn test_file_cursor_store() {
+        let path = std::env::temp_dir().join(format!("zkrust-cursors-{}.json", std::process::id()));
+        let mut store = FileCursorStore::new(&path);
+
+        assert_eq!(store.load("a").await.unwrap(), None);
+
+        let mut cursor = Cursor::default();
+        cursor.advance(&[record("1", 5)]);
+        store.save("a", &cursor).await.unwrap();
+        store.save("b", &Cursor::default()).await.unwrap();
+
+        let mut reopened = FileCursorStore::new(&path);
+        assert_eq!(reopened.load("a").await.unwrap(), Some(cursor));
+        assert_eq!(reopened.load("b").await.unwrap(), Some(Cursor::default()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
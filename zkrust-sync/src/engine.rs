@@ -0,0 +1,294 @@
+//! Polling sync engine
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn};
+
+use zkrust::AttendanceRecord;
+
+use crate::cursor::{Cursor, CursorStore};
+use crate::error::{Error, Result};
+use crate::sink::Sink;
+use crate::source::AttendanceSource;
+
+/// Outcome of syncing one device
+#[derive(Debug)]
+pub struct SyncReport {
+    /// Device ID the report belongs to
+    pub device_id: String,
+
+    /// Records downloaded from the device
+    pub fetched: usize,
+
+    /// New records handed to the sink
+    pub delivered: usize,
+
+    /// Cursor watermark after the sync
+    pub watermark: Option<NaiveDateTime>,
+
+    /// Error that stopped the sync, if any
+    pub error: Option<Error>,
+}
+
+impl SyncReport {
+    pub(crate) fn new(device_id: &str) -> Self {
+        Self {
+            device_id: device_id.to_string(),
+            fetched: 0,
+            delivered: 0,
+            watermark: None,
+            error: None,
+        }
+    }
+
+    /// Whether the device synced without error
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Incremental attendance synchronization across devices
+///
+/// Each [`sync_once`](Self::sync_once) downloads every device's attendance
+/// log, keeps only the records past the device [`Cursor`], writes them to
+/// the sink and then saves the cursor. Operation logs are not synced yet.
+pub struct SyncEngine<S, C> {
+    sink: S,
+    store: C,
+    devices: Vec<(String, Box<dyn AttendanceSource>)>,
+    cursors: HashMap<String, Cursor>,
+}
+
+impl<S: Sink, C: CursorStore> SyncEngine<S, C> {
+    /// Create an engine writing to `sink` with cursors kept in `store`
+    pub fn new(sink: S, store: C) -> Self {
+        Self {
+            sink,
+            store,
+            devices: Vec::new(),
+            cursors: HashMap::new(),
+        }
+    }
+
+    /// Register a device under `device_id`
+    ///
+    /// The ID keys the persistent cursor, so it must stay stable across
+    /// restarts.
+    pub fn add_device(&mut self, device_id: impl Into<String>, source: impl AttendanceSource + 'static) {
+        self.devices.push((device_id.into(), Box::new(source)));
+    }
+
+    /// Builder variant of [`add_device`](Self::add_device)
+    pub fn with_device(mut self, device_id: impl Into<String>, source: impl AttendanceSource + 'static) -> Self {
+        self.add_device(device_id, source);
+        self
+    }
+
+    /// Registered device IDs
+    pub fn device_ids(&self) -> impl Iterator<Item = &str> {
+        self.devices.iter().map(|(id, _)| id.as_str())
+    }
+
+    /// The sink records are written to
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Mutable access to the sink
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Consume the engine and return the sink
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+
+    /// Sync every device once
+    ///
+    /// A failing device does not stop the others; its error is reported
+    /// and its cursor is left untouched.
+    pub async fn sync_once(&mut self) -> Vec<SyncReport> {
+        let mut reports = Vec::with_capacity(self.devices.len());
+
+        for index in 0..self.devices.len() {
+            let device_id = self.devices[index].0.clone();
+            let mut report = SyncReport::new(&device_id);
+
+            if let Err(e) = self.sync_device(index, &mut report).await {
+                warn!("Sync of {} failed: {}", device_id, e);
+                report.error = Some(e);
+            }
+
+            reports.push(report);
+        }
+
+        reports
+    }
+
+    /// Sync on a fixed interval forever
+    pub async fn run(&mut self, interval: Duration) {
+        self.run_until(interval, std::future::pending::<()>()).await
+    }
+
+    /// Sync on a fixed interval until `shutdown` completes
+    ///
+    /// A sync in progress when `shutdown` fires is finished first, so no
+    /// delivered batch is left without its cursor update.
+    pub async fn run_until<F: Future>(&mut self, interval: Duration, shutdown: F) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = ticker.tick() => {
+                    let reports = self.sync_once().await;
+                    let delivered: usize = reports.iter().map(|r| r.delivered).sum();
+                    let failed = reports.iter().filter(|r| !r.is_ok()).count();
+                    info!("Sync pass: {} new records, {} of {} devices failed", delivered, failed, reports.len());
+                }
+            }
+        }
+    }
+
+    async fn sync_device(&mut self, index: usize, report: &mut SyncReport) -> Result<()> {
+        let (device_id, source) = &mut self.devices[index];
+
+        if !self.cursors.contains_key(device_id.as_str()) {
+            let cursor = self.store.load(device_id).await?.unwrap_or_default();
+            self.cursors.insert(device_id.clone(), cursor);
+        }
+
+        let records = source.fetch_attendance().await?;
+        let cursor = self.cursors.get_mut(device_id.as_str()).expect("cursor loaded above");
+        deliver(&mut self.sink, &mut self.store, device_id, cursor, records, report).await
+    }
+}
+
+/// Hand the records past `cursor` to `sink`, then advance and save it
+///
+/// The cursor only moves once both the write and the save succeed, so a
+/// failure means the same records are offered again on the next poll.
+pub(crate) async fn deliver<S: Sink + ?Sized, C: CursorStore + ?Sized>(
+    sink: &mut S,
+    store: &mut C,
+    device_id: &str,
+    cursor: &mut Cursor,
+    records: Vec<AttendanceRecord>,
+    report: &mut SyncReport,
+) -> Result<()> {
+    report.fetched = records.len();
+
+    let new = cursor.filter_new(records);
+    report.watermark = cursor.watermark;
+
+    if new.is_empty() {
+        debug!("No new records on {}", device_id);
+        return Ok(());
+    }
+
+    sink.write_attendance(device_id, &new).await?;
+
+    let mut advanced = cursor.clone();
+    advanced.advance(&new);
+    store.save(device_id, &advanced).await?;
+    *cursor = advanced;
+
+    report.delivered = new.len();
+    report.watermark = cursor.watermark;
+    debug!("Delivered {} new records from {}", new.len(), device_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::MemoryCursorStore;
+    use crate::sink::MemorySink;
+    use chrono::NaiveDate;
+    use std::sync::{Arc, Mutex};
+    use zkrust_core::constants::{PunchType, VerifyMode};
+
+    /// Source replaying a shared, growing log
+    #[derive(Clone, Default)]
+    struct LogSource {
+        log: Arc<Mutex<Vec<AttendanceRecord>>>,
+        fail: Arc<Mutex<bool>>,
+    }
+
+    #[async_trait::async_trait]
+    impl AttendanceSource for LogSource {
+        async fn fetch_attendance(&mut self) -> zkrust::Result<Vec<AttendanceRecord>> {
+            if *self.fail.lock().unwrap() {
+                return Err(zkrust::Error::NotConnected);
+            }
+            Ok(self.log.lock().unwrap().clone())
+        }
+    }
+
+    fn record(user_id: &str, minute: u32) -> AttendanceRecord {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(8, minute, 0)
+            .unwrap();
+        AttendanceRecord::new(user_id, timestamp, VerifyMode::Fingerprint, PunchType::CheckIn)
+    }
+
+    #[tokio::test]
+    async fn test_sync_delivers_only_new_records() {
+        let source = LogSource::default();
+        source.log.lock().unwrap().extend([record("1", 0), record("2", 1)]);
+
+        let mut engine = SyncEngine::new(MemorySink::new(), MemoryCursorStore::new())
+            .with_device("door", source.clone());
+
+        let reports = engine.sync_once().await;
+        assert!(reports[0].is_ok());
+        assert_eq!(reports[0].delivered, 2);
+
+        source.log.lock().unwrap().push(record("3", 2));
+        let reports = engine.sync_once().await;
+        assert_eq!(reports[0].fetched, 3);
+        assert_eq!(reports[0].delivered, 1);
+
+        assert_eq!(engine.sink().attendance("door").len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_survives_restart() {
+        let source = LogSource::default();
+        source.log.lock().unwrap().extend([record("1", 0), record("2", 1)]);
+
+        let mut store = MemoryCursorStore::new();
+        let mut first = SyncEngine::new(MemorySink::new(), &mut store).with_device("door", source.clone());
+        first.sync_once().await;
+
+        let mut second = SyncEngine::new(MemorySink::new(), &mut store).with_device("door", source);
+        let reports = second.sync_once().await;
+        assert_eq!(reports[0].delivered, 0);
+        assert!(second.sink().attendance("door").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failing_device_does_not_block_others() {
+        let broken = LogSource::default();
+        *broken.fail.lock().unwrap() = true;
+
+        let healthy = LogSource::default();
+        healthy.log.lock().unwrap().push(record("1", 0));
+
+        let mut engine = SyncEngine::new(MemorySink::new(), MemoryCursorStore::new())
+            .with_device("broken", broken)
+            .with_device("healthy", healthy);
+
+        let reports = engine.sync_once().await;
+        assert!(matches!(reports[0].error, Some(Error::Device(zkrust::Error::NotConnected))));
+        assert_eq!(reports[1].delivered, 1);
+    }
+}
@@ -0,0 +1,53 @@
+//! Sync error types
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Device error: {0}")]
+    Device(#[from] zkrust::Error),
+    
+    #[error("Device poll timed out after {0:?}")]
+    Timeout(std::time::Duration),
+    
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    
+    #[error("Sink error: {0}")]
+    Sink(String),
+    
+    #[error("Cursor store error: {0}")]
+    Cursor(String),
+    
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    
+    #[cfg(feature = "postgres")]
+    #[error("PostgreSQL error: {0}")]
+    Postgres(#[from] sqlx::Error),
+    
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    
+    #[error("Export error: {0}")]
+    Export(String),
+    
+    #[cfg(feature = "xlsx")]
+    #[error("Excel error: {0}")]
+    Xlsx(#[from] rust_xlsxwriter::XlsxError),
+    
+    #[cfg(feature = "kafka")]
+    #[error("Kafka error: {0}")]
+    Kafka(#[from] rdkafka::error::KafkaError),
+    
+    #[cfg(feature = "mqtt")]
+    #[error("MQTT error: {0}")]
+    Mqtt(#[from] rumqttc::ClientError),
+    
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,271 @@
+//! CSV export
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use zkrust_types::AttendanceRecord;
+use zkrust_types::time::TIMESTAMP_FORMAT;
+
+use super::format_timestamp;
+use crate::error::{Error, Result};
+
+/// A CSV column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Column {
+    /// User ID
+    UserId,
+    /// Date and time, using the timestamp format
+    Timestamp,
+    /// Date only, using the date format
+    Date,
+    /// Time only, using the time format
+    Time,
+    /// Verification mode name (e.g. "Fingerprint")
+    VerifyMode,
+    /// Verification mode code
+    VerifyCode,
+    /// Punch type name (e.g. "Check-In")
+    Punch,
+    /// Punch type code
+    PunchCode,
+    /// Work code, empty if unset
+    WorkCode,
+}
+
+impl Column {
+    /// All columns, in default export order
+    pub const ALL: [Column; 9] = [
+        Column::UserId,
+        Column::Timestamp,
+        Column::Date,
+        Column::Time,
+        Column::VerifyMode,
+        Column::VerifyCode,
+        Column::Punch,
+        Column::PunchCode,
+        Column::WorkCode,
+    ];
+
+    /// Header name, also accepted by [`FromStr`]
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::UserId => "user_id",
+            Column::Timestamp => "timestamp",
+            Column::Date => "date",
+            Column::Time => "time",
+            Column::VerifyMode => "verify_mode",
+            Column::VerifyCode => "verify_code",
+            Column::Punch => "punch",
+            Column::PunchCode => "punch_code",
+            Column::WorkCode => "work_code",
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Column {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.trim().to_ascii_lowercase().replace('-', "_");
+        Column::ALL
+            .into_iter()
+            .find(|column| column.name() == name)
+            .ok_or_else(|| Error::Export(format!("Unknown CSV column {:?}", s)))
+    }
+}
+
+/// Writes attendance records as CSV
+///
+/// # Example
+///
+/// ```
+/// use zkrust_sync::export::{Column, CsvExporter};
+///
+/// let exporter = CsvExporter::new()
+///     .columns([Column::UserId, Column::Date, Column::Time, Column::Punch])
+///     .date_format("%d/%m/%Y");
+///
+/// let csv = exporter.to_string(&[]).unwrap();
+/// assert_eq!(csv, "user_id,date,time,punch\n");
+/// ```
+#[derive(Debug, Clone)]
+pub struct CsvExporter {
+    columns: Vec<Column>,
+    timestamp_format: String,
+    date_format: String,
+    time_format: String,
+    delimiter: u8,
+    header: bool,
+}
+
+impl CsvExporter {
+    /// Create an exporter with the default columns and ISO-style timestamps
+    pub fn new() -> Self {
+        Self {
+            columns: vec![
+                Column::UserId,
+                Column::Timestamp,
+                Column::Punch,
+                Column::VerifyMode,
+                Column::WorkCode,
+            ],
+            timestamp_format: TIMESTAMP_FORMAT.to_string(),
+            date_format: "%Y-%m-%d".to_string(),
+            time_format: "%H:%M:%S".to_string(),
+            delimiter: b',',
+            header: true,
+        }
+    }
+
+    /// Set the columns and their order
+    pub fn columns(mut self, columns: impl IntoIterator<Item = Column>) -> Self {
+        self.columns = columns.into_iter().collect();
+        self
+    }
+
+    /// Set the strftime format of the `timestamp` column
+    pub fn timestamp_format(mut self, format: impl Into<String>) -> Self {
+        self.timestamp_format = format.into();
+        self
+    }
+
+    /// Set the strftime format of the `date` column
+    pub fn date_format(mut self, format: impl Into<String>) -> Self {
+        self.date_format = format.into();
+        self
+    }
+
+    /// Set the strftime format of the `time` column
+    pub fn time_format(mut self, format: impl Into<String>) -> Self {
+        self.time_format = format.into();
+        self
+    }
+
+    /// Set the field delimiter (default `,`)
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Write a header row first (default `true`)
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Write `records` to `writer`
+    pub fn write<'a, W: io::Write>(
+        &self,
+        writer: W,
+        records: impl IntoIterator<Item = &'a AttendanceRecord>,
+    ) -> Result<()> {
+        let mut csv = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .from_writer(writer);
+
+        if self.header {
+            csv.write_record(self.columns.iter().map(|c| c.name()))?;
+        }
+
+        for record in records {
+            let fields = self
+                .columns
+                .iter()
+                .map(|&column| self.field(record, column))
+                .collect::<Result<Vec<_>>>()?;
+            csv.write_record(&fields)?;
+        }
+
+        csv.flush()?;
+        Ok(())
+    }
+
+    /// Render `records` as a CSV string
+    pub fn to_string<'a>(&self, records: impl IntoIterator<Item = &'a AttendanceRecord>) -> Result<String> {
+        let mut out = Vec::new();
+        self.write(&mut out, records)?;
+        String::from_utf8(out).map_err(|e| Error::Export(e.to_string()))
+    }
+
+    fn field(&self, record: &AttendanceRecord, column: Column) -> Result<String> {
+        Ok(match column {
+            Column::UserId => record.user_id.clone(),
+            Column::Timestamp => format_timestamp(&record.timestamp, &self.timestamp_format)?,
+            Column::Date => format_timestamp(&record.timestamp, &self.date_format)?,
+            Column::Time => format_timestamp(&record.timestamp, &self.time_format)?,
+            Column::VerifyMode => record.verify_mode.to_string(),
+            Column::VerifyCode => u8::from(record.verify_mode).to_string(),
+            Column::Punch => record.punch.to_string(),
+            Column::PunchCode => u8::from(record.punch).to_string(),
+            Column::WorkCode => record.work_code.map(|c| c.to_string()).unwrap_or_default(),
+        })
+    }
+}
+
+impl Default for CsvExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+
+    fn records() -> Vec<AttendanceRecord> {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(8, 5, 0)
+            .unwrap();
+        vec![
+            AttendanceRecord::new("1", timestamp, VerifyMode::Fingerprint, PunchType::CheckIn),
+            AttendanceRecord::new("2", timestamp, VerifyMode::Card, PunchType::CheckOut).with_work_code(12),
+        ]
+    }
+
+    #[test]
+    fn test_default_export() {
+        let csv = CsvExporter::new().to_string(&records()).unwrap();
+        assert_eq!(
+            csv,
+            "user_id,timestamp,punch,verify_mode,work_code\n\
+             1,2024-03-01 08:05:00,Check-In,Fingerprint,\n\
+             2,2024-03-01 08:05:00,Check-Out,Card,12\n"
+        );
+    }
+
+    #[test]
+    fn test_custom_columns_and_formats() {
+        let columns: Vec<Column> = ["date", "Time", "user-id", "punch_code"]
+            .iter()
+            .map(|name| name.parse().unwrap())
+            .collect();
+
+        let csv = CsvExporter::new()
+            .columns(columns)
+            .date_format("%d/%m/%Y")
+            .time_format("%H:%M")
+            .delimiter(b';')
+            .header(false)
+            .to_string(&records())
+            .unwrap();
+
+        assert_eq!(csv, "01/03/2024;08:05;1;0\n01/03/2024;08:05;2;1\n");
+    }
+
+    #[test]
+    fn test_invalid_format_is_an_error() {
+        let exporter = CsvExporter::new().timestamp_format("%Q");
+        assert!(matches!(exporter.to_string(&records()), Err(Error::Export(_))));
+        assert!("badge".parse::<Column>().is_err());
+    }
+}
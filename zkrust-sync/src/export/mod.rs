@@ -0,0 +1,30 @@
+//! Attendance export formats
+//!
+//! Exporters turn synced records into files other systems import.
+
+pub mod csv;
+pub mod ndjson;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+pub use self::csv::{Column, CsvExporter};
+pub use self::ndjson::{FieldNaming, NdjsonExporter};
+#[cfg(feature = "xlsx")]
+pub use self::xlsx::XlsxReport;
+
+use std::fmt::Write;
+
+use chrono::NaiveDateTime;
+
+use crate::error::{Error, Result};
+
+/// Format `timestamp` with a strftime pattern, rejecting invalid patterns
+///
+/// `NaiveDateTime::format(..).to_string()` panics on a bad pattern, which
+/// is not acceptable for formats coming from user configuration.
+pub(crate) fn format_timestamp(timestamp: &NaiveDateTime, pattern: &str) -> Result<String> {
+    let mut out = String::new();
+    write!(out, "{}", timestamp.format(pattern))
+        .map_err(|_| Error::Export(format!("Invalid time format {:?}", pattern)))?;
+    Ok(out)
+}
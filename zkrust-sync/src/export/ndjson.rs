@@ -0,0 +1,259 @@
+//! Newline-delimited JSON export
+//!
+//! One JSON object per line, ready to pipe into log shippers. Timestamps
+//! use ISO 8601 (`2024-03-01T08:05:00`) and enums their variant names.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use zkrust_types::{AttendanceRecord, User};
+
+use crate::error::{Error, Result};
+
+/// Case convention for JSON field names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FieldNaming {
+    /// `user_id` (the Rust field names)
+    #[default]
+    SnakeCase,
+    /// `userId`
+    CamelCase,
+    /// `UserId`
+    PascalCase,
+    /// `user-id`
+    KebabCase,
+}
+
+impl FieldNaming {
+    /// Convert a snake_case field name
+    pub fn apply(self, field: &str) -> String {
+        match self {
+            FieldNaming::SnakeCase => field.to_string(),
+            FieldNaming::KebabCase => field.replace('_', "-"),
+            FieldNaming::CamelCase | FieldNaming::PascalCase => {
+                let mut out = String::with_capacity(field.len());
+                for (i, word) in field.split('_').enumerate() {
+                    let mut chars = word.chars();
+                    if let Some(first) = chars.next() {
+                        if i == 0 && self == FieldNaming::CamelCase {
+                            out.push(first);
+                        } else {
+                            out.extend(first.to_uppercase());
+                        }
+                        out.push_str(chars.as_str());
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+
+impl fmt::Display for FieldNaming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldNaming::SnakeCase => "snake_case",
+            FieldNaming::CamelCase => "camelCase",
+            FieldNaming::PascalCase => "PascalCase",
+            FieldNaming::KebabCase => "kebab-case",
+        })
+    }
+}
+
+impl FromStr for FieldNaming {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "snakecase" | "snake" => Ok(FieldNaming::SnakeCase),
+            "camelcase" | "camel" => Ok(FieldNaming::CamelCase),
+            "pascalcase" | "pascal" => Ok(FieldNaming::PascalCase),
+            "kebabcase" | "kebab" => Ok(FieldNaming::KebabCase),
+            _ => Err(Error::Export(format!("Unknown field naming {:?}", s))),
+        }
+    }
+}
+
+/// Writes records as newline-delimited JSON
+///
+/// User passwords are left out unless
+/// [`include_secrets`](Self::include_secrets) is set, since exports tend to
+/// end up in log storage.
+///
+/// # Example
+///
+/// ```
+/// use zkrust_sync::export::{FieldNaming, NdjsonExporter};
+///
+/// let exporter = NdjsonExporter::new()
+///     .naming(FieldNaming::CamelCase)
+///     .tag("deviceId", "front-door");
+///
+/// let mut out = Vec::new();
+/// assert_eq!(exporter.write_attendance(&mut out, &[]).unwrap(), 0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NdjsonExporter {
+    naming: FieldNaming,
+    tags: Map<String, Value>,
+    include_secrets: bool,
+}
+
+impl NdjsonExporter {
+    /// Create an exporter with snake_case fields and no tags
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the field naming convention
+    pub fn naming(mut self, naming: FieldNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Add a constant field to every line (e.g. the device ID)
+    ///
+    /// Tag names are written as given, not converted by the naming
+    /// convention.
+    pub fn tag(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.tags.insert(name.into(), value.into());
+        self
+    }
+
+    /// Include user passwords in user exports (default `false`)
+    pub fn include_secrets(mut self, include: bool) -> Self {
+        self.include_secrets = include;
+        self
+    }
+
+    /// Write attendance records, returning the number of lines written
+    pub fn write_attendance<'a, W: io::Write>(
+        &self,
+        writer: W,
+        records: impl IntoIterator<Item = &'a AttendanceRecord>,
+    ) -> Result<usize> {
+        self.write_lines(writer, records, &[])
+    }
+
+    /// Write users, returning the number of lines written
+    pub fn write_users<'a, W: io::Write>(
+        &self,
+        writer: W,
+        users: impl IntoIterator<Item = &'a User>,
+    ) -> Result<usize> {
+        let hidden: &[&str] = if self.include_secrets { &[] } else { &["password"] };
+        self.write_lines(writer, users, hidden)
+    }
+
+    /// Write any serializable items, returning the number of lines written
+    pub fn write<T: Serialize, W: io::Write>(
+        &self,
+        writer: W,
+        items: impl IntoIterator<Item = T>,
+    ) -> Result<usize> {
+        self.write_lines(writer, items, &[])
+    }
+
+    fn write_lines<T: Serialize, W: io::Write>(
+        &self,
+        mut writer: W,
+        items: impl IntoIterator<Item = T>,
+        hidden: &[&str],
+    ) -> Result<usize> {
+        let mut count = 0;
+
+        for item in items {
+            let value = match serde_json::to_value(item)? {
+                Value::Object(fields) => {
+                    let mut line = Map::with_capacity(fields.len() + self.tags.len());
+                    for (name, value) in fields {
+                        if !hidden.contains(&name.as_str()) {
+                            line.insert(self.naming.apply(&name), value);
+                        }
+                    }
+                    line.extend(self.tags.iter().map(|(k, v)| (k.clone(), v.clone())));
+                    Value::Object(line)
+                }
+                other => other,
+            };
+
+            serde_json::to_writer(&mut writer, &value)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+        }
+
+        writer.flush()?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+
+    #[test]
+    fn test_field_naming() {
+        assert_eq!(FieldNaming::CamelCase.apply("verify_mode"), "verifyMode");
+        assert_eq!(FieldNaming::PascalCase.apply("user_id"), "UserId");
+        assert_eq!(FieldNaming::KebabCase.apply("work_code"), "work-code");
+        assert_eq!(FieldNaming::SnakeCase.apply("punch"), "punch");
+        assert_eq!("camelCase".parse::<FieldNaming>().unwrap(), FieldNaming::CamelCase);
+        assert!("shouting".parse::<FieldNaming>().is_err());
+    }
+
+    #[test]
+    fn test_attendance_lines() {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(8, 5, 0)
+            .unwrap();
+        let records = [
+            AttendanceRecord::new("1", timestamp, VerifyMode::Fingerprint, PunchType::CheckIn),
+            AttendanceRecord::new("2", timestamp, VerifyMode::Card, PunchType::CheckOut).with_work_code(3),
+        ];
+
+        let mut out = Vec::new();
+        let count = NdjsonExporter::new()
+            .naming(FieldNaming::CamelCase)
+            .tag("device", "door")
+            .write_attendance(&mut out, &records)
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let lines: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines[0]["userId"], "1");
+        assert_eq!(lines[0]["timestamp"], "2024-03-01T08:05:00");
+        assert_eq!(lines[0]["verifyMode"], "Fingerprint");
+        assert_eq!(lines[0]["workCode"], Value::Null);
+        assert_eq!(lines[1]["punch"], "CheckOut");
+        assert_eq!(lines[1]["workCode"], 3);
+        assert_eq!(lines[1]["device"], "door");
+    }
+
+    #[test]
+    fn test_user_passwords_hidden_by_default() {
+        let user = User::builder(1, "100").name("Alice").password("1234").build().unwrap();
+
+        let mut out = Vec::new();
+        NdjsonExporter::new().write_users(&mut out, [&user]).unwrap();
+        let line: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(line["name"], "Alice");
+        assert!(line.get("password").is_none());
+
+        let mut out = Vec::new();
+        NdjsonExporter::new().include_secrets(true).write_users(&mut out, [&user]).unwrap();
+        let line: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(line["password"], "1234");
+    }
+}
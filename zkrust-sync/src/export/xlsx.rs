@@ -0,0 +1,265 @@
+//! Excel attendance report
+//!
+//! [`XlsxReport`] writes a workbook with two sheets:
+//!
+//! - **Attendance**: one row per user, one column per day, each cell
+//!   showing the first and last punch of that day (`08:02 - 17:15`)
+//! - **Records**: the raw records, one per row
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveTime};
+use rust_xlsxwriter::{Format, FormatAlign, Workbook, Worksheet};
+
+use zkrust_types::{AttendanceRecord, User};
+
+use crate::error::Result;
+
+/// Punch times per user and day
+pub(crate) type DailyGrid = BTreeMap<String, BTreeMap<NaiveDate, Vec<NaiveTime>>>;
+
+/// Group punch times by user and day, each day's times sorted
+pub(crate) fn daily_grid<'a>(records: impl IntoIterator<Item = &'a AttendanceRecord>) -> DailyGrid {
+    let mut grid = DailyGrid::new();
+    for record in records {
+        grid.entry(record.user_id.clone())
+            .or_default()
+            .entry(record.timestamp.date())
+            .or_default()
+            .push(record.timestamp.time());
+    }
+    for days in grid.values_mut() {
+        for times in days.values_mut() {
+            times.sort();
+        }
+    }
+    grid
+}
+
+/// Per-user, per-day Excel attendance report
+///
+/// # Example
+///
+/// ```no_run
+/// use zkrust_sync::export::XlsxReport;
+/// # fn example(records: &[zkrust::AttendanceRecord], users: &[zkrust::User]) -> zkrust_sync::Result<()> {
+/// XlsxReport::new()
+///     .users(users)
+///     .save("attendance.xlsx", records)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct XlsxReport {
+    range: Option<(NaiveDate, NaiveDate)>,
+    names: HashMap<String, String>,
+    time_format: String,
+}
+
+impl XlsxReport {
+    /// Create a report covering the days present in the records
+    pub fn new() -> Self {
+        Self {
+            range: None,
+            names: HashMap::new(),
+            time_format: "%H:%M".to_string(),
+        }
+    }
+
+    /// Cover `from..=to`, including days without punches
+    ///
+    /// Records outside the range are left out of the daily sheet but kept
+    /// on the records sheet.
+    pub fn range(mut self, from: NaiveDate, to: NaiveDate) -> Self {
+        self.range = Some((from, to));
+        self
+    }
+
+    /// Show user names next to user IDs
+    pub fn users<'a>(mut self, users: impl IntoIterator<Item = &'a User>) -> Self {
+        self.names
+            .extend(users.into_iter().map(|u| (u.user_id.clone(), u.name.clone())));
+        self
+    }
+
+    /// Set the strftime format of punch times in the daily sheet
+    pub fn time_format(mut self, format: impl Into<String>) -> Self {
+        self.time_format = format.into();
+        self
+    }
+
+    /// Write the workbook to `path`
+    pub fn save(&self, path: impl AsRef<Path>, records: &[AttendanceRecord]) -> Result<()> {
+        self.workbook(records)?.save(path)?;
+        Ok(())
+    }
+
+    /// Render the workbook into memory
+    pub fn to_buffer(&self, records: &[AttendanceRecord]) -> Result<Vec<u8>> {
+        Ok(self.workbook(records)?.save_to_buffer()?)
+    }
+
+    fn workbook(&self, records: &[AttendanceRecord]) -> Result<Workbook> {
+        let mut workbook = Workbook::new();
+        self.write_daily(workbook.add_worksheet().set_name("Attendance")?, records)?;
+        self.write_records(workbook.add_worksheet().set_name("Records")?, records)?;
+        Ok(workbook)
+    }
+
+    fn days(&self, grid: &DailyGrid) -> Vec<NaiveDate> {
+        let (from, to) = match self.range {
+            Some(range) => range,
+            None => {
+                let present: BTreeSet<NaiveDate> =
+                    grid.values().flat_map(|days| days.keys().copied()).collect();
+                match (present.first(), present.last()) {
+                    (Some(&from), Some(&to)) => (from, to),
+                    _ => return Vec::new(),
+                }
+            }
+        };
+        from.iter_days().take_while(|day| *day <= to).collect()
+    }
+
+    fn write_daily(&self, sheet: &mut Worksheet, records: &[AttendanceRecord]) -> Result<()> {
+        let grid = daily_grid(records);
+        let days = self.days(&grid);
+        let bold = Format::new().set_bold();
+        let centered = Format::new().set_align(FormatAlign::Center);
+
+        sheet.write_string_with_format(0, 0, "User ID", &bold)?;
+        sheet.write_string_with_format(0, 1, "Name", &bold)?;
+        for (i, day) in days.iter().enumerate() {
+            let header = day.format("%Y-%m-%d %a").to_string();
+            sheet.write_string_with_format(0, 2 + i as u16, &header, &bold)?;
+            sheet.set_column_width(2 + i as u16, 15)?;
+        }
+        let total_col = 2 + days.len() as u16;
+        sheet.write_string_with_format(0, total_col, "Days Present", &bold)?;
+
+        // Users with no punch in the range still get a row if named
+        let mut user_ids: BTreeSet<&str> = grid.keys().map(String::as_str).collect();
+        user_ids.extend(self.names.keys().map(String::as_str));
+
+        for (row, user_id) in user_ids.into_iter().enumerate() {
+            let row = 1 + row as u32;
+            let punches = grid.get(user_id);
+
+            sheet.write_string(row, 0, user_id)?;
+            sheet.write_string(row, 1, self.names.get(user_id).map_or("", String::as_str))?;
+
+            let mut present = 0;
+            for (i, day) in days.iter().enumerate() {
+                let Some(times) = punches.and_then(|p| p.get(day)) else {
+                    continue;
+                };
+                present += 1;
+                sheet.write_string_with_format(row, 2 + i as u16, self.cell(times), &centered)?;
+            }
+            sheet.write_number(row, total_col, present as f64)?;
+        }
+
+        sheet.set_column_width(0, 10)?;
+        sheet.set_column_width(1, 24)?;
+        sheet.set_freeze_panes(1, 2)?;
+        Ok(())
+    }
+
+    fn cell(&self, times: &[NaiveTime]) -> String {
+        let format = |t: &NaiveTime| t.format(&self.time_format).to_string();
+        match times {
+            [] => String::new(),
+            [only] => format(only),
+            [first, .., last] => format!("{} - {}", format(first), format(last)),
+        }
+    }
+
+    fn write_records(&self, sheet: &mut Worksheet, records: &[AttendanceRecord]) -> Result<()> {
+        let bold = Format::new().set_bold();
+        let datetime = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+
+        for (col, header) in ["User ID", "Name", "Timestamp", "Punch", "Verify Mode", "Work Code"]
+            .into_iter()
+            .enumerate()
+        {
+            sheet.write_string_with_format(0, col as u16, header, &bold)?;
+        }
+
+        let mut sorted: Vec<&AttendanceRecord> = records.iter().collect();
+        sorted.sort();
+
+        for (row, record) in sorted.into_iter().enumerate() {
+            let row = 1 + row as u32;
+            sheet.write_string(row, 0, &record.user_id)?;
+            sheet.write_string(row, 1, self.names.get(&record.user_id).map_or("", String::as_str))?;
+            sheet.write_datetime_with_format(row, 2, record.timestamp, &datetime)?;
+            sheet.write_string(row, 3, record.punch.to_string())?;
+            sheet.write_string(row, 4, record.verify_mode.to_string())?;
+            if let Some(code) = record.work_code {
+                sheet.write_number(row, 5, code as f64)?;
+            }
+        }
+
+        sheet.set_column_width(1, 24)?;
+        sheet.set_column_width(2, 20)?;
+        sheet.set_freeze_panes(1, 0)?;
+        Ok(())
+    }
+}
+
+impl Default for XlsxReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Days;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+
+    fn record(user_id: &str, day: u32, hour: u32, minute: u32) -> AttendanceRecord {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap();
+        AttendanceRecord::new(user_id, timestamp, VerifyMode::Fingerprint, PunchType::CheckIn)
+    }
+
+    #[test]
+    fn test_daily_grid_and_cells() {
+        let records = [
+            record("1", 1, 17, 15),
+            record("1", 1, 8, 2),
+            record("1", 1, 12, 0),
+            record("2", 3, 9, 0),
+        ];
+        let grid = daily_grid(&records);
+        let day1 = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        let report = XlsxReport::new();
+        assert_eq!(report.cell(&grid["1"][&day1]), "08:02 - 17:15");
+        assert_eq!(report.cell(&grid["2"][&day1.checked_add_days(Days::new(2)).unwrap()]), "09:00");
+
+        // The span covers the gap day without punches
+        assert_eq!(report.days(&grid).len(), 3);
+    }
+
+    #[test]
+    fn test_workbook_renders() {
+        let alice = User::builder(1, "1").name("Alice").build().unwrap();
+        let from = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 3, 7).unwrap();
+
+        let buffer = XlsxReport::new()
+            .users([&alice])
+            .range(from, to)
+            .to_buffer(&[record("1", 1, 8, 0), record("2", 2, 8, 30)])
+            .unwrap();
+
+        // xlsx files are zip archives
+        assert_eq!(&buffer[..2], b"PK");
+    }
+}
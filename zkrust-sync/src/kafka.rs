@@ -0,0 +1,133 @@
+//! Kafka producer
+//!
+//! [`KafkaSink`] streams attendance records into a Kafka topic as JSON,
+//! one message per record. Every message is keyed by device ID and a hash
+//! of the record, so:
+//!
+//! - all punches of a device land in the same partition, in order
+//! - a record re-delivered after a crash carries the same key, and
+//!   consumers or compacted topics can drop the duplicate
+//!
+//! The producer created by [`KafkaSink::connect`] has idempotence enabled,
+//! so broker-side retries do not duplicate messages either.
+
+use std::time::Duration;
+
+use rdkafka::ClientConfig;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use tracing::debug;
+
+use zkrust_types::AttendanceRecord;
+
+use crate::cursor::record_key;
+use crate::error::{Error, Result};
+use crate::sink::Sink;
+
+/// Sink producing to a Kafka topic
+#[derive(Clone)]
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+    timeout: Duration,
+}
+
+impl KafkaSink {
+    /// Create a sink on an existing producer
+    pub fn new(producer: FutureProducer, topic: impl Into<String>) -> Self {
+        Self {
+            producer,
+            topic: topic.into(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Create an idempotent producer for `brokers` (comma-separated)
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zkrust_sync::{KafkaSink, MemoryCursorStore, SyncEngine};
+    ///
+    /// # async fn example() -> zkrust_sync::Result<()> {
+    /// let sink = KafkaSink::connect("kafka-1:9092,kafka-2:9092", "attendance")?;
+    /// let mut engine = SyncEngine::new(sink, MemoryCursorStore::new());
+    /// engine.sync_once().await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect(brokers: &str, topic: impl Into<String>) -> Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("enable.idempotence", "true")
+            .create()?;
+        Ok(Self::new(producer, topic))
+    }
+
+    /// Set how long to wait for each delivery (default 30 s)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Message key of a record: `{device_id}:{record hash}`
+///
+/// The hash is 64-bit FNV-1a over the record's identity (user, time, punch,
+/// verify mode and work code), stable across processes and releases.
+pub fn message_key(device_id: &str, record: &AttendanceRecord) -> String {
+    let hash = record_key(record)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    format!("{}:{:016x}", device_id, hash)
+}
+
+#[async_trait::async_trait]
+impl Sink for KafkaSink {
+    async fn write_attendance(&mut self, device_id: &str, records: &[AttendanceRecord]) -> Result<()> {
+        for record in records {
+            let key = message_key(device_id, record);
+            let payload = serde_json::to_vec(record)?;
+            let headers = OwnedHeaders::new().insert(Header {
+                key: "device_id",
+                value: Some(device_id),
+            });
+
+            self.producer
+                .send(
+                    FutureRecord::to(&self.topic).key(&key).payload(&payload).headers(headers),
+                    self.timeout,
+                )
+                .await
+                .map_err(|(e, _)| Error::Kafka(e))?;
+        }
+
+        debug!("Produced {} records to {}", records.len(), self.topic);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+
+    fn record(user_id: &str, punch: PunchType) -> AttendanceRecord {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        AttendanceRecord::new(user_id, timestamp, VerifyMode::Fingerprint, punch)
+    }
+
+    #[test]
+    fn test_message_key() {
+        let key = message_key("ABC123", &record("1", PunchType::CheckIn));
+
+        assert!(key.starts_with("ABC123:"));
+        assert_eq!(key.len(), "ABC123:".len() + 16);
+        assert_eq!(key, message_key("ABC123", &record("1", PunchType::CheckIn)));
+        assert_ne!(key, message_key("ABC123", &record("1", PunchType::CheckOut)));
+        assert_ne!(key, message_key("ABC123", &record("2", PunchType::CheckIn)));
+    }
+}
@@ -0,0 +1,79 @@
+//! # zkrust-sync
+//!
+//! Incremental attendance synchronization and export for ZKTeco devices.
+//!
+//! [`SyncEngine`] polls each registered device, keeps a persistent
+//! [`Cursor`] per device so only new records are delivered, removes
+//! duplicates and hands the records to a [`Sink`].
+//!
+//! ## Quick Start
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use zkrust::Device;
+//! use zkrust_sync::{FileCursorStore, MemorySink, SyncEngine};
+//!
+//! #[tokio::main]
+//! async fn main() -> zkrust_sync::Result<()> {
+//!     let mut device = Device::new("192.168.1.201", 4370);
+//!     device.connect().await?;
+//!
+//!     let mut engine = SyncEngine::new(MemorySink::new(), FileCursorStore::new("cursors.json"));
+//!     engine.add_device("front-door", device.into_handle());
+//!
+//!     engine.run(Duration::from_secs(60)).await;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! ## Cargo Features
+//!
+//! - `sqlite`: [`SqliteStore`](sqlite::SqliteStore), a SQLite database
+//!   usable as both sink and cursor store
+//! - `postgres`: [`PostgresStore`](postgres::PostgresStore), the same
+//!   schema on PostgreSQL for aggregating many sites in one database
+//! - `xlsx`: [`XlsxReport`](export::xlsx::XlsxReport), a per-user, per-day
+//!   Excel attendance sheet
+//! - `kafka`: [`KafkaSink`](kafka::KafkaSink), streaming attendance into a
+//!   Kafka topic with stable per-record keys (builds librdkafka)
+//! - `mqtt`: [`MqttSink`](mqtt::MqttSink), publishing attendance and device
+//!   health to `zk/{device}/...` topics
+
+pub mod cursor;
+pub mod engine;
+pub mod error;
+pub mod export;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod normalize;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod report;
+pub mod scheduler;
+pub mod sink;
+pub mod source;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod users;
+
+// Re-exports
+pub use cursor::{Cursor, CursorStore, FileCursorStore, MemoryCursorStore};
+pub use engine::{SyncEngine, SyncReport};
+pub use error::{Error, Result};
+pub use export::{CsvExporter, NdjsonExporter};
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaSink;
+#[cfg(feature = "mqtt")]
+pub use mqtt::MqttSink;
+pub use normalize::{NormalizedEvent, Normalizer};
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStore;
+pub use report::{DailyReport, DailySummary};
+pub use scheduler::{JobStatus, Scheduler, SchedulerHandle};
+pub use sink::{MemorySink, Sink};
+pub use source::AttendanceSource;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+pub use users::{ConflictPolicy, Resolution, SourceUser, UserConflict, UserSync, UserSyncReport, UserSyncState};
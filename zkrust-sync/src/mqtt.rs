@@ -0,0 +1,222 @@
+//! MQTT publisher
+//!
+//! [`MqttSink`] publishes attendance records and device health as JSON to
+//! per-device topics, for home-automation and SCADA systems that already
+//! speak MQTT:
+//!
+//! | Topic                   | Payload                               | Retained |
+//! |-------------------------|---------------------------------------|----------|
+//! | `zk/{device}/attlog`    | one [`AttendanceRecord`] per message  | no       |
+//! | `zk/{device}/health`    | latest [`HealthReport`] summary       | yes      |
+//!
+//! `{device}` is the ID the device was registered under in the
+//! [`SyncEngine`](crate::SyncEngine) or [`Scheduler`](crate::Scheduler);
+//! register devices by serial number to get `zk/{device_sn}/...` topics.
+
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use zkrust::HealthReport;
+use zkrust_types::{AttendanceRecord, DeviceCapacity};
+
+use crate::error::Result;
+use crate::sink::Sink;
+
+/// Delay before polling the event loop again after a connection error
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Sink publishing to an MQTT broker
+#[derive(Clone)]
+pub struct MqttSink {
+    client: AsyncClient,
+    prefix: String,
+    qos: QoS,
+}
+
+impl MqttSink {
+    /// Create a sink on an existing client
+    ///
+    /// The client's event loop must be polled elsewhere; see
+    /// [`connect`](Self::connect) for a sink that drives its own.
+    pub fn new(client: AsyncClient) -> Self {
+        Self {
+            client,
+            prefix: "zk".to_string(),
+            qos: QoS::AtLeastOnce,
+        }
+    }
+
+    /// Connect to a broker and drive the event loop on a background task
+    ///
+    /// The task reconnects after errors until it is aborted through the
+    /// returned handle.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use rumqttc::MqttOptions;
+    /// use zkrust_sync::{MemoryCursorStore, MqttSink, SyncEngine};
+    ///
+    /// # async fn example() {
+    /// let (sink, event_loop) = MqttSink::connect(MqttOptions::new("zkrust", "localhost", 1883), 64);
+    /// let mut engine = SyncEngine::new(sink, MemoryCursorStore::new());
+    /// engine.sync_once().await;
+    /// event_loop.abort();
+    /// # }
+    /// ```
+    pub fn connect(options: MqttOptions, capacity: usize) -> (Self, JoinHandle<()>) {
+        let (client, mut event_loop) = AsyncClient::new(options, capacity);
+
+        let task = tokio::spawn(async move {
+            loop {
+                if let Err(e) = event_loop.poll().await {
+                    warn!("MQTT connection error: {}", e);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        });
+
+        (Self::new(client), task)
+    }
+
+    /// Set the topic prefix (default `zk`)
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Set the QoS of published messages (default at-least-once)
+    pub fn qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Topic for a device and message kind, e.g. `zk/{device}/attlog`
+    pub fn topic(&self, device_id: &str, kind: &str) -> String {
+        format!("{}/{}/{}", self.prefix, device_id, kind)
+    }
+
+    /// Publish a health report as a retained message
+    pub async fn publish_health(&self, device_id: &str, report: &HealthReport) -> Result<()> {
+        let payload = serde_json::to_vec(&HealthPayload::from(report))?;
+        self.client
+            .publish(self.topic(device_id, "health"), self.qos, true, payload)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for MqttSink {
+    async fn write_attendance(&mut self, device_id: &str, records: &[AttendanceRecord]) -> Result<()> {
+        let topic = self.topic(device_id, "attlog");
+        for record in records {
+            let payload = serde_json::to_vec(record)?;
+            self.client.publish(topic.as_str(), self.qos, false, payload).await?;
+        }
+        debug!("Published {} records to {}", records.len(), topic);
+        Ok(())
+    }
+}
+
+/// JSON shape of a published [`HealthReport`]
+#[derive(Serialize)]
+struct HealthPayload {
+    checked_at: NaiveDateTime,
+    healthy: bool,
+    connected: bool,
+    authenticated: bool,
+    latency_ms: Option<u128>,
+    device_time: Option<NaiveDateTime>,
+    clock_skew_secs: Option<i64>,
+    capacity: Option<DeviceCapacity>,
+    issues: Vec<String>,
+}
+
+impl From<&HealthReport> for HealthPayload {
+    fn from(report: &HealthReport) -> Self {
+        Self {
+            checked_at: report.checked_at,
+            healthy: report.is_healthy(),
+            connected: report.connected,
+            authenticated: report.authenticated,
+            latency_ms: report.latency.map(|l| l.as_millis()),
+            device_time: report.device_time,
+            clock_skew_secs: report.clock_skew.map(|s| s.num_seconds()),
+            capacity: report.capacity,
+            issues: report.issues.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rumqttc::Request;
+    use zkrust::HealthIssue;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+
+    fn sink() -> (MqttSink, flume::Receiver<Request>) {
+        let (tx, rx) = flume::bounded(16);
+        (MqttSink::new(AsyncClient::from_senders(tx)), rx)
+    }
+
+    fn published(rx: &flume::Receiver<Request>) -> Vec<rumqttc::Publish> {
+        rx.try_iter()
+            .filter_map(|r| match r {
+                Request::Publish(publish) => Some(publish),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_publish_attendance() {
+        let (sink, rx) = sink();
+        let mut sink = sink.prefix("site1");
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let records = vec![
+            AttendanceRecord::new("1", timestamp, VerifyMode::Fingerprint, PunchType::CheckIn),
+            AttendanceRecord::new("2", timestamp, VerifyMode::Card, PunchType::CheckOut),
+        ];
+
+        sink.write_attendance("ABC123", &records).await.unwrap();
+
+        let messages = published(&rx);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].topic, "site1/ABC123/attlog");
+        assert!(!messages[0].retain);
+        let decoded: AttendanceRecord = serde_json::from_slice(&messages[1].payload).unwrap();
+        assert_eq!(decoded, records[1]);
+    }
+
+    #[tokio::test]
+    async fn test_publish_health_is_retained() {
+        let (sink, rx) = sink();
+        let report = HealthReport {
+            checked_at: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap(),
+            connected: false,
+            authenticated: false,
+            latency: None,
+            device_time: None,
+            clock_skew: None,
+            capacity: None,
+            issues: vec![HealthIssue::NotConnected],
+        };
+
+        sink.publish_health("ABC123", &report).await.unwrap();
+
+        let messages = published(&rx);
+        assert_eq!(messages[0].topic, "zk/ABC123/health");
+        assert!(messages[0].retain);
+        let json: serde_json::Value = serde_json::from_slice(&messages[0].payload).unwrap();
+        assert_eq!(json["healthy"], false);
+        assert_eq!(json["issues"][0], "Device not connected");
+    }
+}
@@ -0,0 +1,323 @@
+//! Attendance normalization for payroll
+//!
+//! Raw logs are noisy: people punch twice, forget to punch out, or use the
+//! wrong state key. [`Normalizer`] turns a batch of records into paired
+//! shifts and flags what it cannot pair:
+//!
+//! 1. punches by the same user within the dedup window collapse into the
+//!    first one
+//! 2. the remaining punches are paired into in/out [`Shift`]s per user
+//! 3. unpaired punches become [`NormalizedEvent::MissingCheckOut`] or
+//!    [`NormalizedEvent::MissingCheckIn`] anomalies
+//!
+//! [`Shift`]: NormalizedEvent::Shift
+
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use zkrust_core::constants::PunchType;
+use zkrust_types::AttendanceRecord;
+
+/// How punches are classified as in or out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PairingMode {
+    /// Use the punch type chosen on the device (check-in, break-out, ...)
+    #[default]
+    PunchType,
+
+    /// Ignore the punch type and alternate in, out, in, ...
+    ///
+    /// For devices left on a single state key, where every punch is logged
+    /// as a check-in. A punch more than the maximum shift after an open
+    /// in-punch starts a new shift.
+    Alternate,
+}
+
+/// Normalized attendance event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NormalizedEvent {
+    /// A paired in and out punch
+    Shift {
+        check_in: AttendanceRecord,
+        check_out: AttendanceRecord,
+    },
+
+    /// Latest in-punch of a user, not closed yet
+    ///
+    /// Not an anomaly: the user may still be at work. Feed it again with
+    /// the next batch to pair it.
+    Open(AttendanceRecord),
+
+    /// In-punch followed by another in-punch, or never closed within the
+    /// maximum shift length
+    MissingCheckOut(AttendanceRecord),
+
+    /// Out-punch without a preceding in-punch
+    MissingCheckIn(AttendanceRecord),
+}
+
+impl NormalizedEvent {
+    /// User the event belongs to
+    pub fn user_id(&self) -> &str {
+        &self.first().user_id
+    }
+
+    /// Time of the first punch of the event
+    pub fn timestamp(&self) -> NaiveDateTime {
+        self.first().timestamp
+    }
+
+    /// Worked time of a shift
+    pub fn duration(&self) -> Option<TimeDelta> {
+        match self {
+            Self::Shift { check_in, check_out } => Some(check_out.timestamp - check_in.timestamp),
+            _ => None,
+        }
+    }
+
+    /// Whether the event needs attention
+    pub fn is_anomaly(&self) -> bool {
+        matches!(self, Self::MissingCheckOut(_) | Self::MissingCheckIn(_))
+    }
+
+    fn first(&self) -> &AttendanceRecord {
+        match self {
+            Self::Shift { check_in, .. } => check_in,
+            Self::Open(record) | Self::MissingCheckOut(record) | Self::MissingCheckIn(record) => record,
+        }
+    }
+}
+
+/// Result of [`Normalizer::normalize`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Normalized {
+    /// Events ordered by time, then user
+    pub events: Vec<NormalizedEvent>,
+
+    /// Punches dropped as double punches
+    pub duplicates: Vec<AttendanceRecord>,
+}
+
+impl Normalized {
+    /// Events that need attention
+    pub fn anomalies(&self) -> impl Iterator<Item = &NormalizedEvent> {
+        self.events.iter().filter(|e| e.is_anomaly())
+    }
+}
+
+/// Dedup, pairing and anomaly detection for attendance records
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    dedup_window: TimeDelta,
+    max_shift: TimeDelta,
+    mode: PairingMode,
+}
+
+impl Normalizer {
+    /// Create a normalizer with a 60 s dedup window and 16 h maximum shift
+    pub fn new() -> Self {
+        Self {
+            dedup_window: TimeDelta::seconds(60),
+            max_shift: TimeDelta::hours(16),
+            mode: PairingMode::default(),
+        }
+    }
+
+    /// Treat punches closer than `window` to the previous one as duplicates
+    pub fn dedup_window(mut self, window: TimeDelta) -> Self {
+        self.dedup_window = window;
+        self
+    }
+
+    /// Longest in-to-out span still paired as one shift
+    pub fn max_shift(mut self, max_shift: TimeDelta) -> Self {
+        self.max_shift = max_shift;
+        self
+    }
+
+    /// Set how punches are classified as in or out
+    pub fn pairing(mut self, mode: PairingMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Normalize a batch of records (any order, any users)
+    ///
+    /// A trailing in-punch is reported as [`NormalizedEvent::Open`] while
+    /// it is within the maximum shift of the newest record in the batch,
+    /// and as missing its check-out after that.
+    pub fn normalize<'a>(&self, records: impl IntoIterator<Item = &'a AttendanceRecord>) -> Normalized {
+        let mut by_user: BTreeMap<&str, Vec<&AttendanceRecord>> = BTreeMap::new();
+        let mut newest = None;
+        for record in records {
+            by_user.entry(&record.user_id).or_default().push(record);
+            newest = newest.max(Some(record.timestamp));
+        }
+
+        let mut normalized = Normalized::default();
+        for punches in by_user.values_mut() {
+            punches.sort();
+            let kept = self.dedup(punches, &mut normalized.duplicates);
+            self.pair(&kept, newest, &mut normalized.events);
+        }
+
+        normalized
+            .events
+            .sort_by(|a, b| (a.timestamp(), a.user_id()).cmp(&(b.timestamp(), b.user_id())));
+        normalized
+    }
+
+    fn dedup<'a>(
+        &self,
+        punches: &[&'a AttendanceRecord],
+        duplicates: &mut Vec<AttendanceRecord>,
+    ) -> Vec<&'a AttendanceRecord> {
+        let mut kept: Vec<&AttendanceRecord> = Vec::with_capacity(punches.len());
+        for &punch in punches {
+            match kept.last() {
+                Some(last) if punch.timestamp - last.timestamp < self.dedup_window => {
+                    duplicates.push(punch.clone());
+                }
+                _ => kept.push(punch),
+            }
+        }
+        kept
+    }
+
+    fn pair(
+        &self,
+        punches: &[&AttendanceRecord],
+        newest: Option<NaiveDateTime>,
+        events: &mut Vec<NormalizedEvent>,
+    ) {
+        let mut open: Option<&AttendanceRecord> = None;
+
+        for &punch in punches {
+            let is_in = match self.mode {
+                PairingMode::PunchType => matches!(
+                    punch.punch,
+                    PunchType::CheckIn | PunchType::BreakIn | PunchType::OvertimeIn
+                ),
+                // A punch long after the open one starts a new shift
+                PairingMode::Alternate => {
+                    open.is_none_or(|open| punch.timestamp - open.timestamp > self.max_shift)
+                }
+            };
+
+            if is_in {
+                if let Some(previous) = open.replace(punch) {
+                    events.push(NormalizedEvent::MissingCheckOut(previous.clone()));
+                }
+                continue;
+            }
+
+            match open.take() {
+                Some(check_in) if punch.timestamp - check_in.timestamp <= self.max_shift => {
+                    events.push(NormalizedEvent::Shift {
+                        check_in: check_in.clone(),
+                        check_out: punch.clone(),
+                    });
+                }
+                stale => {
+                    if let Some(check_in) = stale {
+                        events.push(NormalizedEvent::MissingCheckOut(check_in.clone()));
+                    }
+                    events.push(NormalizedEvent::MissingCheckIn(punch.clone()));
+                }
+            }
+        }
+
+        if let Some(check_in) = open {
+            let expired = newest.is_some_and(|newest| newest - check_in.timestamp > self.max_shift);
+            events.push(if expired {
+                NormalizedEvent::MissingCheckOut(check_in.clone())
+            } else {
+                NormalizedEvent::Open(check_in.clone())
+            });
+        }
+    }
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use zkrust_core::constants::VerifyMode;
+
+    fn punch(user_id: &str, day: u32, hour: u32, minute: u32, punch: PunchType) -> AttendanceRecord {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap();
+        AttendanceRecord::new(user_id, timestamp, VerifyMode::Fingerprint, punch)
+    }
+
+    #[test]
+    fn test_dedup_and_pairing() {
+        let records = [
+            punch("1", 1, 8, 0, PunchType::CheckIn),
+            punch("1", 1, 8, 0, PunchType::CheckIn),
+            punch("1", 1, 17, 0, PunchType::CheckOut),
+            punch("2", 1, 9, 0, PunchType::CheckIn),
+            punch("2", 1, 18, 30, PunchType::CheckOut),
+        ];
+
+        let normalized = Normalizer::new().normalize(&records);
+
+        assert_eq!(normalized.duplicates.len(), 1);
+        assert_eq!(normalized.events.len(), 2);
+        assert_eq!(normalized.events[0].user_id(), "1");
+        assert_eq!(normalized.events[0].duration(), Some(TimeDelta::hours(9)));
+        assert_eq!(normalized.events[1].duration(), Some(TimeDelta::minutes(570)));
+        assert_eq!(normalized.anomalies().count(), 0);
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let records = [
+            // Forgot to punch out on day 1
+            punch("1", 1, 8, 0, PunchType::CheckIn),
+            punch("1", 2, 8, 0, PunchType::CheckIn),
+            punch("1", 2, 17, 0, PunchType::CheckOut),
+            // Out without in
+            punch("1", 2, 19, 0, PunchType::CheckOut),
+            // Still at work at the end of the batch
+            punch("2", 2, 18, 0, PunchType::CheckIn),
+        ];
+
+        let events = Normalizer::new().normalize(&records).events;
+
+        assert_eq!(events[0], NormalizedEvent::MissingCheckOut(records[0].clone()));
+        assert!(matches!(events[1], NormalizedEvent::Shift { .. }));
+        assert_eq!(events[2], NormalizedEvent::Open(records[4].clone()));
+        assert_eq!(events[3], NormalizedEvent::MissingCheckIn(records[3].clone()));
+    }
+
+    #[test]
+    fn test_alternate_mode_and_stale_open() {
+        // Every punch logged as a check-in
+        let records = [
+            punch("1", 1, 8, 0, PunchType::CheckIn),
+            punch("1", 1, 12, 0, PunchType::CheckIn),
+            punch("1", 1, 13, 0, PunchType::CheckIn),
+            punch("1", 3, 8, 0, PunchType::CheckIn),
+            punch("1", 3, 9, 0, PunchType::CheckIn),
+        ];
+
+        let normalized = Normalizer::new().pairing(PairingMode::Alternate).normalize(&records);
+        let events = normalized.events;
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].duration(), Some(TimeDelta::hours(4)));
+        // The 13:00 in-punch never closed within 16 hours
+        assert_eq!(events[1], NormalizedEvent::MissingCheckOut(records[2].clone()));
+        assert_eq!(events[2].duration(), Some(TimeDelta::hours(1)));
+    }
+}
@@ -0,0 +1,371 @@
+//! PostgreSQL storage backend
+//!
+//! [`PostgresStore`] mirrors the [`SqliteStore`](crate::sqlite) schema on
+//! PostgreSQL so many sites can sync into one central database. Every
+//! table is keyed by device ID, so devices never overwrite each other.
+//!
+//! PostgreSQL has no unsigned integers; record indexes and codes are
+//! stored in the next wider signed type.
+
+use chrono::NaiveDateTime;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::{Postgres, Row, Transaction};
+
+use zkrust_core::constants::{Privilege, PunchType, VerifyMode};
+use zkrust_types::{AttendanceRecord, FaceTemplate, FingerprintTemplate, User};
+
+use crate::cursor::{Cursor, CursorStore};
+use crate::error::{Error, Result};
+use crate::sink::Sink;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS devices (
+    device_id   TEXT PRIMARY KEY,
+    cursor      JSONB,
+    last_write  TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    device_id   TEXT NOT NULL,
+    uid         INTEGER NOT NULL,
+    user_id     TEXT NOT NULL,
+    name        TEXT NOT NULL,
+    privilege   SMALLINT NOT NULL,
+    enabled     BOOLEAN NOT NULL,
+    password    TEXT NOT NULL,
+    card        BIGINT NOT NULL,
+    group_id    TEXT NOT NULL,
+    PRIMARY KEY (device_id, uid)
+);
+
+CREATE TABLE IF NOT EXISTS templates (
+    device_id         TEXT NOT NULL,
+    uid               INTEGER NOT NULL,
+    kind              TEXT NOT NULL,
+    slot              SMALLINT NOT NULL,
+    valid             BOOLEAN NOT NULL,
+    duress            BOOLEAN NOT NULL,
+    algorithm_version SMALLINT NOT NULL,
+    data              BYTEA NOT NULL,
+    PRIMARY KEY (device_id, uid, kind, slot)
+);
+
+CREATE TABLE IF NOT EXISTS attendance (
+    device_id   TEXT NOT NULL,
+    user_id     TEXT NOT NULL,
+    timestamp   TIMESTAMP NOT NULL,
+    verify_mode SMALLINT NOT NULL,
+    punch       SMALLINT NOT NULL,
+    work_code   BIGINT NOT NULL,
+    UNIQUE (device_id, user_id, timestamp, verify_mode, punch, work_code)
+);
+
+CREATE INDEX IF NOT EXISTS attendance_by_time ON attendance (device_id, timestamp);
+";
+
+const UPSERT_TEMPLATE: &str = "
+INSERT INTO templates (device_id, uid, kind, slot, valid, duress, algorithm_version, data)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT (device_id, uid, kind, slot) DO UPDATE SET
+    valid = excluded.valid, duress = excluded.duress,
+    algorithm_version = excluded.algorithm_version, data = excluded.data
+";
+
+/// PostgreSQL-backed sink and cursor store
+///
+/// Clones share the connection pool, so the same store can be handed to
+/// [`SyncEngine::new`](crate::SyncEngine::new) as both sink and cursor
+/// store.
+#[derive(Debug, Clone)]
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    /// Connect to `url` and create the schema if needed
+    pub async fn connect(url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new().max_connections(4).connect(url).await?;
+        Self::with_pool(pool).await
+    }
+
+    /// Use an existing pool, creating the schema if needed
+    pub async fn with_pool(pool: PgPool) -> Result<Self> {
+        sqlx::raw_sql(SCHEMA).execute(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    /// The underlying connection pool
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    /// Stored attendance of `device_id`, in chronological order
+    ///
+    /// A missing work code is stored as 0 and read back as `None`.
+    pub async fn attendance(&self, device_id: &str) -> Result<Vec<AttendanceRecord>> {
+        let rows = sqlx::query(
+            "SELECT user_id, timestamp, verify_mode, punch, work_code FROM attendance
+             WHERE device_id = $1 ORDER BY timestamp, user_id",
+        )
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let record = AttendanceRecord::new(
+                    row.try_get::<String, _>("user_id")?,
+                    row.try_get::<NaiveDateTime, _>("timestamp")?,
+                    VerifyMode::try_from(narrow::<u8>(row.try_get::<i16, _>("verify_mode")?)?)
+                        .map_err(corrupt)?,
+                    PunchType::try_from(narrow::<u8>(row.try_get::<i16, _>("punch")?)?)
+                        .map_err(corrupt)?,
+                );
+                Ok(match narrow::<u32>(row.try_get::<i64, _>("work_code")?)? {
+                    0 => record,
+                    code => record.with_work_code(code),
+                })
+            })
+            .collect()
+    }
+
+    /// Stored users of `device_id`, ordered by record index
+    pub async fn users(&self, device_id: &str) -> Result<Vec<User>> {
+        let rows = sqlx::query(
+            "SELECT uid, user_id, name, privilege, enabled, password, card, group_id FROM users
+             WHERE device_id = $1 ORDER BY uid",
+        )
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(User {
+                    uid: narrow(row.try_get::<i32, _>("uid")?)?,
+                    user_id: row.try_get("user_id")?,
+                    name: row.try_get("name")?,
+                    privilege: Privilege::try_from(narrow::<u8>(row.try_get::<i16, _>("privilege")?)?)
+                        .map_err(corrupt)?,
+                    enabled: row.try_get("enabled")?,
+                    password: row.try_get("password")?,
+                    card: narrow(row.try_get::<i64, _>("card")?)?,
+                    group_id: row.try_get("group_id")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn begin(&self, device_id: &str) -> Result<Transaction<'static, Postgres>> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO devices (device_id, last_write) VALUES ($1, now())
+             ON CONFLICT (device_id) DO UPDATE SET last_write = excluded.last_write",
+        )
+        .bind(device_id)
+        .execute(&mut *tx)
+        .await?;
+        Ok(tx)
+    }
+}
+
+fn narrow<T: TryFrom<i64>>(value: impl Into<i64>) -> Result<T> {
+    let value = value.into();
+    T::try_from(value).map_err(|_| Error::Sink(format!("Stored value {} out of range", value)))
+}
+
+async fn upsert_users(tx: &mut Transaction<'static, Postgres>, device_id: &str, users: &[User]) -> Result<()> {
+    for user in users {
+        sqlx::query(
+            "INSERT INTO users (device_id, uid, user_id, name, privilege, enabled, password, card, group_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             ON CONFLICT (device_id, uid) DO UPDATE SET
+                 user_id = excluded.user_id, name = excluded.name,
+                 privilege = excluded.privilege, enabled = excluded.enabled,
+                 password = excluded.password, card = excluded.card,
+                 group_id = excluded.group_id",
+        )
+        .bind(device_id)
+        .bind(i32::from(user.uid))
+        .bind(&user.user_id)
+        .bind(&user.name)
+        .bind(i16::from(u8::from(user.privilege)))
+        .bind(user.enabled)
+        .bind(&user.password)
+        .bind(i64::from(user.card))
+        .bind(&user.group_id)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+fn corrupt(e: zkrust_core::Error) -> Error {
+    Error::Sink(format!("Invalid stored value: {}", e))
+}
+
+#[async_trait::async_trait]
+impl Sink for PostgresStore {
+    async fn write_attendance(&mut self, device_id: &str, records: &[AttendanceRecord]) -> Result<()> {
+        let mut tx = self.begin(device_id).await?;
+
+        for record in records {
+            sqlx::query(
+                "INSERT INTO attendance (device_id, user_id, timestamp, verify_mode, punch, work_code)
+                 VALUES ($1, $2, $3, $4, $5, $6)
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind(device_id)
+            .bind(&record.user_id)
+            .bind(record.timestamp)
+            .bind(i16::from(u8::from(record.verify_mode)))
+            .bind(i16::from(u8::from(record.punch)))
+            .bind(i64::from(record.work_code.unwrap_or(0)))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Upsert `users` and delete stored users no longer on the device
+    async fn write_users(&mut self, device_id: &str, users: &[User]) -> Result<()> {
+        let mut tx = self.begin(device_id).await?;
+        upsert_users(&mut tx, device_id, users).await?;
+
+        let current: Vec<i32> = users.iter().map(|u| i32::from(u.uid)).collect();
+        sqlx::query("DELETE FROM users WHERE device_id = $1 AND NOT (uid = ANY($2))")
+            .bind(device_id)
+            .bind(current)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn update_users(&mut self, device_id: &str, users: &[User]) -> Result<()> {
+        let mut tx = self.begin(device_id).await?;
+        upsert_users(&mut tx, device_id, users).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn write_fingerprints(&mut self, device_id: &str, templates: &[FingerprintTemplate]) -> Result<()> {
+        let mut tx = self.begin(device_id).await?;
+
+        for template in templates {
+            sqlx::query(UPSERT_TEMPLATE)
+                .bind(device_id)
+                .bind(i32::from(template.uid))
+                .bind("finger")
+                .bind(i16::from(template.finger))
+                .bind(template.valid)
+                .bind(template.duress)
+                .bind(i16::from(template.algorithm_version))
+                .bind(&template.data)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn write_faces(&mut self, device_id: &str, templates: &[FaceTemplate]) -> Result<()> {
+        let mut tx = self.begin(device_id).await?;
+
+        for template in templates {
+            sqlx::query(UPSERT_TEMPLATE)
+                .bind(device_id)
+                .bind(i32::from(template.uid))
+                .bind("face")
+                .bind(0i16)
+                .bind(template.valid)
+                .bind(false)
+                .bind(i16::from(template.algorithm_version))
+                .bind(&template.data)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl CursorStore for PostgresStore {
+    async fn load(&mut self, device_id: &str) -> Result<Option<Cursor>> {
+        let cursor: Option<Option<String>> =
+            sqlx::query_scalar("SELECT cursor::text FROM devices WHERE device_id = $1")
+                .bind(device_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        match cursor.flatten() {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(&mut self, device_id: &str, cursor: &Cursor) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO devices (device_id, cursor, last_write) VALUES ($1, $2::jsonb, now())
+             ON CONFLICT (device_id) DO UPDATE SET cursor = excluded.cursor, last_write = excluded.last_write",
+        )
+        .bind(device_id)
+        .bind(serde_json::to_string(cursor)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    // These tests need a scratch database.
+    // Run with: ZKRUST_TEST_POSTGRES=postgres://... cargo test --features postgres -- --ignored
+
+    async fn store() -> PostgresStore {
+        let url = std::env::var("ZKRUST_TEST_POSTGRES").expect("ZKRUST_TEST_POSTGRES not set");
+        PostgresStore::connect(&url).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Only run with a database
+    async fn test_attendance_round_trip() {
+        let mut store = store().await;
+        let device_id = format!("test-{}", std::process::id());
+
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let records = [AttendanceRecord::new("1", timestamp, VerifyMode::Card, PunchType::CheckOut).with_work_code(7)];
+        store.write_attendance(&device_id, &records).await.unwrap();
+        store.write_attendance(&device_id, &records).await.unwrap();
+
+        assert_eq!(store.attendance(&device_id).await.unwrap(), records);
+
+        let mut cursor = Cursor::default();
+        cursor.advance(&records);
+        store.save(&device_id, &cursor).await.unwrap();
+        assert_eq!(store.load(&device_id).await.unwrap(), Some(cursor));
+    }
+
+    #[tokio::test]
+    #[ignore] // Only run with a database
+    async fn test_users_replace_device_list() {
+        let mut store = store().await;
+        let device_id = format!("test-users-{}", std::process::id());
+
+        let alice = User::builder(1, "100").name("Alice").build().unwrap();
+        let bob = User::builder(2, "200").name("Bob").build().unwrap();
+        store.write_users(&device_id, &[alice, bob.clone()]).await.unwrap();
+        store.write_users(&device_id, std::slice::from_ref(&bob)).await.unwrap();
+
+        assert_eq!(store.users(&device_id).await.unwrap(), vec![bob]);
+    }
+}
@@ -0,0 +1,298 @@
+//! Per-user daily attendance summaries
+//!
+//! [`DailyReport`] folds [`NormalizedEvent`]s into one [`DailySummary`] per
+//! user and workday: first in, last out, worked time and missing punches.
+//! Workdays start at a configurable time of day, so a night shift from
+//! 22:00 to 06:00 counts towards the day it started, and an optional
+//! schedule with a grace period flags late arrivals and early departures.
+//!
+//! ```
+//! use chrono::{NaiveTime, TimeDelta};
+//! use zkrust_sync::normalize::Normalizer;
+//! use zkrust_sync::report::DailyReport;
+//!
+//! # fn example(records: &[zkrust::AttendanceRecord]) {
+//! let normalized = Normalizer::new().normalize(records);
+//! let report = DailyReport::new()
+//!     .day_start(NaiveTime::from_hms_opt(4, 0, 0).unwrap())
+//!     .schedule(NaiveTime::from_hms_opt(9, 0, 0).unwrap(), NaiveTime::from_hms_opt(17, 0, 0).unwrap())
+//!     .grace(TimeDelta::minutes(5));
+//!
+//! for day in report.summarize(&normalized.events) {
+//!     println!("{} {}: {}h", day.date, day.user_id, day.worked.num_minutes() as f64 / 60.0);
+//! }
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+
+use crate::normalize::NormalizedEvent;
+
+/// Attendance of one user on one workday
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailySummary {
+    /// User ID as shown on the device
+    pub user_id: String,
+
+    /// Workday (the date the day boundary started on)
+    pub date: NaiveDate,
+
+    /// Earliest in-punch
+    pub first_in: Option<NaiveDateTime>,
+
+    /// Latest out-punch
+    pub last_out: Option<NaiveDateTime>,
+
+    /// Sum of paired shifts
+    pub worked: TimeDelta,
+
+    /// Number of paired shifts
+    pub shifts: usize,
+
+    /// Out-punches without an in-punch
+    pub missing_check_in: usize,
+
+    /// In-punches never closed
+    pub missing_check_out: usize,
+
+    /// The user is still checked in
+    pub open: bool,
+
+    /// Arrival after the scheduled start plus grace
+    pub late_by: Option<TimeDelta>,
+
+    /// Departure before the scheduled end minus grace
+    pub left_early_by: Option<TimeDelta>,
+}
+
+impl DailySummary {
+    fn new(user_id: &str, date: NaiveDate) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            date,
+            first_in: None,
+            last_out: None,
+            worked: TimeDelta::zero(),
+            shifts: 0,
+            missing_check_in: 0,
+            missing_check_out: 0,
+            open: false,
+            late_by: None,
+            left_early_by: None,
+        }
+    }
+
+    /// Number of punches that could not be paired
+    pub fn missing_punches(&self) -> usize {
+        self.missing_check_in + self.missing_check_out
+    }
+
+    /// Whether the day needs attention (missing punches, late, left early)
+    pub fn needs_review(&self) -> bool {
+        self.missing_punches() > 0 || self.late_by.is_some() || self.left_early_by.is_some()
+    }
+
+    fn punch_in(&mut self, at: NaiveDateTime) {
+        self.first_in = Some(self.first_in.map_or(at, |first| first.min(at)));
+    }
+
+    fn punch_out(&mut self, at: NaiveDateTime) {
+        self.last_out = Some(self.last_out.map_or(at, |last| last.max(at)));
+    }
+}
+
+/// Builds [`DailySummary`]s from normalized events
+#[derive(Debug, Clone)]
+pub struct DailyReport {
+    day_start: NaiveTime,
+    schedule: Option<(NaiveTime, NaiveTime)>,
+    grace: TimeDelta,
+}
+
+impl DailyReport {
+    /// Create a report with midnight day boundaries and no schedule
+    pub fn new() -> Self {
+        Self {
+            day_start: NaiveTime::MIN,
+            schedule: None,
+            grace: TimeDelta::zero(),
+        }
+    }
+
+    /// Time of day a workday begins; earlier punches count towards the
+    /// previous day
+    pub fn day_start(mut self, time: NaiveTime) -> Self {
+        self.day_start = time;
+        self
+    }
+
+    /// Expected start and end of work, for late and early flags
+    ///
+    /// Times before the day start belong to the next calendar day, so a
+    /// 22:00-06:00 schedule works with a 12:00 day start.
+    pub fn schedule(mut self, start: NaiveTime, end: NaiveTime) -> Self {
+        self.schedule = Some((start, end));
+        self
+    }
+
+    /// Tolerance before arrivals count as late and departures as early
+    pub fn grace(mut self, grace: TimeDelta) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Workday a punch at `time` belongs to
+    pub fn workday(&self, time: NaiveDateTime) -> NaiveDate {
+        (time - (self.day_start - NaiveTime::MIN)).date()
+    }
+
+    /// Summarize events, ordered by date, then user
+    ///
+    /// Shifts count towards the workday of their check-in.
+    pub fn summarize<'a>(&self, events: impl IntoIterator<Item = &'a NormalizedEvent>) -> Vec<DailySummary> {
+        let mut days: BTreeMap<(NaiveDate, &str), DailySummary> = BTreeMap::new();
+
+        for event in events {
+            let date = self.workday(event.timestamp());
+            let day = days
+                .entry((date, event.user_id()))
+                .or_insert_with(|| DailySummary::new(event.user_id(), date));
+
+            match event {
+                NormalizedEvent::Shift { check_in, check_out } => {
+                    day.punch_in(check_in.timestamp);
+                    day.punch_out(check_out.timestamp);
+                    day.worked += check_out.timestamp - check_in.timestamp;
+                    day.shifts += 1;
+                }
+                NormalizedEvent::Open(record) => {
+                    day.punch_in(record.timestamp);
+                    day.open = true;
+                }
+                NormalizedEvent::MissingCheckOut(record) => {
+                    day.punch_in(record.timestamp);
+                    day.missing_check_out += 1;
+                }
+                NormalizedEvent::MissingCheckIn(record) => {
+                    day.punch_out(record.timestamp);
+                    day.missing_check_in += 1;
+                }
+            }
+        }
+
+        let mut summaries: Vec<_> = days.into_values().collect();
+        if let Some((start, end)) = self.schedule {
+            for day in &mut summaries {
+                let start = self.at(day.date, start) + self.grace;
+                let end = self.at(day.date, end) - self.grace;
+
+                day.late_by = day.first_in.filter(|&first| first > start).map(|first| first - start);
+                // An open day has no departure yet
+                if !day.open {
+                    day.left_early_by = day.last_out.filter(|&last| last < end).map(|last| end - last);
+                }
+            }
+        }
+        summaries
+    }
+
+    /// `time` on workday `date`
+    fn at(&self, date: NaiveDate, time: NaiveTime) -> NaiveDateTime {
+        let at = date.and_time(time);
+        if time < self.day_start {
+            at + TimeDelta::days(1)
+        } else {
+            at
+        }
+    }
+}
+
+impl Default for DailyReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zkrust_core::constants::{PunchType, VerifyMode};
+    use zkrust_types::AttendanceRecord;
+
+    use crate::normalize::Normalizer;
+
+    fn punch(user_id: &str, day: u32, hour: u32, minute: u32, punch: PunchType) -> AttendanceRecord {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap();
+        AttendanceRecord::new(user_id, timestamp, VerifyMode::Fingerprint, punch)
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_daily_summary() {
+        let records = [
+            punch("1", 1, 9, 3, PunchType::CheckIn),
+            punch("1", 1, 12, 0, PunchType::BreakOut),
+            punch("1", 1, 13, 0, PunchType::BreakIn),
+            punch("1", 1, 16, 30, PunchType::CheckOut),
+            // Forgot to punch in
+            punch("2", 1, 17, 0, PunchType::CheckOut),
+        ];
+        let events = Normalizer::new().normalize(&records).events;
+
+        let days = DailyReport::new()
+            .schedule(time(9, 0), time(17, 0))
+            .grace(TimeDelta::minutes(5))
+            .summarize(&events);
+
+        assert_eq!(days.len(), 2);
+        let first = &days[0];
+        assert_eq!(first.user_id, "1");
+        assert_eq!(first.first_in, Some(records[0].timestamp));
+        assert_eq!(first.last_out, Some(records[3].timestamp));
+        assert_eq!(first.worked, TimeDelta::minutes(177 + 210));
+        assert_eq!(first.shifts, 2);
+        // 09:03 is within the grace period; 16:30 is not
+        assert_eq!(first.late_by, None);
+        assert_eq!(first.left_early_by, Some(TimeDelta::minutes(25)));
+
+        let second = &days[1];
+        assert_eq!(second.missing_punches(), 1);
+        assert_eq!(second.first_in, None);
+        assert!(second.needs_review());
+    }
+
+    #[test]
+    fn test_night_shift_day_start() {
+        let records = [
+            punch("1", 1, 22, 20, PunchType::CheckIn),
+            punch("1", 2, 6, 0, PunchType::CheckOut),
+            punch("1", 2, 22, 0, PunchType::CheckIn),
+        ];
+        let events = Normalizer::new().normalize(&records).events;
+
+        let days = DailyReport::new()
+            .day_start(time(12, 0))
+            .schedule(time(22, 0), time(6, 0))
+            .grace(TimeDelta::minutes(10))
+            .summarize(&events);
+
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].date, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        assert_eq!(days[0].worked, TimeDelta::minutes(460));
+        assert_eq!(days[0].late_by, Some(TimeDelta::minutes(10)));
+        assert_eq!(days[0].left_early_by, None);
+
+        assert_eq!(days[1].date, NaiveDate::from_ymd_opt(2024, 3, 2).unwrap());
+        assert!(days[1].open);
+        assert_eq!(days[1].left_early_by, None);
+    }
+}
@@ -0,0 +1,380 @@
+//! Per-device polling scheduler
+//!
+//! Unlike [`SyncEngine`](crate::SyncEngine), which polls devices one after
+//! another, the [`Scheduler`] gives every device its own job with its own
+//! interval. A slow or unreachable device only delays its own job:
+//!
+//! - a job never overlaps itself; ticks missed while a poll is running are
+//!   skipped and counted, not queued
+//! - at most [`max_concurrent`](Scheduler::max_concurrent) devices are
+//!   polled at once
+//! - each download is bounded by [`poll_timeout`](Scheduler::poll_timeout)
+
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use tokio::sync::{Mutex, Semaphore, watch};
+use tokio::task::JoinSet;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{debug, warn};
+
+use crate::cursor::{Cursor, CursorStore};
+use crate::engine::{SyncReport, deliver};
+use crate::error::{Error, Result};
+use crate::sink::Sink;
+use crate::source::AttendanceSource;
+
+/// Default number of devices polled at once
+const DEFAULT_MAX_CONCURRENT: usize = 8;
+
+/// Default bound on one attendance download
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Counters of one polling job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobStatus {
+    /// Device ID of the job
+    pub device_id: String,
+
+    /// Polling interval
+    pub interval: Duration,
+
+    /// Completed polls, successful or not
+    pub polls: u64,
+
+    /// Failed polls
+    pub failures: u64,
+
+    /// Failed polls since the last success
+    pub consecutive_failures: u32,
+
+    /// Ticks skipped because the previous poll was still running
+    pub skipped_ticks: u64,
+
+    /// Records delivered to the sink
+    pub delivered: u64,
+
+    /// Error of the most recent poll, cleared by a success
+    pub last_error: Option<String>,
+}
+
+struct Job {
+    device_id: String,
+    interval: Duration,
+    source: Box<dyn AttendanceSource>,
+}
+
+/// Shared by all jobs of a running scheduler
+struct Shared<S, C> {
+    sink: Mutex<S>,
+    store: Mutex<C>,
+    permits: Semaphore,
+    poll_timeout: Duration,
+    status: StdMutex<Vec<JobStatus>>,
+}
+
+/// Interval-based polling of many devices
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use zkrust::Device;
+/// use zkrust_sync::{MemoryCursorStore, MemorySink, Scheduler};
+///
+/// # async fn example() -> zkrust_sync::Result<()> {
+/// let scheduler = Scheduler::new(MemorySink::new(), MemoryCursorStore::new())
+///     .max_concurrent(4)
+///     .poll_timeout(Duration::from_secs(30))
+///     .job("front-door", Device::new("192.168.1.201", 4370).into_handle(), Duration::from_secs(60))
+///     .job("warehouse", Device::new("192.168.1.202", 4370).into_handle(), Duration::from_secs(300));
+///
+/// let handle = scheduler.start();
+/// tokio::time::sleep(Duration::from_secs(3600)).await;
+/// handle.shutdown().await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Scheduler<S, C> {
+    sink: S,
+    store: C,
+    jobs: Vec<Job>,
+    max_concurrent: usize,
+    poll_timeout: Duration,
+}
+
+impl<S: Sink + 'static, C: CursorStore + 'static> Scheduler<S, C> {
+    /// Create a scheduler writing to `sink` with cursors kept in `store`
+    pub fn new(sink: S, store: C) -> Self {
+        Self {
+            sink,
+            store,
+            jobs: Vec::new(),
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            poll_timeout: DEFAULT_POLL_TIMEOUT,
+        }
+    }
+
+    /// Limit how many devices are polled at once (default 8, minimum 1)
+    pub fn max_concurrent(mut self, limit: usize) -> Self {
+        self.max_concurrent = limit.max(1);
+        self
+    }
+
+    /// Give up on a download after `timeout` (default 120 s)
+    pub fn poll_timeout(mut self, timeout: Duration) -> Self {
+        self.poll_timeout = timeout;
+        self
+    }
+
+    /// Poll `source` every `interval` under `device_id`
+    ///
+    /// The first poll runs as soon as the scheduler starts.
+    pub fn job(
+        mut self,
+        device_id: impl Into<String>,
+        source: impl AttendanceSource + 'static,
+        interval: Duration,
+    ) -> Self {
+        self.jobs.push(Job {
+            device_id: device_id.into(),
+            interval,
+            source: Box::new(source),
+        });
+        self
+    }
+
+    /// Spawn one task per job on the current Tokio runtime
+    pub fn start(self) -> SchedulerHandle {
+        let status = self
+            .jobs
+            .iter()
+            .map(|job| JobStatus {
+                device_id: job.device_id.clone(),
+                interval: job.interval,
+                polls: 0,
+                failures: 0,
+                consecutive_failures: 0,
+                skipped_ticks: 0,
+                delivered: 0,
+                last_error: None,
+            })
+            .collect();
+
+        let shared = Arc::new(Shared {
+            sink: Mutex::new(self.sink),
+            store: Mutex::new(self.store),
+            permits: Semaphore::new(self.max_concurrent),
+            poll_timeout: self.poll_timeout,
+            status: StdMutex::new(status),
+        });
+
+        let (shutdown, signal) = watch::channel(false);
+        let mut tasks = JoinSet::new();
+        for (index, job) in self.jobs.into_iter().enumerate() {
+            tasks.spawn(run_job(index, job, Arc::clone(&shared), signal.clone()));
+        }
+
+        let status: Arc<dyn StatusSource> = shared;
+        SchedulerHandle {
+            shutdown,
+            tasks,
+            status,
+        }
+    }
+}
+
+/// Type-erased access to job status for [`SchedulerHandle`]
+trait StatusSource: Send + Sync {
+    fn snapshot(&self) -> Vec<JobStatus>;
+}
+
+impl<S: Send, C: Send> StatusSource for Shared<S, C> {
+    fn snapshot(&self) -> Vec<JobStatus> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Running scheduler
+pub struct SchedulerHandle {
+    shutdown: watch::Sender<bool>,
+    tasks: JoinSet<()>,
+    status: Arc<dyn StatusSource>,
+}
+
+impl SchedulerHandle {
+    /// Current counters of every job, in registration order
+    pub fn status(&self) -> Vec<JobStatus> {
+        self.status.snapshot()
+    }
+
+    /// Stop all jobs
+    ///
+    /// Polls in progress are finished first, so no delivered batch is
+    /// left without its cursor update.
+    pub async fn shutdown(mut self) {
+        let _ = self.shutdown.send(true);
+        while self.tasks.join_next().await.is_some() {}
+    }
+}
+
+async fn run_job<S: Sink, C: CursorStore>(
+    index: usize,
+    mut job: Job,
+    shared: Arc<Shared<S, C>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut ticker = tokio::time::interval(job.interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut cursor = None;
+
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            _ = ticker.tick() => {}
+        }
+
+        let permit = tokio::select! {
+            _ = shutdown.changed() => break,
+            permit = shared.permits.acquire() => permit,
+        };
+        let Ok(_permit) = permit else { break };
+
+        let started = Instant::now();
+        let mut report = SyncReport::new(&job.device_id);
+        if let Err(e) = poll(&mut job, &shared, &mut cursor, &mut report).await {
+            warn!("Poll of {} failed: {}", job.device_id, e);
+            report.error = Some(e);
+        }
+
+        let elapsed = started.elapsed();
+        let skipped = (elapsed.as_nanos() / job.interval.as_nanos().max(1)) as u64;
+
+        let mut status = shared.status.lock().unwrap_or_else(|e| e.into_inner());
+        let status = &mut status[index];
+        status.polls += 1;
+        status.skipped_ticks += skipped;
+        status.delivered += report.delivered as u64;
+        match report.error {
+            None => {
+                status.consecutive_failures = 0;
+                status.last_error = None;
+            }
+            Some(e) => {
+                status.failures += 1;
+                status.consecutive_failures += 1;
+                status.last_error = Some(e.to_string());
+            }
+        }
+        debug!("Polled {} in {:?}", job.device_id, elapsed);
+    }
+}
+
+async fn poll<S: Sink, C: CursorStore>(
+    job: &mut Job,
+    shared: &Shared<S, C>,
+    cursor: &mut Option<Cursor>,
+    report: &mut SyncReport,
+) -> Result<()> {
+    if cursor.is_none() {
+        let loaded = shared.store.lock().await.load(&job.device_id).await?;
+        *cursor = Some(loaded.unwrap_or_default());
+    }
+
+    // Only the device side is bounded; a sink write is never cut short
+    let records = tokio::time::timeout(shared.poll_timeout, job.source.fetch_attendance())
+        .await
+        .map_err(|_| Error::Timeout(shared.poll_timeout))??;
+
+    let mut sink = shared.sink.lock().await;
+    let mut store = shared.store.lock().await;
+    let cursor = cursor.as_mut().expect("cursor loaded above");
+    deliver(&mut *sink, &mut *store, &job.device_id, cursor, records, report).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::MemoryCursorStore;
+    use crate::sink::MemorySink;
+    use chrono::NaiveDate;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use zkrust::AttendanceRecord;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+
+    /// Source that takes `delay` per download and counts concurrent calls
+    struct SlowSource {
+        delay: Duration,
+        user_id: &'static str,
+        active: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl AttendanceSource for SlowSource {
+        async fn fetch_attendance(&mut self) -> zkrust::Result<Vec<AttendanceRecord>> {
+            let now = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+
+            let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+            Ok(vec![AttendanceRecord::new(
+                self.user_id,
+                timestamp,
+                VerifyMode::Fingerprint,
+                PunchType::CheckIn,
+            )])
+        }
+    }
+
+    fn source(delay_secs: u64, user_id: &'static str, active: &Arc<AtomicUsize>, peak: &Arc<AtomicUsize>) -> SlowSource {
+        SlowSource {
+            delay: Duration::from_secs(delay_secs),
+            user_id,
+            active: Arc::clone(active),
+            peak: Arc::clone(peak),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hung_device_does_not_stall_others() {
+        let (active, peak) = Default::default();
+
+        let handle = Scheduler::new(MemorySink::new(), MemoryCursorStore::new())
+            .poll_timeout(Duration::from_secs(5))
+            .job("hung", source(3600, "1", &active, &peak), Duration::from_secs(10))
+            .job("healthy", source(1, "2", &active, &peak), Duration::from_secs(10))
+            .start();
+
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        let status = handle.status();
+        handle.shutdown().await;
+
+        assert_eq!(status[0].delivered, 0);
+        assert!(status[0].failures >= 2, "{:?}", status[0]);
+        assert!(status[0].last_error.as_deref().unwrap().contains("timed out"));
+
+        assert_eq!(status[1].polls, 3);
+        assert_eq!(status[1].delivered, 1);
+        assert_eq!(status[1].failures, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrency_limit_and_overlap() {
+        let (active, peak) = Default::default();
+
+        let handle = Scheduler::new(MemorySink::new(), MemoryCursorStore::new())
+            .max_concurrent(1)
+            .job("a", source(3, "1", &active, &peak), Duration::from_secs(1))
+            .job("b", source(3, "2", &active, &peak), Duration::from_secs(1))
+            .start();
+
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        let status = handle.status();
+        handle.shutdown().await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+        assert!(status.iter().all(|s| s.polls >= 2 && s.skipped_ticks > 0), "{:?}", status);
+    }
+}
@@ -0,0 +1,129 @@
+//! Record sinks
+
+use std::collections::HashMap;
+
+use zkrust::{AttendanceRecord, User};
+use zkrust_types::{AttendancePhoto, FaceTemplate, FingerprintTemplate};
+
+use crate::error::Result;
+
+/// Destination for synchronized records
+///
+/// A write that returns `Ok` is considered durable: the engine advances the
+/// device cursor right after it. Records may be delivered again if the
+/// process stops between the write and the cursor save, so sinks should
+/// upsert rather than blindly insert.
+#[async_trait::async_trait]
+pub trait Sink: Send {
+    /// Store new attendance records of `device_id`, in chronological order
+    async fn write_attendance(&mut self, device_id: &str, records: &[AttendanceRecord]) -> Result<()>;
+
+    /// Store the user list of `device_id`
+    ///
+    /// Sinks without a user table can keep the default, which drops them.
+    async fn write_users(&mut self, device_id: &str, users: &[User]) -> Result<()> {
+        let _ = (device_id, users);
+        Ok(())
+    }
+
+    /// Store added or changed users of `device_id`, keeping the others
+    ///
+    /// Used when a device reports single enrollments rather than its full
+    /// list. Sinks without a user table can keep the default, which drops
+    /// them.
+    async fn update_users(&mut self, device_id: &str, users: &[User]) -> Result<()> {
+        let _ = (device_id, users);
+        Ok(())
+    }
+
+    /// Store fingerprint templates of `device_id`
+    async fn write_fingerprints(&mut self, device_id: &str, templates: &[FingerprintTemplate]) -> Result<()> {
+        let _ = (device_id, templates);
+        Ok(())
+    }
+
+    /// Store face templates of `device_id`
+    async fn write_faces(&mut self, device_id: &str, templates: &[FaceTemplate]) -> Result<()> {
+        let _ = (device_id, templates);
+        Ok(())
+    }
+
+    /// Store attendance photos of `device_id`
+    ///
+    /// Sinks that don't keep images can keep the default, which drops them.
+    async fn write_photos(&mut self, device_id: &str, photos: &[AttendancePhoto]) -> Result<()> {
+        let _ = (device_id, photos);
+        Ok(())
+    }
+}
+
+/// Sink that keeps everything in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    attendance: HashMap<String, Vec<AttendanceRecord>>,
+    users: HashMap<String, Vec<User>>,
+}
+
+impl MemorySink {
+    /// Create an empty sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attendance records received for `device_id`
+    pub fn attendance(&self, device_id: &str) -> &[AttendanceRecord] {
+        self.attendance.get(device_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Latest user list received for `device_id`
+    pub fn users(&self, device_id: &str) -> &[User] {
+        self.users.get(device_id).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for MemorySink {
+    async fn write_attendance(&mut self, device_id: &str, records: &[AttendanceRecord]) -> Result<()> {
+        self.attendance
+            .entry(device_id.to_string())
+            .or_default()
+            .extend_from_slice(records);
+        Ok(())
+    }
+
+    async fn write_users(&mut self, device_id: &str, users: &[User]) -> Result<()> {
+        self.users.insert(device_id.to_string(), users.to_vec());
+        Ok(())
+    }
+
+    async fn update_users(&mut self, device_id: &str, users: &[User]) -> Result<()> {
+        let stored = self.users.entry(device_id.to_string()).or_default();
+        for user in users {
+            match stored.iter_mut().find(|stored| stored.uid == user.uid) {
+                Some(stored) => *stored = user.clone(),
+                None => stored.push(user.clone()),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(uid: u16, name: &str) -> User {
+        User::builder(uid, (100 * uid).to_string()).name(name).build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_memory_sink_users() {
+        let mut sink = MemorySink::new();
+        sink.write_users("door", &[user(1, "Alice"), user(2, "Bob")]).await.unwrap();
+        sink.update_users("door", &[user(2, "Robert"), user(3, "Carol")]).await.unwrap();
+        assert_eq!(sink.users("door"), [user(1, "Alice"), user(2, "Robert"), user(3, "Carol")]);
+
+        sink.write_users("door", &[user(3, "Carol")]).await.unwrap();
+        assert_eq!(sink.users("door"), [user(3, "Carol")]);
+    }
+}
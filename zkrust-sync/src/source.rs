@@ -0,0 +1,33 @@
+//! Attendance sources
+
+use zkrust::{AttendanceRecord, Device, DeviceHandle};
+
+/// Anything the engine can pull an attendance log from
+///
+/// Implementations connect on demand, so a device that dropped between
+/// polls is picked up again on the next one.
+#[async_trait::async_trait]
+pub trait AttendanceSource: Send + Sync {
+    /// Download the full attendance log
+    async fn fetch_attendance(&mut self) -> zkrust::Result<Vec<AttendanceRecord>>;
+}
+
+#[async_trait::async_trait]
+impl AttendanceSource for DeviceHandle {
+    async fn fetch_attendance(&mut self) -> zkrust::Result<Vec<AttendanceRecord>> {
+        if !self.is_connected().await? {
+            self.connect().await?;
+        }
+        self.get_attendance().await
+    }
+}
+
+#[async_trait::async_trait]
+impl AttendanceSource for Device {
+    async fn fetch_attendance(&mut self) -> zkrust::Result<Vec<AttendanceRecord>> {
+        if !self.is_connected() {
+            self.connect().await?;
+        }
+        self.get_attendance().await
+    }
+}
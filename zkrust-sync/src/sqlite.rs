@@ -0,0 +1,475 @@
+//! SQLite storage backend
+//!
+//! [`SqliteStore`] keeps devices, users, templates and attendance in one
+//! database file and doubles as the engine's cursor store, so a single
+//! file holds everything needed to resume after a restart.
+//!
+//! Writes are upserts: re-delivered attendance is ignored and users and
+//! templates are updated in place. Calls run on the async task; they are
+//! short single transactions, which is fine for the small deployments this
+//! backend targets.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::NaiveDateTime;
+use rusqlite::{Connection, OptionalExtension, params};
+
+use zkrust_core::constants::{Privilege, PunchType, VerifyMode};
+use zkrust_types::time::TIMESTAMP_FORMAT;
+use zkrust_types::{AttendanceRecord, FaceTemplate, FingerprintTemplate, User};
+
+use crate::cursor::{Cursor, CursorStore};
+use crate::error::{Error, Result};
+use crate::sink::Sink;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS devices (
+    device_id   TEXT PRIMARY KEY,
+    cursor      TEXT,
+    last_write  TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    device_id   TEXT NOT NULL,
+    uid         INTEGER NOT NULL,
+    user_id     TEXT NOT NULL,
+    name        TEXT NOT NULL,
+    privilege   INTEGER NOT NULL,
+    enabled     INTEGER NOT NULL,
+    password    TEXT NOT NULL,
+    card        INTEGER NOT NULL,
+    group_id    TEXT NOT NULL,
+    PRIMARY KEY (device_id, uid)
+);
+
+CREATE TABLE IF NOT EXISTS templates (
+    device_id         TEXT NOT NULL,
+    uid               INTEGER NOT NULL,
+    kind              TEXT NOT NULL,
+    slot              INTEGER NOT NULL,
+    valid             INTEGER NOT NULL,
+    duress            INTEGER NOT NULL,
+    algorithm_version INTEGER NOT NULL,
+    data              BLOB NOT NULL,
+    PRIMARY KEY (device_id, uid, kind, slot)
+);
+
+CREATE TABLE IF NOT EXISTS attendance (
+    device_id   TEXT NOT NULL,
+    user_id     TEXT NOT NULL,
+    timestamp   TEXT NOT NULL,
+    verify_mode INTEGER NOT NULL,
+    punch       INTEGER NOT NULL,
+    work_code   INTEGER NOT NULL,
+    UNIQUE (device_id, user_id, timestamp, verify_mode, punch, work_code)
+);
+
+CREATE INDEX IF NOT EXISTS attendance_by_time ON attendance (device_id, timestamp);
+";
+
+const UPSERT_TEMPLATE: &str = "
+INSERT INTO templates (device_id, uid, kind, slot, valid, duress, algorithm_version, data)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+ON CONFLICT (device_id, uid, kind, slot) DO UPDATE SET
+    valid = excluded.valid, duress = excluded.duress,
+    algorithm_version = excluded.algorithm_version, data = excluded.data
+";
+
+/// SQLite-backed sink and cursor store
+///
+/// Clones share one connection, so the same store can be handed to
+/// [`SyncEngine::new`](crate::SyncEngine::new) as both sink and cursor
+/// store.
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    /// Open (or create) the database at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Open a private in-memory database
+    pub fn in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    /// Use an existing connection, creating the schema if needed
+    pub fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Stored attendance of `device_id`, in chronological order
+    ///
+    /// A missing work code is stored as 0 and read back as `None`.
+    pub fn attendance(&self, device_id: &str) -> Result<Vec<AttendanceRecord>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT user_id, timestamp, verify_mode, punch, work_code FROM attendance
+             WHERE device_id = ?1 ORDER BY timestamp, user_id",
+        )?;
+
+        let rows = stmt.query_map([device_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u8>(2)?,
+                row.get::<_, u8>(3)?,
+                row.get::<_, u32>(4)?,
+            ))
+        })?;
+
+        rows.map(|row| {
+            let (user_id, timestamp, verify_mode, punch, work_code) = row?;
+            let record = AttendanceRecord::new(
+                user_id,
+                parse_timestamp(&timestamp)?,
+                VerifyMode::try_from(verify_mode).map_err(corrupt)?,
+                PunchType::try_from(punch).map_err(corrupt)?,
+            );
+            Ok(match work_code {
+                0 => record,
+                code => record.with_work_code(code),
+            })
+        })
+        .collect()
+    }
+
+    /// Stored users of `device_id`, ordered by record index
+    pub fn users(&self, device_id: &str) -> Result<Vec<User>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT uid, user_id, name, privilege, enabled, password, card, group_id FROM users
+             WHERE device_id = ?1 ORDER BY uid",
+        )?;
+
+        let rows = stmt.query_map([device_id], |row| {
+            Ok((
+                User {
+                    uid: row.get(0)?,
+                    user_id: row.get(1)?,
+                    name: row.get(2)?,
+                    privilege: Privilege::User,
+                    enabled: row.get(4)?,
+                    password: row.get(5)?,
+                    card: row.get(6)?,
+                    group_id: row.get(7)?,
+                },
+                row.get::<_, u8>(3)?,
+            ))
+        })?;
+
+        rows.map(|row| {
+            let (mut user, privilege) = row?;
+            user.privilege = Privilege::try_from(privilege).map_err(corrupt)?;
+            Ok(user)
+        })
+        .collect()
+    }
+
+    /// Stored fingerprint templates of `device_id`
+    pub fn fingerprints(&self, device_id: &str) -> Result<Vec<FingerprintTemplate>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT uid, slot, valid, duress, algorithm_version, data FROM templates
+             WHERE device_id = ?1 AND kind = 'finger' ORDER BY uid, slot",
+        )?;
+
+        let rows = stmt.query_map([device_id], |row| {
+            Ok(FingerprintTemplate {
+                uid: row.get(0)?,
+                finger: row.get(1)?,
+                valid: row.get(2)?,
+                duress: row.get(3)?,
+                algorithm_version: row.get(4)?,
+                data: row.get(5)?,
+            })
+        })?;
+
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        // A panic mid-statement leaves no partial transaction behind
+        // (SQLite rolls it back), so a poisoned lock is still usable
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn touch_device(tx: &rusqlite::Transaction<'_>, device_id: &str) -> Result<()> {
+    tx.execute(
+        "INSERT INTO devices (device_id, last_write) VALUES (?1, datetime('now'))
+         ON CONFLICT (device_id) DO UPDATE SET last_write = excluded.last_write",
+        [device_id],
+    )?;
+    Ok(())
+}
+
+fn upsert_users(tx: &rusqlite::Transaction<'_>, device_id: &str, users: &[User]) -> Result<()> {
+    let mut upsert = tx.prepare(
+        "INSERT INTO users (device_id, uid, user_id, name, privilege, enabled, password, card, group_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT (device_id, uid) DO UPDATE SET
+             user_id = excluded.user_id, name = excluded.name,
+             privilege = excluded.privilege, enabled = excluded.enabled,
+             password = excluded.password, card = excluded.card,
+             group_id = excluded.group_id",
+    )?;
+    for user in users {
+        upsert.execute(params![
+            device_id,
+            user.uid,
+            user.user_id,
+            user.name,
+            u8::from(user.privilege),
+            user.enabled,
+            user.password,
+            user.card,
+            user.group_id,
+        ])?;
+    }
+    Ok(())
+}
+
+fn format_timestamp(timestamp: &NaiveDateTime) -> String {
+    timestamp.format(TIMESTAMP_FORMAT).to_string()
+}
+
+fn parse_timestamp(value: &str) -> Result<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT)
+        .map_err(|e| Error::Sink(format!("Invalid stored timestamp {:?}: {}", value, e)))
+}
+
+fn corrupt(e: zkrust_core::Error) -> Error {
+    Error::Sink(format!("Invalid stored value: {}", e))
+}
+
+#[async_trait::async_trait]
+impl Sink for SqliteStore {
+    async fn write_attendance(&mut self, device_id: &str, records: &[AttendanceRecord]) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        touch_device(&tx, device_id)?;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO attendance
+                 (device_id, user_id, timestamp, verify_mode, punch, work_code)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for record in records {
+                stmt.execute(params![
+                    device_id,
+                    record.user_id,
+                    format_timestamp(&record.timestamp),
+                    u8::from(record.verify_mode),
+                    u8::from(record.punch),
+                    record.work_code.unwrap_or(0),
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Upsert `users` and delete stored users no longer on the device
+    async fn write_users(&mut self, device_id: &str, users: &[User]) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        touch_device(&tx, device_id)?;
+        upsert_users(&tx, device_id, users)?;
+
+        {
+            let current: BTreeSet<u16> = users.iter().map(|u| u.uid).collect();
+            let stored: Vec<u16> = tx
+                .prepare("SELECT uid FROM users WHERE device_id = ?1")?
+                .query_map([device_id], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+
+            let mut delete = tx.prepare("DELETE FROM users WHERE device_id = ?1 AND uid = ?2")?;
+            for uid in stored.into_iter().filter(|uid| !current.contains(uid)) {
+                delete.execute(params![device_id, uid])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn update_users(&mut self, device_id: &str, users: &[User]) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        touch_device(&tx, device_id)?;
+        upsert_users(&tx, device_id, users)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn write_fingerprints(&mut self, device_id: &str, templates: &[FingerprintTemplate]) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        touch_device(&tx, device_id)?;
+
+        {
+            let mut stmt = tx.prepare(UPSERT_TEMPLATE)?;
+            for template in templates {
+                stmt.execute(params![
+                    device_id,
+                    template.uid,
+                    "finger",
+                    template.finger,
+                    template.valid,
+                    template.duress,
+                    template.algorithm_version,
+                    template.data,
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn write_faces(&mut self, device_id: &str, templates: &[FaceTemplate]) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        touch_device(&tx, device_id)?;
+
+        {
+            let mut stmt = tx.prepare(UPSERT_TEMPLATE)?;
+            for template in templates {
+                stmt.execute(params![
+                    device_id,
+                    template.uid,
+                    "face",
+                    0,
+                    template.valid,
+                    false,
+                    template.algorithm_version,
+                    template.data,
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl CursorStore for SqliteStore {
+    async fn load(&mut self, device_id: &str) -> Result<Option<Cursor>> {
+        let cursor: Option<Option<String>> = self
+            .lock()
+            .query_row("SELECT cursor FROM devices WHERE device_id = ?1", [device_id], |row| row.get(0))
+            .optional()?;
+
+        match cursor.flatten() {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(&mut self, device_id: &str, cursor: &Cursor) -> Result<()> {
+        let json = serde_json::to_string(cursor)?;
+        self.lock().execute(
+            "INSERT INTO devices (device_id, cursor, last_write) VALUES (?1, ?2, datetime('now'))
+             ON CONFLICT (device_id) DO UPDATE SET cursor = excluded.cursor, last_write = excluded.last_write",
+            params![device_id, json],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn record(user_id: &str, minute: u32) -> AttendanceRecord {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(8, minute, 0)
+            .unwrap();
+        AttendanceRecord::new(user_id, timestamp, VerifyMode::Fingerprint, PunchType::CheckIn)
+    }
+
+    #[tokio::test]
+    async fn test_attendance_upsert_ignores_duplicates() {
+        let mut store = SqliteStore::in_memory().unwrap();
+
+        let records = [record("1", 0), record("2", 1).with_work_code(7)];
+        store.write_attendance("door", &records).await.unwrap();
+        store.write_attendance("door", &records).await.unwrap();
+
+        assert_eq!(store.attendance("door").unwrap(), records);
+        assert!(store.attendance("gate").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_users_replace_device_list() {
+        let mut store = SqliteStore::in_memory().unwrap();
+
+        let alice = User::builder(1, "100").name("Alice").build().unwrap();
+        let bob = User::builder(2, "200").name("Bob").privilege(Privilege::Admin).build().unwrap();
+        store.write_users("door", &[alice, bob]).await.unwrap();
+
+        let renamed = User::builder(1, "100").name("Alicia").build().unwrap();
+        store.write_users("door", std::slice::from_ref(&renamed)).await.unwrap();
+
+        assert_eq!(store.users("door").unwrap(), vec![renamed]);
+    }
+
+    #[tokio::test]
+    async fn test_update_users_keeps_others() {
+        let mut store = SqliteStore::in_memory().unwrap();
+
+        let alice = User::builder(1, "100").name("Alice").build().unwrap();
+        let bob = User::builder(2, "200").name("Bob").build().unwrap();
+        store.write_users("door", &[alice, bob.clone()]).await.unwrap();
+
+        let renamed = User::builder(1, "100").name("Alicia").build().unwrap();
+        let carol = User::builder(3, "300").name("Carol").build().unwrap();
+        store.update_users("door", &[renamed.clone(), carol.clone()]).await.unwrap();
+
+        assert_eq!(store.users("door").unwrap(), vec![renamed, bob, carol]);
+    }
+
+    #[tokio::test]
+    async fn test_templates_upsert() {
+        let mut store = SqliteStore::in_memory().unwrap();
+
+        let template = FingerprintTemplate::new(1, 3, 10, vec![1, 2, 3]).unwrap();
+        store.write_fingerprints("door", std::slice::from_ref(&template)).await.unwrap();
+
+        let mut updated = template;
+        updated.data = vec![4, 5];
+        store.write_fingerprints("door", std::slice::from_ref(&updated)).await.unwrap();
+        store.write_faces("door", &[FaceTemplate::new(1, 7, vec![9])]).await.unwrap();
+
+        assert_eq!(store.fingerprints("door").unwrap(), vec![updated]);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_store() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        assert_eq!(store.load("door").await.unwrap(), None);
+
+        // Writing data registers the device without a cursor
+        store.write_attendance("door", &[record("1", 0)]).await.unwrap();
+        assert_eq!(store.load("door").await.unwrap(), None);
+
+        let mut cursor = Cursor::default();
+        cursor.advance(&[record("1", 0)]);
+        store.save("door", &cursor).await.unwrap();
+        assert_eq!(store.load("door").await.unwrap(), Some(cursor));
+    }
+}
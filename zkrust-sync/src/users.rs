@@ -0,0 +1,512 @@
+//! Pushing users from a source of truth to devices
+//!
+//! [`UserSync`] compares the users of an external system (HR database,
+//! directory, ...) with a device. Users missing on the device are created.
+//! When a user exists on both sides with different data, the configured
+//! [`ConflictPolicy`] decides what happens instead of silently
+//! overwriting the device copy.
+//!
+//! Devices don't record when a user was edited, so for
+//! [`ConflictPolicy::NewestWins`] the time a device copy was first seen in
+//! its current form stands in for its modification time. Persist
+//! [`UserSync::state`] between runs to keep those times across restarts.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use zkrust::{Device, DeviceHandle, User};
+
+use crate::error::{Error, Result};
+
+/// What to do when source and device disagree about a user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Overwrite the device with the source
+    #[default]
+    SourceWins,
+
+    /// Keep the device copy and report it for import into the source
+    DeviceWins,
+
+    /// Keep whichever side changed last
+    NewestWins,
+
+    /// Leave both sides alone and queue the conflict for a person
+    Manual,
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConflictPolicy::SourceWins => "source-wins",
+            ConflictPolicy::DeviceWins => "device-wins",
+            ConflictPolicy::NewestWins => "newest-wins",
+            ConflictPolicy::Manual => "manual",
+        })
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "source-wins" | "source" => Ok(ConflictPolicy::SourceWins),
+            "device-wins" | "device" => Ok(ConflictPolicy::DeviceWins),
+            "newest-wins" | "newest" => Ok(ConflictPolicy::NewestWins),
+            "manual" => Ok(ConflictPolicy::Manual),
+            _ => Err(Error::Config(format!("Unknown conflict policy {:?}", s))),
+        }
+    }
+}
+
+/// A user from the source of truth
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceUser {
+    /// User data; `uid` is ignored, devices assign their own
+    pub user: User,
+
+    /// Last modification in the source (UTC)
+    pub updated_at: NaiveDateTime,
+}
+
+/// A user that differs between source and device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserConflict {
+    /// Device holding the conflicting copy
+    pub device_id: String,
+
+    /// Source copy
+    pub source: SourceUser,
+
+    /// Device copy
+    pub device: User,
+
+    /// When the device copy was first seen in its current form (UTC)
+    pub device_seen_at: NaiveDateTime,
+}
+
+/// Decision for a queued conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Resolution {
+    /// Write the source copy on the next sync
+    UseSource,
+
+    /// Keep the device copy
+    UseDevice,
+}
+
+/// Outcome of syncing users to one device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserSyncReport {
+    /// Users created on the device
+    pub created: usize,
+
+    /// Device users overwritten with the source copy
+    pub updated: usize,
+
+    /// Users identical on both sides
+    pub unchanged: usize,
+
+    /// Device copies that won a conflict, for import into the source
+    pub imported: Vec<User>,
+
+    /// Conflicts added to the manual queue
+    pub queued: usize,
+
+    /// Device users unknown to the source (left in place)
+    pub device_only: usize,
+}
+
+/// Device copy of a user as last seen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Seen {
+    user: User,
+    at: NaiveDateTime,
+}
+
+/// Persistent part of [`UserSync`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserSyncState {
+    seen: HashMap<String, HashMap<String, Seen>>,
+    queue: Vec<UserConflict>,
+    resolutions: HashMap<String, HashMap<String, Resolution>>,
+}
+
+/// Device side of a user sync
+#[async_trait::async_trait]
+pub trait UserDevice: Send {
+    /// Read all users
+    async fn fetch_users(&mut self) -> zkrust::Result<Vec<User>>;
+
+    /// Create or update one user
+    async fn write_user(&mut self, user: &User) -> zkrust::Result<()>;
+}
+
+#[async_trait::async_trait]
+impl UserDevice for Device {
+    async fn fetch_users(&mut self) -> zkrust::Result<Vec<User>> {
+        self.get_users().await
+    }
+
+    async fn write_user(&mut self, user: &User) -> zkrust::Result<()> {
+        self.set_user(user).await
+    }
+}
+
+#[async_trait::async_trait]
+impl UserDevice for DeviceHandle {
+    async fn fetch_users(&mut self) -> zkrust::Result<Vec<User>> {
+        self.get_users().await
+    }
+
+    async fn write_user(&mut self, user: &User) -> zkrust::Result<()> {
+        self.set_user(user.clone()).await
+    }
+}
+
+/// Source-to-device user synchronization with conflict handling
+#[derive(Debug, Clone, Default)]
+pub struct UserSync {
+    policy: ConflictPolicy,
+    state: UserSyncState,
+}
+
+impl UserSync {
+    /// Create a sync applying `policy` to conflicts
+    pub fn new(policy: ConflictPolicy) -> Self {
+        Self {
+            policy,
+            state: UserSyncState::default(),
+        }
+    }
+
+    /// Restore state saved from [`UserSync::state`]
+    pub fn with_state(mut self, state: UserSyncState) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// The conflict policy
+    pub fn policy(&self) -> ConflictPolicy {
+        self.policy
+    }
+
+    /// State to persist between runs
+    pub fn state(&self) -> &UserSyncState {
+        &self.state
+    }
+
+    /// Conflicts waiting for a decision
+    pub fn pending_conflicts(&self) -> &[UserConflict] {
+        &self.state.queue
+    }
+
+    /// Decide a queued conflict; applied on the next sync of that device
+    ///
+    /// Returns the conflict, or `None` if nothing was queued for the user.
+    pub fn resolve(&mut self, device_id: &str, user_id: &str, resolution: Resolution) -> Option<UserConflict> {
+        let index = self
+            .state
+            .queue
+            .iter()
+            .position(|c| c.device_id == device_id && c.source.user.user_id == user_id)?;
+
+        self.state
+            .resolutions
+            .entry(device_id.to_string())
+            .or_default()
+            .insert(user_id.to_string(), resolution);
+        Some(self.state.queue.remove(index))
+    }
+
+    /// Bring `device` in line with `source`
+    pub async fn sync_device(
+        &mut self,
+        device_id: &str,
+        device: &mut impl UserDevice,
+        source: &[SourceUser],
+    ) -> Result<UserSyncReport> {
+        let device_users = device.fetch_users().await?;
+        let now = Utc::now().naive_utc();
+
+        let (writes, mut report) = self.plan(device_id, source, &device_users, now);
+
+        for (user, created) in &writes {
+            device.write_user(user).await?;
+            if *created {
+                report.created += 1;
+            } else {
+                report.updated += 1;
+            }
+            // Our own write is not a device-side change
+            self.remember(device_id, user, now);
+        }
+
+        info!(
+            "User sync of {}: {} created, {} updated, {} imported, {} queued",
+            device_id,
+            report.created,
+            report.updated,
+            report.imported.len(),
+            report.queued
+        );
+        Ok(report)
+    }
+
+    /// Decide the writes for one device; `true` marks a new user
+    fn plan(
+        &mut self,
+        device_id: &str,
+        source: &[SourceUser],
+        device_users: &[User],
+        now: NaiveDateTime,
+    ) -> (Vec<(User, bool)>, UserSyncReport) {
+        let mut report = UserSyncReport::default();
+        let mut writes = Vec::new();
+
+        let on_device: HashMap<&str, &User> = device_users.iter().map(|u| (u.user_id.as_str(), u)).collect();
+        let mut next_uid = device_users.iter().map(|u| u.uid).max().unwrap_or(0);
+
+        for device_user in device_users {
+            self.observe(device_id, device_user, now);
+        }
+        report.device_only = device_users
+            .iter()
+            .filter(|d| !source.iter().any(|s| s.user.user_id == d.user_id))
+            .count();
+
+        for entry in source {
+            let Some(&device_user) = on_device.get(entry.user.user_id.as_str()) else {
+                next_uid = next_uid.saturating_add(1);
+                writes.push((User { uid: next_uid, ..entry.user.clone() }, true));
+                continue;
+            };
+
+            let wanted = User { uid: device_user.uid, ..entry.user.clone() };
+            if wanted == *device_user {
+                report.unchanged += 1;
+                self.drop_conflict(device_id, &entry.user.user_id);
+                continue;
+            }
+
+            let seen_at = self.seen_at(device_id, device_user).unwrap_or(now);
+            let resolution = self
+                .state
+                .resolutions
+                .get_mut(device_id)
+                .and_then(|r| r.remove(&entry.user.user_id));
+
+            let use_source = match (resolution, self.policy) {
+                (Some(resolution), _) => resolution == Resolution::UseSource,
+                (None, ConflictPolicy::SourceWins) => true,
+                (None, ConflictPolicy::DeviceWins) => false,
+                (None, ConflictPolicy::NewestWins) => entry.updated_at >= seen_at,
+                (None, ConflictPolicy::Manual) => {
+                    self.queue(UserConflict {
+                        device_id: device_id.to_string(),
+                        source: entry.clone(),
+                        device: device_user.clone(),
+                        device_seen_at: seen_at,
+                    });
+                    report.queued += 1;
+                    continue;
+                }
+            };
+
+            debug!(
+                "Conflict on {} for user {}: keeping {} copy",
+                device_id,
+                entry.user.user_id,
+                if use_source { "source" } else { "device" }
+            );
+
+            if use_source {
+                writes.push((wanted, false));
+            } else {
+                report.imported.push(device_user.clone());
+            }
+        }
+
+        (writes, report)
+    }
+
+    fn observe(&mut self, device_id: &str, user: &User, now: NaiveDateTime) {
+        let seen = self.state.seen.entry(device_id.to_string()).or_default();
+        match seen.get(&user.user_id) {
+            Some(previous) if previous.user == *user => {}
+            _ => {
+                seen.insert(user.user_id.clone(), Seen { user: user.clone(), at: now });
+            }
+        }
+    }
+
+    fn remember(&mut self, device_id: &str, user: &User, now: NaiveDateTime) {
+        self.state
+            .seen
+            .entry(device_id.to_string())
+            .or_default()
+            .insert(user.user_id.clone(), Seen { user: user.clone(), at: now });
+    }
+
+    fn seen_at(&self, device_id: &str, user: &User) -> Option<NaiveDateTime> {
+        self.state
+            .seen
+            .get(device_id)?
+            .get(&user.user_id)
+            .filter(|seen| seen.user == *user)
+            .map(|seen| seen.at)
+    }
+
+    fn queue(&mut self, conflict: UserConflict) {
+        self.drop_conflict(&conflict.device_id, &conflict.source.user.user_id);
+        self.state.queue.push(conflict);
+    }
+
+    fn drop_conflict(&mut self, device_id: &str, user_id: &str) {
+        self.state
+            .queue
+            .retain(|c| !(c.device_id == device_id && c.source.user.user_id == user_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// In-memory device
+    #[derive(Default)]
+    struct FakeDevice {
+        users: Vec<User>,
+        writes: usize,
+    }
+
+    #[async_trait::async_trait]
+    impl UserDevice for FakeDevice {
+        async fn fetch_users(&mut self) -> zkrust::Result<Vec<User>> {
+            Ok(self.users.clone())
+        }
+
+        async fn write_user(&mut self, user: &User) -> zkrust::Result<()> {
+            self.writes += 1;
+            self.users.retain(|u| u.uid != user.uid);
+            self.users.push(user.clone());
+            Ok(())
+        }
+    }
+
+    fn at(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    fn user(uid: u16, user_id: &str, name: &str) -> User {
+        User::builder(uid, user_id).name(name).build().unwrap()
+    }
+
+    fn source(user_id: &str, name: &str, day: u32) -> SourceUser {
+        SourceUser {
+            user: user(1, user_id, name),
+            updated_at: at(day),
+        }
+    }
+
+    fn device() -> FakeDevice {
+        FakeDevice {
+            users: vec![user(3, "100", "Alice Device"), user(4, "300", "Carol")],
+            writes: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_source_wins_creates_and_overwrites() {
+        let mut sync = UserSync::new(ConflictPolicy::SourceWins);
+        let mut device = device();
+
+        let report = sync
+            .sync_device("door", &mut device, &[source("100", "Alice", 1), source("200", "Bob", 1)])
+            .await
+            .unwrap();
+
+        assert_eq!((report.created, report.updated, report.device_only), (1, 1, 1));
+        assert!(device.users.contains(&user(3, "100", "Alice")));
+        assert!(device.users.contains(&user(5, "200", "Bob")));
+
+        // Second run is a no-op
+        let report = sync
+            .sync_device("door", &mut device, &[source("100", "Alice", 1), source("200", "Bob", 1)])
+            .await
+            .unwrap();
+        assert_eq!(report.unchanged, 2);
+        assert_eq!(device.writes, 2);
+    }
+
+    #[tokio::test]
+    async fn test_device_wins_imports() {
+        let mut sync = UserSync::new(ConflictPolicy::DeviceWins);
+        let mut device = device();
+
+        let report = sync.sync_device("door", &mut device, &[source("100", "Alice", 1)]).await.unwrap();
+
+        assert_eq!(report.imported, vec![user(3, "100", "Alice Device")]);
+        assert_eq!(device.writes, 0);
+    }
+
+    #[test]
+    fn test_newest_wins_uses_first_seen_time() {
+        let mut sync = UserSync::new(ConflictPolicy::NewestWins);
+        let device_users = device().users;
+
+        // Device copy first seen on day 5; source edited on day 3
+        let (writes, report) = sync.plan("door", &[source("100", "Alice", 3)], &device_users, at(5));
+        assert!(writes.is_empty());
+        assert_eq!(report.imported.len(), 1);
+
+        // Source edited after the device copy appeared
+        let (writes, _) = sync.plan("door", &[source("100", "Alice", 6)], &device_users, at(7));
+        assert_eq!(writes, vec![(user(3, "100", "Alice"), false)]);
+    }
+
+    #[tokio::test]
+    async fn test_manual_queue_and_resolve() {
+        let mut sync = UserSync::new(ConflictPolicy::Manual);
+        let mut device = device();
+        let users = [source("100", "Alice", 1)];
+
+        let report = sync.sync_device("door", &mut device, &users).await.unwrap();
+        assert_eq!(report.queued, 1);
+        assert_eq!(device.writes, 0);
+
+        // Re-syncing doesn't duplicate the queue entry
+        sync.sync_device("door", &mut device, &users).await.unwrap();
+        assert_eq!(sync.pending_conflicts().len(), 1);
+        assert_eq!(sync.pending_conflicts()[0].device.name, "Alice Device");
+
+        let conflict = sync.resolve("door", "100", Resolution::UseSource).unwrap();
+        assert_eq!(conflict.source.user.name, "Alice");
+        assert!(sync.pending_conflicts().is_empty());
+
+        let report = sync.sync_device("door", &mut device, &users).await.unwrap();
+        assert_eq!(report.updated, 1);
+        assert!(device.users.contains(&user(3, "100", "Alice")));
+    }
+
+    #[test]
+    fn test_policy_names() {
+        for policy in [
+            ConflictPolicy::SourceWins,
+            ConflictPolicy::DeviceWins,
+            ConflictPolicy::NewestWins,
+            ConflictPolicy::Manual,
+        ] {
+            assert_eq!(policy.to_string().parse::<ConflictPolicy>().unwrap(), policy);
+        }
+        assert!("coin-flip".parse::<ConflictPolicy>().is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! `tokio_util` codec for the ZKTeco protocol
+//!
+//! [`ZkCodec`] turns a byte stream (or datagram) into [`Packet`]s and back,
+//! so it can be plugged into [`Framed`](tokio_util::codec::Framed) or
+//! [`UdpFramed`](tokio_util::udp::UdpFramed) to build custom pipelines.
+//!
+//! # Framing
+//!
+//! The bare protocol has no length field, so without the TCP wrapper every
+//! buffered chunk is treated as exactly one packet (datagram semantics).
+//! With the TCP wrapper enabled each packet is prefixed with:
+//!
+//! ```text
+//! ┌─────────────┬─────────────┬─────────────────┬─────────────┐
+//! │   Magic 1   │   Magic 2   │     Length      │  ZK packet  │
+//! │   0x5050    │   0x8272    │  4 bytes (LE)   │   N bytes   │
+//! └─────────────┴─────────────┴─────────────────┴─────────────┘
+//! ```
+
+use bytes::{BufMut, BytesMut};
+#[cfg(feature = "codec")]
+use bytes::Buf;
+#[cfg(feature = "codec")]
+use tokio_util::codec::{Decoder, Encoder};
+
+use zkrust_core::constants::{TCP_MAGIC_1, TCP_MAGIC_2};
+use zkrust_core::Packet;
+
+use crate::error::{Error, Result};
+#[cfg(feature = "codec")]
+use crate::log::trace;
+
+/// Size of the TCP wrapper header in bytes
+pub const TCP_HEADER_SIZE: usize = 8;
+
+/// Codec for ZKTeco protocol packets
+///
+/// # Examples
+///
+/// ```
+/// use bytes::BytesMut;
+/// use tokio_util::codec::{Decoder, Encoder};
+/// use zkrust_core::{Command, Packet};
+/// use zkrust_transport::ZkCodec;
+///
+/// let mut codec = ZkCodec::tcp();
+/// let mut buf = BytesMut::new();
+/// codec.encode(Packet::new(Command::Connect, 0, 0), &mut buf).unwrap();
+///
+/// let packet = codec.decode(&mut buf).unwrap().unwrap();
+/// assert_eq!(packet.command, Command::Connect);
+/// ```
+#[cfg(feature = "codec")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZkCodec {
+    tcp_wrapper: bool,
+}
+
+#[cfg(feature = "codec")]
+impl ZkCodec {
+    /// Create a codec for bare packets (UDP, or TCP without wrapper)
+    pub fn new() -> Self {
+        Self { tcp_wrapper: false }
+    }
+
+    /// Create a codec using the TCP wrapper framing
+    pub fn tcp() -> Self {
+        Self { tcp_wrapper: true }
+    }
+
+    /// Enable/disable TCP wrapper framing
+    pub fn with_tcp_wrapper(mut self, enabled: bool) -> Self {
+        self.tcp_wrapper = enabled;
+        self
+    }
+
+    /// Check if TCP wrapper framing is enabled
+    pub fn uses_tcp_wrapper(&self) -> bool {
+        self.tcp_wrapper
+    }
+}
+
+#[cfg(feature = "codec")]
+impl Default for ZkCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Write the TCP wrapper header for a packet of `len` bytes
+pub fn put_tcp_header(dst: &mut BytesMut, len: usize) {
+    dst.put_u16_le(TCP_MAGIC_1);
+    dst.put_u16_le(TCP_MAGIC_2);
+    dst.put_u32_le(len as u32);
+}
+
+/// Check whether `data` starts with the TCP wrapper magic
+pub fn has_tcp_header(data: &[u8]) -> bool {
+    data.len() >= TCP_HEADER_SIZE
+        && u16::from_le_bytes([data[0], data[1]]) == TCP_MAGIC_1
+        && u16::from_le_bytes([data[2], data[3]]) == TCP_MAGIC_2
+}
+
+/// Read and validate the length declared in a TCP wrapper header
+///
+/// Fails if `header` is shorter than a wrapper header. The declared length
+/// is attacker-controlled, so it is checked against the protocol maximum
+/// before anything is buffered for it.
+pub fn declared_frame_length(header: &[u8]) -> Result<usize> {
+    if header.len() < TCP_HEADER_SIZE {
+        return Err(Error::InvalidFrame(format!(
+            "TCP wrapper header needs {} bytes, got {}",
+            TCP_HEADER_SIZE,
+            header.len()
+        )));
+    }
+
+    let declared = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+    if declared > Packet::MAX_SIZE {
+        return Err(Error::FrameTooLarge {
+            declared,
+            max: Packet::MAX_SIZE,
+        });
+    }
+
+    Ok(declared)
+}
+
+/// Wrap a raw packet with the TCP header
+pub fn wrap_tcp(data: &[u8]) -> BytesMut {
+    let mut buf = BytesMut::with_capacity(TCP_HEADER_SIZE + data.len());
+    put_tcp_header(&mut buf, data.len());
+    buf.put_slice(data);
+    buf
+}
+
+#[cfg(feature = "codec")]
+impl Encoder<Packet> for ZkCodec {
+    type Error = Error;
+
+    fn encode(&mut self, packet: Packet, dst: &mut BytesMut) -> Result<()> {
+        let encoded = packet.encode();
+
+        if self.tcp_wrapper {
+            dst.reserve(TCP_HEADER_SIZE + encoded.len());
+            put_tcp_header(dst, encoded.len());
+        }
+        dst.extend_from_slice(&encoded);
+
+        trace!(
+            "Encoded {} ({} bytes, TCP wrapper: {})",
+            packet,
+            encoded.len(),
+            self.tcp_wrapper
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "codec")]
+impl Decoder for ZkCodec {
+    type Item = Packet;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>> {
+        if !self.tcp_wrapper {
+            // No length information - the whole buffer is one packet
+            if src.len() < Packet::HEADER_SIZE {
+                return Ok(None);
+            }
+            let frame = src.split();
+            return Ok(Some(Packet::decode(frame)?));
+        }
+
+        if src.len() < TCP_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        if !has_tcp_header(src) {
+            return Err(Error::InvalidFrame(format!(
+                "Bad TCP wrapper magic: {:02X?}",
+                &src[..4]
+            )));
+        }
+
+        let len = declared_frame_length(src)?;
+
+        // A frame that cannot hold a packet header is never going to decode
+        if len < Packet::HEADER_SIZE {
+            return Err(Error::FrameTooShort {
+                declared: len,
+                min: Packet::HEADER_SIZE,
+            });
+        }
+
+        if src.len() < TCP_HEADER_SIZE + len {
+            return Ok(None);
+        }
+
+        src.advance(TCP_HEADER_SIZE);
+        let frame = src.split_to(len);
+
+        Ok(Some(Packet::decode(frame)?))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Packet>> {
+        match self.decode(src)? {
+            Some(packet) => Ok(Some(packet)),
+            None if src.is_empty() => Ok(None),
+            None => Err(Error::InvalidFrame(format!(
+                "{} trailing bytes at end of stream",
+                src.len()
+            ))),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "codec"))]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::Framed;
+    use zkrust_core::Command;
+
+    #[test]
+    fn test_encode_bare() {
+        let mut codec = ZkCodec::new();
+        let mut buf = BytesMut::new();
+
+        let packet = Packet::new(Command::Connect, 0, 0);
+        codec.encode(packet.clone(), &mut buf).unwrap();
+
+        assert_eq!(buf, packet.encode());
+    }
+
+    #[test]
+    fn test_encode_tcp_wrapper() {
+        let mut codec = ZkCodec::tcp();
+        let mut buf = BytesMut::new();
+
+        let packet = Packet::with_payload(Command::Auth, 1, 2, vec![1, 2, 3, 4]);
+        codec.encode(packet.clone(), &mut buf).unwrap();
+
+        assert!(has_tcp_header(&buf));
+        assert_eq!(u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]), 12);
+        assert_eq!(&buf[TCP_HEADER_SIZE..], &packet.encode()[..]);
+    }
+
+    #[test]
+    fn test_decode_bare() {
+        let mut codec = ZkCodec::new();
+        let packet = Packet::with_payload(Command::AckOk, 100, 5, vec![9, 8, 7]);
+        let mut buf = packet.encode();
+
+        let decoded = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(decoded, packet);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_partial_then_complete() {
+        let mut codec = ZkCodec::tcp();
+        let packet = Packet::with_payload(Command::AckData, 100, 5, vec![0xAB; 100]);
+        let full = wrap_tcp(&packet.encode());
+
+        let mut buf = BytesMut::from(&full[..20]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+
+        buf.extend_from_slice(&full[20..]);
+        let decoded = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(decoded, packet);
+    }
+
+    #[test]
+    fn test_decode_multiple_frames() {
+        let mut codec = ZkCodec::tcp();
+        let p1 = Packet::new(Command::AckOk, 1, 1);
+        let p2 = Packet::with_payload(Command::AckData, 1, 2, vec![1, 2]);
+
+        let mut buf = wrap_tcp(&p1.encode());
+        buf.extend_from_slice(&wrap_tcp(&p2.encode()));
+
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap(), p1);
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap(), p2);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_decode_bad_magic() {
+        let mut codec = ZkCodec::tcp();
+        let mut buf = BytesMut::from(&[0u8; 16][..]);
+
+        assert!(matches!(codec.decode(&mut buf), Err(Error::InvalidFrame(_))));
+    }
+
+    #[test]
+    fn test_decode_eof_trailing_bytes() {
+        let mut codec = ZkCodec::tcp();
+        let mut buf = BytesMut::from(&[0x50, 0x50, 0x82][..]);
+
+        assert!(codec.decode_eof(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_decode_rejects_oversized_length() {
+        let mut codec = ZkCodec::tcp();
+        let mut buf = BytesMut::new();
+        put_tcp_header(&mut buf, u32::MAX as usize);
+
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(Error::FrameTooLarge { declared, .. }) if declared == u32::MAX as usize
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_undersized_length() {
+        let mut codec = ZkCodec::tcp();
+        let mut buf = BytesMut::new();
+        put_tcp_header(&mut buf, 3);
+        buf.put_slice(&[1, 2, 3]);
+
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(Error::FrameTooShort { declared: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_declared_length_short_header() {
+        let header = wrap_tcp(&[0; 8]);
+        assert_eq!(declared_frame_length(&header).unwrap(), 8);
+        assert!(matches!(declared_frame_length(&header[..7]), Err(Error::InvalidFrame(_))));
+    }
+
+    #[tokio::test]
+    async fn test_framed_roundtrip() {
+        let (client, server) = tokio::io::duplex(1024);
+        let mut client = Framed::new(client, ZkCodec::tcp());
+        let mut server = Framed::new(server, ZkCodec::tcp());
+
+        let packet = Packet::with_payload(Command::GetVersion, 42, 7, vec![1, 2, 3]);
+        client.send(packet.clone()).await.unwrap();
+
+        let received = server.next().await.unwrap().unwrap();
+        assert_eq!(received, packet);
+    }
+}
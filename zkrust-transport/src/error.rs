@@ -1,29 +1,68 @@
-//! Transport errors
-
-use std::io;
-
-pub type Result<T> = std::result::Result<T, Error>;
-
-#[derive(Debug, thiserror::Error)]
-pub enum Error {
-    #[error("Not connected")]
-    NotConnected,
-    
-    #[error("Already connected")]
-    AlreadyConnected,
-    
-    #[error("Connection timeout")]
-    ConnectionTimeout,
-    
-    #[error("Read timeout")]
-    ReadTimeout,
-    
-    #[error("Connection closed by remote")]
-    ConnectionClosed,
-    
-    #[error("I/O error: {0}")]
-    Io(#[from] io::Error),
-    
-    #[error("Invalid address: {0}")]
-    InvalidAddress(String),
-}
\ No newline at end of file
+//! Transport errors
+
+use std::io;
+
+use zkrust_core::RetryClass;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Not connected")]
+    NotConnected,
+    
+    #[error("Already connected")]
+    AlreadyConnected,
+    
+    #[error("Connection timeout")]
+    ConnectionTimeout,
+    
+    #[error("Read timeout")]
+    ReadTimeout,
+    
+    #[error("Connection closed by remote")]
+    ConnectionClosed,
+    
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    
+    #[error("Invalid frame: {0}")]
+    InvalidFrame(String),
+    
+    #[error("Frame declares {declared} bytes (max: {max} bytes)")]
+    FrameTooLarge { declared: usize, max: usize },
+    
+    #[error("Frame declares {declared} bytes, shorter than a packet header ({min} bytes)")]
+    FrameTooShort { declared: usize, min: usize },
+    
+    #[error("Truncated frame: header declares {declared} bytes, {actual} received")]
+    TruncatedFrame { declared: usize, actual: usize },
+    
+    #[error("Protocol error: {0}")]
+    Protocol(#[from] zkrust_core::Error),
+}
+
+impl Error {
+    /// Classify the error for retry decisions
+    ///
+    /// Framing errors count as [`RetryClass::Reconnect`]: on TCP the byte
+    /// stream is out of sync and only a new connection realigns it.
+    pub fn retry_class(&self) -> RetryClass {
+        match self {
+            Self::ReadTimeout => RetryClass::Retryable,
+            Self::NotConnected
+            | Self::ConnectionTimeout
+            | Self::ConnectionClosed
+            | Self::Io(_)
+            | Self::InvalidFrame(_)
+            | Self::FrameTooLarge { .. }
+            | Self::FrameTooShort { .. }
+            | Self::TruncatedFrame { .. } => RetryClass::Reconnect,
+            Self::AlreadyConnected | Self::InvalidAddress(_) => RetryClass::Fatal,
+            Self::Protocol(e) => e.retry_class(),
+        }
+    }
+}
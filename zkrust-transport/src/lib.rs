@@ -1,14 +1,34 @@
 //! Transport layer for ZKTeco protocol
 //!
 //! Provides TCP/UDP communication with devices.
+//!
+//! ## Features
+//!
+//! | Feature       | Default | Enables                                 |
+//! |---------------|---------|-----------------------------------------|
+//! | `tracing`     | yes     | Log events through `tracing`            |
+//! | `parking_lot` | yes     | `parking_lot` locks in `zkrust-core`    |
+//! | `codec`       | yes     | [`ZkCodec`] for `tokio_util` pipelines  |
+//! | `test-util`   | no      | Scripted `MockTransport` for unit tests |
+//!
+//! The TCP framing helpers in [`codec`] are available without the `codec`
+//! feature; only the `tokio_util` codec needs it.
 
+pub mod codec;
 pub mod tcp;
 pub mod udp;
 pub mod error;
+mod log;
+#[cfg(feature = "test-util")]
+pub mod mock;
 
+#[cfg(feature = "codec")]
+pub use codec::ZkCodec;
 pub use error::{Error, Result};
 pub use tcp::TcpTransport;
 pub use udp::UdpTransport;
+#[cfg(feature = "test-util")]
+pub use mock::MockTransport;
 
 use async_trait::async_trait;
 use bytes::BytesMut;
@@ -31,6 +51,14 @@ pub trait Transport: Send + Sync {
     /// Receive raw bytes (with timeout)
     async fn receive(&mut self, timeout_secs: u64) -> Result<BytesMut>;
     
+    /// Discard data received but not read yet, e.g. late replies
+    ///
+    /// Returns how many messages were dropped. Transports that don't buffer
+    /// anything between commands keep this default.
+    async fn drain(&mut self) -> Result<usize> {
+        Ok(0)
+    }
+    
     /// Get remote address
     fn remote_addr(&self) -> String;
 }
\ No newline at end of file
@@ -0,0 +1,18 @@
+//! Logging macros
+//!
+//! Forward to `tracing` with the `tracing` feature (the default). Without
+//! it the format arguments are still type-checked, so variables only used
+//! in log messages don't turn into warnings, but nothing is formatted.
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{debug, trace, warn};
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! disabled {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use {disabled as debug, disabled as trace, disabled as warn};
@@ -0,0 +1,645 @@
+//! Scripted transport for unit tests (`test-util` feature)
+//!
+//! [`MockTransport`] plays the device from a list of [`Expectation`]s:
+//! each packet sent must match the next expectation, which then queues its
+//! replies. Replies echo the request's reply ID and carry the mock's
+//! session ID. A mismatch panics, so a test fails at the first unexpected
+//! packet; receiving with nothing queued fails with
+//! [`Error::ReadTimeout`] at once, without waiting. [`Expectation::late`]
+//! replies only show up after such a timeout, like a slow device's.
+//!
+//! [`MockTransport::with_faults`] damages the scripted replies on their way
+//! back: lost, delayed, truncated or corrupted frames and a dropped
+//! connection. The faults are drawn from a seeded generator, so a given
+//! seed fails the same frames on every run.
+//!
+//! # Examples
+//!
+//! ```
+//! use zkrust_core::{Command, Packet};
+//! use zkrust_transport::mock::{Expectation, MockTransport};
+//! use zkrust_transport::Transport;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let mut transport = MockTransport::new()
+//!     .expect(Expectation::new(Command::Connect).reply(Command::AckOk))
+//!     .expect(Expectation::new(Command::GetTime).timeout());
+//! let handle = transport.handle();
+//!
+//! transport.connect().await.unwrap();
+//! transport.send(&Packet::new(Command::Connect, 0, 0).encode()).await.unwrap();
+//! let reply = Packet::decode(transport.receive(5).await.unwrap()).unwrap();
+//! assert_eq!(reply.command, Command::AckOk);
+//!
+//! transport.send(&Packet::new(Command::GetTime, 1, 1).encode()).await.unwrap();
+//! assert!(transport.receive(5).await.is_err());
+//!
+//! handle.assert_done();
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+
+use zkrust_core::{Command, Packet};
+
+use crate::{Error, Result, Transport};
+
+/// One expected request and the device's answer to it
+#[derive(Debug, Clone)]
+pub struct Expectation {
+    command: Command,
+    payload: Option<Bytes>, // None = any payload
+    replies: Vec<Reply>,
+    late: bool,
+}
+
+/// Scripted answer packet
+#[derive(Debug, Clone)]
+enum Reply {
+    Packet(Option<u16>, Command, Bytes), // None = the mock's session
+    Raw(Bytes),
+}
+
+impl Expectation {
+    /// Expect `command` with any payload; no reply until one is added
+    pub fn new(command: Command) -> Self {
+        Self {
+            command,
+            payload: None,
+            replies: Vec::new(),
+            late: false,
+        }
+    }
+
+    /// Also require the request payload to equal `payload`
+    pub fn with_payload(mut self, payload: impl Into<Bytes>) -> Self {
+        self.payload = Some(payload.into());
+        self
+    }
+
+    /// Answer with an empty `command` packet
+    pub fn reply(self, command: Command) -> Self {
+        self.reply_with(command, Bytes::new())
+    }
+
+    /// Answer with `command` carrying `payload`
+    ///
+    /// Call repeatedly to script a multi-packet answer such as a bulk
+    /// transfer.
+    pub fn reply_with(mut self, command: Command, payload: impl Into<Bytes>) -> Self {
+        self.replies.push(Reply::Packet(None, command, payload.into()));
+        self
+    }
+
+    /// Answer with `frame` as is
+    ///
+    /// For packets a well-behaved device wouldn't send, such as ones with a
+    /// bad checksum, a wrong reply ID or an unknown command code.
+    pub fn reply_raw(mut self, frame: impl Into<Bytes>) -> Self {
+        self.replies.push(Reply::Raw(frame.into()));
+        self
+    }
+
+    /// Answer with `command` from another session, like a late reply to an
+    /// earlier connection
+    pub fn stray(mut self, session_id: u16, command: Command) -> Self {
+        self.replies.push(Reply::Packet(Some(session_id), command, Bytes::new()));
+        self
+    }
+
+    /// Hold the replies back until a receive has timed out
+    pub fn late(mut self) -> Self {
+        self.late = true;
+        self
+    }
+
+    /// Swallow the request, so the next receive times out
+    pub fn timeout(mut self) -> Self {
+        self.replies.clear();
+        self
+    }
+}
+
+/// Faults injected into the replies of a [`MockTransport`]
+///
+/// Each rate is the probability, from 0 to 1, that one reply frame suffers
+/// the fault. The default injects nothing.
+///
+/// ```
+/// use std::time::Duration;
+/// use zkrust_transport::mock::{Faults, MockTransport};
+///
+/// let transport = MockTransport::new().with_faults(
+///     Faults::seeded(7)
+///         .with_loss(0.2)
+///         .with_delay(0.1, Duration::from_secs(10))
+///         .with_disconnect_after(40),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    seed: u64,
+    loss: f64,
+    delay: f64,
+    delay_by: Duration,
+    truncation: f64,
+    corruption: f64,
+    disconnect_after: Option<usize>,
+}
+
+impl Faults {
+    /// No faults yet, drawn from the generator seeded with `seed`
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Self::default()
+        }
+    }
+
+    /// Drop replies, as a lossy network would
+    ///
+    /// The next queued frame is received instead, or the receive times out.
+    pub fn with_loss(mut self, rate: f64) -> Self {
+        self.loss = check_rate(rate);
+        self
+    }
+
+    /// Hold replies back for `by`
+    ///
+    /// A delay longer than the receive timeout makes the receive time out
+    /// at once, and the frame shows up after it like an
+    /// [`Expectation::late`] reply.
+    pub fn with_delay(mut self, rate: f64, by: Duration) -> Self {
+        self.delay = check_rate(rate);
+        self.delay_by = by;
+        self
+    }
+
+    /// Cut replies short at a random length
+    pub fn with_truncation(mut self, rate: f64) -> Self {
+        self.truncation = check_rate(rate);
+        self
+    }
+
+    /// Flip bits in the checksum of replies
+    pub fn with_corruption(mut self, rate: f64) -> Self {
+        self.corruption = check_rate(rate);
+        self
+    }
+
+    /// Drop the connection once `frames` replies have been received
+    ///
+    /// The receive after them fails with [`Error::ConnectionClosed`] and
+    /// discards any queued replies. It happens once; a reconnected
+    /// transport carries on with the script.
+    pub fn with_disconnect_after(mut self, frames: usize) -> Self {
+        self.disconnect_after = Some(frames);
+        self
+    }
+}
+
+/// # Panics
+///
+/// If `rate` is not between 0 and 1.
+fn check_rate(rate: f64) -> f64 {
+    assert!((0.0..=1.0).contains(&rate), "fault rate {} is not between 0 and 1", rate);
+    rate
+}
+
+/// Faults injected so far, from [`MockHandle::faults`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FaultCounts {
+    pub lost: usize,
+    pub delayed: usize,
+    pub truncated: usize,
+    pub corrupted: usize,
+    pub disconnects: usize,
+}
+
+/// What happens to one reply frame
+enum Delivery {
+    Frame(Bytes, Option<Duration>),
+    Empty,
+    Closed,
+}
+
+#[derive(Debug, Default)]
+struct Script {
+    expected: VecDeque<Expectation>,
+    pending: VecDeque<Bytes>,
+    late: VecDeque<Bytes>,
+    sent: Vec<Bytes>,
+    faults: Faults,
+    rng: u64,
+    received: usize,
+    counts: FaultCounts,
+}
+
+impl Script {
+    /// Next reply, with the faults it suffers
+    fn next_reply(&mut self, timeout: Duration) -> Delivery {
+        if self.faults.disconnect_after == Some(self.received) {
+            self.faults.disconnect_after = None;
+            self.pending.clear();
+            self.late.clear();
+            self.counts.disconnects += 1;
+            return Delivery::Closed;
+        }
+
+        loop {
+            let Some(frame) = self.pending.pop_front() else {
+                return Delivery::Empty;
+            };
+            if self.chance(self.faults.loss) {
+                self.counts.lost += 1;
+                continue;
+            }
+
+            let mut delay = None;
+            if self.chance(self.faults.delay) {
+                self.counts.delayed += 1;
+                if self.faults.delay_by > timeout {
+                    self.late.push_back(frame);
+                    return Delivery::Empty;
+                }
+                delay = Some(self.faults.delay_by);
+            }
+
+            let mut frame = BytesMut::from(frame);
+            if frame.len() > 2 && self.chance(self.faults.corruption) {
+                self.counts.corrupted += 1;
+                frame[2] ^= 0xff;
+            }
+            if frame.len() > 1 && self.chance(self.faults.truncation) {
+                self.counts.truncated += 1;
+                let len = 1 + self.next_random() as usize % (frame.len() - 1);
+                frame.truncate(len);
+            }
+
+            self.received += 1;
+            return Delivery::Frame(frame.freeze(), delay);
+        }
+    }
+
+    fn chance(&mut self, rate: f64) -> bool {
+        if rate == 0.0 {
+            return false;
+        }
+        let draw = (self.next_random() >> 11) as f64 / (1u64 << 53) as f64;
+        draw < rate
+    }
+
+    /// SplitMix64
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Transport that answers from a script instead of a socket
+#[derive(Debug)]
+pub struct MockTransport {
+    connected: bool,
+    session_id: u16,
+    script: Arc<Mutex<Script>>,
+}
+
+impl MockTransport {
+    /// Create a transport with an empty script (session ID 1)
+    pub fn new() -> Self {
+        Self {
+            connected: false,
+            session_id: 1,
+            script: Arc::default(),
+        }
+    }
+
+    /// Set the session ID put in every reply
+    pub fn with_session_id(mut self, session_id: u16) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    /// Inject `faults` into the replies from here on
+    pub fn with_faults(self, faults: Faults) -> Self {
+        {
+            let mut script = self.lock();
+            script.rng = faults.seed;
+            script.received = 0;
+            script.faults = faults;
+        }
+        self
+    }
+
+    /// Append an expectation to the script
+    pub fn expect(self, expectation: Expectation) -> Self {
+        self.lock().expected.push_back(expectation);
+        self
+    }
+
+    /// Handle for inspecting the transport once it has been moved into a device
+    pub fn handle(&self) -> MockHandle {
+        MockHandle {
+            script: Arc::clone(&self.script),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Script> {
+        self.script.lock().unwrap()
+    }
+}
+
+impl Default for MockTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn connect(&mut self) -> Result<()> {
+        self.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.connected = false;
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// # Panics
+    ///
+    /// If the packet doesn't match the next expectation, or none is left.
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        if !self.connected {
+            return Err(Error::NotConnected);
+        }
+
+        let packet = Packet::decode(BytesMut::from(data))?;
+        let session_id = self.session_id;
+        let mut script = self.lock();
+        script.sent.push(Bytes::copy_from_slice(data));
+
+        let Some(expected) = script.expected.pop_front() else {
+            panic!("MockTransport: unexpected {}", packet);
+        };
+        assert_eq!(packet.command, expected.command, "MockTransport: wrong command sent");
+        if let Some(payload) = &expected.payload {
+            assert_eq!(&packet.payload, payload, "MockTransport: wrong payload for {}", packet.command);
+        }
+
+        let replies = expected.replies.into_iter().map(|reply| match reply {
+            Reply::Packet(session, command, payload) => {
+                Packet::with_payload(command, session.unwrap_or(session_id), packet.reply_id, payload)
+                    .encode()
+                    .freeze()
+            }
+            Reply::Raw(frame) => frame,
+        });
+        if expected.late {
+            script.late.extend(replies);
+        } else {
+            script.pending.extend(replies);
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self, timeout_secs: u64) -> Result<BytesMut> {
+        if !self.connected {
+            return Err(Error::NotConnected);
+        }
+
+        let delivery = self.lock().next_reply(Duration::from_secs(timeout_secs));
+        match delivery {
+            Delivery::Frame(frame, delay) => {
+                if let Some(delay) = delay {
+                    tokio::time::sleep(delay).await;
+                }
+                Ok(BytesMut::from(frame))
+            }
+            Delivery::Empty => {
+                // Late replies arrive while nobody is listening
+                let mut script = self.lock();
+                let late = std::mem::take(&mut script.late);
+                script.pending.extend(late);
+                Err(Error::ReadTimeout)
+            }
+            Delivery::Closed => {
+                self.connected = false;
+                Err(Error::ConnectionClosed)
+            }
+        }
+    }
+
+    async fn drain(&mut self) -> Result<usize> {
+        let mut script = self.lock();
+        let dropped = script.pending.len();
+        script.pending.clear();
+        Ok(dropped)
+    }
+
+    fn remote_addr(&self) -> String {
+        "mock".into()
+    }
+}
+
+/// Inspects a [`MockTransport`] after it has been handed to a device
+#[derive(Debug, Clone)]
+pub struct MockHandle {
+    script: Arc<Mutex<Script>>,
+}
+
+impl MockHandle {
+    /// Raw bytes of every packet sent so far
+    pub fn sent(&self) -> Vec<Bytes> {
+        self.script.lock().unwrap().sent.clone()
+    }
+
+    /// Commands of every packet sent so far
+    pub fn sent_commands(&self) -> Vec<Command> {
+        self.sent()
+            .into_iter()
+            .filter_map(|data| Packet::decode(BytesMut::from(&data[..])).ok())
+            .map(|packet| packet.command)
+            .collect()
+    }
+
+    /// Faults injected so far
+    pub fn faults(&self) -> FaultCounts {
+        self.script.lock().unwrap().counts
+    }
+
+    /// Number of expectations not yet met
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().expected.len()
+    }
+
+    /// Panic unless every expectation was met
+    pub fn assert_done(&self) {
+        let script = self.script.lock().unwrap();
+        let left: Vec<_> = script.expected.iter().map(|e| e.command).collect();
+        assert!(left.is_empty(), "MockTransport: expected commands never sent: {:?}", left);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn exchange(transport: &mut MockTransport, packet: Packet) -> Result<Packet> {
+        transport.send(&packet.encode()).await?;
+        Ok(Packet::decode(transport.receive(5).await?)?)
+    }
+
+    #[tokio::test]
+    async fn test_scripted_replies() {
+        let mut transport = MockTransport::new()
+            .with_session_id(77)
+            .expect(
+                Expectation::new(Command::AttLogRrq)
+                    .reply_with(Command::PrepareData, vec![4, 0, 0, 0])
+                    .reply_with(Command::Data, vec![0, 0, 0, 0])
+                    .reply(Command::AckOk),
+            );
+        let handle = transport.handle();
+        transport.connect().await.unwrap();
+
+        let first = exchange(&mut transport, Packet::new(Command::AttLogRrq, 77, 12)).await.unwrap();
+        assert_eq!(first.command, Command::PrepareData);
+        assert_eq!((first.session_id, first.reply_id), (77, 12));
+
+        let rest = [transport.receive(5).await.unwrap(), transport.receive(5).await.unwrap()];
+        assert_eq!(Packet::decode(rest[1].clone()).unwrap().command, Command::AckOk);
+        assert!(matches!(transport.receive(5).await, Err(Error::ReadTimeout)));
+
+        assert_eq!(handle.sent_commands(), vec![Command::AttLogRrq]);
+        handle.assert_done();
+    }
+
+    #[tokio::test]
+    async fn test_payload_check() {
+        let mut transport = MockTransport::new()
+            .expect(Expectation::new(Command::OptionsRrq).with_payload(&b"~Platform\0"[..]).reply(Command::AckOk))
+            .expect(Expectation::new(Command::Exit));
+        let handle = transport.handle();
+        transport.connect().await.unwrap();
+
+        let packet = Packet::with_payload(Command::OptionsRrq, 1, 1, &b"~Platform\0"[..]);
+        exchange(&mut transport, packet.clone()).await.unwrap();
+        assert_eq!(handle.sent(), vec![packet.encode().freeze()]);
+        assert_eq!(handle.remaining(), 1);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "wrong payload")]
+    async fn test_payload_mismatch_panics() {
+        let mut transport = MockTransport::new()
+            .expect(Expectation::new(Command::DeleteUser).with_payload(vec![1, 0]));
+        transport.connect().await.unwrap();
+
+        let _ = transport.send(&Packet::with_payload(Command::DeleteUser, 1, 1, vec![2, 0]).encode()).await;
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "never sent")]
+    async fn test_assert_done() {
+        let transport = MockTransport::new().expect(Expectation::new(Command::Connect).reply(Command::AckOk));
+        transport.handle().assert_done();
+    }
+
+    /// Transport answering `count` CMD_GET_TIME requests
+    fn answering(count: usize, faults: Faults) -> MockTransport {
+        (0..count).fold(MockTransport::new().with_faults(faults), |transport, _| {
+            transport.expect(Expectation::new(Command::GetTime).reply_with(Command::AckOk, vec![1, 2, 3, 4]))
+        })
+    }
+
+    async fn outcomes(transport: &mut MockTransport, count: usize) -> Vec<bool> {
+        transport.connect().await.unwrap();
+        let mut received = Vec::new();
+        for _ in 0..count {
+            received.push(exchange(transport, Packet::new(Command::GetTime, 1, 1)).await.is_ok());
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn test_loss_is_deterministic() {
+        let faults = Faults::seeded(42).with_loss(0.5);
+        let mut first = answering(32, faults.clone());
+        let mut second = answering(32, faults);
+
+        let received = outcomes(&mut first, 32).await;
+        assert_eq!(received, outcomes(&mut second, 32).await);
+
+        let lost = first.handle().faults().lost;
+        assert!(lost > 0 && lost < 32, "{} lost", lost);
+        assert_eq!(received.iter().filter(|ok| !**ok).count(), lost);
+    }
+
+    #[tokio::test]
+    async fn test_corruption_and_truncation() {
+        let mut transport = answering(1, Faults::seeded(1).with_corruption(1.0));
+        transport.connect().await.unwrap();
+        transport.send(&Packet::new(Command::GetTime, 1, 1).encode()).await.unwrap();
+        let frame = transport.receive(5).await.unwrap();
+        assert!(matches!(
+            Packet::decode(frame.clone()),
+            Err(zkrust_core::Error::ChecksumMismatch { .. })
+        ));
+        assert_eq!(Packet::decode_unverified(frame).unwrap().payload, vec![1, 2, 3, 4]);
+
+        let mut transport = answering(1, Faults::seeded(1).with_truncation(1.0));
+        let handle = transport.handle();
+        transport.connect().await.unwrap();
+        transport.send(&Packet::new(Command::GetTime, 1, 1).encode()).await.unwrap();
+        assert!(transport.receive(5).await.unwrap().len() < Packet::HEADER_SIZE + 4);
+        assert_eq!(handle.faults(), FaultCounts { truncated: 1, ..FaultCounts::default() });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delay() {
+        let mut transport = answering(2, Faults::seeded(1).with_delay(1.0, Duration::from_secs(3)));
+        transport.connect().await.unwrap();
+
+        let started = tokio::time::Instant::now();
+        exchange(&mut transport, Packet::new(Command::GetTime, 1, 1)).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+
+        // Longer than the timeout: times out, then arrives late
+        transport.send(&Packet::new(Command::GetTime, 1, 2).encode()).await.unwrap();
+        assert!(matches!(transport.receive(2).await, Err(Error::ReadTimeout)));
+        let late = Packet::decode(transport.receive(5).await.unwrap()).unwrap();
+        assert_eq!(late.reply_id, 2);
+        assert_eq!(transport.handle().faults().delayed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_after() {
+        let mut transport = answering(3, Faults::seeded(1).with_disconnect_after(1));
+        let handle = transport.handle();
+        assert_eq!(outcomes(&mut transport, 1).await, vec![true]);
+
+        transport.send(&Packet::new(Command::GetTime, 1, 2).encode()).await.unwrap();
+        assert!(matches!(transport.receive(5).await, Err(Error::ConnectionClosed)));
+        assert!(!transport.is_connected());
+
+        // Only once: the reconnected transport carries on
+        assert_eq!(outcomes(&mut transport, 1).await, vec![true]);
+        assert_eq!(handle.faults().disconnects, 1);
+        handle.assert_done();
+    }
+}
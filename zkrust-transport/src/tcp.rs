@@ -1,308 +1,379 @@
-//! TCP transport implementation
-
-use std::net::SocketAddr;
-use std::time::Duration;
-
-use async_trait::async_trait;
-use bytes::{Buf, BufMut, BytesMut};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
-use tokio::time::timeout;
-use tracing::{debug, trace, warn};
-
-use crate::{error::*, Transport};
-
-/// TCP transport for ZKTeco devices
-///
-/// Many ZKTeco devices require TCP packets to be wrapped with a header:
-/// [0x5050][0x8272][length: 4 bytes LE] + [ZK packet]
-pub struct TcpTransport {
-    addr: String,
-    port: u16,
-    socket_addr: Option<SocketAddr>,
-    stream: Option<TcpStream>,
-    connect_timeout: Duration,
-    read_timeout: Duration,
-    use_tcp_wrapper: bool, // Enable TCP wrapper for F18 and similar devices
-}
-
-impl TcpTransport {
-    /// Create new TCP transport
-    pub fn new(addr: impl Into<String>, port: u16) -> Self {
-        Self {
-            addr: addr.into(),
-            port,
-            socket_addr: None,
-            stream: None,
-            connect_timeout: Duration::from_secs(5),
-            read_timeout: Duration::from_secs(5),
-            use_tcp_wrapper: true, // Default: enabled (most devices need it)
-        }
-    }
-    
-    /// Set connection timeout
-    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
-        self.connect_timeout = timeout;
-        self
-    }
-    
-    /// Set read timeout
-    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
-        self.read_timeout = timeout;
-        self
-    }
-    
-    /// Enable/disable TCP wrapper
-    pub fn with_tcp_wrapper(mut self, enabled: bool) -> Self {
-        self.use_tcp_wrapper = enabled;
-        self
-    }
-    
-    /// Resolve address to SocketAddr
-    async fn resolve_addr(&mut self) -> Result<SocketAddr> {
-        if let Some(addr) = self.socket_addr {
-            return Ok(addr);
-        }
-        
-        let addr_str = format!("{}:{}", self.addr, self.port);
-        
-        let addrs: Vec<SocketAddr> = tokio::net::lookup_host(&addr_str)
-            .await
-            .map_err(|e| Error::InvalidAddress(format!("{}: {}", addr_str, e)))?
-            .collect();
-        
-        let addr = addrs
-            .first()
-            .ok_or_else(|| Error::InvalidAddress(format!("No addresses found for {}", addr_str)))?;
-        
-        self.socket_addr = Some(*addr);
-        Ok(*addr)
-    }
-    
-    /// Wrap data with TCP header
-    fn wrap_tcp_packet(&self, data: &[u8]) -> BytesMut {
-        let mut buf = BytesMut::with_capacity(8 + data.len());
-        
-        // Magic bytes
-        buf.put_u16_le(0x5050);
-        buf.put_u16_le(0x8272);
-        
-        // Payload length (4 bytes, little-endian)
-        buf.put_u32_le(data.len() as u32);
-        
-        // Payload
-        buf.put_slice(data);
-        
-        trace!(
-            "Wrapped packet: {} bytes payload -> {} bytes total",
-            data.len(),
-            buf.len()
-        );
-        
-        buf
-    }
-    
-    /// Unwrap TCP header from received data
-    fn unwrap_tcp_packet(&self, mut data: BytesMut) -> Result<BytesMut> {
-        if data.len() < 8 {
-            return Ok(data); // Not wrapped or incomplete
-        }
-        
-        // Check for TCP wrapper magic
-        let magic1 = u16::from_le_bytes([data[0], data[1]]);
-        let magic2 = u16::from_le_bytes([data[2], data[3]]);
-        
-        if magic1 == 0x5050 && magic2 == 0x8272 {
-            // Has TCP wrapper - skip 8-byte header
-            let _length = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
-            
-            trace!("Unwrapped TCP packet: {} bytes header removed", 8);
-            
-            // Return data without header
-            data.advance(8);
-        }
-        
-        Ok(data)
-    }
-}
-
-#[async_trait]
-impl Transport for TcpTransport {
-    async fn connect(&mut self) -> Result<()> {
-        if self.is_connected() {
-            return Err(Error::AlreadyConnected);
-        }
-        
-        let addr = self.resolve_addr().await?;
-        
-        debug!("Connecting to {}...", addr);
-        
-        let stream = timeout(self.connect_timeout, TcpStream::connect(addr))
-            .await
-            .map_err(|_| Error::ConnectionTimeout)?
-            .map_err(Error::Io)?;
-        
-        // Disable Nagle's algorithm for low latency
-        stream.set_nodelay(true)?;
-        
-        debug!(
-            "Connected to {} (TCP wrapper: {})",
-            addr,
-            if self.use_tcp_wrapper { "enabled" } else { "disabled" }
-        );
-        
-        self.stream = Some(stream);
-        Ok(())
-    }
-    
-    async fn disconnect(&mut self) -> Result<()> {
-        if let Some(mut stream) = self.stream.take() {
-            debug!("Disconnecting from {}...", self.remote_addr());
-            
-            // Graceful shutdown
-            let _ = stream.shutdown().await;
-        }
-        
-        self.socket_addr = None;
-        Ok(())
-    }
-    
-    fn is_connected(&self) -> bool {
-        self.stream.is_some()
-    }
-    
-    async fn send(&mut self, data: &[u8]) -> Result<()> {
-        // Wrap packet if needed (before getting mutable borrow of stream)
-        let send_data = if self.use_tcp_wrapper {
-            self.wrap_tcp_packet(data)
-        } else {
-            BytesMut::from(data)
-        };
-
-        trace!(
-            "Sending {} bytes: {:02X?}",
-            send_data.len(),
-            &send_data[..send_data.len().min(32)]
-        );
-
-        // Get stream and send
-        let stream = self.stream.as_mut().ok_or(Error::NotConnected)?;
-        stream.write_all(&send_data).await?;
-        stream.flush().await?;
-
-        Ok(())
-    }
-    
-    async fn receive(&mut self, timeout_secs: u64) -> Result<BytesMut> {
-        let timeout_duration = Duration::from_secs(timeout_secs);
-
-        // Read data with timeout
-        let mut buf = BytesMut::with_capacity(2048);
-
-        // Limit scope of mutable borrow
-        let n = {
-            let stream = self.stream.as_mut().ok_or(Error::NotConnected)?;
-
-            // Read with timeout
-            timeout(timeout_duration, stream.read_buf(&mut buf))
-                .await
-                .map_err(|_| {
-                    warn!("Read timeout after {} seconds", timeout_secs);
-                    Error::ReadTimeout
-                })?
-                .map_err(|e| {
-                    warn!("Read error: {}", e);
-                    Error::Io(e)
-                })?
-        };
-
-        if n == 0 {
-            warn!("Connection closed by remote (read 0 bytes)");
-            return Err(Error::ConnectionClosed);
-        }
-
-        trace!(
-            "Received {} bytes: {:02X?}",
-            n,
-            &buf[..n.min(32)]
-        );
-
-        // Unwrap TCP header if present
-        if self.use_tcp_wrapper {
-            self.unwrap_tcp_packet(buf)
-        } else {
-            Ok(buf)
-        }
-    }
-    
-    fn remote_addr(&self) -> String {
-        self.socket_addr
-            .map(|addr| addr.to_string())
-            .unwrap_or_else(|| format!("{}:{}", self.addr, self.port))
-    }
-}
-
-impl Drop for TcpTransport {
-    fn drop(&mut self) {
-        if self.is_connected() {
-            // Don't warn in drop - normal if error occurred
-            let _ = self.stream.take();
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
-    #[test]
-    fn test_wrap_tcp_packet() {
-        let transport = TcpTransport::new("127.0.0.1", 4370);
-        let data = vec![0x01, 0x02, 0x03, 0x04];
-        let wrapped = transport.wrap_tcp_packet(&data);
-        
-        // Check magic
-        assert_eq!(wrapped[0], 0x50);
-        assert_eq!(wrapped[1], 0x50);
-        assert_eq!(wrapped[2], 0x72);
-        assert_eq!(wrapped[3], 0x82);
-        
-        // Check length
-        assert_eq!(u32::from_le_bytes([wrapped[4], wrapped[5], wrapped[6], wrapped[7]]), 4);
-        
-        // Check payload
-        assert_eq!(&wrapped[8..], &data[..]);
-    }
-    
-    #[test]
-    fn test_unwrap_tcp_packet() {
-        let transport = TcpTransport::new("127.0.0.1", 4370);
-        
-        // Create wrapped packet
-        let mut data = BytesMut::new();
-        data.put_u16_le(0x5050);
-        data.put_u16_le(0x8272);
-        data.put_u32_le(4);
-        data.put_slice(&[0x01, 0x02, 0x03, 0x04]);
-        
-        let unwrapped = transport.unwrap_tcp_packet(data).unwrap();
-        
-        assert_eq!(unwrapped.as_ref(), &[0x01, 0x02, 0x03, 0x04]);
-    }
-    
-    #[tokio::test]
-    async fn test_tcp_transport_create() {
-        let transport = TcpTransport::new("192.168.1.201", 4370);
-        assert!(!transport.is_connected());
-        assert!(transport.use_tcp_wrapper);
-    }
-    
-    #[tokio::test]
-    async fn test_tcp_transport_invalid_address() {
-        let mut transport = TcpTransport::new("invalid..address", 4370)
-            .with_connect_timeout(Duration::from_millis(100));
-        
-        let result = transport.connect().await;
-        assert!(result.is_err());
-    }
+//! TCP transport implementation
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use crate::log::{debug, trace, warn};
+
+use crate::{codec, error::*, Transport};
+
+/// TCP transport for ZKTeco devices
+///
+/// Many ZKTeco devices require TCP packets to be wrapped with a header:
+/// [0x5050][0x8272][length: 4 bytes LE] + [ZK packet]
+///
+/// With the wrapper, received bytes are buffered until a whole frame is in,
+/// so a packet split over several reads is reassembled and packets that
+/// arrive in one read are returned one at a time. Without it there is no
+/// length to go by, and each read is taken as one packet.
+pub struct TcpTransport {
+    addr: String,
+    port: u16,
+    socket_addr: Option<SocketAddr>,
+    stream: Option<TcpStream>,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    use_tcp_wrapper: bool, // Enable TCP wrapper for F18 and similar devices
+    buffer: BytesMut, // Received bytes not yet returned as a packet
+}
+
+impl TcpTransport {
+    /// Create new TCP transport
+    pub fn new(addr: impl Into<String>, port: u16) -> Self {
+        Self {
+            addr: addr.into(),
+            port,
+            socket_addr: None,
+            stream: None,
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(5),
+            use_tcp_wrapper: true, // Default: enabled (most devices need it)
+            buffer: BytesMut::new(),
+        }
+    }
+    
+    /// Set connection timeout
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+    
+    /// Set read timeout
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+    
+    /// Enable/disable TCP wrapper
+    pub fn with_tcp_wrapper(mut self, enabled: bool) -> Self {
+        self.use_tcp_wrapper = enabled;
+        self
+    }
+    
+    /// Resolve address to SocketAddr
+    async fn resolve_addr(&mut self) -> Result<SocketAddr> {
+        if let Some(addr) = self.socket_addr {
+            return Ok(addr);
+        }
+        
+        let addr_str = format!("{}:{}", self.addr, self.port);
+        
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host(&addr_str)
+            .await
+            .map_err(|e| Error::InvalidAddress(format!("{}: {}", addr_str, e)))?
+            .collect();
+        
+        let addr = addrs
+            .first()
+            .ok_or_else(|| Error::InvalidAddress(format!("No addresses found for {}", addr_str)))?;
+        
+        self.socket_addr = Some(*addr);
+        Ok(*addr)
+    }
+    
+    /// Wrap data with TCP header
+    fn wrap_tcp_packet(&self, data: &[u8]) -> BytesMut {
+        let buf = codec::wrap_tcp(data);
+        
+        trace!(
+            "Wrapped packet: {} bytes payload -> {} bytes total",
+            data.len(),
+            buf.len()
+        );
+        
+        buf
+    }
+    
+    /// Take the next complete packet out of the receive buffer
+    fn next_frame(&mut self) -> Result<Option<BytesMut>> {
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+        
+        // No length information - whatever arrived is one packet
+        if !self.use_tcp_wrapper {
+            return Ok(Some(self.buffer.split()));
+        }
+        
+        if self.buffer.len() < codec::TCP_HEADER_SIZE {
+            return Ok(None);
+        }
+        
+        // Not wrapped - pass through unchanged
+        if !codec::has_tcp_header(&self.buffer) {
+            return Ok(Some(self.buffer.split()));
+        }
+        
+        // Validate the declared length before waiting on it; past a bad
+        // header the stream can't be resynchronized, so drop what we have
+        let declared = codec::declared_frame_length(&self.buffer).inspect_err(|_| self.buffer.clear())?;
+        let available = self.buffer.len() - codec::TCP_HEADER_SIZE;
+        
+        if available < declared {
+            trace!("Waiting for rest of frame: {} of {} bytes", available, declared);
+            return Ok(None);
+        }
+        
+        self.buffer.advance(codec::TCP_HEADER_SIZE);
+        let frame = self.buffer.split_to(declared);
+        
+        if !self.buffer.is_empty() {
+            trace!("{} bytes buffered beyond frame of {}", self.buffer.len(), declared);
+        }
+        
+        Ok(Some(frame))
+    }
+    
+    /// Read until the buffer holds a complete packet
+    async fn read_frame(&mut self) -> Result<BytesMut> {
+        loop {
+            if let Some(frame) = self.next_frame()? {
+                return Ok(frame);
+            }
+            
+            let stream = self.stream.as_mut().ok_or(Error::NotConnected)?;
+            self.buffer.reserve(2048);
+            
+            let n = stream.read_buf(&mut self.buffer).await.map_err(|e| {
+                warn!("Read error: {}", e);
+                Error::Io(e)
+            })?;
+            
+            if n == 0 {
+                warn!("Connection closed by remote (read 0 bytes)");
+                return Err(Error::ConnectionClosed);
+            }
+            
+            let received = &self.buffer[self.buffer.len() - n..];
+            trace!("Received {} bytes: {:02X?}", n, &received[..n.min(32)]);
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn connect(&mut self) -> Result<()> {
+        if self.is_connected() {
+            return Err(Error::AlreadyConnected);
+        }
+        
+        let addr = self.resolve_addr().await?;
+        
+        debug!("Connecting to {}...", addr);
+        
+        let stream = timeout(self.connect_timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| Error::ConnectionTimeout)?
+            .map_err(Error::Io)?;
+        
+        // Disable Nagle's algorithm for low latency
+        stream.set_nodelay(true)?;
+        
+        debug!(
+            "Connected to {} (TCP wrapper: {})",
+            addr,
+            if self.use_tcp_wrapper { "enabled" } else { "disabled" }
+        );
+        
+        self.stream = Some(stream);
+        self.buffer.clear();
+        Ok(())
+    }
+    
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(mut stream) = self.stream.take() {
+            debug!("Disconnecting from {}...", self.remote_addr());
+            
+            // Graceful shutdown
+            let _ = stream.shutdown().await;
+        }
+        
+        self.socket_addr = None;
+        self.buffer.clear();
+        Ok(())
+    }
+    
+    fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+    
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        // Wrap packet if needed (before getting mutable borrow of stream)
+        let send_data = if self.use_tcp_wrapper {
+            self.wrap_tcp_packet(data)
+        } else {
+            BytesMut::from(data)
+        };
+
+        trace!(
+            "Sending {} bytes: {:02X?}",
+            send_data.len(),
+            &send_data[..send_data.len().min(32)]
+        );
+
+        // Get stream and send
+        let stream = self.stream.as_mut().ok_or(Error::NotConnected)?;
+        stream.write_all(&send_data).await?;
+        stream.flush().await?;
+
+        Ok(())
+    }
+    
+    async fn receive(&mut self, timeout_secs: u64) -> Result<BytesMut> {
+        let timeout_duration = Duration::from_secs(timeout_secs);
+
+        // Reads are cancel safe: bytes of a frame still in flight stay
+        // buffered for the next call
+        timeout(timeout_duration, self.read_frame())
+            .await
+            .map_err(|_| {
+                warn!("Read timeout after {} seconds", timeout_secs);
+                Error::ReadTimeout
+            })?
+    }
+    
+    fn remote_addr(&self) -> String {
+        self.socket_addr
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|| format!("{}:{}", self.addr, self.port))
+    }
+}
+
+impl Drop for TcpTransport {
+    fn drop(&mut self) {
+        if self.is_connected() {
+            // Don't warn in drop - normal if error occurred
+            let _ = self.stream.take();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BufMut;
+    
+    #[test]
+    fn test_wrap_tcp_packet() {
+        let transport = TcpTransport::new("127.0.0.1", 4370);
+        let data = vec![0x01, 0x02, 0x03, 0x04];
+        let wrapped = transport.wrap_tcp_packet(&data);
+        
+        // Check magic
+        assert_eq!(wrapped[0], 0x50);
+        assert_eq!(wrapped[1], 0x50);
+        assert_eq!(wrapped[2], 0x72);
+        assert_eq!(wrapped[3], 0x82);
+        
+        // Check length
+        assert_eq!(u32::from_le_bytes([wrapped[4], wrapped[5], wrapped[6], wrapped[7]]), 4);
+        
+        // Check payload
+        assert_eq!(&wrapped[8..], &data[..]);
+    }
+    
+    fn wrapped(data: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u16_le(0x5050);
+        buf.put_u16_le(0x8272);
+        buf.put_u32_le(data.len() as u32);
+        buf.put_slice(data);
+        buf
+    }
+    
+    #[test]
+    fn test_next_frame() {
+        let mut transport = TcpTransport::new("127.0.0.1", 4370);
+        
+        // Two frames in one read, the second one incomplete
+        transport.buffer.extend_from_slice(&wrapped(&[0x01, 0x02, 0x03, 0x04]));
+        transport.buffer.extend_from_slice(&wrapped(&[0x05, 0x06, 0x07, 0x08])[..10]);
+        
+        let frame = transport.next_frame().unwrap().unwrap();
+        assert_eq!(frame.as_ref(), &[0x01, 0x02, 0x03, 0x04]);
+        assert!(transport.next_frame().unwrap().is_none());
+        
+        transport.buffer.extend_from_slice(&[0x07, 0x08]);
+        let frame = transport.next_frame().unwrap().unwrap();
+        assert_eq!(frame.as_ref(), &[0x05, 0x06, 0x07, 0x08]);
+        assert!(transport.buffer.is_empty());
+    }
+    
+    #[test]
+    fn test_next_frame_rejects_hostile_lengths() {
+        let mut transport = TcpTransport::new("127.0.0.1", 4370);
+        
+        // Absurd declared length
+        transport.buffer.put_u16_le(0x5050);
+        transport.buffer.put_u16_le(0x8272);
+        transport.buffer.put_u32_le(0xFFFF_FFFF);
+        transport.buffer.put_slice(&[0u8; 8]);
+        assert!(matches!(
+            transport.next_frame(),
+            Err(Error::FrameTooLarge { .. })
+        ));
+        assert!(transport.buffer.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_receive_reassembles_frames() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let large = wrapped(&[0xAB; 3000]);
+            
+            // A frame split over two writes, then two frames in one write
+            socket.write_all(&large[..1000]).await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            socket.write_all(&large[1000..]).await.unwrap();
+            
+            let mut both = wrapped(&[0x01; 16]);
+            both.extend_from_slice(&wrapped(&[0x02; 16]));
+            socket.write_all(&both).await.unwrap();
+            socket
+        });
+        
+        let mut transport = TcpTransport::new("127.0.0.1", port);
+        transport.connect().await.unwrap();
+        
+        assert_eq!(transport.receive(5).await.unwrap().as_ref(), &[0xAB; 3000][..]);
+        assert_eq!(transport.receive(5).await.unwrap().as_ref(), &[0x01; 16]);
+        assert_eq!(transport.receive(5).await.unwrap().as_ref(), &[0x02; 16]);
+        
+        drop(server.await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_tcp_transport_create() {
+        let transport = TcpTransport::new("192.168.1.201", 4370);
+        assert!(!transport.is_connected());
+        assert!(transport.use_tcp_wrapper);
+    }
+    
+    #[tokio::test]
+    async fn test_tcp_transport_invalid_address() {
+        let mut transport = TcpTransport::new("invalid..address", 4370)
+            .with_connect_timeout(Duration::from_millis(100));
+        
+        let result = transport.connect().await;
+        assert!(result.is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+//! Record parsing benchmarks
+//!
+//! Run with `cargo bench -p zkrust-types`.
+
+use std::hint::black_box;
+
+use chrono::NaiveDate;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use zkrust_core::constants::{PunchType, VerifyMode};
+use zkrust_types::records::{self, AttendanceLayout};
+use zkrust_types::user::{self, UserRecordLayout};
+use zkrust_types::{AttendanceRecord, User};
+
+/// Records per parsed table, a well-used terminal
+const RECORDS: usize = 10_000;
+
+/// Users per parsed table
+const USERS: usize = 1_000;
+
+fn attendance_table(layout: AttendanceLayout) -> Vec<u8> {
+    let start = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+    (0..RECORDS)
+        .flat_map(|i| {
+            let uid = (i % 500 + 1) as u16;
+            let timestamp = start + chrono::Duration::minutes(i as i64);
+            let record = AttendanceRecord::new(uid.to_string(), timestamp, VerifyMode::Fingerprint, PunchType::CheckIn);
+            layout.encode(uid, &record).unwrap()
+        })
+        .collect()
+}
+
+fn user_table(layout: UserRecordLayout) -> Vec<u8> {
+    (0..USERS)
+        .flat_map(|i| {
+            let user = User::builder(i as u16 + 1, (i + 1).to_string()).name("Employee").build().unwrap();
+            layout.encode(&user).unwrap()
+        })
+        .collect()
+}
+
+fn bench_attendance(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_attendance");
+    for layout in AttendanceLayout::ALL {
+        let data = attendance_table(layout);
+        group.throughput(Throughput::Elements(RECORDS as u64));
+        group.bench_function(format!("{:?}", layout), |b| {
+            b.iter(|| records::parse_attendance(black_box(&data), layout).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_users(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_users");
+    for layout in [UserRecordLayout::Compact, UserRecordLayout::Extended] {
+        let data = user_table(layout);
+        group.throughput(Throughput::Elements(USERS as u64));
+        group.bench_function(format!("{:?}", layout), |b| {
+            b.iter(|| user::parse_users(black_box(&data), layout).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_attendance, bench_users);
+criterion_main!(benches);
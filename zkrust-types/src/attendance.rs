@@ -0,0 +1,133 @@
+//! Attendance records
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone};
+use zkrust_core::constants::{PunchType, VerifyMode};
+
+use crate::time::Timestamp;
+
+/// One attendance log entry
+///
+/// Records order chronologically; ties are broken by user ID, then the
+/// remaining fields, so sorting is deterministic.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttendanceRecord {
+    /// User ID as shown on the device
+    pub user_id: String,
+
+    /// Device local time of the punch
+    pub timestamp: NaiveDateTime,
+
+    /// How the user was verified
+    pub verify_mode: VerifyMode,
+
+    /// Attendance state selected on the device
+    pub punch: PunchType,
+
+    /// Work code, if one was entered
+    pub work_code: Option<u32>,
+}
+
+impl AttendanceRecord {
+    /// Create a record without a work code
+    pub fn new(
+        user_id: impl Into<String>,
+        timestamp: NaiveDateTime,
+        verify_mode: VerifyMode,
+        punch: PunchType,
+    ) -> Self {
+        Self {
+            user_id: user_id.into(),
+            timestamp,
+            verify_mode,
+            punch,
+            work_code: None,
+        }
+    }
+
+    /// Set the work code
+    pub fn with_work_code(mut self, work_code: u32) -> Self {
+        self.work_code = Some(work_code);
+        self
+    }
+
+    /// Timestamp in the device's timezone `tz`
+    ///
+    /// See [`time::localize`](crate::time::localize) for how daylight saving
+    /// transitions are handled.
+    pub fn localized<Tz: TimeZone>(&self, tz: &Tz) -> DateTime<Tz> {
+        crate::time::localize(self.timestamp, tz)
+    }
+}
+
+impl Ord for AttendanceRecord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.timestamp
+            .cmp(&other.timestamp)
+            .then_with(|| self.user_id.cmp(&other.user_id))
+            .then_with(|| self.punch.cmp(&other.punch))
+            .then_with(|| self.verify_mode.cmp(&other.verify_mode))
+            .then_with(|| self.work_code.cmp(&other.work_code))
+    }
+}
+
+impl PartialOrd for AttendanceRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for AttendanceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} ({})",
+            Timestamp(self.timestamp), self.user_id, self.punch, self.verify_mode
+        )?;
+
+        if let Some(work_code) = self.work_code {
+            write!(f, " work code {}", work_code)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_records_sort_chronologically() {
+        let mut records = [
+            AttendanceRecord::new("2", at(17, 0), VerifyMode::Fingerprint, PunchType::CheckOut),
+            AttendanceRecord::new("10", at(8, 0), VerifyMode::Card, PunchType::CheckIn),
+            AttendanceRecord::new("1", at(8, 0), VerifyMode::Face, PunchType::CheckIn),
+        ];
+        records.sort();
+
+        let order: Vec<_> = records.iter().map(|r| r.user_id.as_str()).collect();
+        assert_eq!(order, ["1", "10", "2"]);
+    }
+
+    #[test]
+    fn test_record_equality_includes_work_code() {
+        let record = AttendanceRecord::new("7", at(9, 30), VerifyMode::Password, PunchType::BreakIn);
+        let coded = record.clone().with_work_code(3);
+
+        assert_ne!(record, coded);
+        assert_eq!(coded.work_code, Some(3));
+        assert!(coded.to_string().contains("work code 3"));
+    }
+}
@@ -0,0 +1,204 @@
+//! Device storage capacity
+
+use std::fmt;
+
+use crate::error::{Error, Result};
+
+/// Used and total storage reported by CMD_GET_FREE_SIZES
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceCapacity {
+    /// Enrolled users
+    pub users: u32,
+
+    /// User capacity
+    pub users_capacity: u32,
+
+    /// Stored fingerprint templates
+    pub fingers: u32,
+
+    /// Fingerprint template capacity
+    pub fingers_capacity: u32,
+
+    /// Stored attendance records
+    pub records: u32,
+
+    /// Attendance record capacity
+    pub records_capacity: u32,
+
+    /// Enrolled cards
+    pub cards: u32,
+
+    /// Stored face templates (0 on devices without face support)
+    pub faces: u32,
+
+    /// Face template capacity (0 on devices without face support)
+    pub faces_capacity: u32,
+}
+
+impl DeviceCapacity {
+    /// Minimum payload size: 20 little-endian `u32` counters
+    pub const MIN_SIZE: usize = 80;
+
+    /// Parse the CMD_GET_FREE_SIZES payload
+    ///
+    /// Face counters follow the first 80 bytes on face-capable firmware and
+    /// are left at zero when absent.
+    pub fn parse(payload: &[u8]) -> Result<Self> {
+        if payload.len() < Self::MIN_SIZE {
+            return Err(Error::Parse(format!(
+                "Free sizes payload too short: {} bytes (need {})",
+                payload.len(),
+                Self::MIN_SIZE
+            )));
+        }
+
+        let field = |index: usize| {
+            let offset = index * 4;
+            u32::from_le_bytes([
+                payload[offset],
+                payload[offset + 1],
+                payload[offset + 2],
+                payload[offset + 3],
+            ])
+        };
+
+        let mut capacity = Self {
+            users: field(4),
+            fingers: field(6),
+            records: field(8),
+            cards: field(12),
+            fingers_capacity: field(14),
+            users_capacity: field(15),
+            records_capacity: field(16),
+            ..Self::default()
+        };
+
+        if payload.len() >= Self::MIN_SIZE + 12 {
+            capacity.faces = field(20);
+            capacity.faces_capacity = field(22);
+        }
+
+        Ok(capacity)
+    }
+
+    /// Encode as a CMD_GET_FREE_SIZES payload, including the face counters
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; Self::MIN_SIZE + 12];
+        for (index, value) in [
+            (4, self.users),
+            (6, self.fingers),
+            (8, self.records),
+            (12, self.cards),
+            (14, self.fingers_capacity),
+            (15, self.users_capacity),
+            (16, self.records_capacity),
+            (20, self.faces),
+            (22, self.faces_capacity),
+        ] {
+            buf[index * 4..index * 4 + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Fraction of user slots in use (0.0-1.0), `None` if capacity is unknown
+    pub fn users_usage(&self) -> Option<f64> {
+        usage(self.users, self.users_capacity)
+    }
+
+    /// Fraction of fingerprint slots in use (0.0-1.0)
+    pub fn fingers_usage(&self) -> Option<f64> {
+        usage(self.fingers, self.fingers_capacity)
+    }
+
+    /// Fraction of attendance record slots in use (0.0-1.0)
+    pub fn records_usage(&self) -> Option<f64> {
+        usage(self.records, self.records_capacity)
+    }
+
+    /// Fraction of face template slots in use (0.0-1.0)
+    pub fn faces_usage(&self) -> Option<f64> {
+        usage(self.faces, self.faces_capacity)
+    }
+}
+
+fn usage(used: u32, capacity: u32) -> Option<f64> {
+    (capacity > 0).then(|| used as f64 / capacity as f64)
+}
+
+impl fmt::Display for DeviceCapacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Users: {}/{}, Fingers: {}/{}, Records: {}/{}",
+            self.users,
+            self.users_capacity,
+            self.fingers,
+            self.fingers_capacity,
+            self.records,
+            self.records_capacity
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(fields: &[(usize, u32)], len: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len];
+        for &(index, value) in fields {
+            buf[index * 4..index * 4 + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    fn test_parse_capacity() {
+        let data = payload(
+            &[(4, 10), (6, 20), (8, 300), (12, 5), (14, 3000), (15, 1000), (16, 100000)],
+            80,
+        );
+
+        let capacity = DeviceCapacity::parse(&data).unwrap();
+        assert_eq!(capacity.users, 10);
+        assert_eq!(capacity.users_capacity, 1000);
+        assert_eq!(capacity.fingers, 20);
+        assert_eq!(capacity.records_capacity, 100000);
+        assert_eq!(capacity.cards, 5);
+        assert_eq!(capacity.faces_capacity, 0);
+        assert_eq!(capacity.users_usage(), Some(0.01));
+        assert_eq!(capacity.faces_usage(), None);
+    }
+
+    #[test]
+    fn test_parse_capacity_with_faces() {
+        let data = payload(&[(20, 7), (22, 400)], 92);
+
+        let capacity = DeviceCapacity::parse(&data).unwrap();
+        assert_eq!(capacity.faces, 7);
+        assert_eq!(capacity.faces_capacity, 400);
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let capacity = DeviceCapacity {
+            users: 3,
+            users_capacity: 1000,
+            fingers: 4,
+            fingers_capacity: 3000,
+            records: 12,
+            records_capacity: 100000,
+            cards: 1,
+            faces: 2,
+            faces_capacity: 400,
+        };
+
+        assert_eq!(DeviceCapacity::parse(&capacity.encode()).unwrap(), capacity);
+    }
+
+    #[test]
+    fn test_parse_capacity_too_short() {
+        assert!(matches!(DeviceCapacity::parse(&[0; 40]), Err(Error::Parse(_))));
+    }
+}
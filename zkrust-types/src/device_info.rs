@@ -1,48 +1,88 @@
-//! Device information structures
-
-use std::fmt;
-
-/// Device information
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct DeviceInfo {
-    /// Device serial number
-    pub serial_number: String,
-    
-    /// Firmware version
-    pub firmware_version: String,
-    
-    /// Device model
-    pub model: Option<String>,
-    
-    /// Platform name
-    pub platform: Option<String>,
-    
-    /// Device name (user-assigned)
-    pub device_name: Option<String>,
-    
-    /// MAC address
-    pub mac_address: Option<String>,
-}
-
-impl DeviceInfo {
-    pub fn new(serial_number: String, firmware_version: String) -> Self {
-        Self {
-            serial_number,
-            firmware_version,
-            model: None,
-            platform: None,
-            device_name: None,
-            mac_address: None,
-        }
-    }
-}
-
-impl fmt::Display for DeviceInfo {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Device[SN: {}, FW: {}]",
-            self.serial_number, self.firmware_version
-        )
-    }
+//! Device information structures
+
+use std::fmt;
+
+use crate::error::Result;
+use crate::firmware::FirmwareVersion;
+
+/// Device information
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceInfo {
+    /// Device serial number
+    pub serial_number: String,
+    
+    /// Firmware version
+    pub firmware_version: String,
+    
+    /// Device model
+    pub model: Option<String>,
+    
+    /// Platform name
+    pub platform: Option<String>,
+    
+    /// Device name (user-assigned)
+    pub device_name: Option<String>,
+    
+    /// MAC address
+    pub mac_address: Option<String>,
+}
+
+impl DeviceInfo {
+    pub fn new(serial_number: String, firmware_version: String) -> Self {
+        Self {
+            serial_number,
+            firmware_version,
+            model: None,
+            platform: None,
+            device_name: None,
+            mac_address: None,
+        }
+    }
+    
+    /// Parse the firmware version string
+    pub fn firmware(&self) -> Result<FirmwareVersion> {
+        self.firmware_version.parse()
+    }
+}
+
+/// Algorithm and platform versions reported through device options
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlatformInfo {
+    /// Fingerprint algorithm version (`~ZKFPVersion`), e.g. 10 for ZKFinger VX10.0
+    pub fingerprint_algorithm: u8,
+    
+    /// Face algorithm version (`ZKFaceVersion`), `None` without face support
+    pub face_algorithm: Option<u8>,
+    
+    /// Platform name (`~Platform`), e.g. `ZMM220_TFT`
+    pub platform: Option<String>,
+    
+    /// Manufacturer (`~OEMVendor`)
+    pub vendor: Option<String>,
+    
+    /// Operating system (`~OS`)
+    pub os: Option<String>,
+}
+
+impl fmt::Display for PlatformInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let platform = self.platform.as_deref().unwrap_or("unknown");
+        write!(f, "Platform[{}, FP: {}", platform, self.fingerprint_algorithm)?;
+        if let Some(face) = self.face_algorithm {
+            write!(f, ", Face: {}", face)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Device[SN: {}, FW: {}]",
+            self.serial_number, self.firmware_version
+        )
+    }
 }
\ No newline at end of file
@@ -0,0 +1,110 @@
+//! Firmware version parsing
+//!
+//! Devices report their firmware as e.g. `"Ver 6.60 Apr 28 2017"` or
+//! `"Ver 8.0.4.2-20191101"`. [`FirmwareVersion`] keeps the numeric part so
+//! versions can be compared.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+/// Comparable firmware version (`major.minor.patch`)
+///
+/// Components beyond the third and any build suffix are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FirmwareVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl FirmwareVersion {
+    /// Create a version
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self { major, minor, patch }
+    }
+
+    /// Check if this version is at least `other`
+    pub fn at_least(&self, other: FirmwareVersion) -> bool {
+        *self >= other
+    }
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trimmed = s.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        let rest = trimmed
+            .strip_prefix("Ver")
+            .or_else(|| trimmed.strip_prefix("ver"))
+            .unwrap_or(trimmed)
+            .trim_start();
+
+        let number: String = rest
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == '.')
+            .collect();
+
+        let mut parts = number.split('.').filter(|p| !p.is_empty());
+        let mut component = || -> Result<Option<u16>> {
+            parts
+                .next()
+                .map(|p| p.parse().map_err(|_| invalid(s)))
+                .transpose()
+        };
+
+        let major = component()?.ok_or_else(|| invalid(s))?;
+
+        Ok(Self {
+            major,
+            minor: component()?.unwrap_or(0),
+            patch: component()?.unwrap_or(0),
+        })
+    }
+}
+
+fn invalid(s: &str) -> Error {
+    Error::Parse(format!("Invalid firmware version: {:?}", s))
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_firmware() {
+        let v: FirmwareVersion = "Ver 6.60 Apr 28 2017".parse().unwrap();
+        assert_eq!(v, FirmwareVersion::new(6, 60, 0));
+
+        let v: FirmwareVersion = "Ver 8.0.4.2-20191101\0".parse().unwrap();
+        assert_eq!(v, FirmwareVersion::new(8, 0, 4));
+
+        let v: FirmwareVersion = "6.4.1".parse().unwrap();
+        assert_eq!(v, FirmwareVersion::new(6, 4, 1));
+    }
+
+    #[test]
+    fn test_parse_firmware_invalid() {
+        assert!("Ver abc".parse::<FirmwareVersion>().is_err());
+        assert!("".parse::<FirmwareVersion>().is_err());
+        assert!("Ver 99999.1".parse::<FirmwareVersion>().is_err());
+    }
+
+    #[test]
+    fn test_firmware_ordering() {
+        let old = FirmwareVersion::new(6, 60, 0);
+        let new = FirmwareVersion::new(8, 0, 4);
+        assert!(new > old);
+        assert!(new.at_least(FirmwareVersion::new(8, 0, 0)));
+        assert!(!old.at_least(FirmwareVersion::new(8, 0, 0)));
+    }
+}
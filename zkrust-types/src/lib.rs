@@ -1,7 +1,33 @@
 //! Type definitions for zkrust
 
+pub mod attendance;
+pub mod capacity;
 pub mod device_info;
 pub mod error;
+pub mod firmware;
+pub mod live;
+pub mod options;
+pub mod photo;
+pub mod records;
+pub mod states;
+pub mod template;
+pub mod text;
+pub mod time;
+pub mod user;
 
-pub use device_info::DeviceInfo;
-pub use error::{Error, Result};
\ No newline at end of file
+#[cfg(test)]
+mod vectors;
+
+pub use attendance::AttendanceRecord;
+pub use capacity::DeviceCapacity;
+pub use device_info::{DeviceInfo, PlatformInfo};
+pub use error::{Error, Result};
+pub use firmware::FirmwareVersion;
+pub use live::{LiveEvent, LiveEventKind};
+pub use options::DeviceOptions;
+pub use photo::{AttendancePhoto, PhotoName, PunchPhoto};
+pub use records::AttendanceLayout;
+pub use states::StateTable;
+pub use template::{FaceTemplate, FingerprintTemplate, TemplateIssue, TemplateSummary};
+pub use text::StringCodec;
+pub use user::{User, UserBuilder, UserRecordLayout};
\ No newline at end of file
@@ -0,0 +1,167 @@
+//! Live events
+//!
+//! Attendance terminals and access panels report what happens at the door
+//! in different shapes: terminals as attendance records, C3/inBio panels as
+//! real-time log entries with event codes. [`LiveEvent`] is the common
+//! form, so one consumer can follow both device families.
+
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use zkrust_core::constants::{PunchType, VerifyMode};
+
+use crate::attendance::AttendanceRecord;
+use crate::time::Timestamp;
+
+/// Something that just happened on a device
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LiveEvent {
+    /// Device that reported the event (serial number or configured ID)
+    pub source: String,
+
+    /// Device local time
+    pub timestamp: NaiveDateTime,
+
+    /// User ID, if the user was identified
+    pub user_id: Option<String>,
+
+    pub kind: LiveEventKind,
+}
+
+/// What happened
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LiveEventKind {
+    /// Attendance punch on a terminal
+    Punch {
+        verify_mode: VerifyMode,
+        punch: PunchType,
+    },
+
+    /// Door unlocked for a card, fingerprint or password
+    AccessGranted {
+        door: u8,
+        card: Option<u32>,
+    },
+
+    /// Access attempt refused
+    AccessDenied {
+        door: u8,
+        card: Option<u32>,
+        reason: String,
+    },
+
+    /// Door sensor reports open
+    DoorOpened { door: u8 },
+
+    /// Door sensor reports closed
+    DoorClosed { door: u8 },
+
+    /// Door left open longer than allowed
+    DoorHeldOpen { door: u8 },
+
+    /// Door opened without a grant
+    DoorForcedOpen { door: u8 },
+
+    /// Device-specific event without a common form
+    Other {
+        code: u32,
+        description: String,
+    },
+}
+
+impl LiveEvent {
+    /// Event of an attendance punch reported by `source`
+    pub fn attendance(source: impl Into<String>, record: AttendanceRecord) -> Self {
+        Self {
+            source: source.into(),
+            timestamp: record.timestamp,
+            user_id: Some(record.user_id),
+            kind: LiveEventKind::Punch {
+                verify_mode: record.verify_mode,
+                punch: record.punch,
+            },
+        }
+    }
+
+    /// Door the event concerns, if any
+    pub fn door(&self) -> Option<u8> {
+        match &self.kind {
+            LiveEventKind::AccessGranted { door, .. }
+            | LiveEventKind::AccessDenied { door, .. }
+            | LiveEventKind::DoorOpened { door }
+            | LiveEventKind::DoorClosed { door }
+            | LiveEventKind::DoorHeldOpen { door }
+            | LiveEventKind::DoorForcedOpen { door } => Some(*door),
+            LiveEventKind::Punch { .. } | LiveEventKind::Other { .. } => None,
+        }
+    }
+
+    /// Whether the event needs attention (held or forced door)
+    pub fn is_alarm(&self) -> bool {
+        matches!(
+            self.kind,
+            LiveEventKind::DoorHeldOpen { .. } | LiveEventKind::DoorForcedOpen { .. }
+        )
+    }
+}
+
+impl fmt::Display for LiveEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}] ", Timestamp(self.timestamp), self.source)?;
+
+        match &self.kind {
+            LiveEventKind::Punch { verify_mode, punch } => write!(f, "punch {} ({})", punch, verify_mode)?,
+            LiveEventKind::AccessGranted { door, .. } => write!(f, "access granted at door {}", door)?,
+            LiveEventKind::AccessDenied { door, reason, .. } => {
+                write!(f, "access denied at door {}: {}", door, reason)?
+            }
+            LiveEventKind::DoorOpened { door } => write!(f, "door {} opened", door)?,
+            LiveEventKind::DoorClosed { door } => write!(f, "door {} closed", door)?,
+            LiveEventKind::DoorHeldOpen { door } => write!(f, "door {} held open", door)?,
+            LiveEventKind::DoorForcedOpen { door } => write!(f, "door {} forced open", door)?,
+            LiveEventKind::Other { code, description } => write!(f, "{} ({})", description, code)?,
+        }
+
+        if let Some(user_id) = &self.user_id {
+            write!(f, " by {}", user_id)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_attendance_event() {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 30, 0).unwrap();
+        let record = AttendanceRecord::new("1001", timestamp, VerifyMode::Fingerprint, PunchType::CheckIn);
+        let event = LiveEvent::attendance("CEXJ201260001", record);
+
+        assert_eq!(event.user_id.as_deref(), Some("1001"));
+        assert_eq!(event.door(), None);
+        assert!(!event.is_alarm());
+        assert!(event.to_string().starts_with("2024-03-01 08:30:00 [CEXJ201260001] punch "), "{}", event);
+        assert!(event.to_string().ends_with(" by 1001"));
+    }
+
+    #[test]
+    fn test_door_event() {
+        let event = LiveEvent {
+            source: "panel".into(),
+            timestamp: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(22, 0, 0).unwrap(),
+            user_id: None,
+            kind: LiveEventKind::DoorForcedOpen { door: 2 },
+        };
+
+        assert_eq!(event.door(), Some(2));
+        assert!(event.is_alarm());
+        assert_eq!(event.to_string(), "2024-03-01 22:00:00 [panel] door 2 forced open");
+    }
+}
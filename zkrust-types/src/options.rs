@@ -0,0 +1,169 @@
+//! Device option payloads
+//!
+//! CMD_OPTIONS_RRQ replies with `name=value` pairs, NUL-terminated and
+//! sometimes several per payload (`~Platform=ZMM220_TFT\0~ZKFPVersion=10\0`).
+//! Built-in read-only options start with `~`.
+
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::text::{StringCodec, Utf8};
+
+/// Parsed `name=value` options, in payload order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceOptions {
+    entries: Vec<(String, String)>,
+}
+
+impl DeviceOptions {
+    /// Parse an option payload
+    ///
+    /// Entries are separated by NUL, CR or LF; empty entries are skipped.
+    /// Values keep everything after the first `=`, so they may contain `=`.
+    pub fn parse(payload: &[u8]) -> Result<Self> {
+        Self::parse_with(payload, &Utf8)
+    }
+
+    /// Parse an option payload, decoding it with `codec`
+    pub fn parse_with(payload: &[u8], codec: &dyn StringCodec) -> Result<Self> {
+        let text = codec.decode(payload);
+        let mut entries = Vec::new();
+
+        for entry in text.split(['\0', '\r', '\n']) {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            let (name, value) = entry
+                .split_once('=')
+                .ok_or_else(|| Error::Parse(format!("Option entry without '=': {:?}", entry)))?;
+
+            let name = name.trim();
+            if name.is_empty() || name == "~" {
+                return Err(Error::Parse(format!("Option entry without name: {:?}", entry)));
+            }
+
+            entries.push((name.to_string(), value.trim().to_string()));
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Get an option value
+    ///
+    /// `name` matches with or without the leading `~`. Empty values count
+    /// as unset.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let wanted = name.trim_start_matches('~');
+        self.entries
+            .iter()
+            .find(|(key, _)| key.trim_start_matches('~') == wanted)
+            .map(|(_, value)| value.as_str())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Get an option parsed as `T`
+    pub fn get_parsed<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>> {
+        self.get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| Error::Parse(format!("Option {} has invalid value {:?}", name, value)))
+            })
+            .transpose()
+    }
+
+    /// Iterate over `(name, value)` pairs
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if no entries were parsed
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl fmt::Display for DeviceOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.entries {
+            writeln!(f, "{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encode a CMD_OPTIONS_RRQ request for `name`
+pub fn encode_option_request(name: &str) -> Vec<u8> {
+    let mut payload = name.as_bytes().to_vec();
+    payload.push(0);
+    payload
+}
+
+/// Encode a CMD_OPTIONS_WRQ payload setting `name` to `value` in UTF-8
+pub fn encode_option_write(name: &str, value: &str) -> Result<Vec<u8>> {
+    encode_option_write_with(name, value, &Utf8)
+}
+
+/// Encode a CMD_OPTIONS_WRQ payload, writing `value` with `codec`
+pub fn encode_option_write_with(name: &str, value: &str, codec: &dyn StringCodec) -> Result<Vec<u8>> {
+    if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+        return Err(Error::Validation(format!("Invalid option {:?}={:?}", name, value)));
+    }
+
+    let mut payload = format!("{}=", name).into_bytes();
+    payload.extend(codec.encode(value)?);
+    payload.push(0);
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_single_option() {
+        let options = DeviceOptions::parse(b"~Platform=ZMM220_TFT\0").unwrap();
+
+        assert_eq!(options.len(), 1);
+        assert_eq!(options.get("~Platform"), Some("ZMM220_TFT"));
+        assert_eq!(options.get("Platform"), Some("ZMM220_TFT"));
+    }
+
+    #[test]
+    fn test_parse_multiple_options() {
+        let payload = b"~SerialNumber=ABC123\0~ZKFPVersion=10\0MAC=00:17:61:10:50:1a\r\nIPAddress=192.168.1.201\0\0";
+        let options = DeviceOptions::parse(payload).unwrap();
+
+        assert_eq!(options.len(), 4);
+        assert_eq!(options.get("SerialNumber"), Some("ABC123"));
+        assert_eq!(options.get_parsed::<u8>("~ZKFPVersion").unwrap(), Some(10));
+        assert_eq!(options.get("MAC"), Some("00:17:61:10:50:1a"));
+        assert_eq!(options.get("Missing"), None);
+    }
+
+    #[test]
+    fn test_parse_edge_cases() {
+        let options = DeviceOptions::parse(b"Key=a=b\0Empty=\0").unwrap();
+        assert_eq!(options.get("Key"), Some("a=b"));
+        assert_eq!(options.get("Empty"), None);
+
+        assert!(DeviceOptions::parse(b"").unwrap().is_empty());
+        assert!(DeviceOptions::parse(b"garbage\0").is_err());
+        assert!(DeviceOptions::parse(b"=value\0").is_err());
+        assert!(options.get_parsed::<u8>("Key").is_err());
+    }
+
+    #[test]
+    fn test_encode_options() {
+        assert_eq!(encode_option_request("~Platform"), b"~Platform\0");
+        assert_eq!(encode_option_write("DeviceID", "1").unwrap(), b"DeviceID=1\0");
+        assert!(encode_option_write("a=b", "1").is_err());
+    }
+}
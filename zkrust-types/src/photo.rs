@@ -0,0 +1,200 @@
+//! Attendance photos
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::attendance::AttendanceRecord;
+use crate::error::{Error, Result};
+
+const NAME_TIME_FORMAT: &str = "%Y%m%d%H%M%S";
+
+/// How far apart a punch and its photo may be
+///
+/// The camera saves the photo after the verification finishes, so its name
+/// can be a second or two later than the attendance record.
+pub const PAIRING_TOLERANCE: TimeDelta = TimeDelta::seconds(3);
+
+/// Photo listed by name, before its image is downloaded
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhotoName {
+    /// File name on the device
+    pub name: String,
+
+    /// User ID, if the user was identified
+    pub user_id: Option<String>,
+
+    /// Device local time of the punch
+    pub timestamp: NaiveDateTime,
+}
+
+impl PhotoName {
+    /// Parse a name like `20240301083005-1001.jpg`
+    pub fn parse(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        let invalid = || Error::Parse(format!("Invalid photo name: {:?}", name));
+
+        let stem = name.rsplit_once('.').map_or(name.as_str(), |(stem, _)| stem);
+        let (time, user_id) = match stem.split_once('-') {
+            Some((time, user_id)) if !user_id.is_empty() => (time, Some(user_id.to_string())),
+            Some(_) => return Err(invalid()),
+            None => (stem, None),
+        };
+        let timestamp = NaiveDateTime::parse_from_str(time, NAME_TIME_FORMAT).map_err(|_| invalid())?;
+
+        Ok(Self { name, user_id, timestamp })
+    }
+}
+
+impl fmt::Display for PhotoName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// Photo taken by the device camera at a punch
+///
+/// Devices name photos after the punch time and, when the user was
+/// identified, their user ID: `20240301083005-1001.jpg`. Photos of failed
+/// verifications carry the time only.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttendancePhoto {
+    /// File name on the device
+    pub name: String,
+
+    /// User ID, if the user was identified
+    pub user_id: Option<String>,
+
+    /// Device local time of the punch
+    pub timestamp: NaiveDateTime,
+
+    /// Image bytes (JPEG)
+    pub data: Vec<u8>,
+}
+
+impl AttendancePhoto {
+    /// Create a photo, taking the time and user ID from its name
+    pub fn new(name: impl Into<String>, data: impl Into<Vec<u8>>) -> Result<Self> {
+        let PhotoName { name, user_id, timestamp } = PhotoName::parse(name)?;
+
+        Ok(Self {
+            name,
+            user_id,
+            timestamp,
+            data: data.into(),
+        })
+    }
+
+    /// Image size in bytes
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl fmt::Display for AttendancePhoto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Photo[{}, {} bytes]", self.name, self.size())
+    }
+}
+
+/// Attendance record with the photo taken at that punch, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PunchPhoto {
+    pub record: AttendanceRecord,
+    pub photo: Option<PhotoName>,
+}
+
+/// Pair each record with the photo of the same user closest in time,
+/// within `tolerance`
+///
+/// Every photo is used at most once. Photos without a user ID come from
+/// failed verifications, which leave no record, so they are never paired.
+pub fn pair_photos(records: Vec<AttendanceRecord>, photos: &[PhotoName], tolerance: TimeDelta) -> Vec<PunchPhoto> {
+    let mut by_user: HashMap<&str, Vec<&PhotoName>> = HashMap::new();
+    for photo in photos {
+        if let Some(user_id) = &photo.user_id {
+            by_user.entry(user_id).or_default().push(photo);
+        }
+    }
+
+    records
+        .into_iter()
+        .map(|record| {
+            let photo = by_user.get_mut(record.user_id.as_str()).and_then(|candidates| {
+                let distance = |photo: &PhotoName| (photo.timestamp - record.timestamp).abs();
+                let (index, _) = candidates
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, photo)| distance(photo) <= tolerance)
+                    .min_by_key(|(_, photo)| distance(photo))?;
+                Some(candidates.swap_remove(index).clone())
+            });
+            PunchPhoto { record, photo }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_photo_name() {
+        let photo = AttendancePhoto::new("20240301083005-1001.jpg", vec![0xFF, 0xD8]).unwrap();
+        assert_eq!(photo.user_id.as_deref(), Some("1001"));
+        assert_eq!(
+            photo.timestamp,
+            NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 30, 5).unwrap()
+        );
+        assert_eq!(photo.to_string(), "Photo[20240301083005-1001.jpg, 2 bytes]");
+
+        let photo = AttendancePhoto::new("20240301083005.jpg", vec![]).unwrap();
+        assert_eq!(photo.user_id, None);
+    }
+
+    #[test]
+    fn test_invalid_photo_name() {
+        for name in ["", "photo.jpg", "20241301083005-1.jpg", "20240301083005-.jpg"] {
+            assert!(matches!(AttendancePhoto::new(name, vec![]), Err(Error::Parse(_))), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_pair_photos() {
+        use zkrust_core::constants::{PunchType, VerifyMode};
+
+        let at = |h, m, s| NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(h, m, s).unwrap();
+        let record = |user_id: &str, time| AttendanceRecord::new(user_id, time, VerifyMode::Face, PunchType::CheckIn);
+        let photos: Vec<_> = [
+            "20240301083002-1001.jpg",
+            "20240301083007-1001.jpg",
+            "20240301090000.jpg",
+            "20240301120000-1002.jpg",
+        ]
+        .into_iter()
+        .map(|name| PhotoName::parse(name).unwrap())
+        .collect();
+
+        let records = vec![
+            record("1001", at(8, 30, 5)),
+            record("1001", at(8, 30, 4)),
+            record("1001", at(8, 30, 9)),
+            record("1002", at(9, 0, 0)),
+        ];
+        let pairs = pair_photos(records, &photos, PAIRING_TOLERANCE);
+        let names: Vec<_> = pairs.iter().map(|pair| pair.photo.as_ref().map(|p| p.name.as_str())).collect();
+
+        // Closest photo first, each photo once, never across users
+        assert_eq!(
+            names,
+            [Some("20240301083007-1001.jpg"), Some("20240301083002-1001.jpg"), None, None]
+        );
+        assert_eq!(pairs[3].record.user_id, "1002");
+    }
+}
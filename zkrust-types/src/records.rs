@@ -0,0 +1,396 @@
+//! Binary attendance record parsing
+//!
+//! Attendance logs come back from CMD_ATTLOG_RRQ as packed records whose
+//! size depends on the firmware generation:
+//!
+//! | Layout       | Size | Fields                                                   |
+//! |--------------|------|----------------------------------------------------------|
+//! | [`Legacy`]   | 8    | uid u16, verify u8, time u32, punch u8                    |
+//! | [`Standard`] | 16   | user_id u32, time u32, verify u8, punch u8, 2 reserved, work code u32 |
+//! | [`Extended`] | 40   | uid u16, user_id \[u8; 24\], verify u8, time u32, punch u8, 8 reserved |
+//!
+//! All integers are little-endian; times use the packed format from
+//! [`crate::time`].
+//!
+//! [`Legacy`]: AttendanceLayout::Legacy
+//! [`Standard`]: AttendanceLayout::Standard
+//! [`Extended`]: AttendanceLayout::Extended
+
+use zkrust_core::constants::{PunchType, VerifyMode};
+
+use crate::attendance::AttendanceRecord;
+use crate::error::{Error, Result};
+use crate::time::{decode_time, encode_time};
+
+/// Attendance record layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AttendanceLayout {
+    /// 8-byte records keyed by internal uid (oldest firmware)
+    Legacy,
+
+    /// 16-byte records with numeric user ID and work code
+    Standard,
+
+    /// 40-byte records with string user ID (TFT and newer firmware)
+    Extended,
+}
+
+impl AttendanceLayout {
+    /// Layouts from largest to smallest record
+    pub const ALL: [Self; 3] = [Self::Extended, Self::Standard, Self::Legacy];
+
+    /// Size of one record in bytes
+    pub const fn record_size(self) -> usize {
+        match self {
+            Self::Legacy => 8,
+            Self::Standard => 16,
+            Self::Extended => 40,
+        }
+    }
+
+    /// Detect the layout from the payload size and record count
+    ///
+    /// The count is available from
+    /// [`DeviceCapacity::records`](crate::DeviceCapacity::records).
+    pub fn detect(payload_len: usize, record_count: usize) -> Option<Self> {
+        if record_count == 0 || payload_len % record_count != 0 {
+            return None;
+        }
+
+        let size = payload_len / record_count;
+        Self::ALL.into_iter().find(|layout| layout.record_size() == size)
+    }
+
+    /// Decode one record
+    ///
+    /// `record` must be exactly [`record_size`](Self::record_size) bytes.
+    /// Legacy records carry no user ID; the internal uid is used instead.
+    pub fn decode(self, record: &[u8]) -> Result<AttendanceRecord> {
+        if record.len() != self.record_size() {
+            return Err(Error::Parse(format!(
+                "{:?} attendance record must be {} bytes, got {}",
+                self,
+                self.record_size(),
+                record.len()
+            )));
+        }
+
+        let u16_at = |i: usize| u16::from_le_bytes([record[i], record[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
+
+        let (user_id, verify, time, punch, work_code) = match self {
+            Self::Legacy => (u16_at(0).to_string(), record[2], u32_at(3), record[7], None),
+            Self::Standard => {
+                let work_code = Some(u32_at(12)).filter(|code| *code != 0);
+                (u32_at(0).to_string(), record[8], u32_at(4), record[9], work_code)
+            }
+            Self::Extended => (c_string(&record[2..26])?, record[26], u32_at(27), record[31], None),
+        };
+
+        let verify_mode = VerifyMode::try_from(verify).map_err(|e| Error::Parse(e.to_string()))?;
+        let punch = PunchType::try_from(punch).map_err(|e| Error::Parse(e.to_string()))?;
+
+        Ok(AttendanceRecord {
+            user_id,
+            timestamp: decode_time(time)?,
+            verify_mode,
+            punch,
+            work_code,
+        })
+    }
+
+    /// Encode one record, the inverse of [`decode`](Self::decode)
+    ///
+    /// `uid` is the owner's internal record index; only the Legacy and
+    /// Extended layouts store it. The Standard layout needs a numeric user
+    /// ID and Legacy records drop the user ID entirely.
+    pub fn encode(self, uid: u16, record: &AttendanceRecord) -> Result<Vec<u8>> {
+        let time = encode_time(&record.timestamp)?.to_le_bytes();
+        let verify = u8::from(record.verify_mode);
+        let punch = u8::from(record.punch);
+
+        let mut buf = Vec::with_capacity(self.record_size());
+        match self {
+            Self::Legacy => {
+                buf.extend_from_slice(&uid.to_le_bytes());
+                buf.push(verify);
+                buf.extend_from_slice(&time);
+                buf.push(punch);
+            }
+            Self::Standard => {
+                let user_id: u32 = record.user_id.parse().map_err(|_| {
+                    Error::Validation(format!(
+                        "user_id {:?} does not fit the standard layout",
+                        record.user_id
+                    ))
+                })?;
+                buf.extend_from_slice(&user_id.to_le_bytes());
+                buf.extend_from_slice(&time);
+                buf.push(verify);
+                buf.push(punch);
+                buf.extend_from_slice(&[0; 2]);
+                buf.extend_from_slice(&record.work_code.unwrap_or(0).to_le_bytes());
+            }
+            Self::Extended => {
+                let user_id = record.user_id.as_bytes();
+                if user_id.len() >= 24 || user_id.contains(&0) {
+                    return Err(Error::Validation(format!(
+                        "user_id {:?} does not fit the extended layout",
+                        record.user_id
+                    )));
+                }
+                buf.extend_from_slice(&uid.to_le_bytes());
+                buf.extend_from_slice(user_id);
+                buf.resize(26, 0);
+                buf.push(verify);
+                buf.extend_from_slice(&time);
+                buf.push(punch);
+                buf.extend_from_slice(&[0; 8]);
+            }
+        }
+
+        debug_assert_eq!(buf.len(), self.record_size());
+        Ok(buf)
+    }
+}
+
+/// Parse a block of records in a known layout
+pub fn parse_attendance(data: &[u8], layout: AttendanceLayout) -> Result<Vec<AttendanceRecord>> {
+    let size = layout.record_size();
+    if data.len() % size != 0 {
+        return Err(Error::Parse(format!(
+            "{} bytes is not a whole number of {}-byte records",
+            data.len(),
+            size
+        )));
+    }
+
+    data.chunks_exact(size)
+        .enumerate()
+        .map(|(index, record)| {
+            layout
+                .decode(record)
+                .map_err(|e| Error::Parse(format!("attendance record {}: {}", index, e)))
+        })
+        .collect()
+}
+
+/// Parse a block of records, detecting the layout
+///
+/// With `record_count` the layout is derived from the record size. Without
+/// it, layouts are tried from largest to smallest and the first one that
+/// decodes every record wins; pass the count whenever it is known, since
+/// small payloads can decode under more than one layout.
+pub fn parse_attendance_auto(
+    data: &[u8],
+    record_count: Option<usize>,
+) -> Result<(AttendanceLayout, Vec<AttendanceRecord>)> {
+    if data.is_empty() {
+        return Ok((AttendanceLayout::Extended, Vec::new()));
+    }
+
+    if let Some(count) = record_count {
+        let layout = AttendanceLayout::detect(data.len(), count).ok_or_else(|| {
+            Error::Parse(format!(
+                "{} bytes for {} records matches no known attendance layout",
+                data.len(),
+                count
+            ))
+        })?;
+        return Ok((layout, parse_attendance(data, layout)?));
+    }
+
+    AttendanceLayout::ALL
+        .into_iter()
+        .filter(|layout| data.len() % layout.record_size() == 0)
+        .find_map(|layout| Some((layout, parse_attendance(data, layout).ok()?)))
+        .ok_or_else(|| {
+            Error::Parse(format!(
+                "{} bytes of attendance data match no known layout",
+                data.len()
+            ))
+        })
+}
+
+/// Split off the `u32` total-size prefix of a bulk data payload
+pub fn strip_size_prefix(data: &[u8]) -> Result<&[u8]> {
+    if data.len() < 4 {
+        return Err(Error::Parse(format!(
+            "Bulk data too short for size prefix: {} bytes",
+            data.len()
+        )));
+    }
+
+    let declared = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let body = &data[4..];
+
+    if declared > body.len() {
+        return Err(Error::Parse(format!(
+            "Bulk data declares {} bytes but holds {}",
+            declared,
+            body.len()
+        )));
+    }
+
+    Ok(&body[..declared])
+}
+
+/// Read a NUL-padded string field
+///
+/// Bytes after the first NUL are ignored; the rest must be printable text.
+pub(crate) fn c_string(field: &[u8]) -> Result<String> {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    let text = std::str::from_utf8(&field[..end])
+        .map_err(|_| Error::Parse("String field is not valid UTF-8".into()))?;
+
+    if text.chars().any(char::is_control) {
+        return Err(Error::Parse(format!("String field contains control characters: {:?}", text)));
+    }
+
+    Ok(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    // Two punches: 2024-03-01 08:30:00 check-in and 17:05:12 check-out
+    const EXTENDED: &str = "01003100000000000000000000000000000000000000000000000188844c2e00000000000000000002003130303200000000000000000000000000000000000000000f48fd4c2e010000000000000000";
+    const STANDARD: &str = "e903000088844c2e0100000000000000ea03000048fd4c2e0401000007000000";
+    const LEGACY: &str = "01000188844c2e0002000348fd4c2e01";
+
+    fn morning() -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 30, 0).unwrap()
+    }
+
+    fn evening() -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(17, 5, 12).unwrap()
+    }
+
+    #[test]
+    fn test_parse_extended() {
+        let data = hex::decode(EXTENDED).unwrap();
+        let records = parse_attendance(&data, AttendanceLayout::Extended).unwrap();
+
+        assert_eq!(
+            records,
+            vec![
+                AttendanceRecord::new("1", morning(), VerifyMode::Fingerprint, PunchType::CheckIn),
+                AttendanceRecord::new("1002", evening(), VerifyMode::Face, PunchType::CheckOut),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_standard() {
+        let data = hex::decode(STANDARD).unwrap();
+        let records = parse_attendance(&data, AttendanceLayout::Standard).unwrap();
+
+        assert_eq!(records[0].user_id, "1001");
+        assert_eq!(records[0].timestamp, morning());
+        assert_eq!(records[0].work_code, None);
+        assert_eq!(records[1].verify_mode, VerifyMode::Rfid);
+        assert_eq!(records[1].work_code, Some(7));
+    }
+
+    #[test]
+    fn test_parse_unknown_verify_mode() {
+        // First STANDARD record with verify code 34, past the catalog
+        let data = hex::decode("e903000088844c2e2200000000000000").unwrap();
+        let records = parse_attendance(&data, AttendanceLayout::Standard).unwrap();
+
+        assert_eq!(records[0].verify_mode, VerifyMode::Other(34));
+        assert_eq!(records[0].timestamp, morning());
+        assert_eq!(AttendanceLayout::Standard.encode(0, &records[0]).unwrap(), data);
+    }
+
+    #[test]
+    fn test_parse_legacy() {
+        let data = hex::decode(LEGACY).unwrap();
+        let records = parse_attendance(&data, AttendanceLayout::Legacy).unwrap();
+
+        assert_eq!(records[0].user_id, "1");
+        assert_eq!(records[1].user_id, "2");
+        assert_eq!(records[1].verify_mode, VerifyMode::Card);
+        assert_eq!(records[1].timestamp, evening());
+    }
+
+    #[test]
+    fn test_detect_layout() {
+        for (hex, layout) in [
+            (EXTENDED, AttendanceLayout::Extended),
+            (STANDARD, AttendanceLayout::Standard),
+            (LEGACY, AttendanceLayout::Legacy),
+        ] {
+            let data = hex::decode(hex).unwrap();
+            assert_eq!(AttendanceLayout::detect(data.len(), 2), Some(layout));
+
+            let (detected, records) = parse_attendance_auto(&data, Some(2)).unwrap();
+            assert_eq!(detected, layout);
+            assert_eq!(records.len(), 2);
+        }
+
+        assert_eq!(AttendanceLayout::detect(24, 2), None);
+        assert!(parse_attendance_auto(&[0; 24], Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_detect_layout_without_count() {
+        let data = hex::decode(EXTENDED).unwrap();
+        assert_eq!(parse_attendance_auto(&data, None).unwrap().0, AttendanceLayout::Extended);
+
+        let data = hex::decode(STANDARD).unwrap();
+        assert_eq!(parse_attendance_auto(&data, None).unwrap().0, AttendanceLayout::Standard);
+    }
+
+    #[test]
+    fn test_size_prefix() {
+        let mut data = 16u32.to_le_bytes().to_vec();
+        data.extend(hex::decode(LEGACY).unwrap());
+
+        assert_eq!(strip_size_prefix(&data).unwrap().len(), 16);
+        assert!(strip_size_prefix(&data[..10]).is_err());
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        for (hex, layout) in [
+            (EXTENDED, AttendanceLayout::Extended),
+            (STANDARD, AttendanceLayout::Standard),
+            (LEGACY, AttendanceLayout::Legacy),
+        ] {
+            let data = hex::decode(hex).unwrap();
+            for (index, chunk) in data.chunks_exact(layout.record_size()).enumerate() {
+                let record = layout.decode(chunk).unwrap();
+                let uid = u16::from_le_bytes([chunk[0], chunk[1]]);
+                let encoded = layout.encode(uid, &record).unwrap();
+                assert_eq!(encoded, chunk, "{:?} record {}", layout, index);
+            }
+        }
+
+        let named = AttendanceRecord::new("A1", morning(), VerifyMode::Password, PunchType::CheckIn);
+        assert!(AttendanceLayout::Standard.encode(1, &named).is_err());
+        let encoded = AttendanceLayout::Extended.encode(1, &named).unwrap();
+        assert_eq!(AttendanceLayout::Extended.decode(&encoded).unwrap(), named);
+    }
+
+    #[test]
+    fn test_bad_record() {
+        let mut data = hex::decode(LEGACY).unwrap();
+        data[7] = 99; // firmware-defined punch state
+        let records = parse_attendance(&data, AttendanceLayout::Legacy).unwrap();
+        assert_eq!(records[0].punch, PunchType::Custom(99));
+
+        data[2] = 99; // unknown verify mode
+        let records = parse_attendance(&data, AttendanceLayout::Legacy).unwrap();
+        assert_eq!(records[0].verify_mode, VerifyMode::Other(99));
+
+        // 2024-02-31 00:00:00
+        data[3..7].copy_from_slice(&(((24 * 12 + 1) * 31 + 30) * 86_400u32).to_le_bytes());
+        let err = parse_attendance(&data, AttendanceLayout::Legacy).unwrap_err();
+        assert!(err.to_string().contains("record 0"), "{}", err);
+        assert!(parse_attendance(&data[..5], AttendanceLayout::Legacy).is_err());
+    }
+}
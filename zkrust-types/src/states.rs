@@ -0,0 +1,174 @@
+//! Attendance state tables
+//!
+//! Firmware with configurable status keys lets the site define punch states
+//! beyond check in/out (lunch out, shift handover, ...). The table maps each
+//! state code to the name shown on the device. It travels as one option
+//! value of `code=name` pairs separated by `;`:
+//!
+//! ```text
+//! 0=Check-In;1=Check-Out;6=Lunch Out;7=Lunch In
+//! ```
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use zkrust_core::constants::PunchType;
+
+use crate::error::{Error, Result};
+
+/// Longest state name the device displays
+pub const MAX_STATE_NAME: usize = 24;
+
+/// Punch states a device offers, with their display names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateTable {
+    states: BTreeMap<PunchType, String>,
+}
+
+impl StateTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Table of the six standard states with their default names
+    pub fn standard() -> Self {
+        Self {
+            states: PunchType::STANDARD
+                .into_iter()
+                .map(|punch| (punch, punch.name().to_string()))
+                .collect(),
+        }
+    }
+
+    /// Add or rename a state
+    pub fn with_state(mut self, punch: PunchType, name: impl Into<String>) -> Self {
+        self.insert(punch, name);
+        self
+    }
+
+    /// Add or rename a state
+    pub fn insert(&mut self, punch: PunchType, name: impl Into<String>) {
+        self.states.insert(punch, name.into());
+    }
+
+    /// Remove a state
+    pub fn remove(&mut self, punch: PunchType) -> Option<String> {
+        self.states.remove(&punch)
+    }
+
+    /// Display name of `punch`, if the table has it
+    pub fn get(&self, punch: PunchType) -> Option<&str> {
+        self.states.get(&punch).map(String::as_str)
+    }
+
+    /// Display name of `punch`, falling back to its standard name
+    pub fn name(&self, punch: PunchType) -> Cow<'_, str> {
+        match self.get(punch) {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(punch.to_string()),
+        }
+    }
+
+    /// Iterate over states in code order
+    pub fn iter(&self) -> impl Iterator<Item = (PunchType, &str)> {
+        self.states.iter().map(|(punch, name)| (*punch, name.as_str()))
+    }
+
+    /// Number of states
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Check if the table has no states
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Parse the option value form
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut table = Self::new();
+
+        for entry in value.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (code, name) = entry
+                .split_once('=')
+                .ok_or_else(|| Error::Parse(format!("State entry without '=': {:?}", entry)))?;
+
+            let code: u8 = code
+                .trim()
+                .parse()
+                .map_err(|_| Error::Parse(format!("Invalid state code in {:?}", entry)))?;
+
+            let punch = PunchType::try_from(code).map_err(|e| Error::Parse(e.to_string()))?;
+            table.insert(punch, name.trim());
+        }
+
+        Ok(table)
+    }
+
+    /// Encode to the option value form
+    ///
+    /// Fails if a name is empty, longer than [`MAX_STATE_NAME`] or contains
+    /// a separator.
+    pub fn encode(&self) -> Result<String> {
+        let mut entries = Vec::with_capacity(self.states.len());
+
+        for (punch, name) in &self.states {
+            if name.trim().is_empty()
+                || name.chars().count() > MAX_STATE_NAME
+                || name.contains([';', '=', '\0', '\r', '\n'])
+            {
+                return Err(Error::Validation(format!("Invalid name {:?} for state {}", name, punch.code())));
+            }
+            entries.push(format!("{}={}", punch.code(), name));
+        }
+
+        Ok(entries.join(";"))
+    }
+}
+
+impl fmt::Display for StateTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (punch, name) in self.iter() {
+            writeln!(f, "{:>3}  {}", punch.code(), name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let table = StateTable::standard()
+            .with_state(PunchType::Custom(6), "Lunch Out")
+            .with_state(PunchType::Custom(7), "Lunch In");
+
+        let encoded = table.encode().unwrap();
+        assert!(encoded.starts_with("0=Check-In;1=Check-Out;"));
+        assert!(encoded.ends_with(";6=Lunch Out;7=Lunch In"));
+        assert_eq!(StateTable::parse(&encoded).unwrap(), table);
+
+        assert_eq!(table.name(PunchType::Custom(7)), "Lunch In");
+        assert_eq!(table.name(PunchType::Custom(9)), "State-9");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(StateTable::parse("").unwrap().is_empty());
+        assert!(StateTable::parse("6=Lunch;").unwrap().get(PunchType::Custom(6)).is_some());
+        assert!(StateTable::parse("Lunch").is_err());
+        assert!(StateTable::parse("300=Lunch").is_err());
+    }
+
+    #[test]
+    fn test_encode_rejects_bad_names() {
+        for name in ["", "a;b", "a=b", "A name far too long to fit the screen"] {
+            let table = StateTable::new().with_state(PunchType::Custom(6), name);
+            assert!(matches!(table.encode(), Err(Error::Validation(_))), "{:?}", name);
+        }
+    }
+}
@@ -0,0 +1,729 @@
+//! Biometric template types
+//!
+//! Templates are opaque blobs produced by the device's matching algorithm;
+//! they can only be restored to devices running a compatible algorithm
+//! version. [`content_hash`](FingerprintTemplate::content_hash) gives a
+//! stable key for deduplicating backups.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::text::{StringCodec, Utf8};
+use crate::user::{User, UserRecordLayout};
+
+/// Number of fingers a user can enroll (indices 0-9)
+pub const MAX_FINGERS: u8 = 10;
+
+/// Fingerprint template
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FingerprintTemplate {
+    /// Internal user record index
+    pub uid: u16,
+
+    /// Finger index (0-9)
+    pub finger: u8,
+
+    /// Template can be used for verification
+    pub valid: bool,
+
+    /// Verifying with this finger raises a duress alarm
+    pub duress: bool,
+
+    /// Matching algorithm version (e.g. 10 for ZKFinger VX10.0)
+    pub algorithm_version: u8,
+
+    /// Raw template bytes
+    pub data: Vec<u8>,
+}
+
+impl FingerprintTemplate {
+    /// Flag bit: template is valid
+    pub const FLAG_VALID: u8 = 0x01;
+
+    /// Flag bit: duress finger
+    pub const FLAG_DURESS: u8 = 0x02;
+
+    /// Create a valid, non-duress template
+    pub fn new(uid: u16, finger: u8, algorithm_version: u8, data: impl Into<Vec<u8>>) -> Result<Self> {
+        if finger >= MAX_FINGERS {
+            return Err(Error::Validation(format!(
+                "finger index {} out of range 0-{}",
+                finger,
+                MAX_FINGERS - 1
+            )));
+        }
+
+        Ok(Self {
+            uid,
+            finger,
+            valid: true,
+            duress: false,
+            algorithm_version,
+            data: data.into(),
+        })
+    }
+
+    /// Set the valid/duress flags from the device flag byte
+    pub fn with_flags(mut self, flags: u8) -> Self {
+        self.valid = flags & Self::FLAG_VALID != 0;
+        self.duress = flags & Self::FLAG_DURESS != 0;
+        self
+    }
+
+    /// Device flag byte for this template
+    pub fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.valid {
+            flags |= Self::FLAG_VALID;
+        }
+        if self.duress {
+            flags |= Self::FLAG_DURESS;
+        }
+        flags
+    }
+
+    /// Template size in bytes
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Check if both templates hold the same biometric data
+    ///
+    /// Ignores the owner and flags, so the same finger enrolled under two
+    /// users compares equal.
+    pub fn same_content(&self, other: &Self) -> bool {
+        self.algorithm_version == other.algorithm_version && self.data == other.data
+    }
+
+    /// Stable 64-bit hash of the algorithm version and data
+    ///
+    /// Unlike [`std::hash::Hash`], the value doesn't change between Rust
+    /// releases, so it can be stored alongside backups.
+    pub fn content_hash(&self) -> u64 {
+        content_hash(self.algorithm_version, &self.data)
+    }
+}
+
+impl fmt::Display for FingerprintTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Finger[uid={}, finger={}, v{}, {} bytes{}]",
+            self.uid,
+            self.finger,
+            self.algorithm_version,
+            self.size(),
+            if self.duress { ", duress" } else { "" }
+        )
+    }
+}
+
+/// Face template
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FaceTemplate {
+    /// Internal user record index
+    pub uid: u16,
+
+    /// Template can be used for verification
+    pub valid: bool,
+
+    /// Face algorithm version
+    pub algorithm_version: u8,
+
+    /// Raw template bytes
+    pub data: Vec<u8>,
+}
+
+impl FaceTemplate {
+    /// Create a valid template
+    pub fn new(uid: u16, algorithm_version: u8, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            uid,
+            valid: true,
+            algorithm_version,
+            data: data.into(),
+        }
+    }
+
+    /// Template size in bytes
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Check if both templates hold the same biometric data
+    pub fn same_content(&self, other: &Self) -> bool {
+        self.algorithm_version == other.algorithm_version && self.data == other.data
+    }
+
+    /// Stable 64-bit hash of the algorithm version and data
+    pub fn content_hash(&self) -> u64 {
+        content_hash(self.algorithm_version, &self.data)
+    }
+}
+
+impl fmt::Display for FaceTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Face[uid={}, v{}, {} bytes]",
+            self.uid,
+            self.algorithm_version,
+            self.size()
+        )
+    }
+}
+
+/// Largest fingerprint template a device table entry can hold
+pub const MAX_FINGERPRINT_SIZE: usize = u16::MAX as usize - FINGER_ENTRY_HEADER;
+
+/// Problem found by a template check
+///
+/// See [`FingerprintTemplate::check_for`] and [`FaceTemplate::check_for`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TemplateIssue {
+    /// No template data
+    Empty,
+
+    /// Larger than a device table entry can hold
+    Oversized { size: usize, max: usize },
+
+    /// The device marked the template as unusable
+    Invalid,
+
+    /// Produced by a different algorithm version than the target runs
+    AlgorithmMismatch { template: u8, target: u8 },
+}
+
+impl TemplateIssue {
+    /// Whether restoring the template would store garbage on the target
+    ///
+    /// Invalid templates are carried over as-is, so the device can keep
+    /// ignoring them.
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, Self::Invalid)
+    }
+}
+
+impl fmt::Display for TemplateIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty template"),
+            Self::Oversized { size, max } => write!(f, "{} byte template exceeds {} bytes", size, max),
+            Self::Invalid => write!(f, "template marked invalid"),
+            Self::AlgorithmMismatch { template, target } => {
+                write!(f, "template is VX{}, target runs VX{}", template, target)
+            }
+        }
+    }
+}
+
+impl FingerprintTemplate {
+    /// Problems with the template itself
+    pub fn check(&self) -> Vec<TemplateIssue> {
+        let mut issues = Vec::new();
+        if self.data.is_empty() {
+            issues.push(TemplateIssue::Empty);
+        }
+        if self.size() > MAX_FINGERPRINT_SIZE {
+            issues.push(TemplateIssue::Oversized {
+                size: self.size(),
+                max: MAX_FINGERPRINT_SIZE,
+            });
+        }
+        if !self.valid {
+            issues.push(TemplateIssue::Invalid);
+        }
+        issues
+    }
+
+    /// Problems restoring the template to a device running
+    /// `target_algorithm`
+    pub fn check_for(&self, target_algorithm: u8) -> Vec<TemplateIssue> {
+        let mut issues = self.check();
+        if self.algorithm_version != target_algorithm {
+            issues.push(TemplateIssue::AlgorithmMismatch {
+                template: self.algorithm_version,
+                target: target_algorithm,
+            });
+        }
+        issues
+    }
+
+    /// Check if the template can be restored to a device running
+    /// `target_algorithm`
+    pub fn is_restorable_on(&self, target_algorithm: u8) -> bool {
+        self.check_for(target_algorithm).iter().all(|issue| !issue.is_fatal())
+    }
+}
+
+impl FaceTemplate {
+    /// Problems with the template itself
+    pub fn check(&self) -> Vec<TemplateIssue> {
+        let mut issues = Vec::new();
+        if self.data.is_empty() {
+            issues.push(TemplateIssue::Empty);
+        }
+        if !self.valid {
+            issues.push(TemplateIssue::Invalid);
+        }
+        issues
+    }
+
+    /// Problems restoring the template to a device running face algorithm
+    /// `target_algorithm`
+    pub fn check_for(&self, target_algorithm: u8) -> Vec<TemplateIssue> {
+        let mut issues = self.check();
+        if self.algorithm_version != target_algorithm {
+            issues.push(TemplateIssue::AlgorithmMismatch {
+                template: self.algorithm_version,
+                target: target_algorithm,
+            });
+        }
+        issues
+    }
+}
+
+/// Overview of a set of fingerprint templates
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateSummary {
+    /// Number of templates
+    pub count: usize,
+
+    /// Combined template size in bytes
+    pub total_bytes: usize,
+
+    /// Smallest and largest template size
+    pub size_range: Option<(usize, usize)>,
+
+    /// Number of templates per algorithm version
+    pub versions: BTreeMap<u8, usize>,
+
+    /// Duress fingers
+    pub duress: usize,
+
+    /// Templates marked invalid
+    pub invalid: usize,
+
+    /// Templates with a fatal issue (empty or oversized)
+    pub broken: usize,
+}
+
+impl TemplateSummary {
+    /// Summarize `templates`
+    pub fn of<'a>(templates: impl IntoIterator<Item = &'a FingerprintTemplate>) -> Self {
+        let mut summary = Self::default();
+        for template in templates {
+            let size = template.size();
+            summary.count += 1;
+            summary.total_bytes += size;
+            summary.size_range = Some(match summary.size_range {
+                Some((min, max)) => (min.min(size), max.max(size)),
+                None => (size, size),
+            });
+            *summary.versions.entry(template.algorithm_version).or_insert(0) += 1;
+            summary.duress += usize::from(template.duress);
+            summary.invalid += usize::from(!template.valid);
+            summary.broken += usize::from(template.check().iter().any(TemplateIssue::is_fatal));
+        }
+        summary
+    }
+}
+
+/// Header of each fingerprint table entry: size, uid, finger, flags
+const FINGER_ENTRY_HEADER: usize = 6;
+
+/// Parse the fingerprint table returned for `CMD_DB_RRQ` / `FCT_FINGERTMP`
+///
+/// `data` is the table without its size prefix. Each entry is
+/// `size: u16 | uid: u16 | finger: u8 | flags: u8 | template`, with `size`
+/// counting the 6-byte header. The table does not record the algorithm
+/// version, so the caller supplies the device's.
+pub fn parse_fingerprints(data: &[u8], algorithm_version: u8) -> Result<Vec<FingerprintTemplate>> {
+    let mut templates = Vec::new();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        if rest.len() < FINGER_ENTRY_HEADER {
+            return Err(Error::Parse(format!(
+                "{} trailing bytes after {} fingerprint templates",
+                rest.len(),
+                templates.len()
+            )));
+        }
+
+        let size = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        if size < FINGER_ENTRY_HEADER || size > rest.len() {
+            return Err(Error::Parse(format!(
+                "fingerprint template {} claims {} bytes, {} available",
+                templates.len(),
+                size,
+                rest.len()
+            )));
+        }
+
+        let uid = u16::from_le_bytes([rest[2], rest[3]]);
+        let template = FingerprintTemplate::new(uid, rest[4], algorithm_version, &rest[FINGER_ENTRY_HEADER..size])
+            .map_err(|e| Error::Parse(format!("fingerprint template {}: {}", templates.len(), e)))?
+            .with_flags(rest[5]);
+
+        templates.push(template);
+        rest = &rest[size..];
+    }
+
+    Ok(templates)
+}
+
+/// Encode templates as the `FCT_FINGERTMP` table, the inverse of
+/// [`parse_fingerprints`]
+///
+/// The result carries no size prefix.
+pub fn encode_fingerprints(templates: &[FingerprintTemplate]) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+
+    for template in templates {
+        let size = u16::try_from(FINGER_ENTRY_HEADER + template.size()).map_err(|_| {
+            Error::Validation(format!("{} byte template too large", template.size()))
+        })?;
+
+        buf.extend_from_slice(&size.to_le_bytes());
+        buf.extend_from_slice(&template.uid.to_le_bytes());
+        buf.push(template.finger);
+        buf.push(template.flags());
+        buf.extend_from_slice(&template.data);
+    }
+
+    Ok(buf)
+}
+
+/// Marker byte in front of each uploaded user and table entry
+const UPLOAD_ENTRY: u8 = 2;
+
+/// Offset added to the finger index in upload table entries
+const UPLOAD_FINGER_BASE: u8 = 0x10;
+
+/// Encode users and their fingerprints for `CMD_SAVE_USERTEMPS`
+///
+/// The buffer holds three sections behind a header of their `u32`
+/// lengths: user records (each prefixed with `2`), a table of
+/// `2 | uid: u16 | 0x10 + finger | offset: u32` entries, and the
+/// templates (each prefixed with its `u16` length). Templates are matched
+/// to users by `uid`. The upload format has no flag byte, so templates
+/// arrive as valid, non-duress fingers.
+pub fn encode_user_templates(
+    layout: UserRecordLayout,
+    entries: &[(User, Vec<FingerprintTemplate>)],
+) -> Result<Vec<u8>> {
+    encode_user_templates_with(layout, entries, &Utf8)
+}
+
+/// Encode users and their fingerprints, writing text fields with `codec`
+///
+/// See [`encode_user_templates`] for the buffer format.
+pub fn encode_user_templates_with(
+    layout: UserRecordLayout,
+    entries: &[(User, Vec<FingerprintTemplate>)],
+    codec: &dyn StringCodec,
+) -> Result<Vec<u8>> {
+    let mut users = Vec::new();
+    let mut table = Vec::new();
+    let mut templates = Vec::new();
+
+    for (user, fingers) in entries {
+        users.push(UPLOAD_ENTRY);
+        users.extend_from_slice(&layout.encode_with(user, codec)?);
+
+        for finger in fingers {
+            if finger.uid != user.uid {
+                return Err(Error::Validation(format!(
+                    "template uid {} does not belong to user uid {}",
+                    finger.uid, user.uid
+                )));
+            }
+            let size = u16::try_from(finger.size()).map_err(|_| {
+                Error::Validation(format!("{} byte template too large", finger.size()))
+            })?;
+
+            table.push(UPLOAD_ENTRY);
+            table.extend_from_slice(&user.uid.to_le_bytes());
+            table.push(UPLOAD_FINGER_BASE + finger.finger);
+            table.extend_from_slice(&(templates.len() as u32).to_le_bytes());
+
+            templates.extend_from_slice(&size.to_le_bytes());
+            templates.extend_from_slice(&finger.data);
+        }
+    }
+
+    let mut buf = Vec::with_capacity(12 + users.len() + table.len() + templates.len());
+    for section in [&users, &table, &templates] {
+        buf.extend_from_slice(&(section.len() as u32).to_le_bytes());
+    }
+    buf.extend_from_slice(&users);
+    buf.extend_from_slice(&table);
+    buf.extend_from_slice(&templates);
+    Ok(buf)
+}
+
+/// Size of an upload table entry: marker, uid, finger, template offset
+const UPLOAD_TABLE_ENTRY: usize = 8;
+
+/// Decode a `CMD_SAVE_USERTEMPS` buffer, the inverse of
+/// [`encode_user_templates`]
+///
+/// The upload format doesn't carry the algorithm version, so the caller
+/// supplies the device's.
+pub fn decode_user_templates(
+    layout: UserRecordLayout,
+    data: &[u8],
+    algorithm_version: u8,
+) -> Result<Vec<(User, Vec<FingerprintTemplate>)>> {
+    if data.len() < 12 {
+        return Err(Error::Parse(format!("Upload buffer too short: {} bytes", data.len())));
+    }
+
+    let length = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]) as usize;
+    let (users_len, table_len, templates_len) = (length(0), length(4), length(8));
+
+    // Lengths are untrusted; their sum can overflow on 32-bit targets
+    let total = [users_len, table_len, templates_len]
+        .into_iter()
+        .try_fold(12usize, usize::checked_add);
+    if total != Some(data.len()) {
+        return Err(Error::Parse(format!(
+            "Upload sections of {}, {} and {} bytes don't fill {} bytes",
+            users_len,
+            table_len,
+            templates_len,
+            data.len() - 12
+        )));
+    }
+
+    let users = &data[12..12 + users_len];
+    let table = &data[12 + users_len..12 + users_len + table_len];
+    let templates = &data[12 + users_len + table_len..];
+
+    let entry_size = 1 + layout.record_size();
+    if users.len() % entry_size != 0 || table.len() % UPLOAD_TABLE_ENTRY != 0 {
+        return Err(Error::Parse(format!(
+            "Upload sections don't hold whole {:?} user and table entries",
+            layout
+        )));
+    }
+
+    let mut entries = users
+        .chunks_exact(entry_size)
+        .map(|entry| Ok((layout.decode(&entry[1..])?, Vec::new())))
+        .collect::<Result<Vec<(User, Vec<FingerprintTemplate>)>>>()?;
+
+    for (index, entry) in table.chunks_exact(UPLOAD_TABLE_ENTRY).enumerate() {
+        let uid = u16::from_le_bytes([entry[1], entry[2]]);
+        let offset = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]) as usize;
+
+        let template = templates
+            .get(offset..offset.saturating_add(2))
+            .map(|len| u16::from_le_bytes([len[0], len[1]]) as usize)
+            .and_then(|len| templates.get(offset + 2..offset + 2 + len))
+            .ok_or_else(|| {
+                Error::Parse(format!("table entry {} points outside the template section", index))
+            })?;
+
+        let finger = entry[3].wrapping_sub(UPLOAD_FINGER_BASE);
+        let template = FingerprintTemplate::new(uid, finger, algorithm_version, template)
+            .map_err(|e| Error::Parse(format!("table entry {}: {}", index, e)))?;
+
+        let (_, fingers) = entries
+            .iter_mut()
+            .find(|(user, _)| user.uid == uid)
+            .ok_or_else(|| Error::Parse(format!("table entry {} names unknown uid {}", index, uid)))?;
+        fingers.push(template);
+    }
+
+    Ok(entries)
+}
+
+/// FNV-1a over the version byte followed by the data
+fn content_hash(version: u8, data: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    std::iter::once(version)
+        .chain(data.iter().copied())
+        .fold(OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finger_index_range() {
+        assert!(FingerprintTemplate::new(1, 9, 10, vec![1, 2, 3]).is_ok());
+        assert!(FingerprintTemplate::new(1, 10, 10, vec![1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_flags_roundtrip() {
+        let template = FingerprintTemplate::new(1, 0, 10, vec![0; 4]).unwrap().with_flags(3);
+        assert!(template.valid);
+        assert!(template.duress);
+        assert_eq!(template.flags(), 3);
+
+        let invalid = template.with_flags(0);
+        assert!(!invalid.valid);
+        assert_eq!(invalid.flags(), 0);
+    }
+
+    #[test]
+    fn test_template_checks() {
+        let good = FingerprintTemplate::new(1, 0, 10, vec![1; 600]).unwrap();
+        assert!(good.check_for(10).is_empty());
+        assert!(good.is_restorable_on(10));
+        assert_eq!(
+            good.check_for(9),
+            [TemplateIssue::AlgorithmMismatch { template: 10, target: 9 }]
+        );
+
+        let empty = FingerprintTemplate::new(1, 1, 10, Vec::new()).unwrap().with_flags(0);
+        assert_eq!(empty.check(), [TemplateIssue::Empty, TemplateIssue::Invalid]);
+        assert!(!empty.is_restorable_on(10));
+
+        // Invalid alone is carried over
+        assert!(good.clone().with_flags(0).is_restorable_on(10));
+
+        let huge = FingerprintTemplate::new(1, 2, 10, vec![0; MAX_FINGERPRINT_SIZE + 1]).unwrap();
+        assert!(matches!(huge.check()[..], [TemplateIssue::Oversized { .. }]));
+        assert!(encode_fingerprints(&[huge]).is_err());
+
+        let face = FaceTemplate::new(1, 7, Vec::new());
+        assert_eq!(face.check_for(7), [TemplateIssue::Empty]);
+    }
+
+    #[test]
+    fn test_template_summary() {
+        let templates = [
+            FingerprintTemplate::new(1, 0, 10, vec![1; 500]).unwrap().with_flags(3),
+            FingerprintTemplate::new(1, 1, 10, vec![1; 700]).unwrap(),
+            FingerprintTemplate::new(2, 0, 9, Vec::new()).unwrap(),
+        ];
+
+        let summary = TemplateSummary::of(&templates);
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.total_bytes, 1200);
+        assert_eq!(summary.size_range, Some((0, 700)));
+        assert_eq!(summary.versions, BTreeMap::from([(9, 1), (10, 2)]));
+        assert_eq!(summary.duress, 1);
+        assert_eq!(summary.invalid, 0);
+        assert_eq!(summary.broken, 1);
+    }
+
+    #[test]
+    fn test_dedup_ignores_owner() {
+        let a = FingerprintTemplate::new(1, 0, 10, vec![9, 8, 7]).unwrap();
+        let b = FingerprintTemplate::new(2, 3, 10, vec![9, 8, 7]).unwrap();
+        let other_version = FingerprintTemplate::new(1, 0, 9, vec![9, 8, 7]).unwrap();
+
+        assert_ne!(a, b);
+        assert!(a.same_content(&b));
+        assert_eq!(a.content_hash(), b.content_hash());
+        assert!(!a.same_content(&other_version));
+        assert_ne!(a.content_hash(), other_version.content_hash());
+    }
+
+    #[test]
+    fn test_parse_fingerprints() {
+        let mut data = vec![9, 0, 7, 0, 3, 1, 0xAA, 0xBB, 0xCC];
+        data.extend_from_slice(&[7, 0, 8, 0, 0, 0, 0xDD]);
+
+        let templates = parse_fingerprints(&data, 10).unwrap();
+        assert_eq!(templates.len(), 2);
+        assert_eq!(templates[0].uid, 7);
+        assert_eq!(templates[0].finger, 3);
+        assert_eq!(templates[0].data, vec![0xAA, 0xBB, 0xCC]);
+        assert_eq!(templates[0].algorithm_version, 10);
+        assert!(templates[0].valid);
+        assert!(!templates[1].valid);
+
+        assert!(parse_fingerprints(&data[..8], 10).is_err());
+        assert!(parse_fingerprints(&[1, 0, 0, 0, 0, 0], 10).is_err());
+    }
+
+    #[test]
+    fn test_encode_user_templates() {
+        let user = User::builder(7, "100").name("Alice").build().unwrap();
+        let fingers = vec![
+            FingerprintTemplate::new(7, 0, 10, vec![1, 2]).unwrap(),
+            FingerprintTemplate::new(7, 5, 10, vec![3]).unwrap(),
+        ];
+
+        let buf = encode_user_templates(UserRecordLayout::Extended, &[(user.clone(), fingers)]).unwrap();
+
+        // Section lengths: 73-byte user, two 8-byte table entries, 2+2 and 2+1 template bytes
+        assert_eq!(&buf[..12], &[73, 0, 0, 0, 16, 0, 0, 0, 7, 0, 0, 0]);
+        assert_eq!(buf[12], 2);
+        assert_eq!(&buf[13..85], &UserRecordLayout::Extended.encode(&user).unwrap()[..]);
+        assert_eq!(&buf[85..93], &[2, 7, 0, 0x10, 0, 0, 0, 0]);
+        assert_eq!(&buf[93..101], &[2, 7, 0, 0x15, 4, 0, 0, 0]);
+        assert_eq!(&buf[101..], &[2, 0, 1, 2, 1, 0, 3]);
+
+        let stray = FingerprintTemplate::new(8, 0, 10, vec![1]).unwrap();
+        assert!(encode_user_templates(UserRecordLayout::Extended, &[(user, vec![stray])]).is_err());
+    }
+
+    #[test]
+    fn test_decode_user_templates() {
+        let user = User::builder(7, "100").name("Alice").build().unwrap();
+        let other = User::builder(8, "101").build().unwrap();
+        let fingers = vec![
+            FingerprintTemplate::new(7, 0, 10, vec![1, 2]).unwrap(),
+            FingerprintTemplate::new(7, 5, 10, vec![3]).unwrap(),
+        ];
+        let entries = vec![(user, fingers), (other, Vec::new())];
+
+        let buf = encode_user_templates(UserRecordLayout::Extended, &entries).unwrap();
+        assert_eq!(decode_user_templates(UserRecordLayout::Extended, &buf, 10).unwrap(), entries);
+
+        assert!(decode_user_templates(UserRecordLayout::Extended, &buf[..buf.len() - 1], 10).is_err());
+        assert!(decode_user_templates(UserRecordLayout::Compact, &buf, 10).is_err());
+    }
+
+    #[test]
+    fn test_decode_user_templates_hostile_lengths() {
+        let mut buf = Vec::new();
+        for len in [u32::MAX, u32::MAX, 2] {
+            buf.extend_from_slice(&len.to_le_bytes());
+        }
+        assert!(decode_user_templates(UserRecordLayout::Extended, &buf, 10).is_err());
+
+        // Table entry pointing at the very end of the address range
+        let user = User::builder(7, "100").build().unwrap();
+        let mut buf = encode_user_templates(UserRecordLayout::Extended, &[(user, Vec::new())]).unwrap();
+        buf[4..8].copy_from_slice(&8u32.to_le_bytes());
+        buf.extend_from_slice(&[2, 7, 0, 0x10, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert!(decode_user_templates(UserRecordLayout::Extended, &buf, 10).is_err());
+    }
+
+    #[test]
+    fn test_encode_fingerprints() {
+        let templates = vec![
+            FingerprintTemplate::new(7, 3, 10, vec![0xAA, 0xBB, 0xCC]).unwrap(),
+            FingerprintTemplate::new(8, 0, 10, vec![0xDD]).unwrap().with_flags(0),
+        ];
+
+        let data = encode_fingerprints(&templates).unwrap();
+        assert_eq!(&data[..6], &[9, 0, 7, 0, 3, 1]);
+        assert_eq!(parse_fingerprints(&data, 10).unwrap(), templates);
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        // FNV-1a of [0x00]
+        assert_eq!(content_hash(0, &[]), 0xaf63_bd4c_8601_b7df);
+
+        let face = FaceTemplate::new(5, 7, vec![1, 2, 3]);
+        assert_eq!(face.size(), 3);
+        assert_eq!(face.content_hash(), content_hash(7, &[1, 2, 3]));
+    }
+}
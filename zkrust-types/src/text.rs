@@ -0,0 +1,159 @@
+//! Text encodings of device string fields
+//!
+//! User names, group IDs and option values travel as raw bytes in whatever
+//! encoding the firmware was built for: UTF-8 on recent TFT models, GBK on
+//! terminals for the Chinese market, Latin-1 on older European units. A
+//! [`StringCodec`] converts between those bytes and Rust strings, so one
+//! fleet can mix them without mojibake.
+//!
+//! User IDs, option names and photo names are plain ASCII and don't go
+//! through a codec.
+//!
+//! ```
+//! use zkrust_types::text::{self, Latin1, StringCodec};
+//!
+//! assert_eq!(Latin1.decode(b"Jos\xe9"), "José");
+//! assert_eq!(Latin1.encode("José").unwrap(), b"Jos\xe9");
+//! assert!(Latin1.encode("李").is_err());
+//!
+//! assert_eq!(text::from_name("latin-1").unwrap().name(), "Latin-1");
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+
+/// Conversion between device bytes and text
+pub trait StringCodec: fmt::Debug + Send + Sync {
+    /// Encoding name for logs and errors (e.g. `"GBK"`)
+    fn name(&self) -> &'static str;
+
+    /// Decode bytes read from the device
+    ///
+    /// Never fails: bytes that don't decode become U+FFFD, since a garbled
+    /// name shouldn't make a whole user table unreadable.
+    fn decode(&self, bytes: &[u8]) -> String;
+
+    /// Encode text for the device
+    ///
+    /// Fails on characters the encoding can't represent rather than
+    /// writing a substitute the terminal would display.
+    fn encode(&self, text: &str) -> Result<Vec<u8>>;
+}
+
+fn unmappable(text: &str, codec: &dyn StringCodec) -> Error {
+    Error::Validation(format!("{:?} cannot be encoded as {}", text, codec.name()))
+}
+
+/// UTF-8, the default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Utf8;
+
+impl StringCodec for Utf8 {
+    fn name(&self) -> &'static str {
+        "UTF-8"
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+
+    fn encode(&self, text: &str) -> Result<Vec<u8>> {
+        Ok(text.as_bytes().to_vec())
+    }
+}
+
+/// ISO-8859-1, one byte per character
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Latin1;
+
+impl StringCodec for Latin1 {
+    fn name(&self) -> &'static str {
+        "Latin-1"
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        bytes.iter().map(|&b| char::from(b)).collect()
+    }
+
+    fn encode(&self, text: &str) -> Result<Vec<u8>> {
+        text.chars()
+            .map(|c| u8::try_from(c).map_err(|_| unmappable(text, self)))
+            .collect()
+    }
+}
+
+/// GBK (simplified Chinese), as used by Chinese-market firmware
+#[cfg(feature = "gbk")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Gbk;
+
+#[cfg(feature = "gbk")]
+impl StringCodec for Gbk {
+    fn name(&self) -> &'static str {
+        "GBK"
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        encoding_rs::GBK.decode_without_bom_handling(bytes).0.into_owned()
+    }
+
+    fn encode(&self, text: &str) -> Result<Vec<u8>> {
+        let (bytes, _, unmappable_chars) = encoding_rs::GBK.encode(text);
+        if unmappable_chars {
+            return Err(unmappable(text, self));
+        }
+        Ok(bytes.into_owned())
+    }
+}
+
+/// Look up a built-in codec by name
+///
+/// Case and separators are ignored; `gb2312` and `cp936` map to GBK and
+/// `iso-8859-1` to Latin-1.
+pub fn from_name(name: &str) -> Option<Arc<dyn StringCodec>> {
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    match name.as_str() {
+        "utf8" => Some(Arc::new(Utf8)),
+        "latin1" | "iso88591" => Some(Arc::new(Latin1)),
+        #[cfg(feature = "gbk")]
+        "gbk" | "gb2312" | "cp936" => Some(Arc::new(Gbk)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8() {
+        assert_eq!(Utf8.decode("Zoë".as_bytes()), "Zoë");
+        assert_eq!(Utf8.decode(b"A\xffB"), "A\u{FFFD}B");
+        assert_eq!(Utf8.encode("李").unwrap(), "李".as_bytes());
+    }
+
+    #[cfg(feature = "gbk")]
+    #[test]
+    fn test_gbk() {
+        // 李明 in GBK
+        let bytes = [0xc0, 0xee, 0xc3, 0xf7];
+        assert_eq!(Gbk.decode(&bytes), "李明");
+        assert_eq!(Gbk.encode("李明").unwrap(), bytes);
+        assert_eq!(Gbk.encode("Ann").unwrap(), b"Ann");
+        assert!(Gbk.encode("José 😀").is_err());
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(from_name("UTF-8").unwrap().name(), "UTF-8");
+        assert_eq!(from_name("ISO-8859-1").unwrap().name(), "Latin-1");
+        assert!(from_name("ebcdic").is_none());
+    }
+}
@@ -0,0 +1,204 @@
+//! Device timestamp encoding
+//!
+//! ZKTeco devices store local time as a packed `u32` with no timezone:
+//!
+//! ```text
+//! ((year % 100) * 12 * 31 + (month - 1) * 31 + day - 1) * 86400
+//!     + (hour * 60 + minute) * 60 + second
+//! ```
+//!
+//! Every month is treated as 31 days long, so the value is not a plain
+//! offset in seconds and must be decoded field by field.
+//!
+//! [`localize`] attaches a timezone to decoded times; with the `tz` feature,
+//! [`parse_timezone`] resolves IANA names such as `Africa/Kampala`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, LocalResult, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Timelike};
+#[cfg(feature = "tz")]
+pub use chrono_tz::Tz;
+
+use crate::error::{Error, Result};
+
+/// Decode a packed device timestamp (years are taken as 20xx)
+pub fn decode_time(value: u32) -> Result<NaiveDateTime> {
+    let mut t = value;
+
+    let second = t % 60;
+    t /= 60;
+    let minute = t % 60;
+    t /= 60;
+    let hour = t % 24;
+    t /= 24;
+    let day = t % 31 + 1;
+    t /= 31;
+    let month = t % 12 + 1;
+    t /= 12;
+    let year = t as i32 + 2000;
+
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_opt(hour, minute, second))
+        .ok_or_else(|| Error::Parse(format!("Invalid device timestamp: {}", value)))
+}
+
+/// Encode a local time in the packed device format
+///
+/// Only years 2000-2099 are representable.
+pub fn encode_time(time: &NaiveDateTime) -> Result<u32> {
+    let year = time.year();
+    if !(2000..2100).contains(&year) {
+        return Err(Error::Validation(format!(
+            "Year {} is outside the device range 2000-2099",
+            year
+        )));
+    }
+
+    let days = ((year as u32 % 100) * 12 * 31) + (time.month0() * 31) + time.day0();
+    Ok(days * 86400 + (time.hour() * 60 + time.minute()) * 60 + time.second())
+}
+
+/// Display format for timestamps (`2024-03-01 08:30:00`)
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Accepted input formats, tried in order
+const PARSE_FORMATS: &[&str] = &[
+    TIMESTAMP_FORMAT,
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+    "%Y/%m/%d %H:%M:%S",
+];
+
+/// Device local time with human-friendly `Display`/`FromStr`
+///
+/// Formats as `YYYY-MM-DD HH:MM:SS` and parses that, the ISO 8601 `T`
+/// form, minutes-only variants and `YYYY/MM/DD HH:MM:SS`.
+///
+/// # Examples
+///
+/// ```
+/// use zkrust_types::time::Timestamp;
+///
+/// let ts: Timestamp = "2024-03-01T08:30".parse().unwrap();
+/// assert_eq!(ts.to_string(), "2024-03-01 08:30:00");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub NaiveDateTime);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format(TIMESTAMP_FORMAT))
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        PARSE_FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+            .map(Self)
+            .ok_or_else(|| Error::Parse(format!("Invalid timestamp: {:?}", s)))
+    }
+}
+
+impl From<NaiveDateTime> for Timestamp {
+    fn from(time: NaiveDateTime) -> Self {
+        Self(time)
+    }
+}
+
+impl From<Timestamp> for NaiveDateTime {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0
+    }
+}
+
+/// Interpret a device local time in `tz`
+///
+/// Times repeated when the clocks go back resolve to the earlier instant.
+/// Times skipped when the clocks go forward were logged by a device that
+/// has not switched to summer time yet, so they are read as standard time.
+pub fn localize<Tz: TimeZone>(time: NaiveDateTime, tz: &Tz) -> DateTime<Tz> {
+    match tz.from_local_datetime(&time) {
+        LocalResult::Single(t) | LocalResult::Ambiguous(t, _) => t,
+        LocalResult::None => tz
+            .from_local_datetime(&(time + TimeDelta::hours(1)))
+            .earliest()
+            .unwrap_or_else(|| tz.from_utc_datetime(&time)),
+    }
+}
+
+/// Parse an IANA timezone name (e.g. `Europe/Berlin`)
+#[cfg(feature = "tz")]
+pub fn parse_timezone(name: &str) -> Result<Tz> {
+    name.trim()
+        .parse()
+        .map_err(|_| Error::Parse(format!("Unknown timezone: {:?}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d).unwrap().and_hms_opt(h, mi, s).unwrap()
+    }
+
+    #[test]
+    fn test_time_roundtrip() {
+        let time = datetime(2024, 2, 29, 13, 45, 7);
+        let encoded = encode_time(&time).unwrap();
+        assert_eq!(decode_time(encoded).unwrap(), time);
+    }
+
+    #[test]
+    fn test_time_known_value() {
+        // 2000-01-01 00:00:00 is the epoch of the packed format
+        assert_eq!(decode_time(0).unwrap(), datetime(2000, 1, 1, 0, 0, 0));
+        assert_eq!(encode_time(&datetime(2000, 1, 2, 0, 0, 1)).unwrap(), 86401);
+    }
+
+    #[test]
+    fn test_time_invalid() {
+        // Day 31 of February
+        let value = (31 + 30) * 86400;
+        assert!(decode_time(value).is_err());
+        assert!(encode_time(&datetime(1999, 12, 31, 0, 0, 0)).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_parse_and_display() {
+        let expected = Timestamp(datetime(2024, 3, 1, 8, 30, 0));
+        for input in ["2024-03-01 08:30:00", "2024-03-01T08:30:00", " 2024-03-01 08:30 ", "2024/03/01 08:30:00"] {
+            assert_eq!(input.parse::<Timestamp>().unwrap(), expected, "{}", input);
+        }
+        assert_eq!(expected.to_string(), "2024-03-01 08:30:00");
+        assert!("yesterday".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn test_localize_fixed_offset() {
+        let tz = chrono::FixedOffset::east_opt(3 * 3600).unwrap();
+        let local = localize(datetime(2024, 3, 1, 8, 0, 0), &tz);
+        assert_eq!(local.naive_utc(), datetime(2024, 3, 1, 5, 0, 0));
+    }
+
+    #[cfg(feature = "tz")]
+    #[test]
+    fn test_localize_dst() {
+        let tz = parse_timezone("Europe/Berlin").unwrap();
+        // Clocks went back at 03:00 CEST; 02:30 happened twice
+        let ambiguous = localize(datetime(2024, 10, 27, 2, 30, 0), &tz);
+        assert_eq!(ambiguous.naive_utc(), datetime(2024, 10, 27, 0, 30, 0));
+        // Clocks went forward at 02:00 CET; 02:30 never happened
+        let skipped = localize(datetime(2024, 3, 31, 2, 30, 0), &tz);
+        assert_eq!(skipped.naive_utc(), datetime(2024, 3, 31, 1, 30, 0));
+
+        assert!(parse_timezone("Mars/Olympus").is_err());
+    }
+}
@@ -0,0 +1,652 @@
+//! User record types
+
+use std::fmt;
+use std::sync::Arc;
+
+use zkrust_core::constants::Privilege;
+
+use crate::error::{Error, Result};
+use crate::text::{StringCodec, Utf8};
+
+/// Maximum digits in a user ID
+pub const MAX_USER_ID_DIGITS: usize = 9;
+
+/// Maximum length of a group ID in the extended layout
+pub const MAX_GROUP_ID_LEN: usize = 7;
+
+/// Largest card number the device can store
+pub const MAX_CARD: u64 = u32::MAX as u64;
+
+/// Binary layout of user records in CMD_USERTEMP_RRQ data
+///
+/// Older firmware packs each user into 28 bytes with a numeric user ID;
+/// newer firmware uses 72-byte records with a string user ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum UserRecordLayout {
+    /// 28-byte records (older black & white firmware)
+    Compact,
+
+    /// 72-byte records (TFT and newer firmware)
+    #[default]
+    Extended,
+}
+
+impl UserRecordLayout {
+    /// Size of one record in bytes
+    pub const fn record_size(self) -> usize {
+        match self {
+            Self::Compact => 28,
+            Self::Extended => 72,
+        }
+    }
+
+    /// Maximum name length in bytes
+    pub const fn max_name_len(self) -> usize {
+        match self {
+            Self::Compact => 8,
+            Self::Extended => 24,
+        }
+    }
+
+    /// Maximum password length in digits
+    pub const fn max_password_len(self) -> usize {
+        match self {
+            Self::Compact => 5,
+            Self::Extended => 8,
+        }
+    }
+
+    /// Detect the layout from a bulk user payload and the device's user count
+    ///
+    /// Returns `None` if the payload doesn't divide into `user_count` records
+    /// of either size.
+    pub fn detect(payload_len: usize, user_count: usize) -> Option<Self> {
+        if user_count == 0 {
+            return None;
+        }
+
+        match payload_len / user_count {
+            28 if payload_len % user_count == 0 => Some(Self::Compact),
+            72 if payload_len % user_count == 0 => Some(Self::Extended),
+            _ => None,
+        }
+    }
+
+    /// Decode one user record with UTF-8 text fields
+    ///
+    /// `record` must be exactly [`record_size`](Self::record_size) bytes.
+    pub fn decode(self, record: &[u8]) -> Result<User> {
+        self.decode_with(record, &Utf8)
+    }
+
+    /// Decode one user record, reading the name with `codec`
+    pub fn decode_with(self, record: &[u8], codec: &dyn StringCodec) -> Result<User> {
+        if record.len() != self.record_size() {
+            return Err(Error::Parse(format!(
+                "{:?} user record must be {} bytes, got {}",
+                self,
+                self.record_size(),
+                record.len()
+            )));
+        }
+
+        let u32_at =
+            |i: usize| u32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
+
+        let uid = u16::from_le_bytes([record[0], record[1]]);
+        let (privilege, enabled) = decode_privilege(record[2])?;
+
+        let (password, name, card, group_id, user_id) = match self {
+            // uid u16, privilege u8, password [5], name [8], card u32, pad,
+            // group u8, timezone u16, user_id u32
+            Self::Compact => {
+                let group = record[21];
+                (
+                    padded_str(&record[3..8], &Utf8),
+                    padded_str(&record[8..16], codec),
+                    u32_at(16),
+                    if group == 0 { String::new() } else { group.to_string() },
+                    u32_at(24).to_string(),
+                )
+            }
+            // uid u16, privilege u8, password [8], name [24], card u32, pad,
+            // group_id [7], pad, user_id [24]
+            Self::Extended => (
+                padded_str(&record[3..11], &Utf8),
+                padded_str(&record[11..35], codec),
+                u32_at(35),
+                padded_str(&record[40..47], codec),
+                padded_str(&record[48..72], &Utf8),
+            ),
+        };
+
+        Ok(User {
+            uid,
+            user_id,
+            name,
+            privilege,
+            enabled,
+            password,
+            card,
+            group_id,
+        })
+    }
+
+    /// Encode a user for CMD_USER_WRQ with UTF-8 text fields
+    ///
+    /// The user is checked against this layout's limits first.
+    pub fn encode(self, user: &User) -> Result<Vec<u8>> {
+        self.encode_with(user, &Utf8)
+    }
+
+    /// Encode a user, writing the name and group ID with `codec`
+    ///
+    /// Length limits apply to the encoded bytes, so a GBK name may hold
+    /// more CJK characters than a UTF-8 one.
+    pub fn encode_with(self, user: &User, codec: &dyn StringCodec) -> Result<Vec<u8>> {
+        UserBuilder::from(user.clone()).layout(self).validate(codec)?;
+        let name = codec.encode(&user.name)?;
+
+        let mut buf = Vec::with_capacity(self.record_size());
+        buf.extend_from_slice(&user.uid.to_le_bytes());
+        buf.push(encode_privilege(user.privilege, user.enabled));
+
+        match self {
+            Self::Compact => {
+                let group: u8 = match user.group_id.as_str() {
+                    "" => 0,
+                    group_id => parse_field(group_id, "group_id")?,
+                };
+                let user_id: u32 = parse_field(&user.user_id, "user_id")?;
+
+                put_padded(&mut buf, user.password.as_bytes(), 5);
+                put_padded(&mut buf, &name, 8);
+                buf.extend_from_slice(&user.card.to_le_bytes());
+                buf.push(0);
+                buf.push(group);
+                buf.extend_from_slice(&0u16.to_le_bytes()); // timezone: group default
+                buf.extend_from_slice(&user_id.to_le_bytes());
+            }
+            Self::Extended => {
+                put_padded(&mut buf, user.password.as_bytes(), 8);
+                put_padded(&mut buf, &name, 24);
+                buf.extend_from_slice(&user.card.to_le_bytes());
+                buf.push(0);
+                put_padded(&mut buf, &codec.encode(&user.group_id)?, 7);
+                buf.push(0);
+                put_padded(&mut buf, user.user_id.as_bytes(), 24);
+            }
+        }
+
+        debug_assert_eq!(buf.len(), self.record_size());
+        Ok(buf)
+    }
+}
+
+/// Parse a block of user records in a known layout with UTF-8 text fields
+pub fn parse_users(data: &[u8], layout: UserRecordLayout) -> Result<Vec<User>> {
+    parse_users_with(data, layout, &Utf8)
+}
+
+/// Parse a block of user records, reading text fields with `codec`
+pub fn parse_users_with(data: &[u8], layout: UserRecordLayout, codec: &dyn StringCodec) -> Result<Vec<User>> {
+    let size = layout.record_size();
+    if data.len() % size != 0 {
+        return Err(Error::Parse(format!(
+            "{} bytes is not a whole number of {}-byte user records",
+            data.len(),
+            size
+        )));
+    }
+
+    data.chunks_exact(size)
+        .enumerate()
+        .map(|(index, record)| {
+            layout
+                .decode_with(record, codec)
+                .map_err(|e| Error::Parse(format!("user record {}: {}", index, e)))
+        })
+        .collect()
+}
+
+/// Bit 0 of the privilege byte marks a disabled user
+const DISABLED_FLAG: u8 = 0x01;
+
+fn decode_privilege(byte: u8) -> Result<(Privilege, bool)> {
+    let privilege =
+        Privilege::try_from(byte & !DISABLED_FLAG).map_err(|e| Error::Parse(e.to_string()))?;
+    Ok((privilege, byte & DISABLED_FLAG == 0))
+}
+
+fn encode_privilege(privilege: Privilege, enabled: bool) -> u8 {
+    u8::from(privilege) | if enabled { 0 } else { DISABLED_FLAG }
+}
+
+/// Text up to the first NUL (names may hold garbage after it)
+fn padded_str(field: &[u8], codec: &dyn StringCodec) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    codec.decode(&field[..end])
+}
+
+/// Write `bytes` NUL-padded to `len` bytes (length already validated)
+fn put_padded(buf: &mut Vec<u8>, bytes: &[u8], len: usize) {
+    buf.extend_from_slice(bytes);
+    buf.resize(buf.len() + len - bytes.len(), 0);
+}
+
+fn parse_field<T: std::str::FromStr>(value: &str, field: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::Validation(format!("{} {:?} does not fit the compact layout", field, value)))
+}
+
+/// User enrolled on a device
+///
+/// Construct with [`User::builder`] so device limits are checked before
+/// anything is sent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct User {
+    /// Internal record index (1-based)
+    pub uid: u16,
+
+    /// User ID shown on the device (digits only)
+    pub user_id: String,
+
+    /// Display name
+    pub name: String,
+
+    /// Privilege level
+    pub privilege: Privilege,
+
+    /// Whether the user may verify
+    pub enabled: bool,
+
+    /// Numeric password, empty if unset
+    pub password: String,
+
+    /// Card number, 0 if unset
+    pub card: u32,
+
+    /// Group ID, empty for the default group
+    pub group_id: String,
+}
+
+impl User {
+    /// Start building a user with the given record index and user ID
+    pub fn builder(uid: u16, user_id: impl Into<String>) -> UserBuilder {
+        UserBuilder::new(uid, user_id)
+    }
+}
+
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "User[{}: {}]", self.user_id, self.name)
+    }
+}
+
+/// Builder for [`User`]
+///
+/// # Examples
+///
+/// ```
+/// use zkrust_core::constants::Privilege;
+/// use zkrust_types::User;
+///
+/// let user = User::builder(1, "1001")
+///     .name("Alice")
+///     .password("1234")
+///     .card(4_000_000_000)
+///     .privilege(Privilege::Admin)
+///     .build()
+///     .unwrap();
+/// assert_eq!(user.card, 4_000_000_000);
+///
+/// assert!(User::builder(2, "12ab").build().is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct UserBuilder {
+    uid: u16,
+    user_id: String,
+    name: String,
+    privilege: Privilege,
+    enabled: bool,
+    password: String,
+    card: u64,
+    group_id: String,
+    layout: UserRecordLayout,
+    codec: Option<Arc<dyn StringCodec>>,
+}
+
+impl From<User> for UserBuilder {
+    fn from(user: User) -> Self {
+        Self {
+            uid: user.uid,
+            user_id: user.user_id,
+            name: user.name,
+            privilege: user.privilege,
+            enabled: user.enabled,
+            password: user.password,
+            card: user.card as u64,
+            group_id: user.group_id,
+            layout: UserRecordLayout::default(),
+            codec: None,
+        }
+    }
+}
+
+impl UserBuilder {
+    /// Create a builder (normal privilege, enabled, no credentials)
+    pub fn new(uid: u16, user_id: impl Into<String>) -> Self {
+        Self {
+            uid,
+            user_id: user_id.into(),
+            name: String::new(),
+            privilege: Privilege::User,
+            enabled: true,
+            password: String::new(),
+            card: 0,
+            group_id: String::new(),
+            layout: UserRecordLayout::default(),
+            codec: None,
+        }
+    }
+
+    /// Set the display name
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Set the privilege level
+    pub fn privilege(mut self, privilege: Privilege) -> Self {
+        self.privilege = privilege;
+        self
+    }
+
+    /// Enable or disable the user
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Set the numeric password
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = password.into();
+        self
+    }
+
+    /// Set the card number
+    pub fn card(mut self, card: u64) -> Self {
+        self.card = card;
+        self
+    }
+
+    /// Set the group ID
+    pub fn group_id(mut self, group_id: impl Into<String>) -> Self {
+        self.group_id = group_id.into();
+        self
+    }
+
+    /// Validate against the limits of `layout` (default: extended)
+    pub fn layout(mut self, layout: UserRecordLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Measure the name and group ID in `codec` bytes (default: UTF-8)
+    ///
+    /// Use the codec of the device the user is for, e.g.
+    /// `Device::string_codec`, so names are checked against what the
+    /// device will store.
+    pub fn codec(mut self, codec: Arc<dyn StringCodec>) -> Self {
+        self.codec = Some(codec);
+        self
+    }
+
+    /// Validate and build the user
+    pub fn build(self) -> Result<User> {
+        self.validate(self.codec.as_deref().unwrap_or(&Utf8))?;
+
+        Ok(User {
+            uid: self.uid,
+            user_id: self.user_id,
+            name: self.name,
+            privilege: self.privilege,
+            enabled: self.enabled,
+            password: self.password,
+            card: self.card as u32,
+            group_id: self.group_id,
+        })
+    }
+
+    /// Check the fields against the layout limits, measuring text in `codec`
+    fn validate(&self, codec: &dyn StringCodec) -> Result<()> {
+        if self.uid == 0 {
+            return Err(Error::Validation("uid must be at least 1".into()));
+        }
+
+        if self.user_id.is_empty()
+            || self.user_id.len() > MAX_USER_ID_DIGITS
+            || !self.user_id.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(Error::Validation(format!(
+                "user_id {:?} must be 1-{} digits",
+                self.user_id, MAX_USER_ID_DIGITS
+            )));
+        }
+
+        let max_name = self.layout.max_name_len();
+        let name_len = codec.encode(&self.name)?.len();
+        if name_len > max_name {
+            return Err(Error::Validation(format!(
+                "name is {} {} bytes, limit is {}",
+                name_len,
+                codec.name(),
+                max_name
+            )));
+        }
+
+        let max_password = self.layout.max_password_len();
+        if self.password.len() > max_password || !self.password.bytes().all(|b| b.is_ascii_digit()) {
+            // Don't echo the password
+            return Err(Error::Validation(format!(
+                "password must be at most {} digits",
+                max_password
+            )));
+        }
+
+        if self.card > MAX_CARD {
+            return Err(Error::Validation(format!(
+                "card {} exceeds the maximum {}",
+                self.card, MAX_CARD
+            )));
+        }
+
+        // The compact layout stores the group as a single byte
+        if self.layout == UserRecordLayout::Compact {
+            if !self.group_id.is_empty() && self.group_id.parse::<u8>().is_err() {
+                return Err(Error::Validation(format!(
+                    "group_id {:?} must be a number 0-255 in the compact layout",
+                    self.group_id
+                )));
+            }
+        } else if codec.encode(&self.group_id)?.len() > MAX_GROUP_ID_LEN {
+            return Err(Error::Validation(format!(
+                "group_id {:?} is longer than {} bytes",
+                self.group_id, MAX_GROUP_ID_LEN
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_layout() {
+        assert_eq!(UserRecordLayout::detect(72 * 7, 7), Some(UserRecordLayout::Extended));
+        assert_eq!(UserRecordLayout::detect(28 * 18, 18), Some(UserRecordLayout::Compact));
+        assert_eq!(UserRecordLayout::detect(30, 1), None);
+        assert_eq!(UserRecordLayout::detect(0, 0), None);
+    }
+
+    #[test]
+    fn test_build_user() {
+        let user = User::builder(1, "1001")
+            .name("Alice")
+            .password("12345678")
+            .group_id("1")
+            .build()
+            .unwrap();
+
+        assert_eq!(user.user_id, "1001");
+        assert_eq!(user.privilege, Privilege::User);
+        assert!(user.enabled);
+        assert_eq!(user.card, 0);
+    }
+
+    #[test]
+    fn test_user_id_must_be_numeric() {
+        assert!(User::builder(1, "").build().is_err());
+        assert!(User::builder(1, "12a").build().is_err());
+        assert!(User::builder(1, "1234567890").build().is_err());
+        assert!(User::builder(0, "1").build().is_err());
+    }
+
+    #[test]
+    fn test_layout_limits() {
+        let long_name = "x".repeat(9);
+        assert!(User::builder(1, "1").name(&long_name).build().is_ok());
+        assert!(User::builder(1, "1")
+            .name(&long_name)
+            .layout(UserRecordLayout::Compact)
+            .build()
+            .is_err());
+
+        assert!(User::builder(1, "1")
+            .password("123456")
+            .layout(UserRecordLayout::Compact)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_password_and_card_limits() {
+        let err = User::builder(1, "1").password("12ab").build().unwrap_err();
+        assert!(!err.to_string().contains("12ab"));
+
+        assert!(User::builder(1, "1").card(MAX_CARD).build().is_ok());
+        assert!(User::builder(1, "1").card(MAX_CARD + 1).build().is_err());
+        assert!(User::builder(1, "1").group_id("12345678").build().is_err());
+    }
+
+    fn sample() -> User {
+        User::builder(3, "1003")
+            .name("Bob")
+            .password("4321")
+            .card(123_456)
+            .group_id("2")
+            .privilege(Privilege::Manager)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_user_record_roundtrip() {
+        let user = sample();
+
+        for layout in [UserRecordLayout::Compact, UserRecordLayout::Extended] {
+            let encoded = layout.encode(&user).unwrap();
+            assert_eq!(encoded.len(), layout.record_size());
+            assert_eq!(layout.decode(&encoded).unwrap(), user, "{:?}", layout);
+        }
+    }
+
+    #[test]
+    fn test_decode_captured_extended() {
+        // uid 1, admin, password "123", name "Alice", card 0x00BC614E,
+        // group "1", user_id "1001"
+        let mut record = vec![0x01, 0x00, 0x0E];
+        record.extend(b"123\0\0\0\0\0");
+        record.extend(b"Alice");
+        record.extend([0; 19]);
+        record.extend([0x4E, 0x61, 0xBC, 0x00, 0x00]);
+        record.extend(b"1\0\0\0\0\0\0\0");
+        record.extend(b"1001");
+        record.extend([0; 20]);
+
+        let user = UserRecordLayout::Extended.decode(&record).unwrap();
+        assert_eq!(user.uid, 1);
+        assert_eq!(user.privilege, Privilege::Admin);
+        assert!(user.enabled);
+        assert_eq!(user.name, "Alice");
+        assert_eq!(user.card, 12_345_678);
+        assert_eq!(user.group_id, "1");
+        assert_eq!(user.user_id, "1001");
+    }
+
+    #[test]
+    fn test_privilege_disabled_bit() {
+        let user = User::builder(1, "1").enabled(false).build().unwrap();
+        let encoded = UserRecordLayout::Compact.encode(&user).unwrap();
+
+        assert_eq!(encoded[2], 0x01);
+        assert!(!UserRecordLayout::Compact.decode(&encoded).unwrap().enabled);
+    }
+
+    #[test]
+    fn test_encode_rejects_layout_limits() {
+        let user = User::builder(1, "1").name("A long name").build().unwrap();
+        assert!(UserRecordLayout::Extended.encode(&user).is_ok());
+        assert!(UserRecordLayout::Compact.encode(&user).is_err());
+    }
+
+    #[test]
+    fn test_parse_users() {
+        let layout = UserRecordLayout::Compact;
+        let mut data = layout.encode(&sample()).unwrap();
+        data.extend(layout.encode(&User::builder(4, "9").build().unwrap()).unwrap());
+
+        let users = parse_users(&data, layout).unwrap();
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[1].user_id, "9");
+        assert!(parse_users(&data[..30], layout).is_err());
+    }
+
+    #[test]
+    fn test_codec_fields() {
+        use crate::text::Latin1;
+
+        let user = User::builder(1, "7").name("Zoë").group_id("Ré").build().unwrap();
+        let layout = UserRecordLayout::Extended;
+
+        let record = layout.encode_with(&user, &Latin1).unwrap();
+        assert_eq!(&record[11..15], b"Zo\xeb\0");
+        assert_eq!(layout.decode_with(&record, &Latin1).unwrap(), user);
+
+        // Read back as UTF-8, the Latin-1 bytes are mojibake
+        assert_eq!(layout.decode(&record).unwrap().name, "Zo\u{FFFD}");
+
+        let cyrillic = User::builder(2, "8").name("Юлия").build().unwrap();
+        assert!(layout.encode_with(&cyrillic, &Latin1).is_err());
+    }
+
+    #[cfg(feature = "gbk")]
+    #[test]
+    fn test_gbk_name_limit() {
+        use crate::text::Gbk;
+
+        // 12 characters: 36 bytes in UTF-8, 24 in GBK
+        let name = "张伟李娜王芳刘洋陈静杨磊";
+        assert!(User::builder(1, "1").name(name).build().is_err());
+
+        let user = User::builder(1, "1").name(name).codec(Arc::new(Gbk)).build().unwrap();
+        let record = UserRecordLayout::Extended.encode_with(&user, &Gbk).unwrap();
+        assert_eq!(UserRecordLayout::Extended.decode_with(&record, &Gbk).unwrap().name, name);
+        assert!(UserRecordLayout::Extended.encode(&user).is_err());
+    }
+}
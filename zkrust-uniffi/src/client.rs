@@ -0,0 +1,192 @@
+//! Blocking device client
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::runtime::{Builder, Runtime};
+use zkrust::{Device, DeviceHandle};
+
+use crate::error::ZkError;
+use crate::types::{format_time, parse_time, Attendance, DeviceInfo, User};
+
+/// Connection settings for [`ZkClient::connect`]
+#[derive(Debug, Clone, Copy, Default, uniffi::Record)]
+pub struct ConnectOptions {
+    /// Use UDP instead of TCP
+    #[uniffi(default = false)]
+    pub udp: bool,
+    /// CommKey password, 0 if the device has none
+    #[uniffi(default = 0)]
+    pub password: u32,
+    /// Per-command timeout in milliseconds, 0 for the library default
+    #[uniffi(default = 0)]
+    pub timeout_ms: u32,
+}
+
+/// Connection to one device
+///
+/// Every method blocks until the device answers, so call them off the UI
+/// thread. Methods may be called from several threads; commands are sent
+/// one at a time. Call [`disconnect`](Self::disconnect) when done, otherwise
+/// the device only notices the session is gone when it times out.
+#[derive(uniffi::Object)]
+pub struct ZkClient {
+    runtime: Runtime,
+    handle: DeviceHandle,
+}
+
+impl ZkClient {
+    fn block_on<T>(&self, future: impl Future<Output = zkrust::Result<T>>) -> Result<T, ZkError> {
+        Ok(self.runtime.block_on(future)?)
+    }
+}
+
+#[uniffi::export]
+impl ZkClient {
+    /// Connect and authenticate
+    #[uniffi::constructor]
+    pub fn connect(host: String, port: u16, options: ConnectOptions) -> Result<Arc<Self>, ZkError> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("zkrust")
+            .enable_all()
+            .build()
+            .map_err(|e| ZkError::Failed {
+                message: format!("Failed to start runtime: {}", e),
+            })?;
+
+        let mut device = if options.udp {
+            Device::new_udp(&host, port)
+        } else {
+            Device::new(&host, port)
+        };
+        if options.password != 0 {
+            device = device.with_password(options.password);
+        }
+        if options.timeout_ms != 0 {
+            device = device.with_timeout(Duration::from_millis(u64::from(options.timeout_ms)));
+        }
+
+        let handle = runtime.block_on(async move {
+            device.connect().await?;
+            Ok::<_, zkrust::Error>(device.into_handle())
+        })?;
+        Ok(Arc::new(Self { runtime, handle }))
+    }
+
+    /// Whether the session is still open
+    pub fn is_connected(&self) -> bool {
+        self.block_on(self.handle.is_connected()).unwrap_or(false)
+    }
+
+    /// End the session
+    pub fn disconnect(&self) -> Result<(), ZkError> {
+        self.block_on(self.handle.disconnect())
+    }
+
+    pub fn device_info(&self) -> Result<DeviceInfo, ZkError> {
+        self.block_on(self.handle.get_device_info()).map(DeviceInfo::from)
+    }
+
+    /// Device clock as a local ISO 8601 string
+    pub fn get_time(&self) -> Result<String, ZkError> {
+        self.block_on(self.handle.get_time()).map(format_time)
+    }
+
+    /// Set the device clock from a local ISO 8601 string
+    pub fn set_time(&self, time: String) -> Result<(), ZkError> {
+        let time = parse_time(&time)?;
+        self.block_on(self.handle.set_time(time))
+    }
+
+    pub fn get_users(&self) -> Result<Vec<User>, ZkError> {
+        let users = self.block_on(self.handle.get_users())?;
+        Ok(users.into_iter().map(User::from).collect())
+    }
+
+    /// Create or replace a user
+    pub fn set_user(&self, user: User) -> Result<(), ZkError> {
+        let user = zkrust_types::User::try_from(user)?;
+        self.block_on(self.handle.set_user(user))
+    }
+
+    pub fn delete_user(&self, uid: u16) -> Result<(), ZkError> {
+        self.block_on(self.handle.delete_user(uid))
+    }
+
+    pub fn get_attendance(&self) -> Result<Vec<Attendance>, ZkError> {
+        let records = self.block_on(self.handle.get_attendance())?;
+        Ok(records.into_iter().map(Attendance::from).collect())
+    }
+
+    /// Restart the device, ending the session
+    pub fn restart(&self) -> Result<(), ZkError> {
+        self.block_on(self.handle.restart())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+    use zkrust_emulator::{Emulator, EmulatorHandle};
+    use zkrust_types::AttendanceRecord;
+
+    use crate::types::Privilege;
+
+    /// Emulator on its own runtime, kept alive alongside the handle
+    fn emulator() -> (Runtime, EmulatorHandle) {
+        let runtime = Builder::new_multi_thread().worker_threads(1).enable_all().build().unwrap();
+        let time = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let emulator = runtime.block_on(
+            Emulator::new()
+                .with_commkey(1234)
+                .with_user(zkrust_types::User::builder(1, "42").name("Ann").build().unwrap())
+                .with_attendance(AttendanceRecord::new("42", time, VerifyMode::Card, PunchType::CheckIn))
+                .bind_tcp("127.0.0.1:0"),
+        );
+        (runtime, emulator.unwrap())
+    }
+
+    fn options(password: u32) -> ConnectOptions {
+        ConnectOptions {
+            password,
+            timeout_ms: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_client() {
+        let (_runtime, emulator) = emulator();
+        let port = emulator.local_addr().port();
+        let client = ZkClient::connect("127.0.0.1".into(), port, options(1234)).unwrap();
+        assert!(client.is_connected());
+
+        let records = client.get_attendance().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].time, "2024-03-01T08:00:00");
+        assert_eq!(records[0].verify_mode, 3);
+
+        let mut user = client.get_users().unwrap().remove(0);
+        assert_eq!(user.name, "Ann");
+        user.uid = 2;
+        user.user_id = "43".into();
+        user.privilege = Privilege::Admin;
+        client.set_user(user).unwrap();
+        assert_eq!(client.get_users().unwrap().len(), 2);
+
+        client.disconnect().unwrap();
+        assert!(!client.is_connected());
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let (_runtime, emulator) = emulator();
+        let port = emulator.local_addr().port();
+        let result = ZkClient::connect("127.0.0.1".into(), port, options(999));
+        assert!(matches!(result, Err(ZkError::AuthFailed { .. })));
+    }
+}
@@ -0,0 +1,65 @@
+//! Errors raised to Kotlin and Swift
+
+use zkrust::Error;
+use zkrust_core::Error as CoreError;
+
+/// Failure of a binding call
+///
+/// Thrown as an exception in Kotlin and Swift; the variants group errors
+/// by what an app would do about them.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum ZkError {
+    /// Bad argument, such as an invalid user ID
+    #[error("{message}")]
+    InvalidArgument { message: String },
+
+    /// Device unreachable or the connection dropped
+    #[error("{message}")]
+    Unavailable { message: String },
+
+    /// Device did not answer in time
+    #[error("{message}")]
+    Timeout { message: String },
+
+    /// Device rejected the CommKey password
+    #[error("{message}")]
+    AuthFailed { message: String },
+
+    /// Client was disconnected
+    #[error("{message}")]
+    NotConnected { message: String },
+
+    /// Any other failure
+    #[error("{message}")]
+    Failed { message: String },
+}
+
+impl From<Error> for ZkError {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        match error.root() {
+            Error::Core(CoreError::Timeout { .. }) | Error::Timeout { .. } | Error::OperationTimeout(_) => {
+                Self::Timeout { message }
+            }
+            Error::Core(CoreError::AuthenticationRequired | CoreError::AuthenticationFailed) => {
+                Self::AuthFailed { message }
+            }
+            Error::Transport(_) | Error::DeviceBusy { .. } => Self::Unavailable { message },
+            Error::NotConnected | Error::HandleClosed => Self::NotConnected { message },
+            Error::Types(_) => Self::InvalidArgument { message },
+            _ => Self::Failed { message },
+        }
+    }
+}
+
+impl From<CoreError> for ZkError {
+    fn from(error: CoreError) -> Self {
+        Self::from(Error::Core(error))
+    }
+}
+
+impl From<zkrust_types::Error> for ZkError {
+    fn from(error: zkrust_types::Error) -> Self {
+        Self::from(Error::Types(error))
+    }
+}
@@ -0,0 +1,35 @@
+//! # zkrust-uniffi
+//!
+//! [UniFFI](https://mozilla.github.io/uniffi-rs/) bindings for Kotlin and
+//! Swift apps, such as installer tools on Android and iOS. Exposes the
+//! protocol primitives and [`ZkClient`], a simplified blocking client.
+//!
+//! Generate the sources from the built library:
+//!
+//! ```text
+//! cargo build -p zkrust-uniffi --release
+//! cargo run -p zkrust-uniffi --features bindgen --bin uniffi-bindgen -- generate \
+//!     --library target/release/libzkrust_uniffi.so --language kotlin --out-dir out
+//! ```
+//!
+//! ## Example (Kotlin)
+//!
+//! ```kotlin
+//! val client = ZkClient.connect("192.168.1.201", 4370u, ConnectOptions(password = 1234u))
+//! for (record in client.getAttendance()) {
+//!     println("${record.userId} ${record.time}")
+//! }
+//! client.disconnect()
+//! ```
+
+pub mod client;
+pub mod error;
+pub mod protocol;
+pub mod types;
+
+pub use client::{ConnectOptions, ZkClient};
+pub use error::ZkError;
+pub use protocol::PacketInfo;
+pub use types::{Attendance, DeviceInfo, Privilege, User};
+
+uniffi::setup_scaffolding!();
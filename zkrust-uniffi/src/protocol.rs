@@ -0,0 +1,78 @@
+//! Protocol primitives for tools that build or inspect frames themselves
+
+use zkrust_core::auth;
+use zkrust_core::{checksum, Command, Packet};
+
+use crate::error::ZkError;
+
+/// Decoded frame
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct PacketInfo {
+    pub command: u16,
+    /// Protocol name of the command, e.g. "CMD_CONNECT"
+    pub command_name: String,
+    pub session_id: u16,
+    pub reply_id: u16,
+    pub payload: Vec<u8>,
+}
+
+/// Encode a frame (without the TCP wrapper)
+#[uniffi::export]
+pub fn encode_packet(command: u16, session_id: u16, reply_id: u16, payload: Vec<u8>) -> Result<Vec<u8>, ZkError> {
+    let command = Command::try_from(command)?;
+    Ok(Packet::with_payload(command, session_id, reply_id, payload).encode().to_vec())
+}
+
+/// Decode a frame (without the TCP wrapper), verifying its checksum
+#[uniffi::export]
+pub fn decode_packet(frame: Vec<u8>) -> Result<PacketInfo, ZkError> {
+    let packet = Packet::decode(frame.as_slice().into())?;
+    Ok(PacketInfo {
+        command: packet.command.into(),
+        command_name: packet.command.name().to_string(),
+        session_id: packet.session_id,
+        reply_id: packet.reply_id,
+        payload: packet.payload.to_vec(),
+    })
+}
+
+/// Checksum of a frame's fields
+#[uniffi::export]
+pub fn calculate_checksum(command: u16, session_id: u16, reply_id: u16, payload: Vec<u8>) -> u16 {
+    checksum::calculate(command, session_id, reply_id, &payload)
+}
+
+/// CommKey sent in CMD_AUTH
+#[uniffi::export]
+pub fn make_commkey(password: u32, session_id: u16, ticks: u8) -> Vec<u8> {
+    auth::make_commkey(password, session_id, ticks).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_roundtrip() {
+        let frame = encode_packet(1102, 0x1234, 2, vec![1, 2, 3]).unwrap();
+        assert_eq!(calculate_checksum(1102, 0x1234, 2, vec![1, 2, 3]), u16::from_le_bytes([frame[2], frame[3]]));
+
+        let packet = decode_packet(frame.clone()).unwrap();
+        assert_eq!(packet.command, 1102);
+        assert_eq!(packet.command_name, "CMD_AUTH");
+        assert_eq!((packet.session_id, packet.reply_id), (0x1234, 2));
+        assert_eq!(packet.payload, vec![1, 2, 3]);
+
+        let mut corrupted = frame;
+        corrupted[8] ^= 0xFF;
+        assert!(matches!(decode_packet(corrupted), Err(ZkError::Failed { .. })));
+    }
+
+    #[test]
+    fn test_make_commkey() {
+        assert_eq!(
+            make_commkey(1234, 0x5678, 50),
+            zkrust_core::make_commkey(1234, 0x5678, 50).to_vec()
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Records passed to and from Kotlin and Swift
+//!
+//! Times are device-local ISO 8601 strings without an offset
+//! ("2024-03-01T08:30:00"), which parse directly into `LocalDateTime` on
+//! Android and with an `ISO8601DateFormatter` on iOS.
+
+use chrono::NaiveDateTime;
+use zkrust_core::constants;
+
+use crate::error::ZkError;
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+pub(crate) fn format_time(time: NaiveDateTime) -> String {
+    time.format(TIME_FORMAT).to_string()
+}
+
+pub(crate) fn parse_time(time: &str) -> Result<NaiveDateTime, ZkError> {
+    NaiveDateTime::parse_from_str(time, TIME_FORMAT).map_err(|e| ZkError::InvalidArgument {
+        message: format!("Invalid time {:?}: {}", time, e),
+    })
+}
+
+/// User privilege level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum Privilege {
+    User,
+    Enroller,
+    Manager,
+    Admin,
+}
+
+impl From<constants::Privilege> for Privilege {
+    fn from(privilege: constants::Privilege) -> Self {
+        match privilege {
+            constants::Privilege::User => Self::User,
+            constants::Privilege::Enroller => Self::Enroller,
+            constants::Privilege::Manager => Self::Manager,
+            constants::Privilege::Admin => Self::Admin,
+        }
+    }
+}
+
+impl From<Privilege> for constants::Privilege {
+    fn from(privilege: Privilege) -> Self {
+        match privilege {
+            Privilege::User => Self::User,
+            Privilege::Enroller => Self::Enroller,
+            Privilege::Manager => Self::Manager,
+            Privilege::Admin => Self::Admin,
+        }
+    }
+}
+
+/// Device user
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct User {
+    /// Internal record index, 1 or more
+    pub uid: u16,
+    /// User ID shown on the device (digits only)
+    pub user_id: String,
+    pub name: String,
+    pub privilege: Privilege,
+    /// Whether the user may verify
+    pub enabled: bool,
+    /// Numeric password, empty if unset
+    pub password: String,
+    /// Card number, 0 if unset
+    pub card: u32,
+    /// Group ID, empty for the default group
+    pub group_id: String,
+}
+
+impl From<zkrust_types::User> for User {
+    fn from(user: zkrust_types::User) -> Self {
+        Self {
+            uid: user.uid,
+            user_id: user.user_id,
+            name: user.name,
+            privilege: user.privilege.into(),
+            enabled: user.enabled,
+            password: user.password,
+            card: user.card,
+            group_id: user.group_id,
+        }
+    }
+}
+
+impl TryFrom<User> for zkrust_types::User {
+    type Error = ZkError;
+
+    fn try_from(user: User) -> Result<Self, ZkError> {
+        Ok(zkrust_types::User::builder(user.uid, user.user_id)
+            .name(user.name)
+            .privilege(user.privilege.into())
+            .enabled(user.enabled)
+            .password(user.password)
+            .card(u64::from(user.card))
+            .group_id(user.group_id)
+            .build()?)
+    }
+}
+
+/// Attendance log entry
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct Attendance {
+    pub user_id: String,
+    /// Device-local time, see the module docs
+    pub time: String,
+    /// Verification mode code (1 fingerprint, 3 card, 15 face, ...)
+    pub verify_mode: u8,
+    /// Punch state code (0 check-in, 1 check-out, ...)
+    pub punch: u8,
+    pub work_code: Option<u32>,
+}
+
+impl From<zkrust_types::AttendanceRecord> for Attendance {
+    fn from(record: zkrust_types::AttendanceRecord) -> Self {
+        Self {
+            time: format_time(record.timestamp),
+            verify_mode: u8::from(record.verify_mode),
+            punch: u8::from(record.punch),
+            work_code: record.work_code,
+            user_id: record.user_id,
+        }
+    }
+}
+
+/// Device identification
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct DeviceInfo {
+    pub serial_number: String,
+    pub firmware_version: String,
+    pub model: Option<String>,
+    pub platform: Option<String>,
+    pub device_name: Option<String>,
+    pub mac_address: Option<String>,
+}
+
+impl From<zkrust_types::DeviceInfo> for DeviceInfo {
+    fn from(info: zkrust_types::DeviceInfo) -> Self {
+        Self {
+            serial_number: info.serial_number,
+            firmware_version: info.firmware_version,
+            model: info.model,
+            platform: info.platform,
+            device_name: info.device_name,
+            mac_address: info.mac_address,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_roundtrip() {
+        let time = parse_time("2024-03-01T08:30:05").unwrap();
+        assert_eq!(format_time(time), "2024-03-01T08:30:05");
+        assert!(matches!(parse_time("08:30"), Err(ZkError::InvalidArgument { .. })));
+    }
+
+    #[test]
+    fn test_user_conversion() {
+        let user = User {
+            uid: 3,
+            user_id: "1003".into(),
+            name: "Jane".into(),
+            privilege: Privilege::Admin,
+            enabled: true,
+            password: "".into(),
+            card: 42,
+            group_id: "".into(),
+        };
+        let native = zkrust_types::User::try_from(user.clone()).unwrap();
+        assert_eq!(native.privilege, constants::Privilege::Admin);
+        assert_eq!(User::from(native), user);
+
+        let invalid = User {
+            user_id: "abc".into(),
+            ..user
+        };
+        assert!(matches!(
+            zkrust_types::User::try_from(invalid),
+            Err(ZkError::InvalidArgument { .. })
+        ));
+    }
+}
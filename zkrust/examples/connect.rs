@@ -1,33 +1,32 @@
-//! Simple connection example
-
-use tracing_subscriber;
-use zkrust::Device;
-
-#[tokio::main]
-async fn main() -> zkrust::Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::TRACE)
-        .init();
-    
-    // Change to your device IP
-    let ip = std::env::var("DEVICE_IP").unwrap_or_else(|_| "192.168.1.201".to_string());
-    
-    println!("Connecting to {}...", ip);
-    
-    let mut device = Device::new(ip, 4370);
-    
-    // Connect
-    device.connect().await?;
-    println!("✓ Connected!");
-    
-    // Get device info
-    let info = device.get_device_info().await?;
-    println!("✓ Device: {}", info);
-    
-    // Disconnect
-    device.disconnect().await?;
-    println!("✓ Disconnected");
-    
-    Ok(())
+//! Simple connection example
+
+use zkrust::Device;
+
+#[tokio::main]
+async fn main() -> zkrust::Result<()> {
+    // Initialize logging
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .init();
+    
+    // Change to your device IP
+    let ip = std::env::var("DEVICE_IP").unwrap_or_else(|_| "192.168.1.201".to_string());
+    
+    println!("Connecting to {}...", ip);
+    
+    let mut device = Device::new(ip, 4370);
+    
+    // Connect
+    device.connect().await?;
+    println!("✓ Connected!");
+    
+    // Get device info
+    let info = device.get_device_info().await?;
+    println!("✓ Device: {}", info);
+    
+    // Disconnect
+    device.disconnect().await?;
+    println!("✓ Disconnected");
+    
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,323 @@
+//! Attendance merged across devices
+//!
+//! Sites with several terminals at one entrance log the same person more
+//! than once when they try a second reader. [`merge_attendance`] combines
+//! the logs of many devices into one chronological list, tags each record
+//! with the device it came from, and folds punches by the same user in the
+//! same minute on different terminals into one record.
+//!
+//! With the `tz` feature, devices given a timezone
+//! (`Device::with_timezone`) are compared by
+//! UTC instead of local time, so sites in different zones merge correctly.
+//!
+//! ```no_run
+//! use zkrust::{Device, Fleet};
+//!
+//! # async fn example() {
+//! let mut fleet = Fleet::new()
+//!     .with_device("entrance-a", Device::new("192.168.1.201", 4370))
+//!     .with_device("entrance-b", Device::new("192.168.1.202", 4370));
+//!
+//! let merged = fleet.aggregate_attendance().await;
+//! for tagged in &merged.records {
+//!     println!("{} {} via {}", tagged.record.timestamp, tagged.record.user_id, tagged.device);
+//! }
+//! for (name, error) in &merged.failed {
+//!     eprintln!("{}: {}", name, error);
+//! }
+//! # }
+//! ```
+
+use chrono::{DateTime, NaiveDateTime, Timelike, Utc};
+
+use zkrust_types::AttendanceRecord;
+
+use crate::device::Device;
+use crate::error::Error;
+use crate::fleet::Fleet;
+
+/// Attendance record and the device it was read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedRecord {
+    /// Name of the device the record was kept from
+    pub device: String,
+
+    /// The punch
+    pub record: AttendanceRecord,
+
+    /// Other devices that logged the same user in the same minute
+    pub also_seen_on: Vec<String>,
+
+    /// Timestamp in UTC, if the device's timezone is known
+    pub utc: Option<DateTime<Utc>>,
+}
+
+impl TaggedRecord {
+    /// Time used for ordering: UTC if known, else device local time
+    fn instant(&self) -> NaiveDateTime {
+        instant(&self.record, self.utc)
+    }
+}
+
+/// Result of [`Fleet::aggregate_attendance`]
+#[derive(Debug, Default)]
+pub struct Aggregated {
+    /// Merged records ordered by time (UTC where known), then user, then
+    /// device
+    pub records: Vec<TaggedRecord>,
+
+    /// Number of records folded into a record from another device
+    pub duplicates: usize,
+
+    /// Devices whose log could not be read
+    pub failed: Vec<(String, Error)>,
+}
+
+/// Merge per-device attendance logs
+///
+/// Records of the same user in the same minute from different devices are
+/// folded into the earliest one (ties go to the device listed first). Repeat
+/// punches on one device are kept; see `zkrust_sync::normalize` for
+/// collapsing those. Timestamps are compared as read; see
+/// [`Fleet::aggregate_attendance`] for devices in different timezones.
+pub fn merge_attendance<I, S>(logs: I) -> (Vec<TaggedRecord>, usize)
+where
+    I: IntoIterator<Item = (S, Vec<AttendanceRecord>)>,
+    S: Into<String>,
+{
+    merge(
+        logs.into_iter()
+            .map(|(device, records)| (device, records.into_iter().map(|record| (record, None)).collect())),
+    )
+}
+
+/// [`merge_attendance`] over records paired with their UTC time, if known
+fn merge<I, S>(logs: I) -> (Vec<TaggedRecord>, usize)
+where
+    I: IntoIterator<Item = (S, Vec<(AttendanceRecord, Option<DateTime<Utc>>)>)>,
+    S: Into<String>,
+{
+    let mut all: Vec<(usize, TaggedRecord)> = Vec::new();
+    for (index, (device, records)) in logs.into_iter().enumerate() {
+        let device = device.into();
+        all.extend(records.into_iter().map(|(record, utc)| {
+            let tagged = TaggedRecord {
+                device: device.clone(),
+                record,
+                also_seen_on: Vec::new(),
+                utc,
+            };
+            (index, tagged)
+        }));
+    }
+    all.sort_by(|(a_index, a), (b_index, b)| {
+        let a_key = (&a.record.user_id, minute(a.instant()), a.instant(), a_index);
+        a_key.cmp(&(&b.record.user_id, minute(b.instant()), b.instant(), b_index))
+    });
+
+    let mut merged: Vec<TaggedRecord> = Vec::with_capacity(all.len());
+    let mut group_start = 0;
+    let mut duplicates = 0;
+    for (_, tagged) in all {
+        let same_group = merged.get(group_start).is_some_and(|first| {
+            first.record.user_id == tagged.record.user_id && minute(first.instant()) == minute(tagged.instant())
+        });
+        if !same_group {
+            group_start = merged.len();
+        }
+
+        let existing = merged[group_start..].iter_mut().find(|kept| kept.device != tagged.device);
+        match existing {
+            Some(kept) => {
+                if !kept.also_seen_on.contains(&tagged.device) {
+                    kept.also_seen_on.push(tagged.device);
+                }
+                duplicates += 1;
+            }
+            None => merged.push(tagged),
+        }
+    }
+
+    merged.sort_by(|a, b| {
+        let a_key = (a.instant(), &a.record.user_id, &a.device);
+        a_key.cmp(&(b.instant(), &b.record.user_id, &b.device))
+    });
+    (merged, duplicates)
+}
+
+/// UTC time if known, else the local timestamp
+fn instant(record: &AttendanceRecord, utc: Option<DateTime<Utc>>) -> NaiveDateTime {
+    utc.map_or(record.timestamp, |utc| utc.naive_utc())
+}
+
+/// UTC time of `record`, if `device` has a timezone
+#[cfg(feature = "tz")]
+fn utc_of(device: &Device, record: &AttendanceRecord) -> Option<DateTime<Utc>> {
+    device
+        .timezone()
+        .map(|timezone| record.localized(&timezone).with_timezone(&Utc))
+}
+
+#[cfg(not(feature = "tz"))]
+fn utc_of(_device: &Device, _record: &AttendanceRecord) -> Option<DateTime<Utc>> {
+    None
+}
+
+/// Start of the minute `time` falls in
+fn minute(time: NaiveDateTime) -> NaiveDateTime {
+    time.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(time)
+}
+
+impl Fleet {
+    /// Download the attendance log of every device and merge them
+    ///
+    /// Disconnected devices are connected first and left connected. A
+    /// device that fails is listed in [`Aggregated::failed`]; the others
+    /// are still merged. Records of devices with a timezone carry their UTC
+    /// time and are merged by it.
+    pub async fn aggregate_attendance(&mut self) -> Aggregated {
+        let report = self
+            .run(|device| {
+                Box::pin(async move {
+                    if !device.is_connected() {
+                        device.connect().await?;
+                    }
+                    let records = device.get_attendance().await?;
+                    Ok(records
+                        .into_iter()
+                        .map(|record| {
+                            let utc = utc_of(device, &record);
+                            (record, utc)
+                        })
+                        .collect::<Vec<_>>())
+                })
+            })
+            .await;
+
+        let mut logs = Vec::new();
+        let mut failed = Vec::new();
+        for outcome in report.outcomes {
+            match outcome.result {
+                Ok(records) => logs.push((outcome.name, records)),
+                Err(e) => failed.push((outcome.name, e)),
+            }
+        }
+
+        let (records, duplicates) = merge(logs);
+        Aggregated {
+            records,
+            duplicates,
+            failed,
+        }
+    }
+}
+
+#[cfg(feature = "registry")]
+impl crate::registry::DeviceRegistry {
+    /// Connect to every registered device, merge their attendance logs and
+    /// disconnect again
+    pub async fn aggregate_attendance(&self) -> crate::Result<Aggregated> {
+        let mut fleet = self.fleet()?;
+        let aggregated = fleet.aggregate_attendance().await;
+        fleet.disconnect().await;
+        Ok(aggregated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+    use zkrust_core::Command;
+
+    use crate::device::Device;
+    use crate::testing::AckTransport;
+
+    fn punch(user_id: &str, hour: u32, min: u32, sec: u32) -> AttendanceRecord {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(hour, min, sec)
+            .unwrap();
+        AttendanceRecord::new(user_id, timestamp, VerifyMode::Fingerprint, PunchType::CheckIn)
+    }
+
+    #[test]
+    fn test_merge_attendance() {
+        let (merged, duplicates) = merge_attendance([
+            ("a", vec![punch("1", 8, 0, 40), punch("2", 8, 5, 0), punch("2", 8, 5, 30)]),
+            ("b", vec![punch("1", 8, 0, 10), punch("1", 8, 1, 5), punch("2", 8, 5, 10)]),
+            ("c", vec![punch("1", 8, 0, 59)]),
+        ]);
+
+        assert_eq!(duplicates, 3);
+        let summary: Vec<_> = merged
+            .iter()
+            .map(|t| (t.record.user_id.as_str(), t.record.timestamp.time().to_string(), t.device.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("1", "08:00:10".to_string(), "b"),
+                ("1", "08:01:05".to_string(), "b"),
+                ("2", "08:05:00".to_string(), "a"),
+                // Second punch on the same device in the same minute is kept
+                ("2", "08:05:30".to_string(), "a"),
+            ]
+        );
+        assert_eq!(merged[0].also_seen_on, ["a", "c"]);
+        assert_eq!(merged[2].also_seen_on, ["b"]);
+    }
+
+    #[test]
+    fn test_merge_by_utc() {
+        let utc = |hour, min, sec| Some(punch("", hour, min, sec).timestamp.and_utc());
+        // Site a runs on UTC+3, site b on UTC+1
+        let (merged, duplicates) = merge([
+            ("a", vec![(punch("1", 8, 0, 10), utc(5, 0, 10)), (punch("2", 8, 30, 0), utc(5, 30, 0))]),
+            ("b", vec![(punch("1", 6, 0, 30), utc(5, 0, 30)), (punch("2", 6, 10, 0), utc(5, 10, 0))]),
+        ]);
+
+        assert_eq!(duplicates, 1);
+        let order: Vec<_> = merged.iter().map(|t| (t.record.user_id.as_str(), t.device.as_str())).collect();
+        assert_eq!(order, [("1", "a"), ("2", "b"), ("2", "a")]);
+        assert_eq!(merged[0].also_seen_on, ["b"]);
+    }
+
+    /// Device holding two legacy 8-byte records
+    fn device_with_records() -> Device {
+        let mut data = 16u32.to_le_bytes().to_vec();
+        data.extend([1, 0, 1, 0x88, 0x84, 0x4c, 0x2e, 0, 2, 0, 3, 0x48, 0xfd, 0x4c, 0x2e, 1]);
+        let mut free_sizes = vec![0u8; 80];
+        free_sizes[32..36].copy_from_slice(&2u32.to_le_bytes());
+
+        let transport = AckTransport::new()
+            .0
+            .with_payload(Command::GetFreeSizes, free_sizes)
+            .with_replies(
+                Command::AttLogRrq,
+                vec![
+                    (Command::PrepareData, (data.len() as u32).to_le_bytes().to_vec()),
+                    (Command::Data, data),
+                    (Command::AckOk, Vec::new()),
+                ],
+            );
+        Device::with_transport(transport)
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_attendance() {
+        let (refusing, _) = AckTransport::new();
+        let mut fleet = Fleet::new()
+            .with_device("gate", device_with_records())
+            .with_device("lobby", Device::with_transport(refusing.rejecting(Command::Connect)));
+
+        let merged = fleet.aggregate_attendance().await;
+        assert_eq!(merged.records.len(), 2);
+        assert!(merged.records.iter().all(|tagged| tagged.device == "gate"));
+        assert_eq!(merged.failed.len(), 1);
+        assert_eq!(merged.failed[0].0, "lobby");
+        assert!(fleet.get_mut("gate").unwrap().is_connected());
+    }
+}
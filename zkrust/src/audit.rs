@@ -0,0 +1,451 @@
+//! Audit trail of issued commands
+//!
+//! Some deployments must prove who cleared a log or deleted a user. An
+//! [`AuditLog`] attached to a [`Device`](crate::Device) (or every device of a
+//! [`Fleet`](crate::Fleet)) hands an [`AuditEntry`] to its [`AuditSink`] for
+//! each command: when it was issued, by which operator, to which device,
+//! with what parameters, and how the device answered. Commands refused
+//! before sending (read-only mode, unsupported by the model) are recorded
+//! too.
+//!
+//! The handshake (connect and CommKey authentication) is not audited, so
+//! CommKeys never reach a sink. Commands the device doesn't answer (exit,
+//! restart, power off) are recorded as [`AuditOutcome::Sent`]. User records do carry user passwords; treat
+//! stored payloads accordingly.
+//!
+//! ```no_run
+//! use zkrust::audit::{AuditLog, JsonLinesSink};
+//! use zkrust::Device;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let log = AuditLog::new(JsonLinesSink::append("audit.jsonl")?)
+//!     .with_operator("alice")
+//!     .writes_only();
+//!
+//! let mut device = Device::new("192.168.1.201", 4370).with_audit(log);
+//! device.connect().await?;
+//! device.delete_user(42).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use tracing::warn;
+
+use zkrust_core::{Command, Packet};
+
+use crate::capture::json_string;
+use crate::error::{Error, Result};
+
+/// Payload bytes kept per entry; bulk data chunks are truncated
+pub const MAX_PAYLOAD: usize = 256;
+
+/// How the device answered an audited command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditOutcome {
+    /// The device accepted the command (CMD_ACK_OK, CMD_ACK_DATA, ...)
+    Accepted(Command),
+
+    /// The device answered, but refused (e.g. CMD_ACK_ERROR)
+    Refused(Command),
+
+    /// Sent; the device doesn't answer this command (exit, restart, power
+    /// off)
+    Sent,
+
+    /// No answer: the command was rejected locally, timed out or the
+    /// connection failed
+    Failed(String),
+}
+
+impl AuditOutcome {
+    fn of(result: &Result<Packet>) -> Self {
+        match result {
+            Ok(packet) if packet.is_success() => Self::Accepted(packet.command),
+            Ok(packet) => Self::Refused(packet.command),
+            Err(e) => match e.context().and_then(|context| context.response.as_ref()) {
+                // Rejections reported as errors still carry the answer
+                Some(response) if matches!(e.root(), Error::Rejected { .. }) => {
+                    Self::Refused(response.command)
+                }
+                _ => Self::Failed(e.to_string()),
+            },
+        }
+    }
+
+    /// Check if the device accepted the command
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Self::Accepted(_))
+    }
+}
+
+impl fmt::Display for AuditOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Accepted(response) => write!(f, "accepted ({})", response.name()),
+            Self::Refused(response) => write!(f, "refused ({})", response.name()),
+            Self::Sent => write!(f, "sent"),
+            Self::Failed(error) => write!(f, "failed: {}", error),
+        }
+    }
+}
+
+/// One audited command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// When the command was issued
+    pub at: DateTime<Utc>,
+
+    /// Operator set on the log or device, if any
+    pub operator: Option<String>,
+
+    /// Fleet name of the device, or its address outside a fleet
+    pub device: String,
+
+    /// Device address
+    pub address: String,
+
+    /// The command
+    pub command: Command,
+
+    /// Command parameters, truncated to [`MAX_PAYLOAD`] bytes
+    pub payload: Bytes,
+
+    /// Full payload length
+    pub payload_len: usize,
+
+    /// How the device answered
+    pub outcome: AuditOutcome,
+}
+
+impl AuditEntry {
+    /// Payload as lowercase hex
+    pub fn payload_hex(&self) -> String {
+        self.payload.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Entry as one JSON object (no trailing newline)
+    pub fn to_json(&self) -> String {
+        let (outcome, detail) = match &self.outcome {
+            AuditOutcome::Accepted(response) => ("accepted", response.name().to_string()),
+            AuditOutcome::Refused(response) => ("refused", response.name().to_string()),
+            AuditOutcome::Sent => ("sent", String::new()),
+            AuditOutcome::Failed(error) => ("failed", error.clone()),
+        };
+        format!(
+            "{{\"at\":{},\"operator\":{},\"device\":{},\"address\":{},\"command\":{},\
+             \"payload\":\"{}\",\"payload_len\":{},\"outcome\":\"{}\",\"detail\":{}}}",
+            json_string(&self.at.to_rfc3339_opts(SecondsFormat::Millis, true)),
+            self.operator.as_deref().map_or_else(|| "null".to_string(), json_string),
+            json_string(&self.device),
+            json_string(&self.address),
+            json_string(self.command.name()),
+            self.payload_hex(),
+            self.payload_len,
+            outcome,
+            json_string(&detail),
+        )
+    }
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            self.operator.as_deref().unwrap_or("-"),
+            self.device,
+            self.command.name()
+        )?;
+        if !self.payload.is_empty() {
+            write!(f, " {}", self.payload_hex())?;
+            if self.payload_len > self.payload.len() {
+                write!(f, "... ({} bytes)", self.payload_len)?;
+            }
+        }
+        write!(f, ": {}", self.outcome)
+    }
+}
+
+/// Destination of audit entries
+///
+/// Called synchronously after each command; sinks writing somewhere slow
+/// should hand entries to a channel or background task.
+pub trait AuditSink: Send + Sync {
+    /// Store one entry
+    fn record(&self, entry: &AuditEntry);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditEntry) + Send + Sync,
+{
+    fn record(&self, entry: &AuditEntry) {
+        self(entry)
+    }
+}
+
+/// Keeps entries in memory
+///
+/// Clones share the same entries.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    entries: Arc<Mutex<Vec<AuditEntry>>>,
+}
+
+impl MemorySink {
+    /// Create an empty sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Entries recorded so far
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+}
+
+impl AuditSink for MemorySink {
+    fn record(&self, entry: &AuditEntry) {
+        self.entries.lock().unwrap().push(entry.clone());
+    }
+}
+
+/// Writes one JSON object per line, flushing after each entry
+pub struct JsonLinesSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLinesSink {
+    /// Write entries to `writer`
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Append entries to the file at `path`, creating it if needed
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl AuditSink for JsonLinesSink {
+    fn record(&self, entry: &AuditEntry) {
+        let mut writer = self.writer.lock().unwrap();
+        let written = writeln!(writer, "{}", entry.to_json()).and_then(|_| writer.flush());
+        if let Err(e) = written {
+            warn!("Failed to write audit entry for {}: {}", entry.command, e);
+        }
+    }
+}
+
+impl fmt::Debug for JsonLinesSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLinesSink").finish_non_exhaustive()
+    }
+}
+
+/// Audit settings of a device: the sink, the operator and what to record
+///
+/// Cheap to clone; clones write to the same sink.
+#[derive(Clone)]
+pub struct AuditLog {
+    sink: Arc<dyn AuditSink>,
+    operator: Option<String>,
+    device: Option<String>,
+    writes_only: bool,
+}
+
+impl AuditLog {
+    /// Record every command to `sink`
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            operator: None,
+            device: None,
+            writes_only: false,
+        }
+    }
+
+    /// Attribute entries to `operator`
+    pub fn with_operator(mut self, operator: impl Into<String>) -> Self {
+        self.operator = Some(operator.into());
+        self
+    }
+
+    /// Name entries after `device` instead of its address
+    pub fn with_device_name(mut self, device: impl Into<String>) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// Only record commands that change the device (see
+    /// [`CommandMeta::writes`](zkrust_core::command::CommandMeta::writes))
+    pub fn writes_only(mut self) -> Self {
+        self.writes_only = true;
+        self
+    }
+
+    /// Operator entries are attributed to
+    pub fn operator(&self) -> Option<&str> {
+        self.operator.as_deref()
+    }
+
+    pub(crate) fn set_operator(&mut self, operator: Option<String>) {
+        self.operator = operator;
+    }
+
+    /// Hand the outcome of `command` to the sink
+    pub(crate) fn record(&self, address: String, command: Command, payload: &Bytes, result: &Result<Packet>) {
+        self.push(address, command, payload, AuditOutcome::of(result));
+    }
+
+    /// Hand the outcome of sending `command`, which gets no answer, to the sink
+    pub(crate) fn record_sent(&self, address: String, command: Command, result: &Result<()>) {
+        let outcome = match result {
+            Ok(()) => AuditOutcome::Sent,
+            Err(e) => AuditOutcome::Failed(e.to_string()),
+        };
+        self.push(address, command, &Bytes::new(), outcome);
+    }
+
+    fn push(&self, address: String, command: Command, payload: &Bytes, outcome: AuditOutcome) {
+        if self.writes_only && !command.meta().writes {
+            return;
+        }
+
+        let entry = AuditEntry {
+            at: Utc::now(),
+            operator: self.operator.clone(),
+            device: self.device.clone().unwrap_or_else(|| address.clone()),
+            address,
+            command,
+            payload: payload.slice(..payload.len().min(MAX_PAYLOAD)),
+            payload_len: payload.len(),
+            outcome,
+        };
+        self.sink.record(&entry);
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("operator", &self.operator)
+            .field("device", &self.device)
+            .field("writes_only", &self.writes_only)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::device::Device;
+    use crate::fleet::Fleet;
+    use crate::testing::{AckTransport, SharedBuffer};
+
+    #[tokio::test]
+    async fn test_device_audit() {
+        let sink = MemorySink::new();
+        let (transport, _) = AckTransport::new();
+        let mut device = Device::with_transport(transport.rejecting(Command::DeleteUser))
+            .with_audit(AuditLog::new(sink.clone()).with_operator("alice"));
+        device.connect().await.unwrap();
+
+        device.enable_device().await.unwrap();
+        assert!(device.delete_user(42).await.is_err());
+        device.set_operator(Some("bob".into()));
+        device.refresh_data().await.unwrap();
+
+        let entries = sink.entries();
+        let commands: Vec<_> = entries.iter().map(|entry| entry.command).collect();
+        assert_eq!(commands, [Command::EnableDevice, Command::DeleteUser, Command::RefreshData]);
+
+        let delete = &entries[1];
+        assert_eq!(delete.operator.as_deref(), Some("alice"));
+        assert_eq!(delete.payload_hex(), "2a00");
+        assert!(matches!(delete.outcome, AuditOutcome::Refused(_)));
+        assert!(entries[0].outcome.is_accepted());
+        assert_eq!(entries[2].operator.as_deref(), Some("bob"));
+    }
+
+    #[tokio::test]
+    async fn test_writes_only_and_read_only() {
+        let sink = MemorySink::new();
+        let (transport, _) = AckTransport::new();
+        let mut fleet = Fleet::new()
+            .with_device("gate", Device::with_transport(transport).read_only())
+            .with_audit(AuditLog::new(sink.clone()).writes_only());
+        let device = fleet.get_mut("gate").unwrap();
+        device.connect().await.unwrap();
+
+        device.get_capacity().await.ok();
+        assert!(device.delete_user(42).await.is_err());
+
+        let entries = sink.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].device, "gate");
+        assert_eq!(entries[0].command, Command::DeleteUser);
+        assert!(matches!(entries[0].outcome, AuditOutcome::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_commands() {
+        let sink = MemorySink::new();
+        let audited = || {
+            let (transport, _) = AckTransport::new();
+            Device::with_transport(transport).with_audit(AuditLog::new(sink.clone()))
+        };
+
+        let mut device = audited();
+        device.connect().await.unwrap();
+        device.restart().await.unwrap();
+
+        let mut device = audited();
+        device.connect().await.unwrap();
+        device.power_off().await.unwrap();
+
+        let mut device = audited();
+        device.connect().await.unwrap();
+        device.disconnect().await.unwrap();
+
+        let mut device = audited();
+        device.connect().await.unwrap();
+        drop(device);
+        tokio::task::yield_now().await;
+
+        let entries = sink.entries();
+        let commands: Vec<_> = entries.iter().map(|entry| entry.command).collect();
+        assert_eq!(commands, [Command::Restart, Command::PowerOff, Command::Exit, Command::Exit]);
+        assert!(entries.iter().all(|entry| entry.outcome == AuditOutcome::Sent));
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let buffer = SharedBuffer::default();
+        let log = AuditLog::new(JsonLinesSink::new(buffer.clone())).with_device_name("gate");
+
+        let payload = Bytes::from(vec![7u8; MAX_PAYLOAD + 10]);
+        let reply = Packet::new(Command::AckOk, 1, 1);
+        log.record("10.0.0.1:4370".into(), Command::Data, &payload, &Ok(reply));
+
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(written.lines().count(), 1);
+        assert!(written.contains("\"operator\":null,\"device\":\"gate\",\"address\":\"10.0.0.1:4370\""));
+        assert!(written.contains(&format!("\"payload_len\":{}", MAX_PAYLOAD + 10)));
+        assert!(written.contains(&format!("\"payload\":\"{}\"", "07".repeat(MAX_PAYLOAD))));
+        assert!(written.ends_with("\"outcome\":\"accepted\",\"detail\":\"CMD_ACK_OK\"}\n"));
+    }
+}
@@ -0,0 +1,267 @@
+//! Blocking (synchronous) API
+//!
+//! Wraps the async [`Device`](crate::Device) with a private single-threaded
+//! tokio runtime, for scripts and GUI apps that don't run a runtime of their
+//! own. Enabled with the `blocking` feature.
+//!
+//! These methods must not be called from within an async runtime; they will
+//! panic if they are. Use the async API there instead.
+
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use tokio::runtime::{Builder, Runtime};
+
+use zkrust_core::session::{ObserverId, StateChange};
+use zkrust_core::SessionStats;
+use zkrust_transport::Transport;
+use zkrust_types::{AttendanceRecord, DeviceCapacity, DeviceInfo, User};
+
+use crate::error::Result;
+use crate::health::HealthReport;
+use crate::secret::SecretProvider;
+
+/// Synchronous ZKTeco device
+///
+/// # Examples
+///
+/// ```no_run
+/// use zkrust::blocking::Device;
+///
+/// fn main() -> zkrust::Result<()> {
+///     let mut device = Device::new_udp("192.168.1.201", 4370);
+///     device.connect()?;
+///
+///     let info = device.get_device_info()?;
+///     println!("Device: {}", info);
+///
+///     device.disconnect()
+/// }
+/// ```
+pub struct Device {
+    inner: crate::Device,
+    runtime: Runtime,
+}
+
+impl Device {
+    /// Create a new device instance (TCP transport)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the internal runtime cannot be created.
+    pub fn new(ip: impl Into<String>, port: u16) -> Self {
+        Self::from_async(crate::Device::new(ip, port))
+    }
+
+    /// Create a new device instance using UDP transport (recommended)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the internal runtime cannot be created.
+    pub fn new_udp(ip: impl Into<String>, port: u16) -> Self {
+        Self::from_async(crate::Device::new_udp(ip, port))
+    }
+
+    /// Create a new device instance over a custom transport
+    ///
+    /// # Panics
+    ///
+    /// Panics if the internal runtime cannot be created.
+    pub fn with_transport(transport: impl Transport + 'static) -> Self {
+        Self::from_async(crate::Device::with_transport(transport))
+    }
+
+    /// Wrap an existing async device
+    ///
+    /// # Panics
+    ///
+    /// Panics if the internal runtime cannot be created.
+    pub fn from_async(inner: crate::Device) -> Self {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build tokio runtime for blocking device");
+
+        Self { inner, runtime }
+    }
+
+    /// Unwrap into the async device
+    pub fn into_async(mut self) -> crate::Device {
+        self.take_inner()
+    }
+
+    /// Set command timeout
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.map(|inner| inner.with_timeout(timeout))
+    }
+
+    /// Set CommKey password (default: 0)
+    pub fn with_password(self, password: u32) -> Self {
+        self.map(|inner| inner.with_password(password))
+    }
+
+    /// Resolve the CommKey from a [`SecretProvider`] at connect time
+    pub fn with_secret_provider(self, provider: impl SecretProvider + 'static) -> Self {
+        self.map(|inner| inner.with_secret_provider(provider))
+    }
+
+    /// Set the CommKey ticks value (default: 50)
+    pub fn with_ticks(self, ticks: u8) -> Self {
+        self.map(|inner| inner.with_ticks(ticks))
+    }
+
+    /// Enable read-only safe mode
+    pub fn read_only(self) -> Self {
+        self.map(|inner| inner.read_only())
+    }
+
+    /// Check if read-only safe mode is enabled
+    pub fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    /// Get protocol statistics for this device's session
+    pub fn stats(&self) -> SessionStats {
+        self.inner.stats()
+    }
+
+    /// Register a callback fired on Connected/Authenticated/Disconnected transitions
+    pub fn on_state_change<F>(&self, callback: F) -> ObserverId
+    where
+        F: Fn(StateChange) + Send + Sync + 'static,
+    {
+        self.inner.on_state_change(callback)
+    }
+
+    /// Check if connected
+    pub fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    /// Connect to device
+    pub fn connect(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.connect())
+    }
+
+    /// Disconnect from device
+    pub fn disconnect(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.disconnect())
+    }
+
+    /// Get device information
+    pub fn get_device_info(&mut self) -> Result<DeviceInfo> {
+        self.runtime.block_on(self.inner.get_device_info())
+    }
+
+    /// Get the device clock
+    pub fn get_time(&mut self) -> Result<NaiveDateTime> {
+        self.runtime.block_on(self.inner.get_time())
+    }
+
+    /// Get used and total storage
+    pub fn get_capacity(&mut self) -> Result<DeviceCapacity> {
+        self.runtime.block_on(self.inner.get_capacity())
+    }
+
+    /// Download all attendance records
+    pub fn get_attendance(&mut self) -> Result<Vec<AttendanceRecord>> {
+        self.runtime.block_on(self.inner.get_attendance())
+    }
+
+    /// Download all enrolled users
+    pub fn get_users(&mut self) -> Result<Vec<User>> {
+        self.runtime.block_on(self.inner.get_users())
+    }
+
+    /// Create or update a user
+    pub fn set_user(&mut self, user: &User) -> Result<()> {
+        self.runtime.block_on(self.inner.set_user(user))
+    }
+
+    /// Check device health with the default thresholds
+    pub fn health_check(&mut self) -> HealthReport {
+        self.runtime.block_on(self.inner.health_check())
+    }
+
+    /// Enable device (normal operation mode)
+    pub fn enable_device(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.enable_device())
+    }
+
+    /// Disable device (show "Working..." on LCD)
+    pub fn disable_device(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.disable_device())
+    }
+
+    /// Refresh device data
+    pub fn refresh_data(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.refresh_data())
+    }
+
+    /// Restart device
+    pub fn restart(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.restart())
+    }
+
+    /// Power off device
+    pub fn power_off(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.power_off())
+    }
+
+    fn map(mut self, f: impl FnOnce(crate::Device) -> crate::Device) -> Self {
+        self.inner = f(self.take_inner());
+        self
+    }
+
+    /// Move the async device out, leaving an unconnected one for `drop`
+    fn take_inner(&mut self) -> crate::Device {
+        std::mem::replace(&mut self.inner, crate::Device::new("", 0))
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        // The async device can only spawn its best-effort exit inside a
+        // runtime; ours is idle between calls, so disconnect on it instead
+        if self.inner.is_connected() && tokio::runtime::Handle::try_current().is_err() {
+            let _ = self.runtime.block_on(self.inner.disconnect());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::AckTransport;
+    use zkrust_core::Command;
+
+    #[test]
+    fn test_blocking_roundtrip() {
+        let (transport, sent) = AckTransport::new();
+        let mut device = Device::with_transport(transport).read_only();
+
+        device.connect().unwrap();
+        assert!(device.is_connected());
+
+        device.enable_device().unwrap();
+        assert!(device.restart().is_err());
+
+        device.disconnect().unwrap();
+        assert!(!device.is_connected());
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![Command::Connect, Command::EnableDevice, Command::Exit]
+        );
+    }
+
+    #[test]
+    fn test_blocking_exit_on_drop() {
+        let (transport, sent) = AckTransport::new();
+        let mut device = Device::with_transport(transport);
+        device.connect().unwrap();
+        drop(device);
+
+        assert_eq!(*sent.lock().unwrap(), vec![Command::Connect, Command::Exit]);
+    }
+}
@@ -0,0 +1,439 @@
+//! Chunked bulk deletion of users
+//!
+//! Some firmwares wedge when thousands of CMD_DELETE_USER arrive back to
+//! back. [`BulkDelete`] deletes in chunks with a pause in between, applies
+//! each chunk with CMD_REFRESHDATA and checks the enrolled user count to
+//! confirm the deletions took. Transient failures are retried with backoff
+//! (reconnecting when needed); when the device stays unreachable the run
+//! stops and [`BulkDeleteReport::remaining`] lists what is left, so it can
+//! be resumed later.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use zkrust::bulk::BulkDelete;
+//!
+//! # async fn example(device: &mut zkrust::Device, uids: Vec<u16>) {
+//! let bulk = BulkDelete::new()
+//!     .with_chunk_size(25)
+//!     .with_pause(Duration::from_secs(1));
+//!
+//! let mut report = bulk.run(device, &uids).await;
+//! while let Some(e) = &report.error {
+//!     eprintln!("Stopped with {} left: {}", report.remaining.len(), e);
+//!     tokio::time::sleep(Duration::from_secs(60)).await;
+//!     report = bulk.run(device, &report.remaining).await;
+//! }
+//! # }
+//! ```
+
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use tracing::{debug, info, warn};
+
+use zkrust_core::Command;
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::handle::DeviceFuture;
+
+/// Default number of users deleted between refreshes
+pub const DEFAULT_CHUNK_SIZE: usize = 50;
+
+/// Default pause between chunks
+pub const DEFAULT_PAUSE: Duration = Duration::from_millis(500);
+
+/// Default retries of one command after a transient failure
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Default wait before the first retry; doubled for each further one
+pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Settings of a chunked, rate-limited user deletion
+#[derive(Debug, Clone)]
+pub struct BulkDelete {
+    chunk_size: usize,
+    pause: Duration,
+    max_retries: u32,
+    backoff: Duration,
+    verify: bool,
+}
+
+impl Default for BulkDelete {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Outcome of [`BulkDelete::run`]
+#[derive(Debug, Default)]
+pub struct BulkDeleteReport {
+    /// UIDs deleted
+    pub deleted: Vec<u16>,
+
+    /// UIDs the device refused to delete (usually: no such user)
+    pub refused: Vec<u16>,
+
+    /// UIDs the device accepted twice but still counted afterwards
+    pub unconfirmed: Vec<u16>,
+
+    /// UIDs not deleted because the run stopped; pass them to
+    /// [`BulkDelete::run`] again to resume
+    pub remaining: Vec<u16>,
+
+    /// Commands retried after transient failures
+    pub retries: u32,
+
+    /// Error that stopped the run
+    pub error: Option<Error>,
+}
+
+impl BulkDeleteReport {
+    /// Check if every UID was handled and the run did not stop early
+    pub fn is_complete(&self) -> bool {
+        self.error.is_none() && self.remaining.is_empty()
+    }
+}
+
+impl BulkDelete {
+    /// Create the default settings: chunks of 50, 500 ms apart, 3 retries
+    pub fn new() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            pause: DEFAULT_PAUSE,
+            max_retries: DEFAULT_MAX_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+            verify: true,
+        }
+    }
+
+    /// Set how many users are deleted between refreshes (minimum 1)
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Set the pause between chunks
+    pub fn with_pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Set how often a command is retried after a transient failure, and
+    /// the wait before the first retry
+    pub fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// Skip the user count checks after each chunk
+    pub fn without_verification(mut self) -> Self {
+        self.verify = false;
+        self
+    }
+
+    /// Delete the users with the given internal UIDs
+    ///
+    /// Duplicate UIDs are deleted once. The device must be connected; a
+    /// connection lost on the way is re-established.
+    pub async fn run(&self, device: &mut Device, uids: &[u16]) -> BulkDeleteReport {
+        let mut report = BulkDeleteReport::default();
+        let mut seen = HashSet::new();
+        let mut pending: VecDeque<u16> = uids.iter().copied().filter(|uid| seen.insert(*uid)).collect();
+
+        let enrolled = if self.verify {
+            match self.retrying(device, &mut report, |d| Box::pin(d.get_capacity())).await {
+                Ok(capacity) => Some(capacity.users as usize),
+                Err(e) => {
+                    report.remaining = pending.into();
+                    report.error = Some(e);
+                    return report;
+                }
+            }
+        } else {
+            None
+        };
+
+        info!("Deleting {} users in chunks of {}", pending.len(), self.chunk_size);
+        let mut requeued = HashSet::new();
+        while !pending.is_empty() {
+            let chunk: Vec<u16> = pending.drain(..self.chunk_size.min(pending.len())).collect();
+
+            if let Err((done, e)) = self.delete_chunk(device, &chunk, &mut report).await {
+                report.remaining = chunk[done..].iter().copied().chain(pending).collect();
+                report.error = Some(e);
+                return report;
+            }
+
+            if let Some(enrolled) = enrolled {
+                let verified = self
+                    .verify_chunk(device, enrolled, &chunk, &mut requeued, &mut pending, &mut report)
+                    .await;
+                if let Err(e) = verified {
+                    report.remaining = pending.into();
+                    report.error = Some(e);
+                    return report;
+                }
+            }
+
+            if !pending.is_empty() {
+                tokio::time::sleep(self.pause).await;
+            }
+        }
+
+        info!(
+            "Deleted {} users ({} refused, {} retries)",
+            report.deleted.len(),
+            report.refused.len(),
+            report.retries
+        );
+        report
+    }
+
+    /// Delete and apply one chunk; on failure, returns how many UIDs of the
+    /// chunk were handled
+    async fn delete_chunk(
+        &self,
+        device: &mut Device,
+        chunk: &[u16],
+        report: &mut BulkDeleteReport,
+    ) -> std::result::Result<(), (usize, Error)> {
+        for (done, &uid) in chunk.iter().enumerate() {
+            match self.retrying(device, report, |d| Box::pin(d.delete_user(uid))).await {
+                Ok(()) => report.deleted.push(uid),
+                Err(e) if is_refusal(&e) => {
+                    debug!("Device refused to delete UID {}: {}", uid, e);
+                    report.refused.push(uid);
+                }
+                Err(e) => return Err((done, e)),
+            }
+        }
+
+        // Every deletion is in; nothing of the chunk is left to resume
+        self.retrying(device, report, |d| Box::pin(d.refresh_data()))
+            .await
+            .map_err(|e| (chunk.len(), e))
+    }
+
+    /// Compare the user count with the deletions so far, queueing UIDs the
+    /// device still lists for one more attempt
+    async fn verify_chunk(
+        &self,
+        device: &mut Device,
+        enrolled: usize,
+        chunk: &[u16],
+        requeued: &mut HashSet<u16>,
+        pending: &mut VecDeque<u16>,
+        report: &mut BulkDeleteReport,
+    ) -> Result<()> {
+        let users = self.retrying(device, report, |d| Box::pin(d.get_capacity())).await?.users as usize;
+        let expected = enrolled.saturating_sub(report.deleted.len());
+        if users <= expected {
+            return Ok(());
+        }
+
+        warn!("{} users enrolled after deleting, expected {}", users, expected);
+        let listed: HashSet<u16> = self
+            .retrying(device, report, |d| Box::pin(d.get_users()))
+            .await?
+            .iter()
+            .map(|user| user.uid)
+            .collect();
+
+        for &uid in chunk.iter().rev().filter(|uid| listed.contains(uid)) {
+            report.deleted.retain(|&deleted| deleted != uid);
+            if requeued.insert(uid) {
+                pending.push_front(uid);
+            } else {
+                report.unconfirmed.push(uid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `op`, retrying transient failures with exponential backoff
+    async fn retrying<T, F>(&self, device: &mut Device, report: &mut BulkDeleteReport, op: F) -> Result<T>
+    where
+        F: for<'a> Fn(&'a mut Device) -> DeviceFuture<'a, T>,
+    {
+        let mut attempt = 0;
+        loop {
+            let e = match op(device).await {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            let class = e.retry_class();
+            if attempt >= self.max_retries || !class.is_retryable() || is_refusal(&e) {
+                return Err(e);
+            }
+
+            let delay = self.backoff.saturating_mul(1 << attempt.min(16));
+            attempt += 1;
+            report.retries += 1;
+            warn!("{}; retrying in {:?} ({} of {})", e, delay, attempt, self.max_retries);
+            tokio::time::sleep(delay).await;
+
+            if class.needs_reconnect() || !device.is_connected() {
+                let _ = device.disconnect().await;
+                if let Err(e) = device.connect().await {
+                    // Counted as a failed attempt by the next try
+                    warn!("Reconnecting failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// The device answered, but refused the command
+fn is_refusal(error: &Error) -> bool {
+    error
+        .context()
+        .and_then(|context| context.response.as_ref())
+        .is_some_and(|response| !response.is_success() && response.command != Command::AckRetry)
+}
+
+impl Device {
+    /// Delete many users in chunks with the default [`BulkDelete`] settings
+    pub async fn delete_users(&mut self, uids: &[u16]) -> BulkDeleteReport {
+        BulkDelete::new().run(self, uids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zkrust_transport::mock::{Expectation, Faults, MockTransport};
+    use zkrust_types::{DeviceCapacity, User, UserRecordLayout};
+
+    fn capacity(users: u32) -> Expectation {
+        let capacity = DeviceCapacity {
+            users,
+            ..DeviceCapacity::default()
+        };
+        Expectation::new(Command::GetFreeSizes).reply_with(Command::AckOk, capacity.encode())
+    }
+
+    fn delete(uid: u16) -> Expectation {
+        Expectation::new(Command::DeleteUser).with_payload(uid.to_le_bytes().to_vec())
+    }
+
+    fn refresh() -> Expectation {
+        Expectation::new(Command::RefreshData).reply(Command::AckOk)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_chunks_and_retries() {
+        let transport = MockTransport::new()
+            .expect(Expectation::new(Command::Connect).reply(Command::AckOk))
+            .expect(capacity(10))
+            .expect(delete(1).reply(Command::AckOk))
+            .expect(delete(2).timeout())
+            .expect(delete(2).reply(Command::AckOk))
+            .expect(refresh())
+            .expect(capacity(8))
+            .expect(delete(3).reply(Command::AckError))
+            .expect(refresh())
+            .expect(capacity(8));
+        let handle = transport.handle();
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        let started = tokio::time::Instant::now();
+        let report = BulkDelete::new()
+            .with_chunk_size(2)
+            .with_pause(Duration::from_secs(2))
+            .run(&mut device, &[1, 2, 2, 3])
+            .await;
+        handle.assert_done();
+
+        assert!(report.is_complete());
+        assert_eq!(report.deleted, [1, 2]);
+        assert_eq!(report.refused, [3]);
+        assert_eq!(report.retries, 1);
+        // One backoff, one pause
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnects_after_disconnect() {
+        // The link drops after the reply to deleting uid 1
+        let transport = MockTransport::new()
+            .with_faults(Faults::seeded(0).with_disconnect_after(3))
+            .expect(Expectation::new(Command::Connect).reply(Command::AckOk))
+            .expect(capacity(10))
+            .expect(delete(1).reply(Command::AckOk))
+            .expect(delete(2).reply(Command::AckOk))
+            .expect(Expectation::new(Command::Connect).reply(Command::AckOk))
+            .expect(delete(2).reply(Command::AckOk))
+            .expect(refresh())
+            .expect(capacity(8));
+        let handle = transport.handle();
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        let report = BulkDelete::new().run(&mut device, &[1, 2]).await;
+        handle.assert_done();
+
+        assert!(report.is_complete(), "{:?}", report);
+        assert_eq!(report.deleted, [1, 2]);
+        assert_eq!(report.retries, 1);
+        assert_eq!(handle.faults().disconnects, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stops_with_remaining() {
+        let transport = MockTransport::new()
+            .expect(Expectation::new(Command::Connect).reply(Command::AckOk))
+            .expect(delete(1).reply(Command::AckOk))
+            .expect(delete(2).timeout())
+            .expect(delete(2).timeout());
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        let report = BulkDelete::new()
+            .without_verification()
+            .with_retries(1, Duration::from_millis(100))
+            .run(&mut device, &[1, 2, 3])
+            .await;
+
+        assert!(!report.is_complete());
+        assert_eq!(report.deleted, [1]);
+        assert_eq!(report.remaining, [2, 3]);
+        assert!(report.error.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requeues_users_still_listed() {
+        let layout = UserRecordLayout::Compact;
+        let user = User::builder(1, "42").build().unwrap();
+        let mut users = (layout.record_size() as u32).to_le_bytes().to_vec();
+        users.extend(layout.encode(&user).unwrap());
+
+        let transport = MockTransport::new()
+            .expect(Expectation::new(Command::Connect).reply(Command::AckOk))
+            .expect(capacity(3))
+            .expect(delete(1).reply(Command::AckOk))
+            .expect(refresh())
+            // The deletion did not take
+            .expect(capacity(3))
+            .expect(capacity(3))
+            .expect(Expectation::new(Command::UserTempRrq).reply_with(Command::AckData, users))
+            .expect(delete(1).reply(Command::AckOk))
+            .expect(refresh())
+            .expect(capacity(2));
+        let handle = transport.handle();
+        let mut device = Device::with_transport(transport)
+            .with_profile(crate::profile::DeviceProfile::new("Test").with_user_layout(layout));
+        device.connect().await.unwrap();
+
+        let report = BulkDelete::new().run(&mut device, &[1]).await;
+        handle.assert_done();
+
+        assert!(report.is_complete());
+        assert_eq!(report.deleted, [1]);
+        assert!(report.unconfirmed.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+//! Feature gating by firmware version and model profile
+//!
+//! Old firmware tends to answer unknown commands with a bare CMD_ACK_ERROR
+//! or not at all. APIs that depend on newer firmware call
+//! [`Device::require`] first so users get [`Error::NotSupported`] with the
+//! reason instead.
+
+use std::fmt;
+
+use zkrust_types::FirmwareVersion;
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+
+/// Optional device feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Fingerprint templates
+    Fingerprint,
+
+    /// Face templates (Ver 8+ platforms)
+    Face,
+}
+
+impl Capability {
+    /// Oldest firmware implementing this feature, if limited
+    pub const fn min_firmware(self) -> Option<FirmwareVersion> {
+        match self {
+            Self::Fingerprint => None,
+            Self::Face => Some(FirmwareVersion::new(8, 0, 0)),
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fingerprint => write!(f, "Fingerprint templates"),
+            Self::Face => write!(f, "Face templates"),
+        }
+    }
+}
+
+impl Device {
+    /// Check that the device supports `capability`
+    ///
+    /// Uses the firmware version from [`Device::get_device_info`], the
+    /// algorithm versions from [`Device::get_platform_info`] and the active
+    /// [`DeviceProfile`](crate::profile::DeviceProfile). Anything not known
+    /// yet is assumed to be supported.
+    pub fn require(&self, capability: Capability) -> Result<()> {
+        if let (Some(min), Some(firmware)) = (capability.min_firmware(), self.firmware()) {
+            if !firmware.at_least(min) {
+                return Err(Error::NotSupported(format!(
+                    "{} require firmware {} or newer (device runs {})",
+                    capability, min, firmware
+                )));
+            }
+        }
+
+        if capability == Capability::Face {
+            if let Some(platform) = self.platform_info() {
+                if platform.face_algorithm.is_none() {
+                    return Err(Error::NotSupported(format!(
+                        "{} need a face algorithm, the device reports none",
+                        capability
+                    )));
+                }
+            }
+        }
+
+        if let Some(profile) = self.profile() {
+            let supported = match capability {
+                Capability::Fingerprint => profile.fingerprint,
+                Capability::Face => profile.face,
+            };
+
+            if !supported {
+                return Err(Error::NotSupported(format!(
+                    "{} are not available on {} devices",
+                    capability, profile.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::DeviceProfile;
+
+    #[test]
+    fn test_require_unknown_device_allows() {
+        let device = Device::new("192.168.1.201", 4370);
+        assert!(device.require(Capability::Face).is_ok());
+    }
+
+    #[test]
+    fn test_require_firmware_gate() {
+        let device = Device::new("192.168.1.201", 4370).with_firmware(FirmwareVersion::new(6, 60, 0));
+
+        let err = device.require(Capability::Face).unwrap_err();
+        assert!(err.to_string().contains("8.0.0"), "{}", err);
+        assert!(device.require(Capability::Fingerprint).is_ok());
+
+        let device = device.with_firmware(FirmwareVersion::new(8, 0, 4));
+        assert!(device.require(Capability::Face).is_ok());
+    }
+
+    #[test]
+    fn test_require_profile_gate() {
+        let device = Device::new("192.168.1.201", 4370)
+            .with_profile(DeviceProfile::new("Face only").with_biometrics(false, true));
+
+        assert!(matches!(
+            device.require(Capability::Fingerprint),
+            Err(Error::NotSupported(_))
+        ));
+        assert!(device.require(Capability::Face).is_ok());
+    }
+}
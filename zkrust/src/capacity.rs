@@ -0,0 +1,276 @@
+//! Storage capacity warnings
+//!
+//! Terminals overwrite (or stop accepting) punches once the attendance log
+//! is full. [`CapacityWatch`] reads CMD_GET_FREE_SIZES and reports every
+//! storage area past its threshold, through `tracing` and an optional
+//! callback, so a scheduler can pull and clear the log in time.
+//!
+//! ```no_run
+//! use zkrust::capacity::CapacityWatch;
+//!
+//! # async fn example(device: &mut zkrust::Device) -> zkrust::Result<()> {
+//! let watch = CapacityWatch::new()
+//!     .with_records_threshold(0.75)
+//!     .on_warning(|warning| eprintln!("{}", warning));
+//!
+//! if !watch.check(device).await?.is_empty() {
+//!     let records = device.get_attendance().await?;
+//!     // ... store them, then clear the log
+//! #   let _ = records;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use tracing::warn;
+
+use zkrust_types::DeviceCapacity;
+
+use crate::device::Device;
+use crate::error::Result;
+
+/// Default usage fraction that triggers a warning
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
+
+type WarningCallback = Arc<dyn Fn(&CapacityWarning) + Send + Sync>;
+
+/// Storage area reported by CMD_GET_FREE_SIZES
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StorageArea {
+    /// Attendance log
+    Records,
+
+    /// Fingerprint templates
+    Fingerprints,
+
+    /// Face templates
+    Faces,
+}
+
+impl StorageArea {
+    /// Used and total slots of this area
+    pub fn usage(self, capacity: &DeviceCapacity) -> (u32, u32) {
+        match self {
+            Self::Records => (capacity.records, capacity.records_capacity),
+            Self::Fingerprints => (capacity.fingers, capacity.fingers_capacity),
+            Self::Faces => (capacity.faces, capacity.faces_capacity),
+        }
+    }
+}
+
+impl fmt::Display for StorageArea {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Records => write!(f, "Attendance"),
+            Self::Fingerprints => write!(f, "Fingerprint"),
+            Self::Faces => write!(f, "Face"),
+        }
+    }
+}
+
+/// Storage area past its threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacityWarning {
+    /// Area that crossed the threshold
+    pub area: StorageArea,
+
+    /// Slots in use
+    pub used: u32,
+
+    /// Total slots
+    pub capacity: u32,
+
+    /// Threshold that was crossed (0.0-1.0)
+    pub threshold: f64,
+}
+
+impl CapacityWarning {
+    /// Fraction of slots in use (0.0-1.0)
+    pub fn usage(&self) -> f64 {
+        self.used as f64 / self.capacity as f64
+    }
+
+    /// Slots left before the area is full
+    pub fn remaining(&self) -> u32 {
+        self.capacity.saturating_sub(self.used)
+    }
+}
+
+impl fmt::Display for CapacityWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} storage {:.0}% full ({} of {}, threshold {:.0}%)",
+            self.area,
+            self.usage() * 100.0,
+            self.used,
+            self.capacity,
+            self.threshold * 100.0
+        )
+    }
+}
+
+/// Thresholds checked against the device storage counters
+///
+/// Attendance and template storage have separate thresholds; areas the
+/// device reports no capacity for (e.g. faces on fingerprint-only models)
+/// are skipped.
+#[derive(Clone)]
+pub struct CapacityWatch {
+    records_threshold: f64,
+    templates_threshold: f64,
+    callback: Option<WarningCallback>,
+}
+
+impl Default for CapacityWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CapacityWatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CapacityWatch")
+            .field("records_threshold", &self.records_threshold)
+            .field("templates_threshold", &self.templates_threshold)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
+}
+
+impl CapacityWatch {
+    /// Create a watch with both thresholds at 80%
+    pub fn new() -> Self {
+        Self {
+            records_threshold: DEFAULT_THRESHOLD,
+            templates_threshold: DEFAULT_THRESHOLD,
+            callback: None,
+        }
+    }
+
+    /// Set the attendance log threshold (0.0-1.0)
+    pub fn with_records_threshold(mut self, threshold: f64) -> Self {
+        self.records_threshold = threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the fingerprint and face template threshold (0.0-1.0)
+    pub fn with_templates_threshold(mut self, threshold: f64) -> Self {
+        self.templates_threshold = threshold.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Call `callback` for every warning, in addition to logging it
+    pub fn on_warning<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&CapacityWarning) + Send + Sync + 'static,
+    {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Threshold applied to `area`
+    pub fn threshold(&self, area: StorageArea) -> f64 {
+        match area {
+            StorageArea::Records => self.records_threshold,
+            StorageArea::Fingerprints | StorageArea::Faces => self.templates_threshold,
+        }
+    }
+
+    /// Read the device storage counters and report areas past their threshold
+    pub async fn check(&self, device: &mut Device) -> Result<Vec<CapacityWarning>> {
+        let capacity = device.get_capacity().await?;
+        Ok(self.evaluate(&capacity))
+    }
+
+    /// Report areas of `capacity` past their threshold
+    ///
+    /// Each warning is logged and passed to the callback.
+    pub fn evaluate(&self, capacity: &DeviceCapacity) -> Vec<CapacityWarning> {
+        let mut warnings = Vec::new();
+
+        for area in [StorageArea::Records, StorageArea::Fingerprints, StorageArea::Faces] {
+            let (used, total) = area.usage(capacity);
+            let threshold = self.threshold(area);
+            if total == 0 || (used as f64 / total as f64) < threshold {
+                continue;
+            }
+
+            let warning = CapacityWarning {
+                area,
+                used,
+                capacity: total,
+                threshold,
+            };
+            warn!("{}", warning);
+            if let Some(callback) = &self.callback {
+                callback(&warning);
+            }
+            warnings.push(warning);
+        }
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use zkrust_core::Command;
+
+    use crate::testing::AckTransport;
+
+    fn capacity(records: u32, fingers: u32) -> DeviceCapacity {
+        DeviceCapacity {
+            records,
+            records_capacity: 100_000,
+            fingers,
+            fingers_capacity: 3_000,
+            ..DeviceCapacity::default()
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let watch = CapacityWatch::new().with_templates_threshold(0.5).on_warning({
+            let seen = seen.clone();
+            move |warning| seen.lock().unwrap().push(warning.area)
+        });
+
+        assert!(watch.evaluate(&capacity(79_999, 1_499)).is_empty());
+
+        let warnings = watch.evaluate(&capacity(80_000, 1_500));
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].area, StorageArea::Records);
+        assert_eq!(warnings[0].remaining(), 20_000);
+        assert_eq!(
+            warnings[0].to_string(),
+            "Attendance storage 80% full (80000 of 100000, threshold 80%)"
+        );
+        assert_eq!(warnings[1].threshold, 0.5);
+
+        // No face capacity reported, so faces are never flagged
+        assert_eq!(*seen.lock().unwrap(), [StorageArea::Records, StorageArea::Fingerprints]);
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let (transport, sent) = AckTransport::new();
+        let transport = transport.with_payload(Command::GetFreeSizes, capacity(95_000, 10).encode());
+
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        let warnings = CapacityWatch::new().check(&mut device).await.unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].area, StorageArea::Records);
+        assert_eq!(sent.lock().unwrap().last(), Some(&Command::GetFreeSizes));
+    }
+}
@@ -0,0 +1,492 @@
+//! Protocol capture
+//!
+//! A [`Capture`] records every frame a [`Device`](crate::Device) sends or
+//! receives, with a timestamp, so odd device behavior can be attached to a
+//! bug report. Frames are recorded as bare protocol packets, before any
+//! TCP wrapper is added and after it is removed.
+//!
+//! Two formats are supported:
+//!
+//! - [`CaptureFormat::Pcap`]: classic pcap with each frame inside a
+//!   synthesized IPv4/UDP datagram between `10.0.0.1` (this host) and the
+//!   device, so Wireshark shows direction and ZK dissectors for port 4370
+//!   apply
+//! - [`CaptureFormat::JsonLines`]: one JSON object per frame with the
+//!   direction, decoded header fields and the raw bytes in hex
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use zkrust::capture::Capture;
+//! use zkrust::Device;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut device = Device::new_udp("192.168.1.201", 4370);
+//! device.start_capture(Capture::create("device.pcap")?);
+//! device.connect().await?;
+//! device.get_attendance().await?;
+//! device.stop_capture();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use zkrust_core::Command;
+
+/// Address standing in for this host in pcap captures
+const HOST_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+
+/// Address used for devices whose address isn't IPv4
+const DEVICE_ADDR: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 4370);
+
+/// pcap link type for raw IPv4/IPv6 packets
+const LINKTYPE_RAW: u32 = 101;
+
+/// IPv4 (20 bytes) plus UDP (8 bytes) header
+const IP_UDP_HEADER: usize = 28;
+
+/// Capture file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CaptureFormat {
+    /// pcap with synthesized IPv4/UDP headers
+    Pcap,
+
+    /// One JSON object per line
+    JsonLines,
+}
+
+impl CaptureFormat {
+    /// Pick the format from a file extension (`.pcap` or `.cap`, else JSONL)
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("pcap") || ext.eq_ignore_ascii_case("cap") => {
+                Self::Pcap
+            }
+            _ => Self::JsonLines,
+        }
+    }
+}
+
+/// Which way a frame travelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Host to device
+    Sent,
+
+    /// Device to host
+    Received,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Sent => "sent",
+            Self::Received => "received",
+        })
+    }
+}
+
+/// Frame read back from a capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedFrame {
+    /// Which way the frame travelled
+    pub direction: Direction,
+
+    /// Bare protocol packet
+    pub data: Vec<u8>,
+}
+
+/// Read every frame from a capture file, picking the format from its
+/// extension with [`CaptureFormat::from_path`]
+pub fn load(path: impl AsRef<Path>) -> io::Result<Vec<CapturedFrame>> {
+    let format = CaptureFormat::from_path(&path);
+    parse(&std::fs::read(path)?, format)
+}
+
+/// Read every frame from capture data written by a [`Capture`]
+pub fn parse(data: &[u8], format: CaptureFormat) -> io::Result<Vec<CapturedFrame>> {
+    match format {
+        CaptureFormat::Pcap => parse_pcap(data),
+        CaptureFormat::JsonLines => parse_json_lines(data),
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_pcap(data: &[u8]) -> io::Result<Vec<CapturedFrame>> {
+    let u32_at = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+
+    if data.len() < 24 || !matches!(u32_at(0), 0xa1b2_c3d4 | 0xa1b2_3c4d) {
+        return Err(invalid("Not a little-endian pcap file".into()));
+    }
+    if u32_at(20) != LINKTYPE_RAW {
+        return Err(invalid(format!("Unsupported pcap link type {}", u32_at(20))));
+    }
+
+    let mut frames = Vec::new();
+    let mut offset = 24;
+
+    while offset < data.len() {
+        if data.len() - offset < 16 {
+            return Err(invalid(format!("Truncated pcap record header at byte {}", offset)));
+        }
+
+        let len = u32_at(offset + 8) as usize;
+        let start = offset + 16;
+        let datagram = data
+            .get(start..start + len)
+            .ok_or_else(|| invalid(format!("Truncated pcap record at byte {}", offset)))?;
+
+        let header_len = datagram.first().map_or(0, |b| (b & 0x0f) as usize * 4) + 8;
+        if datagram.len() < header_len || datagram[0] >> 4 != 4 || datagram[9] != 17 {
+            return Err(invalid(format!("pcap record at byte {} is not IPv4/UDP", offset)));
+        }
+
+        let direction = if datagram[12..16] == HOST_ADDR.octets() {
+            Direction::Sent
+        } else {
+            Direction::Received
+        };
+
+        frames.push(CapturedFrame {
+            direction,
+            data: datagram[header_len..].to_vec(),
+        });
+        offset = start + len;
+    }
+
+    Ok(frames)
+}
+
+fn parse_json_lines(data: &[u8]) -> io::Result<Vec<CapturedFrame>> {
+    let text = std::str::from_utf8(data).map_err(|e| invalid(e.to_string()))?;
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let field = |name: &str| {
+                let key = format!("\"{}\":\"", name);
+                let start = line.find(&key)? + key.len();
+                let len = line[start..].find('"')?;
+                Some(&line[start..start + len])
+            };
+            let error = |what: &str| invalid(format!("line {}: {}", index + 1, what));
+
+            let direction = match field("direction") {
+                Some("sent") => Direction::Sent,
+                Some("received") => Direction::Received,
+                _ => return Err(error("missing or unknown direction")),
+            };
+
+            let hex = field("hex").ok_or_else(|| error("missing hex"))?;
+            if hex.len() % 2 != 0 {
+                return Err(error("odd number of hex digits"));
+            }
+            let data = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| error("invalid hex"))?;
+
+            Ok(CapturedFrame { direction, data })
+        })
+        .collect()
+}
+
+/// Frame recorder writing to a file or any [`Write`]
+pub struct Capture {
+    writer: Box<dyn Write + Send + Sync>,
+    format: CaptureFormat,
+    frames: u64,
+}
+
+impl Capture {
+    /// Record into `writer`; a pcap file header is written immediately
+    pub fn new(writer: impl Write + Send + Sync + 'static, format: CaptureFormat) -> io::Result<Self> {
+        let mut capture = Self {
+            writer: Box::new(writer),
+            format,
+            frames: 0,
+        };
+
+        if format == CaptureFormat::Pcap {
+            capture.write_pcap_header()?;
+        }
+
+        Ok(capture)
+    }
+
+    /// Create (or truncate) a capture file, picking the format from its
+    /// extension with [`CaptureFormat::from_path`]
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let format = CaptureFormat::from_path(&path);
+        Self::new(BufWriter::new(File::create(path)?), format)
+    }
+
+    /// Capture format
+    pub fn format(&self) -> CaptureFormat {
+        self.format
+    }
+
+    /// Number of frames recorded so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Record one frame exchanged with the device at `remote`
+    pub fn record(&mut self, direction: Direction, remote: &str, frame: &[u8]) -> io::Result<()> {
+        let now = SystemTime::now();
+
+        match self.format {
+            CaptureFormat::Pcap => self.write_pcap_record(now, direction, remote, frame)?,
+            CaptureFormat::JsonLines => self.write_json_line(now, direction, remote, frame)?,
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Flush buffered frames to the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn write_pcap_header(&mut self) -> io::Result<()> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes()); // magic, microseconds
+        header.extend_from_slice(&2u16.to_le_bytes()); // version 2.4
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&0i32.to_le_bytes()); // UTC
+        header.extend_from_slice(&0u32.to_le_bytes()); // timestamp accuracy
+        header.extend_from_slice(&65535u32.to_le_bytes()); // snap length
+        header.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+        self.writer.write_all(&header)
+    }
+
+    fn write_pcap_record(
+        &mut self,
+        now: SystemTime,
+        direction: Direction,
+        remote: &str,
+        frame: &[u8],
+    ) -> io::Result<()> {
+        let device = remote.parse().unwrap_or(DEVICE_ADDR);
+        let host = SocketAddrV4::new(HOST_ADDR, device.port());
+        let (src, dst) = match direction {
+            Direction::Sent => (host, device),
+            Direction::Received => (device, host),
+        };
+
+        let datagram = udp_datagram(src, dst, frame);
+        let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        let mut record = Vec::with_capacity(16 + datagram.len());
+        record.extend_from_slice(&(since_epoch.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&since_epoch.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(datagram.len() as u32).to_le_bytes()); // captured
+        record.extend_from_slice(&(datagram.len() as u32).to_le_bytes()); // original
+        record.extend_from_slice(&datagram);
+        self.writer.write_all(&record)
+    }
+
+    fn write_json_line(
+        &mut self,
+        now: SystemTime,
+        direction: Direction,
+        remote: &str,
+        frame: &[u8],
+    ) -> io::Result<()> {
+        let ts = DateTime::<Utc>::from(now).to_rfc3339_opts(SecondsFormat::Micros, true);
+        let u16_at = |i: usize| u16::from_le_bytes([frame[i], frame[i + 1]]);
+
+        let mut line = format!(
+            "{{\"ts\":\"{}\",\"direction\":\"{}\",\"device\":{},\"len\":{}",
+            ts,
+            direction,
+            json_string(remote),
+            frame.len()
+        );
+
+        if frame.len() >= 8 {
+            let command = match Command::try_from(u16_at(0)) {
+                Ok(command) => json_string(command.name()),
+                Err(_) => "null".into(),
+            };
+            line.push_str(&format!(
+                ",\"command\":{},\"code\":{},\"session_id\":{},\"reply_id\":{}",
+                command,
+                u16_at(0),
+                u16_at(4),
+                u16_at(6)
+            ));
+        }
+
+        let hex: String = frame.iter().map(|b| format!("{:02x}", b)).collect();
+        line.push_str(&format!(",\"hex\":\"{}\"}}\n", hex));
+
+        self.writer.write_all(line.as_bytes())
+    }
+}
+
+impl fmt::Debug for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Capture")
+            .field("format", &self.format)
+            .field("frames", &self.frames)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+/// Wrap `payload` in IPv4 and UDP headers (UDP checksum left at zero)
+fn udp_datagram(src: SocketAddrV4, dst: SocketAddrV4, payload: &[u8]) -> Vec<u8> {
+    // Frames never come close to this; clamp rather than overflow
+    let payload = &payload[..payload.len().min(u16::MAX as usize - IP_UDP_HEADER)];
+    let total = (IP_UDP_HEADER + payload.len()) as u16;
+
+    let mut ip = [0u8; 20];
+    ip[0] = 0x45; // IPv4, 5-word header
+    ip[2..4].copy_from_slice(&total.to_be_bytes());
+    ip[8] = 64; // TTL
+    ip[9] = 17; // UDP
+    ip[12..16].copy_from_slice(&src.ip().octets());
+    ip[16..20].copy_from_slice(&dst.ip().octets());
+    let checksum = ipv4_checksum(&ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut datagram = Vec::with_capacity(total as usize);
+    datagram.extend_from_slice(&ip);
+    datagram.extend_from_slice(&src.port().to_be_bytes());
+    datagram.extend_from_slice(&dst.port().to_be_bytes());
+    datagram.extend_from_slice(&(total - 20).to_be_bytes());
+    datagram.extend_from_slice(&[0, 0]);
+    datagram.extend_from_slice(payload);
+    datagram
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let sum = header
+        .chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+        .sum::<u32>();
+    let folded = (sum & 0xffff) + (sum >> 16);
+    !((folded & 0xffff) + (folded >> 16)) as u16
+}
+
+/// Quote `text` as a JSON string
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkrust_core::Packet;
+
+    use crate::testing::SharedBuffer as Shared;
+
+    #[test]
+    fn test_json_lines() {
+        let out = Shared::default();
+        let mut capture = Capture::new(out.clone(), CaptureFormat::JsonLines).unwrap();
+
+        let connect = Packet::new(Command::Connect, 0, 0).encode();
+        capture.record(Direction::Sent, "192.168.1.201:4370", &connect).unwrap();
+        capture.record(Direction::Received, "192.168.1.201:4370", &[1, 2, 3]).unwrap();
+        assert_eq!(capture.frames(), 2);
+
+        let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("\"direction\":\"sent\""), "{}", lines[0]);
+        assert!(lines[0].contains("\"command\":\"CMD_CONNECT\",\"code\":1000"), "{}", lines[0]);
+        assert!(lines[0].ends_with(&format!("\"hex\":\"{}\"}}", hex(&connect))), "{}", lines[0]);
+        assert!(!lines[1].contains("command"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn test_pcap_layout() {
+        let out = Shared::default();
+        let mut capture = Capture::new(out.clone(), CaptureFormat::Pcap).unwrap();
+        capture.record(Direction::Received, "192.168.1.201:4370", &[0xAB; 8]).unwrap();
+
+        let data = out.0.lock().unwrap().clone();
+        assert_eq!(&data[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(&data[20..24], &LINKTYPE_RAW.to_le_bytes());
+
+        // Record header, then 28 bytes of IPv4/UDP and the frame
+        let record = &data[24..];
+        assert_eq!(&record[8..12], &36u32.to_le_bytes());
+        let ip = &record[16..];
+        assert_eq!(ip.len(), 36);
+        assert_eq!(&ip[12..16], &[192, 168, 1, 201]);
+        assert_eq!(&ip[16..20], &[10, 0, 0, 1]);
+        assert_eq!(ipv4_checksum(&ip[..20]), 0);
+        assert_eq!(&ip[20..22], &4370u16.to_be_bytes());
+        assert_eq!(&ip[28..], &[0xAB; 8]);
+    }
+
+    #[test]
+    fn test_read_back() {
+        for format in [CaptureFormat::Pcap, CaptureFormat::JsonLines] {
+            let out = Shared::default();
+            let mut capture = Capture::new(out.clone(), format).unwrap();
+
+            let sent = Packet::new(Command::Connect, 0, 0).encode();
+            let received = Packet::with_payload(Command::AckOk, 5, 0, vec![1, 2, 3]).encode();
+            capture.record(Direction::Sent, "192.168.1.201:4370", &sent).unwrap();
+            capture.record(Direction::Received, "[::1]:4370", &received).unwrap();
+
+            let frames = parse(&out.0.lock().unwrap(), format).unwrap();
+            assert_eq!(
+                frames,
+                vec![
+                    CapturedFrame { direction: Direction::Sent, data: sent.to_vec() },
+                    CapturedFrame { direction: Direction::Received, data: received.to_vec() },
+                ],
+                "{:?}",
+                format
+            );
+        }
+
+        assert!(parse(b"{\"direction\":\"sent\",\"hex\":\"0g\"}", CaptureFormat::JsonLines).is_err());
+        assert!(parse(&[0; 24], CaptureFormat::Pcap).is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(CaptureFormat::from_path("dump.PCAP"), CaptureFormat::Pcap);
+        assert_eq!(CaptureFormat::from_path("dump.jsonl"), CaptureFormat::JsonLines);
+        assert_eq!(json_string("a\"b\n"), "\"a\\\"b\\u000a\"");
+    }
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
@@ -0,0 +1,162 @@
+//! Clock synchronization
+//!
+//! Terminal clocks drift by seconds a week and reset after power loss, which
+//! shifts every punch recorded afterwards. [`Device::sync_clock`] measures
+//! the skew against the current time in the device's timezone (the host's
+//! own zone unless one is configured), corrects it and measures again; an
+//! optional limit refuses large jumps, which usually mean the host clock
+//! (or the timezone) is wrong rather than the device.
+
+use std::fmt;
+use std::time::Duration;
+
+#[cfg(feature = "tz")]
+use chrono::Utc;
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use tracing::{debug, warn};
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+
+/// Clock correction of one device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSync {
+    /// Device clock minus current time before the correction
+    pub before: TimeDelta,
+
+    /// Device clock minus current time after the correction
+    pub after: TimeDelta,
+
+    /// Time the device clock was set to, or `None` if it was already
+    /// correct to the second
+    pub set_to: Option<NaiveDateTime>,
+}
+
+impl fmt::Display for ClockSync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "skew {:+}s -> {:+}s",
+            self.before.num_seconds(),
+            self.after.num_seconds()
+        )
+    }
+}
+
+impl Device {
+    /// Current wall-clock time where the device is
+    ///
+    /// Uses the timezone of the device (`Device::with_timezone`, `tz`
+    /// feature) when one is configured, the local time of this host
+    /// otherwise.
+    pub fn local_now(&self) -> NaiveDateTime {
+        #[cfg(feature = "tz")]
+        if let Some(timezone) = self.timezone() {
+            return Utc::now().with_timezone(&timezone).naive_local();
+        }
+        Local::now().naive_local()
+    }
+
+    /// Set the device clock to the current time in its timezone (see
+    /// [`Device::local_now`]) and report the skew before and after
+    ///
+    /// A clock already correct to the second is left alone. With
+    /// `max_adjustment`, a device more than that far off is left untouched
+    /// and [`Error::ClockAdjustmentRefused`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// # async fn example(device: &mut zkrust::Device) -> zkrust::Result<()> {
+    /// let sync = device.sync_clock(Some(Duration::from_secs(3600))).await?;
+    /// println!("{}", sync);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn sync_clock(&mut self, max_adjustment: Option<Duration>) -> Result<ClockSync> {
+        let before = self.get_time().await? - self.local_now();
+        if before.num_seconds() == 0 {
+            debug!("Clock skew {}ms, nothing to correct", before.num_milliseconds());
+            return Ok(ClockSync {
+                before,
+                after: before,
+                set_to: None,
+            });
+        }
+
+        if let Some(limit) = max_adjustment {
+            if before.abs().to_std().is_ok_and(|skew| skew > limit) {
+                warn!("Not correcting clock skew of {}s (limit {:?})", before.num_seconds(), limit);
+                return Err(Error::ClockAdjustmentRefused {
+                    skew_secs: before.num_seconds(),
+                    limit,
+                });
+            }
+        }
+
+        let set_to = self.local_now();
+        self.set_time(set_to).await?;
+        let after = self.get_time().await? - self.local_now();
+
+        debug!("Clock skew {}s -> {}s", before.num_seconds(), after.num_seconds());
+        Ok(ClockSync {
+            before,
+            after,
+            set_to: Some(set_to),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zkrust_core::Command;
+    use zkrust_types::time;
+
+    use crate::testing::AckTransport;
+
+    async fn device_at(time: NaiveDateTime) -> (Device, std::sync::Arc<std::sync::Mutex<Vec<Command>>>) {
+        let encoded = time::encode_time(&time).unwrap();
+        let (transport, sent) = AckTransport::new();
+        let mut device = Device::with_transport(transport.with_payload(Command::GetTime, encoded.to_le_bytes()));
+        device.connect().await.unwrap();
+        (device, sent)
+    }
+
+    #[tokio::test]
+    async fn test_sync_clock() {
+        let (mut device, sent) = device_at(Local::now().naive_local() - TimeDelta::minutes(5)).await;
+
+        let sync = device.sync_clock(Some(Duration::from_secs(600))).await.unwrap();
+        assert!((sync.before.num_seconds() + 300).abs() <= 2);
+        assert!(sent.lock().unwrap().contains(&Command::SetTime));
+    }
+
+    #[tokio::test]
+    async fn test_sync_clock_limit() {
+        let (mut device, sent) = device_at(Local::now().naive_local() + TimeDelta::days(2)).await;
+
+        let result = device.sync_clock(Some(Duration::from_secs(3600))).await;
+        assert!(matches!(result, Err(Error::ClockAdjustmentRefused { skew_secs, .. }) if skew_secs > 3600));
+        assert!(!sent.lock().unwrap().contains(&Command::SetTime));
+
+        assert!(device.sync_clock(None).await.is_ok());
+    }
+
+    #[cfg(feature = "tz")]
+    #[tokio::test]
+    async fn test_sync_clock_timezone() {
+        // No host runs 14 hours ahead of UTC
+        let kiritimati = zkrust_types::time::parse_timezone("Pacific/Kiritimati").unwrap();
+        let (device, sent) = device_at(Utc::now().with_timezone(&kiritimati).naive_local()).await;
+        let mut device = device.with_timezone(kiritimati);
+
+        let sync = device.sync_clock(Some(Duration::from_secs(60))).await.unwrap();
+        assert_eq!(sync.before.num_seconds(), 0);
+        assert_eq!(sync.set_to, None);
+        assert!(!sent.lock().unwrap().contains(&Command::SetTime));
+    }
+}
@@ -1,24 +1,238 @@
-//! High-level error types
-
-pub type Result<T> = std::result::Result<T, Error>;
-
-#[derive(Debug, thiserror::Error)]
-pub enum Error {
-    #[error("Core protocol error: {0}")]
-    Core(#[from] zkrust_core::Error),
-    
-    #[error("Transport error: {0}")]
-    Transport(#[from] zkrust_transport::Error),
-    
-    #[error("Type error: {0}")]
-    Types(#[from] zkrust_types::Error),
-    
-    #[error("Device not connected")]
-    NotConnected,
-    
-    #[error("Operation not supported: {0}")]
-    NotSupported(String),
-    
-    #[error("Invalid response from device: {0}")]
-    InvalidResponse(String),
-}
\ No newline at end of file
+//! High-level error types
+
+use std::fmt;
+use std::time::Duration;
+
+use zkrust_core::{Command, Packet, RetryClass};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Core protocol error: {0}")]
+    Core(#[from] zkrust_core::Error),
+    
+    #[error("Transport error: {0}")]
+    Transport(#[from] zkrust_transport::Error),
+    
+    #[error("Type error: {0}")]
+    Types(#[from] zkrust_types::Error),
+    
+    #[error("Device not connected")]
+    NotConnected,
+    
+    #[error("Device handle closed - the device task has stopped")]
+    HandleClosed,
+    
+    #[error("Command {command} rejected: device is in read-only mode")]
+    ReadOnly { command: zkrust_core::Command },
+    
+    #[error("Operation not supported: {0}")]
+    NotSupported(String),
+    
+    #[error("Not enough {store} capacity: need {needed}, {available} free")]
+    CapacityExceeded {
+        store: &'static str,
+        needed: u32,
+        available: u32,
+    },
+    
+    #[error("Refusing to move the device clock by {skew_secs}s (limit {limit:?})")]
+    ClockAdjustmentRefused { skew_secs: i64, limit: Duration },
+    
+    #[error("Invalid response from device: {0}")]
+    InvalidResponse(String),
+    
+    #[error("Device rejected {command} with {response}")]
+    Rejected { command: Command, response: Command },
+    
+    #[error("{command} to {device} timed out after {elapsed:?} (attempt {attempt})")]
+    Timeout {
+        command: Command,
+        device: String,
+        elapsed: Duration,
+        attempt: u32,
+    },
+    
+    #[error("Device still asked to repeat {command} after {attempts} attempts")]
+    RepeatLimit { command: Command, attempts: u32 },
+    
+    #[error("Device was still busy with {command} after {attempts} attempts")]
+    DeviceBusy { command: Command, attempts: u32 },
+    
+    #[error("Operation timed out after {0:?}")]
+    OperationTimeout(Duration),
+    
+    #[error("Secret provider error: {0}")]
+    Secret(String),
+    
+    #[error("Configuration error: {0}")]
+    Config(String),
+    
+    #[error("{source} ({context})")]
+    Context {
+        context: Box<ErrorContext>,
+        source: Box<Error>,
+    },
+}
+
+/// Details of the device exchange an error came from
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    /// Command being executed
+    pub command: Command,
+    
+    /// Time from sending the command until the error
+    pub elapsed: Duration,
+    
+    /// Attempt number, starting at 1
+    pub attempt: u32,
+    
+    /// Raw response packet, if one was received
+    pub response: Option<Packet>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, attempt {}, after {:?}",
+            self.command, self.attempt, self.elapsed
+        )?;
+        
+        if let Some(response) = &self.response {
+            write!(f, ", response {}", response)?;
+        }
+        
+        Ok(())
+    }
+}
+
+impl Error {
+    /// Attach exchange details, unless the error already has them
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Self::Context { .. } => self,
+            source => Self::Context {
+                context: Box::new(context),
+                source: Box::new(source),
+            },
+        }
+    }
+    
+    /// Get the exchange details, if any
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+    
+    /// Get the underlying error without its context
+    ///
+    /// Match on this to check the error kind, e.g.
+    /// `matches!(err.root(), Error::NotConnected)`.
+    pub fn root(&self) -> &Error {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+    
+    /// Classify the error for retry decisions
+    ///
+    /// Core and transport errors keep their own classification; context is
+    /// looked through.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use zkrust::{Device, RetryClass};
+    ///
+    /// # async fn example(device: &mut Device) -> zkrust::Result<()> {
+    /// let time = loop {
+    ///     match device.get_time().await {
+    ///         Ok(time) => break time,
+    ///         Err(e) => match e.retry_class() {
+    ///             RetryClass::Retryable => continue,
+    ///             RetryClass::Reconnect => {
+    ///                 let _ = device.disconnect().await;
+    ///                 device.connect().await?;
+    ///             }
+    ///             RetryClass::Auth | RetryClass::Fatal => return Err(e),
+    ///         },
+    ///     }
+    /// };
+    /// # let _ = time;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn retry_class(&self) -> RetryClass {
+        match self {
+            Self::Core(e) => e.retry_class(),
+            Self::Transport(e) => e.retry_class(),
+            Self::Context { source, .. } => source.retry_class(),
+            // A garbled or truncated reply may well come through intact next time
+            Self::Timeout { .. }
+            | Self::RepeatLimit { .. }
+            | Self::DeviceBusy { .. }
+            | Self::InvalidResponse(_) => RetryClass::Retryable,
+            // An abandoned exchange leaves unread replies behind
+            Self::NotConnected | Self::OperationTimeout(_) => RetryClass::Reconnect,
+            Self::Secret(_) => RetryClass::Auth,
+            // The device answered and refused; asking again won't change that
+            Self::Rejected { .. }
+            | Self::Types(_)
+            | Self::HandleClosed
+            | Self::ReadOnly { .. }
+            | Self::NotSupported(_)
+            | Self::CapacityExceeded { .. }
+            | Self::ClockAdjustmentRefused { .. }
+            | Self::Config(_) => RetryClass::Fatal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_retry_class() {
+        let timeout = Error::Transport(zkrust_transport::Error::ReadTimeout);
+        assert_eq!(timeout.retry_class(), RetryClass::Retryable);
+        
+        let closed = Error::Transport(zkrust_transport::Error::ConnectionClosed);
+        assert_eq!(closed.retry_class(), RetryClass::Reconnect);
+        assert!(closed.retry_class().is_retryable());
+        
+        let auth = Error::Core(zkrust_core::Error::AuthenticationFailed);
+        assert_eq!(auth.retry_class(), RetryClass::Auth);
+        assert!(!auth.retry_class().is_retryable());
+        
+        let nested = zkrust_transport::Error::Protocol(zkrust_core::Error::AuthenticationRequired);
+        assert_eq!(Error::Transport(nested).retry_class(), RetryClass::Auth);
+        
+        let context = ErrorContext {
+            command: Command::GetTime,
+            elapsed: Duration::ZERO,
+            attempt: 1,
+            response: None,
+        };
+        let busy = Error::DeviceBusy {
+            command: Command::GetTime,
+            attempts: 3,
+        };
+        assert_eq!(busy.with_context(context).retry_class(), RetryClass::Retryable);
+        assert_eq!(Error::NotSupported("face".into()).retry_class(), RetryClass::Fatal);
+        
+        let rejected = Error::Rejected {
+            command: Command::GetTime,
+            response: Command::AckError,
+        };
+        assert_eq!(rejected.retry_class(), RetryClass::Fatal);
+        
+        let truncated = Error::InvalidResponse("Bulk transfer ended at 512 of 1024 bytes".into());
+        assert_eq!(truncated.retry_class(), RetryClass::Retryable);
+    }
+}
@@ -0,0 +1,173 @@
+//! Face recognition detection and settings
+//!
+//! Face terminals expose their recognizer through device options. Option
+//! names are those of ZEM/iFace firmware; fingerprint-only models don't
+//! know them, so [`Device::supports_face`] checks first and the settings
+//! calls fail with [`Error::NotSupported`] there. A fleet of mixed models
+//! can then be configured in one pass, skipping the devices that report
+//! `NotSupported`.
+
+use tracing::debug;
+
+use crate::capability::Capability;
+use crate::device::Device;
+use crate::error::{Error, Result};
+
+/// Face function switch (`1` = face recognition on)
+const FACE_FUNCTION: &str = "~FaceFunOn";
+
+/// 1:N (identification) match threshold
+const MATCH_THRESHOLD: &str = "FaceMThr";
+
+/// 1:1 (verification) match threshold
+const VERIFY_THRESHOLD: &str = "FaceVThr";
+
+/// 1:N identification switch
+const ONE_TO_N: &str = "Face1NOn";
+
+/// Face recognition settings
+///
+/// `None` fields are not reported by the device when read, and left alone
+/// when written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaceSettings {
+    /// Score a face must reach to be identified among all users (1:N)
+    pub match_threshold: Option<u8>,
+
+    /// Score a face must reach to verify a claimed user (1:1)
+    pub verify_threshold: Option<u8>,
+
+    /// Whether faces are identified without entering a user ID first
+    pub one_to_n: Option<bool>,
+}
+
+impl FaceSettings {
+    /// Settings that change nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the 1:N match threshold
+    pub fn with_match_threshold(mut self, threshold: u8) -> Self {
+        self.match_threshold = Some(threshold);
+        self
+    }
+
+    /// Set the 1:1 verification threshold
+    pub fn with_verify_threshold(mut self, threshold: u8) -> Self {
+        self.verify_threshold = Some(threshold);
+        self
+    }
+
+    /// Enable or disable 1:N identification
+    pub fn with_one_to_n(mut self, enabled: bool) -> Self {
+        self.one_to_n = Some(enabled);
+        self
+    }
+}
+
+impl Device {
+    /// Check whether face recognition is switched on (`~FaceFunOn`)
+    ///
+    /// Devices that don't know the option have no face reader.
+    pub async fn supports_face(&mut self) -> Result<bool> {
+        Ok(self.get_option(FACE_FUNCTION).await?.as_deref() == Some("1"))
+    }
+
+    /// Read the face recognition settings
+    pub async fn get_face_settings(&mut self) -> Result<FaceSettings> {
+        self.require_face().await?;
+        debug!("Reading face settings...");
+
+        Ok(FaceSettings {
+            match_threshold: self.get_parsed_option(MATCH_THRESHOLD).await?,
+            verify_threshold: self.get_parsed_option(VERIFY_THRESHOLD).await?,
+            one_to_n: self.get_parsed_option::<u8>(ONE_TO_N).await?.map(|value| value != 0),
+        })
+    }
+
+    /// Write the face recognition settings that are set in `settings`
+    ///
+    /// The device reloads its options afterwards, so they apply at once.
+    pub async fn set_face_settings(&mut self, settings: &FaceSettings) -> Result<()> {
+        self.require_face().await?;
+        debug!("Writing face settings: {:?}", settings);
+
+        let values = [
+            (MATCH_THRESHOLD, settings.match_threshold),
+            (VERIFY_THRESHOLD, settings.verify_threshold),
+            (ONE_TO_N, settings.one_to_n.map(u8::from)),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value {
+                self.set_option(name, &value.to_string()).await?;
+            }
+        }
+
+        self.refresh_options().await
+    }
+
+    /// Fail with [`Error::NotSupported`] unless face recognition is on
+    async fn require_face(&mut self) -> Result<()> {
+        self.require(Capability::Face)?;
+
+        if !self.supports_face().await? {
+            return Err(Error::NotSupported("Face recognition is not available on this device".into()));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zkrust_core::Command;
+
+    use crate::testing::AckTransport;
+
+    #[tokio::test]
+    async fn test_face_settings() {
+        let (transport, sent) = AckTransport::new();
+        let transport = transport.with_payload(
+            Command::OptionsRrq,
+            &b"~FaceFunOn=1\0FaceMThr=82\0Face1NOn=1\0"[..],
+        );
+
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        assert!(device.supports_face().await.unwrap());
+        let settings = device.get_face_settings().await.unwrap();
+        assert_eq!(
+            settings,
+            FaceSettings {
+                match_threshold: Some(82),
+                verify_threshold: None,
+                one_to_n: Some(true),
+            }
+        );
+
+        sent.lock().unwrap().clear();
+        device.set_face_settings(&FaceSettings::new().with_verify_threshold(70)).await.unwrap();
+        assert_eq!(
+            sent.lock().unwrap()[1..],
+            [Command::OptionsWrq, Command::RefreshOption]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_face_function() {
+        let (transport, sent) = AckTransport::new();
+        let transport = transport.with_payload(Command::OptionsRrq, &b"~ZKFPVersion=10\0"[..]);
+
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        assert!(!device.supports_face().await.unwrap());
+        let err = device.set_face_settings(&FaceSettings::new().with_one_to_n(false)).await.unwrap_err();
+        assert!(matches!(err, Error::NotSupported(_)));
+        assert!(!sent.lock().unwrap().contains(&Command::OptionsWrq));
+    }
+}
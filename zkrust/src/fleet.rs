@@ -0,0 +1,406 @@
+//! Running operations across many devices
+//!
+//! [`Fleet`] owns a set of named devices and runs one operation on all of
+//! them concurrently, a bounded number at a time, with an optional
+//! per-device time limit. Every device gets an outcome in the
+//! [`FleetReport`]; one unreachable terminal doesn't fail the rest.
+//!
+//! ```no_run
+//! use zkrust::{Device, Fleet};
+//!
+//! # async fn example() -> zkrust::Result<()> {
+//! let mut fleet = Fleet::new()
+//!     .with_device("gate", Device::new("192.168.1.201", 4370))
+//!     .with_device("canteen", Device::new("192.168.1.202", 4370))
+//!     .with_parallelism(8);
+//!
+//! fleet.connect().await;
+//! let report = fleet.get_attendance().await;
+//! for (name, records) in report.successes() {
+//!     println!("{}: {} records", name, records.len());
+//! }
+//! for (name, error) in report.failures() {
+//!     eprintln!("{}: {}", name, error);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use futures::stream::{self, StreamExt};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use zkrust_types::AttendanceRecord;
+
+use crate::audit::AuditLog;
+use crate::clock::ClockSync;
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::handle::DeviceFuture;
+
+/// Default number of devices worked on at once
+pub const DEFAULT_PARALLELISM: usize = 16;
+
+/// Named devices operated on together
+pub struct Fleet {
+    devices: Vec<(String, Device)>,
+    parallelism: usize,
+    timeout: Option<Duration>,
+    max_clock_adjustment: Option<Duration>,
+    audit: Option<AuditLog>,
+}
+
+impl Default for Fleet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fleet {
+    /// Create an empty fleet
+    pub fn new() -> Self {
+        Self {
+            devices: Vec::new(),
+            parallelism: DEFAULT_PARALLELISM,
+            timeout: None,
+            max_clock_adjustment: None,
+            audit: None,
+        }
+    }
+
+    /// Add a device under `name`
+    pub fn with_device(mut self, name: impl Into<String>, device: Device) -> Self {
+        self.push(name, device);
+        self
+    }
+
+    /// Set how many devices are worked on at once (default: 16, minimum 1)
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    /// Limit how long the operation may take on each device
+    ///
+    /// A device that runs out of time is reported with
+    /// [`Error::OperationTimeout`]. The operation is abandoned mid-exchange,
+    /// so reconnect that device before using it again.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Refuse to move a device clock by more than `limit` in
+    /// [`Fleet::sync_time`] (default: no limit)
+    pub fn with_max_clock_adjustment(mut self, limit: Duration) -> Self {
+        self.max_clock_adjustment = Some(limit);
+        self
+    }
+
+    /// Record every command issued to any device in `audit`, naming
+    /// entries after the devices
+    ///
+    /// Applies to devices added later too.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        for (name, device) in &mut self.devices {
+            device.set_audit(audit.clone().with_device_name(name.as_str()));
+        }
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Add a device under `name`
+    pub fn push(&mut self, name: impl Into<String>, mut device: Device) {
+        let name = name.into();
+        if let Some(audit) = &self.audit {
+            device.set_audit(audit.clone().with_device_name(name.as_str()));
+        }
+        self.devices.push((name, device));
+    }
+
+    /// Number of devices
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Check if the fleet has no devices
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Get a device by name
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Device> {
+        self.devices
+            .iter_mut()
+            .find(|(device_name, _)| device_name == name)
+            .map(|(_, device)| device)
+    }
+
+    /// Iterate over the devices and their names
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Device)> {
+        self.devices.iter_mut().map(|(name, device)| (name.as_str(), device))
+    }
+
+    /// Take the devices back out of the fleet
+    pub fn into_devices(self) -> Vec<(String, Device)> {
+        self.devices
+    }
+
+    /// Run `op` on every device
+    ///
+    /// Outcomes are reported in the order devices were added, whichever
+    /// finishes first.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async fn example(fleet: &mut zkrust::Fleet) {
+    /// let report = fleet.run(|device| Box::pin(async move {
+    ///     Ok(device.get_capacity().await?.records)
+    /// })).await;
+    /// println!("{}", report);
+    /// # }
+    /// ```
+    pub async fn run<'a, F, T>(&'a mut self, op: F) -> FleetReport<T>
+    where
+        F: Fn(&'a mut Device) -> DeviceFuture<'a, T> + Sync,
+    {
+        let timeout = self.timeout;
+        let op = &op;
+
+        debug!(
+            "Running on {} devices, {} at a time",
+            self.devices.len(),
+            self.parallelism
+        );
+
+        let mut outcomes: Vec<(usize, DeviceOutcome<T>)> = stream::iter(self.devices.iter_mut().enumerate())
+            .map(|(index, (name, device))| async move {
+                let started = Instant::now();
+                let result = match timeout {
+                    Some(limit) => match tokio::time::timeout(limit, op(device)).await {
+                        Ok(result) => result,
+                        Err(_) => Err(Error::OperationTimeout(limit)),
+                    },
+                    None => op(device).await,
+                };
+
+                if let Err(e) = &result {
+                    warn!("Fleet operation on {} failed: {}", name, e);
+                }
+
+                let outcome = DeviceOutcome {
+                    name: name.clone(),
+                    result,
+                    elapsed: started.elapsed(),
+                };
+                (index, outcome)
+            })
+            .buffer_unordered(self.parallelism)
+            .collect()
+            .await;
+
+        outcomes.sort_by_key(|(index, _)| *index);
+
+        FleetReport {
+            outcomes: outcomes.into_iter().map(|(_, outcome)| outcome).collect(),
+        }
+    }
+
+    /// Connect every device
+    pub async fn connect(&mut self) -> FleetReport<()> {
+        self.run(|device| Box::pin(device.connect())).await
+    }
+
+    /// Disconnect every device
+    pub async fn disconnect(&mut self) -> FleetReport<()> {
+        self.run(|device| Box::pin(device.disconnect())).await
+    }
+
+    /// Set every device clock to the current time in its timezone (see
+    /// [`Device::local_now`])
+    ///
+    /// Reports the skew of each device before and after. Devices further
+    /// off than the [maximum adjustment](Fleet::with_max_clock_adjustment)
+    /// are left alone and reported with [`Error::ClockAdjustmentRefused`].
+    pub async fn sync_time(&mut self) -> FleetReport<ClockSync> {
+        let limit = self.max_clock_adjustment;
+        self.run(|device| Box::pin(device.sync_clock(limit))).await
+    }
+
+    /// Read every device clock
+    pub async fn get_time(&mut self) -> FleetReport<NaiveDateTime> {
+        self.run(|device| Box::pin(device.get_time())).await
+    }
+
+    /// Download the attendance log of every device
+    pub async fn get_attendance(&mut self) -> FleetReport<Vec<AttendanceRecord>> {
+        self.run(|device| Box::pin(device.get_attendance())).await
+    }
+}
+
+/// Result of a fleet operation on one device
+#[derive(Debug)]
+pub struct DeviceOutcome<T> {
+    /// Device name in the fleet
+    pub name: String,
+
+    /// What the operation returned
+    pub result: Result<T>,
+
+    /// Time the operation took on this device
+    pub elapsed: Duration,
+}
+
+/// Results of a fleet operation, one per device
+#[derive(Debug)]
+pub struct FleetReport<T> {
+    /// Outcomes in fleet order
+    pub outcomes: Vec<DeviceOutcome<T>>,
+}
+
+impl<T> FleetReport<T> {
+    /// Check if the operation succeeded on every device
+    pub fn is_ok(&self) -> bool {
+        self.outcomes.iter().all(|outcome| outcome.result.is_ok())
+    }
+
+    /// Names and results of the devices that succeeded
+    pub fn successes(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| Some((outcome.name.as_str(), outcome.result.as_ref().ok()?)))
+    }
+
+    /// Names and errors of the devices that failed
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &Error)> {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| Some((outcome.name.as_str(), outcome.result.as_ref().err()?)))
+    }
+
+    /// Outcome of the device called `name`
+    pub fn get(&self, name: &str) -> Option<&DeviceOutcome<T>> {
+        self.outcomes.iter().find(|outcome| outcome.name == name)
+    }
+}
+
+impl<T> fmt::Display for FleetReport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.failures().count();
+        write!(
+            f,
+            "{} of {} devices succeeded",
+            self.outcomes.len() - failed,
+            self.outcomes.len()
+        )?;
+
+        for (name, error) in self.failures() {
+            write!(f, "\n  {}: {}", name, error)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::{Local, TimeDelta};
+    use zkrust_core::Command;
+    use zkrust_types::time;
+
+    use crate::testing::{ack_device, AckTransport};
+
+    #[tokio::test]
+    async fn test_run() {
+        let (disconnected, _) = AckTransport::new();
+        let mut fleet = Fleet::new()
+            .with_device("gate", ack_device().await.0)
+            .with_device("canteen", Device::with_transport(disconnected))
+            .with_device("office", ack_device().await.0);
+
+        let report = fleet.run(|device| Box::pin(device.enable_device())).await;
+
+        let names: Vec<_> = report.outcomes.iter().map(|outcome| outcome.name.as_str()).collect();
+        assert_eq!(names, ["gate", "canteen", "office"]);
+        assert!(!report.is_ok());
+        assert_eq!(report.successes().count(), 2);
+        assert!(matches!(report.failures().next(), Some(("canteen", Error::NotConnected))));
+        assert!(report.to_string().starts_with("2 of 3 devices succeeded\n  canteen: "));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_parallelism() {
+        let mut fleet = Fleet::new().with_parallelism(2);
+        for name in ["a", "b", "c", "d"] {
+            fleet.push(name, ack_device().await.0);
+        }
+
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let report = fleet
+            .run(|_| {
+                Box::pin(async {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                })
+            })
+            .await;
+
+        assert!(report.is_ok());
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout() {
+        let mut fleet = Fleet::new()
+            .with_device("gate", ack_device().await.0)
+            .with_timeout(Duration::from_secs(5));
+
+        let report = fleet
+            .run(|_| {
+                Box::pin(async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok(())
+                })
+            })
+            .await;
+
+        let outcome = report.get("gate").unwrap();
+        assert!(matches!(outcome.result, Err(Error::OperationTimeout(limit)) if limit == Duration::from_secs(5)));
+        assert_eq!(outcome.elapsed, Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_sync_time() {
+        let now = Local::now().naive_local();
+        let device_at = |at| {
+            let encoded = time::encode_time(&at).unwrap();
+            Device::with_transport(AckTransport::new().0.with_payload(Command::GetTime, encoded.to_le_bytes()))
+        };
+        let mut fleet = Fleet::new()
+            .with_device("gate", device_at(now - TimeDelta::seconds(90)))
+            .with_device("canteen", device_at(now + TimeDelta::days(365)))
+            .with_max_clock_adjustment(Duration::from_secs(3600));
+        fleet.connect().await;
+
+        let report = fleet.sync_time().await;
+        let (_, sync) = report.successes().next().unwrap();
+        assert!((sync.before.num_seconds() + 90).abs() <= 2);
+        assert!(matches!(
+            report.failures().next(),
+            Some(("canteen", Error::ClockAdjustmentRefused { .. }))
+        ));
+    }
+}
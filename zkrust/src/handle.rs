@@ -0,0 +1,278 @@
+//! Cloneable device handle
+//!
+//! [`Device`] needs `&mut self` for every command because the protocol is
+//! strictly request/response on one connection. [`DeviceHandle`] moves the
+//! device into a background task (an actor) and serializes commands from any
+//! number of clones through a queue, so e.g. an event listener and a polling
+//! task can share one connection.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use chrono::NaiveDateTime;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use zkrust_core::SessionStats;
+use zkrust_types::{AttendanceRecord, DeviceCapacity, DeviceInfo, User};
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::health::HealthReport;
+
+/// Boxed future borrowing the device for the duration of one job
+pub type DeviceFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+type Job = Box<dyn for<'a> FnOnce(&'a mut Device) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> + Send>;
+
+/// Default number of queued commands before callers wait
+pub const DEFAULT_QUEUE_CAPACITY: usize = 32;
+
+/// `Clone + Send` handle to a device owned by a background task
+///
+/// Commands from all clones are executed one at a time, in the order they
+/// were queued. The task disconnects the device and exits once every handle
+/// has been dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use zkrust::Device;
+///
+/// #[tokio::main]
+/// async fn main() -> zkrust::Result<()> {
+///     let mut device = Device::new("192.168.1.201", 4370);
+///     device.connect().await?;
+///
+///     let handle = device.into_handle();
+///     let poller = handle.clone();
+///
+///     tokio::spawn(async move {
+///         let info = poller.get_device_info().await;
+///         println!("{:?}", info);
+///     });
+///
+///     handle.enable_device().await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeviceHandle {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl DeviceHandle {
+    /// Move `device` into a background task and return a handle to it
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn spawn(device: Device) -> Self {
+        Self::spawn_with_capacity(device, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Like [`DeviceHandle::spawn`] with a custom command queue capacity
+    pub fn spawn_with_capacity(mut device: Device, capacity: usize) -> Self {
+        let (jobs, mut queue) = mpsc::channel::<Job>(capacity);
+
+        tokio::spawn(async move {
+            while let Some(job) = queue.recv().await {
+                job(&mut device).await;
+            }
+
+            debug!("All device handles dropped, shutting down actor");
+            if let Err(e) = device.disconnect().await {
+                warn!("Failed to disconnect device on actor shutdown: {}", e);
+            }
+        });
+
+        Self { jobs }
+    }
+
+    /// Run an arbitrary operation on the device
+    ///
+    /// The closure gets exclusive access to the device until its future
+    /// completes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async fn example(handle: zkrust::DeviceHandle) -> zkrust::Result<()> {
+    /// let read_only = handle.run(|device| Box::pin(async move {
+    ///     Ok(device.is_read_only())
+    /// })).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run<F, T>(&self, f: F) -> Result<T>
+    where
+        F: for<'a> FnOnce(&'a mut Device) -> DeviceFuture<'a, T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (reply, response) = oneshot::channel();
+
+        let job: Job = Box::new(move |device| {
+            Box::pin(async move {
+                // Caller may have given up waiting; nothing to do then
+                let _ = reply.send(f(device).await);
+            })
+        });
+
+        self.jobs.send(job).await.map_err(|_| Error::HandleClosed)?;
+        response.await.map_err(|_| Error::HandleClosed)?
+    }
+
+    /// Check if the actor task is still running
+    pub fn is_closed(&self) -> bool {
+        self.jobs.is_closed()
+    }
+
+    /// Check if the device is connected
+    pub async fn is_connected(&self) -> Result<bool> {
+        self.run(|device| Box::pin(async move { Ok(device.is_connected()) })).await
+    }
+
+    /// Get protocol statistics for the device's session
+    pub async fn stats(&self) -> Result<SessionStats> {
+        self.run(|device| Box::pin(async move { Ok(device.stats()) })).await
+    }
+
+    /// Connect to device
+    pub async fn connect(&self) -> Result<()> {
+        self.run(|device| Box::pin(device.connect())).await
+    }
+
+    /// Disconnect from device
+    ///
+    /// The actor keeps running; other handles can reconnect.
+    pub async fn disconnect(&self) -> Result<()> {
+        self.run(|device| Box::pin(device.disconnect())).await
+    }
+
+    /// Get device information
+    pub async fn get_device_info(&self) -> Result<DeviceInfo> {
+        self.run(|device| Box::pin(device.get_device_info())).await
+    }
+
+    /// Get the device clock
+    pub async fn get_time(&self) -> Result<NaiveDateTime> {
+        self.run(|device| Box::pin(device.get_time())).await
+    }
+
+    /// Set the device clock
+    pub async fn set_time(&self, time: NaiveDateTime) -> Result<()> {
+        self.run(move |device| Box::pin(device.set_time(time))).await
+    }
+
+    /// Get used and total storage
+    pub async fn get_capacity(&self) -> Result<DeviceCapacity> {
+        self.run(|device| Box::pin(device.get_capacity())).await
+    }
+
+    /// Download all attendance records
+    pub async fn get_attendance(&self) -> Result<Vec<AttendanceRecord>> {
+        self.run(|device| Box::pin(device.get_attendance())).await
+    }
+
+    /// Download all enrolled users
+    pub async fn get_users(&self) -> Result<Vec<User>> {
+        self.run(|device| Box::pin(device.get_users())).await
+    }
+
+    /// Create or update a user
+    pub async fn set_user(&self, user: User) -> Result<()> {
+        self.run(move |device| Box::pin(async move { device.set_user(&user).await })).await
+    }
+
+    /// Delete a user by internal UID
+    pub async fn delete_user(&self, uid: u16) -> Result<()> {
+        self.run(move |device| Box::pin(device.delete_user(uid))).await
+    }
+
+    /// Check device health with the default thresholds
+    pub async fn health_check(&self) -> Result<HealthReport> {
+        self.run(|device| Box::pin(async move { Ok(device.health_check().await) })).await
+    }
+
+    /// Enable device (normal operation mode)
+    pub async fn enable_device(&self) -> Result<()> {
+        self.run(|device| Box::pin(device.enable_device())).await
+    }
+
+    /// Disable device (show "Working..." on LCD)
+    pub async fn disable_device(&self) -> Result<()> {
+        self.run(|device| Box::pin(device.disable_device())).await
+    }
+
+    /// Refresh device data
+    pub async fn refresh_data(&self) -> Result<()> {
+        self.run(|device| Box::pin(device.refresh_data())).await
+    }
+
+    /// Restart device
+    pub async fn restart(&self) -> Result<()> {
+        self.run(|device| Box::pin(device.restart())).await
+    }
+
+    /// Power off device
+    pub async fn power_off(&self) -> Result<()> {
+        self.run(|device| Box::pin(device.power_off())).await
+    }
+}
+
+impl Device {
+    /// Move this device into a background task and return a cloneable handle
+    ///
+    /// See [`DeviceHandle`].
+    pub fn into_handle(self) -> DeviceHandle {
+        DeviceHandle::spawn(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ack_device;
+    use zkrust_core::Command;
+
+    #[tokio::test]
+    async fn test_handle_serializes_commands_from_clones() {
+        let (device, sent) = ack_device().await;
+        let handle = device.into_handle();
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let handle = handle.clone();
+                tokio::spawn(async move { handle.enable_device().await })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(*sent.lock().unwrap(), vec![Command::EnableDevice; 4]);
+        // CMD_CONNECT plus the four commands above
+        assert_eq!(handle.stats().await.unwrap().commands_sent, 5);
+    }
+
+    #[tokio::test]
+    async fn test_handle_disconnects_when_dropped() {
+        let (device, sent) = ack_device().await;
+        let handle = device.into_handle();
+
+        assert!(handle.is_connected().await.unwrap());
+        drop(handle);
+
+        // Give the actor a chance to shut down
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(*sent.lock().unwrap(), vec![Command::Exit]);
+    }
+
+    #[test]
+    fn test_handle_is_send_sync_clone() {
+        fn assert_traits<T: Send + Sync + Clone>() {}
+        assert_traits::<DeviceHandle>();
+    }
+}
@@ -0,0 +1,483 @@
+//! Hardware compatibility harness (`integration-tests` feature)
+//!
+//! Runs a read-only test matrix against real terminals and reports, per
+//! model, which connection variants and queries work. Nothing on the device
+//! is changed: the matrix connects, reads the device info, clock, capacity
+//! and an option, and lists users and attendance only when there are few.
+//!
+//! Devices come from environment variables, so the same test binary runs
+//! against whatever hardware is on the bench:
+//!
+//! | Variable | Meaning |
+//! |---|---|
+//! | `ZKRUST_IT_DEVICES` | Comma-separated `host[:port]` list (required) |
+//! | `ZKRUST_IT_COMMKEY` | CommKey for every device |
+//! | `ZKRUST_IT_TIMEOUT` | Receive timeout in seconds |
+//! | `ZKRUST_IT_REPORT` | File to write the Markdown report to |
+//!
+//! ```bash
+//! ZKRUST_IT_DEVICES=192.168.1.201,192.168.1.202 \
+//!     cargo test -p zkrust --features integration-tests --test hardware -- --nocapture
+//! ```
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+
+/// Variable listing the devices under test
+pub const DEVICES_VAR: &str = "ZKRUST_IT_DEVICES";
+
+/// Variable holding the CommKey
+pub const COMMKEY_VAR: &str = "ZKRUST_IT_COMMKEY";
+
+/// Variable holding the receive timeout in seconds
+pub const TIMEOUT_VAR: &str = "ZKRUST_IT_TIMEOUT";
+
+/// Variable naming the report file
+pub const REPORT_VAR: &str = "ZKRUST_IT_REPORT";
+
+/// Most users the matrix lists; larger tables are skipped
+pub const SMALL_READ_USERS: u32 = 500;
+
+/// Most attendance records the matrix downloads; larger logs are skipped
+pub const SMALL_READ_RECORDS: u32 = 2000;
+
+/// Device under test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HarnessTarget {
+    pub host: String,
+    pub port: u16,
+    pub password: Option<u32>,
+    pub timeout: Option<Duration>,
+}
+
+impl HarnessTarget {
+    /// Target at `host:port` without a CommKey
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            password: None,
+            timeout: None,
+        }
+    }
+
+    /// Targets from [`DEVICES_VAR`], [`COMMKEY_VAR`] and [`TIMEOUT_VAR`]
+    ///
+    /// Empty if no devices are listed, so callers can skip the matrix.
+    pub fn from_env() -> Result<Vec<Self>> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        let Some(devices) = var(DEVICES_VAR) else {
+            return Ok(Vec::new());
+        };
+
+        let password = var(COMMKEY_VAR)
+            .map(|key| key.trim().parse::<u32>())
+            .transpose()
+            .map_err(|e| Error::Config(format!("{}: {}", COMMKEY_VAR, e)))?;
+        let timeout = var(TIMEOUT_VAR)
+            .map(|secs| secs.trim().parse::<u64>().map(Duration::from_secs))
+            .transpose()
+            .map_err(|e| Error::Config(format!("{}: {}", TIMEOUT_VAR, e)))?;
+
+        devices
+            .split(',')
+            .map(str::trim)
+            .filter(|device| !device.is_empty())
+            .map(|device| {
+                let mut target = Self::parse(device)?;
+                target.password = password;
+                target.timeout = timeout;
+                Ok(target)
+            })
+            .collect()
+    }
+
+    /// Parse `host` or `host:port` (default port 4370)
+    pub fn parse(address: &str) -> Result<Self> {
+        match address.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|_| Error::Config(format!("Invalid port in {:?}", address)))?;
+                Ok(Self::new(host, port))
+            }
+            None => Ok(Self::new(address, 4370)),
+        }
+    }
+
+    /// Disconnected device for one connection variant
+    pub fn device(&self, variant: ConnectVariant) -> Device {
+        let mut device = match variant {
+            ConnectVariant::Tcp => Device::new(self.host.clone(), self.port),
+            ConnectVariant::Udp => Device::new_udp(self.host.clone(), self.port),
+        };
+        if let Some(password) = self.password {
+            device = device.with_password(password);
+        }
+        if let Some(timeout) = self.timeout {
+            device = device.with_timeout(timeout);
+        }
+        device
+    }
+}
+
+impl fmt::Display for HarnessTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+/// Way of connecting tried by the matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectVariant {
+    /// TCP, classic packets
+    Tcp,
+    /// UDP, classic packets
+    Udp,
+}
+
+impl ConnectVariant {
+    /// Every variant, baseline first
+    pub const ALL: [Self; 2] = [Self::Tcp, Self::Udp];
+
+    /// Short name used in reports
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tcp => "tcp",
+            Self::Udp => "udp",
+        }
+    }
+}
+
+/// Result of one case
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseOutcome {
+    /// Worked; the detail is what the device answered
+    Passed(String),
+    /// Failed with this error
+    Failed(String),
+    /// Not run, for this reason
+    Skipped(String),
+}
+
+/// One row of a [`CompatReport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseResult {
+    pub name: String,
+    pub outcome: CaseOutcome,
+    pub elapsed: Duration,
+
+    /// Whether a failure makes the device incompatible; the other cases
+    /// only record what the firmware supports
+    pub required: bool,
+}
+
+impl CaseResult {
+    /// Whether the case failed
+    pub fn failed(&self) -> bool {
+        matches!(self.outcome, CaseOutcome::Failed(_))
+    }
+}
+
+/// Matrix results for one device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatReport {
+    pub target: String,
+    pub model: Option<String>,
+    pub firmware: Option<String>,
+    pub serial_number: Option<String>,
+    pub cases: Vec<CaseResult>,
+}
+
+impl CompatReport {
+    /// Whether every required case passed
+    pub fn is_compatible(&self) -> bool {
+        !self.cases.iter().any(|case| case.required && case.failed())
+    }
+
+    /// Failed cases, required or not
+    pub fn failures(&self) -> impl Iterator<Item = &CaseResult> {
+        self.cases.iter().filter(|case| case.failed())
+    }
+
+    fn record<T>(
+        &mut self,
+        name: &str,
+        required: bool,
+        started: Instant,
+        result: Result<T>,
+        detail: impl Fn(&T) -> String,
+    ) {
+        let outcome = match &result {
+            Ok(value) => CaseOutcome::Passed(detail(value)),
+            Err(e) => CaseOutcome::Failed(e.to_string()),
+        };
+        self.cases.push(CaseResult {
+            name: name.to_string(),
+            outcome,
+            elapsed: started.elapsed(),
+            required,
+        });
+    }
+
+    fn skip(&mut self, name: &str, reason: impl Into<String>) {
+        self.cases.push(CaseResult {
+            name: name.to_string(),
+            outcome: CaseOutcome::Skipped(reason.into()),
+            elapsed: Duration::ZERO,
+            required: false,
+        });
+    }
+}
+
+/// Run the matrix against `target`
+pub async fn run_matrix(target: &HarnessTarget) -> CompatReport {
+    run_matrix_with(target.to_string(), |variant| target.device(variant)).await
+}
+
+/// Run the matrix on devices built by `device` for each variant
+///
+/// Each variant connects and disconnects on its own; the queries then run
+/// over the first variant that connected.
+pub async fn run_matrix_with(
+    target: impl Into<String>,
+    mut device: impl FnMut(ConnectVariant) -> Device,
+) -> CompatReport {
+    let mut report = CompatReport {
+        target: target.into(),
+        model: None,
+        firmware: None,
+        serial_number: None,
+        cases: Vec::new(),
+    };
+
+    let mut working = None;
+    for variant in ConnectVariant::ALL {
+        let mut candidate = device(variant);
+        let started = Instant::now();
+        let result = candidate.connect().await;
+        let connected = result.is_ok();
+        report.record(
+            &format!("connect/{}", variant.name()),
+            variant == ConnectVariant::Tcp,
+            started,
+            result,
+            |()| "connected".into(),
+        );
+        if connected {
+            let _ = candidate.disconnect().await;
+            working.get_or_insert(variant);
+        }
+    }
+
+    let queries = ["info", "platform", "time", "capacity", "option", "users", "attendance"];
+    let Some(variant) = working else {
+        for name in queries {
+            report.skip(name, "no connection variant worked");
+        }
+        return report;
+    };
+
+    let mut device = device(variant);
+    if let Err(e) = device.connect().await {
+        for name in queries {
+            report.skip(name, format!("reconnecting over {} failed: {}", variant.name(), e));
+        }
+        return report;
+    }
+    queries_on(&mut device, &mut report).await;
+    let _ = device.disconnect().await;
+    report
+}
+
+async fn queries_on(device: &mut Device, report: &mut CompatReport) {
+    let started = Instant::now();
+    let info = device.get_device_info().await;
+    if let Ok(info) = &info {
+        report.model = info.model.clone();
+        report.firmware = Some(info.firmware_version.clone());
+        report.serial_number = Some(info.serial_number.clone());
+    }
+    report.record("info", true, started, info, |info| info.to_string());
+
+    let started = Instant::now();
+    let platform = device.get_platform_info().await;
+    report.record("platform", false, started, platform, |platform| format!("{:?}", platform));
+
+    let started = Instant::now();
+    let time = device.get_time().await;
+    report.record("time", true, started, time, |time| {
+        format!("{} (skew {}s)", time, (*time - Local::now().naive_local()).num_seconds())
+    });
+
+    let started = Instant::now();
+    let capacity = device.get_capacity().await;
+    let counts = capacity.as_ref().ok().map(|capacity| (capacity.users, capacity.records));
+    report.record("capacity", true, started, capacity, |capacity| capacity.to_string());
+
+    let started = Instant::now();
+    let option = device.get_option("~Platform").await;
+    report.record("option", false, started, option, |value| format!("~Platform={:?}", value));
+
+    let Some((users, records)) = counts else {
+        report.skip("users", "capacity unknown");
+        report.skip("attendance", "capacity unknown");
+        return;
+    };
+
+    if users <= SMALL_READ_USERS {
+        let started = Instant::now();
+        let result = device.get_users().await;
+        report.record("users", false, started, result, |users| format!("{} users", users.len()));
+    } else {
+        report.skip("users", format!("{} users is more than {}", users, SMALL_READ_USERS));
+    }
+
+    if records <= SMALL_READ_RECORDS {
+        let started = Instant::now();
+        let result = device.get_attendance().await;
+        report.record("attendance", false, started, result, |records| format!("{} records", records.len()));
+    } else {
+        report.skip("attendance", format!("{} records is more than {}", records, SMALL_READ_RECORDS));
+    }
+}
+
+/// Reports of several devices, printed as Markdown grouped by model
+#[derive(Debug, Clone, Default)]
+pub struct CompatMatrix {
+    reports: Vec<CompatReport>,
+}
+
+impl CompatMatrix {
+    /// Empty matrix
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one device's report
+    pub fn push(&mut self, report: CompatReport) {
+        self.reports.push(report);
+    }
+
+    /// Reports in the order added
+    pub fn reports(&self) -> &[CompatReport] {
+        &self.reports
+    }
+
+    /// Devices with a failed required case
+    pub fn incompatible(&self) -> impl Iterator<Item = &CompatReport> {
+        self.reports.iter().filter(|report| !report.is_compatible())
+    }
+}
+
+impl fmt::Display for CompatMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Compatibility report")?;
+
+        let mut models: Vec<&str> = self
+            .reports
+            .iter()
+            .map(|report| report.model.as_deref().unwrap_or("Unknown model"))
+            .collect();
+        models.sort_unstable();
+        models.dedup();
+
+        for model in models {
+            writeln!(f, "\n## {}", model)?;
+            let reports = self
+                .reports
+                .iter()
+                .filter(|report| report.model.as_deref().unwrap_or("Unknown model") == model);
+            for report in reports {
+                writeln!(
+                    f,
+                    "\n### {} ({})\n",
+                    report.target,
+                    if report.is_compatible() { "compatible" } else { "INCOMPATIBLE" }
+                )?;
+                if let Some(firmware) = &report.firmware {
+                    let serial = report.serial_number.as_deref().unwrap_or("?");
+                    writeln!(f, "Firmware {}, serial {}\n", firmware, serial)?;
+                }
+                writeln!(f, "| Case | Result | Time | Detail |")?;
+                writeln!(f, "|---|---|---|---|")?;
+                for case in &report.cases {
+                    let (result, detail) = match &case.outcome {
+                        CaseOutcome::Passed(detail) => ("pass", detail),
+                        CaseOutcome::Failed(error) if case.required => ("FAIL", error),
+                        CaseOutcome::Failed(error) => ("unsupported", error),
+                        CaseOutcome::Skipped(reason) => ("skipped", reason),
+                    };
+                    writeln!(
+                        f,
+                        "| {} | {} | {} ms | {} |",
+                        case.name,
+                        result,
+                        case.elapsed.as_millis(),
+                        detail.replace('|', "\\|")
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zkrust_core::Command;
+    use zkrust_types::DeviceCapacity;
+
+    use crate::testing::AckTransport;
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(HarnessTarget::parse("10.0.0.5").unwrap(), HarnessTarget::new("10.0.0.5", 4370));
+        assert_eq!(HarnessTarget::parse("10.0.0.5:4371").unwrap().port, 4371);
+        assert!(HarnessTarget::parse("10.0.0.5:x").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_matrix() {
+        let capacity = DeviceCapacity {
+            users: 2,
+            records: 100_000,
+            ..DeviceCapacity::default()
+        };
+        let now = zkrust_types::time::encode_time(&Local::now().naive_local()).unwrap();
+        let report = run_matrix_with("bench", |variant| {
+            let (transport, _) = AckTransport::new();
+            let transport = transport
+                .with_payload(Command::GetVersion, &b"Ver 6.60 Apr 28 2016\0"[..])
+                .with_payload(Command::GetTime, now.to_le_bytes())
+                .with_payload(Command::GetFreeSizes, capacity.encode());
+            match variant {
+                // No UDP on this bench
+                ConnectVariant::Udp => Device::with_transport(transport.rejecting(Command::Connect)),
+                ConnectVariant::Tcp => Device::with_transport(transport),
+            }
+        })
+        .await;
+
+        let outcome = |name: &str| &report.cases.iter().find(|case| case.name == name).unwrap().outcome;
+        assert_eq!(outcome("connect/tcp"), &CaseOutcome::Passed("connected".into()));
+        assert!(matches!(outcome("connect/udp"), CaseOutcome::Failed(_)));
+        assert!(matches!(outcome("attendance"), CaseOutcome::Skipped(_)));
+        assert_eq!(report.firmware.as_deref(), Some("Ver 6.60 Apr 28 2016"));
+        assert!(report.is_compatible(), "{:?}", report.cases);
+
+        let mut matrix = CompatMatrix::new();
+        matrix.push(report);
+        let text = matrix.to_string();
+        assert!(text.contains("## Unknown model\n\n### bench (compatible)"), "{}", text);
+        assert!(text.contains("| connect/udp | unsupported |"), "{}", text);
+        assert_eq!(matrix.incompatible().count(), 0);
+    }
+}
@@ -0,0 +1,262 @@
+//! Device health checks
+//!
+//! [`Device::health_check`] gathers connectivity, auth state, clock skew and
+//! storage usage in one call and summarizes problems as [`HealthIssue`]s, so
+//! a fleet monitor can expose one report per terminal.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use tracing::debug;
+
+use zkrust_types::DeviceCapacity;
+
+use crate::device::Device;
+
+/// Limits above which a health check reports an issue
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthThresholds {
+    /// Largest tolerated difference between device and host clock
+    pub max_clock_skew: Duration,
+
+    /// Storage usage fraction (0.0-1.0) that counts as nearly full
+    pub capacity_warning: f64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            max_clock_skew: Duration::from_secs(60),
+            capacity_warning: 0.9,
+        }
+    }
+}
+
+/// Problem found by a health check
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthIssue {
+    /// The device is not connected
+    NotConnected,
+
+    /// A query failed; `check` names the query
+    CheckFailed { check: &'static str, error: String },
+
+    /// Device clock differs from the host clock by more than the threshold
+    ClockSkew { skew: TimeDelta },
+
+    /// A storage area is nearly full
+    CapacityLow { store: &'static str, usage: f64 },
+}
+
+impl fmt::Display for HealthIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConnected => write!(f, "Device not connected"),
+            Self::CheckFailed { check, error } => write!(f, "{} check failed: {}", check, error),
+            Self::ClockSkew { skew } => {
+                write!(f, "Device clock is off by {}s", skew.num_seconds())
+            }
+            Self::CapacityLow { store, usage } => {
+                write!(f, "{} storage {:.0}% full", store, usage * 100.0)
+            }
+        }
+    }
+}
+
+/// Result of [`Device::health_check`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    /// Host time when the check started
+    pub checked_at: NaiveDateTime,
+
+    /// Whether the device was connected
+    pub connected: bool,
+
+    /// Whether the session authenticated with a CommKey
+    ///
+    /// `false` is normal for devices without a password.
+    pub authenticated: bool,
+
+    /// Round-trip time of the clock query
+    pub latency: Option<Duration>,
+
+    /// Device clock
+    pub device_time: Option<NaiveDateTime>,
+
+    /// Device clock minus host clock
+    pub clock_skew: Option<TimeDelta>,
+
+    /// Storage usage
+    pub capacity: Option<DeviceCapacity>,
+
+    /// Problems found, empty if the device is healthy
+    pub issues: Vec<HealthIssue>,
+}
+
+impl HealthReport {
+    /// Check if no issues were found
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl Device {
+    /// Check device health with the default thresholds
+    ///
+    /// Never fails: unreachable devices and failed queries are reported as
+    /// issues. The device is not connected automatically.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # async fn example(device: &mut zkrust::Device) {
+    /// let report = device.health_check().await;
+    /// for issue in &report.issues {
+    ///     println!("{}", issue);
+    /// }
+    /// # }
+    /// ```
+    pub async fn health_check(&mut self) -> HealthReport {
+        self.health_check_with(&HealthThresholds::default()).await
+    }
+
+    /// Check device health with custom thresholds
+    pub async fn health_check_with(&mut self, thresholds: &HealthThresholds) -> HealthReport {
+        let mut report = HealthReport {
+            checked_at: Local::now().naive_local(),
+            connected: self.is_connected(),
+            authenticated: self.is_authenticated(),
+            latency: None,
+            device_time: None,
+            clock_skew: None,
+            capacity: None,
+            issues: Vec::new(),
+        };
+
+        if !report.connected {
+            report.issues.push(HealthIssue::NotConnected);
+            return report;
+        }
+
+        let started = Instant::now();
+        match self.get_time().await {
+            Ok(device_time) => {
+                report.latency = Some(started.elapsed());
+                report.device_time = Some(device_time);
+
+                // Devices keep local time, so compare against the time where they are
+                let skew = device_time - self.local_now();
+                report.clock_skew = Some(skew);
+
+                if skew.abs().to_std().is_ok_and(|s| s > thresholds.max_clock_skew) {
+                    report.issues.push(HealthIssue::ClockSkew { skew });
+                }
+            }
+            Err(e) => report.issues.push(HealthIssue::CheckFailed {
+                check: "time",
+                error: e.to_string(),
+            }),
+        }
+
+        match self.get_capacity().await {
+            Ok(capacity) => {
+                let stores = [
+                    ("User", capacity.users_usage()),
+                    ("Fingerprint", capacity.fingers_usage()),
+                    ("Record", capacity.records_usage()),
+                    ("Face", capacity.faces_usage()),
+                ];
+
+                for (store, usage) in stores {
+                    if let Some(usage) = usage.filter(|u| *u >= thresholds.capacity_warning) {
+                        report.issues.push(HealthIssue::CapacityLow { store, usage });
+                    }
+                }
+
+                report.capacity = Some(capacity);
+            }
+            Err(e) => report.issues.push(HealthIssue::CheckFailed {
+                check: "capacity",
+                error: e.to_string(),
+            }),
+        }
+
+        debug!("Health check: {} issue(s)", report.issues.len());
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::AckTransport;
+    use zkrust_core::Command;
+    use zkrust_types::time;
+
+    fn free_sizes(users: u32, users_capacity: u32) -> Vec<u8> {
+        let mut payload = vec![0u8; 80];
+        payload[16..20].copy_from_slice(&users.to_le_bytes());
+        payload[60..64].copy_from_slice(&users_capacity.to_le_bytes());
+        payload
+    }
+
+    async fn device_with(transport: AckTransport) -> Device {
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+        device
+    }
+
+    #[tokio::test]
+    async fn test_health_check_healthy() {
+        let now = time::encode_time(&Local::now().naive_local()).unwrap();
+        let (transport, _) = AckTransport::new();
+        let transport = transport
+            .with_payload(Command::GetTime, now.to_le_bytes())
+            .with_payload(Command::GetFreeSizes, free_sizes(10, 1000));
+
+        let report = device_with(transport).await.health_check().await;
+
+        assert!(report.is_healthy(), "{:?}", report.issues);
+        assert!(report.connected);
+        assert!(report.latency.is_some());
+        assert_eq!(report.capacity.unwrap().users, 10);
+    }
+
+    #[tokio::test]
+    async fn test_health_check_reports_issues() {
+        let (transport, _) = AckTransport::new();
+        let transport = transport
+            .with_payload(Command::GetTime, 0u32.to_le_bytes())
+            .with_payload(Command::GetFreeSizes, free_sizes(950, 1000));
+
+        let report = device_with(transport).await.health_check().await;
+
+        assert!(matches!(report.issues[0], HealthIssue::ClockSkew { .. }));
+        assert_eq!(
+            report.issues[1],
+            HealthIssue::CapacityLow {
+                store: "User",
+                usage: 0.95
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_check_failed_queries() {
+        let (transport, _) = AckTransport::new();
+        let report = device_with(transport).await.health_check().await;
+
+        assert_eq!(report.issues.len(), 2);
+        assert!(matches!(report.issues[0], HealthIssue::CheckFailed { check: "time", .. }));
+    }
+
+    #[tokio::test]
+    async fn test_health_check_disconnected() {
+        let report = Device::new("192.168.1.201", 4370).health_check().await;
+
+        assert!(!report.connected);
+        assert_eq!(report.issues, vec![HealthIssue::NotConnected]);
+    }
+}
@@ -30,14 +30,74 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! The [`prelude`] re-exports the device, record types and protocol enums
+//! for a single `use zkrust::prelude::*;`.
 
+pub mod aggregate;
+pub mod audit;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod bulk;
+pub mod capability;
+pub mod capacity;
+pub mod capture;
+pub mod clock;
 pub mod device;
 pub mod error;
+pub mod face;
+pub mod fleet;
+pub mod handle;
+#[cfg(feature = "integration-tests")]
+pub mod harness;
+pub mod health;
+pub mod monitor;
+pub mod pool;
+pub mod power;
+pub mod prelude;
+pub mod profile;
+#[cfg(feature = "registry")]
+pub mod registry;
+pub mod replay;
+pub mod replicate;
+pub mod secret;
+pub mod settings;
+pub mod stream;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+#[cfg(feature = "tz")]
+pub mod timezone;
+
+#[cfg(test)]
+mod testing;
 
 // Re-exports
+pub use aggregate::{Aggregated, TaggedRecord};
+pub use audit::{AuditEntry, AuditLog, AuditSink};
+pub use bulk::{BulkDelete, BulkDeleteReport};
+pub use capability::Capability;
+pub use capacity::{CapacityWarning, CapacityWatch};
+pub use clock::ClockSync;
 pub use device::Device;
-pub use error::{Error, Result};
+pub use error::{Error, ErrorContext, Result};
+pub use face::FaceSettings;
+pub use fleet::{Fleet, FleetReport};
+pub use handle::DeviceHandle;
+pub use health::{HealthIssue, HealthReport};
+pub use monitor::{DeviceStatus, FleetMonitor, StatusBoard};
+pub use pool::{DevicePool, Lease};
+pub use power::PowerSchedule;
+pub use profile::{DeviceProfile, ProfileRegistry};
+#[cfg(feature = "registry")]
+pub use registry::{DeviceEntry, DeviceRegistry};
+pub use replicate::{replicate_users, ReplicationReport, SkippedTemplate, SkippedUser};
+pub use settings::{SettingsDiff, SettingsProfile};
+pub use stream::RecordStream;
+#[cfg(feature = "tz")]
+pub use timezone::ZonedRecord;
 
 // Re-export types
-pub use zkrust_core::{Command, Packet, Session};
-pub use zkrust_types::DeviceInfo;
\ No newline at end of file
+pub use zkrust_core::{Command, Packet, RetryClass, Session, SessionStats};
+pub use zkrust_types::{
+    AttendanceRecord, DeviceInfo, FingerprintTemplate, FirmwareVersion, PlatformInfo, StringCodec, User,
+};
\ No newline at end of file
@@ -0,0 +1,432 @@
+//! Background fleet health monitoring
+//!
+//! [`FleetMonitor`] health-checks every device on an interval, reconnecting
+//! dropped connections, and keeps the latest [`DeviceState`] of each on a
+//! [`StatusBoard`]. Status transitions (e.g. healthy to unreachable) are
+//! broadcast as [`StatusChange`]s, so dashboards, HTTP endpoints and metrics
+//! exporters can all read from one monitor instead of polling the devices
+//! themselves.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use zkrust::monitor::FleetMonitor;
+//! use zkrust::Device;
+//!
+//! # async fn example() {
+//! let monitor = FleetMonitor::new()
+//!     .with_device("gate", Device::new("192.168.1.201", 4370))
+//!     .with_device("canteen", Device::new("192.168.1.202", 4370))
+//!     .with_interval(Duration::from_secs(30))
+//!     .spawn();
+//!
+//! let board = monitor.board();
+//! let mut changes = board.subscribe();
+//! while let Ok(change) = changes.recv().await {
+//!     println!("{}: {} -> {}", change.device, change.previous, change.state.status);
+//! }
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{Local, NaiveDateTime};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+
+use zkrust_core::RetryClass;
+
+use crate::device::Device;
+use crate::fleet::Fleet;
+use crate::health::{HealthReport, HealthThresholds};
+
+/// Default time between health checks of one device
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of status changes buffered for slow subscribers
+const EVENT_CAPACITY: usize = 64;
+
+/// Overall condition of a monitored device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceStatus {
+    /// Not checked yet
+    Unknown,
+
+    /// Connected, no issues
+    Healthy,
+
+    /// Connected, but the health check found issues (clock skew, storage
+    /// nearly full, failed queries)
+    Degraded,
+
+    /// The device rejected the CommKey
+    AuthFailed,
+
+    /// The device could not be reached
+    Unreachable,
+}
+
+impl fmt::Display for DeviceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "unknown"),
+            Self::Healthy => write!(f, "healthy"),
+            Self::Degraded => write!(f, "degraded"),
+            Self::AuthFailed => write!(f, "auth failed"),
+            Self::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+/// Latest known state of a monitored device
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceState {
+    /// Current status
+    pub status: DeviceStatus,
+
+    /// Host time the device entered this status
+    pub since: NaiveDateTime,
+
+    /// Health report of the last check that reached the device
+    pub report: Option<HealthReport>,
+
+    /// Connection error of the most recent check
+    pub error: Option<String>,
+}
+
+impl DeviceState {
+    fn unknown() -> Self {
+        Self {
+            status: DeviceStatus::Unknown,
+            since: Local::now().naive_local(),
+            report: None,
+            error: None,
+        }
+    }
+}
+
+/// Status transition of one device
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusChange {
+    /// Device name
+    pub device: String,
+
+    /// Status before the transition
+    pub previous: DeviceStatus,
+
+    /// State after the transition
+    pub state: DeviceState,
+}
+
+/// Shared view of the monitored devices
+///
+/// Cheap to clone; every clone sees the same states and events.
+#[derive(Debug, Clone)]
+pub struct StatusBoard {
+    states: Arc<Mutex<BTreeMap<String, DeviceState>>>,
+    events: broadcast::Sender<StatusChange>,
+}
+
+impl StatusBoard {
+    fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let states = names
+            .into_iter()
+            .map(|name| (name.to_string(), DeviceState::unknown()))
+            .collect();
+        Self {
+            states: Arc::new(Mutex::new(states)),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// State of one device
+    pub fn get(&self, name: &str) -> Option<DeviceState> {
+        self.states.lock().unwrap().get(name).cloned()
+    }
+
+    /// States of every device, sorted by name
+    pub fn snapshot(&self) -> BTreeMap<String, DeviceState> {
+        self.states.lock().unwrap().clone()
+    }
+
+    /// Number of devices in each status
+    pub fn counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for state in self.states.lock().unwrap().values() {
+            *counts.entry(state.status.to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Receive future status transitions
+    pub fn subscribe(&self) -> broadcast::Receiver<StatusChange> {
+        self.events.subscribe()
+    }
+
+    /// Record the outcome of one check, broadcasting a change of status
+    fn update(&self, name: &str, status: DeviceStatus, report: Option<HealthReport>, error: Option<String>) {
+        let change = {
+            let mut states = self.states.lock().unwrap();
+            let state = states.entry(name.to_string()).or_insert_with(DeviceState::unknown);
+            let previous = state.status;
+
+            if report.is_some() {
+                state.report = report;
+            }
+            state.error = error;
+            if status == previous {
+                return;
+            }
+            state.status = status;
+            state.since = Local::now().naive_local();
+
+            StatusChange {
+                device: name.to_string(),
+                previous,
+                state: state.clone(),
+            }
+        };
+
+        info!("{} is now {} (was {})", name, change.state.status, change.previous);
+        // No subscribers is fine
+        let _ = self.events.send(change);
+    }
+}
+
+/// Periodic health checks of a set of named devices
+pub struct FleetMonitor {
+    devices: Vec<(String, Device)>,
+    interval: Duration,
+    thresholds: HealthThresholds,
+}
+
+impl Default for FleetMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FleetMonitor {
+    /// Create a monitor without devices
+    pub fn new() -> Self {
+        Self {
+            devices: Vec::new(),
+            interval: DEFAULT_INTERVAL,
+            thresholds: HealthThresholds::default(),
+        }
+    }
+
+    /// Monitor every device of `fleet`
+    pub fn from_fleet(fleet: Fleet) -> Self {
+        Self {
+            devices: fleet.into_devices(),
+            ..Self::new()
+        }
+    }
+
+    /// Monitor every device in `registry`
+    #[cfg(feature = "registry")]
+    pub fn from_registry(registry: &crate::registry::DeviceRegistry) -> crate::Result<Self> {
+        Ok(Self::from_fleet(registry.fleet()?))
+    }
+
+    /// Add a device under `name`
+    pub fn with_device(mut self, name: impl Into<String>, device: Device) -> Self {
+        self.devices.push((name.into(), device));
+        self
+    }
+
+    /// Set the time between checks of one device (default: 60 seconds)
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the limits used by the health checks
+    pub fn with_thresholds(mut self, thresholds: HealthThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Start one background task per device
+    ///
+    /// The first check runs immediately. Must be called within a Tokio
+    /// runtime.
+    pub fn spawn(self) -> MonitorHandle {
+        let board = StatusBoard::new(self.devices.iter().map(|(name, _)| name.as_str()));
+        let (stop, stopped) = watch::channel(false);
+
+        let tasks = self
+            .devices
+            .into_iter()
+            .map(|(name, device)| {
+                let watcher = Watcher {
+                    name,
+                    device,
+                    interval: self.interval,
+                    thresholds: self.thresholds,
+                    board: board.clone(),
+                };
+                tokio::spawn(watcher.run(stopped.clone()))
+            })
+            .collect();
+
+        MonitorHandle { board, stop, tasks }
+    }
+}
+
+/// Running [`FleetMonitor`]
+///
+/// Dropping the handle aborts the checks; [`MonitorHandle::shutdown`] stops
+/// them and disconnects the devices.
+pub struct MonitorHandle {
+    board: StatusBoard,
+    stop: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MonitorHandle {
+    /// Shared view of the device states
+    pub fn board(&self) -> StatusBoard {
+        self.board.clone()
+    }
+
+    /// Stop checking and disconnect every device
+    pub async fn shutdown(mut self) {
+        let _ = self.stop.send(true);
+        for task in std::mem::take(&mut self.tasks) {
+            let _ = task.await;
+        }
+    }
+}
+
+impl fmt::Debug for MonitorHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MonitorHandle")
+            .field("devices", &self.tasks.len())
+            .finish()
+    }
+}
+
+impl Drop for MonitorHandle {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Check loop of one device
+struct Watcher {
+    name: String,
+    device: Device,
+    interval: Duration,
+    thresholds: HealthThresholds,
+    board: StatusBoard,
+}
+
+impl Watcher {
+    async fn run(mut self, mut stopped: watch::Receiver<bool>) {
+        let mut ticks = tokio::time::interval(self.interval);
+
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => self.check().await,
+                _ = stopped.changed() => break,
+            }
+        }
+
+        let _ = self.device.disconnect().await;
+        debug!("Stopped monitoring {}", self.name);
+    }
+
+    async fn check(&mut self) {
+        if !self.device.is_connected() {
+            if let Err(e) = self.device.connect().await {
+                let status = match e.retry_class() {
+                    RetryClass::Auth => DeviceStatus::AuthFailed,
+                    _ => DeviceStatus::Unreachable,
+                };
+                self.board.update(&self.name, status, None, Some(e.to_string()));
+                return;
+            }
+        }
+
+        let report = self.device.health_check_with(&self.thresholds).await;
+        let status = if !report.connected {
+            DeviceStatus::Unreachable
+        } else if report.is_healthy() {
+            DeviceStatus::Healthy
+        } else {
+            DeviceStatus::Degraded
+        };
+        self.board.update(&self.name, status, Some(report), None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zkrust_core::Command;
+    use zkrust_types::{time, DeviceCapacity};
+
+    use crate::testing::AckTransport;
+
+    fn healthy() -> Device {
+        let now = time::encode_time(&Local::now().naive_local()).unwrap();
+        let (transport, _) = AckTransport::new();
+        let transport = transport
+            .with_payload(Command::GetTime, now.to_le_bytes())
+            .with_payload(Command::GetFreeSizes, DeviceCapacity::default().encode());
+        Device::with_transport(transport)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_status_transitions() {
+        let (refusing, _) = AckTransport::new();
+        let monitor = FleetMonitor::new()
+            .with_device("gate", healthy())
+            .with_device("lobby", Device::with_transport(refusing.rejecting(Command::Connect)))
+            .with_interval(Duration::from_secs(10))
+            .spawn();
+        let board = monitor.board();
+        let mut changes = board.subscribe();
+
+        let mut seen = BTreeMap::new();
+        for _ in 0..2 {
+            let change = changes.recv().await.unwrap();
+            assert_eq!(change.previous, DeviceStatus::Unknown);
+            seen.insert(change.device, change.state.status);
+        }
+        assert_eq!(seen["gate"], DeviceStatus::Healthy);
+        assert_eq!(seen["lobby"], DeviceStatus::Unreachable);
+
+        let gate = board.get("gate").unwrap();
+        assert!(gate.report.unwrap().connected);
+        assert!(board.get("lobby").unwrap().error.is_some());
+        assert_eq!(board.counts()["healthy"], 1);
+
+        // Unchanged statuses are not broadcast again
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert!(changes.try_recv().is_err());
+
+        monitor.shutdown().await;
+    }
+
+    #[test]
+    fn test_board_update() {
+        let board = StatusBoard::new(["gate"]);
+        assert_eq!(board.get("gate").unwrap().status, DeviceStatus::Unknown);
+
+        board.update("gate", DeviceStatus::Unreachable, None, Some("refused".into()));
+        let state = board.get("gate").unwrap();
+        assert_eq!(state.status, DeviceStatus::Unreachable);
+        assert_eq!(state.error.as_deref(), Some("refused"));
+        assert_eq!(board.snapshot().len(), 1);
+    }
+}
@@ -0,0 +1,363 @@
+//! Pooled device connections
+//!
+//! Connecting and authenticating takes a few hundred milliseconds, too much
+//! to repeat for every request a gateway serves. [`DevicePool`] keeps one
+//! connected [`Device`] per name and leases it to one caller at a time; the
+//! connection goes back to the pool when the [`Lease`] is dropped.
+//! Connections left idle longer than the idle timeout are closed, either on
+//! the next lease or by [`DevicePool::evict_idle`] /
+//! [`DevicePool::spawn_reaper`].
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use zkrust::pool::DevicePool;
+//! use zkrust::Device;
+//!
+//! # async fn example() -> zkrust::Result<()> {
+//! let pool = DevicePool::new(|name: &str| match name {
+//!     "gate" => Ok(Device::new("192.168.1.201", 4370)),
+//!     _ => Err(zkrust::Error::Config(format!("Unknown device {}", name))),
+//! })
+//! .with_idle_timeout(Duration::from_secs(30));
+//! let _reaper = pool.spawn_reaper(Duration::from_secs(10));
+//!
+//! // In a request handler
+//! let mut device = pool.lease("gate").await?;
+//! let time = device.get_time().await?;
+//! # let _ = time;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::device::Device;
+use crate::error::Result;
+
+/// Default time a connection may sit unused before it is closed
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Factory = Arc<dyn Fn(&str) -> Result<Device> + Send + Sync>;
+type Slots = Mutex<HashMap<String, Arc<AsyncMutex<Slot>>>>;
+
+/// Pooled connection of one device
+struct Slot {
+    device: Option<Device>,
+    last_used: Instant,
+}
+
+impl Slot {
+    fn is_idle(&self, timeout: Duration) -> bool {
+        self.device.is_some() && self.last_used.elapsed() >= timeout
+    }
+}
+
+/// Warm connections to named devices, leased one caller at a time
+///
+/// Devices are created on first use by the factory passed to
+/// [`DevicePool::new`] and connected by the pool. Clones share the same
+/// connections.
+#[derive(Clone)]
+pub struct DevicePool {
+    factory: Factory,
+    idle_timeout: Duration,
+    slots: Arc<Slots>,
+}
+
+impl fmt::Debug for DevicePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DevicePool")
+            .field("idle_timeout", &self.idle_timeout)
+            .field("devices", &self.slots.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl DevicePool {
+    /// Create a pool building devices by name with `factory`
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: Fn(&str) -> Result<Device> + Send + Sync + 'static,
+    {
+        Self {
+            factory: Arc::new(factory),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            slots: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Create a pool of the devices in `registry`
+    #[cfg(feature = "registry")]
+    pub fn from_registry(registry: crate::registry::DeviceRegistry) -> Self {
+        Self::new(move |name: &str| registry.device(name))
+    }
+
+    /// Set how long a connection may sit unused (default: 60 seconds)
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Idle timeout in effect
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Lease the connection to `name`, connecting first if needed
+    ///
+    /// Waits while another caller holds the lease. Connections that are
+    /// closed or idle past the timeout are replaced.
+    pub async fn lease(&self, name: &str) -> Result<Lease> {
+        let slot = self.slot(name);
+        let mut slot = slot.lock_owned().await;
+
+        let usable = slot.device.as_ref().is_some_and(Device::is_connected);
+        if !usable || slot.is_idle(self.idle_timeout) {
+            if let Some(mut stale) = slot.device.take() {
+                debug!("Replacing pooled connection to {}", name);
+                let _ = stale.disconnect().await;
+            }
+
+            let mut device = (self.factory)(name)?;
+            device.connect().await?;
+            slot.device = Some(device);
+        }
+        slot.last_used = Instant::now();
+
+        Ok(Lease {
+            name: name.to_string(),
+            slot,
+        })
+    }
+
+    /// Close connections idle past the timeout, returning how many
+    ///
+    /// Leased connections are left alone.
+    pub async fn evict_idle(&self) -> usize {
+        evict_idle(&self.slots, self.idle_timeout).await
+    }
+
+    /// Run [`DevicePool::evict_idle`] every `interval` in a background task
+    ///
+    /// The task stops once every clone of the pool has been dropped.
+    pub fn spawn_reaper(&self, interval: Duration) -> JoinHandle<()> {
+        let slots = Arc::downgrade(&self.slots);
+        let timeout = self.idle_timeout;
+
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                let Some(slots) = Weak::upgrade(&slots) else {
+                    break;
+                };
+                evict_idle(&slots, timeout).await;
+            }
+        })
+    }
+
+    /// Number of open connections, leased or not
+    pub fn connected(&self) -> usize {
+        self.snapshot()
+            .iter()
+            .filter(|slot| match slot.try_lock() {
+                Ok(slot) => slot.device.is_some(),
+                // Leased
+                Err(_) => true,
+            })
+            .count()
+    }
+
+    /// Disconnect every device, waiting for outstanding leases
+    pub async fn close(&self) {
+        for slot in self.snapshot() {
+            if let Some(mut device) = slot.lock().await.device.take() {
+                let _ = device.disconnect().await;
+            }
+        }
+    }
+
+    fn slot(&self, name: &str) -> Arc<AsyncMutex<Slot>> {
+        let mut slots = self.slots.lock().unwrap();
+        slots
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                Arc::new(AsyncMutex::new(Slot {
+                    device: None,
+                    last_used: Instant::now(),
+                }))
+            })
+            .clone()
+    }
+
+    fn snapshot(&self) -> Vec<Arc<AsyncMutex<Slot>>> {
+        self.slots.lock().unwrap().values().cloned().collect()
+    }
+}
+
+async fn evict_idle(slots: &Slots, timeout: Duration) -> usize {
+    let named: Vec<_> = slots
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, slot)| (name.clone(), slot.clone()))
+        .collect();
+
+    let mut evicted = 0;
+    for (name, slot) in named {
+        let Ok(mut slot) = slot.try_lock_owned() else {
+            continue;
+        };
+        if slot.is_idle(timeout) {
+            if let Some(mut device) = slot.device.take() {
+                debug!("Closing idle connection to {}", name);
+                let _ = device.disconnect().await;
+                evicted += 1;
+            }
+        }
+    }
+    evicted
+}
+
+/// Exclusive use of a pooled connection
+///
+/// Dereferences to the [`Device`]. Dropping the lease returns the
+/// connection to the pool; call [`Lease::discard`] instead after an error
+/// that leaves the connection unusable (see
+/// [`RetryClass::needs_reconnect`](zkrust_core::RetryClass::needs_reconnect)).
+pub struct Lease {
+    name: String,
+    slot: OwnedMutexGuard<Slot>,
+}
+
+impl Lease {
+    /// Name the device was leased under
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Disconnect instead of returning the connection to the pool
+    pub async fn discard(mut self) {
+        if let Some(mut device) = self.slot.device.take() {
+            let _ = device.disconnect().await;
+        }
+    }
+}
+
+impl fmt::Debug for Lease {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lease").field("name", &self.name).finish()
+    }
+}
+
+impl Deref for Lease {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        self.slot.device.as_ref().expect("leased slot holds a device")
+    }
+}
+
+impl DerefMut for Lease {
+    fn deref_mut(&mut self) -> &mut Device {
+        self.slot.device.as_mut().expect("leased slot holds a device")
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.slot.last_used = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::testing::AckTransport;
+
+    fn counting_pool() -> (DevicePool, Arc<AtomicUsize>) {
+        let built = Arc::new(AtomicUsize::new(0));
+        let pool = DevicePool::new({
+            let built = built.clone();
+            move |_: &str| {
+                built.fetch_add(1, Ordering::SeqCst);
+                Ok(Device::with_transport(AckTransport::new().0))
+            }
+        })
+        .with_idle_timeout(Duration::from_secs(30));
+        (pool, built)
+    }
+
+    #[tokio::test]
+    async fn test_lease_reuses_connection() {
+        let (pool, built) = counting_pool();
+
+        let lease = pool.lease("gate").await.unwrap();
+        assert_eq!(lease.name(), "gate");
+        assert!(lease.is_connected());
+        drop(lease);
+
+        pool.lease("gate").await.unwrap().enable_device().await.unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+
+        pool.lease("canteen").await.unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.connected(), 2);
+
+        pool.lease("gate").await.unwrap().discard().await;
+        assert_eq!(pool.connected(), 1);
+        pool.lease("gate").await.unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 3);
+
+        pool.close().await;
+        assert_eq!(pool.connected(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_eviction() {
+        let (pool, built) = counting_pool();
+
+        drop(pool.lease("gate").await.unwrap());
+        let held = pool.lease("canteen").await.unwrap();
+
+        tokio::time::advance(Duration::from_secs(29)).await;
+        assert_eq!(pool.evict_idle().await, 0);
+
+        // The held lease is skipped even though it was taken 31s ago
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert_eq!(pool.evict_idle().await, 1);
+        assert_eq!(pool.connected(), 1);
+        drop(held);
+
+        pool.lease("gate").await.unwrap();
+        assert_eq!(built.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reaper() {
+        let (pool, _) = counting_pool();
+        let reaper = pool.spawn_reaper(Duration::from_secs(10));
+
+        drop(pool.lease("gate").await.unwrap());
+        tokio::time::sleep(Duration::from_secs(45)).await;
+        assert_eq!(pool.connected(), 0);
+
+        drop(pool);
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert!(reaper.is_finished());
+    }
+}
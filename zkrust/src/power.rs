@@ -0,0 +1,225 @@
+//! Automatic power schedule
+//!
+//! Terminals can switch themselves off, on or into sleep at fixed times of
+//! day, and sleep or power off after a stretch of inactivity. The settings
+//! are device options holding times as `hour * 256 + minute`, with 65535
+//! meaning unset.
+//!
+//! ```no_run
+//! use chrono::NaiveTime;
+//! use zkrust::power::{IdleAction, PowerSchedule};
+//!
+//! # async fn example(device: &mut zkrust::Device) -> zkrust::Result<()> {
+//! // Sleep overnight, wake up for the morning shift
+//! let schedule = PowerSchedule::new()
+//!     .with_suspend(NaiveTime::from_hms_opt(22, 0, 0).unwrap())
+//!     .with_power_on(NaiveTime::from_hms_opt(6, 0, 0).unwrap())
+//!     .with_idle(IdleAction::Sleep, 30);
+//! device.set_power_schedule(&schedule).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{NaiveTime, Timelike};
+use tracing::debug;
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+
+/// Time of day the device powers off
+const POWER_OFF: &str = "AutoPowerOff";
+
+/// Time of day the device powers on
+const POWER_ON: &str = "AutoPowerOn";
+
+/// Time of day the device goes to sleep
+const SUSPEND: &str = "AutoPowerSuspend";
+
+/// What the device does once idle ([`IdleAction`] code)
+const IDLE_POWER: &str = "IdlePower";
+
+/// Idle minutes before [`IDLE_POWER`] applies, 0 = never
+const IDLE_MINUTES: &str = "IdleMinute";
+
+/// Option value of an unset time
+const UNSET: u16 = 0xFFFF;
+
+/// What an idle device does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum IdleAction {
+    /// Turn the screen off; a touch wakes it up
+    #[default]
+    Sleep,
+
+    /// Shut down
+    PowerOff,
+}
+
+impl IdleAction {
+    /// Option code
+    const fn code(self) -> u16 {
+        match self {
+            Self::PowerOff => 87,
+            Self::Sleep => 88,
+        }
+    }
+}
+
+/// Scheduled power-off, power-on and sleep, plus idle behaviour
+///
+/// `None` times are unset on the device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PowerSchedule {
+    /// Daily power-off time
+    pub power_off: Option<NaiveTime>,
+
+    /// Daily power-on time
+    pub power_on: Option<NaiveTime>,
+
+    /// Daily sleep time
+    pub suspend: Option<NaiveTime>,
+
+    /// What the device does after `idle_minutes` without use
+    pub idle_action: IdleAction,
+
+    /// Minutes without use before `idle_action`, 0 = never
+    pub idle_minutes: u16,
+}
+
+impl PowerSchedule {
+    /// Schedule with nothing set: the device stays on
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Power off daily at `time`
+    pub fn with_power_off(mut self, time: NaiveTime) -> Self {
+        self.power_off = Some(time);
+        self
+    }
+
+    /// Power on daily at `time`
+    pub fn with_power_on(mut self, time: NaiveTime) -> Self {
+        self.power_on = Some(time);
+        self
+    }
+
+    /// Go to sleep daily at `time`
+    pub fn with_suspend(mut self, time: NaiveTime) -> Self {
+        self.suspend = Some(time);
+        self
+    }
+
+    /// Sleep or power off after `minutes` without use
+    pub fn with_idle(mut self, action: IdleAction, minutes: u16) -> Self {
+        self.idle_action = action;
+        self.idle_minutes = minutes;
+        self
+    }
+}
+
+/// Encode a time of day as the option value, minutes precision
+fn encode_time(time: Option<NaiveTime>) -> u16 {
+    time.map_or(UNSET, |time| (time.hour() as u16) << 8 | time.minute() as u16)
+}
+
+/// Decode an option time value
+fn decode_time(name: &str, value: u16) -> Result<Option<NaiveTime>> {
+    if value == UNSET {
+        return Ok(None);
+    }
+
+    NaiveTime::from_hms_opt(u32::from(value >> 8), u32::from(value & 0xFF), 0)
+        .map(Some)
+        .ok_or_else(|| Error::InvalidResponse(format!("Invalid {} time 0x{:04X}", name, value)))
+}
+
+impl Device {
+    /// Read the automatic power schedule
+    pub async fn get_power_schedule(&mut self) -> Result<PowerSchedule> {
+        debug!("Reading power schedule...");
+
+        let idle_action = match self.get_parsed_option::<u16>(IDLE_POWER).await? {
+            Some(code) if code == IdleAction::PowerOff.code() => IdleAction::PowerOff,
+            _ => IdleAction::Sleep,
+        };
+
+        Ok(PowerSchedule {
+            power_off: self.get_time_option(POWER_OFF).await?,
+            power_on: self.get_time_option(POWER_ON).await?,
+            suspend: self.get_time_option(SUSPEND).await?,
+            idle_action,
+            idle_minutes: self.get_parsed_option(IDLE_MINUTES).await?.unwrap_or(0),
+        })
+    }
+
+    /// Write the automatic power schedule and reload the device options
+    pub async fn set_power_schedule(&mut self, schedule: &PowerSchedule) -> Result<()> {
+        debug!("Writing power schedule: {:?}", schedule);
+
+        let values = [
+            (POWER_OFF, encode_time(schedule.power_off)),
+            (POWER_ON, encode_time(schedule.power_on)),
+            (SUSPEND, encode_time(schedule.suspend)),
+            (IDLE_POWER, schedule.idle_action.code()),
+            (IDLE_MINUTES, schedule.idle_minutes),
+        ];
+        for (name, value) in values {
+            self.set_option(name, &value.to_string()).await?;
+        }
+
+        self.refresh_options().await
+    }
+
+    async fn get_time_option(&mut self, name: &str) -> Result<Option<NaiveTime>> {
+        match self.get_parsed_option::<u16>(name).await? {
+            Some(value) => decode_time(name, value),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zkrust_core::Command;
+
+    use crate::testing::AckTransport;
+
+    #[test]
+    fn test_time_encoding() {
+        let time = NaiveTime::from_hms_opt(22, 30, 0).unwrap();
+        assert_eq!(encode_time(Some(time)), 22 * 256 + 30);
+        assert_eq!(decode_time("t", 22 * 256 + 30).unwrap(), Some(time));
+        assert_eq!(encode_time(None), UNSET);
+        assert_eq!(decode_time("t", UNSET).unwrap(), None);
+        assert!(decode_time("t", 25 * 256).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_power_schedule() {
+        let (transport, sent) = AckTransport::new();
+        let transport = transport.with_payload(
+            Command::OptionsRrq,
+            &b"AutoPowerOff=65535\0AutoPowerSuspend=5632\0IdlePower=87\0IdleMinute=15\0"[..],
+        );
+
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        let schedule = device.get_power_schedule().await.unwrap();
+        assert_eq!(
+            schedule,
+            PowerSchedule::new()
+                .with_suspend(NaiveTime::from_hms_opt(22, 0, 0).unwrap())
+                .with_idle(IdleAction::PowerOff, 15)
+        );
+
+        sent.lock().unwrap().clear();
+        device.set_power_schedule(&PowerSchedule::new()).await.unwrap();
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.iter().filter(|&&command| command == Command::OptionsWrq).count(), 5);
+        assert_eq!(sent.last(), Some(&Command::RefreshOption));
+    }
+}
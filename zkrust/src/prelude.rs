@@ -0,0 +1,33 @@
+//! Common imports
+//!
+//! Brings the device, the record types, the protocol enums and the
+//! `Result` alias into scope from one place:
+//!
+//! ```no_run
+//! use zkrust::prelude::*;
+//!
+//! # async fn example() -> Result<()> {
+//! let mut device = Device::new("192.168.1.201", 4370);
+//! device.connect().await?;
+//!
+//! for record in device.get_attendance().await? {
+//!     if record.punch == PunchType::CheckIn {
+//!         println!("{} checked in at {}", record.user_id, record.timestamp);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `Result` here is [`zkrust::Result`](crate::Result); import the prelude
+//! with care in modules that use `std::result::Result` with two parameters.
+
+pub use crate::device::Device;
+pub use crate::error::{Error, ErrorContext, Result};
+
+pub use zkrust_core::constants::{Privilege, PunchType, Relay, VerifyMode};
+pub use zkrust_core::{Command, RetryClass};
+pub use zkrust_types::{
+    AttendanceRecord, DeviceCapacity, DeviceInfo, FaceTemplate, FingerprintTemplate, LiveEvent, LiveEventKind, User,
+    UserBuilder,
+};
@@ -0,0 +1,280 @@
+//! Per-model device quirks
+//!
+//! ZKTeco models differ in whether they expect the TCP wrapper, how user
+//! records are laid out and which commands they implement. A
+//! [`DeviceProfile`] captures those differences; [`ProfileRegistry`] maps the
+//! model/platform strings reported by the device to a profile.
+
+use tracing::debug;
+
+use zkrust_core::Command;
+use zkrust_types::UserRecordLayout;
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+
+/// Quirks of one device family
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceProfile {
+    /// Profile name
+    pub name: String,
+
+    /// Case-insensitive prefixes matched against the model and platform
+    pub patterns: Vec<String>,
+
+    /// Whether TCP connections use the 0x5050/0x8272 wrapper
+    pub tcp_wrapper: bool,
+
+    /// User record layout
+    pub user_layout: UserRecordLayout,
+
+    /// Fingerprint support
+    pub fingerprint: bool,
+
+    /// Face recognition support
+    pub face: bool,
+
+    /// Commands the firmware does not implement
+    pub unsupported: Vec<Command>,
+}
+
+impl DeviceProfile {
+    /// Create a profile with generic defaults (TCP wrapper, 72-byte users,
+    /// fingerprint only, all commands supported)
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            patterns: Vec::new(),
+            tcp_wrapper: true,
+            user_layout: UserRecordLayout::Extended,
+            fingerprint: true,
+            face: false,
+            unsupported: Vec::new(),
+        }
+    }
+
+    /// Profile used when nothing in the registry matches
+    pub fn generic() -> Self {
+        Self::new("Generic")
+    }
+
+    /// Add a model/platform prefix this profile applies to
+    pub fn with_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    /// Set TCP wrapper use
+    pub fn with_tcp_wrapper(mut self, enabled: bool) -> Self {
+        self.tcp_wrapper = enabled;
+        self
+    }
+
+    /// Set the user record layout
+    pub fn with_user_layout(mut self, layout: UserRecordLayout) -> Self {
+        self.user_layout = layout;
+        self
+    }
+
+    /// Set fingerprint and face support
+    pub fn with_biometrics(mut self, fingerprint: bool, face: bool) -> Self {
+        self.fingerprint = fingerprint;
+        self.face = face;
+        self
+    }
+
+    /// Mark a command as not implemented by this family
+    pub fn without_command(mut self, command: Command) -> Self {
+        self.unsupported.push(command);
+        self
+    }
+
+    /// Check if `command` is implemented
+    pub fn supports(&self, command: Command) -> bool {
+        !self.unsupported.contains(&command)
+    }
+
+    /// Check if any pattern is a prefix of `value` (case-insensitive)
+    pub fn matches(&self, value: &str) -> bool {
+        let value = value.trim().to_ascii_uppercase();
+        self.patterns
+            .iter()
+            .any(|p| !p.is_empty() && value.starts_with(&p.to_ascii_uppercase()))
+    }
+}
+
+/// Lookup table from model/platform strings to [`DeviceProfile`]s
+#[derive(Debug, Clone)]
+pub struct ProfileRegistry {
+    profiles: Vec<DeviceProfile>,
+}
+
+impl ProfileRegistry {
+    /// Create an empty registry
+    pub fn empty() -> Self {
+        Self {
+            profiles: Vec::new(),
+        }
+    }
+
+    /// Create a registry with the built-in profiles
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register(
+            DeviceProfile::new("K40")
+                .with_pattern("K40")
+                .with_tcp_wrapper(false)
+                .with_user_layout(UserRecordLayout::Compact),
+        );
+        registry.register(
+            DeviceProfile::new("F18")
+                .with_pattern("F18")
+                .with_pattern("ZMM100"),
+        );
+        registry.register(
+            DeviceProfile::new("MB360")
+                .with_pattern("MB360")
+                .with_pattern("ZLM60")
+                .with_biometrics(true, true),
+        );
+        registry.register(
+            DeviceProfile::new("SpeedFace")
+                .with_pattern("SpeedFace")
+                .with_pattern("ZAM180")
+                .with_biometrics(true, true),
+        );
+
+        registry
+    }
+
+    /// Add a profile
+    ///
+    /// Later registrations take precedence, so custom profiles can override
+    /// the built-in ones.
+    pub fn register(&mut self, profile: DeviceProfile) {
+        self.profiles.insert(0, profile);
+    }
+
+    /// Find a profile by name (case-insensitive)
+    pub fn get(&self, name: &str) -> Option<&DeviceProfile> {
+        self.profiles.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Find the profile for a model and/or platform string
+    ///
+    /// The model is tried before the platform.
+    pub fn lookup(&self, model: Option<&str>, platform: Option<&str>) -> Option<&DeviceProfile> {
+        [model, platform]
+            .into_iter()
+            .flatten()
+            .find_map(|value| self.profiles.iter().find(|p| p.matches(value)))
+    }
+
+    /// Like [`ProfileRegistry::lookup`], falling back to [`DeviceProfile::generic`]
+    pub fn resolve(&self, model: Option<&str>, platform: Option<&str>) -> DeviceProfile {
+        self.lookup(model, platform)
+            .cloned()
+            .unwrap_or_else(DeviceProfile::generic)
+    }
+}
+
+impl Default for ProfileRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl Device {
+    /// Detect the device profile from the `~DeviceName` and `~Platform`
+    /// options using the built-in registry
+    ///
+    /// The profile is stored on the device and used for later commands.
+    /// The TCP wrapper setting can only take effect on the next connection;
+    /// use [`Device::for_profile`] when the model is known up front.
+    pub async fn detect_profile(&mut self) -> Result<&DeviceProfile> {
+        self.detect_profile_with(&ProfileRegistry::builtin()).await
+    }
+
+    /// Detect the device profile using a custom registry
+    pub async fn detect_profile_with(&mut self, registry: &ProfileRegistry) -> Result<&DeviceProfile> {
+        let model = self.get_option("~DeviceName").await?;
+        let platform = self.get_option("~Platform").await?;
+
+        let profile = registry.resolve(model.as_deref(), platform.as_deref());
+        debug!(
+            "Detected profile {} (model={:?}, platform={:?})",
+            profile.name, model, platform
+        );
+
+        Ok(self.set_profile(profile))
+    }
+
+    /// Reject `command` if the active profile doesn't implement it
+    pub(crate) fn check_supported(&self, command: Command) -> Result<()> {
+        match self.profile() {
+            Some(profile) if !profile.supports(command) => Err(Error::NotSupported(format!(
+                "{} is not implemented by {} devices",
+                command, profile.name
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::AckTransport;
+
+    #[test]
+    fn test_lookup_builtin() {
+        let registry = ProfileRegistry::builtin();
+
+        let k40 = registry.lookup(Some("k40/ID"), None).unwrap();
+        assert_eq!(k40.name, "K40");
+        assert_eq!(k40.user_layout, UserRecordLayout::Compact);
+        assert!(!k40.tcp_wrapper);
+
+        let face = registry.lookup(None, Some("ZAM180_TFT")).unwrap();
+        assert_eq!(face.name, "SpeedFace");
+        assert!(face.face);
+
+        assert!(registry.lookup(Some("X999"), Some("UNKNOWN")).is_none());
+        assert_eq!(registry.resolve(None, None), DeviceProfile::generic());
+    }
+
+    #[test]
+    fn test_custom_profile_overrides_builtin() {
+        let mut registry = ProfileRegistry::builtin();
+        registry.register(DeviceProfile::new("Custom K40").with_pattern("K40"));
+
+        assert_eq!(registry.lookup(Some("K40"), None).unwrap().name, "Custom K40");
+    }
+
+    #[tokio::test]
+    async fn test_detect_profile_gates_commands() {
+        let (transport, _) = AckTransport::new();
+        let transport = transport.with_payload(Command::OptionsRrq, &b"~DeviceName=F18\0"[..]);
+
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        assert_eq!(device.detect_profile().await.unwrap().name, "F18");
+        assert!(device.check_supported(Command::EnableClock).is_ok());
+
+        let mut registry = ProfileRegistry::empty();
+        registry.register(
+            DeviceProfile::new("No clock")
+                .with_pattern("F18")
+                .without_command(Command::EnableClock),
+        );
+        device.detect_profile_with(&registry).await.unwrap();
+
+        assert!(matches!(
+            device.check_supported(Command::EnableClock),
+            Err(Error::NotSupported(_))
+        ));
+        assert!(device.check_supported(Command::GetTime).is_ok());
+    }
+}
@@ -0,0 +1,597 @@
+//! Named device configurations
+//!
+//! A [`DeviceRegistry`] maps names to connection settings (address,
+//! transport, CommKey source, model profile, text encoding) and builds
+//! configured [`Device`]s from them. It loads from TOML or JSON, so the same
+//! file can drive the CLI, a gateway and fleet jobs.
+//!
+//! ```toml
+//! default = "front-door"
+//!
+//! [devices.front-door]
+//! label = "Front door"
+//! host = "192.168.1.201"
+//! password = 1234
+//! profile = "K40"
+//!
+//! [devices.warehouse]
+//! host = "10.0.0.50"
+//! transport = "udp"
+//! password_env = "WAREHOUSE_COMMKEY"
+//! encoding = "gbk"
+//! timeout_secs = 10
+//! timezone = "Asia/Shanghai"
+//! ```
+//!
+//! ```no_run
+//! use zkrust::registry::DeviceRegistry;
+//!
+//! # async fn example() -> zkrust::Result<()> {
+//! let registry = DeviceRegistry::load("devices.toml")?;
+//!
+//! let mut device = registry.device("front-door")?;
+//! device.connect().await?;
+//!
+//! let mut fleet = registry.fleet()?;
+//! let report = fleet.get_attendance().await;
+//! # let _ = report;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use zkrust_types::text;
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::fleet::Fleet;
+use crate::profile::ProfileRegistry;
+use crate::secret::{EnvSecret, FileSecret};
+
+/// Default ZKTeco protocol port
+pub const DEFAULT_PORT: u16 = 4370;
+
+/// Transport used to reach a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for TransportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "tcp"),
+            Self::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+impl FromStr for TransportKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "tcp" => Ok(Self::Tcp),
+            "udp" => Ok(Self::Udp),
+            _ => Err(Error::Config(format!("Unknown transport {:?} (expected tcp or udp)", s))),
+        }
+    }
+}
+
+/// Connection settings of one device
+///
+/// At most one CommKey source may be set: `password` inline, or
+/// `password_env` / `password_file` resolved at connect time (see
+/// [`crate::secret`]). The inline key is redacted in `Debug` output.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceEntry {
+    /// Human-readable name, e.g. for dashboards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// Hostname or IP address
+    pub host: String,
+
+    #[serde(default = "default_port")]
+    pub port: u16,
+
+    #[serde(default)]
+    pub transport: TransportKind,
+
+    /// CommKey
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<u32>,
+
+    /// Environment variable holding the CommKey
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_env: Option<String>,
+
+    /// File holding the CommKey
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_file: Option<PathBuf>,
+
+    /// Model profile name, e.g. `"K40"` (see [`crate::profile`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    /// Text encoding, e.g. `"gbk"` (see [`zkrust_types::text::from_name`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+
+    /// Per-command timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+
+    /// IANA timezone of the device clock, e.g. `"Europe/Berlin"` (needs the
+    /// `tz` feature, see [`crate::timezone`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+impl fmt::Debug for DeviceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceEntry")
+            .field("label", &self.label)
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("transport", &self.transport)
+            .field("password", &self.password.map(|_| "***"))
+            .field("password_env", &self.password_env)
+            .field("password_file", &self.password_file)
+            .field("profile", &self.profile)
+            .field("encoding", &self.encoding)
+            .field("timeout_secs", &self.timeout_secs)
+            .field("timezone", &self.timezone)
+            .finish()
+    }
+}
+
+impl DeviceEntry {
+    /// Create TCP settings for `host` on the default port
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            label: None,
+            host: host.into(),
+            port: DEFAULT_PORT,
+            transport: TransportKind::Tcp,
+            password: None,
+            password_env: None,
+            password_file: None,
+            profile: None,
+            encoding: None,
+            timeout_secs: None,
+            timezone: None,
+        }
+    }
+
+    /// Set the human-readable name
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Set the port
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Set the transport
+    pub fn with_transport(mut self, transport: TransportKind) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Use a fixed CommKey, replacing any other CommKey source
+    pub fn with_password(mut self, password: u32) -> Self {
+        self.password = Some(password);
+        self.password_env = None;
+        self.password_file = None;
+        self
+    }
+
+    /// Set the model profile name
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Set the text encoding name
+    pub fn with_encoding(mut self, encoding: impl Into<String>) -> Self {
+        self.encoding = Some(encoding.into());
+        self
+    }
+
+    /// Set the per-command timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_secs = Some(timeout.as_secs());
+        self
+    }
+
+    /// Set the IANA timezone name of the device clock
+    pub fn with_timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = Some(timezone.into());
+        self
+    }
+
+    /// `host:port`
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// Check the settings against the built-in profiles
+    pub fn validate(&self) -> Result<()> {
+        self.validate_with(&ProfileRegistry::builtin())
+    }
+
+    /// Check the settings against `profiles`
+    pub fn validate_with(&self, profiles: &ProfileRegistry) -> Result<()> {
+        let sources = [
+            self.password.is_some(),
+            self.password_env.is_some(),
+            self.password_file.is_some(),
+        ];
+        if sources.into_iter().filter(|&set| set).count() > 1 {
+            return Err(Error::Config(format!(
+                "{}: set only one of password, password_env and password_file",
+                self.address()
+            )));
+        }
+        if let Some(name) = &self.profile {
+            if profiles.get(name).is_none() {
+                return Err(Error::Config(format!("{}: unknown profile {:?}", self.address(), name)));
+            }
+        }
+        if let Some(name) = &self.encoding {
+            if text::from_name(name).is_none() {
+                return Err(Error::Config(format!("{}: unknown encoding {:?}", self.address(), name)));
+            }
+        }
+        if let Some(name) = &self.timezone {
+            self.parse_timezone(name)?;
+        }
+        Ok(())
+    }
+
+    /// Create the device described by these settings, using the built-in
+    /// profiles
+    pub fn build(&self) -> Result<Device> {
+        self.build_with(&ProfileRegistry::builtin())
+    }
+
+    /// Create the device described by these settings, looking the profile
+    /// up in `profiles`
+    pub fn build_with(&self, profiles: &ProfileRegistry) -> Result<Device> {
+        self.validate_with(profiles)?;
+
+        let profile = self.profile.as_deref().and_then(|name| profiles.get(name)).cloned();
+        let mut device = match (self.transport, profile) {
+            (TransportKind::Tcp, Some(profile)) => Device::for_profile(profile, self.host.clone(), self.port),
+            (TransportKind::Tcp, None) => Device::new(self.host.clone(), self.port),
+            (TransportKind::Udp, profile) => {
+                let device = Device::new_udp(self.host.clone(), self.port);
+                match profile {
+                    Some(profile) => device.with_profile(profile),
+                    None => device,
+                }
+            }
+        };
+
+        if let Some(password) = self.password {
+            device = device.with_password(password);
+        }
+        if let Some(var) = &self.password_env {
+            device = device.with_secret_provider(EnvSecret::new(var.clone()));
+        }
+        if let Some(path) = &self.password_file {
+            device = device.with_secret_provider(FileSecret::new(path.clone()));
+        }
+        if let Some(codec) = self.encoding.as_deref().and_then(text::from_name) {
+            device.set_string_codec(codec);
+        }
+        if let Some(secs) = self.timeout_secs {
+            device = device.with_timeout(Duration::from_secs(secs));
+        }
+        #[cfg(feature = "tz")]
+        if let Some(name) = &self.timezone {
+            device = device.with_timezone(self.parse_timezone(name)?);
+        }
+
+        Ok(device)
+    }
+
+    #[cfg(feature = "tz")]
+    fn parse_timezone(&self, name: &str) -> Result<zkrust_types::time::Tz> {
+        zkrust_types::time::parse_timezone(name)
+            .map_err(|_| Error::Config(format!("{}: unknown timezone {:?}", self.address(), name)))
+    }
+
+    #[cfg(not(feature = "tz"))]
+    fn parse_timezone(&self, _name: &str) -> Result<()> {
+        Err(Error::Config(format!("{}: timezone needs the tz feature", self.address())))
+    }
+}
+
+/// Named device settings, loadable from TOML or JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceRegistry {
+    /// Device used when no name is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<String>,
+
+    #[serde(default)]
+    devices: BTreeMap<String, DeviceEntry>,
+
+    #[serde(skip)]
+    profiles: ProfileRegistry,
+}
+
+impl DeviceRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse TOML
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let registry: Self = toml::from_str(contents).map_err(|e| Error::Config(e.to_string()))?;
+        registry.validate()?;
+        Ok(registry)
+    }
+
+    /// Parse JSON
+    pub fn from_json(contents: &str) -> Result<Self> {
+        let registry: Self = serde_json::from_str(contents).map_err(|e| Error::Config(e.to_string()))?;
+        registry.validate()?;
+        Ok(registry)
+    }
+
+    /// Load a file, parsed as JSON if its extension is `.json` and as TOML
+    /// otherwise
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("Cannot read {}: {}", path.display(), e)))?;
+
+        let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let registry = if is_json {
+            Self::from_json(&contents)
+        } else {
+            Self::from_toml(&contents)
+        };
+        registry.map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Serialize to TOML
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    /// Serialize to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    /// Resolve `profile` names against a custom registry (default: built-in)
+    ///
+    /// Entries are re-checked against the new profiles when building.
+    pub fn with_profiles(mut self, profiles: ProfileRegistry) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    /// Add a device under `name`
+    pub fn with_device(mut self, name: impl Into<String>, entry: DeviceEntry) -> Self {
+        self.insert(name, entry);
+        self
+    }
+
+    /// Set the device used when no name is given
+    pub fn with_default(mut self, name: impl Into<String>) -> Self {
+        self.default = Some(name.into());
+        self
+    }
+
+    /// Add or replace a device, returning the previous settings
+    pub fn insert(&mut self, name: impl Into<String>, entry: DeviceEntry) -> Option<DeviceEntry> {
+        self.devices.insert(name.into(), entry)
+    }
+
+    /// Remove a device
+    pub fn remove(&mut self, name: &str) -> Option<DeviceEntry> {
+        self.devices.remove(name)
+    }
+
+    /// Settings of a device
+    pub fn get(&self, name: &str) -> Option<&DeviceEntry> {
+        self.devices.get(name)
+    }
+
+    /// Name of the default device
+    pub fn default_name(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    /// Number of devices
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Whether the registry has no devices
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Device names, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.devices.keys().map(String::as_str)
+    }
+
+    /// Names and settings, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &DeviceEntry)> {
+        self.devices.iter().map(|(name, entry)| (name.as_str(), entry))
+    }
+
+    /// Look up a device by name, or the default device when `name` is None
+    pub fn resolve(&self, name: Option<&str>) -> Result<(&str, &DeviceEntry)> {
+        let name = name
+            .or(self.default.as_deref())
+            .ok_or_else(|| Error::Config("No device name given and no default device set".into()))?;
+
+        self.devices
+            .get_key_value(name)
+            .map(|(name, entry)| (name.as_str(), entry))
+            .ok_or_else(|| Error::Config(format!("Device {:?} not found in registry", name)))
+    }
+
+    /// Create the device registered under `name`
+    pub fn device(&self, name: &str) -> Result<Device> {
+        let (_, entry) = self.resolve(Some(name))?;
+        entry.build_with(&self.profiles)
+    }
+
+    /// Create the default device
+    pub fn default_device(&self) -> Result<Device> {
+        let (_, entry) = self.resolve(None)?;
+        entry.build_with(&self.profiles)
+    }
+
+    /// Create a [`Fleet`] of every registered device, keyed by name
+    pub fn fleet(&self) -> Result<Fleet> {
+        let mut fleet = Fleet::new();
+        for (name, entry) in &self.devices {
+            fleet.push(name.clone(), entry.build_with(&self.profiles)?);
+        }
+        Ok(fleet)
+    }
+
+    /// Check every entry and the default name
+    fn validate(&self) -> Result<()> {
+        if let Some(name) = &self.default {
+            if !self.devices.contains_key(name) {
+                return Err(Error::Config(format!("Default device {:?} not found in registry", name)));
+            }
+        }
+        for (name, entry) in &self.devices {
+            entry
+                .validate_with(&self.profiles)
+                .map_err(|e| Error::Config(format!("device {:?}: {}", name, e)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+        default = "door"
+
+        [devices.door]
+        label = "Front door"
+        host = "192.168.1.201"
+        password = 1234
+        profile = "k40"
+
+        [devices.gate]
+        host = "10.0.0.50"
+        port = 5005
+        transport = "udp"
+        password_env = "GATE_COMMKEY"
+        encoding = "latin1"
+        timeout_secs = 10
+    "#;
+
+    #[test]
+    fn test_from_toml() {
+        let registry = DeviceRegistry::from_toml(TOML).unwrap();
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.names().collect::<Vec<_>>(), ["door", "gate"]);
+
+        let (name, door) = registry.resolve(None).unwrap();
+        assert_eq!(name, "door");
+        assert_eq!(door.label.as_deref(), Some("Front door"));
+        assert_eq!(door.port, DEFAULT_PORT);
+        assert_eq!(door.transport, TransportKind::Tcp);
+        assert!(!format!("{:?}", door).contains("1234"));
+
+        let gate = registry.get("gate").unwrap();
+        assert_eq!(gate.address(), "10.0.0.50:5005");
+        assert_eq!(gate.transport, TransportKind::Udp);
+
+        let device = registry.device("door").unwrap();
+        assert_eq!(device.profile().unwrap().name, "K40");
+        let device = registry.device("gate").unwrap();
+        assert_eq!(device.string_codec().name(), "Latin-1");
+
+        assert!(registry.device("lobby").is_err());
+        assert_eq!(registry.fleet().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let registry = DeviceRegistry::from_toml(TOML).unwrap();
+
+        let json = DeviceRegistry::from_json(&registry.to_json().unwrap()).unwrap();
+        assert_eq!(json.get("gate"), registry.get("gate"));
+        assert_eq!(json.default_name(), Some("door"));
+
+        let toml = DeviceRegistry::from_toml(&registry.to_toml().unwrap()).unwrap();
+        assert_eq!(toml.get("door"), registry.get("door"));
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        // Typo in a key
+        assert!(DeviceRegistry::from_toml("[devices.door]\nhost = \"a\"\npasword = 1\n").is_err());
+        // Two CommKey sources
+        let both = "[devices.door]\nhost = \"a\"\npassword = 1\npassword_env = \"K\"\n";
+        assert!(DeviceRegistry::from_toml(both).is_err());
+        assert!(DeviceRegistry::from_toml("[devices.door]\nhost = \"a\"\nprofile = \"nope\"\n").is_err());
+        assert!(DeviceRegistry::from_toml("[devices.door]\nhost = \"a\"\nencoding = \"ebcdic\"\n").is_err());
+        assert!(DeviceRegistry::from_toml("[devices.door]\nhost = \"a\"\ntimezone = \"Mars/Olympus\"\n").is_err());
+        assert!(DeviceRegistry::from_toml("default = \"gate\"\n[devices.door]\nhost = \"a\"\n").is_err());
+        assert!(DeviceRegistry::new().default_device().is_err());
+
+        assert_eq!("UDP".parse::<TransportKind>().unwrap(), TransportKind::Udp);
+        assert!("serial".parse::<TransportKind>().is_err());
+    }
+
+    #[test]
+    fn test_builder() {
+        let entry = DeviceEntry::new("10.0.0.9")
+            .with_port(4371)
+            .with_timeout(Duration::from_secs(3))
+            .with_password(7);
+        let registry = DeviceRegistry::new().with_device("lab", entry).with_default("lab");
+
+        assert_eq!(registry.resolve(None).unwrap().1.address(), "10.0.0.9:4371");
+        assert!(registry.default_device().is_ok());
+    }
+
+    #[cfg(feature = "tz")]
+    #[test]
+    fn test_timezone() {
+        let toml = "[devices.door]\nhost = \"a\"\ntimezone = \"Asia/Tokyo\"\n";
+        let registry = DeviceRegistry::from_toml(toml).unwrap();
+        let device = registry.device("door").unwrap();
+        assert_eq!(device.timezone(), Some(zkrust_types::time::Tz::Asia__Tokyo));
+    }
+}
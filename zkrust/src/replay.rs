@@ -0,0 +1,297 @@
+//! Replay of captured device conversations
+//!
+//! [`ReplayTransport`] stands in for a device by playing back a capture
+//! recorded with [`crate::capture`]: every frame the [`Device`] sends is
+//! checked against the next sent frame of the trace, and the frames the
+//! device answered with are handed back in order. Running the same
+//! operations over a replay pushes real traces (F18, K40, SpeedFace, ...)
+//! through the decoder and the device state machine, so protocol fixes can
+//! be checked against them without the hardware.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use zkrust::replay::ReplayTransport;
+//! use zkrust::Device;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let transport = ReplayTransport::open("traces/k40-attlog.jsonl")?;
+//! let progress = transport.progress();
+//!
+//! let mut device = Device::with_transport(transport);
+//! device.connect().await?;
+//! let records = device.get_attendance().await?;
+//! assert_eq!(records.len(), 120);
+//!
+//! // Every frame of the trace was consumed
+//! assert!(progress.is_finished());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Device`]: crate::Device
+
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use tracing::trace;
+
+use zkrust_core::Packet;
+use zkrust_transport::{Error, Result, Transport};
+
+use crate::capture::{self, CapturedFrame, Direction};
+
+/// How strictly sent frames must match the trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MatchMode {
+    /// Same command (traces from other clients, or with time-dependent payloads)
+    #[default]
+    Command,
+
+    /// Same command and payload; session and reply IDs may differ
+    Payload,
+
+    /// Byte-for-byte identical
+    Exact,
+}
+
+/// Transport answering from a captured conversation
+#[derive(Debug)]
+pub struct ReplayTransport {
+    connected: bool,
+    mode: MatchMode,
+    frames: Arc<Mutex<VecDeque<CapturedFrame>>>,
+}
+
+impl ReplayTransport {
+    /// Replay `frames` in order
+    pub fn new(frames: impl IntoIterator<Item = CapturedFrame>) -> Self {
+        Self {
+            connected: false,
+            mode: MatchMode::default(),
+            frames: Arc::new(Mutex::new(frames.into_iter().collect())),
+        }
+    }
+
+    /// Replay a capture file (see [`capture::load`])
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(capture::load(path)?))
+    }
+
+    /// Set how sent frames are compared with the trace (default: command only)
+    pub fn matching(mut self, mode: MatchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Handle for checking how far the replay got
+    pub fn progress(&self) -> ReplayProgress {
+        ReplayProgress {
+            frames: Arc::clone(&self.frames),
+        }
+    }
+
+    fn check_sent(&self, expected: &[u8], actual: &[u8]) -> Result<()> {
+        let matches = match self.mode {
+            MatchMode::Exact => expected == actual,
+            MatchMode::Command | MatchMode::Payload => {
+                let expected = Packet::decode(BytesMut::from(expected))?;
+                let actual = Packet::decode(BytesMut::from(actual))?;
+                expected.command == actual.command
+                    && (self.mode == MatchMode::Command || expected.payload == actual.payload)
+            }
+        };
+
+        if !matches {
+            return Err(Error::InvalidFrame(format!(
+                "Replay diverged: trace sent {}, device sent {}",
+                describe(expected),
+                describe(actual)
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn connect(&mut self) -> Result<()> {
+        self.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.connected = false;
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Fails if the trace expects the device to answer first, has no
+    /// frames left, or sent something else at this point.
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        if !self.connected {
+            return Err(Error::NotConnected);
+        }
+
+        let expected = {
+            let mut frames = self.frames.lock().unwrap();
+            match frames.front() {
+                Some(frame) if frame.direction == Direction::Sent => frames.pop_front().unwrap(),
+                Some(frame) => {
+                    return Err(Error::InvalidFrame(format!(
+                        "Replay diverged: trace has unread reply {} before {}",
+                        describe(&frame.data),
+                        describe(data)
+                    )));
+                }
+                None => {
+                    return Err(Error::InvalidFrame(format!(
+                        "Replay diverged: trace ended before {}",
+                        describe(data)
+                    )));
+                }
+            }
+        };
+
+        self.check_sent(&expected.data, data)
+    }
+
+    /// Times out when the trace has no reply at this point, as the device did
+    async fn receive(&mut self, _timeout_secs: u64) -> Result<BytesMut> {
+        if !self.connected {
+            return Err(Error::NotConnected);
+        }
+
+        let mut frames = self.frames.lock().unwrap();
+        match frames.front() {
+            Some(frame) if frame.direction == Direction::Received => {
+                let frame = frames.pop_front().unwrap();
+                trace!("Replaying {}", describe(&frame.data));
+                Ok(BytesMut::from(&frame.data[..]))
+            }
+            _ => Err(Error::ReadTimeout),
+        }
+    }
+
+    fn remote_addr(&self) -> String {
+        "replay".into()
+    }
+}
+
+/// Shows how much of a [`ReplayTransport`]'s trace is left
+#[derive(Debug, Clone)]
+pub struct ReplayProgress {
+    frames: Arc<Mutex<VecDeque<CapturedFrame>>>,
+}
+
+impl ReplayProgress {
+    /// Frames not yet sent or received
+    pub fn remaining(&self) -> usize {
+        self.frames.lock().unwrap().len()
+    }
+
+    /// Check if the whole trace was played back
+    pub fn is_finished(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+/// Command name of a frame, or its length if it doesn't decode
+fn describe(frame: &[u8]) -> String {
+    match Packet::decode(BytesMut::from(frame)) {
+        Ok(packet) => packet.command.to_string(),
+        Err(_) => format!("{} undecodable bytes", frame.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+    use zkrust_core::Command;
+    use zkrust_transport::mock::{Expectation, MockTransport};
+
+    use crate::capture::{Capture, CaptureFormat};
+    use crate::testing::SharedBuffer as Shared;
+    use crate::Device;
+
+    /// Capture a connect, a bulk attendance read and a disconnect
+    async fn record_session() -> Vec<CapturedFrame> {
+        let time = zkrust_types::time::encode_time(
+            &chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(8, 30, 0).unwrap(),
+        )
+        .unwrap();
+        let mut record = vec![1, 0, 0, 0];
+        record.extend_from_slice(&time.to_le_bytes());
+        record.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        let mut data = 16u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&record);
+
+        let mut sizes = vec![0u8; 80];
+        sizes[32..36].copy_from_slice(&1u32.to_le_bytes());
+
+        let transport = MockTransport::new()
+            .with_session_id(0x4d2)
+            .expect(Expectation::new(Command::Connect).reply(Command::AckOk))
+            .expect(Expectation::new(Command::GetFreeSizes).reply_with(Command::AckOk, sizes))
+            .expect(
+                Expectation::new(Command::AttLogRrq)
+                    .reply_with(Command::PrepareData, Bytes::copy_from_slice(&20u32.to_le_bytes()))
+                    .reply_with(Command::Data, data)
+                    .reply(Command::AckOk),
+            )
+            .expect(Expectation::new(Command::FreeData).reply(Command::AckOk))
+            .expect(Expectation::new(Command::Exit));
+
+        let out = Shared::default();
+        let mut device = Device::with_transport(transport);
+        device.start_capture(Capture::new(out.clone(), CaptureFormat::JsonLines).unwrap());
+        device.connect().await.unwrap();
+        assert_eq!(device.get_attendance().await.unwrap().len(), 1);
+        device.disconnect().await.unwrap();
+
+        let data = out.0.lock().unwrap().clone();
+        capture::parse(&data, CaptureFormat::JsonLines).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replay_session() {
+        let frames = record_session().await;
+        assert_eq!(frames.len(), 11);
+
+        let transport = ReplayTransport::new(frames).matching(MatchMode::Exact);
+        let progress = transport.progress();
+
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+        let records = device.get_attendance().await.unwrap();
+        device.disconnect().await.unwrap();
+
+        assert_eq!(records[0].user_id, "1");
+        assert!(progress.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_replay_divergence() {
+        let frames = record_session().await;
+
+        let mut device = Device::with_transport(ReplayTransport::new(frames.clone()));
+        device.connect().await.unwrap();
+        let err = device.get_time().await.unwrap_err();
+        assert!(err.to_string().contains("Replay diverged"), "{}", err);
+        assert!(err.to_string().contains("device sent CMD_GET_TIME"), "{}", err);
+
+        // The trace holds only the connect reply here
+        let mut device = Device::with_transport(ReplayTransport::new(frames[..1].to_vec()));
+        assert!(device.connect().await.is_err());
+    }
+}
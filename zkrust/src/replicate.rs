@@ -0,0 +1,509 @@
+//! Copying users between devices
+//!
+//! [`replicate_users`] reads the users and fingerprint templates of one
+//! terminal and uploads them to others, so an employee enrolled once can
+//! clock in anywhere on site.
+
+use std::collections::HashMap;
+
+use tracing::{info, warn};
+
+use zkrust_types::{FingerprintTemplate, TemplateIssue, User};
+
+use crate::capability::Capability;
+use crate::device::Device;
+use crate::error::{Error, Result};
+
+/// Users uploaded per CMD_SAVE_USERTEMPS buffer
+const USERS_PER_UPLOAD: usize = 64;
+
+/// User left out of a replication
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedUser {
+    /// User ID on the source device
+    pub user_id: String,
+
+    /// Why the user was not copied
+    pub reason: String,
+}
+
+/// Fingerprint template left out of a replication
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedTemplate {
+    /// User ID on the source device
+    pub user_id: String,
+
+    /// Finger index (0-9)
+    pub finger: u8,
+
+    /// Why the template was not copied
+    pub issue: TemplateIssue,
+}
+
+/// Outcome of replicating to one target
+#[derive(Debug)]
+pub struct ReplicationReport {
+    /// Index of the target in the `targets` slice
+    pub target: usize,
+
+    /// Users written (created or updated)
+    pub users_written: usize,
+
+    /// Fingerprint templates written
+    pub fingerprints_written: usize,
+
+    /// Users the target cannot store (e.g. name too long for its layout)
+    pub skipped: Vec<SkippedUser>,
+
+    /// Why no fingerprints were copied, if they weren't
+    pub fingerprints_skipped: Option<String>,
+
+    /// Broken templates left out (e.g. empty ones)
+    pub templates_skipped: Vec<SkippedTemplate>,
+
+    /// Why the source's face templates were not copied, if it has any
+    pub faces_skipped: Option<String>,
+
+    /// Error that stopped replication to this target
+    pub error: Option<Error>,
+}
+
+impl ReplicationReport {
+    /// Whether the target was updated without error
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Copy users, passwords, cards and fingerprint templates to other devices
+///
+/// Users are matched by user ID: existing users on a target keep their
+/// record index and are overwritten, new users get the next free index.
+/// Each target is checked for free user and fingerprint slots first, and
+/// users are re-encoded in the target's record layout (from its
+/// [`DeviceProfile`](crate::DeviceProfile)); users that don't fit the
+/// layout are skipped and reported. Fingerprints are only copied between
+/// devices running the same algorithm version, since templates are not
+/// portable across versions, and broken templates (see
+/// [`FingerprintTemplate::check_for`]) are skipped and reported.
+///
+/// Face templates are not copied: there is no face template transfer over
+/// this protocol, and the device only reports how many it stores, not whose
+/// they are. When the source holds any, every report says so in
+/// [`ReplicationReport::faces_skipped`], so those users have to be
+/// re-enrolled on the targets.
+///
+/// Reading the source is all-or-nothing and returns an error. A failing
+/// target does not stop the others; its error is in its report.
+///
+/// # Example
+///
+/// ```no_run
+/// use zkrust::{replicate_users, Device};
+///
+/// # async fn example() -> zkrust::Result<()> {
+/// let mut source = Device::new("192.168.1.201", 4370);
+/// let mut targets = vec![Device::new("192.168.1.202", 4370), Device::new("192.168.1.203", 4370)];
+///
+/// source.connect().await?;
+/// for target in &mut targets {
+///     target.connect().await?;
+/// }
+///
+/// for report in replicate_users(&mut source, &mut targets).await? {
+///     println!("target {}: {} users", report.target, report.users_written);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn replicate_users(source: &mut Device, targets: &mut [Device]) -> Result<Vec<ReplicationReport>> {
+    let users = source.get_users().await?;
+
+    let fingerprints = match source.require(Capability::Fingerprint) {
+        Ok(()) => Some((source.fingerprint_algorithm().await?, source.get_fingerprints().await?)),
+        Err(_) => None,
+    };
+
+    let faces = match source.require(Capability::Face) {
+        Ok(()) => source.get_capacity().await?.faces,
+        Err(_) => 0,
+    };
+    let faces_skipped = (faces > 0).then(|| {
+        warn!("Source holds {} face templates, which are not replicated", faces);
+        format!("{} face templates on the source: face template transfer is not supported", faces)
+    });
+
+    info!(
+        "Replicating {} users to {} devices",
+        users.len(),
+        targets.len()
+    );
+
+    let mut reports = Vec::with_capacity(targets.len());
+    for (index, target) in targets.iter_mut().enumerate() {
+        let mut report = ReplicationReport {
+            target: index,
+            users_written: 0,
+            fingerprints_written: 0,
+            skipped: Vec::new(),
+            fingerprints_skipped: None,
+            templates_skipped: Vec::new(),
+            faces_skipped: faces_skipped.clone(),
+            error: None,
+        };
+
+        if let Err(e) = replicate_to(target, &users, fingerprints.as_ref(), &mut report).await {
+            warn!("Replication to target {} failed: {}", index, e);
+            report.error = Some(e);
+        }
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
+
+async fn replicate_to(
+    target: &mut Device,
+    users: &[User],
+    fingerprints: Option<&(u8, Vec<FingerprintTemplate>)>,
+    report: &mut ReplicationReport,
+) -> Result<()> {
+    let existing: HashMap<String, u16> = target
+        .get_users()
+        .await?
+        .into_iter()
+        .map(|u| (u.user_id, u.uid))
+        .collect();
+
+    let templates = match fingerprints {
+        None => {
+            report.fingerprints_skipped = Some("source has no fingerprint support".into());
+            None
+        }
+        Some((version, templates)) => match target.require(Capability::Fingerprint) {
+            Err(e) => {
+                report.fingerprints_skipped = Some(e.to_string());
+                None
+            }
+            Ok(()) => {
+                let target_version = target.fingerprint_algorithm().await?;
+                if target_version == *version {
+                    Some((target_version, templates))
+                } else {
+                    report.fingerprints_skipped = Some(format!(
+                        "algorithm mismatch: source VX{}, target VX{}",
+                        version, target_version
+                    ));
+                    None
+                }
+            }
+        },
+    };
+
+    let layout = target.profile().map(|p| p.user_layout).unwrap_or_default();
+    let codec = target.string_codec();
+    let mut next_uid = existing.values().copied().max().unwrap_or(0);
+    let mut entries = Vec::with_capacity(users.len());
+    let (mut new_users, mut new_fingers) = (0u32, 0u32);
+
+    for user in users {
+        let is_new = !existing.contains_key(&user.user_id);
+        let uid = match existing.get(&user.user_id) {
+            Some(&uid) => uid,
+            None => match next_uid.checked_add(1) {
+                Some(uid) => {
+                    next_uid = uid;
+                    uid
+                }
+                None => {
+                    report.skipped.push(SkippedUser {
+                        user_id: user.user_id.clone(),
+                        reason: "no free record index".into(),
+                    });
+                    continue;
+                }
+            },
+        };
+
+        let mut copy = user.clone();
+        copy.uid = uid;
+        if let Err(e) = layout.encode_with(&copy, codec.as_ref()) {
+            report.skipped.push(SkippedUser {
+                user_id: user.user_id.clone(),
+                reason: e.to_string(),
+            });
+            continue;
+        }
+
+        let mut fingers = Vec::new();
+        if let Some((target_version, templates)) = templates {
+            for template in templates.iter().filter(|t| t.uid == user.uid) {
+                match template.check_for(target_version).into_iter().find(TemplateIssue::is_fatal) {
+                    Some(issue) => report.templates_skipped.push(SkippedTemplate {
+                        user_id: user.user_id.clone(),
+                        finger: template.finger,
+                        issue,
+                    }),
+                    None => fingers.push(FingerprintTemplate { uid, ..template.clone() }),
+                }
+            }
+        }
+
+        if is_new {
+            new_users += 1;
+            new_fingers += fingers.len() as u32;
+        }
+        entries.push((copy, fingers));
+    }
+
+    let capacity = target.get_capacity().await?;
+    check_capacity("user", new_users, capacity.users, capacity.users_capacity)?;
+    check_capacity("fingerprint", new_fingers, capacity.fingers, capacity.fingers_capacity)?;
+
+    target
+        .batch(async |device| {
+            for chunk in entries.chunks(USERS_PER_UPLOAD) {
+                device.save_users(chunk).await?;
+            }
+            Ok(())
+        })
+        .await?;
+
+    report.users_written = entries.len();
+    report.fingerprints_written = entries.iter().map(|(_, fingers)| fingers.len()).sum();
+    Ok(())
+}
+
+/// Fail if `needed` more entries don't fit; unknown capacity (0) passes
+fn check_capacity(store: &'static str, needed: u32, used: u32, total: u32) -> Result<()> {
+    let available = total.saturating_sub(used);
+    if total > 0 && needed > available {
+        return Err(Error::CapacityExceeded {
+            store,
+            needed,
+            available,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::DeviceProfile;
+    use crate::testing::AckTransport;
+    use zkrust_core::Command;
+    use zkrust_types::text::Gbk;
+    use zkrust_types::{DeviceCapacity, UserRecordLayout};
+
+    fn free_sizes(users: u32, users_capacity: u32) -> Vec<u8> {
+        let mut payload = vec![0u8; 80];
+        payload[16..20].copy_from_slice(&users.to_le_bytes());
+        payload[60..64].copy_from_slice(&users_capacity.to_le_bytes());
+        payload
+    }
+
+    fn sized(body: Vec<u8>) -> Vec<u8> {
+        let mut data = (body.len() as u32).to_le_bytes().to_vec();
+        data.extend(body);
+        data
+    }
+
+    fn users_payload(users: &[User]) -> Vec<u8> {
+        sized(users.iter().flat_map(|u| UserRecordLayout::Extended.encode(u).unwrap()).collect())
+    }
+
+    async fn device(transport: AckTransport) -> Device {
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+        device
+    }
+
+    #[tokio::test]
+    async fn test_replicates_users_and_fingerprints() {
+        let alice = User::builder(1, "100").name("Alice").card(1234).build().unwrap();
+        let bob = User::builder(2, "200").name("Bob").password("42").build().unwrap();
+        // Bob's second finger is an empty (corrupt) entry
+        let finger = sized(vec![9, 0, 2, 0, 3, 1, 0xAA, 0xBB, 0xCC, 6, 0, 2, 0, 4, 1]);
+
+        let (transport, _) = AckTransport::new();
+        let mut source = device(
+            transport
+                .with_payload(Command::GetFreeSizes, free_sizes(2, 1000))
+                .with_payload(Command::UserTempRrq, users_payload(&[alice.clone(), bob]))
+                .with_payload(Command::OptionsRrq, b"~ZKFPVersion=10\0".to_vec())
+                .with_payload(Command::DbRrq, finger),
+        )
+        .await;
+
+        // Target already knows Alice under another index
+        let existing = User { uid: 5, ..alice };
+        let (transport, sent) = AckTransport::new();
+        let target = device(
+            transport
+                .with_payload(Command::GetFreeSizes, free_sizes(1, 1000))
+                .with_payload(Command::UserTempRrq, users_payload(&[existing]))
+                .with_payload(Command::OptionsRrq, b"~ZKFPVersion=10\0".to_vec()),
+        )
+        .await;
+        sent.lock().unwrap().clear();
+
+        let mut targets = vec![target];
+        let reports = replicate_users(&mut source, &mut targets).await.unwrap();
+
+        let report = &reports[0];
+        assert!(report.is_ok(), "{:?}", report.error);
+        assert_eq!(report.users_written, 2);
+        assert_eq!(report.fingerprints_written, 1);
+        assert!(report.skipped.is_empty());
+        assert!(report.faces_skipped.is_none());
+        assert_eq!(
+            report.templates_skipped,
+            [SkippedTemplate {
+                user_id: "200".into(),
+                finger: 4,
+                issue: TemplateIssue::Empty,
+            }]
+        );
+
+        let sent = sent.lock().unwrap();
+        let upload = sent.iter().position(|c| *c == Command::PrepareData).unwrap();
+        assert_eq!(
+            sent[upload - 2..],
+            [
+                Command::DisableDevice,
+                Command::FreeData,
+                Command::PrepareData,
+                Command::Data,
+                Command::SaveUserTemps,
+                Command::EnableDevice,
+                Command::RefreshData,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skips_incompatible_users_and_templates() {
+        let long_name = User::builder(1, "100").name("Alexandria Longname").build().unwrap();
+        let short_name = User::builder(2, "200").name("Bo").build().unwrap();
+
+        let (transport, _) = AckTransport::new();
+        let mut source = device(
+            transport
+                .with_payload(Command::GetFreeSizes, free_sizes(2, 1000))
+                .with_payload(Command::UserTempRrq, users_payload(&[long_name, short_name]))
+                .with_payload(Command::OptionsRrq, b"~ZKFPVersion=10\0".to_vec())
+                .with_payload(Command::DbRrq, sized(Vec::new())),
+        )
+        .await;
+
+        let (transport, _) = AckTransport::new();
+        let target = device(
+            transport
+                .with_payload(Command::GetFreeSizes, free_sizes(0, 1000))
+                .with_payload(Command::OptionsRrq, b"~ZKFPVersion=9\0".to_vec()),
+        )
+        .await
+        .with_profile(DeviceProfile::new("Compact").with_user_layout(UserRecordLayout::Compact));
+
+        let mut targets = vec![target];
+        let reports = replicate_users(&mut source, &mut targets).await.unwrap();
+
+        let report = &reports[0];
+        assert!(report.is_ok(), "{:?}", report.error);
+        assert_eq!(report.users_written, 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].user_id, "100");
+        assert!(report.fingerprints_skipped.as_deref().unwrap().contains("VX9"));
+    }
+
+    #[tokio::test]
+    async fn test_checks_names_in_target_codec() {
+        // 12 bytes in UTF-8 but 8 in GBK, which just fits a compact record
+        let zhang = User::builder(1, "100").name("张三丰李").build().unwrap();
+
+        let (transport, _) = AckTransport::new();
+        let mut source = device(
+            transport
+                .with_payload(Command::GetFreeSizes, free_sizes(1, 1000))
+                .with_payload(Command::UserTempRrq, users_payload(&[zhang]))
+                .with_payload(Command::DbRrq, sized(Vec::new())),
+        )
+        .await;
+
+        let (transport, sent) = AckTransport::new();
+        let target = device(transport.with_payload(Command::GetFreeSizes, free_sizes(0, 1000)))
+            .await
+            .with_profile(DeviceProfile::new("Compact").with_user_layout(UserRecordLayout::Compact))
+            .with_string_codec(Gbk);
+
+        let mut targets = vec![target];
+        let reports = replicate_users(&mut source, &mut targets).await.unwrap();
+
+        let report = &reports[0];
+        assert!(report.is_ok(), "{:?}", report.error);
+        assert!(report.skipped.is_empty(), "{:?}", report.skipped);
+        assert_eq!(report.users_written, 1);
+        assert!(sent.lock().unwrap().contains(&Command::SaveUserTemps));
+    }
+
+    #[tokio::test]
+    async fn test_reports_uncopied_faces() {
+        let alice = User::builder(1, "100").name("Alice").build().unwrap();
+        let capacity = DeviceCapacity {
+            users: 1,
+            users_capacity: 1000,
+            faces: 1,
+            faces_capacity: 400,
+            ..DeviceCapacity::default()
+        };
+
+        let (transport, _) = AckTransport::new();
+        let mut source = device(
+            transport
+                .with_payload(Command::GetFreeSizes, capacity.encode())
+                .with_payload(Command::UserTempRrq, users_payload(&[alice]))
+                .with_payload(Command::DbRrq, sized(Vec::new())),
+        )
+        .await;
+
+        let (transport, _) = AckTransport::new();
+        let target = device(transport.with_payload(Command::GetFreeSizes, free_sizes(0, 1000))).await;
+
+        let mut targets = vec![target];
+        let reports = replicate_users(&mut source, &mut targets).await.unwrap();
+
+        let report = &reports[0];
+        assert!(report.is_ok(), "{:?}", report.error);
+        assert_eq!(report.users_written, 1);
+        assert!(report.faces_skipped.as_deref().unwrap().starts_with("1 face templates"));
+    }
+
+    #[tokio::test]
+    async fn test_capacity_check() {
+        let users: Vec<User> = (1..=3)
+            .map(|uid| User::builder(uid, uid.to_string()).build().unwrap())
+            .collect();
+
+        let (transport, _) = AckTransport::new();
+        let mut source = device(
+            transport
+                .with_payload(Command::GetFreeSizes, free_sizes(3, 1000))
+                .with_payload(Command::UserTempRrq, users_payload(&users))
+                .with_payload(Command::DbRrq, sized(Vec::new())),
+        )
+        .await;
+
+        let (transport, sent) = AckTransport::new();
+        let target = device(transport.with_payload(Command::GetFreeSizes, free_sizes(0, 2))).await;
+
+        let mut targets = vec![target];
+        let reports = replicate_users(&mut source, &mut targets).await.unwrap();
+
+        assert!(matches!(
+            reports[0].error,
+            Some(Error::CapacityExceeded { store: "user", needed: 3, available: 2 })
+        ));
+        assert!(!sent.lock().unwrap().contains(&Command::SaveUserTemps));
+    }
+}
@@ -0,0 +1,156 @@
+//! CommKey secret providers
+//!
+//! A [`SecretProvider`] supplies the device CommKey at connect time, so
+//! passwords don't have to live in config structs or show up in logs.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::error::{Error, Result};
+
+/// Source of a device CommKey
+///
+/// Implemented for closures returning `Result<u32>`, so ad-hoc lookups
+/// (vaults, keyrings) can be plugged in directly.
+///
+/// # Examples
+///
+/// ```
+/// use zkrust::secret::{EnvSecret, SecretProvider};
+/// use zkrust::Device;
+///
+/// let device = Device::new("192.168.1.201", 4370)
+///     .with_secret_provider(EnvSecret::new("ZK_COMMKEY"));
+///
+/// let lookup = || Ok(1234);
+/// assert_eq!(lookup.commkey().unwrap(), 1234);
+/// ```
+pub trait SecretProvider: Send + Sync {
+    /// Resolve the CommKey
+    fn commkey(&self) -> Result<u32>;
+}
+
+impl<F> SecretProvider for F
+where
+    F: Fn() -> Result<u32> + Send + Sync,
+{
+    fn commkey(&self) -> Result<u32> {
+        self()
+    }
+}
+
+/// Fixed CommKey (redacted in `Debug` output)
+#[derive(Clone, Copy, Default)]
+pub struct StaticSecret(u32);
+
+impl StaticSecret {
+    /// Create a provider for a fixed CommKey
+    pub fn new(commkey: u32) -> Self {
+        Self(commkey)
+    }
+}
+
+impl SecretProvider for StaticSecret {
+    fn commkey(&self) -> Result<u32> {
+        Ok(self.0)
+    }
+}
+
+impl fmt::Debug for StaticSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StaticSecret(***)")
+    }
+}
+
+/// CommKey read from an environment variable at connect time
+#[derive(Debug, Clone)]
+pub struct EnvSecret {
+    var: String,
+}
+
+impl EnvSecret {
+    /// Create a provider reading the variable `var`
+    pub fn new(var: impl Into<String>) -> Self {
+        Self { var: var.into() }
+    }
+}
+
+impl SecretProvider for EnvSecret {
+    fn commkey(&self) -> Result<u32> {
+        let value = std::env::var(&self.var)
+            .map_err(|e| Error::Secret(format!("environment variable {}: {}", self.var, e)))?;
+
+        parse_commkey(&value, &self.var)
+    }
+}
+
+/// CommKey read from a file at connect time
+///
+/// The file must contain the numeric key; surrounding whitespace is ignored.
+#[derive(Debug, Clone)]
+pub struct FileSecret {
+    path: PathBuf,
+}
+
+impl FileSecret {
+    /// Create a provider reading the file at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SecretProvider for FileSecret {
+    fn commkey(&self) -> Result<u32> {
+        let source = self.path.display().to_string();
+        let value = std::fs::read_to_string(&self.path)
+            .map_err(|e| Error::Secret(format!("{}: {}", source, e)))?;
+
+        parse_commkey(&value, &source)
+    }
+}
+
+/// Parse a CommKey without echoing the value in the error
+fn parse_commkey(value: &str, source: &str) -> Result<u32> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::Secret(format!("{} does not contain a numeric CommKey", source)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_secret_redacted() {
+        let secret = StaticSecret::new(123456);
+        assert_eq!(secret.commkey().unwrap(), 123456);
+        assert!(!format!("{:?}", secret).contains("123456"));
+    }
+
+    #[test]
+    fn test_closure_secret() {
+        let provider = || Ok(42);
+        assert_eq!(provider.commkey().unwrap(), 42);
+    }
+
+    #[test]
+    fn test_env_secret_missing() {
+        let provider = EnvSecret::new("ZKRUST_TEST_COMMKEY_THAT_IS_NOT_SET");
+        assert!(matches!(provider.commkey(), Err(Error::Secret(_))));
+    }
+
+    #[test]
+    fn test_file_secret() {
+        let path = std::env::temp_dir().join(format!("zkrust-commkey-{}", std::process::id()));
+        std::fs::write(&path, " 8888\n").unwrap();
+
+        assert_eq!(FileSecret::new(&path).commkey().unwrap(), 8888);
+
+        std::fs::write(&path, "hunter2").unwrap();
+        let err = FileSecret::new(&path).commkey().unwrap_err();
+        assert!(!err.to_string().contains("hunter2"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,454 @@
+//! Device configuration profiles
+//!
+//! A [`SettingsProfile`] holds device options (language, volume,
+//! thresholds, alarms, ...) that can be saved to a file and applied to other
+//! devices, so new terminals are set up like a golden unit.
+//! [`Device::diff_settings`] previews what applying a profile would change;
+//! [`Device::apply_settings`] writes only the options that differ.
+//!
+//! Options are read one at a time by name and devices cannot list the ones
+//! they have, so [`Device::snapshot_settings`] captures a curated subset,
+//! [`CURATED_OPTIONS`]; name others with [`Device::snapshot_options`]. Options
+//! asked for but not reported by the device are listed in the profile
+//! ([`SettingsProfile::not_captured`]) and its file.
+//!
+//! Options that identify a device or its network ([`EXCLUDED_OPTIONS`]) and
+//! read-only `~` options are never part of a profile.
+//!
+//! The file format is the device's own `name=value` form, one option per
+//! line, with `#` comments:
+//!
+//! ```text
+//! # Snapshot of 192.168.1.201:4370
+//! Language=69
+//! VOLUME=67
+//! # Not captured: RS485On, WorkCode
+//! ```
+//!
+//! ```no_run
+//! use zkrust::settings::SettingsProfile;
+//! use zkrust::Device;
+//!
+//! # async fn example(golden: &mut Device, new: &mut Device) -> zkrust::Result<()> {
+//! golden.snapshot_settings().await?.save("golden.profile")?;
+//!
+//! let profile = SettingsProfile::load("golden.profile")?;
+//! let diff = new.diff_settings(&profile).await?;
+//! println!("{}", diff);
+//! new.apply_diff(&diff).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use tracing::{debug, info};
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::fleet::{Fleet, FleetReport};
+
+/// Curated subset of options read by [`Device::snapshot_settings`]
+///
+/// Common user-facing settings found across models; anything else on the
+/// device (access control, schedules, model-specific options, ...) is not
+/// captured. Options a model doesn't know end up in
+/// [`SettingsProfile::not_captured`].
+pub const CURATED_OPTIONS: &[&str] = &[
+    "Language",
+    "DtFmt",
+    "VOLUME",
+    "IdleMinute",
+    "IdlePower",
+    "AutoPowerOff",
+    "LockOn",
+    "AlarmAttLog",
+    "AlarmOpLog",
+    "AlarmReRec",
+    "MThreshold",
+    "EThreshold",
+    "VThreshold",
+    "ShowScore",
+    "UnlockPerson",
+    "OnlyPINCard",
+    "MustEnroll",
+    "WorkCode",
+    "RS232BaudRate",
+    "RS232On",
+    "RS485On",
+    "NetOn",
+];
+
+/// Options never snapshotted or applied: copying them would make two
+/// devices collide on the network or share an identity
+pub const EXCLUDED_OPTIONS: &[&str] = &["IPAddress", "NetMask", "GATEIPAddress", "MAC", "DeviceID", "COMKey"];
+
+/// Comment line listing [`SettingsProfile::not_captured`] in profile files
+const NOT_CAPTURED: &str = "# Not captured: ";
+
+/// Check if `name` may be part of a profile
+pub fn is_transferable(name: &str) -> bool {
+    !name.starts_with('~') && !EXCLUDED_OPTIONS.iter().any(|excluded| excluded.eq_ignore_ascii_case(name))
+}
+
+/// Option values to configure devices with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SettingsProfile {
+    options: BTreeMap<String, String>,
+    not_captured: Vec<String>,
+    comment: Option<String>,
+}
+
+impl SettingsProfile {
+    /// Create an empty profile
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set an option, rejecting read-only and excluded ones
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) -> Result<()> {
+        let name = name.into();
+        if !is_transferable(&name) {
+            return Err(Error::Config(format!("Option {} cannot be part of a profile", name)));
+        }
+        self.not_captured.retain(|missing| *missing != name);
+        self.options.insert(name, value.into());
+        Ok(())
+    }
+
+    /// Add an option
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<String>) -> Result<Self> {
+        self.set(name, value)?;
+        Ok(self)
+    }
+
+    /// Remove an option, returning its value
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.options.remove(name)
+    }
+
+    /// Value of an option
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    /// Iterate over `(name, value)` pairs, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.options.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Number of options
+    pub fn len(&self) -> usize {
+        self.options.len()
+    }
+
+    /// Check if the profile has no options
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    /// Options a snapshot asked for but the device didn't report
+    pub fn not_captured(&self) -> &[String] {
+        &self.not_captured
+    }
+
+    /// Comment written at the top of the file
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Set the comment written at the top of the file
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Read a profile file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("Cannot read {}: {}", path.display(), e)))?;
+        contents
+            .parse()
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Write the profile to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_string())
+            .map_err(|e| Error::Config(format!("Cannot write {}: {}", path.display(), e)))
+    }
+}
+
+impl fmt::Display for SettingsProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(comment) = &self.comment {
+            for line in comment.lines() {
+                writeln!(f, "# {}", line)?;
+            }
+        }
+        for (name, value) in &self.options {
+            writeln!(f, "{}={}", name, value)?;
+        }
+        if !self.not_captured.is_empty() {
+            writeln!(f, "{}{}", NOT_CAPTURED, self.not_captured.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SettingsProfile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut profile = Self::new();
+        let mut comment = Vec::new();
+
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if let Some(names) = line.strip_prefix(NOT_CAPTURED) {
+                profile.not_captured.extend(names.split(',').map(|name| name.trim().to_string()));
+                continue;
+            }
+            if let Some(text) = line.strip_prefix('#') {
+                if profile.is_empty() {
+                    comment.push(text.trim());
+                }
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| Error::Config(format!("line {}: expected name=value", number + 1)))?;
+            profile
+                .set(name.trim(), value.trim())
+                .map_err(|e| Error::Config(format!("line {}: {}", number + 1, e)))?;
+        }
+
+        if !comment.is_empty() {
+            profile.comment = Some(comment.join("\n"));
+        }
+        Ok(profile)
+    }
+}
+
+/// One option that differs between a device and a profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingChange {
+    /// Option name
+    pub name: String,
+
+    /// Value on the device, `None` if unset
+    pub current: Option<String>,
+
+    /// Value in the profile
+    pub wanted: String,
+}
+
+impl fmt::Display for SettingChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.current {
+            Some(current) => write!(f, "{}: {} -> {}", self.name, current, self.wanted),
+            None => write!(f, "{}: (unset) -> {}", self.name, self.wanted),
+        }
+    }
+}
+
+/// Differences between a device and a profile
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SettingsDiff {
+    /// Options to write, sorted by name
+    pub changes: Vec<SettingChange>,
+
+    /// Options that already match
+    pub unchanged: usize,
+}
+
+impl SettingsDiff {
+    /// Check if the device already matches the profile
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for SettingsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        write!(f, "{} to change, {} unchanged", self.changes.len(), self.unchanged)
+    }
+}
+
+impl Device {
+    /// Read the [`CURATED_OPTIONS`] into a profile
+    ///
+    /// This is not the device's full configuration, see the
+    /// [module documentation](crate::settings).
+    pub async fn snapshot_settings(&mut self) -> Result<SettingsProfile> {
+        self.snapshot_options(CURATED_OPTIONS).await
+    }
+
+    /// Read the given options into a profile
+    ///
+    /// Options the device doesn't know are listed in
+    /// [`SettingsProfile::not_captured`]; read-only and
+    /// [`EXCLUDED_OPTIONS`] are rejected.
+    pub async fn snapshot_options(&mut self, names: &[&str]) -> Result<SettingsProfile> {
+        if let Some(name) = names.iter().find(|name| !is_transferable(name)) {
+            return Err(Error::Config(format!("Option {} cannot be part of a profile", name)));
+        }
+
+        let mut profile = SettingsProfile::new();
+        for &name in names {
+            match self.get_option(name).await? {
+                Some(value) => profile.set(name, value)?,
+                None => profile.not_captured.push(name.to_string()),
+            }
+        }
+
+        debug!("Snapshot of {} options, {} not captured", profile.len(), profile.not_captured.len());
+        Ok(profile)
+    }
+
+    /// Compare the device with `profile` without changing anything
+    pub async fn diff_settings(&mut self, profile: &SettingsProfile) -> Result<SettingsDiff> {
+        let mut diff = SettingsDiff::default();
+        for (name, wanted) in profile.iter() {
+            let current = self.get_option(name).await?;
+            if current.as_deref() == Some(wanted) {
+                diff.unchanged += 1;
+            } else {
+                diff.changes.push(SettingChange {
+                    name: name.to_string(),
+                    current,
+                    wanted: wanted.to_string(),
+                });
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Write the changes of a previewed diff and reload the options
+    pub async fn apply_diff(&mut self, diff: &SettingsDiff) -> Result<()> {
+        if diff.is_empty() {
+            return Ok(());
+        }
+
+        for change in &diff.changes {
+            if !is_transferable(&change.name) {
+                return Err(Error::Config(format!("Option {} cannot be applied", change.name)));
+            }
+            self.set_option(&change.name, &change.wanted).await?;
+        }
+        self.refresh_options().await?;
+
+        info!("Applied {} option changes", diff.changes.len());
+        Ok(())
+    }
+
+    /// Bring the device in line with `profile`, returning what changed
+    pub async fn apply_settings(&mut self, profile: &SettingsProfile) -> Result<SettingsDiff> {
+        let diff = self.diff_settings(profile).await?;
+        self.apply_diff(&diff).await?;
+        Ok(diff)
+    }
+}
+
+impl Fleet {
+    /// Preview [`Fleet::apply_settings`] on every device
+    pub async fn diff_settings(&mut self, profile: &SettingsProfile) -> FleetReport<SettingsDiff> {
+        self.run(|device| {
+            let profile = profile.clone();
+            Box::pin(async move { device.diff_settings(&profile).await })
+        })
+        .await
+    }
+
+    /// Apply `profile` to every device
+    pub async fn apply_settings(&mut self, profile: &SettingsProfile) -> FleetReport<SettingsDiff> {
+        self.run(|device| {
+            let profile = profile.clone();
+            Box::pin(async move { device.apply_settings(&profile).await })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use zkrust_core::Command;
+    use zkrust_transport::mock::{Expectation, MockTransport};
+
+    fn option(name: &str, value: Option<&str>) -> Expectation {
+        let request = Expectation::new(Command::OptionsRrq).with_payload(format!("{}\0", name).into_bytes());
+        match value {
+            Some(value) => request.reply_with(Command::AckOk, format!("{}={}\0", name, value).into_bytes()),
+            None => request.reply(Command::AckError),
+        }
+    }
+
+    #[test]
+    fn test_profile_file() {
+        let profile = SettingsProfile::new()
+            .with("VOLUME", "67")
+            .unwrap()
+            .with("Language", "69")
+            .unwrap()
+            .with_comment("Golden unit");
+
+        let text = profile.to_string();
+        assert_eq!(text, "# Golden unit\nLanguage=69\nVOLUME=67\n");
+        assert_eq!(text.parse::<SettingsProfile>().unwrap(), profile);
+
+        let partial: SettingsProfile = "Language=69\n# Not captured: VOLUME, WorkCode\n".parse().unwrap();
+        assert_eq!(partial.not_captured(), ["VOLUME", "WorkCode"]);
+        assert_eq!(partial.to_string(), "Language=69\n# Not captured: VOLUME, WorkCode\n");
+
+        assert!("IPAddress=10.0.0.1".parse::<SettingsProfile>().is_err());
+        assert!("~Platform=X".parse::<SettingsProfile>().is_err());
+        assert!("VOLUME".parse::<SettingsProfile>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_diff_and_apply() {
+        let transport = MockTransport::new()
+            .expect(Expectation::new(Command::Connect).reply(Command::AckOk))
+            .expect(option("Language", Some("69")))
+            .expect(option("VOLUME", None))
+            .expect(option("Language", Some("69")))
+            .expect(option("VOLUME", Some("40")))
+            .expect(
+                Expectation::new(Command::OptionsWrq)
+                    .with_payload(b"VOLUME=67\0".to_vec())
+                    .reply(Command::AckOk),
+            )
+            .expect(Expectation::new(Command::RefreshOption).reply(Command::AckOk));
+        let handle = transport.handle();
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        let snapshot = device.snapshot_options(&["Language", "VOLUME"]).await.unwrap();
+        assert_eq!(snapshot.iter().collect::<Vec<_>>(), [("Language", "69")]);
+        assert_eq!(snapshot.not_captured(), ["VOLUME"]);
+        assert!(device.snapshot_options(&["MAC"]).await.is_err());
+
+        let golden = snapshot.with("VOLUME", "67").unwrap();
+        assert!(golden.not_captured().is_empty());
+        let diff = device.apply_settings(&golden).await.unwrap();
+        handle.assert_done();
+
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].to_string(), "VOLUME: 40 -> 67");
+    }
+}
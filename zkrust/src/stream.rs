@@ -0,0 +1,372 @@
+//! Streaming bulk reads
+//!
+//! [`Device::get_attendance`] and [`Device::get_users`] collect the whole
+//! transfer before decoding it, which for a terminal holding 100k+ punches
+//! means megabytes in memory and nothing to show until the last chunk
+//! lands. The stream variants decode records as CMD_DATA chunks arrive:
+//!
+//! ```no_run
+//! use futures::TryStreamExt;
+//! use zkrust::Device;
+//!
+//! # async fn example() -> zkrust::Result<()> {
+//! let mut device = Device::new("192.168.1.201", 4370);
+//! device.connect().await?;
+//!
+//! let mut records = device.attendance_stream().await?;
+//! while let Some(record) = records.try_next().await? {
+//!     println!("{}", record);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The stream borrows the device for the duration of the transfer. Dropping
+//! it before the end leaves the device mid-transfer; reconnect before
+//! sending further commands.
+
+use std::sync::Arc;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use tracing::debug;
+use zkrust_core::constants::DataType;
+use zkrust_core::Command;
+use zkrust_types::records::AttendanceLayout;
+use zkrust_types::user::UserRecordLayout;
+use zkrust_types::{AttendanceRecord, StringCodec, User};
+
+use crate::device::{BulkStart, Device};
+use crate::error::{Error, Result};
+
+/// Records decoded from a bulk transfer as it runs
+pub type RecordStream<'a, T> = BoxStream<'a, Result<T>>;
+
+/// Fixed-size record layout of a bulk table
+trait RecordLayout: Copy + Send + 'static {
+    type Record: Send;
+
+    fn record_size(self) -> usize;
+
+    fn decode(self, record: &[u8], codec: &dyn StringCodec) -> zkrust_types::Result<Self::Record>;
+}
+
+impl RecordLayout for AttendanceLayout {
+    type Record = AttendanceRecord;
+
+    fn record_size(self) -> usize {
+        AttendanceLayout::record_size(self)
+    }
+
+    fn decode(self, record: &[u8], _codec: &dyn StringCodec) -> zkrust_types::Result<AttendanceRecord> {
+        AttendanceLayout::decode(self, record)
+    }
+}
+
+impl RecordLayout for UserRecordLayout {
+    type Record = User;
+
+    fn record_size(self) -> usize {
+        UserRecordLayout::record_size(self)
+    }
+
+    fn decode(self, record: &[u8], codec: &dyn StringCodec) -> zkrust_types::Result<User> {
+        UserRecordLayout::decode_with(self, record, codec)
+    }
+}
+
+/// Incremental decoder of a size-prefixed record table
+///
+/// The layout is chosen once the `u32` size prefix is in, since detection
+/// needs the table length.
+struct Decoder<L, F> {
+    buf: BytesMut,
+    codec: Arc<dyn StringCodec>,
+    select: F,
+    layout: Option<L>,
+    remaining: usize,
+    decoded: usize,
+}
+
+impl<L, F> Decoder<L, F>
+where
+    L: RecordLayout,
+    F: FnMut(usize) -> Result<L>,
+{
+    fn new(codec: Arc<dyn StringCodec>, select: F) -> Self {
+        Self {
+            buf: BytesMut::new(),
+            codec,
+            select,
+            layout: None,
+            remaining: 0,
+            decoded: 0,
+        }
+    }
+
+    /// Add transferred bytes and decode every complete record
+    fn feed(&mut self, data: &[u8]) -> Result<Vec<L::Record>> {
+        self.buf.extend_from_slice(data);
+
+        let layout = match self.layout {
+            Some(layout) => layout,
+            None if self.buf.len() < 4 => return Ok(Vec::new()),
+            None => {
+                let declared = self.buf.get_u32_le() as usize;
+                let layout = (self.select)(declared)?;
+                self.layout = Some(layout);
+                self.remaining = declared;
+                layout
+            }
+        };
+
+        let size = layout.record_size();
+        let available = self.buf.len().min(self.remaining) / size;
+        let mut records = Vec::with_capacity(available);
+        for _ in 0..available {
+            let record = self.buf.split_to(size);
+            let record = layout
+                .decode(&record, self.codec.as_ref())
+                .map_err(|e| Error::InvalidResponse(format!("record {}: {}", self.decoded, e)))?;
+            records.push(record);
+            self.remaining -= size;
+            self.decoded += 1;
+        }
+
+        Ok(records)
+    }
+
+    /// Check the table was complete once the transfer is over
+    fn finish(&self) -> Result<()> {
+        if self.layout.is_none() && !self.buf.is_empty() {
+            return Err(Error::InvalidResponse(format!(
+                "Bulk data too short for size prefix: {} bytes",
+                self.buf.len()
+            )));
+        }
+
+        if self.remaining > 0 {
+            return Err(Error::InvalidResponse(format!(
+                "Bulk data ended {} bytes short of its declared size",
+                self.remaining
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Progress of a streamed transfer
+enum State {
+    Start(Bytes),
+    Chunked { size: usize, received: usize },
+    Done,
+}
+
+/// Stream the records of `command`, decoding each chunk as it arrives
+fn records<'a, L, F>(
+    device: &'a mut Device,
+    command: Command,
+    payload: Bytes,
+    select: F,
+) -> RecordStream<'a, L::Record>
+where
+    L: RecordLayout,
+    F: FnMut(usize) -> Result<L> + Send + 'a,
+{
+    let decoder = Decoder::new(device.string_codec(), select);
+
+    stream::try_unfold(
+        (device, decoder, State::Start(payload)),
+        move |(device, mut decoder, state)| async move {
+            let step: Result<Option<_>> = match state {
+                State::Start(payload) => match device.start_bulk(command, payload).await? {
+                    BulkStart::Inline(data) => {
+                        let records = decoder.feed(&data)?;
+                        decoder.finish()?;
+                        Ok(Some((records, (device, decoder, State::Done))))
+                    }
+                    BulkStart::Chunked(size) => {
+                        let state = State::Chunked { size, received: 0 };
+                        Ok(Some((Vec::new(), (device, decoder, state))))
+                    }
+                },
+                State::Chunked { size, received } => match device.next_bulk_chunk().await? {
+                    Some(chunk) => {
+                        let records = decoder.feed(&chunk)?;
+                        let state = State::Chunked {
+                            size,
+                            received: received + chunk.len(),
+                        };
+                        Ok(Some((records, (device, decoder, state))))
+                    }
+                    None => {
+                        device.finish_bulk(received, size).await?;
+                        decoder.finish()?;
+                        debug!("Streamed {} records of {}", decoder.decoded, command);
+                        Ok(None)
+                    }
+                },
+                State::Done => Ok(None),
+            };
+            step
+        },
+    )
+    .map_ok(|records| stream::iter(records.into_iter().map(Ok)))
+    .try_flatten()
+    .boxed()
+}
+
+impl Device {
+    /// Stream the attendance log, decoding records as they arrive
+    ///
+    /// Yields the same records as [`Device::get_attendance`] without
+    /// holding the whole log in memory. See the [module docs](crate::stream) for
+    /// what dropping the stream early means.
+    pub async fn attendance_stream(&mut self) -> Result<RecordStream<'_, AttendanceRecord>> {
+        debug!("Streaming attendance log...");
+
+        let count = self.get_capacity().await?.records as usize;
+        if count == 0 {
+            return Ok(stream::empty().boxed());
+        }
+
+        Ok(records(self, Command::AttLogRrq, Bytes::new(), move |len| {
+            AttendanceLayout::detect(len, count).ok_or_else(|| {
+                Error::InvalidResponse(format!(
+                    "{} bytes for {} records matches no known attendance layout",
+                    len, count
+                ))
+            })
+        }))
+    }
+
+    /// Stream the user table, decoding users as they arrive
+    ///
+    /// Counterpart of [`Device::get_users`]; the profile's user layout is
+    /// used when set, otherwise it is detected from the table size.
+    pub async fn user_stream(&mut self) -> Result<RecordStream<'_, User>> {
+        debug!("Streaming users...");
+
+        let count = self.get_capacity().await?.users as usize;
+        if count == 0 {
+            return Ok(stream::empty().boxed());
+        }
+
+        let profile_layout = self.profile().map(|profile| profile.user_layout);
+        let payload = Bytes::copy_from_slice(&[u8::from(DataType::User)]);
+
+        Ok(records(self, Command::UserTempRrq, payload, move |len| {
+            profile_layout
+                .or_else(|| UserRecordLayout::detect(len, count))
+                .ok_or_else(|| {
+                    Error::InvalidResponse(format!(
+                        "{} bytes for {} users matches no known user layout",
+                        len, count
+                    ))
+                })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::AckTransport;
+
+    /// Two 8-byte legacy records behind the size prefix
+    fn attendance_table() -> Vec<u8> {
+        let mut data = 16u32.to_le_bytes().to_vec();
+        data.extend([1, 0, 1, 0x88, 0x84, 0x4c, 0x2e, 0, 2, 0, 3, 0x48, 0xfd, 0x4c, 0x2e, 1]);
+        data
+    }
+
+    fn free_sizes(users: u32, records: u32) -> Vec<u8> {
+        let mut free_sizes = vec![0u8; 80];
+        free_sizes[16..20].copy_from_slice(&users.to_le_bytes());
+        free_sizes[32..36].copy_from_slice(&records.to_le_bytes());
+        free_sizes
+    }
+
+    #[tokio::test]
+    async fn test_attendance_stream() {
+        let data = attendance_table();
+
+        // Chunks split the prefix and the records
+        let (transport, sent) = AckTransport::new();
+        let transport = transport.with_payload(Command::GetFreeSizes, free_sizes(0, 2)).with_replies(
+            Command::AttLogRrq,
+            vec![
+                (Command::PrepareData, (data.len() as u32).to_le_bytes().to_vec()),
+                (Command::Data, data[..2].to_vec()),
+                (Command::Data, data[2..10].to_vec()),
+                (Command::Data, data[10..].to_vec()),
+                (Command::AckOk, Vec::new()),
+            ],
+        );
+
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        let records: Vec<_> = device.attendance_stream().await.unwrap().try_collect().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].user_id, "1");
+        assert_eq!(records[1].user_id, "2");
+        assert_eq!(
+            sent.lock().unwrap()[1..],
+            [Command::GetFreeSizes, Command::AttLogRrq, Command::FreeData]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_truncated_stream() {
+        let data = attendance_table();
+
+        let (transport, _) = AckTransport::new();
+        let transport = transport.with_payload(Command::GetFreeSizes, free_sizes(0, 2)).with_replies(
+            Command::AttLogRrq,
+            vec![
+                (Command::PrepareData, (data.len() as u32).to_le_bytes().to_vec()),
+                (Command::Data, data[..12].to_vec()),
+                (Command::AckOk, Vec::new()),
+            ],
+        );
+
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        let mut records = device.attendance_stream().await.unwrap();
+        assert_eq!(records.try_next().await.unwrap().unwrap().user_id, "1");
+        assert!(records.try_next().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_user_stream() {
+        let layout = UserRecordLayout::Extended;
+        let users = [
+            User::builder(1, "42").name("Ann").build().unwrap(),
+            User::builder(2, "43").name("Bob").build().unwrap(),
+        ];
+
+        let mut data = (2 * layout.record_size() as u32).to_le_bytes().to_vec();
+        for user in &users {
+            data.extend(layout.encode(user).unwrap());
+        }
+
+        let (transport, _) = AckTransport::new();
+        let transport = transport
+            .with_payload(Command::GetFreeSizes, free_sizes(2, 0))
+            .with_replies(Command::UserTempRrq, vec![(Command::AckData, data)]);
+
+        let mut device = Device::with_transport(transport);
+        device.connect().await.unwrap();
+
+        let streamed: Vec<_> = device.user_stream().await.unwrap().try_collect().await.unwrap();
+        assert_eq!(streamed.len(), 2);
+        assert_eq!(streamed[1].user_id, "43");
+        assert_eq!(streamed[1].name, "Bob");
+
+        assert!(device.attendance_stream().await.unwrap().next().await.is_none());
+    }
+}
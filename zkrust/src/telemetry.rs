@@ -0,0 +1,59 @@
+//! OTLP export of device command spans
+//!
+//! Every command sent by a [`Device`](crate::Device) runs in a `zk.command`
+//! tracing span with the command name, device address, reply ID and
+//! outcome. This module ships those spans to an OpenTelemetry collector,
+//! so distributed traces show which terminal call slowed a request down.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tracing_subscriber::layer::SubscriberExt;
+//! use tracing_subscriber::util::SubscriberInitExt;
+//!
+//! let provider = zkrust::telemetry::otlp_provider("http://localhost:4318/v1/traces", "attendance-sync")?;
+//! tracing_subscriber::registry()
+//!     .with(zkrust::telemetry::layer(&provider))
+//!     .init();
+//!
+//! // ... use devices ...
+//!
+//! // Flush pending spans before exiting
+//! provider.shutdown()?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Build a tracer provider exporting over OTLP/HTTP to `endpoint`
+///
+/// Spans are batched on a background thread. Call
+/// [`SdkTracerProvider::shutdown`] before exiting to flush them.
+pub fn otlp_provider(
+    endpoint: &str,
+    service_name: &str,
+) -> std::result::Result<SdkTracerProvider, ExporterBuildError> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name.to_string()).build())
+        .build())
+}
+
+/// Tracing layer forwarding spans to `provider`
+pub fn layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, SdkTracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("zkrust"))
+}
@@ -0,0 +1,124 @@
+//! Shared helpers for unit tests
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use bytes::BytesMut;
+use zkrust_core::{Command, Packet};
+use zkrust_transport::Transport;
+
+use crate::Device;
+
+/// Transport that answers every packet with CMD_ACK_OK and logs commands
+pub(crate) struct AckTransport {
+    connected: bool,
+    sent: Arc<Mutex<Vec<Command>>>,
+    pending: VecDeque<BytesMut>,
+    payloads: HashMap<Command, Vec<u8>>,
+    rejected: HashSet<Command>,
+    scripts: HashMap<Command, Vec<(Command, Vec<u8>)>>,
+}
+
+impl AckTransport {
+    /// Create a disconnected transport and its log of sent commands
+    pub(crate) fn new() -> (Self, Arc<Mutex<Vec<Command>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let transport = Self {
+            connected: false,
+            sent: Arc::clone(&sent),
+            pending: VecDeque::new(),
+            payloads: HashMap::new(),
+            rejected: HashSet::new(),
+            scripts: HashMap::new(),
+        };
+        (transport, sent)
+    }
+
+    /// Attach `payload` to the CMD_ACK_OK sent in reply to `command`
+    pub(crate) fn with_payload(mut self, command: Command, payload: impl Into<Vec<u8>>) -> Self {
+        self.payloads.insert(command, payload.into());
+        self
+    }
+
+    /// Answer `command` with CMD_ACK_ERROR instead
+    pub(crate) fn rejecting(mut self, command: Command) -> Self {
+        self.rejected.insert(command);
+        self
+    }
+
+    /// Answer `command` with a sequence of packets (e.g. a bulk transfer)
+    pub(crate) fn with_replies(mut self, command: Command, replies: Vec<(Command, Vec<u8>)>) -> Self {
+        self.scripts.insert(command, replies);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for AckTransport {
+    async fn connect(&mut self) -> zkrust_transport::Result<()> {
+        self.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> zkrust_transport::Result<()> {
+        self.connected = false;
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    async fn send(&mut self, data: &[u8]) -> zkrust_transport::Result<()> {
+        let packet = Packet::decode(BytesMut::from(data))?;
+        self.sent.lock().unwrap().push(packet.command);
+
+        if let Some(replies) = self.scripts.get(&packet.command) {
+            self.pending.extend(replies.iter().map(|(reply, payload)| {
+                Packet::with_payload(*reply, 1, packet.reply_id, payload.clone()).encode()
+            }));
+            return Ok(());
+        }
+
+        let payload = self.payloads.get(&packet.command).cloned().unwrap_or_default();
+        let reply = if self.rejected.contains(&packet.command) {
+            Command::AckError
+        } else {
+            Command::AckOk
+        };
+        self.pending.push_back(Packet::with_payload(reply, 1, packet.reply_id, payload).encode());
+        Ok(())
+    }
+
+    async fn receive(&mut self, _timeout_secs: u64) -> zkrust_transport::Result<BytesMut> {
+        self.pending.pop_front().ok_or(zkrust_transport::Error::ReadTimeout)
+    }
+
+    fn remote_addr(&self) -> String {
+        "ack".into()
+    }
+}
+
+/// Connected device over an [`AckTransport`], plus its log of sent commands
+pub(crate) async fn ack_device() -> (Device, Arc<Mutex<Vec<Command>>>) {
+    let (transport, sent) = AckTransport::new();
+    let mut device = Device::with_transport(transport);
+    device.connect().await.unwrap();
+    sent.lock().unwrap().clear();
+    (device, sent)
+}
+
+/// Writer whose contents stay readable after it is handed over
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(pub(crate) Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
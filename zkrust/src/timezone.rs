@@ -0,0 +1,99 @@
+//! Timezone-aware attendance
+//!
+//! Devices keep local wall-clock time with no zone attached, so logs from
+//! sites in different zones cannot be compared as read. Give each device its
+//! IANA zone with [`Device::with_timezone`] (or `timezone = "..."` in the
+//! registry) and read [`ZonedRecord`]s carrying a real point in time.
+//!
+//! ```no_run
+//! use zkrust::Device;
+//! use zkrust_types::time::parse_timezone;
+//!
+//! # async fn example() -> zkrust::Result<()> {
+//! let mut device = Device::new("192.168.1.201", 4370).with_timezone(parse_timezone("Africa/Nairobi")?);
+//! device.connect().await?;
+//!
+//! for zoned in device.get_attendance_zoned().await? {
+//!     println!("{} at {} ({} UTC)", zoned.record.user_id, zoned.time, zoned.utc());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Utc};
+
+use zkrust_types::time::Tz;
+use zkrust_types::AttendanceRecord;
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+
+/// Attendance record with its timestamp placed in the device's timezone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZonedRecord {
+    /// The record as read (local device time)
+    pub record: AttendanceRecord,
+
+    /// Timestamp in the device's timezone
+    pub time: DateTime<Tz>,
+}
+
+impl ZonedRecord {
+    /// Place `record` in `timezone`
+    pub fn new(record: AttendanceRecord, timezone: &Tz) -> Self {
+        let time = record.localized(timezone);
+        Self { record, time }
+    }
+
+    /// Timestamp in UTC
+    pub fn utc(&self) -> DateTime<Utc> {
+        self.time.with_timezone(&Utc)
+    }
+}
+
+impl Device {
+    /// Download attendance records with timestamps in the device's timezone
+    ///
+    /// Fails with [`Error::Config`] if no timezone was set with
+    /// [`Device::with_timezone`].
+    pub async fn get_attendance_zoned(&mut self) -> Result<Vec<ZonedRecord>> {
+        let timezone = self
+            .timezone()
+            .ok_or_else(|| Error::Config("No timezone configured for the device".to_string()))?;
+        let records = self.get_attendance().await?;
+        Ok(records
+            .into_iter()
+            .map(|record| ZonedRecord::new(record, &timezone))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+    use zkrust_core::constants::{PunchType, VerifyMode};
+    use zkrust_types::time::parse_timezone;
+
+    use crate::testing::AckTransport;
+
+    #[test]
+    fn test_zoned_record_utc() {
+        let timestamp = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let record = AttendanceRecord::new("1", timestamp, VerifyMode::Fingerprint, PunchType::CheckIn);
+
+        let kampala = ZonedRecord::new(record.clone(), &parse_timezone("Africa/Kampala").unwrap());
+        let lisbon = ZonedRecord::new(record, &parse_timezone("Europe/Lisbon").unwrap());
+        assert_eq!(kampala.utc().to_rfc3339(), "2024-07-01T05:00:00+00:00");
+        // Same wall-clock time, two hours apart
+        assert_eq!((lisbon.utc() - kampala.utc()).num_hours(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_zoned_requires_timezone() {
+        let mut device = Device::with_transport(AckTransport::new().0);
+        device.connect().await.unwrap();
+        assert!(matches!(device.get_attendance_zoned().await, Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,29 @@
+//! Read-only compatibility matrix against real devices
+//!
+//! Skipped unless `ZKRUST_IT_DEVICES` lists some; see [`zkrust::harness`].
+
+#![cfg(feature = "integration-tests")]
+
+use zkrust::harness::{run_matrix, CompatMatrix, HarnessTarget, REPORT_VAR};
+
+#[tokio::test]
+async fn compatibility_matrix() {
+    let targets = HarnessTarget::from_env().expect("invalid harness environment");
+    if targets.is_empty() {
+        eprintln!("ZKRUST_IT_DEVICES is not set; skipping the hardware matrix");
+        return;
+    }
+
+    let mut matrix = CompatMatrix::new();
+    for target in &targets {
+        matrix.push(run_matrix(target).await);
+    }
+
+    println!("{}", matrix);
+    if let Ok(path) = std::env::var(REPORT_VAR) {
+        std::fs::write(&path, matrix.to_string()).expect("cannot write the report");
+    }
+
+    let incompatible: Vec<_> = matrix.incompatible().map(|report| report.target.as_str()).collect();
+    assert!(incompatible.is_empty(), "incompatible devices: {:?}", incompatible);
+}